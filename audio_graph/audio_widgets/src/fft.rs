@@ -0,0 +1,146 @@
+// The fft the analyzer widgets are built on
+
+/// An in place radix 2 fft, with the tables for one size
+pub struct Fft {
+    size: usize,
+    cos: Vec<f32>,
+    sin: Vec<f32>,
+    reverse: Vec<usize>,
+}
+
+impl Fft {
+    /// `size` has to be a power of two
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two(), "fft size {} is not a power of two", size);
+        let bits = size.trailing_zeros();
+        let (cos, sin) = (0..size / 2).map( | i | {
+            let angle = -2.0 * std::f64::consts::PI * i as f64 / size as f64;
+            (angle.cos() as f32, angle.sin() as f32)
+        }).unzip();
+        Self {
+            size,
+            cos,
+            sin,
+            reverse: (0..size).map( | i | if bits == 0 {0} else {i.reverse_bits() >> (usize::BITS - bits)}).collect(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Transforms `re` and `im` in place, they are `size` long
+    pub fn forward(&self, re: &mut [f32], im: &mut [f32]) {
+        assert!(re.len() == self.size && im.len() == self.size);
+        for i in 0..self.size {
+            let j = self.reverse[i];
+            if j > i {
+                re.swap(i, j);
+                im.swap(i, j);
+            }
+        }
+        let mut half = 1;
+        while half < self.size {
+            let step = self.size / (half * 2);
+            for start in (0..self.size).step_by(half * 2) {
+                for k in 0..half {
+                    let (c, s) = (self.cos[k * step], self.sin[k * step]);
+                    let (a, b) = (start + k, start + k + half);
+                    let tr = re[b] * c - im[b] * s;
+                    let ti = re[b] * s + im[b] * c;
+                    re[b] = re[a] - tr;
+                    im[b] = im[a] - ti;
+                    re[a] += tr;
+                    im[a] += ti;
+                }
+            }
+            half *= 2;
+        }
+    }
+}
+
+pub fn hann_window(size: usize) -> Vec<f32> {
+    (0..size).map( | i | {
+        (0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / size as f64).cos()) as f32
+    }).collect()
+}
+
+/// Keeps the last `size` samples pushed into it and gives the spectrum of
+/// them, windowed so a full scale sine comes out at a magnitude of 1
+pub struct SpectrumAnalyzer {
+    fft: Fft,
+    window: Vec<f32>,
+    window_gain: f32,
+    history: Vec<f32>,
+    pos: usize,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    magnitudes: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// `size` is a power of two of at least 2
+    pub fn new(size: usize) -> Self {
+        assert!(size >= 2, "spectrum size {} is too small", size);
+        let window = hann_window(size);
+        Self {
+            fft: Fft::new(size),
+            window_gain: 2.0 / window.iter().sum::<f32>(),
+            window,
+            history: vec![0.0; size],
+            pos: 0,
+            re: vec![0.0; size],
+            im: vec![0.0; size],
+            magnitudes: vec![0.0; size / 2 + 1],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.fft.size()
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for s in samples {
+            self.history[self.pos] = *s;
+            self.pos = (self.pos + 1) % self.history.len();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.history.iter_mut().for_each( | s | *s = 0.0);
+    }
+
+    /// The magnitudes of the `size / 2 + 1` bins from 0 to half the rate
+    pub fn analyze(&mut self) -> &[f32] {
+        let size = self.size();
+        for i in 0..size {
+            self.re[i] = self.history[(self.pos + i) % size] * self.window[i];
+            self.im[i] = 0.0;
+        }
+        self.fft.forward(&mut self.re, &mut self.im);
+        for (k, m) in self.magnitudes.iter_mut().enumerate() {
+            *m = (self.re[k] * self.re[k] + self.im[k] * self.im[k]).sqrt() * self.window_gain;
+        }
+        // the ends of the spectrum have no mirror image to share with
+        self.magnitudes[0] *= 0.5;
+        self.magnitudes[size / 2] *= 0.5;
+        &self.magnitudes
+    }
+
+    /// The magnitudes of the last `analyze`
+    pub fn magnitudes(&self) -> &[f32] {
+        &self.magnitudes
+    }
+
+    pub fn bin_frequency(&self, bin: usize, sample_rate: f64) -> f64 {
+        bin as f64 * sample_rate / self.size() as f64
+    }
+
+    /// The magnitude at `frequency`, interpolated between the bins around it
+    pub fn magnitude_at(&self, frequency: f64, sample_rate: f64) -> f32 {
+        let bin = (frequency * self.size() as f64 / sample_rate).clamp(0.0, (self.magnitudes.len() - 1) as f64);
+        let i = (bin as usize).min(self.magnitudes.len() - 2);
+        let t = (bin - i as f64) as f32;
+        self.magnitudes[i] * (1.0 - t) + self.magnitudes[i + 1] * t
+    }
+}
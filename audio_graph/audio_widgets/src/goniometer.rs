@@ -0,0 +1,166 @@
+use {
+    crate::{
+        makepad_draw::*,
+        makepad_widgets::*,
+        spectrum::pack_u16_pair,
+    }
+};
+
+live_design!{
+    use link::shaders::*;
+
+    DrawGoniometer = {{DrawGoniometer}} {
+        texture trace_texture: texture2d
+
+        fn pixel(self) -> vec4 {
+            let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+            let bar = 6.0;
+            let size = min(self.rect_size.x, self.rect_size.y - bar);
+            let left = 0.5 * (self.rect_size.x - size);
+            let scope = (self.pos * self.rect_size - vec2(left, 0.0)) / size;
+            let data = sample2d(self.trace_texture, clamp(scope, vec2(0.0, 0.0), vec2(1.0, 1.0)));
+            sdf.rect(left, 0.0, size, size);
+            sdf.fill(vec4(self.color.xyz, data.w + data.x / 256.0));
+            // the axes of the left and right channel
+            sdf.move_to(left, 0.0);
+            sdf.line_to(left + size, size);
+            sdf.move_to(left + size, 0.0);
+            sdf.line_to(left, size);
+            sdf.stroke(#ffffff30, 0.5);
+            // the correlation from -1 on the left to 1 on the right
+            let mid = 0.5 * self.rect_size.x;
+            let end = mid + self.correlation * mid;
+            sdf.rect(min(mid, end), self.rect_size.y - bar + 2.0, abs(end - mid), bar - 2.0);
+            sdf.fill(mix(#f44, #4f4, 0.5 + 0.5 * self.correlation));
+            return sdf.result
+        }
+    }
+
+    pub Goniometer = {{Goniometer}} {
+        width: Fill,
+        height: Fill
+        draw_goniometer: {
+            color: #7cf
+        }
+    }
+}
+
+const TRACE_SIZE: usize = 128;
+
+#[derive(Live, LiveHook, LiveRegister)]#[repr(C)]
+struct DrawGoniometer {
+    #[deref] draw_super: DrawQuad,
+    #[live] color: Vec4,
+    #[live] correlation: f32,
+}
+
+/// A stereo vectorscope, mono audio is a vertical line and audio out of
+/// phase a horizontal one, with the correlation of the channels in a bar
+/// below. The trace fades out over `persistence` seconds
+#[derive(Live, Widget)]
+pub struct Goniometer {
+    #[walk] walk: Walk,
+    #[redraw] #[live] draw_goniometer: DrawGoniometer,
+    #[live(1.0)] gain: f32,
+    #[live(0.2)] persistence: f64,
+    #[live(48000.0)] sample_rate: f64,
+    #[rust(Texture::new(cx))] trace_texture: Texture,
+    #[rust(vec![0.0; TRACE_SIZE * TRACE_SIZE])] trace: Vec<f32>,
+    #[rust] correlation: f32,
+}
+
+impl Widget for Goniometer {
+    fn handle_event(&mut self, _cx: &mut Cx, _event: &Event, _scope: &mut Scope){
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.draw_goniometer.draw_vars.set_texture(0, &self.trace_texture);
+        self.draw_goniometer.correlation = self.correlation;
+        self.draw_goniometer.draw_walk(cx, walk);
+        DrawStep::done()
+    }
+}
+
+impl LiveHook for Goniometer {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        self.trace_texture = Texture::new_with_format(cx, TextureFormat::VecBGRAu8_32 {
+            data: Some(vec![0; TRACE_SIZE * TRACE_SIZE]),
+            width: TRACE_SIZE,
+            height: TRACE_SIZE,
+            updated: TextureUpdated::Full,
+        });
+    }
+}
+
+impl Goniometer {
+    /// From -1 for channels out of phase to 1 for mono
+    pub fn correlation(&self) -> f32 {
+        self.correlation
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn process_buffer(&mut self, cx: &mut Cx, audio: &AudioBuffer) {
+        if audio.channel_count() == 0 {
+            return
+        }
+        let elapsed = audio.frame_count() as f64 / self.sample_rate;
+        let fade = (-elapsed / self.persistence.max(0.001)).exp() as f32;
+        self.trace.iter_mut().for_each( | t | *t *= fade);
+
+        let left = audio.channel(0);
+        let right = audio.channel(audio.channel_count().min(2) - 1);
+        let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+        let half = TRACE_SIZE as f32 * 0.5;
+        for (l, r) in left.iter().zip(right) {
+            lr += l * r;
+            ll += l * l;
+            rr += r * r;
+            // turned 45 degrees so the sum goes up and the difference sideways
+            let x = half + (l - r) * std::f32::consts::FRAC_1_SQRT_2 * self.gain * half;
+            let y = half - (l + r) * std::f32::consts::FRAC_1_SQRT_2 * self.gain * half;
+            if x >= 0.0 && y >= 0.0 && x < TRACE_SIZE as f32 && y < TRACE_SIZE as f32 {
+                let t = &mut self.trace[y as usize * TRACE_SIZE + x as usize];
+                *t = (*t + 0.25).min(1.0);
+            }
+        }
+        if ll > 0.0 && rr > 0.0 {
+            let correlation = lr / (ll * rr).sqrt();
+            self.correlation += (correlation - self.correlation) * 0.5;
+        }
+        else {
+            self.correlation *= 0.5;
+        }
+
+        let mut data = self.trace_texture.take_vec_u32(cx);
+        for (pixel, t) in data.iter_mut().zip(&self.trace) {
+            *pixel = pack_u16_pair(t.sqrt(), 0.0);
+        }
+        self.trace_texture.put_back_vec_u32(cx, data, None);
+        self.draw_goniometer.redraw(cx);
+    }
+}
+
+impl GoniometerRef {
+    pub fn process_buffer(&self, cx: &mut Cx, buffer: &AudioBuffer) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.process_buffer(cx, buffer);
+        }
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f64) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_sample_rate(sample_rate);
+        }
+    }
+}
+
+impl GoniometerSet {
+    pub fn process_buffer(&self, cx: &mut Cx, buffer: &AudioBuffer) {
+        for item in self.iter(){
+            item.process_buffer(cx, buffer);
+        }
+    }
+}
@@ -1,6 +1,11 @@
 
 pub mod piano;
 pub mod display_audio;
+pub mod fft;
+pub mod spectrum;
+pub mod spectrogram;
+pub mod goniometer;
+pub mod oscilloscope;
 
 use makepad_platform::Cx;
 pub use makepad_widgets;
@@ -12,4 +17,8 @@ pub fn live_design(cx:&mut Cx){
     makepad_widgets::live_design(cx);
     self::piano::live_design(cx);
     self::display_audio::live_design(cx);
+    self::spectrum::live_design(cx);
+    self::spectrogram::live_design(cx);
+    self::goniometer::live_design(cx);
+    self::oscilloscope::live_design(cx);
 }
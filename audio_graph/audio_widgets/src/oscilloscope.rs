@@ -0,0 +1,152 @@
+use {
+    crate::{
+        makepad_draw::*,
+        makepad_widgets::*,
+        spectrum::{mono_samples, pack_u16_pair},
+    }
+};
+
+live_design!{
+    use link::shaders::*;
+
+    DrawScope = {{DrawScope}} {
+        texture trace_texture: texture2d
+
+        fn value_at(self, x: float) -> float {
+            let data = sample2d(self.trace_texture, vec2(x, 0.5));
+            return (1.0 - (data.w + data.x / 256.0)) * self.rect_size.y
+        }
+
+        fn pixel(self) -> vec4 {
+            let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+            let half = 0.5 * self.rect_size.y;
+            sdf.hline(half, 0.5);
+            sdf.fill(#ffffff20);
+            sdf.hline(half - self.trigger_level * half, 0.5);
+            sdf.fill(#ffb74d40);
+            // a line through the samples around this pixel
+            let step = 1.0 / self.rect_size.x;
+            sdf.move_to((self.pos.x - step) * self.rect_size.x, self.value_at(self.pos.x - step));
+            sdf.line_to(self.pos.x * self.rect_size.x, self.value_at(self.pos.x));
+            sdf.line_to((self.pos.x + step) * self.rect_size.x, self.value_at(self.pos.x + step));
+            sdf.stroke(self.color, 1.2);
+            return sdf.result
+        }
+    }
+
+    pub Oscilloscope = {{Oscilloscope}} {
+        width: Fill,
+        height: Fill
+        draw_scope: {
+            color: #8f8
+        }
+    }
+}
+
+const TRACE_SIZE: usize = 1024;
+// without a trigger this long the scope runs free
+const AUTO_TRIGGER: usize = TRACE_SIZE * 4;
+
+#[derive(Live, LiveHook, LiveRegister)]#[repr(C)]
+struct DrawScope {
+    #[deref] draw_super: DrawQuad,
+    #[live] color: Vec4,
+    #[live] trigger_level: f32,
+}
+
+/// An oscilloscope that starts a trace where the audio rises through
+/// `trigger_level`, so a periodic wave stands still. It runs free when
+/// `trigger` is off or nothing crosses the level for a while
+#[derive(Live, Widget)]
+pub struct Oscilloscope {
+    #[walk] walk: Walk,
+    #[redraw] #[live] draw_scope: DrawScope,
+    #[live(true)] trigger: bool,
+    #[live(0.0)] trigger_level: f32,
+    #[live(1.0)] gain: f32,
+    #[rust(Texture::new(cx))] trace_texture: Texture,
+    #[rust] mono: Vec<f32>,
+    #[rust] capture: Vec<f32>,
+    #[rust] capturing: bool,
+    #[rust] waiting: usize,
+    #[rust] last: f32,
+}
+
+impl Widget for Oscilloscope {
+    fn handle_event(&mut self, _cx: &mut Cx, _event: &Event, _scope: &mut Scope){
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.draw_scope.draw_vars.set_texture(0, &self.trace_texture);
+        self.draw_scope.trigger_level = self.trigger_level * self.gain;
+        self.draw_scope.draw_walk(cx, walk);
+        DrawStep::done()
+    }
+}
+
+impl LiveHook for Oscilloscope {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        self.trace_texture = Texture::new_with_format(cx, TextureFormat::VecBGRAu8_32 {
+            data: Some(vec![pack_u16_pair(0.5, 0.0); TRACE_SIZE]),
+            width: TRACE_SIZE,
+            height: 1,
+            updated: TextureUpdated::Full,
+        });
+    }
+}
+
+impl Oscilloscope {
+    pub fn process_buffer(&mut self, cx: &mut Cx, chan: Option<usize>, audio: &AudioBuffer) {
+        mono_samples(audio, chan, &mut self.mono);
+        let mut traced = false;
+        for i in 0..self.mono.len() {
+            let s = self.mono[i];
+            if self.capturing {
+                self.capture.push(s);
+                if self.capture.len() == TRACE_SIZE {
+                    self.capturing = false;
+                    self.waiting = 0;
+                    self.show_capture(cx);
+                    traced = true;
+                }
+            }
+            else {
+                self.waiting += 1;
+                let crossed = self.last < self.trigger_level && s >= self.trigger_level;
+                if !self.trigger || crossed || self.waiting > AUTO_TRIGGER {
+                    self.capturing = true;
+                    self.capture.clear();
+                    self.capture.push(s);
+                }
+            }
+            self.last = s;
+        }
+        if traced {
+            self.draw_scope.redraw(cx);
+        }
+    }
+
+    fn show_capture(&mut self, cx: &mut Cx) {
+        let mut data = self.trace_texture.take_vec_u32(cx);
+        for (pixel, s) in data.iter_mut().zip(&self.capture) {
+            *pixel = pack_u16_pair(0.5 + 0.5 * s * self.gain, 0.0);
+        }
+        self.trace_texture.put_back_vec_u32(cx, data, None);
+    }
+}
+
+impl OscilloscopeRef {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.process_buffer(cx, chan, buffer);
+        }
+    }
+}
+
+impl OscilloscopeSet {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        for item in self.iter(){
+            item.process_buffer(cx, chan, buffer);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use {
+    crate::{
+        makepad_draw::*,
+        makepad_widgets::*,
+        fft::SpectrumAnalyzer,
+        spectrum::*,
+    }
+};
+
+live_design!{
+    use link::shaders::*;
+
+    DrawSpectrogram = {{DrawSpectrogram}} {
+        texture spectrogram_texture: texture2d
+
+        fn pixel(self) -> vec4 {
+            // the newest row is at the top, the texture is a ring of rows
+            let data = sample2d(self.spectrogram_texture, vec2(self.pos.x, fract(self.newest - self.pos.y + 1.0)));
+            let level = data.w + data.x / 256.0;
+            return vec4(Pal::iq1(0.35 + 0.5 * level) * level, 1.0)
+        }
+    }
+
+    pub Spectrogram = {{Spectrogram}} {
+        width: Fill,
+        height: Fill
+    }
+}
+
+const FFT_SIZE: usize = 2048;
+const ROWS: usize = 256;
+
+#[derive(Live, LiveHook, LiveRegister)]#[repr(C)]
+struct DrawSpectrogram {
+    #[deref] draw_super: DrawQuad,
+    // where the newest row is in the texture from 0 to 1
+    #[live] newest: f32,
+}
+
+/// A spectrum over time that scrolls down, a row every `interval` seconds of
+/// the audio it is given with `process_buffer`
+#[derive(Live, Widget)]
+pub struct Spectrogram {
+    #[walk] walk: Walk,
+    #[redraw] #[live] draw_spectrogram: DrawSpectrogram,
+    #[live(true)] log_frequency: bool,
+    #[live(20.0)] min_frequency: f64,
+    #[live(20000.0)] max_frequency: f64,
+    #[live(-90.0)] min_db: f32,
+    #[live(0.0)] max_db: f32,
+    #[live(0.02)] interval: f64,
+    #[live(48000.0)] sample_rate: f64,
+    #[rust(SpectrumAnalyzer::new(FFT_SIZE))] analyzer: SpectrumAnalyzer,
+    #[rust(Texture::new(cx))] spectrogram_texture: Texture,
+    #[rust] mono: Vec<f32>,
+    #[rust(vec![0.0; COLUMNS])] levels: Vec<f32>,
+    #[rust] row: usize,
+    // frames since the last row
+    #[rust] frames: usize,
+}
+
+impl Widget for Spectrogram {
+    fn handle_event(&mut self, _cx: &mut Cx, _event: &Event, _scope: &mut Scope){
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.draw_spectrogram.draw_vars.set_texture(0, &self.spectrogram_texture);
+        self.draw_spectrogram.newest = (self.row as f32 + 0.5) / ROWS as f32;
+        self.draw_spectrogram.draw_walk(cx, walk);
+        DrawStep::done()
+    }
+}
+
+impl LiveHook for Spectrogram {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        self.spectrogram_texture = Texture::new_with_format(cx, TextureFormat::VecBGRAu8_32 {
+            data: Some(vec![0; COLUMNS * ROWS]),
+            width: COLUMNS,
+            height: ROWS,
+            updated: TextureUpdated::Full,
+        });
+    }
+}
+
+impl Spectrogram {
+    pub fn frequency_scale(&self) -> FrequencyScale {
+        FrequencyScale {
+            log: self.log_frequency,
+            min: self.min_frequency,
+            max: self.max_frequency.min(self.sample_rate * 0.5),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn process_buffer(&mut self, cx: &mut Cx, chan: Option<usize>, audio: &AudioBuffer) {
+        mono_samples(audio, chan, &mut self.mono);
+        let hop = ((self.interval * self.sample_rate) as usize).max(1);
+        let scale = self.frequency_scale();
+        let range = (self.max_db - self.min_db).max(1.0);
+        let mut data = self.spectrogram_texture.take_vec_u32(cx);
+        let mut done = 0;
+        while done < self.mono.len() {
+            // the interval can have shrunk since the last call, then a row is
+            // overdue and comes right away
+            let len = hop.saturating_sub(self.frames).min(self.mono.len() - done);
+            self.analyzer.push(&self.mono[done..done + len]);
+            done += len;
+            self.frames += len;
+            if self.frames >= hop {
+                self.frames = 0;
+                self.analyzer.analyze();
+                scale.sample(&self.analyzer, self.sample_rate, &mut self.levels);
+                self.row = (self.row + 1) % ROWS;
+                let row = &mut data[self.row * COLUMNS..(self.row + 1) * COLUMNS];
+                for (pixel, level) in row.iter_mut().zip(&self.levels) {
+                    *pixel = pack_u16_pair((gain_to_db(*level) - self.min_db) / range, 0.0);
+                }
+            }
+        }
+        self.spectrogram_texture.put_back_vec_u32(cx, data, None);
+        self.draw_spectrogram.redraw(cx);
+    }
+}
+
+impl SpectrogramRef {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.process_buffer(cx, chan, buffer);
+        }
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f64) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_sample_rate(sample_rate);
+        }
+    }
+}
+
+impl SpectrogramSet {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        for item in self.iter(){
+            item.process_buffer(cx, chan, buffer);
+        }
+    }
+}
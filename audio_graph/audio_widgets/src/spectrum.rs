@@ -0,0 +1,242 @@
+use {
+    crate::{
+        makepad_draw::*,
+        makepad_widgets::*,
+        fft::SpectrumAnalyzer,
+    }
+};
+
+live_design!{
+    use link::shaders::*;
+
+    DrawSpectrum = {{DrawSpectrum}} {
+        texture spectrum_texture: texture2d
+
+        fn pixel(self) -> vec4 {
+            let data = sample2d(self.spectrum_texture, vec2(self.pos.x, 0.5));
+            let level = data.w + data.x / 256.0;
+            let peak = data.y + data.z / 256.0;
+            let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+            let top = self.rect_size.y * (1.0 - level);
+            sdf.rect(0., top, self.rect_size.x, self.rect_size.y - top);
+            sdf.fill(mix(self.color * 0.35, self.color, 1.0 - self.pos.y));
+            sdf.hline(self.rect_size.y * (1.0 - peak), 0.75);
+            sdf.fill(self.peak_color);
+            return sdf.result
+        }
+    }
+
+    pub Spectrum = {{Spectrum}} {
+        width: Fill,
+        height: Fill
+        draw_spectrum: {
+            color: #3fa9f5
+            peak_color: #ffb74d
+        }
+    }
+}
+
+const FFT_SIZE: usize = 4096;
+// the texture holds a level and a peak for every column
+pub(crate) const COLUMNS: usize = 512;
+const MIN_DB: f32 = -200.0;
+
+pub(crate) fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-10).log10()
+}
+
+// two values from 0 to 1 as 16 bits each, the shaders read them back as
+// `w + x / 256.0` and `y + z / 256.0`
+pub(crate) fn pack_u16_pair(hi: f32, lo: f32) -> u32 {
+    let hi = (hi.clamp(0.0, 1.0) * 65535.0) as u32;
+    let lo = (lo.clamp(0.0, 1.0) * 65535.0) as u32;
+    hi << 16 | lo
+}
+
+// the samples of one channel, or all channels mixed down
+pub(crate) fn mono_samples(audio: &AudioBuffer, chan: Option<usize>, out: &mut Vec<f32>) {
+    out.clear();
+    if audio.channel_count() == 0 {
+        return
+    }
+    match chan {
+        Some(chan) => out.extend_from_slice(audio.channel(chan.min(audio.channel_count() - 1))),
+        None => {
+            let scale = 1.0 / audio.channel_count() as f32;
+            out.resize(audio.frame_count(), 0.0);
+            for c in 0..audio.channel_count() {
+                for (o, s) in out.iter_mut().zip(audio.channel(c)) {
+                    *o += s * scale;
+                }
+            }
+        }
+    }
+}
+
+/// Maps positions across an analyzer to frequencies
+#[derive(Clone, Copy, Debug)]
+pub struct FrequencyScale {
+    pub log: bool,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl FrequencyScale {
+    /// The frequency at `x` from 0 to 1
+    pub fn frequency_at(&self, x: f64) -> f64 {
+        if self.log {
+            let min = self.min.max(1.0);
+            min * (self.max.max(min) / min).powf(x)
+        }
+        else {
+            self.min + (self.max - self.min) * x
+        }
+    }
+
+    pub fn position_of(&self, frequency: f64) -> f64 {
+        if self.log {
+            let min = self.min.max(1.0);
+            (frequency.max(min) / min).ln() / (self.max.max(min) / min).ln()
+        }
+        else {
+            (frequency - self.min) / (self.max - self.min)
+        }
+    }
+
+    /// Fills `columns` with the magnitudes across the scale, a column that
+    /// spans more than one bin gets the loudest of them
+    pub fn sample(&self, analyzer: &SpectrumAnalyzer, sample_rate: f64, columns: &mut [f32]) {
+        let magnitudes = analyzer.magnitudes();
+        let bins_per_hz = analyzer.size() as f64 / sample_rate;
+        let count = columns.len();
+        for (i, column) in columns.iter_mut().enumerate() {
+            let from = self.frequency_at(i as f64 / count as f64) * bins_per_hz;
+            let to = self.frequency_at((i + 1) as f64 / count as f64) * bins_per_hz;
+            let (from, to) = (from.ceil() as usize, (to.floor() as usize).min(magnitudes.len() - 1));
+            *column = if to > from {
+                magnitudes[from..=to].iter().fold(0.0, | m, s | s.max(m))
+            }
+            else {
+                analyzer.magnitude_at(self.frequency_at((i as f64 + 0.5) / count as f64), sample_rate)
+            };
+        }
+    }
+}
+
+#[derive(Live, LiveHook, LiveRegister)]#[repr(C)]
+struct DrawSpectrum {
+    #[deref] draw_super: DrawQuad,
+    #[live] color: Vec4,
+    #[live] peak_color: Vec4,
+}
+
+/// An fft spectrum of the audio it is given with `process_buffer`, on a
+/// linear or log frequency axis, with peaks that hold for `peak_hold`
+/// seconds and then fall `peak_fall` dB a second
+#[derive(Live, Widget)]
+pub struct Spectrum {
+    #[walk] walk: Walk,
+    #[redraw] #[live] draw_spectrum: DrawSpectrum,
+    #[live(true)] log_frequency: bool,
+    #[live(20.0)] min_frequency: f64,
+    #[live(20000.0)] max_frequency: f64,
+    #[live(-90.0)] min_db: f32,
+    #[live(0.0)] max_db: f32,
+    #[live(1.0)] peak_hold: f64,
+    #[live(24.0)] peak_fall: f32,
+    #[live(48000.0)] sample_rate: f64,
+    #[rust(SpectrumAnalyzer::new(FFT_SIZE))] analyzer: SpectrumAnalyzer,
+    #[rust(Texture::new(cx))] spectrum_texture: Texture,
+    #[rust] mono: Vec<f32>,
+    #[rust(vec![MIN_DB; COLUMNS])] levels: Vec<f32>,
+    // the peak of every column in dB and when it was hit
+    #[rust(vec![(MIN_DB, 0.0); COLUMNS])] peaks: Vec<(f32, f64)>,
+    #[rust] time: f64,
+}
+
+impl Widget for Spectrum {
+    fn handle_event(&mut self, _cx: &mut Cx, _event: &Event, _scope: &mut Scope){
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.draw_spectrum.draw_vars.set_texture(0, &self.spectrum_texture);
+        self.draw_spectrum.draw_walk(cx, walk);
+        DrawStep::done()
+    }
+}
+
+impl LiveHook for Spectrum {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        self.spectrum_texture = Texture::new_with_format(cx, TextureFormat::VecBGRAu8_32 {
+            data: Some(vec![0; COLUMNS]),
+            width: COLUMNS,
+            height: 1,
+            updated: TextureUpdated::Full,
+        });
+    }
+}
+
+impl Spectrum {
+    pub fn frequency_scale(&self) -> FrequencyScale {
+        FrequencyScale {
+            log: self.log_frequency,
+            min: self.min_frequency,
+            max: self.max_frequency.min(self.sample_rate * 0.5),
+        }
+    }
+
+    /// The levels across the width after the last buffer, in dB
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn process_buffer(&mut self, cx: &mut Cx, chan: Option<usize>, audio: &AudioBuffer) {
+        mono_samples(audio, chan, &mut self.mono);
+        self.analyzer.push(&self.mono);
+        self.analyzer.analyze();
+        self.frequency_scale().sample(&self.analyzer, self.sample_rate, &mut self.levels);
+        let elapsed = audio.frame_count() as f64 / self.sample_rate;
+        self.time += elapsed;
+
+        let mut data = self.spectrum_texture.take_vec_u32(cx);
+        let range = (self.max_db - self.min_db).max(1.0);
+        for ((level, peak), pixel) in self.levels.iter_mut().zip(self.peaks.iter_mut()).zip(data.iter_mut()) {
+            *level = gain_to_db(*level);
+            if *level >= peak.0 {
+                *peak = (*level, self.time);
+            }
+            else if self.time - peak.1 > self.peak_hold {
+                peak.0 = (peak.0 - self.peak_fall * elapsed as f32).max(*level);
+            }
+            *pixel = pack_u16_pair((*level - self.min_db) / range, (peak.0 - self.min_db) / range);
+        }
+        self.spectrum_texture.put_back_vec_u32(cx, data, None);
+        self.draw_spectrum.redraw(cx);
+    }
+}
+
+impl SpectrumRef {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.process_buffer(cx, chan, buffer);
+        }
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f64) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_sample_rate(sample_rate);
+        }
+    }
+}
+
+impl SpectrumSet {
+    pub fn process_buffer(&self, cx: &mut Cx, chan: Option<usize>, buffer: &AudioBuffer) {
+        for item in self.iter(){
+            item.process_buffer(cx, chan, buffer);
+        }
+    }
+}
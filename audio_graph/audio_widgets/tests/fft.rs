@@ -0,0 +1,83 @@
+use makepad_audio_widgets::fft::*;
+use makepad_audio_widgets::spectrum::FrequencyScale;
+
+// the textbook transform to check against
+fn dft(re: &[f32], im: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    let n = re.len();
+    (0..n).map( | k | {
+        (0..n).fold((0.0, 0.0), | (sr, si), t | {
+            let angle = -2.0 * std::f64::consts::PI * (k * t) as f64 / n as f64;
+            let (s, c) = angle.sin_cos();
+            (sr + re[t] as f64 * c - im[t] as f64 * s, si + re[t] as f64 * s + im[t] as f64 * c)
+        })
+    }).map( | (r, i) | (r as f32, i as f32)).unzip()
+}
+
+#[test]
+fn fft_matches_dft() {
+    for size in [1, 2, 4, 8, 64, 256] {
+        let mut re: Vec<f32> = (0..size).map( | i | ((i * 37 + 11) % 17) as f32 / 8.0 - 1.0).collect();
+        let mut im: Vec<f32> = (0..size).map( | i | ((i * 13 + 5) % 11) as f32 / 5.0 - 1.0).collect();
+        let (expect_re, expect_im) = dft(&re, &im);
+        Fft::new(size).forward(&mut re, &mut im);
+        for k in 0..size {
+            assert!((re[k] - expect_re[k]).abs() < 1e-3 && (im[k] - expect_im[k]).abs() < 1e-3, "size {} bin {}", size, k);
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn fft_needs_a_power_of_two() {
+    Fft::new(48);
+}
+
+#[test]
+fn spectrum_of_a_sine() {
+    let rate = 48000.0;
+    let mut analyzer = SpectrumAnalyzer::new(1024);
+    // on a bin a full scale sine comes out at 1, the window spreads it over
+    // the bins next to it at half
+    let bin = 64;
+    let freq = analyzer.bin_frequency(bin, rate);
+    let sine: Vec<f32> = (0..3000).map( | i | (2.0 * std::f64::consts::PI * freq * i as f64 / rate).sin() as f32).collect();
+    analyzer.push(&sine);
+    let magnitudes = analyzer.analyze().to_vec();
+    assert_eq!(magnitudes.len(), 513);
+    assert!((magnitudes[bin] - 1.0).abs() < 1e-3, "{}", magnitudes[bin]);
+    assert!((magnitudes[bin + 1] - 0.5).abs() < 1e-3);
+    assert!(magnitudes.iter().enumerate().all( | (k, m) | k.abs_diff(bin) <= 1 || *m < 1e-3));
+    assert!((analyzer.magnitude_at(freq, rate) - 1.0).abs() < 1e-3);
+
+    // dc on its own has no mirror bin
+    analyzer.push(&[0.5; 1024]);
+    assert!((analyzer.analyze()[0] - 0.5).abs() < 1e-3);
+    analyzer.clear();
+    assert!(analyzer.analyze().iter().all( | m | *m == 0.0));
+}
+
+#[test]
+fn frequency_scale() {
+    let log = FrequencyScale {log: true, min: 20.0, max: 20000.0};
+    assert!((log.frequency_at(0.0) - 20.0).abs() < 1e-9);
+    assert!((log.frequency_at(0.5) - 632.455532).abs() < 1e-3);
+    assert!((log.frequency_at(1.0) - 20000.0).abs() < 1e-6);
+    assert!((log.position_of(2000.0) - 2.0 / 3.0).abs() < 1e-9);
+    let linear = FrequencyScale {log: false, min: 0.0, max: 24000.0};
+    assert_eq!(linear.frequency_at(0.25), 6000.0);
+    assert_eq!(linear.position_of(12000.0), 0.5);
+
+    // a column over many bins gets the loudest one, so a tone high up
+    // doesn't fall between columns
+    let rate = 48000.0;
+    let mut analyzer = SpectrumAnalyzer::new(1024);
+    let freq = analyzer.bin_frequency(300, rate);
+    let sine: Vec<f32> = (0..1024).map( | i | (2.0 * std::f64::consts::PI * freq * i as f64 / rate).sin() as f32).collect();
+    analyzer.push(&sine);
+    analyzer.analyze();
+    let mut columns = vec![0.0; 16];
+    linear.sample(&analyzer, rate, &mut columns);
+    let column = (linear.position_of(freq) * 16.0) as usize;
+    assert!((columns[column] - 1.0).abs() < 1e-3, "{:?}", columns);
+    assert!(columns.iter().enumerate().all( | (i, c) | i == column || *c < 0.6));
+}
@@ -0,0 +1,16 @@
+use makepad_audio_widgets::spectrogram::Spectrogram;
+use makepad_audio_widgets::makepad_widgets::*;
+
+#[test]
+fn spectrogram_interval_can_shrink() {
+    let mut cx = Cx::new(Box::new( | _, _ | {}));
+    let mut spectrogram = Spectrogram::new(&mut cx);
+    // what a spectrogram from a live design gets
+    spectrogram.after_new_from_doc(&mut cx);
+    let audio = AudioBuffer::new_with_size(500, 2);
+    // 500 frames into a row of 960, then rows of 240
+    spectrogram.process_buffer(&mut cx, None, &audio);
+    spectrogram.apply_over(&mut cx, live!{interval: 0.005});
+    spectrogram.process_buffer(&mut cx, None, &audio);
+    spectrogram.process_buffer(&mut cx, Some(1), &audio);
+}
@@ -0,0 +1,183 @@
+use {
+    crate::makepad_audio_graph::makepad_platform::*,
+    std::{
+        io::{BufRead, BufReader, Write},
+        path::Path,
+        process::{Child, ChildStdin, Command, Stdio},
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+    },
+};
+
+// A makepad window can't live inside the host, the event loop of the app owns
+// its thread and there is one per process. So the gui is a makepad app of its
+// own that the plugin starts, they talk in lines over its stdin and stdout:
+// `param <id> <value>` both ways, `begin <id>` and `end <id>` around an edit
+// from the gui
+
+pub const CLAP_GUI_ARG: &str = "--clap-gui";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClapGuiMessage {
+    Value {id: u32, value: f64},
+    GestureBegin {id: u32},
+    GestureEnd {id: u32},
+}
+
+impl ClapGuiMessage {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let kind = parts.next() ?;
+        let id = parts.next()?.parse().ok() ?;
+        match kind {
+            "param" => Some(Self::Value {id, value: parts.next()?.parse().ok()?}),
+            "begin" => Some(Self::GestureBegin {id}),
+            "end" => Some(Self::GestureEnd {id}),
+            _ => None
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Value {id, value} => format!("param {} {}\n", id, value),
+            Self::GestureBegin {id} => format!("begin {}\n", id),
+            Self::GestureEnd {id} => format!("end {}\n", id),
+        }
+    }
+}
+
+/// What the gui process sent since the last look, and whether it went away
+#[derive(Default)]
+pub(crate) struct GuiInbox {
+    pub messages: Vec<ClapGuiMessage>,
+    pub closed: bool,
+}
+
+/// The running gui app, on the plugin side
+pub(crate) struct GuiProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl GuiProcess {
+    /// Starts `executable` as the gui. `notify` is called from the reader
+    /// thread after something arrived in `inbox`
+    pub fn spawn(
+        executable: &Path,
+        title: &str,
+        inbox: Arc<Mutex<GuiInbox>>,
+        notify: Box<dyn Fn() + Send>
+    ) -> std::io::Result<Self> {
+        let mut child = Command::new(executable)
+            .arg(CLAP_GUI_ARG)
+            .arg(title)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn() ?;
+        let stdout = child.stdout.take().unwrap();
+        let reader = std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {break};
+                if let Some(msg) = ClapGuiMessage::parse(&line) {
+                    inbox.lock().unwrap().messages.push(msg);
+                    notify();
+                }
+            }
+            inbox.lock().unwrap().closed = true;
+            notify();
+        });
+        Ok(Self {
+            stdin: child.stdin.take(),
+            child,
+            reader: Some(reader),
+        })
+    }
+
+    pub fn send(&mut self, msg: ClapGuiMessage) {
+        if let Some(stdin) = &mut self.stdin {
+            if stdin.write_all(msg.to_line().as_bytes()).and_then( | _ | stdin.flush()).is_err() {
+                self.stdin = None;
+            }
+        }
+    }
+}
+
+impl Drop for GuiProcess {
+    fn drop(&mut self) {
+        // closing stdin asks the app to quit, the kill makes sure
+        self.stdin = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        // the reader calls into the host, it has to be done before the plugin goes
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// The plugin side as a makepad app sees it, when the plugin started it as
+/// its gui. Changes from the plugin arrive as a `Signal` event
+pub struct ClapGuiLink {
+    title: String,
+    receiver: ToUIReceiver<ClapGuiMessage>,
+}
+
+impl ClapGuiLink {
+    /// Connects to the plugin when the app was started as its gui. The app
+    /// exits when the plugin closes the gui
+    pub fn from_args() -> Option<Self> {
+        let mut args = std::env::args().skip_while( | arg | arg != CLAP_GUI_ARG);
+        args.next() ?;
+        let title = args.next().unwrap_or_default();
+        let receiver = ToUIReceiver::default();
+        let sender = receiver.sender();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {break};
+                if let Some(msg) = ClapGuiMessage::parse(&line) {
+                    let _ = sender.send(msg);
+                }
+            }
+            std::process::exit(0);
+        });
+        Some(Self {title, receiver})
+    }
+
+    /// The title the host suggested for the window
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The parameter values the plugin sent since the last call, the host
+    /// automated them or loaded a preset
+    pub fn param_changes(&self) -> Vec<(u32, f64)> {
+        let mut changes = Vec::new();
+        while let Ok(msg) = self.receiver.try_recv() {
+            if let ClapGuiMessage::Value {id, value} = msg {
+                changes.push((id, value));
+            }
+        }
+        changes
+    }
+
+    fn send(&self, msg: ClapGuiMessage) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(msg.to_line().as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// Call before a series of `set_param` from a drag, so the host records
+    /// the edit as one
+    pub fn begin_edit(&self, id: u32) {
+        self.send(ClapGuiMessage::GestureBegin {id});
+    }
+
+    pub fn set_param(&self, id: u32, value: f64) {
+        self.send(ClapGuiMessage::Value {id, value});
+    }
+
+    pub fn end_edit(&self, id: u32) {
+        self.send(ClapGuiMessage::GestureEnd {id});
+    }
+}
@@ -0,0 +1,90 @@
+// Audio components as CLAP plugins. A crate with `crate-type = ["cdylib"]`
+// implements ClapExport and calls clap_export!, the library it builds is the
+// .clap file. The CLAP ABI lives with the host on Linux, so this does too
+#![cfg(all(target_os = "linux", not(target_env = "ohos")))]
+
+pub mod param;
+pub mod gui;
+pub mod plugin;
+
+pub use makepad_audio_graph;
+pub use makepad_audio_graph::makepad_platform::os::linux::clap_sys;
+pub use crate::{
+    param::*,
+    gui::*,
+    plugin::*,
+};
+
+use makepad_audio_graph::{
+    makepad_platform::Cx,
+    AudioComponent,
+};
+
+/// What the host shows of the plugin
+#[derive(Clone, Debug)]
+pub struct ClapDescriptor {
+    /// Reverse domain, like `dev.makepad.ironfish`
+    pub id: &'static str,
+    pub name: &'static str,
+    pub vendor: &'static str,
+    pub url: &'static str,
+    pub version: &'static str,
+    pub description: &'static str,
+    /// CLAP features, like `instrument` or `audio-effect`. Instruments get no
+    /// audio input
+    pub features: &'static [&'static str],
+    /// The makepad app that is the gui, next to the .clap file. It is started
+    /// with `--clap-gui` and talks to the plugin with a `ClapGuiLink`
+    pub gui: Option<&'static str>,
+}
+
+impl ClapDescriptor {
+    pub fn is_instrument(&self) -> bool {
+        self.features.contains(&"instrument")
+    }
+}
+
+pub trait ClapExport: 'static {
+    fn descriptor() -> ClapDescriptor;
+
+    /// The live properties of the component the host can automate
+    fn params() -> Vec<ClapParam>;
+
+    /// Registers the live design the component is made from and its main
+    /// module, which `app_main!` does for an app. It is expanded before
+    /// `new_component`, so the component starts from its live design
+    fn live_register(cx: &mut Cx);
+
+    /// Makes the component, once per plugin instance on the main thread. The
+    /// parameters are applied over it at their defaults after
+    fn new_component(cx: &mut Cx) -> Box<dyn AudioComponent>;
+}
+
+/// Exports the `ClapExport` type as the plugin of this library, in the
+/// `clap_entry` symbol hosts look for
+#[macro_export]
+macro_rules! clap_export {
+    ($ty: ty) => {
+        static MAKEPAD_CLAP_FACTORY: $crate::ClapFactory = $crate::ClapFactory::new::<$ty>();
+
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static clap_entry: $crate::clap_sys::clap_plugin_entry = {
+            unsafe extern "C" fn init(plugin_path: *const std::os::raw::c_char) -> bool {
+                MAKEPAD_CLAP_FACTORY.entry_init(plugin_path)
+            }
+            unsafe extern "C" fn deinit() {
+                MAKEPAD_CLAP_FACTORY.entry_deinit()
+            }
+            unsafe extern "C" fn get_factory(factory_id: *const std::os::raw::c_char) -> *const std::os::raw::c_void {
+                MAKEPAD_CLAP_FACTORY.entry_get_factory(factory_id)
+            }
+            $crate::clap_sys::clap_plugin_entry {
+                clap_version: $crate::clap_sys::CLAP_VERSION,
+                init,
+                deinit,
+                get_factory,
+            }
+        };
+    }
+}
@@ -0,0 +1,166 @@
+use {
+    crate::makepad_audio_graph::makepad_platform::{
+        *,
+        os::linux::clap_sys::*,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClapParamKind {
+    Float,
+    Int,
+    Toggle,
+    // the value is the index of the option, applied as an enum variant
+    Choice(Vec<String>),
+}
+
+/// A parameter of the plugin, it sets the live property at `path` of the
+/// component, like `settings.filter1.cutoff`
+#[derive(Clone, Debug)]
+pub struct ClapParam {
+    pub id: u32,
+    pub path: String,
+    pub name: String,
+    pub module: String,
+    pub unit: String,
+    pub kind: ClapParamKind,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+impl ClapParam {
+    fn new(path: &str, kind: ClapParamKind, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            id: Self::id_of(path),
+            path: path.to_string(),
+            name: path.rsplit('.').next().unwrap_or(path).to_string(),
+            module: String::new(),
+            unit: String::new(),
+            kind,
+            min,
+            max,
+            default: default.clamp(min, max),
+        }
+    }
+
+    pub fn float(path: &str, min: f64, max: f64, default: f64) -> Self {
+        Self::new(path, ClapParamKind::Float, min, max, default)
+    }
+
+    pub fn int(path: &str, min: i64, max: i64, default: i64) -> Self {
+        Self::new(path, ClapParamKind::Int, min as f64, max as f64, default as f64)
+    }
+
+    pub fn toggle(path: &str, default: bool) -> Self {
+        Self::new(path, ClapParamKind::Toggle, 0.0, 1.0, if default {1.0} else {0.0})
+    }
+
+    /// `options` are the names of the enum variants, in order
+    pub fn choice(path: &str, options: &[&str], default: usize) -> Self {
+        let options: Vec<String> = options.iter().map( | o | o.to_string()).collect();
+        let max = options.len().max(1) as f64 - 1.0;
+        Self::new(path, ClapParamKind::Choice(options), 0.0, max, default as f64)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Groups parameters in the host, like `Filter` or `Oscillators/Osc 1`
+    pub fn with_module(mut self, module: &str) -> Self {
+        self.module = module.to_string();
+        self
+    }
+
+    pub fn with_unit(mut self, unit: &str) -> Self {
+        self.unit = unit.to_string();
+        self
+    }
+
+    /// The id the host knows the parameter of `path` by. It comes from the path
+    /// so automation keeps working when parameters are added or reordered
+    pub fn id_of(path: &str) -> u32 {
+        let id = LiveId::from_str(path).0 as u32;
+        if id == CLAP_INVALID_ID {0} else {id}
+    }
+
+    pub fn flags(&self) -> u32 {
+        match self.kind {
+            ClapParamKind::Float => CLAP_PARAM_IS_AUTOMATABLE,
+            _ => CLAP_PARAM_IS_AUTOMATABLE | CLAP_PARAM_IS_STEPPED,
+        }
+    }
+
+    pub fn clamp(&self, value: f64) -> f64 {
+        let value = if value.is_nan() {self.default} else {value.clamp(self.min, self.max)};
+        match self.kind {
+            ClapParamKind::Float => value,
+            _ => value.round(),
+        }
+    }
+
+    pub fn live_value(&self, value: f64) -> LiveValue {
+        let value = self.clamp(value);
+        match &self.kind {
+            ClapParamKind::Float => LiveValue::Float64(value),
+            ClapParamKind::Int => LiveValue::Int64(value as i64),
+            ClapParamKind::Toggle => LiveValue::Bool(value >= 0.5),
+            ClapParamKind::Choice(options) => match options.get(value as usize) {
+                Some(option) => LiveValue::BareEnum(LiveId::from_str(option)),
+                None => LiveValue::None,
+            }
+        }
+    }
+
+    /// The path as ids, what audio nodes take parameters by
+    pub fn live_path(&self) -> Vec<LiveId> {
+        self.path.split('.').map(LiveId::from_str).collect()
+    }
+
+    /// The nodes that set the property to `value` with `apply_over`
+    pub fn live_nodes(&self, value: f64) -> Vec<LiveNode> {
+        let mut path = self.live_path();
+        let last = path.pop().unwrap();
+        let mut nodes = vec![LiveNode::from_value(LiveValue::Object)];
+        for id in &path {
+            nodes.push(LiveNode::from_id_value(*id, LiveValue::Object));
+        }
+        nodes.push(LiveNode::from_id_value(last, self.live_value(value)));
+        for _ in 0..path.len() + 1 {
+            nodes.push(LiveNode::from_value(LiveValue::Close));
+        }
+        nodes
+    }
+
+    pub fn value_to_text(&self, value: f64) -> String {
+        let value = self.clamp(value);
+        let text = match &self.kind {
+            ClapParamKind::Float => format!("{:.3}", value),
+            ClapParamKind::Int => format!("{}", value as i64),
+            ClapParamKind::Toggle => return if value >= 0.5 {"On"} else {"Off"}.to_string(),
+            ClapParamKind::Choice(options) => return options.get(value as usize).cloned().unwrap_or_default(),
+        };
+        if self.unit.is_empty() {text} else {format!("{} {}", text, self.unit)}
+    }
+
+    pub fn text_to_value(&self, text: &str) -> Option<f64> {
+        let text = text.trim();
+        match &self.kind {
+            ClapParamKind::Toggle => match text.to_lowercase().as_str() {
+                "on" | "true" | "1" => Some(1.0),
+                "off" | "false" | "0" => Some(0.0),
+                _ => None
+            },
+            ClapParamKind::Choice(options) => options.iter()
+                .position( | o | o.eq_ignore_ascii_case(text))
+                .map( | index | index as f64)
+                .or_else( || text.parse::<usize>().ok().filter( | i | *i < options.len()).map( | i | i as f64)),
+            ClapParamKind::Float | ClapParamKind::Int => {
+                let number = text.strip_suffix(self.unit.as_str()).unwrap_or(text).trim();
+                number.parse::<f64>().ok().filter( | v | v.is_finite()).map( | v | self.clamp(v))
+            }
+        }
+    }
+}
@@ -0,0 +1,919 @@
+use {
+    crate::{
+        makepad_audio_graph::{
+            AudioComponent,
+            AudioGraphNode,
+            DisplayAudioGraph,
+            ToUIDisplayMsg,
+            makepad_platform::{
+                *,
+                os::linux::clap_sys::*,
+            },
+        },
+        param::*,
+        gui::*,
+        ClapDescriptor,
+        ClapExport,
+    },
+    std::{
+        cell::UnsafeCell,
+        ffi::{CStr, CString},
+        os::raw::{c_char, c_void},
+        path::PathBuf,
+        ptr,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex, OnceLock,
+        },
+    },
+};
+
+// the descriptor with the strings it points into
+struct ExportedDescriptor {
+    clap: clap_plugin_descriptor,
+    _strings: Vec<CString>,
+    _features: Vec<*const c_char>,
+}
+
+unsafe impl Send for ExportedDescriptor {}
+unsafe impl Sync for ExportedDescriptor {}
+
+impl ExportedDescriptor {
+    fn new(descriptor: &ClapDescriptor) -> Self {
+        let mut strings = Vec::new();
+        let mut c_str = | s: &str | {
+            let s = CString::new(s).unwrap_or_default();
+            let ptr = s.as_ptr();
+            strings.push(s);
+            ptr
+        };
+        let id = c_str(descriptor.id);
+        let name = c_str(descriptor.name);
+        let vendor = c_str(descriptor.vendor);
+        let url = c_str(descriptor.url);
+        let version = c_str(descriptor.version);
+        let description = c_str(descriptor.description);
+        let mut features: Vec<*const c_char> = descriptor.features.iter().map( | f | c_str(f)).collect();
+        features.push(ptr::null());
+        Self {
+            clap: clap_plugin_descriptor {
+                clap_version: CLAP_VERSION,
+                id,
+                name,
+                vendor,
+                url,
+                manual_url: url,
+                support_url: url,
+                version,
+                description,
+                features: features.as_ptr(),
+            },
+            _strings: strings,
+            _features: features,
+        }
+    }
+}
+
+/// The plugin factory of a bundle, `clap_export!` puts one in a static and
+/// hands it to the host from its `clap_entry`
+#[repr(C)]
+pub struct ClapFactory {
+    // first, the host gets a pointer to it and we cast it back
+    clap: clap_plugin_factory,
+    descriptor: fn() -> ClapDescriptor,
+    params: fn() -> Vec<ClapParam>,
+    live_register: fn(&mut Cx),
+    new_component: fn(&mut Cx) -> Box<dyn AudioComponent>,
+    exported: OnceLock<ExportedDescriptor>,
+    bundle_path: Mutex<Option<PathBuf>>,
+}
+
+impl ClapFactory {
+    pub const fn new<T: ClapExport>() -> Self {
+        Self {
+            clap: clap_plugin_factory {
+                get_plugin_count: factory_count,
+                get_plugin_descriptor: factory_descriptor,
+                create_plugin: factory_create,
+            },
+            descriptor: T::descriptor,
+            params: T::params,
+            live_register: T::live_register,
+            new_component: T::new_component,
+            exported: OnceLock::new(),
+            bundle_path: Mutex::new(None),
+        }
+    }
+
+    fn exported(&self) -> &ExportedDescriptor {
+        self.exported.get_or_init( || ExportedDescriptor::new(&(self.descriptor)()))
+    }
+
+    /// `clap_entry.init`, with the path of the `.clap` file
+    ///
+    /// # Safety
+    /// `plugin_path` is null or a C string, as the host hands it over
+    pub unsafe fn entry_init(&self, plugin_path: *const c_char) -> bool {
+        if !plugin_path.is_null() {
+            let path = CStr::from_ptr(plugin_path).to_string_lossy().to_string();
+            *self.bundle_path.lock().unwrap() = Some(PathBuf::from(path));
+        }
+        true
+    }
+
+    /// # Safety
+    /// Called by the host once it is done with the bundle
+    pub unsafe fn entry_deinit(&self) {
+    }
+
+    /// # Safety
+    /// `factory_id` is a C string, as the host hands it over
+    pub unsafe fn entry_get_factory(&self, factory_id: *const c_char) -> *const c_void {
+        if CStr::from_ptr(factory_id).to_bytes_with_nul() == CLAP_PLUGIN_FACTORY_ID {
+            &self.clap as *const clap_plugin_factory as *const c_void
+        }
+        else {
+            ptr::null()
+        }
+    }
+
+    // the gui app lives next to the .clap file
+    fn gui_executable(&self, name: &str) -> Option<PathBuf> {
+        let bundle = self.bundle_path.lock().unwrap().clone() ?;
+        Some(bundle.parent()?.join(name))
+    }
+}
+
+unsafe fn factory<'a>(factory: *const clap_plugin_factory) -> &'a ClapFactory {
+    &*(factory as *const ClapFactory)
+}
+
+unsafe extern "C" fn factory_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_descriptor(f: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor {
+    if index == 0 {&factory(f).exported().clap} else {ptr::null()}
+}
+
+unsafe extern "C" fn factory_create(f: *const clap_plugin_factory, host: *const clap_host, plugin_id: *const c_char) -> *const clap_plugin {
+    let factory = factory(f);
+    if host.is_null() || plugin_id.is_null() || CStr::from_ptr(plugin_id) != CStr::from_ptr(factory.exported().clap.id) {
+        return ptr::null()
+    }
+    Plugin::create(factory, host)
+}
+
+// the host pointer for the gui reader thread
+struct HostPtr(*const clap_host);
+unsafe impl Send for HostPtr {}
+
+struct MainThread {
+    host_params: *const clap_host_params,
+    host_state: *const clap_host_state,
+    host_gui: *const clap_host_gui,
+    cx: Cx,
+    component: Box<dyn AudioComponent>,
+    gui: Option<GuiProcess>,
+    gui_created: bool,
+    gui_title: String,
+}
+
+struct AudioThread {
+    node: Box<dyn AudioGraphNode + Send>,
+    sample_rate: f64,
+    steady_time: i64,
+    output: AudioBuffer,
+    input: AudioBuffer,
+    display: ToUIReceiver<ToUIDisplayMsg>,
+    display_buffers: Vec<AudioBuffer>,
+}
+
+// clap_plugin is first so the plugin pointer is the plugin too, but we go
+// through plugin_data like the host expects
+#[repr(C)]
+struct Plugin {
+    clap: clap_plugin,
+    factory: &'static ClapFactory,
+    descriptor: ClapDescriptor,
+    host: *const clap_host,
+    params: Vec<ClapParam>,
+    // the live paths of the params, made up front for the audio thread
+    paths: Vec<Vec<LiveId>>,
+    values: Vec<AtomicU64>,
+    // changed from the host, not applied to the component yet
+    dirty: Vec<AtomicBool>,
+    // edits from the gui on their way to the host
+    to_host: Mutex<Vec<ClapGuiMessage>>,
+    gui_inbox: Arc<Mutex<GuiInbox>>,
+    // only touched on the main thread, and created in init
+    main: UnsafeCell<Option<MainThread>>,
+    // set up on the main thread while not processing, then the audio thread's
+    audio: UnsafeCell<Option<AudioThread>>,
+}
+
+impl Plugin {
+    unsafe fn create(factory: &'static ClapFactory, host: *const clap_host) -> *const clap_plugin {
+        let params = (factory.params)();
+        let plugin = Box::new(Plugin {
+            clap: clap_plugin {
+                desc: &factory.exported().clap,
+                plugin_data: ptr::null_mut(),
+                init: plugin_init,
+                destroy: plugin_destroy,
+                activate: plugin_activate,
+                deactivate: plugin_deactivate,
+                start_processing: plugin_start_processing,
+                stop_processing: plugin_stop_processing,
+                reset: plugin_reset,
+                process: plugin_process,
+                get_extension: plugin_get_extension,
+                on_main_thread: plugin_on_main_thread,
+            },
+            factory,
+            descriptor: (factory.descriptor)(),
+            host,
+            values: params.iter().map( | p | AtomicU64::new(p.default.to_bits())).collect(),
+            dirty: params.iter().map( | _ | AtomicBool::new(false)).collect(),
+            paths: params.iter().map( | p | p.live_path()).collect(),
+            params,
+            to_host: Mutex::new(Vec::new()),
+            gui_inbox: Arc::new(Mutex::new(GuiInbox::default())),
+            main: UnsafeCell::new(None),
+            audio: UnsafeCell::new(None),
+        });
+        let plugin = Box::into_raw(plugin);
+        (*plugin).clap.plugin_data = plugin as *mut c_void;
+        &(*plugin).clap
+    }
+
+    unsafe fn from_clap<'a>(plugin: *const clap_plugin) -> &'a Plugin {
+        &*((*plugin).plugin_data as *const Plugin)
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn main(&self) -> Option<&mut MainThread> {
+        (*self.main.get()).as_mut()
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn audio(&self) -> Option<&mut AudioThread> {
+        (*self.audio.get()).as_mut()
+    }
+
+    unsafe fn host_extension<T>(&self, id: &[u8]) -> *const T {
+        ((*self.host).get_extension)(self.host, id.as_ptr() as *const c_char) as *const T
+    }
+
+    fn param_index(&self, id: u32) -> Option<usize> {
+        self.params.iter().position( | p | p.id == id)
+    }
+
+    fn value(&self, index: usize) -> f64 {
+        f64::from_bits(self.values[index].load(Ordering::Relaxed))
+    }
+
+    fn store_value(&self, index: usize, value: f64) -> f64 {
+        let value = self.params[index].clamp(value);
+        self.values[index].store(value.to_bits(), Ordering::Relaxed);
+        value
+    }
+
+    // a value from the host, on either thread. On the audio thread the node
+    // gets it right away, the component always catches up on the main thread.
+    // Returns if it has to wait for the main thread
+    fn set_from_host(&self, audio: Option<&mut AudioThread>, id: u32, value: f64) -> bool {
+        let Some(index) = self.param_index(id) else {return false};
+        let value = self.store_value(index, value);
+        if let Some(audio) = audio {
+            audio.node.set_param(&self.paths[index], &self.params[index].live_value(value));
+        }
+        self.dirty[index].store(true, Ordering::Release);
+        true
+    }
+
+    // main thread
+    fn apply_param(&self, main: &mut MainThread, index: usize) {
+        let param = &self.params[index];
+        let value = self.value(index);
+        main.component.apply_over(&mut main.cx, &param.live_nodes(value));
+    }
+
+    // main thread
+    fn apply_dirty(&self, main: &mut MainThread) {
+        for index in 0..self.params.len() {
+            if self.dirty[index].swap(false, Ordering::Acquire) {
+                self.apply_param(main, index);
+                if let Some(gui) = &mut main.gui {
+                    gui.send(ClapGuiMessage::Value {id: self.params[index].id, value: self.value(index)});
+                }
+            }
+        }
+    }
+
+    // main thread
+    unsafe fn handle_gui(&self, main: &mut MainThread) {
+        let (messages, closed) = {
+            let mut inbox = self.gui_inbox.lock().unwrap();
+            (std::mem::take(&mut inbox.messages), std::mem::take(&mut inbox.closed))
+        };
+        let mut edited = false;
+        for msg in messages {
+            let msg = match msg {
+                ClapGuiMessage::Value {id, value} => {
+                    let Some(index) = self.param_index(id) else {continue};
+                    let value = self.store_value(index, value);
+                    self.apply_param(main, index);
+                    ClapGuiMessage::Value {id, value}
+                }
+                ClapGuiMessage::GestureBegin {id} | ClapGuiMessage::GestureEnd {id} if self.param_index(id).is_none() => continue,
+                msg => msg
+            };
+            self.to_host.lock().unwrap().push(msg);
+            edited = true;
+        }
+        if edited {
+            if let Some(host_params) = main.host_params.as_ref() {
+                (host_params.request_flush)(self.host);
+            }
+            if let Some(host_state) = main.host_state.as_ref() {
+                (host_state.mark_dirty)(self.host);
+            }
+        }
+        if closed && main.gui.take().is_some() {
+            if let Some(host_gui) = main.host_gui.as_ref() {
+                (host_gui.closed)(self.host, false);
+            }
+        }
+    }
+
+    unsafe fn push_to_host(&self, out: *const clap_output_events) {
+        let Some(out) = out.as_ref() else {return};
+        let Ok(mut to_host) = self.to_host.try_lock() else {return};
+        for msg in to_host.drain(..) {
+            let _ = match msg {
+                ClapGuiMessage::Value {id, value} => {
+                    let event = clap_event_param_value {
+                        header: clap_event_header::new::<clap_event_param_value>(CLAP_EVENT_PARAM_VALUE, 0),
+                        param_id: id,
+                        cookie: ptr::null_mut(),
+                        note_id: -1,
+                        port_index: -1,
+                        channel: -1,
+                        key: -1,
+                        value,
+                    };
+                    (out.try_push)(out, &event.header)
+                }
+                ClapGuiMessage::GestureBegin {id} | ClapGuiMessage::GestureEnd {id} => {
+                    let type_ = if let ClapGuiMessage::GestureBegin {..} = msg {CLAP_EVENT_PARAM_GESTURE_BEGIN} else {CLAP_EVENT_PARAM_GESTURE_END};
+                    let event = clap_event_param_gesture {
+                        header: clap_event_header::new::<clap_event_param_gesture>(type_, 0),
+                        param_id: id,
+                    };
+                    (out.try_push)(out, &event.header)
+                }
+            };
+        }
+    }
+
+    // an input event, on the audio thread or in a flush. Returns if a
+    // parameter changed
+    unsafe fn handle_event(&self, audio: Option<&mut AudioThread>, header: &clap_event_header) -> bool {
+        if header.space_id != CLAP_CORE_EVENT_SPACE_ID {
+            return false
+        }
+        match header.type_ {
+            CLAP_EVENT_PARAM_VALUE => {
+                let event = &*(header as *const clap_event_header as *const clap_event_param_value);
+                return self.set_from_host(audio, event.param_id, event.value)
+            }
+            CLAP_EVENT_NOTE_ON | CLAP_EVENT_NOTE_OFF | CLAP_EVENT_NOTE_CHOKE => {
+                let Some(audio) = audio else {return false};
+                let event = &*(header as *const clap_event_header as *const clap_event_note);
+                if event.key < 0 {
+                    if header.type_ != CLAP_EVENT_NOTE_ON {
+                        audio.node.all_notes_off();
+                    }
+                    return false
+                }
+                let channel = event.channel.clamp(0, 15) as u8;
+                let key = event.key.min(127) as u8;
+                let velocity = (event.velocity * 127.0).round().clamp(0.0, 127.0) as u8;
+                let data = if header.type_ == CLAP_EVENT_NOTE_ON {
+                    [0x90 | channel, key, velocity.max(1)]
+                }
+                else {
+                    [0x80 | channel, key, velocity]
+                };
+                audio.node.handle_midi_data(MidiData {data});
+            }
+            CLAP_EVENT_MIDI => {
+                let Some(audio) = audio else {return false};
+                let event = &*(header as *const clap_event_header as *const clap_event_midi);
+                audio.node.handle_midi_data(MidiData {data: event.data});
+            }
+            _ => ()
+        }
+        false
+    }
+
+    unsafe fn render(&self, audio: &mut AudioThread, process: &clap_process, from: usize, to: usize) {
+        let frames = to - from;
+        if frames == 0 {
+            return
+        }
+        audio.output.resize(frames, 2);
+        audio.output.zero();
+        let input = match process.audio_inputs.as_ref().filter( | _ | process.audio_inputs_count > 0) {
+            Some(port) if !port.data32.is_null() && port.channel_count > 0 => {
+                audio.input.resize(frames, port.channel_count as usize);
+                for c in 0..port.channel_count as usize {
+                    let channel = std::slice::from_raw_parts(*port.data32.add(c), to);
+                    audio.input.channel_mut(c).copy_from_slice(&channel[from..to]);
+                }
+                true
+            }
+            _ => false
+        };
+        let info = AudioInfo {
+            device_id: AudioDeviceId(LiveId(0)),
+            time: Some(AudioTime {
+                sample_time: (audio.steady_time + from as i64) as f64,
+                host_time: 0,
+                rate_scalar: 1.0,
+            }),
+            sample_rate: audio.sample_rate,
+        };
+        let to_ui = audio.display.sender();
+        let mut display = DisplayAudioGraph {
+            to_ui: &to_ui,
+            buffers: &mut audio.display_buffers,
+        };
+        if input {
+            audio.node.render_to_audio_buffer(info, &mut [&mut audio.output], &[&audio.input], &mut display);
+        }
+        else {
+            audio.node.render_to_audio_buffer(info, &mut [&mut audio.output], &[], &mut display);
+        }
+        // there is nobody to show the display audio to
+        while let Ok(msg) = audio.display.try_recv() {
+            if let ToUIDisplayMsg::DisplayAudio {buffer, ..} = msg {
+                audio.display_buffers.push(buffer);
+            }
+        }
+        let Some(port) = process.audio_outputs.as_ref().filter( | _ | process.audio_outputs_count > 0) else {return};
+        if port.data32.is_null() {
+            return
+        }
+        for c in 0..port.channel_count as usize {
+            let channel = std::slice::from_raw_parts_mut(*port.data32.add(c), to);
+            channel[from..to].copy_from_slice(audio.output.channel(c.min(1)));
+        }
+    }
+
+    unsafe fn process(&self, process: &clap_process) -> clap_process_status {
+        let Some(audio) = self.audio() else {return CLAP_PROCESS_ERROR};
+        let frames = process.frames_count as usize;
+        let mut done = 0;
+        let mut params_changed = false;
+        if let Some(events) = process.in_events.as_ref() {
+            for index in 0..(events.size)(events) {
+                let Some(header) = (events.get)(events, index).as_ref() else {continue};
+                // split the block so notes and parameters land on their frame
+                let time = (header.time as usize).min(frames);
+                if time > done {
+                    self.render(audio, process, done, time);
+                    done = time;
+                }
+                params_changed |= self.handle_event(Some(&mut *audio), header);
+            }
+        }
+        self.render(audio, process, done, frames);
+        audio.steady_time += frames as i64;
+        self.push_to_host(process.out_events);
+        if params_changed {
+            ((*self.host).request_callback)(self.host);
+        }
+        CLAP_PROCESS_CONTINUE
+    }
+
+    // main thread
+    unsafe fn open_gui(&self, main: &mut MainThread) -> bool {
+        if main.gui.is_some() {
+            return true
+        }
+        let Some(executable) = self.descriptor.gui.and_then( | gui | self.factory.gui_executable(gui)) else {return false};
+        *self.gui_inbox.lock().unwrap() = GuiInbox::default();
+        let host = HostPtr(self.host);
+        let notify = Box::new(move || {
+            let host = &host;
+            unsafe {((*host.0).request_callback)(host.0)}
+        });
+        match GuiProcess::spawn(&executable, &main.gui_title, self.gui_inbox.clone(), notify) {
+            Ok(mut gui) => {
+                for (index, param) in self.params.iter().enumerate() {
+                    gui.send(ClapGuiMessage::Value {id: param.id, value: self.value(index)});
+                }
+                main.gui = Some(gui);
+                true
+            }
+            Err(err) => {
+                error!("Cannot start the plugin gui {}: {}", executable.display(), err);
+                false
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn plugin_init(plugin: *const clap_plugin) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let mut cx = Cx::new(Box::new( | _, _ | {}));
+    (plugin.factory.live_register)(&mut cx);
+    cx.live_expand();
+    let component = (plugin.factory.new_component)(&mut cx);
+    let mut main = MainThread {
+        host_params: plugin.host_extension(CLAP_EXT_PARAMS),
+        host_state: plugin.host_extension(CLAP_EXT_STATE),
+        host_gui: plugin.host_extension(CLAP_EXT_GUI),
+        cx,
+        component,
+        gui: None,
+        gui_created: false,
+        gui_title: plugin.descriptor.name.to_string(),
+    };
+    // the component starts out at the defaults the host was told about
+    for index in 0..plugin.params.len() {
+        plugin.apply_param(&mut main, index);
+    }
+    *plugin.main.get() = Some(main);
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    let plugin = (*plugin).plugin_data as *mut Plugin;
+    // stops the gui and its reader before the host goes away
+    drop(Box::from_raw(plugin));
+}
+
+unsafe extern "C" fn plugin_activate(plugin: *const clap_plugin, sample_rate: f64, _min_frames: u32, max_frames: u32) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(main) = plugin.main() else {return false};
+    plugin.apply_dirty(main);
+    let node = main.component.get_graph_node(&mut main.cx);
+    let max_frames = max_frames.max(1) as usize;
+    *plugin.audio.get() = Some(AudioThread {
+        node,
+        sample_rate,
+        steady_time: 0,
+        output: AudioBuffer::new_with_size(max_frames, 2),
+        input: AudioBuffer::new_with_size(max_frames, 2),
+        display: ToUIReceiver::default(),
+        display_buffers: (0..16).map( | _ | AudioBuffer::new_with_size(max_frames, 2)).collect(),
+    });
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(plugin: *const clap_plugin) {
+    let plugin = Plugin::from_clap(plugin);
+    *plugin.audio.get() = None;
+}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(plugin: *const clap_plugin) {
+    if let Some(audio) = Plugin::from_clap(plugin).audio() {
+        audio.node.all_notes_off();
+    }
+}
+
+unsafe extern "C" fn plugin_reset(plugin: *const clap_plugin) {
+    if let Some(audio) = Plugin::from_clap(plugin).audio() {
+        audio.node.all_notes_off();
+    }
+}
+
+unsafe extern "C" fn plugin_process(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status {
+    match process.as_ref() {
+        Some(process) => Plugin::from_clap(plugin).process(process),
+        None => CLAP_PROCESS_ERROR
+    }
+}
+
+unsafe extern "C" fn plugin_get_extension(plugin: *const clap_plugin, id: *const c_char) -> *const c_void {
+    let plugin = Plugin::from_clap(plugin);
+    let id = CStr::from_ptr(id).to_bytes_with_nul();
+    match id {
+        _ if id == CLAP_EXT_PARAMS => &PARAMS as *const clap_plugin_params as *const c_void,
+        _ if id == CLAP_EXT_STATE => &STATE as *const clap_plugin_state as *const c_void,
+        _ if id == CLAP_EXT_AUDIO_PORTS => &AUDIO_PORTS as *const clap_plugin_audio_ports as *const c_void,
+        _ if id == CLAP_EXT_NOTE_PORTS => &NOTE_PORTS as *const clap_plugin_note_ports as *const c_void,
+        _ if id == CLAP_EXT_GUI && plugin.descriptor.gui.is_some() => &GUI as *const clap_plugin_gui as *const c_void,
+        _ => ptr::null(),
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(plugin: *const clap_plugin) {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(main) = plugin.main() else {return};
+    plugin.apply_dirty(main);
+    plugin.handle_gui(main);
+    main.component.handle_event_with(&mut main.cx, &Event::Signal, &mut | _, _ | {});
+}
+
+// params
+
+static PARAMS: clap_plugin_params = clap_plugin_params {
+    count: params_count,
+    get_info: params_get_info,
+    get_value: params_get_value,
+    value_to_text: params_value_to_text,
+    text_to_value: params_text_to_value,
+    flush: params_flush,
+};
+
+fn write_c_str(target: &mut [c_char], text: &str) {
+    let len = text.len().min(target.len() - 1);
+    for (t, b) in target.iter_mut().zip(&text.as_bytes()[..len]) {
+        *t = *b as c_char;
+    }
+    target[len] = 0;
+}
+
+unsafe extern "C" fn params_count(plugin: *const clap_plugin) -> u32 {
+    Plugin::from_clap(plugin).params.len() as u32
+}
+
+unsafe extern "C" fn params_get_info(plugin: *const clap_plugin, index: u32, info: *mut clap_param_info) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let (Some(param), Some(info)) = (plugin.params.get(index as usize), info.as_mut()) else {return false};
+    info.id = param.id;
+    info.flags = param.flags();
+    info.cookie = ptr::null_mut();
+    write_c_str(&mut info.name, &param.name);
+    write_c_str(&mut info.module, &param.module);
+    info.min_value = param.min;
+    info.max_value = param.max;
+    info.default_value = param.default;
+    true
+}
+
+unsafe extern "C" fn params_get_value(plugin: *const clap_plugin, id: clap_id, value: *mut f64) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(index) = plugin.param_index(id) else {return false};
+    *value = plugin.value(index);
+    true
+}
+
+unsafe extern "C" fn params_value_to_text(plugin: *const clap_plugin, id: clap_id, value: f64, buffer: *mut c_char, capacity: u32) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(index) = plugin.param_index(id) else {return false};
+    if buffer.is_null() || capacity == 0 {
+        return false
+    }
+    let target = std::slice::from_raw_parts_mut(buffer, capacity as usize);
+    write_c_str(target, &plugin.params[index].value_to_text(value));
+    true
+}
+
+unsafe extern "C" fn params_text_to_value(plugin: *const clap_plugin, id: clap_id, text: *const c_char, value: *mut f64) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(index) = plugin.param_index(id) else {return false};
+    match plugin.params[index].text_to_value(&CStr::from_ptr(text).to_string_lossy()) {
+        Some(v) => {
+            *value = v;
+            true
+        }
+        None => false
+    }
+}
+
+unsafe extern "C" fn params_flush(plugin: *const clap_plugin, in_: *const clap_input_events, out: *const clap_output_events) {
+    let plugin = Plugin::from_clap(plugin);
+    let mut params_changed = false;
+    let mut audio = plugin.audio();
+    if let Some(events) = in_.as_ref() {
+        for index in 0..(events.size)(events) {
+            if let Some(header) = (events.get)(events, index).as_ref() {
+                params_changed |= plugin.handle_event(audio.as_deref_mut(), header);
+            }
+        }
+    }
+    plugin.push_to_host(out);
+    if !params_changed {
+        return
+    }
+    // active it is called on the audio thread, otherwise on the main thread
+    match audio {
+        Some(_) => ((*plugin.host).request_callback)(plugin.host),
+        None => if let Some(main) = plugin.main() {
+            plugin.apply_dirty(main);
+        }
+    }
+}
+
+// state, the parameter values by id
+
+const STATE_MAGIC: &[u8; 8] = b"MPCLAP1\0";
+
+static STATE: clap_plugin_state = clap_plugin_state {
+    save: state_save,
+    load: state_load,
+};
+
+unsafe extern "C" fn state_save(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(stream) = stream.as_ref() else {return false};
+    let mut data = STATE_MAGIC.to_vec();
+    data.extend_from_slice(&(plugin.params.len() as u32).to_le_bytes());
+    for (index, param) in plugin.params.iter().enumerate() {
+        data.extend_from_slice(&param.id.to_le_bytes());
+        data.extend_from_slice(&plugin.value(index).to_le_bytes());
+    }
+    let mut written = 0;
+    while written < data.len() {
+        let n = (stream.write)(stream, data[written..].as_ptr() as *const c_void, (data.len() - written) as u64);
+        if n <= 0 {
+            return false
+        }
+        written += n as usize;
+    }
+    true
+}
+
+unsafe extern "C" fn state_load(plugin: *const clap_plugin, stream: *const clap_istream) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    let Some(stream) = stream.as_ref() else {return false};
+    let mut data = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = (stream.read)(stream, chunk.as_mut_ptr() as *mut c_void, chunk.len() as u64);
+        if n < 0 {
+            return false
+        }
+        if n == 0 {
+            break
+        }
+        data.extend_from_slice(&chunk[..n as usize]);
+    }
+    let Some(rest) = data.strip_prefix(STATE_MAGIC.as_slice()) else {return false};
+    let Some((count, mut rest)) = rest.split_first_chunk::<4>() else {return false};
+    for _ in 0..u32::from_le_bytes(*count) {
+        let Some((id, tail)) = rest.split_first_chunk::<4>() else {return false};
+        let Some((value, tail)) = tail.split_first_chunk::<8>() else {return false};
+        // values of parameters that went away since are dropped
+        plugin.set_from_host(None, u32::from_le_bytes(*id), f64::from_le_bytes(*value));
+        rest = tail;
+    }
+    let Some(main) = plugin.main() else {return false};
+    plugin.apply_dirty(main);
+    if let Some(host_params) = main.host_params.as_ref() {
+        (host_params.rescan)(plugin.host, CLAP_PARAM_RESCAN_VALUES);
+    }
+    true
+}
+
+// one stereo output, and a stereo input for effects
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: audio_ports_count,
+    get: audio_ports_get,
+};
+
+unsafe extern "C" fn audio_ports_count(plugin: *const clap_plugin, is_input: bool) -> u32 {
+    if is_input && Plugin::from_clap(plugin).descriptor.is_instrument() {0} else {1}
+}
+
+unsafe extern "C" fn audio_ports_get(plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_audio_port_info) -> bool {
+    let (true, Some(info)) = (index < audio_ports_count(plugin, is_input), info.as_mut()) else {return false};
+    info.id = 0;
+    write_c_str(&mut info.name, if is_input {"Input"} else {"Output"});
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = c"stereo".as_ptr();
+    info.in_place_pair = CLAP_INVALID_ID;
+    true
+}
+
+// one note input, the components take midi
+
+static NOTE_PORTS: clap_plugin_note_ports = clap_plugin_note_ports {
+    count: note_ports_count,
+    get: note_ports_get,
+};
+
+unsafe extern "C" fn note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+    if is_input {1} else {0}
+}
+
+unsafe extern "C" fn note_ports_get(_plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_note_port_info) -> bool {
+    let (true, 0, Some(info)) = (is_input, index, info.as_mut()) else {return false};
+    info.id = 0;
+    info.supported_dialects = CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI;
+    info.preferred_dialect = CLAP_NOTE_DIALECT_MIDI;
+    write_c_str(&mut info.name, "Notes");
+    true
+}
+
+// gui, a floating window of the gui app
+
+static GUI: clap_plugin_gui = clap_plugin_gui {
+    is_api_supported: gui_is_api_supported,
+    get_preferred_api: gui_get_preferred_api,
+    create: gui_create,
+    destroy: gui_destroy,
+    set_scale: gui_set_scale,
+    get_size: gui_get_size,
+    can_resize: gui_can_resize,
+    get_resize_hints: gui_get_resize_hints,
+    adjust_size: gui_adjust_size,
+    set_size: gui_set_size,
+    set_parent: gui_set_parent,
+    set_transient: gui_set_transient,
+    suggest_title: gui_suggest_title,
+    show: gui_show,
+    hide: gui_hide,
+};
+
+unsafe extern "C" fn gui_is_api_supported(_plugin: *const clap_plugin, api: *const c_char, is_floating: bool) -> bool {
+    is_floating && !api.is_null() && CStr::from_ptr(api).to_bytes_with_nul() == CLAP_WINDOW_API_X11
+}
+
+unsafe extern "C" fn gui_get_preferred_api(_plugin: *const clap_plugin, api: *mut *const c_char, is_floating: *mut bool) -> bool {
+    *api = CLAP_WINDOW_API_X11.as_ptr() as *const c_char;
+    *is_floating = true;
+    true
+}
+
+unsafe extern "C" fn gui_create(plugin: *const clap_plugin, api: *const c_char, is_floating: bool) -> bool {
+    let plugin_ptr = plugin;
+    let Some(main) = Plugin::from_clap(plugin).main() else {return false};
+    if !gui_is_api_supported(plugin_ptr, api, is_floating) {
+        return false
+    }
+    main.gui_created = true;
+    true
+}
+
+unsafe extern "C" fn gui_destroy(plugin: *const clap_plugin) {
+    if let Some(main) = Plugin::from_clap(plugin).main() {
+        main.gui = None;
+        main.gui_created = false;
+    }
+}
+
+unsafe extern "C" fn gui_set_scale(_plugin: *const clap_plugin, _scale: f64) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_get_size(_plugin: *const clap_plugin, _width: *mut u32, _height: *mut u32) -> bool {
+    // the app sizes its own window
+    false
+}
+
+unsafe extern "C" fn gui_can_resize(_plugin: *const clap_plugin) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_get_resize_hints(_plugin: *const clap_plugin, _hints: *mut clap_gui_resize_hints) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_adjust_size(_plugin: *const clap_plugin, _width: *mut u32, _height: *mut u32) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_set_size(_plugin: *const clap_plugin, _width: u32, _height: u32) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_set_parent(_plugin: *const clap_plugin, _window: *const clap_window) -> bool {
+    false
+}
+
+unsafe extern "C" fn gui_set_transient(_plugin: *const clap_plugin, _window: *const clap_window) -> bool {
+    true
+}
+
+unsafe extern "C" fn gui_suggest_title(plugin: *const clap_plugin, title: *const c_char) {
+    if let (Some(main), false) = (Plugin::from_clap(plugin).main(), title.is_null()) {
+        main.gui_title = CStr::from_ptr(title).to_string_lossy().to_string();
+    }
+}
+
+unsafe extern "C" fn gui_show(plugin: *const clap_plugin) -> bool {
+    let plugin = Plugin::from_clap(plugin);
+    match plugin.main() {
+        Some(main) if main.gui_created => plugin.open_gui(main),
+        _ => false
+    }
+}
+
+unsafe extern "C" fn gui_hide(plugin: *const clap_plugin) -> bool {
+    if let Some(main) = Plugin::from_clap(plugin).main() {
+        main.gui = None;
+    }
+    true
+}
@@ -0,0 +1,251 @@
+#![cfg(all(target_os = "linux", not(target_env = "ohos")))]
+
+use makepad_clap_export::*;
+use makepad_clap_export::makepad_audio_graph::*;
+use makepad_clap_export::makepad_audio_graph::makepad_platform::*;
+use makepad_clap_export::makepad_audio_graph::makepad_platform::os::linux::clap::*;
+use std::{path::PathBuf, sync::Arc, time::{Duration, Instant}};
+
+// A sample player with a sample of all ones as the instrument, so what comes
+// out is the gain of the voice. It is loaded in process by the host
+
+struct TestPlayer;
+
+impl ClapExport for TestPlayer {
+    fn descriptor() -> ClapDescriptor {
+        ClapDescriptor {
+            id: "dev.makepad.test-player",
+            name: "Test Player",
+            vendor: "Makepad",
+            url: "https://makepad.dev",
+            version: "1.0.0",
+            description: "A sample player for testing the export",
+            features: &["instrument", "sampler", "stereo"],
+            gui: Some("test-player-gui"),
+        }
+    }
+
+    fn params() -> Vec<ClapParam> {
+        vec![
+            ClapParam::float("gain", 0.0, 2.0, 0.5).with_name("Gain"),
+            ClapParam::choice("mode", &["OneShot", "Gate", "Loop"], 1).with_name("Mode"),
+            ClapParam::float("release", 0.0, 1.0, 0.001).with_name("Release").with_unit("s"),
+        ]
+    }
+
+    fn live_register(cx: &mut Cx) {
+        SamplePlayer::register_main_module(cx);
+        makepad_audio_graph::live_design(cx);
+    }
+
+    fn new_component(cx: &mut Cx) -> Box<dyn AudioComponent> {
+        let mut player = SamplePlayer::new(cx);
+        player.apply_over(cx, live!{velocity_amount: 0.0});
+        let mut buffer = AudioBuffer::new_with_size(96000, 1);
+        buffer.data.iter_mut().for_each( | s | *s = 1.0);
+        player.set_sample(Some(AudioFile {sample_rate: 48000, buffer}));
+        Box::new(player)
+    }
+}
+
+clap_export!(TestPlayer);
+
+const RATE: f64 = 48000.0;
+
+fn gain() -> u32 {ClapParam::id_of("gain")}
+fn mode() -> u32 {ClapParam::id_of("mode")}
+
+fn bundle_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join("makepad-clap-export-test");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn bundle() -> Arc<ClapBundle> {
+    let path = bundle_dir().join("test-player.clap");
+    ClapBundle::from_entry(&path.to_string_lossy(), &clap_entry).unwrap()
+}
+
+fn new_instance() -> ClapInstance {
+    bundle().new_instance("dev.makepad.test-player").unwrap()
+}
+
+fn note(on: bool, key: u8) -> MidiData {
+    MidiData {data: [if on {0x90} else {0x80}, key, 100]}
+}
+
+fn process(processor: &mut ClapProcessor, frames: usize) -> AudioBuffer {
+    let mut output = AudioBuffer::new_with_size(frames, 2);
+    assert!(processor.process(None, &mut output));
+    output
+}
+
+#[test]
+fn describes_the_plugin() {
+    let plugins = bundle().plugins();
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].name, "Test Player");
+    assert!(plugins[0].is_instrument());
+
+    let instance = new_instance();
+    let params = instance.params();
+    assert_eq!(params.iter().map( | p | p.id).collect::<Vec<_>>(), vec![gain(), mode(), ClapParam::id_of("release")]);
+    assert_eq!((params[0].name.as_str(), params[0].min, params[0].max, params[0].default), ("Gain", 0.0, 2.0, 0.5));
+    assert!(!params[0].is_stepped() && params[0].is_automatable());
+    assert!(params[1].is_stepped());
+    assert_eq!(instance.param_value(mode()), Some(1.0));
+    assert_eq!(instance.param_text(gain(), 0.25).as_deref(), Some("0.250"));
+    assert_eq!(instance.param_text(mode(), 2.0).as_deref(), Some("Loop"));
+    assert_eq!(instance.param_text(ClapParam::id_of("release"), 0.5).as_deref(), Some("0.500 s"));
+
+    assert!(instance.audio_ports(true).is_empty());
+    let outputs = instance.audio_ports(false);
+    assert_eq!(outputs.len(), 1);
+    assert!(outputs[0].is_main && outputs[0].channel_count == 2);
+    assert!(instance.takes_notes());
+}
+
+#[test]
+fn params_map_to_live_properties() {
+    // nested paths and enum options, straight onto a component
+    let mut cx = Cx::new(Box::new( | _, _ | {}));
+    let mut eq = Equalizer::new(&mut cx);
+    let boost = ClapParam::float("low_mid.gain", -24.0, 24.0, 0.0);
+    let kind = ClapParam::choice("low_mid.kind", &["Peak", "LowShelf", "HighShelf"], 0);
+    eq.apply_over(&mut cx, &boost.live_nodes(12.0));
+    // a high shelf far below the tone boosts it all the way, a peak would not
+    eq.apply_over(&mut cx, &kind.live_nodes(2.0));
+    eq.apply_over(&mut cx, live!{low_mid: {freq: 100.0, q: 0.707}});
+
+    let mut input = AudioBuffer::new_with_size(9600, 2);
+    for c in 0..2 {
+        for (i, s) in input.channel_mut(c).iter_mut().enumerate() {
+            *s = (std::f32::consts::TAU * 1000.0 * i as f32 / RATE as f32).sin() * 0.1;
+        }
+    }
+    let out = OfflineRender::new(eq.get_graph_node(&mut cx), RATE).with_input(input.clone()).render(9600);
+    let rms = | s: &[f32] | (s.iter().map( | s | s * s).sum::<f32>() / s.len() as f32).sqrt();
+    let gain = gain_to_db(rms(&out.channel(0)[4800..]) / rms(&input.channel(0)[4800..]));
+    assert!((gain - 12.0).abs() < 0.2, "boost {}", gain);
+
+    assert_eq!(boost.text_to_value("30"), Some(24.0));
+    assert_eq!(kind.text_to_value("highshelf"), Some(2.0));
+    assert_eq!(kind.text_to_value("Bell"), None);
+}
+
+#[test]
+fn plays_notes_and_follows_automation() {
+    let mut instance = new_instance();
+    let mut processor = instance.activate(RATE, 256).unwrap();
+    processor.handle_midi_data(note(true, 60));
+    let out = process(&mut processor, 1000);
+    assert!(out.channel(0)[10..].iter().all( | s | (s - 0.5).abs() < 1e-4));
+    assert_eq!(out.channel(0), out.channel(1));
+
+    // the change arrives on the audio thread, the plugin applies it to the
+    // component on the main thread, new notes get it
+    instance.set_param(gain(), 1.0);
+    process(&mut processor, 256);
+    assert!(instance.signal().check_and_clear());
+    instance.handle_main_thread();
+    assert_eq!(instance.param_value(gain()), Some(1.0));
+    // the node picks the settings up at the start of its next block
+    process(&mut processor, 256);
+    processor.handle_midi_data(note(false, 60));
+    processor.handle_midi_data(note(true, 60));
+    let out = process(&mut processor, 1000);
+    assert!((out.channel(0)[999] - 1.0).abs() < 1e-4, "{}", out.channel(0)[999]);
+
+    // gate mode stops on the note off
+    processor.handle_midi_data(note(false, 60));
+    let out = process(&mut processor, 1000);
+    assert!(out.channel(0)[100..].iter().all( | s | *s == 0.0));
+    assert!(instance.param_events().is_empty());
+}
+
+#[test]
+fn automation_lands_on_its_frame() {
+    let mut instance = new_instance();
+    let mut processor = instance.activate(RATE, 256).unwrap();
+    // the node takes the gain on the audio thread, so the note after it in
+    // the same block already plays louder
+    processor.set_param_at(100, gain(), 1.0);
+    processor.handle_midi_data_at(200, note(true, 60));
+    let out = process(&mut processor, 256);
+    assert!(out.channel(0)[..200].iter().all( | s | *s == 0.0));
+    assert!((out.channel(0)[255] - 1.0).abs() < 1e-4, "{}", out.channel(0)[255]);
+    // and the component catches up on the main thread
+    assert!(instance.signal().check_and_clear());
+    instance.handle_main_thread();
+    assert_eq!(instance.param_value(gain()), Some(1.0));
+}
+
+#[test]
+fn state_round_trips() {
+    let mut instance = new_instance();
+    instance.set_param(gain(), 1.5);
+    instance.set_param(mode(), 2.0);
+    let state = instance.save_state().unwrap();
+
+    let mut other = new_instance();
+    assert_eq!(other.param_value(gain()), Some(0.5));
+    assert!(other.load_state(&state));
+    assert_eq!(other.param_value(gain()), Some(1.5));
+    assert_eq!(other.param_value(mode()), Some(2.0));
+    assert!(other.handle_main_thread().rescan_params);
+    // and the component follows
+    let mut processor = other.activate(RATE, 256).unwrap();
+    process(&mut processor, 256);
+    processor.handle_midi_data(note(true, 60));
+    let out = process(&mut processor, 256);
+    assert!((out.channel(0)[255] - 1.5).abs() < 1e-4);
+
+    assert!(!other.load_state(b"not a state"));
+}
+
+#[test]
+fn gui_edits_reach_the_host() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = bundle_dir();
+    let received = dir.join("received");
+    let _ = std::fs::remove_file(&received);
+    // a stand in for the makepad app, it turns the gain down and quits
+    let script = format!(
+        "#!/bin/sh\nread line\necho \"$line\" > \"{}\"\necho \"begin {id}\"\necho \"param {id} 0.25\"\necho \"end {id}\"\n",
+        received.display(),
+        id = gain()
+    );
+    let path = dir.join("test-player-gui");
+    let temp = dir.join("test-player-gui.tmp");
+    std::fs::write(&temp, script).unwrap();
+    std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::rename(&temp, &path).unwrap();
+
+    let mut instance = new_instance();
+    assert!(instance.has_gui(true));
+    assert!(!instance.has_gui(false));
+    instance.open_gui(None).unwrap();
+
+    let mut events = Vec::new();
+    let mut closed = false;
+    let start = Instant::now();
+    while !closed && start.elapsed() < Duration::from_secs(10) {
+        if instance.signal().check_and_clear() {
+            let requests = instance.handle_main_thread();
+            closed |= requests.gui_closed;
+            events.extend(instance.param_events());
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(closed);
+    assert!(!instance.is_gui_open());
+    assert_eq!(events, vec![
+        ClapParamEvent::GestureBegin {id: gain()},
+        ClapParamEvent::Value {id: gain(), value: 0.25},
+        ClapParamEvent::GestureEnd {id: gain()},
+    ]);
+    assert_eq!(instance.param_value(gain()), Some(0.25));
+    // the gui got the values when it started
+    let line = std::fs::read_to_string(&received).unwrap();
+    assert_eq!(ClapGuiMessage::parse(&line), Some(ClapGuiMessage::Value {id: gain(), value: 0.5}));
+}
@@ -0,0 +1,48 @@
+use {
+    crate::{
+        makepad_platform::*,
+        wav::decode_wav,
+        flac::decode_flac,
+        ogg_vorbis::decode_ogg_vorbis,
+    },
+    std::path::Path,
+};
+
+/// A decoded sound, whatever format the file was in
+#[derive(Clone, Debug, Default)]
+pub struct AudioFile {
+    pub sample_rate: u32,
+    pub buffer: AudioBuffer,
+}
+
+impl AudioFile {
+    pub fn frame_count(&self) -> usize {
+        self.buffer.frame_count()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.buffer.frame_count() as f64 / self.sample_rate.max(1) as f64
+    }
+}
+
+/// Decodes a wav, flac or ogg vorbis file, told apart by their contents
+pub fn decode_audio_file(data: &[u8]) -> Result<AudioFile, String> {
+    if data.starts_with(b"RIFF") {
+        let wav = decode_wav(data)?;
+        Ok(AudioFile {sample_rate: wav.sample_rate, buffer: wav.buffer})
+    }
+    else if data.starts_with(b"fLaC") {
+        decode_flac(data)
+    }
+    else if data.starts_with(b"OggS") {
+        decode_ogg_vorbis(data)
+    }
+    else {
+        Err("Unknown audio file format".into())
+    }
+}
+
+pub fn load_audio_file(path: impl AsRef<Path>) -> Result<AudioFile, String> {
+    let data = std::fs::read(path.as_ref()).map_err( | e | format!("Can't read {:?}: {}", path.as_ref(), e)) ?;
+    decode_audio_file(&data)
+}
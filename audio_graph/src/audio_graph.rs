@@ -2,6 +2,8 @@
     crate::{
         makepad_platform::*,
         audio_traits::*,
+        offline_render::OfflineRender,
+        meter::AudioMeter,
     },
     std::any::TypeId,
     std::sync::{Arc, Mutex},
@@ -25,19 +27,22 @@ pub enum AudioGraphAction<'a> {
         voice: usize,
         buffer: &'a AudioBuffer
     },
-    VoiceOff {voice: usize}
+    VoiceOff {voice: usize},
+    Meter {id: usize, meter: &'a AudioMeter}
 }
 
 #[derive(Live, LiveRegister)]
 pub struct AudioGraph {
     #[live] root: AudioComponentRef,
+    // renders at this rate and converts to the device rate, 0 runs at the device rate
+    #[live(0.0)] sample_rate: f64,
     #[rust] from_ui: FromUISender<FromUI>,
     #[rust] to_ui: ToUIReceiver<ToUIDisplayMsg>,
 }
 
 impl LiveHook for AudioGraph {
     fn after_new_from_doc(&mut self, cx: &mut Cx) {
-        Self::start_audio_output(cx, self.from_ui.receiver(), self.to_ui.sender());
+        Self::start_audio_output(cx, self.sample_rate, self.from_ui.receiver(), self.to_ui.sender());
         // we should have a component
         
         if let Some(root) = self.root.as_mut() {
@@ -79,6 +84,13 @@ pub fn send_midi_data(&self, data: MidiData) {
     pub fn all_notes_off(&self) {
         let _ =  self.from_ui.send(FromUI::AllNotesOff);
     }
+    
+    /// A separate instance of the graph to render without a device, the one
+    /// playing is left alone
+    pub fn offline_render(&mut self, cx: &mut Cx, sample_rate: f64) -> Option<OfflineRender> {
+        let root = self.root.as_mut()?;
+        Some(OfflineRender::new(root.get_graph_node(cx), sample_rate))
+    }
      
     fn render_to_output_buffer(node: &mut Node, to_ui: &ToUISender<ToUIDisplayMsg>, info: AudioInfo, output: &mut AudioBuffer) {
         
@@ -122,7 +134,7 @@ fn render_to_output_buffer(node: &mut Node, to_ui: &ToUISender<ToUIDisplayMsg>,
         }
     }
     
-    fn start_audio_output(cx: &mut Cx, from_ui: FromUIReceiver<FromUI>, to_ui: ToUISender<ToUIDisplayMsg>) {
+    fn start_audio_output(cx: &mut Cx, sample_rate: f64, from_ui: FromUIReceiver<FromUI>, to_ui: ToUISender<ToUIDisplayMsg>) {
         let mut buffers = Vec::new();
         for _ in 0..512 {
             buffers.push(AudioBuffer::new_with_size(512, 2));
@@ -136,11 +148,17 @@ fn start_audio_output(cx: &mut Cx, from_ui: FromUIReceiver<FromUI>, to_ui: ToUIS
         
         let to_ui = Arc::new(Mutex::new(to_ui));
         
-        cx.audio_output(0, move | info, output_buffer | {
+        let render = move | info, output_buffer: &mut AudioBuffer | {
             let mut state = state.lock().unwrap();
             let to_ui = to_ui.lock().unwrap();
             Self::render_to_output_buffer(&mut state, &to_ui, info, output_buffer);
-        });
+        };
+        if sample_rate > 0.0 {
+            cx.audio_output_at_rate(0, sample_rate, ResampleQuality::default(), render);
+        }
+        else {
+            cx.audio_output(0, render);
+        }
     }
     
     pub fn handle_event_with(
@@ -164,6 +182,9 @@ pub fn handle_event_with(
                     //log!("GOT DISPLAY AUDIO");
                     dispatch_action(cx, AudioGraphAction::VoiceOff {voice});
                 },
+                ToUIDisplayMsg::Meter {id, meter} => {
+                    dispatch_action(cx, AudioGraphAction::Meter {id, meter: &meter});
+                },
                 ToUIDisplayMsg::OutOfBuffers => { // inject some new buffers
                 }
             }
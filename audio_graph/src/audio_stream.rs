@@ -2,6 +2,7 @@
 use {
     crate::{
         makepad_platform::audio::*,
+        makepad_platform::audio_resample::*,
     },
     std::sync::{Arc, Mutex},
     std::sync::mpsc::{
@@ -14,7 +15,7 @@
 
 #[derive(Clone)]
 pub struct AudioStreamSender {
-    stream_send: Sender<(u64, AudioBuffer)>,
+    stream_send: Sender<(u64, AudioBuffer, f64)>,
 }
 unsafe impl Send for AudioStreamSender {}
 
@@ -23,7 +24,10 @@ unsafe impl Send for AudioStreamSender {}
 
 pub struct ReceiverInner {
     pub routes: Vec<AudioRoute>,
-    stream_recv: Receiver<(u64, AudioBuffer)>,
+    // buffers sent at another rate are converted to this one, 0 leaves them as is
+    sample_rate: f64,
+    quality: ResampleQuality,
+    stream_recv: Receiver<(u64, AudioBuffer, f64)>,
 }
 
 unsafe impl Send for AudioStreamReceiver {}
@@ -31,22 +35,61 @@ unsafe impl Send for AudioStreamReceiver {}
 pub struct AudioRoute {
     id: u64,
     start_offset: usize,
-    buffers: Vec<AudioBuffer>
+    buffers: Vec<AudioBuffer>,
+    resampler: Option<Resampler>,
 }
 
 impl AudioStreamSender {
     pub fn create_pair() -> (AudioStreamSender, AudioStreamReceiver) {
-        let (stream_send, stream_recv) = channel::<(u64, AudioBuffer)>();
+        let (stream_send, stream_recv) = channel::<(u64, AudioBuffer, f64)>();
         (AudioStreamSender {
             stream_send,
         }, AudioStreamReceiver(Arc::new(Mutex::new(ReceiverInner {
             stream_recv,
+            sample_rate: 0.0,
+            quality: ResampleQuality::default(),
             routes: Vec::new()
         }))))
     }
     
     pub fn write_buffer(&self, route_id: u64, buffer: AudioBuffer) -> Result<(), SendError<(u64, AudioBuffer) >> {
-        self.stream_send.send((route_id, buffer))
+        self.write_buffer_at_rate(route_id, buffer, 0.0)
+    }
+    
+    /// Sends a buffer recorded or rendered at `sample_rate`, the receiver
+    /// converts it if it reads at another rate
+    pub fn write_buffer_at_rate(&self, route_id: u64, buffer: AudioBuffer, sample_rate: f64) -> Result<(), SendError<(u64, AudioBuffer) >> {
+        self.stream_send.send((route_id, buffer, sample_rate)).map_err( | SendError((id, buffer, _)) | SendError((id, buffer)))
+    }
+}
+
+impl ReceiverInner {
+    fn push_buffer(&mut self, route_id: u64, buf: AudioBuffer, buf_rate: f64) {
+        let route = if let Some(index) = self.routes.iter().position( | v | v.id == route_id) {
+            &mut self.routes[index]
+        }
+        else {
+            self.routes.push(AudioRoute {
+                id: route_id,
+                buffers: Vec::new(),
+                start_offset: 0,
+                resampler: None,
+            });
+            self.routes.last_mut().unwrap()
+        };
+        if self.sample_rate <= 0.0 || buf_rate <= 0.0 || buf_rate == self.sample_rate {
+            route.resampler = None;
+            route.buffers.push(buf);
+            return
+        }
+        let resampler = match &mut route.resampler {
+            Some(r) if r.from_rate() == buf_rate && r.to_rate() == self.sample_rate && r.channel_count() == buf.channel_count() => r,
+            _ => route.resampler.insert(Resampler::new(buf_rate, self.sample_rate, buf.channel_count(), self.quality))
+        };
+        let out = resampler.process(&buf);
+        if out.frame_count() > 0 {
+            route.buffers.push(out);
+        }
     }
 }
 
@@ -61,36 +104,32 @@ pub fn route_id(&self, route_num: usize) -> u64 {
         iself.routes[route_num].id
     }
 
+    /// The rate `read_buffer` delivers at. Routes written with
+    /// `write_buffer_at_rate` at another rate are resampled on arrival
+    pub fn set_sample_rate(&mut self, sample_rate: f64, quality: ResampleQuality) {
+        let mut iself = self.0.lock().unwrap();
+        iself.sample_rate = sample_rate;
+        iself.quality = quality;
+    }
+    
+    /// The delay the resampler of a route adds, in seconds
+    pub fn route_latency(&self, route_num: usize) -> f64 {
+        let iself = self.0.lock().unwrap();
+        iself.routes.get(route_num).and_then( | r | r.resampler.as_ref()).map_or(0.0, | r | r.latency())
+    }
+
     pub fn try_recv_stream(&mut self) {
         let mut iself = self.0.lock().unwrap();
-        while let Ok((route_id, buf)) = iself.stream_recv.try_recv() {
-            if let Some(route) = iself.routes.iter_mut().find( | v | v.id == route_id) {
-                route.buffers.push(buf);
-            }
-            else {
-                iself.routes.push(AudioRoute {
-                    id: route_id,
-                    buffers: vec![buf],
-                    start_offset: 0
-                });
-            }
+        while let Ok((route_id, buf, buf_rate)) = iself.stream_recv.try_recv() {
+            iself.push_buffer(route_id, buf, buf_rate);
         }
     }
     
     pub fn recv_stream(&mut self) {
         {
             let mut iself = self.0.lock().unwrap();
-            if let Ok((route_id, buf)) = iself.stream_recv.recv() {
-                if let Some(route) = iself.routes.iter_mut().find( | v | v.id == route_id) {
-                    route.buffers.push(buf);
-                }
-                else {
-                    iself.routes.push(AudioRoute {
-                        id: route_id,
-                        buffers: vec![buf],
-                        start_offset: 0
-                    });
-                }
+            if let Ok((route_id, buf, buf_rate)) = iself.stream_recv.recv() {
+                iself.push_buffer(route_id, buf, buf_rate);
             }
         }
         self.try_recv_stream();
@@ -2,6 +2,7 @@
     std::collections::BTreeMap,
     crate::{
         makepad_platform::*,
+        meter::AudioMeter,
     }
 };
 
@@ -30,6 +31,13 @@ fn render_to_audio_buffer(
         inputs: &[&AudioBuffer],
         display: &mut DisplayAudioGraph
     );
+    /// Sets the live property at `path` of the component from the audio
+    /// thread, for plugin hosts that automate parameters within a block.
+    /// Returns false when the node can't, the value then only reaches it
+    /// through the component
+    fn set_param(&mut self, _path: &[LiveId], _value: &LiveValue) -> bool {
+        false
+    }
 }
 
 generate_any_trait_api!(AudioComponent);
@@ -41,6 +49,7 @@ fn render_to_audio_buffer(
 pub enum ToUIDisplayMsg{
     DisplayAudio{voice: usize, buffer:AudioBuffer, active:bool},
     VoiceOff{voice: usize},
+    Meter{id: usize, meter: AudioMeter},
     OutOfBuffers
 }
 
@@ -68,6 +77,10 @@ pub fn send_buffer(&self, active:bool, voice: usize, buffer:AudioBuffer){
     pub fn send_voice_off(&self, voice: usize){
         self.to_ui.send(ToUIDisplayMsg::VoiceOff{voice}).unwrap();
     }
+    
+    pub fn send_meter(&self, id: usize, meter: AudioMeter){
+        self.to_ui.send(ToUIDisplayMsg::Meter{id, meter}).unwrap();
+    }
 }
 
 
@@ -0,0 +1,176 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::*,
+    },
+};
+
+live_design!{
+    pub Chorus = {{Chorus}} {
+    }
+}
+
+const MAX_VOICES: usize = 4;
+// the longest the base delay plus the depth can get, in seconds
+const MAX_DELAY: f32 = 0.06;
+
+#[derive(Copy, Clone, Debug)]
+struct ChorusSettings {
+    rate: f32,
+    depth: f32,
+    delay: f32,
+    voices: usize,
+    feedback: f32,
+    mix: f32,
+}
+
+enum FromUI {
+    Settings(ChorusSettings),
+}
+
+/// A chorus with up to four modulated delay voices per channel. `rate` is in
+/// Hz, `delay` and `depth` in seconds. The voices of the right channel run a
+/// quarter cycle behind the left ones so the sound widens
+#[derive(Live)]
+pub struct Chorus {
+    #[live(0.8)] rate: f64,
+    #[live(0.003)] depth: f64,
+    #[live(0.012)] delay: f64,
+    #[live(2)] voices: i64,
+    #[live(0.0)] feedback: f64,
+    #[live(0.5)] mix: f64,
+    #[rust] from_ui: FromUISender<FromUI>,
+}
+
+impl LiveRegister for Chorus {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Chorus)
+    }
+}
+
+impl LiveHook for Chorus {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl Chorus {
+    fn settings(&self) -> ChorusSettings {
+        let delay = self.delay.clamp(0.001, MAX_DELAY as f64 / 2.0);
+        ChorusSettings {
+            rate: self.rate.max(0.0) as f32,
+            depth: self.depth.clamp(0.0, delay) as f32,
+            delay: delay as f32,
+            voices: self.voices.clamp(1, MAX_VOICES as i64) as usize,
+            feedback: self.feedback.clamp(-0.95, 0.95) as f32,
+            mix: self.mix.clamp(0.0, 1.0) as f32,
+        }
+    }
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    settings: ChorusSettings,
+    lines: Vec<DelayLine>,
+    // the wet signal of the last frame per channel, for the feedback
+    last: Vec<f32>,
+    phase: f32,
+    depth: Smoothed,
+    delay: Smoothed,
+    feedback: Smoothed,
+    mix: Smoothed,
+    sample_rate: f32,
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(FromUI::Settings(settings)) = self.from_ui.try_recv() {
+            self.settings = settings;
+            self.depth.set_target(settings.depth);
+            self.delay.set_target(settings.delay);
+            self.feedback.set_target(settings.feedback);
+            self.mix.set_target(settings.mix);
+        }
+        let sample_rate = (info.sample_rate as f32).min(MAX_SAMPLE_RATE);
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.lines.iter_mut().for_each( | l | l.clear());
+            for smoothed in [&mut self.depth, &mut self.delay, &mut self.feedback, &mut self.mix] {
+                smoothed.set_time(0.05, sample_rate);
+            }
+        }
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count();
+        if self.lines.len() < channel_count {
+            // only when the channel count grows, normally never
+            self.lines.resize(channel_count, DelayLine::new((MAX_DELAY * MAX_SAMPLE_RATE) as usize));
+            self.last.resize(channel_count, 0.0);
+        }
+        let voices = self.settings.voices;
+        let step = self.settings.rate / sample_rate;
+        let max_frames = self.lines[0].max_delay() as f32;
+        for i in 0..output.frame_count() {
+            let depth = self.depth.tick() * sample_rate;
+            let delay = self.delay.tick() * sample_rate;
+            let feedback = self.feedback.tick();
+            let mix = self.mix.tick();
+            self.phase = (self.phase + step).fract();
+            for c in 0..channel_count {
+                let line = &mut self.lines[c];
+                let dry = output.channel(c)[i];
+                line.push(dry + self.last[c] * feedback);
+                let mut wet = 0.0;
+                for v in 0..voices {
+                    let phase = self.phase + v as f32 / voices as f32 + c as f32 * 0.25;
+                    let lfo = (phase * std::f32::consts::TAU).sin();
+                    wet += line.tap_frac((delay + lfo * depth).clamp(1.0, max_frames));
+                }
+                wet /= voices as f32;
+                self.last[c] = wet;
+                output.channel_mut(c)[i] = dry * (1.0 - mix) + wet * mix;
+            }
+        }
+    }
+}
+
+impl AudioComponent for Chorus {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            settings,
+            lines: vec![DelayLine::new((MAX_DELAY * MAX_SAMPLE_RATE) as usize); 2],
+            last: vec![0.0; 2],
+            phase: 0.0,
+            depth: Smoothed::new(settings.depth),
+            delay: Smoothed::new(settings.delay),
+            feedback: Smoothed::new(settings.feedback),
+            mix: Smoothed::new(settings.mix),
+            sample_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
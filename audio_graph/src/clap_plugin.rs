@@ -0,0 +1,238 @@
+use {
+    crate::{
+        makepad_platform::os::linux::clap::*,
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::copy_input,
+    },
+};
+
+live_design!{
+    pub ClapPlugin = {{ClapPlugin}} {
+    }
+}
+
+enum ToUI {
+    // the node runs at this rate and has no processor for it
+    Activate(f64),
+}
+
+enum FromUI {
+    NewProcessor(Box<ClapProcessor>),
+    // the plugin wants to be activated again, drop the processor
+    Restart,
+}
+
+/// Hosts a CLAP plugin, an instrument or an effect. `plugin` is its id or
+/// name, it is looked up in the installed plugins unless `path` points at the
+/// `.clap` file. The plugin gets the midi and the input of the node, and
+/// `show_gui` opens its own gui in a window
+#[derive(Live)]
+pub struct ClapPlugin {
+    #[live] path: String,
+    #[live] plugin: String,
+    #[live] show_gui: bool,
+    #[rust] loaded: Option<(String, String)>,
+    #[rust] instance: Option<ClapInstance>,
+    // the rate the node asked for that still has to be activated
+    #[rust] pending_rate: Option<f64>,
+    #[rust] from_ui: FromUISender<FromUI>,
+    #[rust] to_ui: ToUIReceiver<ToUI>,
+}
+
+impl LiveRegister for ClapPlugin {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, ClapPlugin)
+    }
+}
+
+impl LiveHook for ClapPlugin {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let wanted = (self.path.clone(), self.plugin.clone());
+        if !self.plugin.is_empty() && self.loaded.as_ref() != Some(&wanted) {
+            self.loaded = Some(wanted);
+            self.load();
+        }
+        self.update_gui();
+    }
+}
+
+impl ClapPlugin {
+    fn load(&mut self) {
+        let _ = self.from_ui.send(FromUI::Restart);
+        self.instance = None;
+        let path = if self.path.is_empty() {
+            let plugins = ClapBundle::scan();
+            match plugins.iter().find( | p | p.id == self.plugin || p.name == self.plugin) {
+                Some(info) => info.path.clone(),
+                None => {
+                    error!("Cannot find CLAP plugin {}", self.plugin);
+                    for info in &plugins {
+                        error!("CLAP plugins: {} ({})", info.name, info.id);
+                    }
+                    return
+                }
+            }
+        }
+        else {
+            self.path.clone()
+        };
+        match ClapBundle::load(&path).and_then( | bundle | bundle.new_instance(&self.plugin)) {
+            Ok(instance) => self.instance = Some(instance),
+            Err(err) => error!("Cannot load CLAP plugin {} from {}: {:?}", self.plugin, path, err)
+        }
+    }
+
+    fn activate(&mut self) {
+        let (Some(instance), Some(sample_rate)) = (&mut self.instance, self.pending_rate) else {return};
+        match instance.activate(sample_rate, 4096) {
+            Ok(processor) => {
+                self.pending_rate = None;
+                let _ = self.from_ui.send(FromUI::NewProcessor(Box::new(processor)));
+            }
+            // the old processor is on its way out, try again on the next event
+            Err(ClapError::StillProcessing) => (),
+            Err(err) => {
+                self.pending_rate = None;
+                error!("Cannot activate CLAP plugin {}: {:?}", self.plugin, err);
+            }
+        }
+    }
+
+    fn update_gui(&mut self) {
+        let Some(instance) = &mut self.instance else {return};
+        if self.show_gui && !instance.is_gui_open() {
+            if let Err(err) = instance.open_gui(None) {
+                error!("Cannot open the gui of CLAP plugin {}: {:?}", self.plugin, err);
+                self.show_gui = false;
+            }
+        }
+        else if !self.show_gui && instance.is_gui_open() {
+            instance.close_gui();
+        }
+    }
+
+    /// The running plugin, for its audio ports, gui and the like
+    pub fn instance(&mut self) -> Option<&mut ClapInstance> {
+        self.instance.as_mut()
+    }
+
+    pub fn params(&self) -> Vec<ClapParamInfo> {
+        self.instance.as_ref().map(ClapInstance::params).unwrap_or_default()
+    }
+
+    pub fn param_value(&self, id: u32) -> Option<f64> {
+        self.instance.as_ref()?.param_value(id)
+    }
+
+    /// Automates a parameter, it changes at the start of the next block
+    pub fn set_param(&mut self, id: u32, value: f64) {
+        if let Some(instance) = &mut self.instance {
+            instance.set_param(id, value);
+        }
+    }
+
+    /// The changes the plugin made to its parameters itself since the last call
+    pub fn param_events(&mut self) -> Vec<ClapParamEvent> {
+        self.instance.as_mut().map(ClapInstance::param_events).unwrap_or_default()
+    }
+
+    /// Puts the gui of the plugin inside an X11 window instead of its own
+    pub fn embed_gui(&mut self, x11_window: u64) -> Result<(u32, u32), ClapError> {
+        let instance = self.instance.as_mut().ok_or(ClapError::NoGui) ?;
+        instance.open_gui(Some(x11_window))
+    }
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    to_ui: ToUISender<ToUI>,
+    processor: Option<Box<ClapProcessor>>,
+    requested_rate: f64,
+}
+
+impl AudioGraphNode for Node {
+    fn handle_midi_data(&mut self, data: MidiData) {
+        if let Some(processor) = &mut self.processor {
+            processor.handle_midi_data(data);
+        }
+    }
+
+    fn all_notes_off(&mut self) {
+        if let Some(processor) = &mut self.processor {
+            processor.all_notes_off();
+        }
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(msg) = self.from_ui.try_recv() {
+            match msg {
+                FromUI::NewProcessor(processor) => self.processor = Some(processor),
+                FromUI::Restart => {
+                    self.processor = None;
+                    self.requested_rate = 0.0;
+                }
+            }
+        }
+        if self.processor.as_ref().is_some_and( | p | p.sample_rate() != info.sample_rate) {
+            // stops the processing, the ui activates the plugin at the new rate
+            self.processor = None;
+        }
+        match &mut self.processor {
+            Some(processor) => {
+                processor.process(inputs.first().copied(), outputs[0]);
+            }
+            None => {
+                if self.requested_rate != info.sample_rate {
+                    self.requested_rate = info.sample_rate;
+                    let _ = self.to_ui.send(ToUI::Activate(info.sample_rate));
+                }
+                copy_input(inputs, outputs[0]);
+            }
+        }
+    }
+}
+
+impl AudioComponent for ClapPlugin {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            to_ui: self.to_ui.sender(),
+            processor: None,
+            requested_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+        while let Ok(ToUI::Activate(sample_rate)) = self.to_ui.try_recv() {
+            self.pending_rate = Some(sample_rate);
+        }
+        self.activate();
+        let Some(instance) = &mut self.instance else {return};
+        if !instance.signal().check_and_clear() {
+            return
+        }
+        let requests = instance.handle_main_thread();
+        if let Some((width, height)) = requests.gui_resize {
+            instance.set_gui_size(width, height);
+        }
+        if requests.gui_closed {
+            self.show_gui = false;
+        }
+        if requests.restart {
+            let _ = self.from_ui.send(FromUI::Restart);
+        }
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
@@ -0,0 +1,258 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::*,
+    },
+};
+
+live_design!{
+    pub Delay = {{Delay}} {
+    }
+}
+
+const MAX_DELAY: f32 = 4.0;
+// midi clock runs at 24 pulses per beat
+const CLOCK_PULSES: usize = 24;
+
+#[derive(Copy, Clone, Debug)]
+struct DelaySettings {
+    sync: bool,
+    beats: f32,
+    time: f32,
+    bpm: f32,
+    follow_clock: bool,
+    feedback: f32,
+    ping_pong: bool,
+    high_cut: f32,
+    mix: f32,
+}
+
+enum FromUI {
+    Settings(DelaySettings),
+}
+
+/// An echo. With `sync` the time is `beats` long at the tempo, which comes
+/// from midi clock when there is one and `follow_clock` is on, and from
+/// `bpm` otherwise. Without `sync` it is `time` seconds. Ping pong bounces
+/// the echoes between the left and right channel
+#[derive(Live)]
+pub struct Delay {
+    #[live(true)] sync: bool,
+    #[live(0.75)] beats: f64,
+    #[live(0.3)] time: f64,
+    #[live(120.0)] bpm: f64,
+    #[live(true)] follow_clock: bool,
+    #[live(0.4)] feedback: f64,
+    #[live(false)] ping_pong: bool,
+    // the echoes get darker by a lowpass at this frequency
+    #[live(6000.0)] high_cut: f64,
+    #[live(0.3)] mix: f64,
+    #[rust] from_ui: FromUISender<FromUI>,
+}
+
+impl LiveRegister for Delay {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Delay)
+    }
+}
+
+impl LiveHook for Delay {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl Delay {
+    fn settings(&self) -> DelaySettings {
+        DelaySettings {
+            sync: self.sync,
+            beats: self.beats.max(0.0) as f32,
+            time: self.time.max(0.0) as f32,
+            bpm: self.bpm.max(1.0) as f32,
+            follow_clock: self.follow_clock,
+            feedback: self.feedback.clamp(0.0, 0.99) as f32,
+            ping_pong: self.ping_pong,
+            high_cut: self.high_cut.max(20.0) as f32,
+            mix: self.mix.clamp(0.0, 1.0) as f32,
+        }
+    }
+}
+
+/// Works out the tempo from the spacing of midi clock pulses, averaged over
+/// a beat so the jitter of the midi timing evens out
+#[derive(Default)]
+pub struct ClockTempo {
+    last_pulse: Option<u64>,
+    intervals: Vec<u64>,
+    next: usize,
+}
+
+impl ClockTempo {
+    /// A clock pulse arrived at `frame`
+    pub fn pulse(&mut self, frame: u64, sample_rate: f32) {
+        if let Some(last) = self.last_pulse {
+            let interval = frame.saturating_sub(last);
+            // a pulse a second late means the clock stopped in between
+            if interval as f32 > sample_rate {
+                self.reset();
+            }
+            else if self.intervals.len() < CLOCK_PULSES {
+                self.intervals.push(interval);
+            }
+            else {
+                self.intervals[self.next] = interval;
+                self.next = (self.next + 1) % CLOCK_PULSES;
+            }
+        }
+        self.last_pulse = Some(frame);
+    }
+
+    pub fn reset(&mut self) {
+        self.last_pulse = None;
+        self.intervals.clear();
+        self.next = 0;
+    }
+
+    /// The tempo once a beat of pulses came in, as long as they keep coming
+    pub fn bpm(&self, frame: u64, sample_rate: f32) -> Option<f32> {
+        let last = self.last_pulse?;
+        if self.intervals.len() < CLOCK_PULSES || (frame.saturating_sub(last)) as f32 > sample_rate {
+            return None
+        }
+        let beat: u64 = self.intervals.iter().sum();
+        if beat == 0 {
+            return None
+        }
+        Some(60.0 * sample_rate / beat as f32)
+    }
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    settings: DelaySettings,
+    lines: [DelayLine; 2],
+    filters: [OnePole; 2],
+    delay_frames: Smoothed,
+    feedback: Smoothed,
+    mix: Smoothed,
+    clock: ClockTempo,
+    frame: u64,
+    sample_rate: f32,
+}
+
+impl Node {
+    fn delay_seconds(&self) -> f32 {
+        if !self.settings.sync {
+            return self.settings.time
+        }
+        let clock = if self.settings.follow_clock {self.clock.bpm(self.frame, self.sample_rate)} else {None};
+        self.settings.beats * 60.0 / clock.unwrap_or(self.settings.bpm)
+    }
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, data: MidiData) {
+        match data.data[0] {
+            0xf8 => self.clock.pulse(self.frame, self.sample_rate),
+            // start and stop
+            0xfa | 0xfc => self.clock.reset(),
+            _ => ()
+        }
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(FromUI::Settings(settings)) = self.from_ui.try_recv() {
+            self.settings = settings;
+            self.feedback.set_target(settings.feedback);
+            self.mix.set_target(settings.mix);
+        }
+        let sample_rate = (info.sample_rate as f32).min(MAX_SAMPLE_RATE);
+        let first = self.sample_rate == 0.0;
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.lines.iter_mut().for_each( | l | l.clear());
+            // time changes glide, like a tape machine
+            self.delay_frames.set_time(0.1, sample_rate);
+            self.feedback.set_time(0.02, sample_rate);
+            self.mix.set_time(0.02, sample_rate);
+        }
+        let max_frames = self.lines[0].max_delay() as f32 - 1.0;
+        let delay_frames = (self.delay_seconds() * sample_rate).clamp(1.0, max_frames);
+        if first {
+            self.delay_frames.jump(delay_frames);
+        }
+        else {
+            self.delay_frames.set_target(delay_frames);
+        }
+        let tone = one_pole_coef(self.settings.high_cut, sample_rate);
+        let ping_pong = self.settings.ping_pong;
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count().min(2);
+        for i in 0..output.frame_count() {
+            let delay = self.delay_frames.tick();
+            let feedback = self.feedback.tick();
+            let mix = self.mix.tick();
+            let echoes = [self.lines[0].tap_frac(delay - 1.0), self.lines[1].tap_frac(delay - 1.0)];
+            if ping_pong && channel_count == 2 {
+                // the input goes in on the left, every echo swaps sides
+                let input = (output.channel(0)[i] + output.channel(1)[i]) * 0.5;
+                let left = self.filters[0].lowpass(input + echoes[1] * feedback, tone);
+                let right = self.filters[1].lowpass(echoes[0] * feedback, tone);
+                self.lines[0].push(left);
+                self.lines[1].push(right);
+            }
+            else {
+                for (c, echo) in echoes.iter().enumerate().take(channel_count) {
+                    let input = output.channel(c)[i];
+                    let v = self.filters[c].lowpass(input + echo * feedback, tone);
+                    self.lines[c].push(v);
+                }
+            }
+            for c in 0..output.channel_count() {
+                let s = &mut output.channel_mut(c)[i];
+                *s = *s * (1.0 - mix) + echoes[c.min(1)] * mix;
+            }
+        }
+        self.frame += output.frame_count() as u64;
+    }
+}
+
+impl AudioComponent for Delay {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        let line = DelayLine::new((MAX_DELAY * MAX_SAMPLE_RATE) as usize);
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            settings,
+            lines: [line.clone(), line],
+            filters: Default::default(),
+            delay_frames: Smoothed::new(0.0),
+            feedback: Smoothed::new(settings.feedback),
+            mix: Smoothed::new(settings.mix),
+            clock: ClockTempo::default(),
+            frame: 0,
+            sample_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
@@ -0,0 +1,254 @@
+// The building blocks the effect components are made of
+
+use crate::makepad_platform::*;
+
+/// Delay lines are allocated for this rate up front so nothing allocates on
+/// the audio thread when the device rate shows up
+pub const MAX_SAMPLE_RATE: f32 = 192000.0;
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-9).log10()
+}
+
+/// Moves toward a target a bit every step so parameter changes from the ui
+/// don't zipper or click
+#[derive(Clone, Copy, Debug)]
+pub struct Smoothed {
+    value: f32,
+    target: f32,
+    coef: f32,
+}
+
+impl Smoothed {
+    pub fn new(value: f32) -> Self {
+        Self {value, target: value, coef: 1.0}
+    }
+
+    /// `seconds` is the time constant, `rate` how often `next` gets called
+    pub fn set_time(&mut self, seconds: f32, rate: f32) {
+        self.coef = 1.0 - (-1.0 / (seconds * rate).max(1.0)).exp();
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    /// Goes to `value` right away
+    pub fn jump(&mut self, value: f32) {
+        self.value = value;
+        self.target = value;
+    }
+
+    pub fn tick(&mut self) -> f32 {
+        self.value += (self.target - self.value) * self.coef;
+        if (self.target - self.value).abs() <= 1e-6 * self.target.abs().max(1.0) {
+            self.value = self.target;
+        }
+        self.value
+    }
+
+    pub fn value(&self) -> f32 {self.value}
+    pub fn target(&self) -> f32 {self.target}
+    pub fn is_settled(&self) -> bool {self.value == self.target}
+}
+
+/// The filter shapes a biquad can take
+#[derive(Copy, Clone, Debug, PartialEq, Live, LiveHook)]
+#[live_ignore]
+pub enum BiquadKind {
+    #[pick] Peak,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+/// Biquad coefficients after the audio eq cookbook, normalized on a0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiquadCoefs {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl Default for BiquadCoefs {
+    fn default() -> Self {
+        Self {b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0}
+    }
+}
+
+impl BiquadCoefs {
+    /// `gain` in dB only matters for the peak and shelf kinds, for shelves
+    /// `q` sets the slope
+    pub fn new(kind: BiquadKind, freq: f32, q: f32, gain: f32, sample_rate: f32) -> Self {
+        let freq = (freq as f64).clamp(1.0, sample_rate as f64 * 0.49);
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * (q as f64).max(0.01));
+        let a = 10f64.powf(gain as f64 / 40.0);
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::Peak => (1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a),
+            BiquadKind::LowShelf => {
+                let sq = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + sq),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - sq),
+                    (a + 1.0) + (a - 1.0) * cos + sq,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - sq,
+                )
+            }
+            BiquadKind::HighShelf => {
+                let sq = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + sq),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - sq),
+                    (a + 1.0) - (a - 1.0) * cos + sq,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - sq,
+                )
+            }
+        };
+        Self {
+            b0: (b0 / a0) as f32,
+            b1: (b1 / a0) as f32,
+            b2: (b2 / a0) as f32,
+            a1: (a1 / a0) as f32,
+            a2: (a2 / a0) as f32,
+        }
+    }
+
+    /// The magnitude response at `freq`, for drawing curves
+    pub fn response(&self, freq: f32, sample_rate: f32) -> f32 {
+        let w = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        let (s1, c1) = w.sin_cos();
+        let (s2, c2) = (2.0 * w).sin_cos();
+        let (b0, b1, b2, a1, a2) = (self.b0 as f64, self.b1 as f64, self.b2 as f64, self.a1 as f64, self.a2 as f64);
+        let num_re = b0 + b1 * c1 + b2 * c2;
+        let num_im = -(b1 * s1 + b2 * s2);
+        let den_re = 1.0 + a1 * c1 + a2 * c2;
+        let den_im = -(a1 * s1 + a2 * s2);
+        ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im)).sqrt() as f32
+    }
+}
+
+/// The memory of one biquad on one channel, transposed direct form 2
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadState {
+    pub fn process(&mut self, c: &BiquadCoefs, x: f32) -> f32 {
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// A one pole lowpass, `coef` comes from `one_pole_coef`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OnePole {
+    z: f32,
+}
+
+impl OnePole {
+    pub fn lowpass(&mut self, x: f32, coef: f32) -> f32 {
+        self.z += (x - self.z) * coef;
+        self.z
+    }
+
+    pub fn reset(&mut self) {
+        self.z = 0.0;
+    }
+}
+
+pub fn one_pole_coef(freq: f32, sample_rate: f32) -> f32 {
+    1.0 - (-2.0 * std::f32::consts::PI * freq.min(sample_rate * 0.49) / sample_rate).exp()
+}
+
+/// A ring buffer that can be read back at any delay up to the size it was
+/// made with, fractional delays are interpolated linearly
+#[derive(Clone, Debug, Default)]
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    mask: usize,
+    // where the last pushed sample is
+    pos: usize,
+}
+
+impl DelayLine {
+    pub fn new(max_delay: usize) -> Self {
+        let size = (max_delay + 2).next_power_of_two();
+        Self {
+            buffer: vec![0.0; size],
+            mask: size - 1,
+            pos: 0,
+        }
+    }
+
+    /// The longest delay `tap` can read
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len() - 2
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each( | s | *s = 0.0);
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.pos = (self.pos + 1) & self.mask;
+        self.buffer[self.pos] = value;
+    }
+
+    /// The sample pushed `delay` pushes ago, 0 is the last one
+    pub fn tap(&self, delay: usize) -> f32 {
+        self.buffer[self.pos.wrapping_sub(delay) & self.mask]
+    }
+
+    pub fn tap_frac(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(0.0, self.max_delay() as f32);
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let a = self.tap(whole);
+        let b = self.tap(whole + 1);
+        a + (b - a) * frac
+    }
+}
+
+/// Copies the input of an effect to its output so it can be processed in
+/// place. A mono input is spread over all outputs, no input is silence
+pub fn copy_input(inputs: &[&AudioBuffer], output: &mut AudioBuffer) {
+    match inputs.first() {
+        Some(input) if input.channel_count() > 0 => {
+            let frames = output.frame_count().min(input.frame_count());
+            for c in 0..output.channel_count() {
+                let from = input.channel(c.min(input.channel_count() - 1));
+                let to = output.channel_mut(c);
+                to[..frames].copy_from_slice(&from[..frames]);
+                to[frames..].iter_mut().for_each( | s | *s = 0.0);
+            }
+        }
+        _ => output.zero()
+    }
+}
@@ -0,0 +1,357 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::*,
+    },
+    std::collections::VecDeque,
+};
+
+live_design!{
+    pub Compressor = {{Compressor}} {
+    }
+    pub Limiter = {{Limiter}} {
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct CompressorSettings {
+    threshold: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    knee: f32,
+    makeup: f32,
+    mix: f32,
+}
+
+enum CompressorFromUI {
+    Settings(CompressorSettings),
+}
+
+/// A feed forward compressor with a soft knee. The channels are detected
+/// together so the stereo image stays put. Levels are in dB, times in seconds
+#[derive(Live)]
+pub struct Compressor {
+    #[live(-18.0)] threshold: f64,
+    #[live(4.0)] ratio: f64,
+    #[live(0.01)] attack: f64,
+    #[live(0.15)] release: f64,
+    #[live(6.0)] knee: f64,
+    #[live(0.0)] makeup: f64,
+    // blends in the dry signal for parallel compression
+    #[live(1.0)] mix: f64,
+    #[rust] from_ui: FromUISender<CompressorFromUI>,
+}
+
+impl LiveRegister for Compressor {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Compressor)
+    }
+}
+
+impl LiveHook for Compressor {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(CompressorFromUI::Settings(self.settings()));
+    }
+}
+
+impl Compressor {
+    fn settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            threshold: self.threshold as f32,
+            ratio: self.ratio.max(1.0) as f32,
+            attack: self.attack.max(0.0) as f32,
+            release: self.release.max(0.0) as f32,
+            knee: self.knee.max(0.0) as f32,
+            makeup: self.makeup as f32,
+            mix: self.mix.clamp(0.0, 1.0) as f32,
+        }
+    }
+}
+
+/// The static curve of the compressor, how many dB the level `input` gets
+/// turned down by
+pub fn compressor_gain_reduction(input: f32, threshold: f32, ratio: f32, knee: f32) -> f32 {
+    let over = input - threshold;
+    let output = if 2.0 * over < -knee {
+        input
+    }
+    else if 2.0 * over.abs() <= knee && knee > 0.0 {
+        input + (1.0 / ratio - 1.0) * (over + knee / 2.0).powi(2) / (2.0 * knee)
+    }
+    else {
+        threshold + over / ratio
+    };
+    output - input
+}
+
+struct CompressorNode {
+    from_ui: FromUIReceiver<CompressorFromUI>,
+    settings: CompressorSettings,
+    threshold: Smoothed,
+    ratio: Smoothed,
+    makeup: Smoothed,
+    mix: Smoothed,
+    // the smoothed gain reduction in dB
+    envelope: f32,
+    sample_rate: f32,
+}
+
+impl AudioGraphNode for CompressorNode {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(CompressorFromUI::Settings(settings)) = self.from_ui.try_recv() {
+            self.settings = settings;
+            self.threshold.set_target(settings.threshold);
+            self.ratio.set_target(settings.ratio);
+            self.makeup.set_target(db_to_gain(settings.makeup));
+            self.mix.set_target(settings.mix);
+        }
+        let sample_rate = info.sample_rate as f32;
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            for smoothed in [&mut self.threshold, &mut self.ratio, &mut self.makeup, &mut self.mix] {
+                smoothed.set_time(0.02, sample_rate);
+            }
+        }
+        let attack = (-1.0 / (self.settings.attack * sample_rate).max(1.0)).exp();
+        let release = (-1.0 / (self.settings.release * sample_rate).max(1.0)).exp();
+        let knee = self.settings.knee;
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count();
+        for i in 0..output.frame_count() {
+            let mut peak = 0.0f32;
+            for c in 0..channel_count {
+                peak = peak.max(output.channel(c)[i].abs());
+            }
+            let reduction = compressor_gain_reduction(gain_to_db(peak), self.threshold.tick(), self.ratio.tick(), knee);
+            let coef = if reduction < self.envelope {attack} else {release};
+            self.envelope = reduction + (self.envelope - reduction) * coef;
+            let mix = self.mix.tick();
+            let gain = db_to_gain(self.envelope) * self.makeup.tick() * mix + (1.0 - mix);
+            for c in 0..channel_count {
+                output.channel_mut(c)[i] *= gain;
+            }
+        }
+    }
+}
+
+impl AudioComponent for Compressor {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        Box::new(CompressorNode {
+            from_ui: self.from_ui.receiver(),
+            settings,
+            threshold: Smoothed::new(settings.threshold),
+            ratio: Smoothed::new(settings.ratio),
+            makeup: Smoothed::new(db_to_gain(settings.makeup)),
+            mix: Smoothed::new(settings.mix),
+            envelope: 0.0,
+            sample_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LimiterSettings {
+    ceiling: f32,
+    release: f32,
+    lookahead: f32,
+}
+
+enum LimiterFromUI {
+    Settings(LimiterSettings),
+}
+
+/// A brickwall limiter, no sample leaves above `ceiling` dB. It looks
+/// `lookahead` seconds ahead to fade the gain down in time, which is also the
+/// delay it adds
+#[derive(Live)]
+pub struct Limiter {
+    #[live(-1.0)] ceiling: f64,
+    #[live(0.05)] release: f64,
+    #[live(0.005)] lookahead: f64,
+    #[rust] from_ui: FromUISender<LimiterFromUI>,
+}
+
+impl LiveRegister for Limiter {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Limiter)
+    }
+}
+
+impl LiveHook for Limiter {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(LimiterFromUI::Settings(self.settings()));
+    }
+}
+
+impl Limiter {
+    fn settings(&self) -> LimiterSettings {
+        LimiterSettings {
+            ceiling: self.ceiling.min(0.0) as f32,
+            release: self.release.max(0.0) as f32,
+            lookahead: self.lookahead.clamp(0.0, 0.1) as f32,
+        }
+    }
+}
+
+struct LimiterNode {
+    from_ui: FromUIReceiver<LimiterFromUI>,
+    settings: LimiterSettings,
+    ceiling: Smoothed,
+    sample_rate: f32,
+    // frames of lookahead, the window of the minimum and the average
+    window: usize,
+    frame: u64,
+    delays: Vec<DelayLine>,
+    // the smallest needed gain over the window, as (frame, gain) rising
+    minimum: VecDeque<(u64, f32)>,
+    // the last window of minimums and their sum, averaging them gives a
+    // fade that is down in time for the peak
+    averages: Vec<f32>,
+    sum: f64,
+    gain: f32,
+}
+
+impl LimiterNode {
+    fn restart(&mut self) {
+        self.window = ((self.settings.lookahead * self.sample_rate) as usize).max(1);
+        self.frame = 0;
+        self.delays.iter_mut().for_each( | d | d.clear());
+        self.minimum.clear();
+        self.averages.clear();
+        self.averages.resize(self.window, 1.0);
+        self.sum = self.window as f64;
+        self.gain = 1.0;
+    }
+}
+
+impl AudioGraphNode for LimiterNode {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        let mut restart = false;
+        while let Ok(LimiterFromUI::Settings(settings)) = self.from_ui.try_recv() {
+            // a different lookahead changes the delay, that can't glide
+            restart |= settings.lookahead != self.settings.lookahead;
+            self.settings = settings;
+            self.ceiling.set_target(db_to_gain(settings.ceiling));
+        }
+        let sample_rate = info.sample_rate as f32;
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            self.ceiling.set_time(0.05, sample_rate);
+            restart = true;
+        }
+        if restart {
+            self.restart();
+        }
+        let release = 1.0 - (-1.0 / (self.settings.release * sample_rate).max(1.0)).exp();
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count();
+        if self.delays.len() < channel_count {
+            // only when the channel count grows, normally once
+            self.delays.resize(channel_count, DelayLine::new((0.1 * MAX_SAMPLE_RATE) as usize));
+        }
+        let window = self.window;
+        for i in 0..output.frame_count() {
+            let ceiling = self.ceiling.tick();
+            let mut peak = 0.0f32;
+            for c in 0..channel_count {
+                let s = output.channel(c)[i];
+                peak = peak.max(s.abs());
+                self.delays[c].push(s);
+            }
+            let needed = if peak > ceiling {ceiling / peak} else {1.0};
+
+            while self.minimum.back().is_some_and( | (_, g) | *g >= needed) {
+                self.minimum.pop_back();
+            }
+            self.minimum.push_back((self.frame, needed));
+            while self.minimum.front().is_some_and( | (f, _) | *f + window as u64 <= self.frame) {
+                self.minimum.pop_front();
+            }
+            let min = self.minimum.front().map_or(1.0, | (_, g) | *g);
+
+            let slot = (self.frame % window as u64) as usize;
+            self.sum += min as f64 - self.averages[slot] as f64;
+            self.averages[slot] = min;
+            // the running sum drifts, every so often it is added up again
+            if slot == 0 {
+                self.sum = self.averages.iter().map( | a | *a as f64).sum();
+            }
+            let target = (self.sum / window as f64) as f32;
+
+            self.gain = if target < self.gain {target} else {self.gain + (target - self.gain) * release};
+            for c in 0..channel_count {
+                let delayed = self.delays[c].tap(window - 1);
+                output.channel_mut(c)[i] = (delayed * self.gain).clamp(-ceiling, ceiling);
+            }
+            self.frame += 1;
+        }
+    }
+}
+
+impl AudioComponent for Limiter {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        let window_max = (0.1 * MAX_SAMPLE_RATE) as usize + 1;
+        Box::new(LimiterNode {
+            from_ui: self.from_ui.receiver(),
+            settings,
+            ceiling: Smoothed::new(db_to_gain(settings.ceiling)),
+            sample_rate: 0.0,
+            window: 1,
+            frame: 0,
+            delays: vec![DelayLine::new((0.1 * MAX_SAMPLE_RATE) as usize); 2],
+            minimum: VecDeque::with_capacity(window_max),
+            averages: Vec::with_capacity(window_max),
+            sum: 0.0,
+            gain: 1.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
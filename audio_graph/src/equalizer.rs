@@ -0,0 +1,209 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::*,
+    },
+};
+
+live_design!{
+    pub Equalizer = {{Equalizer}} {
+        low: {kind: LowShelf, freq: 100.0}
+        low_mid: {kind: Peak, freq: 400.0}
+        high_mid: {kind: Peak, freq: 2500.0}
+        high: {kind: HighShelf, freq: 8000.0}
+    }
+}
+
+// coefficients are worked out again this often while a band is moving
+const CONTROL_FRAMES: usize = 32;
+// channels past this pass through unfiltered
+const MAX_EQ_CHANNELS: usize = 8;
+
+/// One band of the equalizer, `gain` is in dB
+#[derive(Copy, Clone, Debug, Live, LiveHook, LiveRegister)]
+#[live_ignore]
+pub struct EqBand {
+    #[live] pub kind: BiquadKind,
+    #[live(1000.0)] pub freq: f64,
+    #[live(0.0)] pub gain: f64,
+    #[live(0.707)] pub q: f64,
+    #[live(true)] pub enabled: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct EqSettings {
+    bands: [EqBand; 4],
+    gain: f32,
+}
+
+enum FromUI {
+    Settings(EqSettings),
+}
+
+/// A four band parametric equalizer. Every band can take any filter shape,
+/// the defaults are a low shelf, two peaks and a high shelf that all start flat
+#[derive(Live)]
+pub struct Equalizer {
+    #[live] low: EqBand,
+    #[live] low_mid: EqBand,
+    #[live] high_mid: EqBand,
+    #[live] high: EqBand,
+    // output gain in dB
+    #[live(0.0)] gain: f64,
+    #[rust] from_ui: FromUISender<FromUI>,
+}
+
+impl LiveRegister for Equalizer {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Equalizer)
+    }
+}
+
+impl LiveHook for Equalizer {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl Equalizer {
+    fn settings(&self) -> EqSettings {
+        EqSettings {
+            bands: [self.low, self.low_mid, self.high_mid, self.high],
+            gain: self.gain as f32,
+        }
+    }
+}
+
+struct Band {
+    kind: BiquadKind,
+    enabled: bool,
+    // the frequency glides in octaves so sweeps sound even
+    octave: Smoothed,
+    gain: Smoothed,
+    q: Smoothed,
+    coefs: BiquadCoefs,
+    states: [BiquadState; MAX_EQ_CHANNELS],
+}
+
+impl Band {
+    fn new(band: &EqBand) -> Self {
+        Self {
+            kind: band.kind,
+            enabled: band.enabled,
+            octave: Smoothed::new((band.freq.max(1.0) as f32).log2()),
+            gain: Smoothed::new(band.gain as f32),
+            q: Smoothed::new(band.q as f32),
+            coefs: BiquadCoefs::default(),
+            states: [BiquadState::default(); MAX_EQ_CHANNELS],
+        }
+    }
+
+    fn set(&mut self, band: &EqBand) {
+        if self.kind != band.kind {
+            self.states.iter_mut().for_each( | s | s.reset());
+        }
+        self.kind = band.kind;
+        self.enabled = band.enabled;
+        self.octave.set_target((band.freq.max(1.0) as f32).log2());
+        self.gain.set_target(band.gain as f32);
+        self.q.set_target(band.q as f32);
+    }
+
+    fn update(&mut self, sample_rate: f32) {
+        let freq = self.octave.tick().exp2();
+        self.coefs = BiquadCoefs::new(self.kind, freq, self.q.tick(), self.gain.tick(), sample_rate);
+    }
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    bands: Vec<Band>,
+    gain: Smoothed,
+    sample_rate: f32,
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(FromUI::Settings(settings)) = self.from_ui.try_recv() {
+            for (band, settings) in self.bands.iter_mut().zip(&settings.bands) {
+                band.set(settings);
+            }
+            self.gain.set_target(db_to_gain(settings.gain));
+        }
+        let sample_rate = info.sample_rate as f32;
+        if self.sample_rate != sample_rate {
+            self.sample_rate = sample_rate;
+            for band in &mut self.bands {
+                band.octave.set_time(0.02, sample_rate / CONTROL_FRAMES as f32);
+                band.gain.set_time(0.02, sample_rate / CONTROL_FRAMES as f32);
+                band.q.set_time(0.02, sample_rate / CONTROL_FRAMES as f32);
+                band.update(sample_rate);
+            }
+            self.gain.set_time(0.02, sample_rate);
+        }
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count();
+        let filtered = channel_count.min(MAX_EQ_CHANNELS);
+        let frame_count = output.frame_count();
+        let mut start = 0;
+        while start < frame_count {
+            let end = (start + CONTROL_FRAMES).min(frame_count);
+            for band in &mut self.bands {
+                if !band.octave.is_settled() || !band.gain.is_settled() || !band.q.is_settled() {
+                    band.update(sample_rate);
+                }
+                if !band.enabled {
+                    continue
+                }
+                for (c, state) in band.states[..filtered].iter_mut().enumerate() {
+                    for s in &mut output.channel_mut(c)[start..end] {
+                        *s = state.process(&band.coefs, *s);
+                    }
+                }
+            }
+            start = end;
+        }
+        for i in 0..frame_count {
+            let gain = self.gain.tick();
+            for c in 0..channel_count {
+                output.channel_mut(c)[i] *= gain;
+            }
+        }
+    }
+}
+
+impl AudioComponent for Equalizer {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            bands: settings.bands.iter().map(Band::new).collect(),
+            gain: Smoothed::new(db_to_gain(settings.gain)),
+            sample_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
@@ -0,0 +1,687 @@
+use {
+    crate::{
+        makepad_platform::*,
+        audio_file::AudioFile,
+    },
+    std::{
+        io::{self, Cursor, Write, Seek, SeekFrom},
+        path::Path,
+    },
+};
+
+// flac packs everything most significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {data, pos: 0}
+    }
+
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self.data.get(self.pos >> 3).ok_or_else( || "Unexpected end of flac data".to_string()) ?;
+        let bit = (byte >> (7 - (self.pos & 7))) & 1;
+        self.pos += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0u64;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()? as u64;
+        }
+        Ok(value as u32)
+    }
+
+    fn signed(&mut self, count: u32) -> Result<i32, String> {
+        if count == 0 {
+            return Ok(0)
+        }
+        // the side channel of 32 bit audio is 33 bits wide
+        let mut value = 0u64;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()? as u64;
+        }
+        Ok((((value << (64 - count)) as i64) >> (64 - count)) as i32)
+    }
+
+    fn unary(&mut self) -> Result<u32, String> {
+        let mut count = 0;
+        while self.bit()? == 0 {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rice(&mut self, param: u32) -> Result<i32, String> {
+        let value = (self.unary()? << param) | self.bits(param)?;
+        Ok(((value >> 1) as i32) ^ -((value & 1) as i32))
+    }
+
+    fn align(&mut self) {
+        self.pos = (self.pos + 7) & !7;
+    }
+
+    fn byte_pos(&self) -> usize {
+        self.pos >> 3
+    }
+}
+
+struct StreamInfo {
+    sample_rate: u32,
+    channel_count: usize,
+    bits_per_sample: u32,
+    total_samples: u64,
+}
+
+/// Decodes a whole flac file, any bit depth up to 32 and up to 8 channels
+pub fn decode_flac(data: &[u8]) -> Result<AudioFile, String> {
+    if !data.starts_with(b"fLaC") {
+        return Err("Not a flac file".into())
+    }
+    let mut offset = 4;
+    let mut info = None;
+    loop {
+        if offset + 4 > data.len() {
+            return Err("Flac metadata is cut off".into())
+        }
+        let last = data[offset] & 0x80 != 0;
+        let block_type = data[offset] & 0x7f;
+        let size = u32::from_be_bytes([0, data[offset + 1], data[offset + 2], data[offset + 3]]) as usize;
+        let body = offset + 4;
+        if body + size > data.len() {
+            return Err("Flac metadata is cut off".into())
+        }
+        if block_type == 0 {
+            let mut r = BitReader::new(&data[body..body + size]);
+            r.bits(16)?;
+            r.bits(16)?;
+            r.bits(24)?;
+            r.bits(24)?;
+            let sample_rate = r.bits(20)?;
+            let channel_count = r.bits(3)? as usize + 1;
+            let bits_per_sample = r.bits(5)? + 1;
+            let total_samples = ((r.bits(4)? as u64) << 32) | r.bits(32)? as u64;
+            info = Some(StreamInfo {sample_rate, channel_count, bits_per_sample, total_samples});
+        }
+        offset = body + size;
+        if last {
+            break
+        }
+    }
+    let info = info.ok_or_else( || "Flac file has no stream info".to_string()) ?;
+
+    let mut channels: Vec<Vec<i32>> = vec![Vec::new(); info.channel_count];
+    let mut bits_per_sample = info.bits_per_sample;
+    let mut error = None;
+    while offset + 2 <= data.len() {
+        // frames start on a sync code, anything else is skipped
+        if data[offset] != 0xff || data[offset + 1] & 0xfe != 0xf8 {
+            offset += 1;
+            continue
+        }
+        let mut r = BitReader::new(&data[offset..]);
+        match decode_frame(&mut r, &info, &mut channels) {
+            Ok(bits) => {
+                bits_per_sample = bits;
+                offset += r.byte_pos();
+            }
+            // a damaged frame is dropped, the search goes on for the next one
+            Err(e) => {
+                error = Some(e);
+                offset += 1;
+            }
+        }
+    }
+    if channels[0].is_empty() && info.total_samples != 0 {
+        return Err(error.unwrap_or_else( || "Flac file has no frames".into()))
+    }
+
+    let mut frame_count = channels[0].len();
+    if info.total_samples > 0 {
+        frame_count = frame_count.min(info.total_samples as usize);
+    }
+    let scale = 1.0 / (1u64 << (bits_per_sample - 1)) as f32;
+    let mut buffer = AudioBuffer::new_with_size(frame_count, info.channel_count);
+    for (c, samples) in channels.iter().enumerate() {
+        for (out, s) in buffer.channel_mut(c).iter_mut().zip(samples) {
+            *out = *s as f32 * scale;
+        }
+    }
+    Ok(AudioFile {sample_rate: info.sample_rate, buffer})
+}
+
+// returns the bit depth of the frame
+fn decode_frame(r: &mut BitReader, info: &StreamInfo, channels: &mut [Vec<i32>]) -> Result<u32, String> {
+    r.bits(16)?;
+    let block_size_code = r.bits(4)?;
+    let sample_rate_code = r.bits(4)?;
+    let assignment = r.bits(4)?;
+    let bits_code = r.bits(3)?;
+    r.bits(1)?;
+    // the utf-8 like coded frame or sample number
+    let first = r.bits(8)?;
+    let extra = (first as u8).leading_ones();
+    if extra == 1 || extra > 7 {
+        return Err("Bad flac frame number".into())
+    }
+    for _ in 1..extra {
+        r.bits(8)?;
+    }
+    let block_size = match block_size_code {
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => r.bits(8)? as usize + 1,
+        7 => r.bits(16)? as usize + 1,
+        8..=15 => 256 << (block_size_code - 8),
+        _ => return Err("Reserved flac block size".into())
+    };
+    match sample_rate_code {
+        12 => {r.bits(8)?;}
+        13 | 14 => {r.bits(16)?;}
+        15 => return Err("Bad flac sample rate".into()),
+        _ => ()
+    }
+    let bits_per_sample = match bits_code {
+        0 => info.bits_per_sample,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => return Err("Reserved flac sample size".into())
+    };
+    let header_len = r.byte_pos();
+    if r.bits(8)? != crc8(&r.data[..header_len]) {
+        return Err("Flac frame header crc mismatch".into())
+    }
+
+    let channel_count = match assignment {
+        0..=7 => assignment as usize + 1,
+        8..=10 => 2,
+        _ => return Err("Reserved flac channel assignment".into())
+    };
+    if channel_count != channels.len() {
+        return Err("Flac frame channel count differs from the stream".into())
+    }
+    let mut frame: Vec<Vec<i32>> = Vec::with_capacity(channel_count);
+    for c in 0..channel_count {
+        // the side channel needs one bit more
+        let side = match assignment {
+            8 | 10 => c == 1,
+            9 => c == 0,
+            _ => false
+        };
+        frame.push(decode_subframe(r, block_size, bits_per_sample + side as u32)?);
+    }
+    if assignment >= 8 {
+        let (a, b) = frame.split_at_mut(1);
+        let (a, b) = (&mut a[0], &mut b[0]);
+        for i in 0..block_size {
+            let (x, y) = (a[i], b[i]);
+            match assignment {
+                // left and side
+                8 => b[i] = x.wrapping_sub(y),
+                // side and right
+                9 => a[i] = x.wrapping_add(y),
+                // mid and side
+                _ => {
+                    let mid = ((x as i64) << 1) | (y as i64 & 1);
+                    a[i] = ((mid + y as i64) >> 1) as i32;
+                    b[i] = ((mid - y as i64) >> 1) as i32;
+                }
+            }
+        }
+    }
+    r.align();
+    let frame_len = r.byte_pos();
+    if r.bits(16)? != crc16(&r.data[..frame_len]) {
+        return Err("Flac frame crc mismatch".into())
+    }
+    for (out, samples) in channels.iter_mut().zip(frame) {
+        out.extend_from_slice(&samples);
+    }
+    Ok(bits_per_sample)
+}
+
+fn decode_subframe(r: &mut BitReader, block_size: usize, bits_per_sample: u32) -> Result<Vec<i32>, String> {
+    if r.bit()? != 0 {
+        return Err("Bad flac subframe padding".into())
+    }
+    let kind = r.bits(6)?;
+    let wasted = if r.bit()? == 1 {r.unary()? + 1} else {0};
+    if wasted >= bits_per_sample {
+        return Err("Bad flac wasted bits".into())
+    }
+    let bits = bits_per_sample - wasted;
+    let mut samples = Vec::with_capacity(block_size);
+    match kind {
+        0 => {
+            let value = r.signed(bits)?;
+            samples.resize(block_size, value);
+        }
+        1 => {
+            for _ in 0..block_size {
+                samples.push(r.signed(bits)?);
+            }
+        }
+        8..=12 => {
+            let order = (kind & 7) as usize;
+            for _ in 0..order {
+                samples.push(r.signed(bits)?);
+            }
+            decode_residual(r, block_size, order, &mut samples)?;
+            let coefs: &[i64] = match order {
+                0 => &[],
+                1 => &[1],
+                2 => &[2, -1],
+                3 => &[3, -3, 1],
+                _ => &[4, -6, 4, -1],
+            };
+            predict(&mut samples, coefs, 0);
+        }
+        32..=63 => {
+            let order = (kind & 31) as usize + 1;
+            for _ in 0..order {
+                samples.push(r.signed(bits)?);
+            }
+            let precision = r.bits(4)? + 1;
+            if precision == 16 {
+                return Err("Bad flac lpc precision".into())
+            }
+            let shift = r.signed(5)?.max(0) as u32;
+            let mut coefs = Vec::with_capacity(order);
+            for _ in 0..order {
+                coefs.push(r.signed(precision)? as i64);
+            }
+            decode_residual(r, block_size, order, &mut samples)?;
+            predict(&mut samples, &coefs, shift);
+        }
+        _ => return Err("Reserved flac subframe type".into())
+    }
+    if wasted > 0 {
+        for s in &mut samples {
+            *s <<= wasted;
+        }
+    }
+    Ok(samples)
+}
+
+// the residual is appended after the warmup samples, the prediction is
+// added to it in place afterwards
+fn decode_residual(r: &mut BitReader, block_size: usize, order: usize, samples: &mut Vec<i32>) -> Result<(), String> {
+    let method = r.bits(2)?;
+    let (param_bits, escape) = match method {
+        0 => (4, 15),
+        1 => (5, 31),
+        _ => return Err("Reserved flac residual coding".into())
+    };
+    let partition_order = r.bits(4)?;
+    let partitions = 1usize << partition_order;
+    let partition_size = block_size >> partition_order;
+    if partition_size < order || (partition_size << partition_order) != block_size {
+        return Err("Bad flac residual partitioning".into())
+    }
+    for p in 0..partitions {
+        let count = if p == 0 {partition_size - order} else {partition_size};
+        let param = r.bits(param_bits)?;
+        if param == escape {
+            let raw_bits = r.bits(5)?;
+            for _ in 0..count {
+                samples.push(r.signed(raw_bits)?);
+            }
+        }
+        else {
+            for _ in 0..count {
+                samples.push(r.rice(param)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn predict(samples: &mut [i32], coefs: &[i64], shift: u32) {
+    let order = coefs.len();
+    for i in order..samples.len() {
+        let mut sum = 0i64;
+        for (j, coef) in coefs.iter().enumerate() {
+            sum += coef * samples[i - 1 - j] as i64;
+        }
+        samples[i] = samples[i].wrapping_add((sum >> shift) as i32);
+    }
+}
+
+// the frames the encoder makes, the last one can be shorter
+const BLOCK_SIZE: usize = 4096;
+
+struct BitWriter {
+    data: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {data: Vec::new(), acc: 0, bits: 0}
+    }
+
+    fn bits(&mut self, count: u32, value: u64) {
+        for shift in (0..count).rev() {
+            self.acc = (self.acc << 1) | ((value >> shift) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.data.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn signed(&mut self, count: u32, value: i64) {
+        self.bits(count, value as u64 & ((1u64 << count) - 1));
+    }
+
+    fn rice(&mut self, param: u32, value: i32) {
+        let folded = ((value << 1) ^ (value >> 31)) as u32;
+        let mut zeros = folded >> param;
+        while zeros >= 32 {
+            self.bits(32, 0);
+            zeros -= 32;
+        }
+        self.bits(zeros + 1, 1);
+        self.bits(param, (folded & ((1u32 << param) - 1)) as u64);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.bits(8 - self.bits, 0);
+        }
+    }
+}
+
+// the residual of the fixed predictors, they are differences of the order
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let coefs: &[i64] = match order {
+        0 => &[],
+        1 => &[1],
+        2 => &[2, -1],
+        3 => &[3, -3, 1],
+        _ => &[4, -6, 4, -1],
+    };
+    (order..samples.len()).map( | i | {
+        let prediction: i64 = coefs.iter().enumerate().map( | (j, c) | c * samples[i - 1 - j] as i64).sum();
+        (samples[i] as i64 - prediction) as i32
+    }).collect()
+}
+
+// the best rice parameter for a partition, tried around the one the mean
+// points at, with the bits it takes
+fn rice_param(residual: &[i32]) -> (u32, u64) {
+    let folded = | r: &i32 | ((r << 1) ^ (r >> 31)) as u32 as u64;
+    let sum: u64 = residual.iter().map(folded).sum();
+    let mean = sum / residual.len().max(1) as u64;
+    let guess = 64 - mean.leading_zeros();
+    let mut best = (0, u64::MAX);
+    for param in guess.saturating_sub(1)..=(guess + 1).min(30) {
+        let bits = residual.iter().map( | r | (folded(r) >> param) + 1 + param as u64).sum();
+        if bits < best.1 {
+            best = (param, bits);
+        }
+    }
+    best
+}
+
+// picks how many partitions the residual is split in, returns the partition
+// order, the parameters and the bits it all takes
+fn partition_residual(residual: &[i32], block_size: usize, order: usize) -> (u32, Vec<u32>, u64) {
+    let mut best = (0, Vec::new(), u64::MAX);
+    for partition_order in 0..=6u32 {
+        let partition_size = block_size >> partition_order;
+        if (partition_size << partition_order) != block_size || partition_size < order.max(1) {
+            break
+        }
+        let mut params = Vec::new();
+        let mut bits = 0;
+        let mut start = 0;
+        for p in 0..1usize << partition_order {
+            let count = if p == 0 {partition_size - order} else {partition_size};
+            let (param, param_bits) = rice_param(&residual[start..start + count]);
+            params.push(param);
+            bits += 5 + param_bits;
+            start += count;
+        }
+        if bits < best.2 {
+            best = (partition_order, params, bits);
+        }
+    }
+    best
+}
+
+fn encode_subframe(w: &mut BitWriter, samples: &[i32], bits_per_sample: u32) {
+    let block_size = samples.len();
+    if samples.iter().all( | s | *s == samples[0]) {
+        w.bits(8, 0);
+        w.signed(bits_per_sample, samples[0] as i64);
+        return
+    }
+    // the fixed predictor that comes out smallest, or the samples as they are
+    let verbatim = bits_per_sample as u64 * block_size as u64;
+    let mut best: Option<(usize, Vec<i32>, u32, Vec<u32>)> = None;
+    let mut best_bits = verbatim;
+    for order in 0..=4.min(block_size - 1) {
+        let residual = fixed_residual(samples, order);
+        let (partition_order, params, bits) = partition_residual(&residual, block_size, order);
+        let bits = bits + order as u64 * bits_per_sample as u64 + 6;
+        if bits < best_bits {
+            best_bits = bits;
+            best = Some((order, residual, partition_order, params));
+        }
+    }
+    match best {
+        Some((order, residual, partition_order, params)) => {
+            w.bits(8, (8 | order as u64) << 1);
+            for s in &samples[..order] {
+                w.signed(bits_per_sample, *s as i64);
+            }
+            // 5 bit rice parameters
+            w.bits(2, 1);
+            w.bits(4, partition_order as u64);
+            let partition_size = block_size >> partition_order;
+            let mut start = 0;
+            for (p, param) in params.iter().enumerate() {
+                let count = if p == 0 {partition_size - order} else {partition_size};
+                w.bits(5, *param as u64);
+                for r in &residual[start..start + count] {
+                    w.rice(*param, *r);
+                }
+                start += count;
+            }
+        }
+        None => {
+            w.bits(8, 1 << 1);
+            for s in samples {
+                w.signed(bits_per_sample, *s as i64);
+            }
+        }
+    }
+}
+
+// the frame number is coded like utf-8, extended to 31 bits
+fn push_frame_number(w: &mut BitWriter, number: u32) {
+    if number < 0x80 {
+        w.bits(8, number as u64);
+        return
+    }
+    let mut extra = 1;
+    while number >> (6 * extra) >= 1 << (6 - extra) {
+        extra += 1;
+    }
+    w.bits(8, ((0xff00u32 >> (extra + 1)) as u8 | (number >> (6 * extra)) as u8) as u64);
+    for i in (0..extra).rev() {
+        w.bits(8, (0x80 | ((number >> (6 * i)) & 0x3f)) as u64);
+    }
+}
+
+fn encode_frame(channels: &[Vec<i32>], number: u32, bits_per_sample: u32) -> Vec<u8> {
+    let block_size = channels[0].len();
+    let mut w = BitWriter::new();
+    // fixed block size sync code
+    w.bits(16, 0xfff8);
+    // block size follows the header as 16 bits, the rate comes from the stream info
+    w.bits(4, 7);
+    w.bits(4, 0);
+    w.bits(4, channels.len() as u64 - 1);
+    w.bits(3, if bits_per_sample == 16 {4} else {6});
+    w.bits(1, 0);
+    push_frame_number(&mut w, number);
+    w.bits(16, block_size as u64 - 1);
+    let crc = crc8(&w.data);
+    w.bits(8, crc as u64);
+    for samples in channels {
+        encode_subframe(&mut w, samples, bits_per_sample);
+    }
+    w.align();
+    let crc = crc16(&w.data);
+    w.bits(16, crc as u64);
+    w.data
+}
+
+/// Writes a flac file as the audio comes in, with the fixed predictors and
+/// independent channels. The stream info is filled in by `finish`
+pub struct FlacWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    pending: Vec<Vec<i32>>,
+    frame_number: u32,
+    frame_count: u64,
+    min_frame_size: usize,
+    max_frame_size: usize,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    /// `bits_per_sample` is 16 or 24, anything else is written as 24
+    pub fn new(writer: W, sample_rate: u32, channel_count: usize, bits_per_sample: u32) -> io::Result<Self> {
+        let mut flac = Self {
+            writer,
+            sample_rate,
+            bits_per_sample: if bits_per_sample == 16 {16} else {24},
+            pending: vec![Vec::with_capacity(BLOCK_SIZE); channel_count.clamp(1, 8)],
+            frame_number: 0,
+            frame_count: 0,
+            min_frame_size: 0,
+            max_frame_size: 0,
+        };
+        // a placeholder until the length is known
+        let info = flac.stream_info();
+        flac.writer.write_all(&info) ?;
+        Ok(flac)
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn stream_info(&self) -> Vec<u8> {
+        let mut w = BitWriter::new();
+        w.bits(32, u32::from_be_bytes(*b"fLaC") as u64);
+        // the only metadata block, 34 bytes of stream info
+        w.bits(8, 0x80);
+        w.bits(24, 34);
+        w.bits(16, BLOCK_SIZE as u64);
+        w.bits(16, BLOCK_SIZE as u64);
+        w.bits(24, self.min_frame_size as u64);
+        w.bits(24, self.max_frame_size as u64);
+        w.bits(20, self.sample_rate as u64);
+        w.bits(3, self.pending.len() as u64 - 1);
+        w.bits(5, self.bits_per_sample as u64 - 1);
+        w.bits(36, self.frame_count);
+        // no md5 of the audio, it is allowed to be unknown
+        w.bits(64, 0);
+        w.bits(64, 0);
+        w.data
+    }
+
+    fn flush_frame(&mut self) -> io::Result<()> {
+        if self.pending[0].is_empty() {
+            return Ok(())
+        }
+        let frame = encode_frame(&self.pending, self.frame_number, self.bits_per_sample);
+        self.writer.write_all(&frame) ?;
+        self.min_frame_size = if self.frame_number == 0 {frame.len()} else {self.min_frame_size.min(frame.len())};
+        self.max_frame_size = self.max_frame_size.max(frame.len());
+        self.frame_number += 1;
+        self.pending.iter_mut().for_each( | p | p.clear());
+        Ok(())
+    }
+
+    /// Appends the frames of `buffer`, samples are clipped and rounded
+    pub fn write(&mut self, buffer: &AudioBuffer) -> io::Result<()> {
+        if buffer.channel_count() == 0 {
+            return Ok(())
+        }
+        // the scale the decoder reads with, so decoded files write back the same
+        let scale = (1u32 << (self.bits_per_sample - 1)) as f32;
+        let mut done = 0;
+        while done < buffer.frame_count() {
+            let len = (BLOCK_SIZE - self.pending[0].len()).min(buffer.frame_count() - done);
+            for (c, pending) in self.pending.iter_mut().enumerate() {
+                let channel = buffer.channel(c.min(buffer.channel_count() - 1));
+                pending.extend(channel[done..done + len].iter().map( | s | (s * scale).round().clamp(-scale, scale - 1.0) as i32));
+            }
+            done += len;
+            self.frame_count += len as u64;
+            if self.pending[0].len() == BLOCK_SIZE {
+                self.flush_frame() ?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the last short frame and puts the length in the stream info
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_frame() ?;
+        let info = self.stream_info();
+        self.writer.seek(SeekFrom::Start(0)) ?;
+        self.writer.write_all(&info) ?;
+        self.writer.seek(SeekFrom::End(0)) ?;
+        self.writer.flush() ?;
+        Ok(self.writer)
+    }
+}
+
+/// Encodes a buffer as a 16 or 24 bit flac file
+pub fn encode_flac(buffer: &AudioBuffer, sample_rate: u32, bits_per_sample: u32) -> Vec<u8> {
+    let mut flac = FlacWriter::new(Cursor::new(Vec::new()), sample_rate, buffer.channel_count(), bits_per_sample).unwrap();
+    flac.write(buffer).unwrap();
+    flac.finish().unwrap().into_inner()
+}
+
+pub fn write_flac(path: impl AsRef<Path>, buffer: &AudioBuffer, sample_rate: u32, bits_per_sample: u32) -> io::Result<()> {
+    std::fs::write(path, encode_flac(buffer, sample_rate, bits_per_sample))
+}
+
+fn crc8(data: &[u8]) -> u32 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {(crc << 1) ^ 0x07} else {crc << 1};
+        }
+    }
+    crc as u32
+}
+
+fn crc16(data: &[u8]) -> u32 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {(crc << 1) ^ 0x8005} else {crc << 1};
+        }
+    }
+    crc as u32
+}
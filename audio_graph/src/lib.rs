@@ -4,19 +4,65 @@
 pub mod audio_unit_effect;
 #[cfg(target_os = "macos")]
 pub mod audio_unit_instrument;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub mod clap_plugin;
 
 pub mod mixer;
 pub mod instrument;
 pub mod audio_stream;
+pub mod offline_render;
+pub mod wav;
+pub mod flac;
+pub mod ogg_vorbis;
+pub mod audio_file;
+pub mod sample_player;
+pub mod dsp;
+pub mod equalizer;
+pub mod dynamics;
+pub mod reverb;
+pub mod delay;
+pub mod chorus;
+pub mod midi_file;
+pub mod midi_sequencer;
+pub mod meter;
+pub mod recorder;
 
 use makepad_platform::Cx;
 pub use makepad_platform;
 pub use makepad_platform::makepad_math;
 pub use crate::audio_graph::*;
 pub use crate::audio_traits::*;
+pub use crate::offline_render::*;
+pub use crate::wav::*;
+pub use crate::flac::*;
+pub use crate::ogg_vorbis::*;
+pub use crate::audio_file::*;
+pub use crate::sample_player::*;
+pub use crate::dsp::*;
+pub use crate::equalizer::*;
+pub use crate::dynamics::*;
+pub use crate::reverb::*;
+pub use crate::delay::*;
+pub use crate::chorus::*;
+pub use crate::midi_file::*;
+pub use crate::midi_sequencer::*;
+pub use crate::meter::*;
+pub use crate::recorder::*;
+#[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+pub use crate::clap_plugin::*;
 
 pub fn live_design(cx:&mut Cx){
     self::audio_graph::live_design(cx);
     self::mixer::live_design(cx);
     self::instrument::live_design(cx);
+    self::sample_player::live_design(cx);
+    self::equalizer::live_design(cx);
+    self::dynamics::live_design(cx);
+    self::reverb::live_design(cx);
+    self::delay::live_design(cx);
+    self::chorus::live_design(cx);
+    self::midi_sequencer::live_design(cx);
+    self::recorder::live_design(cx);
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    self::clap_plugin::live_design(cx);
 }
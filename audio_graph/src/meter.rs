@@ -0,0 +1,236 @@
+// Level and loudness metering after ITU-R BS.1770 / EBU R128
+
+use crate::{
+    makepad_platform::*,
+    dsp::*,
+};
+
+/// Channels past this are not metered
+pub const MAX_METER_CHANNELS: usize = 8;
+
+// readings are made of blocks of 100ms, momentary loudness is 4 of them,
+// short term 30 and the rms 3
+const BLOCKS: usize = 30;
+const MOMENTARY_BLOCKS: usize = 4;
+const RMS_BLOCKS: usize = 3;
+
+// the histogram the integrated loudness is gated with, 0.1 LU bins from the
+// absolute gate up
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+const HISTOGRAM_BINS: usize = 1000;
+
+fn loudness(power: f64) -> f32 {
+    (-0.691 + 10.0 * power.max(1e-18).log10()) as f32
+}
+
+/// A meter reading, levels are in dB and loudness in LUFS
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AudioMeter {
+    pub channel_count: usize,
+    /// The sample peak since the last reading
+    pub peak: [f32; MAX_METER_CHANNELS],
+    pub rms: [f32; MAX_METER_CHANNELS],
+    pub momentary: f32,
+    pub short_term: f32,
+    pub integrated: f32,
+}
+
+impl AudioMeter {
+    pub fn peak(&self) -> &[f32] {
+        &self.peak[..self.channel_count]
+    }
+
+    pub fn rms(&self) -> &[f32] {
+        &self.rms[..self.channel_count]
+    }
+}
+
+/// Measures what goes through it, it doesn't allocate after `new` so it can
+/// run on the audio thread
+pub struct LevelMeter {
+    channel_count: usize,
+    block_size: usize,
+    block_pos: usize,
+    shelf: BiquadCoefs,
+    highpass: BiquadCoefs,
+    filters: [[BiquadState; 2]; MAX_METER_CHANNELS],
+    // sums of the squares of the block being filled
+    square: [f64; MAX_METER_CHANNELS],
+    weighted: f64,
+    peak: [f32; MAX_METER_CHANNELS],
+    // the mean squares of the last blocks, `blocks` is how many are filled
+    squares: [[f64; MAX_METER_CHANNELS]; RMS_BLOCKS],
+    powers: [f64; BLOCKS],
+    block_index: usize,
+    blocks: usize,
+    histogram_count: Vec<u64>,
+    histogram_power: Vec<f64>,
+}
+
+impl LevelMeter {
+    pub fn new(channel_count: usize, sample_rate: f64) -> Self {
+        let mut meter = Self {
+            channel_count: 0,
+            block_size: 1,
+            block_pos: 0,
+            shelf: BiquadCoefs::default(),
+            highpass: BiquadCoefs::default(),
+            filters: Default::default(),
+            square: [0.0; MAX_METER_CHANNELS],
+            weighted: 0.0,
+            peak: [0.0; MAX_METER_CHANNELS],
+            squares: [[0.0; MAX_METER_CHANNELS]; RMS_BLOCKS],
+            powers: [0.0; BLOCKS],
+            block_index: 0,
+            blocks: 0,
+            histogram_count: vec![0; HISTOGRAM_BINS],
+            histogram_power: vec![0.0; HISTOGRAM_BINS],
+        };
+        meter.set_format(channel_count, sample_rate);
+        meter
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Starts over for other audio, without allocating
+    pub fn set_format(&mut self, channel_count: usize, sample_rate: f64) {
+        self.channel_count = channel_count.clamp(1, MAX_METER_CHANNELS);
+        self.block_size = ((sample_rate / 10.0).round() as usize).max(1);
+        self.block_pos = 0;
+        self.filters = Default::default();
+        self.square = [0.0; MAX_METER_CHANNELS];
+        self.weighted = 0.0;
+        self.peak = [0.0; MAX_METER_CHANNELS];
+        self.squares = [[0.0; MAX_METER_CHANNELS]; RMS_BLOCKS];
+        self.powers = [0.0; BLOCKS];
+        self.blocks = 0;
+        self.reset_integrated();
+        // the k-weighting filters worked out for any rate, the constants are
+        // the ones libebur128 matches the 48kHz coefficients of the spec with
+        let pi = std::f64::consts::PI;
+        let k = (pi * 1681.974450955533 / sample_rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        self.shelf = BiquadCoefs {
+            b0: ((vh + vb * k / q + k * k) / a0) as f32,
+            b1: (2.0 * (k * k - vh) / a0) as f32,
+            b2: ((vh - vb * k / q + k * k) / a0) as f32,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+        let k = (pi * 38.13547087602444 / sample_rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        self.highpass = BiquadCoefs {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+        };
+    }
+
+    pub fn process(&mut self, buffer: &AudioBuffer) {
+        let channel_count = self.channel_count.min(buffer.channel_count());
+        let mut done = 0;
+        while done < buffer.frame_count() {
+            let len = (self.block_size - self.block_pos).min(buffer.frame_count() - done);
+            for c in 0..channel_count {
+                let [shelf, highpass] = &mut self.filters[c];
+                let mut square = 0.0;
+                let mut weighted = 0.0;
+                let mut peak = self.peak[c];
+                for s in &buffer.channel(c)[done..done + len] {
+                    peak = peak.max(s.abs());
+                    square += (*s as f64) * (*s as f64);
+                    let k = highpass.process(&self.highpass, shelf.process(&self.shelf, *s)) as f64;
+                    weighted += k * k;
+                }
+                self.peak[c] = peak;
+                self.square[c] += square;
+                self.weighted += weighted;
+            }
+            done += len;
+            self.block_pos += len;
+            if self.block_pos == self.block_size {
+                self.end_block();
+            }
+        }
+    }
+
+    fn end_block(&mut self) {
+        let size = self.block_size as f64;
+        self.block_index = (self.block_index + 1) % BLOCKS;
+        self.powers[self.block_index] = self.weighted / size;
+        for (c, square) in self.square.iter_mut().enumerate() {
+            self.squares[self.block_index % RMS_BLOCKS][c] = *square / size;
+            *square = 0.0;
+        }
+        self.weighted = 0.0;
+        self.block_pos = 0;
+        self.blocks += 1;
+        // the gating blocks are the momentary ones, overlapping by 75%
+        if self.blocks >= MOMENTARY_BLOCKS {
+            let power = self.power(MOMENTARY_BLOCKS);
+            let bin = (loudness(power) as f64 - ABSOLUTE_GATE) * 10.0;
+            if bin >= 0.0 {
+                let bin = (bin as usize).min(HISTOGRAM_BINS - 1);
+                self.histogram_count[bin] += 1;
+                self.histogram_power[bin] += power;
+            }
+        }
+    }
+
+    // the mean power of the last `count` blocks, or of the ones there are
+    fn power(&self, count: usize) -> f64 {
+        let count = count.min(self.blocks);
+        if count == 0 {
+            return 0.0
+        }
+        (0..count).map( | i | self.powers[(self.block_index + BLOCKS - i) % BLOCKS]).sum::<f64>() / count as f64
+    }
+
+    fn integrated(&self) -> f32 {
+        let gated = | from: usize | {
+            let count: u64 = self.histogram_count[from..].iter().sum();
+            let power: f64 = self.histogram_power[from..].iter().sum();
+            if count == 0 {0.0} else {power / count as f64}
+        };
+        let power = gated(0);
+        if power == 0.0 {
+            return loudness(0.0)
+        }
+        let relative = (loudness(power) as f64 + RELATIVE_GATE - ABSOLUTE_GATE) * 10.0;
+        loudness(gated((relative.max(0.0).ceil() as usize).min(HISTOGRAM_BINS - 1)))
+    }
+
+    /// The levels up to now, the peak starts over after a reading
+    pub fn reading(&mut self) -> AudioMeter {
+        let mut meter = AudioMeter {
+            channel_count: self.channel_count,
+            momentary: loudness(self.power(MOMENTARY_BLOCKS)),
+            short_term: loudness(self.power(BLOCKS)),
+            integrated: self.integrated(),
+            ..Default::default()
+        };
+        let blocks = self.blocks.clamp(1, RMS_BLOCKS);
+        for c in 0..self.channel_count {
+            let square: f64 = self.squares.iter().map( | s | s[c]).sum::<f64>() / blocks as f64;
+            meter.rms[c] = gain_to_db(square.sqrt() as f32);
+            meter.peak[c] = gain_to_db(self.peak[c]);
+            self.peak[c] = 0.0;
+        }
+        meter
+    }
+
+    /// Starts the integrated loudness over, for a new take
+    pub fn reset_integrated(&mut self) {
+        self.histogram_count.iter_mut().for_each( | c | *c = 0);
+        self.histogram_power.iter_mut().for_each( | p | *p = 0.0);
+    }
+}
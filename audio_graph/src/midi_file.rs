@@ -0,0 +1,487 @@
+use {
+    crate::makepad_platform::*,
+    std::{
+        io,
+        path::Path,
+    },
+};
+
+// a quarter note at 120 bpm, what a file plays at before its first tempo
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// How the ticks of a midi file relate to time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiTimeDivision {
+    /// Musical time, the tempo events decide how long a quarter note is
+    TicksPerQuarter(u16),
+    /// A fixed rate of 24, 25, 29 or 30 frames per second, 29 means 29.97 drop
+    /// frame
+    Smpte {frames_per_second: u8, ticks_per_frame: u8},
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MidiFileEvent {
+    Midi(MidiData),
+    /// A sysex message starting with 0xf0, or the raw bytes of an escape
+    /// event when it doesn't
+    SysEx(Vec<u8>),
+    /// Microseconds per quarter note
+    Tempo(u32),
+    TimeSignature {numerator: u8, denominator: u8, clocks_per_click: u8, notated_32nds: u8},
+    TrackName(String),
+    Meta {kind: u8, data: Vec<u8>},
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiTrackEvent {
+    /// Counted from the start of the file, not from the previous event
+    pub tick: u64,
+    pub event: MidiFileEvent,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiTrack {
+    /// Sorted on tick
+    pub events: Vec<MidiTrackEvent>,
+    /// Where the end of track marker is, it can be after the last event
+    pub end_tick: u64,
+}
+
+impl MidiTrack {
+    /// Adds an event after the ones on the same tick
+    pub fn push(&mut self, tick: u64, event: MidiFileEvent) {
+        let index = self.events.partition_point( | e | e.tick <= tick);
+        self.events.insert(index, MidiTrackEvent {tick, event});
+        self.end_tick = self.end_tick.max(tick);
+    }
+}
+
+/// A standard midi file, type 0 has one track and type 1 plays all its
+/// tracks at once with the tempo map in the first one
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiFile {
+    pub format: u16,
+    pub division: MidiTimeDivision,
+    pub tracks: Vec<MidiTrack>,
+}
+
+impl MidiFile {
+    /// An empty type 1 file
+    pub fn new(ticks_per_quarter: u16) -> Self {
+        Self {
+            format: 1,
+            division: MidiTimeDivision::TicksPerQuarter(ticks_per_quarter.max(1)),
+            tracks: Vec::new(),
+        }
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        self.tracks.iter().map( | t | t.end_tick).max().unwrap_or(0)
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.tempo_map().tick_to_seconds(self.end_tick() as f64)
+    }
+
+    /// The tempo events of all tracks, they are only meant to be in the first
+    /// one but not every file sticks to that
+    pub fn tempo_map(&self) -> TempoMap {
+        match self.division {
+            MidiTimeDivision::TicksPerQuarter(ticks) => {
+                let mut map = TempoMap::new(ticks as f64);
+                for track in &self.tracks {
+                    for e in &track.events {
+                        if let MidiFileEvent::Tempo(tempo) = e.event {
+                            map.push(e.tick, tempo);
+                        }
+                    }
+                }
+                map
+            }
+            MidiTimeDivision::Smpte {frames_per_second, ticks_per_frame} => {
+                // a fixed tick rate, put on the default tempo
+                let fps = if frames_per_second == 29 {29.97} else {frames_per_second as f64};
+                TempoMap::new(fps * ticks_per_frame as f64 * DEFAULT_TEMPO as f64 / 1e6)
+            }
+        }
+    }
+
+    /// All the tracks in one, on the same tick the earlier tracks go first
+    pub fn merged_track(&self) -> MidiTrack {
+        let mut events: Vec<MidiTrackEvent> = self.tracks.iter().flat_map( | t | t.events.iter().cloned()).collect();
+        events.sort_by_key( | e | e.tick);
+        MidiTrack {events, end_tick: self.end_tick()}
+    }
+
+    /// The channel messages of all tracks in playing order
+    pub fn midi_events(&self) -> Vec<(u64, MidiData)> {
+        self.merged_track().events.into_iter().filter_map( | e | match e.event {
+            MidiFileEvent::Midi(data) => Some((e.tick, data)),
+            _ => None
+        }).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TempoChange {
+    tick: u64,
+    // when the change happens, summed over the changes before it
+    seconds: f64,
+    tempo: u32,
+}
+
+/// Converts between ticks and seconds over the tempo changes of a file
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    ticks_per_quarter: f64,
+    // always starts with a change on tick 0
+    changes: Vec<TempoChange>,
+}
+
+impl TempoMap {
+    /// A map at 120 bpm
+    pub fn new(ticks_per_quarter: f64) -> Self {
+        Self {
+            ticks_per_quarter: ticks_per_quarter.max(1e-6),
+            changes: vec![TempoChange {tick: 0, seconds: 0.0, tempo: DEFAULT_TEMPO}],
+        }
+    }
+
+    pub fn with_bpm(ticks_per_quarter: f64, bpm: f64) -> Self {
+        let mut map = Self::new(ticks_per_quarter);
+        map.push(0, (60e6 / bpm.max(1e-3)) as u32);
+        map
+    }
+
+    pub fn ticks_per_quarter(&self) -> f64 {
+        self.ticks_per_quarter
+    }
+
+    /// Sets the tempo from `tick` on, a later change on the same tick wins
+    pub fn push(&mut self, tick: u64, tempo: u32) {
+        let tempo = tempo.max(1);
+        let index = self.changes.partition_point( | c | c.tick < tick);
+        if self.changes.get(index).is_some_and( | c | c.tick == tick) {
+            self.changes[index].tempo = tempo;
+        }
+        else {
+            self.changes.insert(index, TempoChange {tick, seconds: 0.0, tempo});
+        }
+        for i in 1..self.changes.len() {
+            let prev = self.changes[i - 1];
+            self.changes[i].seconds = prev.seconds + self.seconds_per_tick(prev.tempo) * (self.changes[i].tick - prev.tick) as f64;
+        }
+    }
+
+    fn seconds_per_tick(&self, tempo: u32) -> f64 {
+        tempo as f64 / 1e6 / self.ticks_per_quarter
+    }
+
+    fn change_at(&self, tick: f64) -> &TempoChange {
+        let index = self.changes.partition_point( | c | c.tick as f64 <= tick);
+        &self.changes[index.max(1) - 1]
+    }
+
+    /// Microseconds per quarter note at `tick`
+    pub fn tempo_at(&self, tick: f64) -> u32 {
+        self.change_at(tick).tempo
+    }
+
+    pub fn bpm_at(&self, tick: f64) -> f64 {
+        60e6 / self.tempo_at(tick) as f64
+    }
+
+    /// The first tempo change after `tick`
+    pub fn next_change(&self, tick: f64) -> Option<u64> {
+        self.changes.iter().map( | c | c.tick).find( | t | *t as f64 > tick)
+    }
+
+    pub fn tick_to_seconds(&self, tick: f64) -> f64 {
+        let change = self.change_at(tick);
+        change.seconds + (tick - change.tick as f64) * self.seconds_per_tick(change.tempo)
+    }
+
+    pub fn seconds_to_tick(&self, seconds: f64) -> f64 {
+        let index = self.changes.partition_point( | c | c.seconds <= seconds);
+        let change = &self.changes[index.max(1) - 1];
+        change.tick as f64 + (seconds - change.seconds) / self.seconds_per_tick(change.tempo)
+    }
+
+    /// The tempo events, for writing the map into a track
+    pub fn events(&self) -> Vec<(u64, u32)> {
+        self.changes.iter().map( | c | (c.tick, c.tempo)).collect()
+    }
+}
+
+/// How many data bytes a channel message has after its status byte
+fn data_len(status: u8) -> usize {
+    match status >> 4 {
+        0xc | 0xd => 1,
+        _ => 2,
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, String> {
+        let v = *self.data.get(self.offset).ok_or("Midi track ends in the middle of an event") ?;
+        self.offset += 1;
+        Ok(v)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.offset + len > self.data.len() {
+            return Err("Midi track ends in the middle of an event".into())
+        }
+        self.offset += len;
+        Ok(&self.data[self.offset - len..self.offset])
+    }
+
+    fn var_len(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        // at most 4 bytes, 28 bits
+        for _ in 0..4 {
+            let byte = self.u8() ?;
+            value = (value << 7) | (byte & 0x7f) as u64;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err("Midi variable length number is too long".into())
+    }
+}
+
+fn decode_track(data: &[u8]) -> Result<MidiTrack, String> {
+    let mut reader = Reader {data, offset: 0};
+    let mut track = MidiTrack::default();
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while reader.offset < data.len() {
+        tick += reader.var_len() ?;
+        let mut status = reader.u8() ?;
+        let event = match status {
+            0xff => {
+                running_status = None;
+                let kind = reader.u8() ?;
+                let len = reader.var_len() ? as usize;
+                let body = reader.bytes(len) ?;
+                match (kind, body.len()) {
+                    (0x2f, _) => {
+                        track.end_tick = track.end_tick.max(tick);
+                        break
+                    }
+                    (0x51, 3) => MidiFileEvent::Tempo(u32::from_be_bytes([0, body[0], body[1], body[2]])),
+                    (0x58, 4) => MidiFileEvent::TimeSignature {
+                        numerator: body[0],
+                        denominator: 1u8.checked_shl(body[1] as u32).unwrap_or(0),
+                        clocks_per_click: body[2],
+                        notated_32nds: body[3],
+                    },
+                    (0x03, _) => MidiFileEvent::TrackName(String::from_utf8_lossy(body).into_owned()),
+                    _ => MidiFileEvent::Meta {kind, data: body.to_vec()},
+                }
+            }
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let len = reader.var_len() ? as usize;
+                let body = reader.bytes(len) ?;
+                let mut bytes = Vec::with_capacity(len + 1);
+                if status == 0xf0 {
+                    bytes.push(0xf0);
+                }
+                bytes.extend_from_slice(body);
+                MidiFileEvent::SysEx(bytes)
+            }
+            _ => {
+                let mut first = None;
+                if status < 0x80 {
+                    // running status, this byte was already data
+                    first = Some(status);
+                    status = running_status.ok_or("Midi data byte without a status") ?;
+                }
+                else if status >= 0xf0 {
+                    return Err(format!("Unexpected midi status {:x} in a file", status))
+                }
+                running_status = Some(status);
+                let mut message = [status, 0, 0];
+                for (i, byte) in message[1..=data_len(status)].iter_mut().enumerate() {
+                    *byte = match (i, first) {
+                        (0, Some(first)) => first,
+                        _ => reader.u8() ?
+                    };
+                }
+                MidiFileEvent::Midi(MidiData {data: message})
+            }
+        };
+        track.events.push(MidiTrackEvent {tick, event});
+        track.end_tick = tick;
+    }
+    Ok(track)
+}
+
+pub fn decode_midi_file(data: &[u8]) -> Result<MidiFile, String> {
+    if data.len() < 14 || &data[0..4] != b"MThd" {
+        return Err("Not a midi file".into())
+    }
+    let u16_at = | o: usize | u16::from_be_bytes([data[o], data[o + 1]]);
+    let u32_at = | o: usize | u32::from_be_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+    let header_len = u32_at(4) as usize;
+    if header_len < 6 {
+        return Err("Midi header chunk too short".into())
+    }
+    let format = u16_at(8);
+    if format > 1 {
+        return Err(format!("Unsupported midi file format {}", format))
+    }
+    let track_count = u16_at(10) as usize;
+    let division = u16_at(12);
+    let division = if division & 0x8000 != 0 {
+        // the rate is stored negated, 0x80 would be -128
+        let frames_per_second = ((division >> 8) as u8 as i8).wrapping_neg() as u8;
+        if !matches!(frames_per_second, 24 | 25 | 29 | 30) {
+            return Err(format!("Unsupported SMPTE rate {}", (division >> 8) as u8 as i8))
+        }
+        if division as u8 == 0 {
+            return Err("Midi file has no ticks per SMPTE frame".into())
+        }
+        MidiTimeDivision::Smpte {
+            frames_per_second,
+            ticks_per_frame: division as u8,
+        }
+    }
+    else if division == 0 {
+        return Err("Midi file has no ticks per quarter note".into())
+    }
+    else {
+        MidiTimeDivision::TicksPerQuarter(division)
+    };
+
+    let mut tracks = Vec::new();
+    let mut offset = 8 + header_len;
+    while offset + 8 <= data.len() && tracks.len() < track_count {
+        let size = u32_at(offset + 4) as usize;
+        let body = offset + 8;
+        // some writers get the length wrong on the last chunk
+        let end = body.saturating_add(size).min(data.len());
+        if &data[offset..offset + 4] == b"MTrk" {
+            tracks.push(decode_track(&data[body..end]) ?);
+        }
+        offset = end;
+    }
+    if tracks.is_empty() {
+        return Err("Midi file has no tracks".into())
+    }
+    Ok(MidiFile {format, division, tracks})
+}
+
+pub fn read_midi_file(path: impl AsRef<Path>) -> Result<MidiFile, String> {
+    let data = std::fs::read(path.as_ref()).map_err( | e | format!("Can't read {:?}: {}", path.as_ref(), e)) ?;
+    decode_midi_file(&data)
+}
+
+fn write_var_len(out: &mut Vec<u8>, value: u64) {
+    let value = value.min(0x0fff_ffff);
+    let mut shift = 21;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        out.push(((value >> shift) & 0x7f) as u8 | 0x80);
+        shift -= 7;
+    }
+    out.push((value & 0x7f) as u8);
+}
+
+fn write_meta(out: &mut Vec<u8>, kind: u8, body: &[u8]) {
+    out.push(0xff);
+    out.push(kind);
+    write_var_len(out, body.len() as u64);
+    out.extend_from_slice(body);
+}
+
+fn encode_track(track: &MidiTrack) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut tick = 0;
+    let mut running_status = None;
+    for e in &track.events {
+        write_var_len(&mut out, e.tick.saturating_sub(tick));
+        tick = tick.max(e.tick);
+        match &e.event {
+            MidiFileEvent::Midi(data) => {
+                let status = data.data[0];
+                if running_status != Some(status) {
+                    out.push(status);
+                }
+                running_status = Some(status);
+                out.extend_from_slice(&data.data[1..=data_len(status)]);
+                continue
+            }
+            MidiFileEvent::SysEx(bytes) => {
+                match bytes.split_first() {
+                    Some((0xf0, rest)) => {
+                        out.push(0xf0);
+                        write_var_len(&mut out, rest.len() as u64);
+                        out.extend_from_slice(rest);
+                    }
+                    _ => {
+                        out.push(0xf7);
+                        write_var_len(&mut out, bytes.len() as u64);
+                        out.extend_from_slice(bytes);
+                    }
+                }
+            }
+            MidiFileEvent::Tempo(tempo) => write_meta(&mut out, 0x51, &tempo.to_be_bytes()[1..]),
+            MidiFileEvent::TimeSignature {numerator, denominator, clocks_per_click, notated_32nds} => {
+                let power = (*denominator).max(1).trailing_zeros() as u8;
+                write_meta(&mut out, 0x58, &[*numerator, power, *clocks_per_click, *notated_32nds])
+            }
+            MidiFileEvent::TrackName(name) => write_meta(&mut out, 0x03, name.as_bytes()),
+            MidiFileEvent::Meta {kind, data} => write_meta(&mut out, *kind, data),
+        }
+        running_status = None;
+    }
+    write_var_len(&mut out, track.end_tick.saturating_sub(tick));
+    write_meta(&mut out, 0x2f, &[]);
+    out
+}
+
+/// Encodes a midi file, a type 0 file with more than one track gets them
+/// merged into one. Channel messages use running status
+pub fn encode_midi_file(file: &MidiFile) -> Vec<u8> {
+    let merged;
+    let tracks = if file.format == 0 && file.tracks.len() > 1 {
+        merged = [file.merged_track()];
+        &merged[..]
+    }
+    else {
+        &file.tracks[..]
+    };
+    let division = match file.division {
+        MidiTimeDivision::TicksPerQuarter(ticks) => ticks & 0x7fff,
+        MidiTimeDivision::Smpte {frames_per_second, ticks_per_frame} => {
+            // a rate that isn't one of the four doesn't read back
+            (((frames_per_second as i8).wrapping_neg() as u8 as u16) << 8) | ticks_per_frame as u16
+        }
+    };
+    let mut out = Vec::new();
+    out.extend_from_slice(b"MThd");
+    out.extend_from_slice(&6u32.to_be_bytes());
+    out.extend_from_slice(&file.format.to_be_bytes());
+    out.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    out.extend_from_slice(&division.to_be_bytes());
+    for track in tracks {
+        let body = encode_track(track);
+        out.extend_from_slice(b"MTrk");
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&body);
+    }
+    out
+}
+
+pub fn write_midi_file(path: impl AsRef<Path>, file: &MidiFile) -> io::Result<()> {
+    std::fs::write(path, encode_midi_file(file))
+}
@@ -0,0 +1,576 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        midi_file::*,
+        delay::ClockTempo,
+    },
+    std::sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+live_design!{
+    pub MidiSequencer = {{MidiSequencer}} {
+    }
+}
+
+// the resolution of sequences that don't come from a file
+const TICKS_PER_QUARTER: u16 = 480;
+// midi clock runs at 24 pulses per quarter note
+const CLOCK_PULSES: f64 = 24.0;
+// positions this close count as the same tick, they pile up rounding errors
+const TICK_EPSILON: f64 = 1e-6;
+
+/// The channel messages of a file with the tempo map to play them on
+struct Sequence {
+    events: Vec<(u64, MidiData)>,
+    tempo_map: TempoMap,
+    end_tick: u64,
+}
+
+impl Sequence {
+    fn from_file(file: &MidiFile) -> Self {
+        Self {
+            events: file.midi_events(),
+            tempo_map: file.tempo_map(),
+            end_tick: file.end_tick(),
+        }
+    }
+
+    fn empty(bpm: f64) -> Self {
+        Self {
+            events: Vec::new(),
+            tempo_map: TempoMap::with_bpm(TICKS_PER_QUARTER as f64, bpm),
+            end_tick: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct SequencerSettings {
+    looping: bool,
+    follow_clock: bool,
+    send_clock: bool,
+}
+
+enum FromUI {
+    Sequence(Arc<Sequence>),
+    Settings(SequencerSettings),
+    Play,
+    Stop,
+    Seek(f64),
+    Record(bool),
+    MidiOutput(Option<MidiOutput>),
+}
+
+enum ToUI {
+    Playing(bool),
+    Recorded(u64, MidiData),
+}
+
+/// Plays a midi file into `instrument`, every message lands on the exact
+/// frame its tick falls on by rendering the instrument in pieces between
+/// them. The tempo comes from the file, or from incoming midi clock with
+/// `follow_clock`, which also obeys its start, stop and song position. With
+/// `send_clock` it sends clock to the instrument and to the midi output set
+/// with `set_midi_output` while it plays. Midi sent to the node is passed
+/// on to the instrument, and recorded while recording and playing
+#[derive(Live)]
+pub struct MidiSequencer {
+    #[live] source: LiveDependency,
+    #[live] instrument: AudioComponentRef,
+    #[live(false)] looping: bool,
+    #[live(true)] follow_clock: bool,
+    #[live(false)] send_clock: bool,
+    // the tempo when there is no file
+    #[live(120.0)] bpm: f64,
+    #[rust] file: Option<MidiFile>,
+    #[rust] loaded_source: String,
+    #[rust(Arc::new(Sequence::empty(120.0)))] sequence: Arc<Sequence>,
+    #[rust] playing: bool,
+    #[rust] recording: bool,
+    #[rust] recorded: MidiTrack,
+    // the tick the node is at as f64 bits
+    #[rust] position: Arc<AtomicU64>,
+    #[rust] from_ui: FromUISender<FromUI>,
+    #[rust] to_ui: ToUIReceiver<ToUI>,
+}
+
+impl LiveRegister for MidiSequencer {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, MidiSequencer)
+    }
+}
+
+impl LiveHook for MidiSequencer {
+    fn after_apply(&mut self, cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let source = self.source.as_str().to_string();
+        if source != self.loaded_source {
+            self.loaded_source = source.clone();
+            if !source.is_empty() {
+                match cx.get_dependency(&source).and_then( | data | decode_midi_file(&data)) {
+                    Ok(file) => self.file = Some(file),
+                    Err(err) => error!("MidiSequencer: can't load {} {}", source, err)
+                }
+            }
+        }
+        self.update_sequence();
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl MidiSequencer {
+    fn settings(&self) -> SequencerSettings {
+        SequencerSettings {
+            looping: self.looping,
+            follow_clock: self.follow_clock,
+            send_clock: self.send_clock,
+        }
+    }
+
+    fn update_sequence(&mut self) {
+        self.sequence = Arc::new(match &self.file {
+            Some(file) => Sequence::from_file(file),
+            None => Sequence::empty(self.bpm),
+        });
+        let _ = self.from_ui.send(FromUI::Sequence(self.sequence.clone()));
+    }
+
+    /// Replaces what plays, for files that don't come from a dependency
+    pub fn set_midi_file(&mut self, file: Option<MidiFile>) {
+        self.file = file;
+        self.update_sequence();
+    }
+
+    pub fn midi_file(&self) -> Option<&MidiFile> {
+        self.file.as_ref()
+    }
+
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.sequence.tempo_map
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+        let _ = self.from_ui.send(FromUI::Play);
+    }
+
+    /// Stops and turns off the notes the sequence was playing, the position
+    /// stays where it is
+    pub fn stop(&mut self) {
+        self.playing = false;
+        let _ = self.from_ui.send(FromUI::Stop);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn seek(&mut self, tick: f64) {
+        self.position.store(tick.max(0.0).to_bits(), Ordering::Relaxed);
+        let _ = self.from_ui.send(FromUI::Seek(tick.max(0.0)));
+    }
+
+    pub fn seek_seconds(&mut self, seconds: f64) {
+        self.seek(self.sequence.tempo_map.seconds_to_tick(seconds));
+    }
+
+    /// The tick the audio thread got to
+    pub fn position(&self) -> f64 {
+        f64::from_bits(self.position.load(Ordering::Relaxed))
+    }
+
+    pub fn position_seconds(&self) -> f64 {
+        self.sequence.tempo_map.tick_to_seconds(self.position())
+    }
+
+    /// Records the midi the node gets while it plays, on the tick it was at
+    /// when the block it came in started
+    pub fn record(&mut self, recording: bool) {
+        self.recording = recording;
+        let _ = self.from_ui.send(FromUI::Record(recording));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// What was recorded so far as a type 1 file, the first track has the
+    /// tempo map. The recording starts over
+    pub fn take_recording(&mut self) -> MidiFile {
+        let tempo_map = &self.sequence.tempo_map;
+        let mut file = match &self.file {
+            Some(source) => MidiFile {format: 1, division: source.division, tracks: Vec::new()},
+            None => MidiFile::new(TICKS_PER_QUARTER),
+        };
+        let mut conductor = MidiTrack::default();
+        if let MidiTimeDivision::TicksPerQuarter(_) = file.division {
+            for (tick, tempo) in tempo_map.events() {
+                conductor.push(tick, MidiFileEvent::Tempo(tempo));
+            }
+        }
+        file.tracks.push(conductor);
+        file.tracks.push(std::mem::take(&mut self.recorded));
+        file
+    }
+
+    /// Where clock and transport messages go to when sending clock
+    pub fn set_midi_output(&mut self, output: Option<MidiOutput>) {
+        let _ = self.from_ui.send(FromUI::MidiOutput(output));
+    }
+
+    /// The node playing into `instrument` instead of the instrument component,
+    /// for rendering a file through any node offline. A new node starts out
+    /// stopped at the current position
+    pub fn graph_node_with(&mut self, instrument: Option<Box<dyn AudioGraphNode + Send>>) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        self.playing = false;
+        let tick = self.position();
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            to_ui: self.to_ui.sender(),
+            instrument,
+            sequence: self.sequence.clone(),
+            settings: self.settings(),
+            midi_output: None,
+            playing: false,
+            recording: self.recording,
+            tick,
+            next_event: self.sequence.events.partition_point( | e | (e.0 as f64) < tick - TICK_EPSILON),
+            next_pulse: (tick * CLOCK_PULSES / self.sequence.tempo_map.ticks_per_quarter() - TICK_EPSILON).ceil().max(0.0) as u64,
+            sounding: Vec::new(),
+            clock: ClockTempo::default(),
+            clock_tick: None,
+            frame: 0,
+            sample_rate: 0.0,
+            block: AudioBuffer::default(),
+            input_block: AudioBuffer::default(),
+            position: self.position.clone(),
+        })
+    }
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    to_ui: ToUISender<ToUI>,
+    instrument: Option<Box<dyn AudioGraphNode + Send>>,
+    sequence: Arc<Sequence>,
+    settings: SequencerSettings,
+    midi_output: Option<MidiOutput>,
+    playing: bool,
+    recording: bool,
+    tick: f64,
+    next_event: usize,
+    next_pulse: u64,
+    // the notes the sequence turned on, to turn off when it stops or jumps
+    sounding: Vec<(u8, u8)>,
+    clock: ClockTempo,
+    // while an external clock runs the transport, the tick of its last
+    // pulse. The position doesn't run more than a pulse ahead of it
+    clock_tick: Option<f64>,
+    frame: u64,
+    sample_rate: f64,
+    block: AudioBuffer,
+    input_block: AudioBuffer,
+    position: Arc<AtomicU64>,
+}
+
+impl Node {
+    fn pulse_ticks(&self) -> f64 {
+        self.sequence.tempo_map.ticks_per_quarter() / CLOCK_PULSES
+    }
+
+    fn is_sending_clock(&self) -> bool {
+        self.settings.send_clock && self.clock_tick.is_none()
+    }
+
+    fn ticks_per_second(&self) -> f64 {
+        let map = &self.sequence.tempo_map;
+        let clock = if self.settings.follow_clock {self.clock.bpm(self.frame, self.sample_rate as f32)} else {None};
+        // a tick a rounding error short of a tempo change is on it
+        let bpm = clock.map_or_else( || map.bpm_at(self.tick + TICK_EPSILON), | bpm | bpm as f64);
+        bpm * map.ticks_per_quarter() / 60.0
+    }
+
+    fn send(&mut self, data: MidiData) {
+        if let Some(instrument) = &mut self.instrument {
+            instrument.handle_midi_data(data);
+        }
+    }
+
+    fn send_clock(&mut self, data: MidiData) {
+        self.send(data);
+        if let Some(output) = &self.midi_output {
+            output.send(None, data);
+        }
+    }
+
+    fn notes_off(&mut self) {
+        for (channel, note) in std::mem::take(&mut self.sounding) {
+            self.send(MidiData {data: [0x80 | channel, note, 0]});
+        }
+    }
+
+    fn jump(&mut self, tick: f64) {
+        self.notes_off();
+        self.tick = tick;
+        self.next_event = self.sequence.events.partition_point( | e | (e.0 as f64) < tick - TICK_EPSILON);
+        self.next_pulse = (tick / self.pulse_ticks() - TICK_EPSILON).ceil().max(0.0) as u64;
+        if self.clock_tick.is_some() {
+            self.clock_tick = Some(tick - self.pulse_ticks());
+        }
+        if self.is_sending_clock() {
+            // song position is counted in sixteenth notes
+            let position = (tick / (self.pulse_ticks() * 6.0)) as u32;
+            self.send_clock(MidiData {data: [0xf2, (position & 0x7f) as u8, ((position >> 7) & 0x7f) as u8]});
+        }
+    }
+
+    fn start(&mut self) {
+        if self.playing {
+            return
+        }
+        self.playing = true;
+        let _ = self.to_ui.send(ToUI::Playing(true));
+        if self.is_sending_clock() {
+            self.send_clock(MidiData {data: [if self.tick <= TICK_EPSILON {0xfa} else {0xfb}, 0, 0]});
+        }
+    }
+
+    fn stop(&mut self) {
+        if !self.playing {
+            return
+        }
+        self.playing = false;
+        self.notes_off();
+        let _ = self.to_ui.send(ToUI::Playing(false));
+        if self.is_sending_clock() {
+            self.send_clock(MidiData {data: [0xfc, 0, 0]});
+        }
+    }
+
+    fn handle_clock(&mut self, data: MidiData) {
+        let pulse_ticks = self.pulse_ticks();
+        match data.data[0] {
+            0xf8 => {
+                self.clock.pulse(self.frame, self.sample_rate as f32);
+                if let Some(clock_tick) = &mut self.clock_tick {
+                    *clock_tick += pulse_ticks;
+                    // running behind the clock, catch up
+                    if self.tick < *clock_tick {
+                        self.tick = *clock_tick;
+                    }
+                }
+            }
+            0xfa => {
+                // the first pulse after start is tick 0
+                self.clock_tick = Some(0.0);
+                self.jump(0.0);
+                self.start();
+            }
+            0xfb => {
+                self.clock_tick = Some(self.tick - pulse_ticks);
+                self.start();
+            }
+            0xfc => {
+                self.stop();
+                self.clock_tick = None;
+            }
+            0xf2 => {
+                let position = data.data[1] as u32 | ((data.data[2] as u32) << 7);
+                self.jump(position as f64 * pulse_ticks * 6.0);
+            }
+            _ => ()
+        }
+    }
+
+    /// Sends the messages that are due on the current tick
+    fn fire_events(&mut self) {
+        while let Some((tick, data)) = self.sequence.events.get(self.next_event).copied() {
+            if tick as f64 > self.tick + TICK_EPSILON {
+                break
+            }
+            self.next_event += 1;
+            let channel = data.channel();
+            match data.decode() {
+                MidiEvent::Note(note) if note.is_on => self.sounding.push((channel, note.note_number)),
+                MidiEvent::Note(note) => self.sounding.retain( | s | *s != (channel, note.note_number)),
+                _ => ()
+            }
+            self.send(data);
+        }
+    }
+
+    fn fire_pulses(&mut self) {
+        if self.is_sending_clock() {
+            while self.next_pulse as f64 * self.pulse_ticks() <= self.tick + TICK_EPSILON {
+                self.next_pulse += 1;
+                self.send_clock(MidiData {data: [0xf8, 0, 0]});
+            }
+        }
+    }
+
+    /// The first tick after the current one something happens on
+    fn next_tick(&self) -> Option<f64> {
+        let mut next = self.sequence.events.get(self.next_event).map( | e | e.0 as f64);
+        let mut min = | tick: f64 | {
+            if tick > self.tick + TICK_EPSILON {
+                next = Some(next.map_or(tick, | n | n.min(tick)));
+            }
+        };
+        if self.is_sending_clock() {
+            min(self.next_pulse as f64 * self.pulse_ticks());
+        }
+        if let Some(change) = self.sequence.tempo_map.next_change(self.tick + TICK_EPSILON) {
+            min(change as f64);
+        }
+        min(self.sequence.end_tick as f64);
+        next
+    }
+
+    fn is_at_end(&self) -> bool {
+        let end = self.sequence.end_tick;
+        end > 0 && self.tick >= end as f64 - TICK_EPSILON && self.next_event >= self.sequence.events.len()
+    }
+
+    fn render_part(&mut self, info: AudioInfo, output: &mut AudioBuffer, inputs: &[&AudioBuffer], display: &mut DisplayAudioGraph, offset: usize, len: usize) {
+        let Some(instrument) = &mut self.instrument else {return};
+        if offset == 0 && len == output.frame_count() {
+            instrument.render_to_audio_buffer(info, &mut [output], inputs, display);
+            return
+        }
+        let info = AudioInfo {
+            time: info.time.map( | t | AudioTime {sample_time: t.sample_time + offset as f64, ..t}),
+            ..info
+        };
+        self.block.resize(len, output.channel_count());
+        self.block.zero();
+        if let Some(input) = inputs.first() {
+            self.input_block.resize(len, input.channel_count());
+            for c in 0..input.channel_count() {
+                self.input_block.channel_mut(c).copy_from_slice(&input.channel(c)[offset..offset + len]);
+            }
+            instrument.render_to_audio_buffer(info, &mut [&mut self.block], &[&self.input_block], display);
+        }
+        else {
+            instrument.render_to_audio_buffer(info, &mut [&mut self.block], &[], display);
+        }
+        for c in 0..output.channel_count() {
+            output.channel_mut(c)[offset..offset + len].copy_from_slice(self.block.channel(c));
+        }
+    }
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+        self.sounding.clear();
+        if let Some(instrument) = &mut self.instrument {
+            instrument.all_notes_off();
+        }
+    }
+
+    fn handle_midi_data(&mut self, data: MidiData) {
+        if self.settings.follow_clock {
+            self.handle_clock(data);
+        }
+        if self.recording && self.playing && data.data[0] < 0xf0 {
+            let _ = self.to_ui.send(ToUI::Recorded(self.tick.round() as u64, data));
+        }
+        self.send(data);
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(msg) = self.from_ui.try_recv() {
+            match msg {
+                FromUI::Sequence(sequence) => {
+                    self.sequence = sequence;
+                    self.jump(self.tick);
+                }
+                FromUI::Settings(settings) => {
+                    self.settings = settings;
+                    if !settings.follow_clock {
+                        self.clock_tick = None;
+                    }
+                }
+                FromUI::Play => self.start(),
+                FromUI::Stop => self.stop(),
+                FromUI::Seek(tick) => self.jump(tick),
+                FromUI::Record(recording) => self.recording = recording,
+                FromUI::MidiOutput(output) => self.midi_output = output,
+            }
+        }
+        self.sample_rate = info.sample_rate;
+        let output = &mut outputs[0];
+        output.zero();
+        let frame_count = output.frame_count();
+        let mut done = 0;
+        while done < frame_count {
+            let mut len = frame_count - done;
+            if self.playing {
+                self.fire_events();
+                if self.is_at_end() {
+                    if self.settings.looping {
+                        self.jump(0.0);
+                        self.fire_events();
+                    }
+                    // a recording goes on past the end
+                    else if !self.recording {
+                        self.stop();
+                    }
+                }
+            }
+            if self.playing {
+                self.fire_pulses();
+            }
+            let ticks_per_frame = self.ticks_per_second() / info.sample_rate;
+            if self.playing {
+                if let Some(next) = self.next_tick() {
+                    let frames = ((next - self.tick - TICK_EPSILON) / ticks_per_frame).ceil().max(1.0);
+                    len = len.min(frames as usize);
+                }
+            }
+            self.render_part(info, output, inputs, display, done, len);
+            if self.playing {
+                self.tick += len as f64 * ticks_per_frame;
+                if let Some(clock_tick) = self.clock_tick {
+                    self.tick = self.tick.min(clock_tick + self.pulse_ticks());
+                }
+            }
+            done += len;
+        }
+        self.frame += frame_count as u64;
+        self.position.store(self.tick.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl AudioComponent for MidiSequencer {
+    fn get_graph_node(&mut self, cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        let instrument = self.instrument.as_mut().map( | i | i.get_graph_node(cx));
+        self.graph_node_with(instrument)
+    }
+
+    fn handle_event_with(&mut self, cx: &mut Cx, event: &Event, dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+        while let Ok(msg) = self.to_ui.try_recv() {
+            match msg {
+                ToUI::Playing(playing) => self.playing = playing,
+                ToUI::Recorded(tick, data) => self.recorded.push(tick, MidiFileEvent::Midi(data)),
+            }
+        }
+        if let Some(instrument) = self.instrument.as_mut() {
+            instrument.handle_event_with(cx, event, dispatch_action);
+        }
+    }
+
+    fn audio_query(&mut self, query: &AudioQuery, callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        self.instrument.audio_query(query, callback)
+    }
+}
@@ -0,0 +1,155 @@
+use {
+    crate::{
+        makepad_platform::*,
+        audio_traits::*,
+        wav::*,
+    },
+    std::{io, path::Path},
+};
+
+/// Pulls an audio graph without a sound card, as fast as it will go.
+/// Midi is delivered on the exact frame it was queued for by splitting
+/// the render blocks around it
+pub struct OfflineRender {
+    root: Box<dyn AudioGraphNode + Send>,
+    sample_rate: f64,
+    channel_count: usize,
+    block_size: usize,
+    frame: u64,
+    // sorted on frame, events on the same frame keep their queue order
+    midi: Vec<(u64, MidiData)>,
+    block: AudioBuffer,
+    // the external input fed to the graph, for running effects over a sound
+    input: Option<AudioBuffer>,
+    input_block: AudioBuffer,
+    to_ui: ToUIReceiver<ToUIDisplayMsg>,
+    display_buffers: Vec<AudioBuffer>,
+}
+
+impl OfflineRender {
+    pub fn new(root: Box<dyn AudioGraphNode + Send>, sample_rate: f64) -> Self {
+        let mut display_buffers = Vec::new();
+        for _ in 0..64 {
+            display_buffers.push(AudioBuffer::new_with_size(512, 2));
+        }
+        Self {
+            root,
+            sample_rate,
+            channel_count: 2,
+            block_size: 512,
+            frame: 0,
+            midi: Vec::new(),
+            block: AudioBuffer::default(),
+            input: None,
+            input_block: AudioBuffer::default(),
+            to_ui: ToUIReceiver::default(),
+            display_buffers,
+        }
+    }
+
+    pub fn with_channel_count(mut self, channel_count: usize) -> Self {
+        self.channel_count = channel_count.max(1);
+        self
+    }
+
+    /// The most frames the graph is asked for at once, like the buffer size
+    /// of a device
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self
+    }
+
+    /// Feeds `input` to the graph as its input, starting at frame 0. Past its
+    /// end the graph gets silence so effect tails can be rendered
+    pub fn with_input(mut self, input: AudioBuffer) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    pub fn sample_rate(&self) -> f64 {self.sample_rate}
+    pub fn channel_count(&self) -> usize {self.channel_count}
+
+    /// The frame the next render starts at
+    pub fn frame(&self) -> u64 {self.frame}
+
+    /// Queues midi for a frame counted from the start of the render. Frames
+    /// already rendered get it at the start of the next render
+    pub fn queue_midi(&mut self, frame: u64, data: MidiData) {
+        let index = self.midi.partition_point( | (f, _) | *f <= frame);
+        self.midi.insert(index, (frame, data));
+    }
+
+    pub fn queue_midi_at_time(&mut self, time: f64, data: MidiData) {
+        self.queue_midi((time * self.sample_rate).round().max(0.0) as u64, data);
+    }
+
+    pub fn all_notes_off(&mut self) {
+        self.root.all_notes_off();
+    }
+
+    pub fn render(&mut self, frame_count: usize) -> AudioBuffer {
+        let mut output = AudioBuffer::new_with_size(frame_count, self.channel_count);
+        let mut done = 0;
+        while done < frame_count {
+            let mut len = self.block_size.min(frame_count - done);
+            while let Some((frame, data)) = self.midi.first().cloned() {
+                if frame > self.frame {
+                    len = len.min((frame - self.frame) as usize);
+                    break
+                }
+                self.midi.remove(0);
+                self.root.handle_midi_data(data);
+            }
+            self.render_block(len);
+            for c in 0..self.channel_count {
+                output.channel_mut(c)[done..done + len].copy_from_slice(self.block.channel(c));
+            }
+            done += len;
+        }
+        output
+    }
+
+    fn render_block(&mut self, frame_count: usize) {
+        self.block.resize(frame_count, self.channel_count);
+        self.block.zero();
+        let info = AudioInfo {
+            device_id: AudioDeviceId(LiveId(0)),
+            time: Some(AudioTime {
+                sample_time: self.frame as f64,
+                host_time: 0,
+                rate_scalar: 1.0,
+            }),
+            sample_rate: self.sample_rate,
+        };
+        let to_ui = self.to_ui.sender();
+        let mut display = DisplayAudioGraph {
+            to_ui: &to_ui,
+            buffers: &mut self.display_buffers,
+        };
+        if let Some(input) = &self.input {
+            self.input_block.resize(frame_count, input.channel_count());
+            self.input_block.zero();
+            let start = (self.frame as usize).min(input.frame_count());
+            let end = (start + frame_count).min(input.frame_count());
+            for c in 0..input.channel_count() {
+                self.input_block.channel_mut(c)[..end - start].copy_from_slice(&input.channel(c)[start..end]);
+            }
+            self.root.render_to_audio_buffer(info, &mut [&mut self.block], &[&self.input_block], &mut display);
+        }
+        else {
+            self.root.render_to_audio_buffer(info, &mut [&mut self.block], &[], &mut display);
+        }
+        // nobody looks at the display audio, hand the buffers straight back
+        while let Ok(msg) = self.to_ui.try_recv() {
+            if let ToUIDisplayMsg::DisplayAudio {buffer, ..} = msg {
+                self.display_buffers.push(buffer);
+            }
+        }
+        self.frame += frame_count as u64;
+    }
+
+    pub fn render_to_wav(&mut self, path: impl AsRef<Path>, frame_count: usize, format: WavFormat) -> io::Result<()> {
+        let buffer = self.render(frame_count);
+        write_wav(path, &buffer, self.sample_rate as u32, format)
+    }
+}
@@ -0,0 +1,395 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::copy_input,
+        meter::*,
+        wav::*,
+        flac::*,
+    },
+    std::{
+        fs::File,
+        io::BufWriter,
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+            mpsc::{sync_channel, Receiver, SyncSender},
+        },
+    },
+};
+
+live_design!{
+    pub AudioRecorder = {{AudioRecorder}} {
+    }
+}
+
+// The audio thread doesn't allocate: it copies into buffers from a pool the
+// writer thread hands back when they are written, a block that doesn't fit one
+// goes out in parts, and the queues have room for every buffer there is. A take
+// starts with stereo buffers for its first blocks, and the writer adds as many
+// again sized to the channels and block size of the first block
+const POOL_BUFFERS: usize = 64;
+const POOL_FRAMES: usize = 4096;
+const QUEUE_LEN: usize = 2 * POOL_BUFFERS + 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Live, LiveHook)]
+#[live_ignore]
+pub enum RecordFormat {
+    Wav16,
+    #[pick] Wav24,
+    WavFloat,
+    Flac16,
+    Flac24,
+}
+
+/// A take that was written to disk
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedTake {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub channel_count: usize,
+    pub frame_count: u64,
+    // frames lost because the writer thread couldn't keep up
+    pub dropped_frames: u64,
+    // frames past the 4 GB a wav file holds, the file ends before them
+    pub cut_frames: u64,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct RecorderSettings {
+    punch_in: f64,
+    punch_out: f64,
+    meter_id: usize,
+    meter_rate: f64,
+}
+
+enum WriterMsg {
+    Format {sample_rate: u32, channel_count: usize, block_frames: usize},
+    Audio(AudioBuffer),
+    // the pool comes along so its buffers are freed on the writer thread
+    Stop(Receiver<AudioBuffer>),
+}
+
+// the ends of a take the audio thread holds
+struct Take {
+    to_writer: SyncSender<WriterMsg>,
+    pool: Receiver<AudioBuffer>,
+    dropped_frames: Arc<AtomicU64>,
+}
+
+enum FromUI {
+    Settings(RecorderSettings),
+    Start(Take),
+    Stop,
+    Punch(bool),
+}
+
+enum ToUI {
+    Finished(Result<RecordedTake, String>),
+}
+
+/// Passes its input through and meters it, and records it to a wav or flac
+/// file while a take runs. Meter readings go out `meter_rate` times a second
+/// as `AudioGraphAction::Meter` with `meter_id`. A take only writes between
+/// `punch_in` and `punch_out` seconds from its start, a `punch_out` of 0
+/// doesn't end it, and only while punched in with `punch`. The file is
+/// written on a thread of its own
+#[derive(Live)]
+pub struct AudioRecorder {
+    #[live] format: RecordFormat,
+    #[live(0.0)] punch_in: f64,
+    #[live(0.0)] punch_out: f64,
+    #[live] meter_id: usize,
+    #[live(30.0)] meter_rate: f64,
+    #[rust] recording: bool,
+    #[rust] finished: Option<Result<RecordedTake, String>>,
+    #[rust] from_ui: FromUISender<FromUI>,
+    #[rust] to_ui: ToUIReceiver<ToUI>,
+}
+
+impl LiveRegister for AudioRecorder {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, AudioRecorder)
+    }
+}
+
+impl LiveHook for AudioRecorder {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl AudioRecorder {
+    fn settings(&self) -> RecorderSettings {
+        RecorderSettings {
+            punch_in: self.punch_in.max(0.0),
+            punch_out: self.punch_out.max(0.0),
+            meter_id: self.meter_id,
+            meter_rate: self.meter_rate,
+        }
+    }
+
+    /// Starts a take into `path`, a take that is running is stopped first
+    pub fn record(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        self.stop();
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err( | e | format!("can't create {}: {}", path.display(), e)) ?;
+        let (to_writer, from_node) = sync_channel(QUEUE_LEN);
+        let (to_pool, pool) = sync_channel(2 * POOL_BUFFERS);
+        for _ in 0..POOL_BUFFERS {
+            let _ = to_pool.send(AudioBuffer::new_with_size(POOL_FRAMES, 2));
+        }
+        let dropped_frames = Arc::new(AtomicU64::new(0));
+        let format = self.format;
+        let to_ui = self.to_ui.sender();
+        let dropped = dropped_frames.clone();
+        std::thread::spawn(move || {
+            let result = write_take(BufWriter::new(file), &path, format, from_node, to_pool, &dropped);
+            let _ = to_ui.send(ToUI::Finished(result));
+        });
+        self.finished = None;
+        self.recording = true;
+        let _ = self.from_ui.send(FromUI::Start(Take {to_writer, pool, dropped_frames}));
+        Ok(())
+    }
+
+    /// Ends the take, `finished_take` has it when the file is written
+    pub fn stop(&mut self) {
+        if self.recording {
+            self.recording = false;
+            let _ = self.from_ui.send(FromUI::Stop);
+        }
+    }
+
+    pub fn punch(&mut self, punched_in: bool) {
+        let _ = self.from_ui.send(FromUI::Punch(punched_in));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn finished_take(&mut self) -> Option<Result<RecordedTake, String>> {
+        self.finished.take()
+    }
+
+    pub fn graph_node(&mut self) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        // a take doesn't move over to a new node, its writer finishes it
+        self.recording = false;
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            settings: self.settings(),
+            meter: LevelMeter::new(2, 48000.0),
+            meter_frames: 0,
+            sample_rate: 0.0,
+            take: None,
+            take_frame: 0,
+            channel_count: 0,
+            punched_in: true,
+            started: false,
+        })
+    }
+}
+
+enum TakeWriter {
+    Wav(WavWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+fn write_take(file: BufWriter<File>, path: &Path, format: RecordFormat, from_node: Receiver<WriterMsg>, to_pool: SyncSender<AudioBuffer>, dropped_frames: &AtomicU64) -> Result<RecordedTake, String> {
+    let error = | e: std::io::Error | format!("can't write {}: {}", path.display(), e);
+    let mut file = Some(file);
+    let mut writer = None;
+    let mut take = RecordedTake {
+        path: path.to_path_buf(),
+        sample_rate: 0,
+        channel_count: 0,
+        frame_count: 0,
+        dropped_frames: 0,
+        cut_frames: 0,
+    };
+    // a node that goes away ends the take like a stop
+    while let Ok(msg) = from_node.recv() {
+        match msg {
+            WriterMsg::Format {sample_rate, channel_count, block_frames} => if let Some(file) = file.take() {
+                take.sample_rate = sample_rate;
+                take.channel_count = channel_count;
+                for _ in 0..POOL_BUFFERS {
+                    let _ = to_pool.send(AudioBuffer::new_with_size(block_frames.max(1), channel_count));
+                }
+                writer = Some(match format {
+                    RecordFormat::Wav16 => TakeWriter::Wav(WavWriter::new(file, sample_rate, channel_count, WavFormat::Pcm16).map_err(error) ?),
+                    RecordFormat::Wav24 => TakeWriter::Wav(WavWriter::new(file, sample_rate, channel_count, WavFormat::Pcm24).map_err(error) ?),
+                    RecordFormat::WavFloat => TakeWriter::Wav(WavWriter::new(file, sample_rate, channel_count, WavFormat::Float32).map_err(error) ?),
+                    RecordFormat::Flac16 => TakeWriter::Flac(FlacWriter::new(file, sample_rate, channel_count, 16).map_err(error) ?),
+                    RecordFormat::Flac24 => TakeWriter::Flac(FlacWriter::new(file, sample_rate, channel_count, 24).map_err(error) ?),
+                });
+            }
+            WriterMsg::Audio(buffer) => {
+                match &mut writer {
+                    Some(TakeWriter::Wav(w)) => w.write(&buffer).map_err(error) ?,
+                    Some(TakeWriter::Flac(w)) => w.write(&buffer).map_err(error) ?,
+                    None => ()
+                }
+                take.frame_count += buffer.frame_count() as u64;
+                let _ = to_pool.send(buffer);
+            }
+            WriterMsg::Stop(_pool) => break,
+        }
+    }
+    take.dropped_frames = dropped_frames.load(Ordering::Relaxed);
+    match writer {
+        Some(TakeWriter::Wav(w)) => {
+            take.cut_frames = w.cut_frames();
+            take.frame_count -= take.cut_frames;
+            w.finish().map_err(error) ?;
+        }
+        Some(TakeWriter::Flac(w)) => {w.finish().map_err(error) ?;}
+        None => return Err(format!("no audio was recorded to {}", path.display()))
+    }
+    Ok(take)
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    settings: RecorderSettings,
+    meter: LevelMeter,
+    meter_frames: usize,
+    sample_rate: f64,
+    take: Option<Take>,
+    // frames since the take started
+    take_frame: u64,
+    // of the take, from its first block
+    channel_count: usize,
+    punched_in: bool,
+    // the writer knows the format of the take
+    started: bool,
+}
+
+impl Node {
+    fn stop_take(&mut self) {
+        if let Some(take) = self.take.take() {
+            let _ = take.to_writer.try_send(WriterMsg::Stop(take.pool));
+        }
+    }
+
+    fn record(&mut self, input: &AudioBuffer) {
+        let Some(take) = &self.take else {return};
+        if !self.started {
+            self.started = true;
+            self.channel_count = input.channel_count().max(1);
+            let _ = take.to_writer.try_send(WriterMsg::Format {
+                sample_rate: self.sample_rate as u32,
+                channel_count: self.channel_count,
+                block_frames: input.frame_count(),
+            });
+        }
+        let frame_count = input.frame_count() as u64;
+        let start = self.take_frame;
+        self.take_frame += frame_count;
+        if !self.punched_in || input.channel_count() == 0 {
+            return
+        }
+        // the part of the block inside the punch range
+        let punch_in = (self.settings.punch_in * self.sample_rate).round() as u64;
+        let punch_out = if self.settings.punch_out > 0.0 {(self.settings.punch_out * self.sample_rate).round() as u64} else {u64::MAX};
+        let from = punch_in.clamp(start, start + frame_count);
+        let to = punch_out.clamp(from, start + frame_count);
+        let (mut from, to) = ((from - start) as usize, (to - start) as usize);
+        while from < to {
+            let Ok(mut buffer) = take.pool.try_recv() else {break};
+            // resized within what it holds
+            let frames = (buffer.data.capacity() / self.channel_count).min(to - from);
+            if frames == 0 {
+                break
+            }
+            buffer.resize(frames, self.channel_count);
+            for c in 0..self.channel_count {
+                buffer.channel_mut(c).copy_from_slice(&input.channel(c.min(input.channel_count() - 1))[from..from + frames]);
+            }
+            // the queue has room for all the buffers, it is never full
+            if take.to_writer.try_send(WriterMsg::Audio(buffer)).is_err() {
+                break
+            }
+            from += frames;
+        }
+        if from < to {
+            take.dropped_frames.fetch_add((to - from) as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(msg) = self.from_ui.try_recv() {
+            match msg {
+                FromUI::Settings(settings) => self.settings = settings,
+                FromUI::Start(take) => {
+                    self.stop_take();
+                    self.take = Some(take);
+                    self.take_frame = 0;
+                    self.punched_in = true;
+                    self.started = false;
+                    self.meter.reset_integrated();
+                }
+                FromUI::Stop => self.stop_take(),
+                FromUI::Punch(punched_in) => self.punched_in = punched_in,
+            }
+        }
+        for output in outputs.iter_mut() {
+            copy_input(inputs, output);
+        }
+        let Some(input) = inputs.first() else {return};
+        let channel_count = input.channel_count().min(MAX_METER_CHANNELS);
+        if channel_count == 0 {
+            return
+        }
+        if info.sample_rate != self.sample_rate || channel_count != self.meter.channel_count() {
+            self.sample_rate = info.sample_rate;
+            self.meter.set_format(channel_count, info.sample_rate);
+            self.meter_frames = 0;
+        }
+        self.record(input);
+        self.meter.process(input);
+        if self.settings.meter_rate > 0.0 {
+            self.meter_frames += input.frame_count();
+            if self.meter_frames as f64 >= self.sample_rate / self.settings.meter_rate {
+                self.meter_frames = 0;
+                display.send_meter(self.settings.meter_id, self.meter.reading());
+            }
+        }
+    }
+}
+
+impl AudioComponent for AudioRecorder {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.graph_node()
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+        while let Ok(msg) = self.to_ui.try_recv() {
+            match msg {
+                ToUI::Finished(result) => self.finished = Some(result),
+            }
+        }
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
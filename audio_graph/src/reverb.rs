@@ -0,0 +1,269 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        dsp::*,
+    },
+};
+
+live_design!{
+    pub Reverb = {{Reverb}} {
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct ReverbSettings {
+    decay: f32,
+    damping: f32,
+    predelay: f32,
+    mix: f32,
+    width: f32,
+}
+
+enum FromUI {
+    Settings(ReverbSettings),
+}
+
+/// A plate reverb after Dattorro's figure-of-eight tank. `decay` and `damping`
+/// go from 0 to 1, `predelay` is in seconds and `width` spreads the tail
+/// from mono at 0 to full stereo at 1
+#[derive(Live)]
+pub struct Reverb {
+    #[live(0.5)] decay: f64,
+    #[live(0.3)] damping: f64,
+    #[live(0.01)] predelay: f64,
+    #[live(0.25)] mix: f64,
+    #[live(1.0)] width: f64,
+    #[rust] from_ui: FromUISender<FromUI>,
+}
+
+impl LiveRegister for Reverb {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, Reverb)
+    }
+}
+
+impl LiveHook for Reverb {
+    fn after_apply(&mut self, _cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl Reverb {
+    fn settings(&self) -> ReverbSettings {
+        ReverbSettings {
+            decay: self.decay.clamp(0.0, 1.0) as f32,
+            damping: self.damping.clamp(0.0, 1.0) as f32,
+            predelay: self.predelay.clamp(0.0, MAX_PREDELAY as f64) as f32,
+            mix: self.mix.clamp(0.0, 1.0) as f32,
+            width: self.width.clamp(0.0, 1.0) as f32,
+        }
+    }
+}
+
+const MAX_PREDELAY: f32 = 0.5;
+// the delays of the paper are given at this rate
+const PLATE_RATE: f32 = 29761.0;
+const INPUT_DIFFUSERS: [(f32, f32); 4] = [(142.0, 0.75), (107.0, 0.75), (379.0, 0.625), (277.0, 0.625)];
+// per half of the tank: modulated allpass, delay, allpass, delay
+const TANK: [[f32; 4]; 2] = [[672.0, 4453.0, 1800.0, 3720.0], [908.0, 4217.0, 2656.0, 3163.0]];
+const EXCURSION: f32 = 16.0;
+// output taps as (half, line, position, sign), line 0 is the first delay,
+// 1 the second allpass and 2 the last delay
+const LEFT_TAPS: [(usize, usize, f32, f32); 7] = [
+    (1, 0, 266.0, 1.0), (1, 0, 2974.0, 1.0), (1, 1, 1913.0, -1.0), (1, 2, 1996.0, 1.0),
+    (0, 0, 1990.0, -1.0), (0, 1, 187.0, -1.0), (0, 2, 1066.0, -1.0),
+];
+const RIGHT_TAPS: [(usize, usize, f32, f32); 7] = [
+    (0, 0, 353.0, 1.0), (0, 0, 3627.0, 1.0), (0, 1, 1228.0, -1.0), (0, 2, 2673.0, 1.0),
+    (1, 0, 2111.0, -1.0), (1, 1, 335.0, -1.0), (1, 2, 121.0, -1.0),
+];
+
+fn scaled(length: f32, sample_rate: f32) -> usize {
+    (length * sample_rate / PLATE_RATE).round().max(1.0) as usize
+}
+
+fn plate_line(length: f32) -> DelayLine {
+    DelayLine::new(scaled(length + EXCURSION, MAX_SAMPLE_RATE) + 1)
+}
+
+// a schroeder allpass running on a delay line, the line holds the inner
+// signal so the output taps can read it
+fn allpass(line: &mut DelayLine, length: f32, coef: f32, input: f32) -> f32 {
+    let delayed = line.tap_frac(length - 1.0);
+    let inner = input + coef * delayed;
+    line.push(inner);
+    delayed - coef * inner
+}
+
+struct TankHalf {
+    modulated: DelayLine,
+    // first delay, second allpass, last delay
+    lines: [DelayLine; 3],
+    damping: OnePole,
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    predelay: DelayLine,
+    bandwidth: OnePole,
+    diffusers: [DelayLine; 4],
+    tank: [TankHalf; 2],
+    lfo_phase: f32,
+    decay: Smoothed,
+    damping: Smoothed,
+    predelay_frames: Smoothed,
+    predelay_seconds: f32,
+    mix: Smoothed,
+    width: Smoothed,
+    sample_rate: f32,
+}
+
+impl Node {
+    fn clear(&mut self) {
+        self.predelay.clear();
+        self.bandwidth.reset();
+        self.diffusers.iter_mut().for_each( | d | d.clear());
+        for half in &mut self.tank {
+            half.modulated.clear();
+            half.lines.iter_mut().for_each( | l | l.clear());
+            half.damping.reset();
+        }
+    }
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+    }
+
+    fn handle_midi_data(&mut self, _data: MidiData) {
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(FromUI::Settings(settings)) = self.from_ui.try_recv() {
+            self.decay.set_target(settings.decay);
+            self.damping.set_target(settings.damping);
+            self.predelay_seconds = settings.predelay;
+            self.predelay_frames.set_target(settings.predelay * self.sample_rate);
+            self.mix.set_target(settings.mix);
+            self.width.set_target(settings.width);
+        }
+        let sr = (info.sample_rate as f32).min(MAX_SAMPLE_RATE);
+        if self.sample_rate != sr {
+            self.sample_rate = sr;
+            self.clear();
+            for smoothed in [&mut self.decay, &mut self.damping, &mut self.mix, &mut self.width] {
+                smoothed.set_time(0.05, sr);
+            }
+            // slow, a quick change of the predelay would warble
+            self.predelay_frames.set_time(0.2, sr);
+            self.predelay_frames.jump(self.predelay_seconds * sr);
+        }
+        let scale = sr / PLATE_RATE;
+        let lfo_step = std::f32::consts::TAU * 1.0 / sr;
+        let bandwidth = 0.9995f32.powf(PLATE_RATE / sr);
+
+        let output = &mut outputs[0];
+        copy_input(inputs, output);
+        let channel_count = output.channel_count();
+        if channel_count == 0 {
+            return
+        }
+        for i in 0..output.frame_count() {
+            let mut input = 0.0;
+            for c in 0..channel_count {
+                input += output.channel(c)[i];
+            }
+            input /= channel_count as f32;
+
+            let decay = self.decay.tick() * 0.98;
+            let damping = self.damping.tick();
+            self.predelay.push(input);
+            let mut x = self.predelay.tap_frac(self.predelay_frames.tick());
+            x = self.bandwidth.lowpass(x, bandwidth);
+            for (line, (length, coef)) in self.diffusers.iter_mut().zip(INPUT_DIFFUSERS) {
+                x = allpass(line, length * scale, coef, x);
+            }
+
+            self.lfo_phase = (self.lfo_phase + lfo_step) % std::f32::consts::TAU;
+            let wobble = self.lfo_phase.sin() * EXCURSION * scale;
+            // each half is fed by the end of the other one
+            let ends = [
+                self.tank[1].lines[2].tap(scaled(TANK[1][3], sr) - 1),
+                self.tank[0].lines[2].tap(scaled(TANK[0][3], sr) - 1),
+            ];
+            for (h, half) in self.tank.iter_mut().enumerate() {
+                let lengths = TANK[h];
+                let wobble = if h == 0 {wobble} else {-wobble};
+                let mut v = x + ends[h] * decay;
+                v = allpass(&mut half.modulated, lengths[0] * scale + wobble, -0.7, v);
+                let delayed = half.lines[0].tap(scaled(lengths[1], sr) - 1);
+                half.lines[0].push(v);
+                let v = half.damping.lowpass(delayed, 1.0 - damping * 0.95) * decay;
+                let v = allpass(&mut half.lines[1], lengths[2] * scale, 0.5, v);
+                half.lines[2].push(v);
+            }
+
+            let read = | taps: &[(usize, usize, f32, f32); 7], tank: &[TankHalf; 2] | {
+                taps.iter().map( | (h, l, pos, sign) | tank[*h].lines[*l].tap(scaled(*pos, sr)) * sign).sum::<f32>() * 0.6
+            };
+            let left = read(&LEFT_TAPS, &self.tank);
+            let right = read(&RIGHT_TAPS, &self.tank);
+            let width = self.width.tick();
+            let mid = (left + right) * 0.5;
+            let side = (left - right) * 0.5 * width;
+            let mix = self.mix.tick();
+            for c in 0..channel_count {
+                let wet = match (channel_count, c) {
+                    (1, _) => mid,
+                    (_, 0) => mid + side,
+                    (_, 1) => mid - side,
+                    _ => mid,
+                };
+                let s = &mut output.channel_mut(c)[i];
+                *s = *s * (1.0 - mix) + wet * mix;
+            }
+        }
+    }
+}
+
+impl AudioComponent for Reverb {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        let settings = self.settings();
+        let half = | lengths: [f32; 4] | TankHalf {
+            modulated: plate_line(lengths[0]),
+            lines: [plate_line(lengths[1]), plate_line(lengths[2]), plate_line(lengths[3])],
+            damping: OnePole::default(),
+        };
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            predelay: DelayLine::new((MAX_PREDELAY * MAX_SAMPLE_RATE) as usize + 1),
+            bandwidth: OnePole::default(),
+            diffusers: INPUT_DIFFUSERS.map( | (length, _) | plate_line(length)),
+            tank: [half(TANK[0]), half(TANK[1])],
+            lfo_phase: 0.0,
+            decay: Smoothed::new(settings.decay),
+            damping: Smoothed::new(settings.damping),
+            predelay_frames: Smoothed::new(0.0),
+            predelay_seconds: settings.predelay,
+            mix: Smoothed::new(settings.mix),
+            width: Smoothed::new(settings.width),
+            sample_rate: 0.0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
@@ -0,0 +1,323 @@
+use {
+    crate::{
+        makepad_platform::*,
+        register_audio_component,
+        audio_traits::*,
+        audio_file::*,
+    },
+    std::sync::Arc,
+};
+
+live_design!{
+    pub SamplePlayer = {{SamplePlayer}} {
+    }
+}
+
+/// What a voice does with its note
+#[derive(Copy, Clone, Debug, PartialEq, Live, LiveHook)]
+#[live_ignore]
+pub enum SamplePlayMode {
+    // plays from start to end, note offs are ignored, for drums
+    #[pick] OneShot,
+    // plays until the note is released
+    Gate,
+    // repeats the loop points while the note is held
+    Loop,
+}
+
+// what the audio thread needs to know, sent over whenever it changes
+#[derive(Copy, Clone, Debug)]
+struct SampleSettings {
+    mode: SamplePlayMode,
+    root_note: f64,
+    track_pitch: bool,
+    pitch: f64,
+    gain: f64,
+    velocity_amount: f64,
+    start: f64,
+    end: f64,
+    loop_start: f64,
+    loop_end: f64,
+    release: f64,
+    voices: usize,
+    channel: i64,
+}
+
+enum FromUI {
+    Sample(Option<Arc<AudioFile>>),
+    Settings(SampleSettings),
+}
+
+/// Plays a sound file on midi notes. Positions are fractions of the sample
+/// length, `pitch` is in semitones, `channel` -1 listens to all channels
+#[derive(Live)]
+pub struct SamplePlayer {
+    #[live] source: LiveDependency,
+    #[live] mode: SamplePlayMode,
+    #[live(60.0)] root_note: f64,
+    #[live(true)] track_pitch: bool,
+    #[live(0.0)] pitch: f64,
+    #[live(1.0)] gain: f64,
+    #[live(1.0)] velocity_amount: f64,
+    #[live(0.0)] start: f64,
+    #[live(1.0)] end: f64,
+    #[live(0.0)] loop_start: f64,
+    #[live(1.0)] loop_end: f64,
+    #[live(0.01)] release: f64,
+    #[live(8)] voices: i64,
+    #[live(-1)] channel: i64,
+    #[rust] sample: Option<Arc<AudioFile>>,
+    #[rust] loaded_source: String,
+    #[rust] from_ui: FromUISender<FromUI>,
+}
+
+impl LiveRegister for SamplePlayer {
+    fn live_register(cx: &mut Cx) {
+        register_audio_component!(cx, SamplePlayer)
+    }
+}
+
+impl LiveHook for SamplePlayer {
+    fn after_apply(&mut self, cx: &mut Cx, _apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        let source = self.source.as_str().to_string();
+        if source != self.loaded_source {
+            self.loaded_source = source.clone();
+            if !source.is_empty() {
+                match cx.get_dependency(&source).and_then( | data | decode_audio_file(&data)) {
+                    Ok(file) => self.set_sample(Some(file)),
+                    Err(err) => error!("SamplePlayer: can't load {} {}", source, err)
+                }
+            }
+        }
+        let _ = self.from_ui.send(FromUI::Settings(self.settings()));
+    }
+}
+
+impl SamplePlayer {
+    /// Replaces the sound, for samples that don't come from a dependency
+    pub fn set_sample(&mut self, sample: Option<AudioFile>) {
+        self.sample = sample.map(Arc::new);
+        let _ = self.from_ui.send(FromUI::Sample(self.sample.clone()));
+    }
+
+    pub fn sample(&self) -> Option<&AudioFile> {
+        self.sample.as_deref()
+    }
+
+    fn settings(&self) -> SampleSettings {
+        SampleSettings {
+            mode: self.mode,
+            root_note: self.root_note,
+            track_pitch: self.track_pitch,
+            pitch: self.pitch,
+            gain: self.gain,
+            velocity_amount: self.velocity_amount,
+            start: self.start.clamp(0.0, 1.0),
+            end: self.end.clamp(0.0, 1.0),
+            loop_start: self.loop_start.clamp(0.0, 1.0),
+            loop_end: self.loop_end.clamp(0.0, 1.0),
+            release: self.release.max(0.0),
+            voices: self.voices.max(1) as usize,
+            channel: self.channel,
+        }
+    }
+}
+
+struct Voice {
+    note: u8,
+    gain: f32,
+    position: f64,
+    // playback speed before the sample rates are accounted for
+    ratio: f64,
+    held: bool,
+    // counts down from 1 to 0 once the voice is let go
+    fade: f32,
+    age: u64,
+}
+
+struct Node {
+    from_ui: FromUIReceiver<FromUI>,
+    sample: Option<Arc<AudioFile>>,
+    settings: SampleSettings,
+    voices: Vec<Voice>,
+    note_count: u64,
+}
+
+impl Node {
+    fn frames(&self) -> (f64, f64, f64, f64) {
+        let len = self.sample.as_ref().map_or(0, | s | s.frame_count()) as f64;
+        let s = &self.settings;
+        let start = s.start.min(s.end) * len;
+        let end = s.end.max(s.start) * len;
+        let loop_start = (s.loop_start.min(s.loop_end) * len).clamp(start, end);
+        let loop_end = (s.loop_end.max(s.loop_start) * len).clamp(start, end);
+        (start, end, loop_start, loop_end)
+    }
+
+    fn note_on(&mut self, note: u8, velocity: u8) {
+        if self.sample.is_none() {
+            return
+        }
+        let s = self.settings;
+        let semitones = s.pitch + if s.track_pitch {note as f64 - s.root_note} else {0.0};
+        let velocity = velocity as f64 / 127.0;
+        let gain = s.gain * (1.0 - s.velocity_amount + s.velocity_amount * velocity);
+        let (start, ..) = self.frames();
+        // a note played again takes over its old voice, else the oldest one goes
+        if let Some(i) = self.voices.iter().position( | v | v.note == note) {
+            self.voices.remove(i);
+        }
+        else if self.voices.len() >= s.voices {
+            let oldest = (0..self.voices.len()).min_by_key( | i | self.voices[*i].age).unwrap();
+            self.voices.remove(oldest);
+        }
+        self.note_count += 1;
+        self.voices.push(Voice {
+            note,
+            gain: gain as f32,
+            position: start,
+            ratio: 2f64.powf(semitones / 12.0),
+            held: true,
+            fade: 1.0,
+            age: self.note_count,
+        });
+    }
+
+    fn note_off(&mut self, note: u8) {
+        for voice in &mut self.voices {
+            if voice.note == note {
+                voice.held = false;
+            }
+        }
+    }
+}
+
+impl AudioGraphNode for Node {
+    fn all_notes_off(&mut self) {
+        self.voices.clear();
+    }
+
+    fn handle_midi_data(&mut self, data: MidiData) {
+        if self.settings.channel >= 0 && data.channel() as i64 != self.settings.channel {
+            return
+        }
+        match data.decode() {
+            MidiEvent::Note(note) if note.is_on && note.velocity > 0 => self.note_on(note.note_number, note.velocity),
+            MidiEvent::Note(note) => self.note_off(note.note_number),
+            _ => ()
+        }
+    }
+
+    fn set_param(&mut self, path: &[LiveId], value: &LiveValue) -> bool {
+        let s = &mut self.settings;
+        let [id] = path else {return false};
+        if *id == live_id!(mode) {
+            s.mode = match value {
+                LiveValue::BareEnum(live_id!(OneShot)) => SamplePlayMode::OneShot,
+                LiveValue::BareEnum(live_id!(Gate)) => SamplePlayMode::Gate,
+                LiveValue::BareEnum(live_id!(Loop)) => SamplePlayMode::Loop,
+                _ => return false
+            };
+            return true
+        }
+        if *id == live_id!(track_pitch) {
+            let Some(v) = value.as_bool() else {return false};
+            s.track_pitch = v;
+            return true
+        }
+        let Some(v) = value.as_float() else {return false};
+        // clamped like the component does
+        match *id {
+            live_id!(root_note) => s.root_note = v,
+            live_id!(pitch) => s.pitch = v,
+            live_id!(gain) => s.gain = v,
+            live_id!(velocity_amount) => s.velocity_amount = v,
+            live_id!(start) => s.start = v.clamp(0.0, 1.0),
+            live_id!(end) => s.end = v.clamp(0.0, 1.0),
+            live_id!(loop_start) => s.loop_start = v.clamp(0.0, 1.0),
+            live_id!(loop_end) => s.loop_end = v.clamp(0.0, 1.0),
+            live_id!(release) => s.release = v.max(0.0),
+            _ => return false
+        }
+        true
+    }
+
+    fn render_to_audio_buffer(
+        &mut self,
+        info: AudioInfo,
+        outputs: &mut [&mut AudioBuffer],
+        _inputs: &[&AudioBuffer],
+        _display: &mut DisplayAudioGraph
+    ) {
+        while let Ok(msg) = self.from_ui.try_recv() {
+            match msg {
+                FromUI::Sample(sample) => {
+                    self.voices.clear();
+                    self.sample = sample;
+                }
+                FromUI::Settings(settings) => self.settings = settings,
+            }
+        }
+        let output = &mut outputs[0];
+        output.zero();
+        let Some(sample) = self.sample.clone() else {return};
+        let buffer = &sample.buffer;
+        let (_, end, loop_start, loop_end) = self.frames();
+        let mode = self.settings.mode;
+        let fade_step = 1.0 / (self.settings.release * info.sample_rate).max(1.0) as f32;
+        let rate_ratio = sample.sample_rate as f64 / info.sample_rate;
+        let looping = mode == SamplePlayMode::Loop && loop_end - loop_start >= 1.0;
+        let last = buffer.frame_count().saturating_sub(1);
+
+        for voice in &mut self.voices {
+            let increment = voice.ratio * rate_ratio;
+            for i in 0..output.frame_count() {
+                if !voice.held && mode != SamplePlayMode::OneShot {
+                    voice.fade -= fade_step;
+                    if voice.fade <= 0.0 {
+                        break
+                    }
+                }
+                if looping && voice.held && voice.position >= loop_end {
+                    voice.position = loop_start + (voice.position - loop_end) % (loop_end - loop_start);
+                }
+                if voice.position >= end {
+                    voice.fade = 0.0;
+                    break
+                }
+                // linear interpolation between the two nearest frames
+                let index = voice.position as usize;
+                let frac = (voice.position - index as f64) as f32;
+                let next = (index + 1).min(last);
+                let gain = voice.gain * voice.fade;
+                for c in 0..output.channel_count() {
+                    let channel = buffer.channel(c % buffer.channel_count());
+                    let value = channel[index] + (channel[next] - channel[index]) * frac;
+                    output.channel_mut(c)[i] += value * gain;
+                }
+                voice.position += increment;
+            }
+        }
+        self.voices.retain( | v | v.fade > 0.0);
+    }
+}
+
+impl AudioComponent for SamplePlayer {
+    fn get_graph_node(&mut self, _cx: &mut Cx) -> Box<dyn AudioGraphNode + Send> {
+        self.from_ui.new_channel();
+        Box::new(Node {
+            from_ui: self.from_ui.receiver(),
+            sample: self.sample.clone(),
+            settings: self.settings(),
+            voices: Vec::new(),
+            note_count: 0,
+        })
+    }
+
+    fn handle_event_with(&mut self, _cx: &mut Cx, _event: &Event, _dispatch_action: &mut dyn FnMut(&mut Cx, AudioComponentAction)) {
+    }
+
+    fn audio_query(&mut self, _query: &AudioQuery, _callback: &mut Option<AudioQueryCb>) -> AudioResult<'_> {
+        AudioResult::not_found()
+    }
+}
@@ -0,0 +1,265 @@
+use {
+    crate::makepad_platform::*,
+    std::{
+        io::{self, Write, Seek, SeekFrom},
+        path::Path,
+    },
+};
+
+/// The sample encodings `encode_wav` can write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavFormat {
+    Pcm16,
+    Pcm24,
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            Self::Pcm16 => 16,
+            Self::Pcm24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+
+    fn format_tag(&self) -> u16 {
+        match self {
+            Self::Pcm16 | Self::Pcm24 => 1,
+            Self::Float32 => 3,
+        }
+    }
+}
+
+/// A decoded wav file, samples are scaled to -1.0..1.0 whatever the format was
+#[derive(Clone, Debug)]
+pub struct WavFile {
+    pub sample_rate: u32,
+    pub format: WavFormat,
+    pub buffer: AudioBuffer,
+}
+
+// the most data a wav file holds, the RIFF size counts the rest of the header
+// and a pad byte along with it
+const MAX_WAV_DATA: u64 = u32::MAX as u64 - 36 - 1;
+
+fn wav_header(channel_count: usize, sample_rate: u32, format: WavFormat, data_size: u64) -> Vec<u8> {
+    let bytes_per_sample = format.bits_per_sample() as usize / 8;
+    // odd sized chunks get a pad byte, 24 bit mono can end up here
+    let pad = data_size & 1;
+    let mut out = Vec::with_capacity(44);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&((36 + data_size + pad) as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&format.format_tag().to_le_bytes());
+    out.extend_from_slice(&(channel_count as u16).to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * (channel_count * bytes_per_sample) as u32).to_le_bytes());
+    out.extend_from_slice(&((channel_count * bytes_per_sample) as u16).to_le_bytes());
+    out.extend_from_slice(&format.bits_per_sample().to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data_size as u32).to_le_bytes());
+    out
+}
+
+// interleaves `channel_count` channels of the first frames of the buffer, a
+// buffer with fewer channels repeats its last one
+fn push_samples(out: &mut Vec<u8>, buffer: &AudioBuffer, frame_count: usize, channel_count: usize, format: WavFormat) {
+    for i in 0..frame_count {
+        for c in 0..channel_count {
+            let sample = buffer.channel(c.min(buffer.channel_count() - 1))[i];
+            match format {
+                WavFormat::Pcm16 => {
+                    let s = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+                    out.extend_from_slice(&s.to_le_bytes());
+                }
+                WavFormat::Pcm24 => {
+                    let s = (sample.clamp(-1.0, 1.0) * 8388607.0).round() as i32;
+                    out.extend_from_slice(&s.to_le_bytes()[0..3]);
+                }
+                WavFormat::Float32 => {
+                    out.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Encodes a buffer as a wav file. Integer formats are clipped and rounded,
+/// float is written as is
+pub fn encode_wav(buffer: &AudioBuffer, sample_rate: u32, format: WavFormat) -> Vec<u8> {
+    let channel_count = buffer.channel_count();
+    let data_size = buffer.frame_count() * channel_count * format.bits_per_sample() as usize / 8;
+    let mut out = wav_header(channel_count, sample_rate, format, data_size as u64);
+    out.reserve(data_size + 1);
+    push_samples(&mut out, buffer, buffer.frame_count(), channel_count, format);
+    if data_size & 1 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+/// Writes a wav file as the audio comes in, for recordings that don't fit
+/// in memory. The sizes in the header are filled in by `finish`
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+    channel_count: usize,
+    format: WavFormat,
+    frame_count: u64,
+    cut_frames: u64,
+    max_data: u64,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32, channel_count: usize, format: WavFormat) -> io::Result<Self> {
+        let channel_count = channel_count.max(1);
+        writer.write_all(&wav_header(channel_count, sample_rate, format, 0)) ?;
+        Ok(Self {writer, sample_rate, channel_count, format, frame_count: 0, cut_frames: 0, max_data: MAX_WAV_DATA, bytes: Vec::new()})
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// The frames that came after the file was full, a wav file holds up to
+    /// 4 GB and `write` leaves out what doesn't fit
+    pub fn cut_frames(&self) -> u64 {
+        self.cut_frames
+    }
+
+    fn bytes_per_frame(&self) -> u64 {
+        self.channel_count as u64 * self.format.bits_per_sample() as u64 / 8
+    }
+
+    /// Appends the frames of `buffer`, it is made to fit the channel count
+    /// of the file
+    pub fn write(&mut self, buffer: &AudioBuffer) -> io::Result<()> {
+        if buffer.channel_count() == 0 {
+            return Ok(())
+        }
+        let room = self.max_data / self.bytes_per_frame() - self.frame_count;
+        let frame_count = (buffer.frame_count() as u64).min(room);
+        self.cut_frames += buffer.frame_count() as u64 - frame_count;
+        self.bytes.clear();
+        push_samples(&mut self.bytes, buffer, frame_count as usize, self.channel_count, self.format);
+        self.writer.write_all(&self.bytes) ?;
+        self.frame_count += frame_count;
+        Ok(())
+    }
+
+    /// Pads the data and puts the sizes in the header
+    pub fn finish(mut self) -> io::Result<W> {
+        let data_size = self.frame_count * self.bytes_per_frame();
+        if data_size & 1 == 1 {
+            self.writer.write_all(&[0]) ?;
+        }
+        let header = wav_header(self.channel_count, self.sample_rate, self.format, data_size);
+        self.writer.seek(SeekFrom::Start(0)) ?;
+        self.writer.write_all(&header) ?;
+        self.writer.seek(SeekFrom::End(0)) ?;
+        self.writer.flush() ?;
+        Ok(self.writer)
+    }
+}
+
+pub fn write_wav(path: impl AsRef<Path>, buffer: &AudioBuffer, sample_rate: u32, format: WavFormat) -> io::Result<()> {
+    std::fs::write(path, encode_wav(buffer, sample_rate, format))
+}
+
+/// Reads back the formats `encode_wav` writes, including the
+/// `WAVE_FORMAT_EXTENSIBLE` header other tools put around them
+pub fn decode_wav(data: &[u8]) -> Result<WavFile, String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("Not a wav file".into())
+    }
+    let u16_at = | o: usize | u16::from_le_bytes([data[o], data[o + 1]]);
+    let u32_at = | o: usize | u32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+
+    let mut fmt = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32_at(offset + 4) as usize;
+        let body = offset + 8;
+        let end = body.saturating_add(size).min(data.len());
+        if id == b"fmt " {
+            if size < 16 || end - body < 16 {
+                return Err("Wav fmt chunk too short".into())
+            }
+            let mut tag = u16_at(body);
+            if tag == 0xfffe {
+                // extensible, the real format is the start of the sub format guid
+                if size < 26 || end - body < 26 {
+                    return Err("Wav extensible fmt chunk too short".into())
+                }
+                tag = u16_at(body + 24);
+            }
+            fmt = Some((tag, u16_at(body + 2) as usize, u32_at(body + 4), u16_at(body + 14)));
+        }
+        else if id == b"data" {
+            let Some((tag, channel_count, sample_rate, bits)) = fmt else {
+                return Err("Wav data chunk before fmt chunk".into())
+            };
+            let format = match (tag, bits) {
+                (1, 16) => WavFormat::Pcm16,
+                (1, 24) => WavFormat::Pcm24,
+                (3, 32) => WavFormat::Float32,
+                _ => return Err(format!("Unsupported wav format {} with {} bits", tag, bits))
+            };
+            if channel_count == 0 {
+                return Err("Wav file has no channels".into())
+            }
+            let bytes_per_sample = bits as usize / 8;
+            let samples = &data[body..end];
+            let frame_count = samples.len() / (bytes_per_sample * channel_count);
+            let mut buffer = AudioBuffer::new_with_size(frame_count, channel_count);
+            for i in 0..frame_count {
+                for c in 0..channel_count {
+                    let s = &samples[(i * channel_count + c) * bytes_per_sample..];
+                    buffer.channel_mut(c)[i] = match format {
+                        WavFormat::Pcm16 => i16::from_le_bytes([s[0], s[1]]) as f32 / 32767.0,
+                        WavFormat::Pcm24 => (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8388607.0,
+                        WavFormat::Float32 => f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+                    };
+                }
+            }
+            return Ok(WavFile {sample_rate, format, buffer})
+        }
+        // a chunk size near the top of the range can't be skipped over
+        let Some(next) = body.checked_add(size).and_then( | end | end.checked_add(size & 1)) else {
+            break
+        };
+        offset = next;
+    }
+    Err("Wav file has no data chunk".into())
+}
+
+pub fn read_wav(path: impl AsRef<Path>) -> Result<WavFile, String> {
+    let data = std::fs::read(path.as_ref()).map_err( | e | format!("Can't read {:?}: {}", path.as_ref(), e)) ?;
+    decode_wav(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor};
+
+    #[test]
+    fn writer_stops_when_full() {
+        let mut buffer = AudioBuffer::new_with_size(10, 2);
+        buffer.data.fill(0.5);
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), 48000, 2, WavFormat::Pcm24).unwrap();
+        // room for 7 frames of 6 bytes
+        writer.max_data = 45;
+        writer.write(&buffer).unwrap();
+        writer.write(&buffer).unwrap();
+        assert_eq!((writer.frame_count(), writer.cut_frames()), (7, 13));
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!(data.len(), 44 + 42);
+        let file = decode_wav(&data).unwrap();
+        assert_eq!(file.buffer.frame_count(), 7);
+    }
+}
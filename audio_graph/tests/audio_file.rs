@@ -0,0 +1,517 @@
+use makepad_audio_graph::*;
+use makepad_audio_graph::makepad_platform::*;
+
+// there are no encoders around in tests, so the streams are put
+// together by hand from the format descriptions
+
+struct MsbWriter {
+    data: Vec<u8>,
+    bits: usize,
+}
+
+impl MsbWriter {
+    fn new() -> Self {
+        Self {data: Vec::new(), bits: 0}
+    }
+
+    fn write(&mut self, count: u32, value: u64) {
+        for i in (0..count).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.data.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn signed(&mut self, count: u32, value: i64) {
+        self.write(count, value as u64 & ((1u64 << count) - 1));
+    }
+
+    fn rice(&mut self, param: u32, value: i32) {
+        let folded = ((value << 1) ^ (value >> 31)) as u32;
+        for _ in 0..folded >> param {
+            self.write(1, 0);
+        }
+        self.write(1, 1);
+        self.write(param, (folded & ((1 << param) - 1)) as u64);
+    }
+
+    fn align(&mut self) {
+        self.bits = self.data.len() * 8;
+    }
+}
+
+fn crc8(data: &[u8]) -> u64 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {(crc << 1) ^ 0x07} else {crc << 1};
+        }
+    }
+    crc as u64
+}
+
+fn crc16(data: &[u8]) -> u64 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {(crc << 1) ^ 0x8005} else {crc << 1};
+        }
+    }
+    crc as u64
+}
+
+fn residual(samples: &[i32], coefs: &[i64], shift: u32) -> Vec<i32> {
+    (coefs.len()..samples.len()).map( | i | {
+        let prediction: i64 = coefs.iter().enumerate().map( | (j, c) | c * samples[i - 1 - j] as i64).sum();
+        samples[i] - (prediction >> shift) as i32
+    }).collect()
+}
+
+fn flac_frame(out: &mut Vec<u8>, number: u8, assignment: u64, subframes: &dyn Fn(&mut MsbWriter)) {
+    let mut w = MsbWriter::new();
+    w.write(16, 0xfff8);
+    // block size in 16 bits, rate from stream info, 16 bit samples
+    w.write(4, 7);
+    w.write(4, 0);
+    w.write(4, assignment);
+    w.write(3, 4);
+    w.write(1, 0);
+    w.write(8, number as u64);
+    w.write(16, 15);
+    let crc = crc8(&w.data);
+    w.write(8, crc);
+    subframes(&mut w);
+    w.align();
+    let crc = crc16(&w.data);
+    w.write(16, crc);
+    out.extend_from_slice(&w.data);
+}
+
+#[test]
+fn flac() {
+    let left: Vec<i32> = (0..48i32).map( | i | (i * 37) % 200 * 100 - 9000).collect();
+    let right: Vec<i32> = (0..48i32).map( | i | (i - 20) * (i % 7) * 30).collect();
+
+    let mut data = b"fLaC".to_vec();
+    let mut w = MsbWriter::new();
+    w.write(8, 0x80);
+    w.write(24, 34);
+    w.write(16, 16);
+    w.write(16, 16);
+    w.write(24, 0);
+    w.write(24, 0);
+    w.write(20, 44100);
+    w.write(3, 1);
+    w.write(5, 15);
+    w.write(36, 45);
+    w.write(64, 0);
+    w.write(64, 0);
+    data.extend_from_slice(&w.data);
+
+    // independent channels, the left one verbatim with a wasted bit, the
+    // right one with a fixed predictor
+    let (l, r) = (&left[0..16], &right[0..16]);
+    flac_frame(&mut data, 0, 1, &| w | {
+        w.write(8, 1 << 1 | 1);
+        w.write(1, 1);
+        for s in l {
+            w.signed(15, (*s >> 1) as i64);
+        }
+        w.write(8, (8 | 2) << 1);
+        w.signed(16, r[0] as i64);
+        w.signed(16, r[1] as i64);
+        w.write(2, 0);
+        w.write(4, 0);
+        w.write(4, 9);
+        for v in residual(r, &[2, -1], 0) {
+            w.rice(9, v);
+        }
+    });
+
+    // mid and side, lpc on the side with an escaped partition
+    let (l, r) = (&left[16..32], &right[16..32]);
+    let mid: Vec<i32> = l.iter().zip(r).map( | (a, b) | (a + b) >> 1).collect();
+    let side: Vec<i32> = l.iter().zip(r).map( | (a, b) | a - b).collect();
+    flac_frame(&mut data, 1, 10, &| w | {
+        w.write(8, 1 << 1);
+        for s in &mid {
+            w.signed(16, *s as i64);
+        }
+        let coefs = [3, -1];
+        w.write(8, (32 + 1) << 1);
+        w.signed(17, side[0] as i64);
+        w.signed(17, side[1] as i64);
+        w.write(4, 4);
+        w.signed(5, 1);
+        w.signed(5, coefs[0]);
+        w.signed(5, coefs[1]);
+        w.write(2, 1);
+        w.write(4, 1);
+        let res = residual(&side, &coefs, 1);
+        w.write(5, 31);
+        w.write(5, 18);
+        for v in &res[0..6] {
+            w.signed(18, *v as i64);
+        }
+        w.write(5, 12);
+        for v in &res[6..] {
+            w.rice(12, *v);
+        }
+    });
+
+    // left and side, a constant side
+    let (l, r) = (&left[32..48], &right[32..48]);
+    flac_frame(&mut data, 2, 8, &| w | {
+        w.write(8, 1 << 1);
+        for s in l {
+            w.signed(16, *s as i64);
+        }
+        w.write(8, 0);
+        w.signed(17, (l[0] - r[0]) as i64);
+    });
+    let right: Vec<i32> = right[..32].iter().cloned().chain(left[32..48].iter().map( | s | s - (left[32] - right[32]))).collect();
+
+    let file = decode_audio_file(&data).unwrap();
+    assert_eq!(file.sample_rate, 44100);
+    // stream info says 45 samples, the last frame is cut to that
+    assert_eq!(file.frame_count(), 45);
+    for i in 0..45 {
+        assert_eq!((file.buffer.channel(0)[i] * 32768.0) as i32, left[i], "left {}", i);
+        assert_eq!((file.buffer.channel(1)[i] * 32768.0) as i32, right[i], "right {}", i);
+    }
+
+    // a damaged frame is skipped, the others still decode
+    let mut damaged = data.clone();
+    let len = damaged.len();
+    damaged[len - 20] ^= 0x55;
+    assert_eq!(decode_flac(&damaged).unwrap().frame_count(), 32);
+}
+
+struct LsbWriter {
+    data: Vec<u8>,
+    bits: usize,
+}
+
+impl LsbWriter {
+    fn new() -> Self {
+        Self {data: Vec::new(), bits: 0}
+    }
+
+    fn write(&mut self, count: u32, value: u64) {
+        for i in 0..count {
+            if self.bits.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.data.last_mut().unwrap() |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    // huffman codewords go out first bit first
+    fn codeword(&mut self, length: u32, code: u64) {
+        for i in (0..length).rev() {
+            self.write(1, (code >> i) & 1);
+        }
+    }
+}
+
+fn ogg_page(out: &mut Vec<u8>, sequence: u32, granule: u64, packet: &[u8]) {
+    out.extend_from_slice(b"OggS");
+    out.push(0);
+    out.push(if sequence == 0 {2} else {0});
+    out.extend_from_slice(&granule.to_le_bytes());
+    out.extend_from_slice(&7u32.to_le_bytes());
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    let mut lacing = vec![255u8; packet.len() / 255];
+    lacing.push((packet.len() % 255) as u8);
+    out.push(lacing.len() as u8);
+    out.extend_from_slice(&lacing);
+    out.extend_from_slice(packet);
+}
+
+// blocks of 64 and 256, a single floor1 that is flat at value 200 and
+// residue straight from a codebook mapping entry e to e - 8. Two channels
+// are coupled and share an interleaved type 2 residue, like encoders do it
+fn vorbis_setup(channels: usize) -> Vec<u8> {
+    let mut w = LsbWriter::new();
+    for b in b"\x05vorbis" {
+        w.write(8, *b as u64);
+    }
+    w.write(8, 1);
+    // codebook 0, 16 entries of length 4 with the values -8..7
+    w.write(24, 0x564342);
+    w.write(16, 1);
+    w.write(24, 16);
+    w.write(1, 0);
+    w.write(1, 0);
+    for _ in 0..16 {
+        w.write(5, 3);
+    }
+    w.write(4, 1);
+    w.write(32, 1 << 31 | 771 << 21 | 1 << 20);
+    w.write(32, 768 << 21 | 1 << 20);
+    w.write(4, 3);
+    w.write(1, 0);
+    for i in 0..16 {
+        w.write(4, i);
+    }
+    // codebook 1, the classbook with two entries and no values
+    w.write(24, 0x564342);
+    w.write(16, 1);
+    w.write(24, 2);
+    w.write(1, 0);
+    w.write(1, 0);
+    w.write(5, 0);
+    w.write(5, 0);
+    w.write(4, 0);
+    // time domain transforms
+    w.write(6, 0);
+    w.write(16, 0);
+    // floor1 with one partition of one extra point at x 16
+    w.write(6, 0);
+    w.write(16, 1);
+    w.write(5, 1);
+    w.write(4, 0);
+    w.write(3, 0);
+    w.write(2, 0);
+    w.write(8, 1);
+    w.write(2, 0);
+    w.write(4, 8);
+    w.write(8, 16);
+    // residue type 1 or 2, partitions of 8 using book 0 in the first pass
+    w.write(6, 0);
+    w.write(16, channels as u64);
+    w.write(24, 0);
+    w.write(24, 128 * channels as u64);
+    w.write(24, 7);
+    w.write(6, 0);
+    w.write(8, 1);
+    w.write(3, 1);
+    w.write(1, 0);
+    w.write(8, 0);
+    // mapping
+    w.write(6, 0);
+    w.write(16, 0);
+    w.write(1, 0);
+    if channels == 2 {
+        // the second channel is the angle of the first
+        w.write(1, 1);
+        w.write(8, 0);
+        w.write(1, 0);
+        w.write(1, 1);
+    }
+    else {
+        w.write(1, 0);
+    }
+    w.write(2, 0);
+    w.write(8, 0);
+    w.write(8, 0);
+    w.write(8, 0);
+    // a short and a long mode
+    w.write(6, 1);
+    for long in [0, 1] {
+        w.write(1, long);
+        w.write(16, 0);
+        w.write(16, 0);
+        w.write(8, 0);
+    }
+    w.write(1, 1);
+    w.data
+}
+
+// the residue entries of all channels, interleaved when there are two
+fn vorbis_packet(long: bool, previous_long: bool, next_long: bool, channels: usize, entries: &[u64]) -> Vec<u8> {
+    let mut w = LsbWriter::new();
+    w.write(1, 0);
+    w.write(1, long as u64);
+    if long {
+        w.write(1, previous_long as u64);
+        w.write(1, next_long as u64);
+    }
+    for _ in 0..channels {
+        w.write(1, 1);
+        w.write(8, 200);
+        w.write(8, 200);
+        w.codeword(4, 0);
+    }
+    for partition in entries.chunks(8) {
+        w.codeword(1, 0);
+        for e in partition {
+            w.codeword(4, *e);
+        }
+    }
+    w.data
+}
+
+fn window(n: usize, long: bool, previous_long: bool, next_long: bool, i: usize) -> f64 {
+    let slope = | x: f64, size: usize | {
+        let s = ((x + 0.5) / size as f64 * std::f64::consts::FRAC_PI_2).sin();
+        (std::f64::consts::FRAC_PI_2 * s * s).sin()
+    };
+    let (left_start, left_n) = if long && !previous_long {(n / 4 - 16, 32)} else {(0, n / 2)};
+    let (right_start, right_n) = if long && !next_long {(n * 3 / 4 - 16, 32)} else {(n / 2, n / 2)};
+    if i < left_start || i >= right_start + right_n {
+        0.0
+    }
+    else if i < left_start + left_n {
+        slope((i - left_start) as f64, left_n)
+    }
+    else if i >= right_start {
+        slope((right_n - 1 - (i - right_start)) as f64, right_n)
+    }
+    else {
+        1.0
+    }
+}
+
+const VORBIS_BLOCKS: [(bool, bool, bool); 4] = [(true, true, true), (true, true, false), (false, false, false), (true, false, true)];
+
+// an ogg vorbis stream of the blocks above with the residue entries of
+// `entries(packet, half)`, cut at 280 frames
+fn vorbis_stream(channels: usize, entries: &dyn Fn(u64, u64) -> Vec<u64>) -> (Vec<u8>, Vec<Vec<u64>>) {
+    let mut ident = b"\x01vorbis".to_vec();
+    ident.extend_from_slice(&0u32.to_le_bytes());
+    ident.push(channels as u8);
+    ident.extend_from_slice(&8000u32.to_le_bytes());
+    ident.extend_from_slice(&[0; 12]);
+    ident.push(6 | 8 << 4);
+    ident.push(1);
+    let comment = b"\x03vorbis\0\0\0\0\0\0\0\0\x01".to_vec();
+
+    let mut data = Vec::new();
+    ogg_page(&mut data, 0, 0, &ident);
+    ogg_page(&mut data, 1, 0, &comment);
+    ogg_page(&mut data, 2, 0, &vorbis_setup(channels));
+    let mut spectra = Vec::new();
+    for (p, (long, previous_long, next_long)) in VORBIS_BLOCKS.iter().enumerate() {
+        let half = if *long {128} else {32};
+        let entries = entries(p as u64, half * channels as u64);
+        let granule = if p == 3 {280} else {u64::MAX};
+        ogg_page(&mut data, 3 + p as u32, granule, &vorbis_packet(*long, *previous_long, *next_long, channels, &entries));
+        spectra.push(entries);
+    }
+    (data, spectra)
+}
+
+// what the blocks decode to for the given spectra, with the mdct written out
+fn vorbis_expected(spectra: &[Vec<f64>]) -> Vec<f64> {
+    let floor = 10f64.powf((200.0 - 255.0) * 140.0 / 256.0 / 20.0) as f32 as f64;
+    let mut windowed = Vec::new();
+    for ((long, previous_long, next_long), spectrum) in VORBIS_BLOCKS.iter().zip(spectra) {
+        let n = if *long {256} else {64};
+        windowed.push((0..n).map( | i | {
+            let mut sum = 0.0;
+            for (k, x) in spectrum.iter().enumerate() {
+                sum += x * floor * (std::f64::consts::PI / 2.0 / n as f64 * (2 * i + 1 + n / 2) as f64 * (2 * k + 1) as f64).cos();
+            }
+            sum * window(n, *long, *previous_long, *next_long, i)
+        }).collect::<Vec<f64>>());
+    }
+    let mut expected = Vec::new();
+    for pair in windowed.windows(2) {
+        let (prev, cur) = (&pair[0], &pair[1]);
+        let (pn, cn) = (prev.len(), cur.len());
+        for t in 0..pn / 4 + cn / 4 {
+            let mut s = 0.0;
+            if t + pn / 2 < pn {
+                s += prev[t + pn / 2];
+            }
+            if t + cn / 4 >= pn / 4 {
+                s += cur[t + cn / 4 - pn / 4];
+            }
+            expected.push(s);
+        }
+    }
+    assert_eq!(expected.len(), 288);
+    expected
+}
+
+fn assert_close(decoded: &[f32], expected: &[f64]) {
+    let peak = expected.iter().fold(0.0f64, | m, s | m.max(s.abs()));
+    assert!(peak > 0.001);
+    for (i, (a, b)) in decoded.iter().zip(expected).enumerate() {
+        assert!((*a as f64 - b).abs() < peak * 1e-4, "sample {}: {} vs {}", i, a, b);
+    }
+}
+
+#[test]
+fn ogg_vorbis() {
+    let (data, entries) = vorbis_stream(1, &| p, half | (0..half).map( | k | (k * 5 + p * 3) % 16).collect());
+    let spectra: Vec<Vec<f64>> = entries.iter().map( | e | e.iter().map( | e | *e as f64 - 8.0).collect()).collect();
+    let expected = vorbis_expected(&spectra);
+
+    let file = decode_audio_file(&data).unwrap();
+    assert_eq!(file.sample_rate, 8000);
+    assert_eq!(file.buffer.channel_count(), 1);
+    // the granule position of the last page cuts the end off
+    assert_eq!(file.frame_count(), 280);
+    assert_close(file.buffer.channel(0), &expected);
+}
+
+#[test]
+fn ogg_vorbis_stereo() {
+    let (data, entries) = vorbis_stream(2, &| p, size | (0..size).map( | k | (k * 7 + p * 5 + k / 3) % 16).collect());
+    // undo the interleaving, then the square polar coupling
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for entries in &entries {
+        let (mut l, mut r) = (Vec::new(), Vec::new());
+        for pair in entries.chunks(2) {
+            let (m, a) = (pair[0] as f64 - 8.0, pair[1] as f64 - 8.0);
+            let (m, a) = match (m > 0.0, a > 0.0) {
+                (true, true) => (m, m - a),
+                (true, false) => (m + a, m),
+                (false, true) => (m, m + a),
+                (false, false) => (m - a, m),
+            };
+            l.push(m);
+            r.push(a);
+        }
+        left.push(l);
+        right.push(r);
+    }
+
+    let file = decode_audio_file(&data).unwrap();
+    assert_eq!(file.buffer.channel_count(), 2);
+    assert_eq!(file.frame_count(), 280);
+    assert_close(file.buffer.channel(0), &vorbis_expected(&left));
+    assert_close(file.buffer.channel(1), &vorbis_expected(&right));
+    assert_ne!(file.buffer.channel(0), file.buffer.channel(1));
+}
+
+#[test]
+fn sample_player() {
+    let mut cx = Cx::new(Box::new( | _, _ | {}));
+    let mut player = SamplePlayer::new(&mut cx);
+    // a rising ramp makes the play position easy to read back
+    let mut sample = AudioBuffer::new_with_size(1000, 1);
+    for (i, s) in sample.channel_mut(0).iter_mut().enumerate() {
+        *s = i as f32 / 1000.0;
+    }
+    player.set_sample(Some(AudioFile {sample_rate: 8000, buffer: sample}));
+
+    let mut render = OfflineRender::new(player.get_graph_node(&mut cx), 16000.0).with_channel_count(2);
+    render.queue_midi(10, MidiData {data: [0x90, 60, 127]});
+    render.queue_midi(20, MidiData {data: [0x80, 60, 0]});
+    render.queue_midi(3000, MidiData {data: [0x90, 72, 127]});
+    let out = render.render(4000);
+    let left = out.channel(0);
+    assert_eq!(left, out.channel(1));
+    assert!(left[..10].iter().all( | s | *s == 0.0));
+    // played at half speed for the higher output rate, the note off doesn't
+    // stop a one shot
+    assert!((left[10 + 500] - 0.25).abs() < 1e-4);
+    assert!((left[10 + 1998] - 0.999).abs() < 1e-4);
+    assert!(left[2020..3000].iter().all( | s | *s == 0.0));
+    // an octave up is back at the rate of the sample
+    assert!((left[3000 + 500] - 0.5).abs() < 1e-4);
+}
@@ -0,0 +1,183 @@
+use makepad_audio_graph::*;
+use makepad_audio_graph::makepad_platform::*;
+use std::f32::consts::TAU;
+
+const RATE: f64 = 48000.0;
+
+fn cx() -> Cx {
+    Cx::new(Box::new( | _, _ | {}))
+}
+
+fn sine(frequency: f32, amplitude: f32, frames: usize) -> AudioBuffer {
+    let mut buffer = AudioBuffer::new_with_size(frames, 2);
+    for c in 0..2 {
+        for (i, s) in buffer.channel_mut(c).iter_mut().enumerate() {
+            *s = (TAU * frequency * i as f32 / RATE as f32).sin() * amplitude;
+        }
+    }
+    buffer
+}
+
+fn impulse(at: usize, frames: usize) -> AudioBuffer {
+    let mut buffer = AudioBuffer::new_with_size(frames, 2);
+    buffer.channel_mut(0)[at] = 1.0;
+    buffer.channel_mut(1)[at] = 1.0;
+    buffer
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    (samples.iter().map( | s | s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+fn render(effect: &mut dyn AudioComponent, cx: &mut Cx, input: AudioBuffer) -> AudioBuffer {
+    let frames = input.frame_count();
+    OfflineRender::new(effect.get_graph_node(cx), RATE).with_input(input).render(frames)
+}
+
+#[test]
+fn equalizer() {
+    let mut cx = cx();
+    let mut eq = Equalizer::new(&mut cx);
+    eq.apply_over(&mut cx, live!{low_mid: {freq: 1000.0, gain: 12.0, q: 1.0}});
+
+    let boosted = render(&mut eq, &mut cx, sine(1000.0, 0.1, 9600));
+    let gain = rms(&boosted.channel(0)[4800..]) / rms(&sine(1000.0, 0.1, 9600).channel(0)[4800..]);
+    assert!((gain_to_db(gain) - 12.0).abs() < 0.1, "boost {}", gain_to_db(gain));
+    let expected = BiquadCoefs::new(BiquadKind::Peak, 1000.0, 1.0, 12.0, RATE as f32).response(1000.0, RATE as f32);
+    assert!((gain - expected).abs() < 0.01);
+
+    let far = render(&mut eq, &mut cx, sine(60.0, 0.1, 9600));
+    let gain = rms(&far.channel(1)[4800..]) / rms(&sine(60.0, 0.1, 9600).channel(1)[4800..]);
+    assert!(gain_to_db(gain).abs() < 0.2, "far away {}", gain_to_db(gain));
+}
+
+#[test]
+fn parameter_changes_glide() {
+    let mut cx = cx();
+    let mut eq = Equalizer::new(&mut cx);
+    let mut dc = AudioBuffer::new_with_size(20000, 2);
+    dc.data.iter_mut().for_each( | s | *s = 0.5);
+    let mut render = OfflineRender::new(eq.get_graph_node(&mut cx), RATE).with_input(dc);
+    let before = render.render(1000);
+    // doubles the output on a running node
+    eq.apply_over(&mut cx, live!{gain: 6.0206});
+    let after = render.render(19000);
+    assert!((before.channel(0)[999] - 0.5).abs() < 1e-4);
+    assert!((after.channel(0)[18999] - 1.0).abs() < 1e-3);
+    let mut last = before.channel(0)[999];
+    for s in after.channel(0) {
+        assert!((s - last).abs() < 2e-3, "jump from {} to {}", last, s);
+        last = *s;
+    }
+}
+
+#[test]
+fn compressor() {
+    // below the knee nothing happens, far above it the ratio is followed
+    assert_eq!(compressor_gain_reduction(-30.0, -20.0, 4.0, 6.0), 0.0);
+    assert!((compressor_gain_reduction(0.0, -20.0, 4.0, 6.0) + 15.0).abs() < 1e-4);
+    let in_knee = compressor_gain_reduction(-20.0, -20.0, 4.0, 6.0);
+    assert!(in_knee < 0.0 && in_knee > -1.0);
+
+    let mut cx = cx();
+    let mut compressor = Compressor::new(&mut cx);
+    compressor.apply_over(&mut cx, live!{threshold: (-20.0), ratio: 4.0, knee: 0.0, attack: 0.001, release: 0.05});
+    let mut loud = AudioBuffer::new_with_size(9600, 2);
+    loud.data.iter_mut().for_each( | s | *s = 1.0);
+    let out = render(&mut compressor, &mut cx, loud);
+    // 0dB in comes out at -15dB once the envelope settled
+    assert!((gain_to_db(out.channel(0)[9599]) + 15.0).abs() < 0.05);
+    // the attack takes a moment
+    assert!(out.channel(0)[0] > out.channel(0)[100]);
+}
+
+#[test]
+fn limiter() {
+    let mut cx = cx();
+    let mut limiter = Limiter::new(&mut cx);
+    limiter.apply_over(&mut cx, live!{ceiling: (-1.0), lookahead: 0.005});
+    let ceiling = db_to_gain(-1.0);
+    let window = (0.005 * RATE) as usize;
+
+    // quiet, then a burst far over the ceiling, then quiet again
+    let mut input = sine(440.0, 0.1, 24000);
+    for c in 0..2 {
+        for (i, s) in input.channel_mut(c)[6000..12000].iter_mut().enumerate() {
+            *s = (TAU * 97.0 * i as f32 / RATE as f32).sin() * 3.0 + if i % 2 == 0 {0.5} else {-0.5};
+        }
+    }
+    let out = render(&mut limiter, &mut cx, input.clone());
+    assert!(out.data.iter().all( | s | s.abs() <= ceiling + 1e-6));
+    // the quiet part comes through untouched, only delayed by the lookahead
+    for i in window..5000 {
+        assert!((out.channel(0)[i] - input.channel(0)[i + 1 - window]).abs() < 1e-6);
+    }
+    // and the gain comes back up after the burst
+    let tail = rms(&out.channel(0)[20000..24000]);
+    assert!((tail - rms(&input.channel(0)[20000..24000])).abs() < 1e-3, "tail {}", tail);
+}
+
+#[test]
+fn reverb() {
+    let mut cx = cx();
+    let tail_energy = | cx: &mut Cx, decay: f64 | {
+        let mut reverb = Reverb::new(cx);
+        reverb.apply_over(cx, live!{decay: (decay), mix: 1.0, predelay: 0.0});
+        let out = render(&mut reverb, cx, impulse(0, 96000));
+        assert!(out.data.iter().all( | s | s.is_finite() && s.abs() < 1.0));
+        // a stereo tail from a mono impulse
+        assert!(out.channel(0)[4800..9600] != out.channel(1)[4800..9600]);
+        (rms(&out.channel(0)[4800..24000]), rms(&out.channel(0)[48000..96000]))
+    };
+    let (early, late) = tail_energy(&mut cx, 0.5);
+    assert!(early > 1e-3 && late < early);
+    let (_, long_late) = tail_energy(&mut cx, 0.9);
+    assert!(long_late > late * 4.0, "{} {}", long_late, late);
+}
+
+fn clock() -> MidiData {
+    MidiData {data: [0xf8, 0, 0]}
+}
+
+#[test]
+fn delay() {
+    let mut cx = cx();
+    let mut delay = Delay::new(&mut cx);
+    delay.apply_over(&mut cx, live!{bpm: 120.0, beats: 0.5, feedback: 0.5, mix: 1.0, high_cut: 20000.0});
+    let out = render(&mut delay, &mut cx, impulse(100, 48000));
+    // half a beat at 120 is 0.25 seconds, the one pole spreads the echo a little
+    let echo = | n: usize | out.channel(0)[100 + n * 12000 - 2..100 + n * 12000 + 40].iter().sum::<f32>();
+    assert!(out.channel(0)[..12000].iter().all( | s | *s == 0.0));
+    assert!((echo(1) - 1.0).abs() < 0.01, "first {}", echo(1));
+    assert!((echo(2) - 0.5).abs() < 0.01, "second {}", echo(2));
+    assert!((echo(3) - 0.25).abs() < 0.01, "third {}", echo(3));
+
+    // midi clock at 150 bpm, 24 pulses of 800 frames per beat, takes over
+    let mut delay = Delay::new(&mut cx);
+    delay.apply_over(&mut cx, live!{bpm: 120.0, beats: 0.5, feedback: 0.0, mix: 1.0, high_cut: 20000.0});
+    let mut render = OfflineRender::new(delay.get_graph_node(&mut cx), RATE).with_input(impulse(48000, 72000));
+    for pulse in 0..90 {
+        render.queue_midi(pulse * 800, clock());
+    }
+    let out = render.render(72000);
+    let peak = (0..72000).max_by( | a, b | out.channel(0)[*a].total_cmp(&out.channel(0)[*b])).unwrap();
+    assert!((peak as i64 - (48000 + 9600)).abs() <= 2, "echo at {}", peak);
+}
+
+#[test]
+fn chorus() {
+    let mut cx = cx();
+    let mut chorus = Chorus::new(&mut cx);
+    chorus.apply_over(&mut cx, live!{mix: 0.0});
+    let input = sine(330.0, 0.5, 9600);
+    let dry = render(&mut chorus, &mut cx, input.clone());
+    assert_eq!(dry.data, input.data);
+
+    chorus.apply_over(&mut cx, live!{mix: 0.5, voices: 3});
+    let wet = render(&mut chorus, &mut cx, input.clone());
+    assert!(wet.data.iter().all( | s | s.abs() <= 0.5 + 1e-4));
+    // the channels are modulated out of step, which widens a mono source
+    assert!(rms(&wet.channel(0)[4800..]) > 0.1);
+    let difference: Vec<f32> = wet.channel(0).iter().zip(wet.channel(1)).map( | (l, r) | l - r).collect();
+    assert!(rms(&difference[4800..]) > 1e-3);
+}
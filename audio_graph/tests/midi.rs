@@ -0,0 +1,261 @@
+use makepad_audio_graph::*;
+use makepad_audio_graph::makepad_platform::*;
+use std::sync::{Arc, Mutex};
+
+const RATE: f64 = 44100.0;
+
+fn cx() -> Cx {
+    Cx::new(Box::new( | _, _ | {}))
+}
+
+fn note(on: bool, note: u8) -> MidiData {
+    MidiData {data: [if on {0x90} else {0x80}, note, if on {100} else {0}]}
+}
+
+// logs the midi it gets with the frame it arrived on
+#[derive(Clone, Default)]
+struct MidiLog {
+    log: Arc<Mutex<Vec<(u64, MidiData)>>>,
+    frame: u64,
+}
+
+impl MidiLog {
+    fn notes(&self) -> Vec<(u64, MidiData)> {
+        self.log.lock().unwrap().iter().filter( | (_, d) | d.data[0] < 0xf0).copied().collect()
+    }
+
+    fn clock(&self, status: u8) -> Vec<u64> {
+        self.log.lock().unwrap().iter().filter( | (_, d) | d.data[0] == status).map( | (f, _) | *f).collect()
+    }
+}
+
+impl AudioGraphNode for MidiLog {
+    fn handle_midi_data(&mut self, data: MidiData) {
+        self.log.lock().unwrap().push((self.frame, data));
+    }
+
+    fn all_notes_off(&mut self) {
+    }
+
+    fn render_to_audio_buffer(&mut self, _info: AudioInfo, outputs: &mut [&mut AudioBuffer], _inputs: &[&AudioBuffer], _display: &mut DisplayAudioGraph) {
+        self.frame += outputs[0].frame_count() as u64;
+    }
+}
+
+fn song() -> MidiFile {
+    let mut file = MidiFile::new(480);
+    let mut conductor = MidiTrack::default();
+    conductor.push(0, MidiFileEvent::TrackName("song".into()));
+    conductor.push(0, MidiFileEvent::TimeSignature {numerator: 3, denominator: 4, clocks_per_click: 24, notated_32nds: 8});
+    conductor.push(0, MidiFileEvent::Tempo(500_000));
+    // 60 bpm from the third beat
+    conductor.push(960, MidiFileEvent::Tempo(1_000_000));
+    let mut notes = MidiTrack::default();
+    notes.push(0, MidiFileEvent::Midi(MidiData {data: [0xc1, 5, 0]}));
+    notes.push(0, MidiFileEvent::SysEx(vec![0xf0, 0x7e, 0x7f, 0x09, 0x01, 0xf7]));
+    notes.push(1, MidiFileEvent::Midi(note(true, 60)));
+    notes.push(480, MidiFileEvent::Midi(note(false, 60)));
+    notes.push(480, MidiFileEvent::Midi(note(true, 64)));
+    notes.push(1440, MidiFileEvent::Midi(note(false, 64)));
+    notes.push(1440, MidiFileEvent::Midi(MidiData {data: [0xe0, 0, 0x40]}));
+    notes.end_tick = 1920;
+    file.tracks = vec![conductor, notes];
+    file
+}
+
+#[test]
+fn midi_file_round_trip() {
+    let file = song();
+    let data = encode_midi_file(&file);
+    assert_eq!(&data[0..4], b"MThd");
+    assert_eq!(decode_midi_file(&data).unwrap(), file);
+
+    // type 0 merges the tracks, the conductor track goes first on a tick
+    let type_0 = MidiFile {format: 0, ..file.clone()};
+    let merged = decode_midi_file(&encode_midi_file(&type_0)).unwrap();
+    assert_eq!(merged.tracks.len(), 1);
+    assert_eq!(merged.tracks[0], file.merged_track());
+    assert_eq!(merged.midi_events(), file.midi_events());
+
+    assert!(decode_midi_file(b"RIFF\0\0\0\0").is_err());
+    assert!(decode_midi_file(&data[..data.len() - 3]).is_err());
+}
+
+#[test]
+fn midi_file_smpte() {
+    let header = | division: u16 | {
+        let mut data = b"MThd\0\0\0\x06\0\0\0\x01".to_vec();
+        data.extend_from_slice(&division.to_be_bytes());
+        data.extend_from_slice(b"MTrk\0\0\0\x04\0\xff\x2f\0");
+        data
+    };
+    // 25 fps at 40 ticks a frame is a millisecond a tick
+    let file = decode_midi_file(&header(0xe728)).unwrap();
+    assert_eq!(file.division, MidiTimeDivision::Smpte {frames_per_second: 25, ticks_per_frame: 40});
+    assert_eq!(file.tempo_map().tick_to_seconds(1000.0), 1.0);
+    assert_eq!(encode_midi_file(&file), header(0xe728));
+
+    // -128, a rate that isn't SMPTE, and no ticks
+    for division in [0x8028, 0xe128, 0xe700] {
+        assert!(decode_midi_file(&header(division)).is_err());
+    }
+    let odd = MidiFile {division: MidiTimeDivision::Smpte {frames_per_second: 128, ticks_per_frame: 40}, ..file};
+    assert!(decode_midi_file(&encode_midi_file(&odd)).is_err());
+}
+
+#[test]
+fn midi_file_running_status() {
+    let data = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+        b'M', b'T', b'r', b'k', 0, 0, 0, 15,
+        // a note on and a note off by velocity 0 sharing the status, a
+        // delta of 200 ticks takes two bytes
+        0x00, 0x90, 60, 100,
+        0x81, 0x48, 60, 0,
+        // program change has one data byte
+        0x00, 0xc0, 7,
+        0x00, 0xff, 0x2f, 0x00,
+    ];
+    let file = decode_midi_file(&data).unwrap();
+    assert_eq!(file.division, MidiTimeDivision::TicksPerQuarter(96));
+    assert_eq!(file.midi_events(), vec![
+        (0, MidiData {data: [0x90, 60, 100]}),
+        (200, MidiData {data: [0x90, 60, 0]}),
+        (200, MidiData {data: [0xc0, 7, 0]}),
+    ]);
+    assert_eq!(file.end_tick(), 200);
+    // written back with running status it comes out the same
+    assert_eq!(encode_midi_file(&file), data);
+}
+
+#[test]
+fn tempo_map() {
+    let map = song().tempo_map();
+    assert_eq!(map.tick_to_seconds(480.0), 0.5);
+    assert_eq!(map.tick_to_seconds(960.0), 1.0);
+    assert_eq!(map.tick_to_seconds(1440.0), 2.0);
+    assert_eq!(map.seconds_to_tick(1.5), 1200.0);
+    assert_eq!(map.bpm_at(959.0), 120.0);
+    assert_eq!(map.bpm_at(960.0), 60.0);
+    assert_eq!(map.next_change(0.0), Some(960));
+    assert_eq!(map.next_change(960.0), None);
+    assert_eq!(song().duration(), 3.0);
+}
+
+fn play(sequencer: &mut MidiSequencer, block_size: usize, frames: usize) -> MidiLog {
+    let log = MidiLog::default();
+    let mut render = OfflineRender::new(sequencer.graph_node_with(Some(Box::new(log.clone()))), RATE).with_block_size(block_size);
+    sequencer.seek(0.0);
+    sequencer.play();
+    render.render(frames);
+    log
+}
+
+#[test]
+fn sequencer_is_sample_accurate() {
+    let mut cx = cx();
+    let mut sequencer = MidiSequencer::new(&mut cx);
+    let file = song();
+    let map = file.tempo_map();
+    sequencer.set_midi_file(Some(file.clone()));
+    let expected: Vec<(u64, MidiData)> = file.midi_events().into_iter().map( | (tick, data) | {
+        ((map.tick_to_seconds(tick as f64) * RATE).ceil() as u64, data)
+    }).collect();
+    // tick 1 is between frames, it goes on the next one
+    assert_eq!(expected[1].0, 46);
+    for block_size in [1, 64, 333, 512, 4096] {
+        let log = play(&mut sequencer, block_size, 4 * RATE as usize);
+        assert_eq!(log.notes(), expected, "block size {}", block_size);
+    }
+    // the end of the file stops it
+    assert!((sequencer.position() - 1920.0).abs() < 1e-3);
+    sequencer.handle_event_with(&mut cx, &Event::Pause, &mut | _, _ | {});
+    assert!(!sequencer.is_playing());
+}
+
+#[test]
+fn sequencer_loops_and_sends_clock() {
+    let mut cx = cx();
+    let mut sequencer = MidiSequencer::new(&mut cx);
+    sequencer.apply_over(&mut cx, live!{looping: true, send_clock: true});
+    let mut file = MidiFile::new(96);
+    let mut track = MidiTrack::default();
+    track.push(0, MidiFileEvent::Midi(note(true, 60)));
+    track.end_tick = 96 * 4;
+    file.tracks.push(track);
+    sequencer.set_midi_file(Some(file));
+    // four beats at 120 bpm are 2 seconds
+    let log = play(&mut sequencer, 256, 5 * RATE as usize);
+    let notes = log.notes();
+    assert_eq!(notes[0], (0, note(true, 60)));
+    // held over the loop point, it gets turned off before it plays again
+    assert_eq!(notes[1], (2 * RATE as u64, note(false, 60)));
+    assert_eq!(notes[2], (2 * RATE as u64, note(true, 60)));
+    assert_eq!(notes[4], (4 * RATE as u64, note(true, 60)));
+
+    assert_eq!(log.clock(0xfa), vec![0]);
+    // 24 pulses a beat, one every 918.75 frames
+    let pulses = log.clock(0xf8);
+    for (i, frame) in pulses.iter().take(96).enumerate() {
+        assert_eq!(*frame, (i as f64 * RATE / 48.0).ceil() as u64);
+    }
+    assert_eq!(pulses[96], 2 * RATE as u64);
+}
+
+#[test]
+fn sequencer_follows_clock() {
+    let mut cx = cx();
+    let mut sequencer = MidiSequencer::new(&mut cx);
+    let mut file = MidiFile::new(480);
+    let mut track = MidiTrack::default();
+    track.push(480, MidiFileEvent::Midi(note(true, 60)));
+    track.push(960, MidiFileEvent::Midi(note(true, 62)));
+    track.push(2400, MidiFileEvent::Midi(note(true, 64)));
+    file.tracks.push(track);
+    sequencer.set_midi_file(Some(file));
+    let log = MidiLog::default();
+    let mut render = OfflineRender::new(sequencer.graph_node_with(Some(Box::new(log.clone()))), RATE);
+    // 240 bpm, twice the tempo of the file, and a stop after two beats
+    let pulse = RATE / 96.0;
+    render.queue_midi(0, MidiData {data: [0xfa, 0, 0]});
+    for i in 0..=48 {
+        render.queue_midi((i as f64 * pulse).round() as u64, MidiData {data: [0xf8, 0, 0]});
+    }
+    render.queue_midi((48.5 * pulse) as u64, MidiData {data: [0xfc, 0, 0]});
+    render.render(RATE as usize);
+    let notes = log.notes();
+    assert_eq!(notes.len(), 4);
+    // the first beat runs at the tempo of the file until the clock tempo is
+    // known, but it catches up on every pulse
+    assert_eq!(notes[0], ((24.0 * pulse).round() as u64, note(true, 60)));
+    assert!((notes[1].0 as f64 - 48.0 * pulse).abs() <= 1.0, "{:?}", notes[1]);
+    // the stop turns off what was playing
+    let stop = (48.5 * pulse) as u64;
+    assert_eq!(&notes[2..], &[(stop, note(false, 60)), (stop, note(false, 62))]);
+    assert_eq!(log.clock(0xfc).len(), 1);
+}
+
+#[test]
+fn sequencer_records() {
+    let mut cx = cx();
+    let mut sequencer = MidiSequencer::new(&mut cx);
+    sequencer.apply_over(&mut cx, live!{bpm: 60.0});
+    let log = MidiLog::default();
+    let mut render = OfflineRender::new(sequencer.graph_node_with(Some(Box::new(log.clone()))), RATE);
+    sequencer.record(true);
+    sequencer.play();
+    render.queue_midi(RATE as u64 / 2, note(true, 60));
+    render.queue_midi(RATE as u64, note(false, 60));
+    render.render(2 * RATE as usize);
+    sequencer.handle_event_with(&mut cx, &Event::Pause, &mut | _, _ | {});
+    assert!(sequencer.is_playing());
+    // played through to the instrument as well
+    assert_eq!(log.notes().len(), 2);
+
+    let recording = sequencer.take_recording();
+    assert_eq!(recording.tempo_map().bpm_at(0.0), 60.0);
+    assert_eq!(recording.midi_events(), vec![(240, note(true, 60)), (480, note(false, 60))]);
+    let written = decode_midi_file(&encode_midi_file(&recording)).unwrap();
+    assert_eq!(written, recording);
+    assert!(sequencer.take_recording().midi_events().is_empty());
+}
@@ -0,0 +1,169 @@
+use makepad_audio_graph::*;
+use makepad_audio_graph::makepad_platform::*;
+use std::path::PathBuf;
+
+// a small synth with nothing platform dependent in it, so the golden
+// files hold on every machine
+#[derive(Default)]
+struct TestSynth {
+    sample_rate: f64,
+    voices: Vec<(u8, f64, f64)>,
+}
+
+impl AudioGraphNode for TestSynth {
+    fn handle_midi_data(&mut self, data: MidiData) {
+        match data.decode() {
+            MidiEvent::Note(note) if note.is_on => {
+                self.voices.push((note.note_number, 0.0, note.velocity as f64 / 127.0));
+            }
+            MidiEvent::Note(note) => {
+                self.voices.retain( | v | v.0 != note.note_number);
+            }
+            _ => ()
+        }
+    }
+
+    fn all_notes_off(&mut self) {
+        self.voices.clear();
+    }
+
+    fn render_to_audio_buffer(&mut self, info: AudioInfo, outputs: &mut [&mut AudioBuffer], _inputs: &[&AudioBuffer], _display: &mut DisplayAudioGraph) {
+        self.sample_rate = info.sample_rate;
+        let output = &mut outputs[0];
+        for i in 0..output.frame_count() {
+            let mut left = 0.0;
+            let mut right = 0.0;
+            for (note, phase, gain) in &mut self.voices {
+                // a saw, panned by note so the channels differ
+                let saw = (*phase * 2.0 - 1.0) * *gain * 0.25;
+                let pan = (*note as f64 - 48.0) / 48.0;
+                left += saw * (1.0 - pan);
+                right += saw * pan;
+                *phase = (*phase + 440.0 * 2f64.powf((*note as f64 - 69.0) / 12.0) / self.sample_rate).fract();
+            }
+            output.channel_mut(0)[i] = left as f32;
+            output.channel_mut(1)[i] = right as f32;
+        }
+    }
+}
+
+fn note(on: bool, note: u8, velocity: u8) -> MidiData {
+    MidiData {data: [if on {0x90} else {0x80}, note, velocity]}
+}
+
+// rendered in two calls, the second one continues where the first stopped
+fn render_song(block_size: usize) -> AudioBuffer {
+    let mut render = OfflineRender::new(Box::<TestSynth>::default(), 22050.0).with_block_size(block_size);
+    render.queue_midi(100, note(true, 60, 100));
+    render.queue_midi(700, note(true, 67, 64));
+    render.queue_midi_at_time(0.05, note(false, 60, 0));
+    render.queue_midi(1500, note(true, 72, 127));
+    render.queue_midi(1900, note(false, 67, 0));
+    let first = render.render(1000);
+    let second = render.render(1048);
+    assert_eq!(render.frame(), 2048);
+    let mut song = AudioBuffer::new_with_size(2048, 2);
+    for c in 0..2 {
+        song.channel_mut(c)[..1000].copy_from_slice(first.channel(c));
+        song.channel_mut(c)[1000..].copy_from_slice(second.channel(c));
+    }
+    song
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+// set MAKEPAD_UPDATE_GOLDEN=1 to write the golden files again after an
+// intended change in the output
+fn check_golden(name: &str, song: &AudioBuffer, format: WavFormat) {
+    let path = golden_path(name);
+    if std::env::var_os("MAKEPAD_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        write_wav(&path, song, 22050, format).unwrap();
+    }
+    let golden = read_wav(&path).unwrap();
+    assert_eq!(golden.sample_rate, 22050);
+    assert_eq!(golden.format, format);
+    assert_eq!(golden.buffer.channel_count(), 2);
+    assert_eq!(golden.buffer.frame_count(), song.frame_count());
+    // one step of the integer formats, float only gets rounding slack
+    let tolerance = match format {
+        WavFormat::Pcm16 => 1.5 / 32767.0,
+        WavFormat::Pcm24 => 1.5 / 8388607.0,
+        WavFormat::Float32 => 1e-6,
+    };
+    for c in 0..2 {
+        for (i, (a, b)) in golden.buffer.channel(c).iter().zip(song.channel(c)).enumerate() {
+            assert!((a - b).abs() <= tolerance, "{} differs at channel {} frame {}: {} vs {}", name, c, i, a, b);
+        }
+    }
+}
+
+#[test]
+fn midi_is_sample_accurate() {
+    let song = render_song(512);
+    // nothing plays before the first note on
+    assert!(song.channel(0)[..100].iter().all( | s | *s == 0.0));
+    assert!(song.channel(0)[100] != 0.0);
+    // the block size the graph is pulled with doesn't change the output
+    for block_size in [1, 64, 333, 4096] {
+        assert_eq!(render_song(block_size).data, song.data);
+    }
+}
+
+#[test]
+fn golden_wav_files() {
+    let song = render_song(256);
+    check_golden("song_16.wav", &song, WavFormat::Pcm16);
+    check_golden("song_24.wav", &song, WavFormat::Pcm24);
+    check_golden("song_f32.wav", &song, WavFormat::Float32);
+}
+
+#[test]
+fn wav_round_trip() {
+    let mut buffer = AudioBuffer::new_with_size(5, 1);
+    buffer.channel_mut(0).copy_from_slice(&[0.0, 0.5, -0.5, 1.5, -1.0]);
+    for format in [WavFormat::Pcm16, WavFormat::Pcm24, WavFormat::Float32] {
+        let data = encode_wav(&buffer, 48000, format);
+        // riff chunks are padded to an even size
+        assert_eq!(data.len() % 2, 0);
+        let wav = decode_wav(&data).unwrap();
+        assert_eq!(wav.sample_rate, 48000);
+        assert_eq!(wav.buffer.frame_count(), 5);
+        let expect: &[f32] = if format == WavFormat::Float32 {&[0.0, 0.5, -0.5, 1.5, -1.0]} else {&[0.0, 0.5, -0.5, 1.0, -1.0]};
+        for (a, b) in wav.buffer.channel(0).iter().zip(expect) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+    assert!(decode_wav(b"RIFF\0\0\0\0AVI ").is_err());
+}
+
+#[test]
+fn wav_bad_chunks() {
+    let mut buffer = AudioBuffer::new_with_size(2, 1);
+    buffer.channel_mut(0).copy_from_slice(&[0.25, -0.25]);
+    let data = encode_wav(&buffer, 48000, WavFormat::Pcm16);
+
+    // an extensible fmt chunk that ends before its sub format
+    let mut cut = data[..36].to_vec();
+    cut[20..22].copy_from_slice(&0xfffeu16.to_le_bytes());
+    cut[16..20].copy_from_slice(&40u32.to_le_bytes());
+    assert!(decode_wav(&cut).is_err());
+
+    // an unknown chunk claiming to run to the end of the address space
+    let mut junk = data[..12].to_vec();
+    junk.extend_from_slice(b"junk");
+    junk.extend_from_slice(&u32::MAX.to_le_bytes());
+    junk.extend_from_slice(&data[12..]);
+    assert!(decode_wav(&junk).is_err());
+
+    // the same chunk with a real size is skipped, padding included
+    let mut padded = data[..12].to_vec();
+    padded.extend_from_slice(b"junk");
+    padded.extend_from_slice(&3u32.to_le_bytes());
+    padded.extend_from_slice(&[1, 2, 3, 0]);
+    padded.extend_from_slice(&data[12..]);
+    let wav = decode_wav(&padded).unwrap();
+    assert_eq!(wav.buffer.channel(0).len(), 2);
+}
@@ -0,0 +1,201 @@
+use makepad_audio_graph::*;
+use makepad_audio_graph::makepad_platform::*;
+use std::io::Cursor;
+
+const RATE: f64 = 48000.0;
+
+fn cx() -> Cx {
+    Cx::new(Box::new( | _, _ | {}))
+}
+
+fn sine(frame_count: usize, channel_count: usize, freq: f64, gain: f32) -> AudioBuffer {
+    let mut buffer = AudioBuffer::new_with_size(frame_count, channel_count);
+    for c in 0..channel_count {
+        for (i, s) in buffer.channel_mut(c).iter_mut().enumerate() {
+            *s = (2.0 * std::f64::consts::PI * freq * (i + c) as f64 / RATE).sin() as f32 * gain;
+        }
+    }
+    buffer
+}
+
+// samples as the integers a file of `bits` stores
+fn quantize(buffer: &AudioBuffer, bits: u32) -> Vec<Vec<i32>> {
+    let scale = (1u32 << (bits - 1)) as f32;
+    (0..buffer.channel_count()).map( | c | {
+        buffer.channel(c).iter().map( | s | (s * scale).round().clamp(-scale, scale - 1.0) as i32).collect()
+    }).collect()
+}
+
+#[test]
+fn flac_round_trip() {
+    for (bits, channel_count, frame_count) in [(16, 1, 1), (16, 2, 4096), (24, 2, 10001), (24, 1, 4097)] {
+        let mut buffer = sine(frame_count, channel_count, 441.0, 0.9);
+        // something the predictors can't do much with, and a clipped sample
+        for (i, s) in buffer.channel_mut(0).iter_mut().enumerate().skip(3000) {
+            *s = if i % 3 == 0 {1.5} else {((i * 7919) % 2000) as f32 / 1000.0 - 1.0};
+        }
+        let data = encode_flac(&buffer, RATE as u32, bits);
+        let file = decode_flac(&data).unwrap();
+        assert_eq!(file.sample_rate, RATE as u32);
+        assert_eq!(file.frame_count(), frame_count);
+        assert_eq!(quantize(&file.buffer, bits), quantize(&buffer, bits), "{} bits {} channels {} frames", bits, channel_count, frame_count);
+    }
+    // silence is a constant subframe, a sine is predicted well
+    let silence = encode_flac(&AudioBuffer::new_with_size(48000, 2), RATE as u32, 16);
+    assert!(silence.len() < 500, "{}", silence.len());
+    let tone = encode_flac(&sine(48000, 2, 441.0, 0.5), RATE as u32, 16);
+    assert!(tone.len() < 48000 * 2 * 2 / 2, "{}", tone.len());
+}
+
+#[test]
+fn wav_writer_streams() {
+    let buffer = sine(5001, 2, 1000.0, 0.7);
+    for format in [WavFormat::Pcm16, WavFormat::Pcm24, WavFormat::Float32] {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), RATE as u32, 2, format).unwrap();
+        for start in (0..buffer.frame_count()).step_by(333) {
+            let len = 333.min(buffer.frame_count() - start);
+            let mut part = AudioBuffer::new_with_size(len, 2);
+            for c in 0..2 {
+                part.channel_mut(c).copy_from_slice(&buffer.channel(c)[start..start + len]);
+            }
+            writer.write(&part).unwrap();
+        }
+        assert_eq!(writer.frame_count(), 5001);
+        assert_eq!(writer.finish().unwrap().into_inner(), encode_wav(&buffer, RATE as u32, format));
+    }
+}
+
+#[test]
+fn loudness_meter() {
+    let mut meter = LevelMeter::new(2, RATE);
+    // a 1kHz sine is not changed by the k-weighting, at -20dBFS on both
+    // channels that is -20 LUFS give or take the 0.691 of the formula
+    let tone = sine(RATE as usize * 5, 2, 1000.0, 0.1);
+    meter.process(&tone);
+    let reading = meter.reading();
+    assert_eq!(reading.channel_count, 2);
+    for value in [reading.momentary, reading.short_term, reading.integrated] {
+        assert!((value - -20.0).abs() < 0.1, "{:?}", reading);
+    }
+    assert!((reading.peak()[0] - -20.0).abs() < 0.01, "{:?}", reading);
+    assert!((reading.rms()[1] - -23.01).abs() < 0.01, "{:?}", reading);
+    // the peak starts over, silence is gated out of the integrated loudness
+    // but the blocks that fade out into it are not
+    meter.process(&AudioBuffer::new_with_size(RATE as usize * 5, 2));
+    let reading = meter.reading();
+    assert!(reading.peak()[0] < -150.0);
+    assert!(reading.momentary < -150.0);
+    assert!((reading.integrated - -20.0).abs() < 0.2, "{:?}", reading);
+    meter.reset_integrated();
+    assert!(meter.reading().integrated < -150.0);
+}
+
+fn wait_for_take(cx: &mut Cx, recorder: &mut AudioRecorder) -> Result<RecordedTake, String> {
+    for _ in 0..500 {
+        recorder.handle_event_with(cx, &Event::Pause, &mut | _, _ | {});
+        if let Some(take) = recorder.finished_take() {
+            return take
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("the take never finished")
+}
+
+#[test]
+fn recorder_punches_in_and_out() {
+    let mut cx = cx();
+    let mut recorder = AudioRecorder::new(&mut cx);
+    recorder.apply_over(&mut cx, live!{format: Flac24, punch_in: 0.5, punch_out: 1.0});
+    let input = sine(RATE as usize * 2, 2, 220.0, 0.5);
+    let mut render = OfflineRender::new(recorder.graph_node(), RATE).with_block_size(700).with_input(input.clone());
+    let path = std::env::temp_dir().join(format!("makepad_recorder_{}.flac", std::process::id()));
+    recorder.record(&path).unwrap();
+    assert!(recorder.is_recording());
+    // passed through while it records
+    let output = render.render(RATE as usize * 2);
+    assert_eq!(output.channel(1), input.channel(1));
+    recorder.stop();
+    // the node sees the stop on the next block
+    render.render(1);
+    let take = wait_for_take(&mut cx, &mut recorder).unwrap();
+    assert_eq!(take, RecordedTake {
+        path: path.clone(),
+        sample_rate: RATE as u32,
+        channel_count: 2,
+        frame_count: RATE as u64 / 2,
+        dropped_frames: 0,
+        cut_frames: 0,
+    });
+    let file = load_audio_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let start = RATE as usize / 2;
+    let mut expected = AudioBuffer::new_with_size(RATE as usize / 2, 2);
+    for c in 0..2 {
+        expected.channel_mut(c).copy_from_slice(&input.channel(c)[start..start * 2]);
+    }
+    assert_eq!(quantize(&file.buffer, 24), quantize(&expected, 24));
+
+    // punched out by hand nothing gets written
+    let mut render = OfflineRender::new(recorder.graph_node(), RATE).with_input(input);
+    recorder.apply_over(&mut cx, live!{format: Wav16, punch_in: 0.0, punch_out: 0.0});
+    recorder.record(&path).unwrap();
+    render.render(RATE as usize / 2);
+    recorder.punch(false);
+    render.render(RATE as usize / 2);
+    recorder.punch(true);
+    render.render(100);
+    recorder.stop();
+    render.render(1);
+    let take = wait_for_take(&mut cx, &mut recorder).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(take.frame_count, RATE as u64 / 2 + 100);
+}
+
+#[test]
+fn recorder_splits_big_blocks() {
+    let mut cx = cx();
+    let mut recorder = AudioRecorder::new(&mut cx);
+    recorder.apply_over(&mut cx, live!{format: WavFloat});
+    // 6 channels of 5000 frames don't fit the stereo buffers a take starts with
+    let input = sine(RATE as usize, 6, 330.0, 0.5);
+    let mut render = OfflineRender::new(recorder.graph_node(), RATE).with_block_size(5000).with_input(input.clone());
+    let path = std::env::temp_dir().join(format!("makepad_recorder_big_{}.wav", std::process::id()));
+    recorder.record(&path).unwrap();
+    render.render(RATE as usize);
+    recorder.stop();
+    render.render(1);
+    let take = wait_for_take(&mut cx, &mut recorder).unwrap();
+    let file = load_audio_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((take.channel_count, take.frame_count, take.dropped_frames), (6, RATE as u64, 0));
+    assert_eq!(file.buffer.data, input.data);
+}
+
+#[test]
+fn recorder_sends_meter_readings() {
+    let mut cx = cx();
+    let mut recorder = AudioRecorder::new(&mut cx);
+    recorder.apply_over(&mut cx, live!{meter_id: 7, meter_rate: 10.0});
+    let mut node = recorder.graph_node();
+    let to_ui = ToUIReceiver::<ToUIDisplayMsg>::default();
+    let sender = to_ui.sender();
+    let mut buffers = Vec::new();
+    let input = sine(480, 1, 1000.0, 0.5);
+    let mut output = AudioBuffer::new_with_size(480, 2);
+    let info = AudioInfo {device_id: Default::default(), time: None, sample_rate: RATE};
+    // 10 readings a second are one every 10 blocks
+    for _ in 0..30 {
+        let mut display = DisplayAudioGraph {to_ui: &sender, buffers: &mut buffers};
+        node.render_to_audio_buffer(info, &mut [&mut output], &[&input], &mut display);
+    }
+    let mut readings = Vec::new();
+    while let Ok(msg) = to_ui.try_recv() {
+        if let ToUIDisplayMsg::Meter {id, meter} = msg {
+            assert_eq!(id, 7);
+            readings.push(meter);
+        }
+    }
+    assert_eq!(readings.len(), 3);
+    assert_eq!(readings[0].channel_count, 1);
+    assert!((readings[2].peak()[0] - gain_to_db(0.5)).abs() < 0.01);
+}
@@ -52,6 +52,16 @@ fn pixel(self) -> vec4 {
         }
     }
 
+    DrawBreakpoint = {{DrawBreakpoint}} {
+        fn pixel(self) -> vec4 {
+            let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+            let r = min(self.rect_size.x, self.rect_size.y) * 0.4;
+            sdf.circle(self.rect_size.x * 0.5, self.rect_size.y * 0.5, r);
+            sdf.fill(mix(self.color, self.paused_color, self.paused));
+            return sdf.result
+        }
+    }
+
     DrawDecoration = {{DrawDecoration}} {
         fn pixel(self) -> vec4 {
             let transformed_pos = vec2(self.pos.x, self.pos.y + 0.03 * sin(self.pos.x * self.rect_size.x));
@@ -148,6 +158,11 @@ fn blend_color(self, incol: vec4) -> vec4 {
         draw_decoration: {
           //  draw_depth: 2.0,
         }
+        draw_breakpoint: {
+            draw_depth: 1.0,
+            color: #e04848,
+            paused_color: #f0c040,
+        }
         draw_selection: {
            // draw_depth: 3.0,
         }
@@ -230,6 +245,7 @@ pub struct CodeEditor {
     #[live] token_colors: TokenColors,
     #[live] draw_indent_guide: DrawIndentGuide,
     #[live] draw_decoration: DrawDecoration,
+    #[live] draw_breakpoint: DrawBreakpoint,
     #[live] draw_selection: DrawSelection,
     #[live] draw_cursor: DrawColor,
     #[live] draw_cursor_bg: DrawColor,
@@ -251,6 +267,10 @@ pub struct CodeEditor {
     #[live(true)] show_gutter: bool,
     #[live(2usize)] gutter_pad: usize,
     #[live(true)] empty_page_at_end: bool,
+    /// Lines with a breakpoint marker in the gutter
+    #[rust] pub breakpoint_lines: Vec<usize>,
+    /// The line a debugger is paused on, marked in the gutter
+    #[rust] pub paused_line: Option<usize>,
         
     
     #[live(0.5)] blink_speed: f64,
@@ -954,6 +974,11 @@ pub fn handle_event(
                 self.animator_play(cx, ids!(focus.on));
                 cx.set_key_focus(self.scroll_bars.area());
                 let ((cursor, affinity), is_in_gutter) = self.pick(session, abs);
+                // the strip left of the line numbers is where breakpoints go
+                if is_in_gutter && self.show_gutter && abs.x < self.gutter_rect.pos.x {
+                    actions.push(CodeEditorAction::GutterClicked(cursor.line_index));
+                    return actions
+                }
                 session.set_selection(
                     cursor,
                     affinity,
@@ -1066,6 +1091,20 @@ fn draw_gutter(&mut self, cx: &mut Cx2d, session: &CodeSession) {
                         5=>write!(buf, "{: >4}", line_index + 1).unwrap(),
                         _=>write!(buf, "{: >5}", line_index + 1).unwrap(),
                     }
+                    let paused = self.paused_line == Some(line_index);
+                    if paused || self.breakpoint_lines.contains(&line_index) {
+                        self.draw_breakpoint.paused = if paused {1.0} else {0.0};
+                        self.draw_breakpoint.draw_abs(
+                            cx,
+                            Rect {
+                                pos: dvec2(
+                                    self.gutter_rect.pos.x - self.pad_left_top.x,
+                                    origin_y * self.cell_size.y + self.gutter_rect.pos.y,
+                                ),
+                                size: dvec2(self.pad_left_top.x, line.scale() * self.cell_size.y),
+                            },
+                        );
+                    }
                     self.draw_gutter.draw_abs(
                         cx,
                         DVec2 {
@@ -1519,6 +1558,8 @@ fn pick(&self, session: &CodeSession, position: DVec2) -> ((Position, Affinity),
 pub enum CodeEditorAction {
     TextDidChange,
     UnhandledKeyDown(KeyEvent),
+    /// The breakpoint strip of the gutter was clicked on a line, from 0
+    GutterClicked(usize),
     None
 }
 
@@ -2013,6 +2054,19 @@ pub struct DrawIndentGuide {
     color: Vec4,
 }
 
+#[derive(Live, LiveHook, LiveRegister)]
+#[repr(C)]
+struct DrawBreakpoint {
+    #[deref]
+    draw_super: DrawQuad,
+    #[live]
+    color: Vec4,
+    #[live]
+    paused_color: Vec4,
+    #[live]
+    paused: f32,
+}
+
 #[derive(Live, LiveHook, LiveRegister)]
 struct DrawDecoration {
     #[deref]
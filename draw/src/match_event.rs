@@ -19,6 +19,7 @@ fn handle_actions(&mut self, cx: &mut Cx, actions:&Actions){
     }
     fn handle_signal(&mut self, _cx: &mut Cx){}
     fn handle_audio_devices(&mut self, _cx: &mut Cx, _e:&AudioDevicesEvent){}
+    fn handle_audio_xrun(&mut self, _cx: &mut Cx, _e:&AudioXrunEvent){}
     fn handle_midi_ports(&mut self, _cx: &mut Cx, _e:&MidiPortsEvent){}
     fn handle_video_inputs(&mut self, _cx: &mut Cx, _e:&VideoInputsEvent){}
 
@@ -27,6 +28,8 @@ fn handle_http_request_error(&mut self, _cx:&mut Cx, _request_id:LiveId, _err:&H
     fn handle_http_progress(&mut self, _cx:&mut Cx, _request_id:LiveId, _progress:&HttpProgress){}
     fn handle_http_stream(&mut self, _cx:&mut Cx, _request_id:LiveId, _data:&HttpResponse){}
     fn handle_http_stream_complete(&mut self, _cx:&mut Cx, _request_id:LiveId, _data:&HttpResponse){}
+    fn handle_http_sse_event(&mut self, _cx:&mut Cx, _request_id:LiveId, _event:&SseEvent){}
+    fn handle_http_json_line(&mut self, _cx:&mut Cx, _request_id:LiveId, _line:&str){}
     
     fn handle_network_responses(&mut self, cx: &mut Cx, e:&NetworkResponsesEvent ){
         for e in e{
@@ -46,6 +49,12 @@ fn handle_network_responses(&mut self, cx: &mut Cx, e:&NetworkResponsesEvent ){
                 NetworkResponse::HttpStreamComplete(res)=>{
                     self.handle_http_stream_complete(cx, e.request_id, res);
                 }
+                NetworkResponse::HttpSseEvent(event)=>{
+                    self.handle_http_sse_event(cx, e.request_id, event);
+                }
+                NetworkResponse::HttpJsonLine(line)=>{
+                    self.handle_http_json_line(cx, e.request_id, line);
+                }
             }
         }
     }
@@ -80,6 +89,7 @@ fn match_event(&mut self, cx:&mut Cx, event:&Event){
             Event::Actions(e)=>self.handle_actions(cx,e),
             Event::Draw(e)=>self.handle_draw(cx, e),
             Event::AudioDevices(e)=>self.handle_audio_devices(cx, e),
+            Event::AudioXrun(e)=>self.handle_audio_xrun(cx, e),
             Event::MidiPorts(e)=>self.handle_midi_ports(cx, e),
             Event::VideoInputs(e)=>self.handle_video_inputs(cx, e),
             Event::NetworkResponses(e)=>self.handle_network_responses(cx, e),
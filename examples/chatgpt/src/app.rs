@@ -88,7 +88,6 @@ fn send_message(&self, cx: &mut Cx, message: String) {
         let completion_url = format!("{}/chat/completions", OPENAI_BASE_URL);
         let request_id = live_id!(SendChatMessage);
         let mut request = HttpRequest::new(completion_url, HttpMethod::POST);
-        request.set_is_streaming();
         let ai_key = std::fs::read_to_string("OPENAI_KEY").unwrap_or("".to_string());
         request.set_header("Content-Type".to_string(), "application/json".to_string());
         request.set_header("Authorization".to_string(), format!("Bearer {ai_key}"));
@@ -100,7 +99,7 @@ fn send_message(&self, cx: &mut Cx, message: String) {
             stream: true,
         });
         self.ui.label(ids!(message_label)).set_text(cx, "Answering:..\n");
-        cx.http_request(request_id, request);
+        cx.http_sse_request(request_id, request, false);
     }
 }
 
@@ -121,43 +120,35 @@ fn handle_network_responses(&mut self, cx: &mut Cx, responses:&NetworkResponsesE
         
         for event in responses{
             match &event.response {
-                NetworkResponse::HttpStreamResponse(response)=>{
-                    let data = response.get_string_body().unwrap();
-                    for data in data.split("\n\n"){
-                        if let Some(data) = data.strip_prefix("data: "){
-                            if data != "[DONE]"{
-                                match ChatResponse::deserialize_json(data){
-                                    Ok(chat_response)=>{
-                                        if let Some(content) = &chat_response.choices[0].delta.as_ref().unwrap().content{
-                                            let msg = format!("{}{}", label.text(), content);
-                                            label.set_text(cx, &msg);
-                                        }
-                                    }
-                                    Err(e)=>{
-                                        error!("JSon parse error {:?} {}", e, data);
-                                    }
-                                }
+                NetworkResponse::HttpSseEvent(sse) if sse.data != "[DONE]"=>{
+                    match ChatResponse::deserialize_json(&sse.data){
+                        Ok(chat_response)=>{
+                            if let Some(content) = &chat_response.choices[0].delta.as_ref().unwrap().content{
+                                let msg = format!("{}{}", label.text(), content);
+                                label.set_text(cx, &msg);
                             }
                         }
+                        Err(e)=>{
+                            error!("JSon parse error {:?} {}", e, sse.data);
+                        }
+                    }
+                }
+                // an error page rather than events, streaming backends deliver
+                // it in parts and then complete with the status alone
+                NetworkResponse::HttpStreamResponse(response) |
+                NetworkResponse::HttpStreamComplete(response) if response.status_code >= 300 => {
+                    let body = response.get_string_body().unwrap_or_default();
+                    let text = label.text();
+                    if text.starts_with("Failed to connect with OpenAI"){
+                        label.set_text(cx, &format!("{}{}", text, body));
+                    }
+                    else{
+                        label.set_text(cx, &format!("Failed to connect with OpenAI: {}\n{}", response.status_code, body));
                     }
                 }
                 NetworkResponse::HttpStreamComplete(_res)=>{
                     error!("Stream complete");
                 }
-                NetworkResponse::HttpResponse(response) => {
-                                       
-                    match event.request_id {
-                        live_id!(SendChatMessage) => {
-                            if response.status_code == 200 {
-                                let chat_response = response.get_json_body::<ChatResponse>().unwrap();
-                                label.set_text(cx, &chat_response.choices[0].message.as_ref().unwrap().content.as_ref().unwrap());
-                            } else {
-                                label.set_text(cx, "Failed to connect with OpenAI");
-                            }
-                        },
-                        _ => (),
-                    }
-                }
                 NetworkResponse::HttpRequestError(error) => {
                     let label = self.ui.label(ids!(message_label));
                     label.set_text(cx, &format!("Failed to connect with OpenAI {:?}", error));
@@ -0,0 +1,151 @@
+// The ironfish synth as a CLAP plugin:
+//   cargo build --release -p makepad-example-ironfish-clap
+//   cp target/release/libmakepad_example_ironfish_clap.so ~/.clap/ironfish.clap
+//   clap-validator validate ~/.clap/ironfish.clap
+#![cfg(all(target_os = "linux", not(target_env = "ohos")))]
+
+use {
+    makepad_clap_export::*,
+    makepad_synth_ironfish::{
+        ironfish::IronFish,
+        makepad_audio_graph::{self, AudioComponent},
+        makepad_platform::*,
+    },
+};
+
+const OSC_TYPES: &[&str] = &["DPWSawPulse", "BlampTri", "Pure", "SuperSaw", "HyperSaw", "HarmonicSeries"];
+
+struct IronFishClap;
+
+impl ClapExport for IronFishClap {
+    fn descriptor() -> ClapDescriptor {
+        ClapDescriptor {
+            id: "dev.makepad.ironfish",
+            name: "Ironfish",
+            vendor: "Makepad",
+            url: "https://makepad.dev",
+            version: "1.0.0",
+            description: "The makepad ironfish synthesizer",
+            features: &["instrument", "synthesizer", "stereo"],
+            // out of scope here, the ironfish app doesn't speak the `--clap-gui`
+            // link yet so hosts show their own editor for the parameters
+            gui: None,
+        }
+    }
+
+    fn params() -> Vec<ClapParam> {
+        let osc = | n: &str, module: &str | vec![
+            ClapParam::choice(&format!("settings.{}.osc_type", n), OSC_TYPES, 0).with_name("Type").with_module(module),
+            ClapParam::int(&format!("settings.{}.transpose", n), -24, 24, 0).with_name("Transpose").with_module(module).with_unit("st"),
+            ClapParam::float(&format!("settings.{}.detune", n), -1.0, 1.0, 0.0).with_name("Detune").with_module(module),
+            ClapParam::float(&format!("settings.{}.harmonic", n), 0.0, 1.0, 0.0).with_name("Harmonic").with_module(module),
+        ];
+        let envelope = | n: &str, module: &str | vec![
+            ClapParam::float(&format!("settings.{}.a", n), 0.0, 1.0, 0.05).with_name("Attack").with_module(module),
+            ClapParam::float(&format!("settings.{}.h", n), 0.0, 1.0, 0.0).with_name("Hold").with_module(module),
+            ClapParam::float(&format!("settings.{}.d", n), 0.0, 1.0, 0.2).with_name("Decay").with_module(module),
+            ClapParam::float(&format!("settings.{}.s", n), 0.0, 1.0, 0.5).with_name("Sustain").with_module(module),
+            ClapParam::float(&format!("settings.{}.r", n), 0.0, 1.0, 0.2).with_name("Release").with_module(module),
+        ];
+        let mut params = Vec::new();
+        params.extend(osc("osc1", "Osc 1"));
+        params.extend(osc("osc2", "Osc 2"));
+        params.extend([
+            ClapParam::float("settings.osc_balance", 0.0, 1.0, 0.5).with_name("Osc Balance").with_module("Mixer"),
+            ClapParam::float("settings.sub_osc", 0.0, 1.0, 0.1).with_name("Sub Osc").with_module("Mixer"),
+            ClapParam::float("settings.noise", 0.0, 1.0, 0.0).with_name("Noise").with_module("Mixer"),
+            ClapParam::float("settings.portamento", 0.0, 1.0, 0.0).with_name("Portamento").with_module("Mixer"),
+            ClapParam::choice("settings.filter1.filter_type", &["LowPass", "HighPass", "BandPass", "BandReject"], 0).with_name("Type").with_module("Filter"),
+            ClapParam::float("settings.filter1.cutoff", 0.0, 1.0, 0.5).with_name("Cutoff").with_module("Filter"),
+            ClapParam::float("settings.filter1.resonance", 0.0, 1.0, 0.05).with_name("Resonance").with_module("Filter"),
+            ClapParam::float("settings.filter1.envelope_amount", 0.0, 1.0, 0.1).with_name("Envelope").with_module("Filter"),
+            ClapParam::float("settings.filter1.lfo_amount", 0.0, 1.0, 0.1).with_name("LFO").with_module("Filter"),
+            ClapParam::float("settings.lfo.rate", 0.0, 1.0, 0.2).with_name("Rate").with_module("LFO"),
+            ClapParam::choice("settings.lfo.waveform", &["Saw", "Sine", "Pulse", "Triangle"], 0).with_name("Waveform").with_module("LFO"),
+        ]);
+        params.extend(envelope("volume_envelope", "Volume Envelope"));
+        params.extend(envelope("mod_envelope", "Mod Envelope"));
+        params.extend([
+            ClapParam::float("settings.delay.delaysend", 0.0, 1.0, 0.15).with_name("Send").with_module("Delay"),
+            ClapParam::float("settings.delay.delayfeedback", 0.0, 1.0, 0.8).with_name("Feedback").with_module("Delay"),
+            ClapParam::toggle("settings.bitcrush.enable", false).with_name("Enable").with_module("Bitcrush"),
+            ClapParam::float("settings.bitcrush.amount", 0.0, 1.0, 0.4).with_name("Amount").with_module("Bitcrush"),
+            ClapParam::toggle("settings.arp.enabled", false).with_name("Enable").with_module("Arpeggiator"),
+            ClapParam::int("settings.arp.octaves", 0, 4, 0).with_name("Octaves").with_module("Arpeggiator"),
+        ]);
+        params
+    }
+
+    fn live_register(cx: &mut Cx) {
+        IronFish::register_main_module(cx);
+        makepad_audio_graph::live_design(cx);
+        makepad_synth_ironfish::live_design(cx);
+    }
+
+    fn new_component(cx: &mut Cx) -> Box<dyn AudioComponent> {
+        Box::new(new_ironfish(cx))
+    }
+}
+
+// the synth as its live design has it
+fn new_ironfish(cx: &mut Cx) -> IronFish {
+    let mut ironfish = IronFish::new_main(cx).unwrap_or_else( || IronFish::new(cx));
+    // the host plays the notes, the arpeggiator is there to turn on
+    ironfish.apply_over(cx, live!{settings: {arp: {enabled: false}}});
+    ironfish
+}
+
+clap_export!(IronFishClap);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the value of a parameter a live property of the synth stands for
+    fn param_value(param: &ClapParam, value: &LiveValue) -> Option<f64> {
+        match (value, &param.kind) {
+            (LiveValue::Float32(v), ClapParamKind::Float) => Some(*v as f64),
+            (LiveValue::Float64(v), ClapParamKind::Float) => Some(*v),
+            (LiveValue::Int64(v), ClapParamKind::Int) => Some(*v as f64),
+            (LiveValue::Bool(v), ClapParamKind::Toggle) => Some(*v as u8 as f64),
+            (LiveValue::BareEnum(id), ClapParamKind::Choice(options)) => options.iter().position( | o | LiveId::from_str(o) == *id).map( | i | i as f64),
+            _ => None
+        }
+    }
+
+    // the parameters are written out by hand, they have to match the synth
+    #[test]
+    fn params_match_the_synth() {
+        let mut cx = Cx::new(Box::new( | _, _ | {}));
+        IronFishClap::live_register(&mut cx);
+        cx.live_expand();
+        let settings = new_ironfish(&mut cx).settings.live_read();
+        for param in IronFishClap::params() {
+            let path: Vec<LiveId> = param.path.strip_prefix("settings.").unwrap().split('.').map(LiveId::from_str).collect();
+            let value = settings.read_field_value(&path).unwrap_or_else( || panic!("{} is not in the synth", param.path));
+            let value = param_value(&param, value).unwrap_or_else( || panic!("{} is {:?} in the synth", param.path, value));
+            assert!((value - param.default).abs() < 1e-6, "{} is {} in the synth, not {}", param.path, value, param.default);
+        }
+    }
+
+    // automation goes straight to the node on the audio thread
+    #[test]
+    fn node_takes_every_param() {
+        let mut cx = Cx::new(Box::new( | _, _ | {}));
+        IronFishClap::live_register(&mut cx);
+        cx.live_expand();
+        let mut synth = new_ironfish(&mut cx);
+        let mut node = synth.get_graph_node(&mut cx);
+        let params = IronFishClap::params();
+        let target = | param: &ClapParam | if param.default == param.max {param.min} else {param.max};
+        for param in &params {
+            assert!(node.set_param(&param.live_path(), &param.live_value(target(param))), "{} is not taken", param.path);
+        }
+        let settings = synth.settings.live_read();
+        for param in &params {
+            let path = &param.live_path()[1..];
+            let value = param_value(param, settings.read_field_value(path).unwrap()).unwrap();
+            assert!((value - target(param)).abs() < 1e-6, "{} is {}", param.path, value);
+        }
+    }
+}
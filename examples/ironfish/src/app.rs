@@ -610,6 +610,7 @@ fn handle_event(&mut self, cx: &mut Cx, event: &Event) {
                     AudioGraphAction::VoiceOff { voice } => {
                         display_audio.voice_off(cx, voice);
                     }
+                    AudioGraphAction::Meter { .. } => ()
                 };
             });
     }
@@ -2283,6 +2283,14 @@ fn render_to_audio_buffer(
     ) {
         self.fill_buffer(outputs[0], display)
     }
+
+    fn set_param(&mut self, path: &[LiveId], value: &LiveValue) -> bool {
+        // the settings are shared with the component, so this is all it takes
+        match path.split_first() {
+            Some((id, rest)) if *id == live_id!(settings) => self.settings.set_atomic_path(rest, value),
+            _ => false,
+        }
+    }
 }
 
 impl AudioComponent for IronFish {
@@ -0,0 +1,228 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::client::HttpUrl;
+
+/// A cookie as a server set it (RFC 6265)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase, without a leading dot
+    pub domain: String,
+    /// Only sent to the host that set it, when the server gave no Domain
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// None for session cookies
+    pub expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` value received from `url`. None when it is
+    /// malformed or for a domain the url can't set cookies on
+    pub fn parse(url: &HttpUrl, set_cookie: &str) -> Option<Cookie> {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None
+        }
+        let host = url.host.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(&url.path),
+            secure: false,
+            http_only: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attr in parts {
+            let (key, value) = attr.split_once('=').map( | (k, v) | (k.trim(), v.trim())).unwrap_or((attr.trim(), ""));
+            if key.eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    if !domain_match(&host, &domain) {
+                        return None
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+            }
+            else if key.eq_ignore_ascii_case("path") {
+                if value.starts_with('/') {
+                    cookie.path = value.to_string();
+                }
+            }
+            else if key.eq_ignore_ascii_case("max-age") {
+                // max-age wins over expires
+                if let Ok(secs) = value.parse::<i64>() {
+                    max_age = Some(secs);
+                }
+            }
+            else if key.eq_ignore_ascii_case("expires") {
+                if cookie.expires.is_none() {
+                    cookie.expires = parse_http_date(value);
+                }
+            }
+            else if key.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+            }
+            else if key.eq_ignore_ascii_case("httponly") {
+                cookie.http_only = true;
+            }
+        }
+        if let Some(secs) = max_age {
+            cookie.expires = Some(if secs <= 0 {UNIX_EPOCH} else {SystemTime::now() + Duration::from_secs(secs as u64)});
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.map( | e | e <= now).unwrap_or(false)
+    }
+
+    /// Whether a request to `url` carries this cookie
+    pub fn matches(&self, url: &HttpUrl) -> bool {
+        let host = url.host.to_ascii_lowercase();
+        let domain_ok = if self.host_only {host == self.domain} else {domain_match(&host, &self.domain)};
+        domain_ok && path_match(&url.path, &self.path) && (!self.secure || url.is_https())
+    }
+}
+
+/// The cookies an `HttpClient` remembers between requests
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> CookieJar {
+        CookieJar::default()
+    }
+
+    /// Remembers a `Set-Cookie` header received from `url`. A cookie that
+    /// has already expired removes the one it replaces
+    pub fn store(&mut self, url: &HttpUrl, set_cookie: &str) {
+        let Some(cookie) = Cookie::parse(url, set_cookie) else {
+            return
+        };
+        self.cookies.retain( | c | !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path));
+        if !cookie.is_expired(SystemTime::now()) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    pub fn insert(&mut self, cookie: Cookie) {
+        self.cookies.retain( | c | !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path));
+        self.cookies.push(cookie);
+    }
+
+    /// The `Cookie` header for a request to `url`, longer paths first
+    pub fn header(&self, url: &HttpUrl) -> Option<String> {
+        let now = SystemTime::now();
+        let mut cookies: Vec<&Cookie> = self.cookies.iter().filter( | c | !c.is_expired(now) && c.matches(url)).collect();
+        if cookies.is_empty() {
+            return None
+        }
+        cookies.sort_by_key( | c | std::cmp::Reverse(c.path.len()));
+        let pairs: Vec<String> = cookies.iter().map( | c | format!("{}={}", c.name, c.value)).collect();
+        Some(pairs.join("; "))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        self.cookies.iter().find( | c | c.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.') && host.parse::<std::net::IpAddr>().is_err())
+}
+
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path || (request_path.starts_with(cookie_path) && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// the directory of the request path
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string()
+    }
+}
+
+/// Parses the `Sun, 06 Nov 1994 08:49:37 GMT` dates of http headers, and
+/// the dashed `06-Nov-1994` form old servers put in cookies
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let date = date.replace('-', " ");
+    let mut parts = date.split_whitespace();
+    let mut day_part = parts.next()?;
+    if day_part.ends_with(',') {
+        day_part = parts.next()?;
+    }
+    let day: u32 = day_part.parse().ok()?;
+    let month_part = parts.next()?.to_ascii_lowercase();
+    let month = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"]
+        .iter().position( | m | month_part.starts_with(m))? as u32 + 1;
+    let mut year: i64 = parts.next()?.parse().ok()?;
+    if year < 100 {
+        year += if year < 70 {2000} else {1900};
+    }
+    let mut time = parts.next()?.split(':').map( | t | t.parse::<u64>());
+    let (Some(Ok(h)), Some(Ok(m)), Some(Ok(s))) = (time.next(), time.next(), time.next()) else {
+        return None
+    };
+    if !(1..=31).contains(&day) || h > 23 || m > 59 || s > 60 {
+        return None
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Some(UNIX_EPOCH)
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(days as u64 * 86400 + h * 3600 + m * 60 + s))
+}
+
+/// Formats a time the way http headers want it
+pub fn format_http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map( | d | d.as_secs()).unwrap_or(0);
+    let days = (secs / 86400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let weekday = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"][(days % 7) as usize];
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"][month as usize - 1];
+    let rem = secs % 86400;
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT", weekday, day, month, year, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// days since 1970-01-01 of a proleptic gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 {year - 1} else {year};
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 {m - 3} else {m + 9}) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 {mp + 3} else {mp - 9} as u32;
+    let year = yoe + era * 400 + if month <= 2 {1} else {0};
+    (year, month, day)
+}
@@ -2,3 +2,9 @@
  pub mod utils;
  pub mod server;
  pub mod websocket;
+ pub mod websocket_deflate;
+ pub mod request;
+ pub mod router;
+ pub mod static_files;
+ pub mod client;
+ pub mod cookie;
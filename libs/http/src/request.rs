@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+// limits on what a client can make us buffer
+const MAX_LINE_LEN: usize = 8192;
+const MAX_HEADERS: usize = 100;
+
+/// A request as the router sees it, with the body read in full
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Percent decoded, without the query
+    pub path: String,
+    /// The raw query after the `?`
+    pub query: Option<String>,
+    pub http_10: bool,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The values of the `:name` and `*name` parts of the matched route
+    pub params: HashMap<String, String>,
+    pub remote_addr: Option<SocketAddr>,
+    pub received: Instant,
+}
+
+impl HttpRequest {
+    pub fn new(method: &str, path: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: None,
+            http_10: false,
+            headers: Vec::new(),
+            body: Vec::new(),
+            params: HashMap::new(),
+            remote_addr: None,
+            received: Instant::now(),
+        }
+    }
+
+    /// The first header with this name, names are case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find( | (n, _) | n.eq_ignore_ascii_case(name)).map( | (_, v) | v.as_str())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> HttpRequest {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, content_type: &str, body: Vec<u8>) -> HttpRequest {
+        self.headers.retain( | (n, _) | !n.eq_ignore_ascii_case("Content-Type"));
+        self.headers.push(("Content-Type".to_string(), content_type.to_string()));
+        self.body = body;
+        self
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map( | v | v.as_str())
+    }
+
+    /// A decoded value from the query string
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        for pair in self.query.as_deref()?.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if percent_decode(key, true).as_deref() == Some(name) {
+                return percent_decode(value, true)
+            }
+        }
+        None
+    }
+
+    /// HTTP/1.1 connections stay open unless the client asks to close them,
+    /// HTTP/1.0 ones only when it asks to keep them
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.header("Connection").unwrap_or("");
+        let has = | token: &str | connection.split(',').any( | t | t.trim().eq_ignore_ascii_case(token));
+        if self.http_10 {has("keep-alive")} else {!has("close")}
+    }
+}
+
+/// A response, `Content-Length` and `Connection` are added when it is written
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16) -> HttpResponse {
+        HttpResponse {status, headers: Vec::new(), body: Vec::new()}
+    }
+
+    pub fn ok() -> HttpResponse {
+        HttpResponse::new(200)
+    }
+
+    pub fn not_found() -> HttpResponse {
+        HttpResponse::text(404, "Not Found")
+    }
+
+    pub fn text(status: u16, text: &str) -> HttpResponse {
+        HttpResponse::new(status).with_body("text/plain; charset=utf-8", text.as_bytes().to_vec())
+    }
+
+    pub fn html(status: u16, html: &str) -> HttpResponse {
+        HttpResponse::new(status).with_body("text/html; charset=utf-8", html.as_bytes().to_vec())
+    }
+
+    pub fn with_body(mut self, content_type: &str, body: Vec<u8>) -> HttpResponse {
+        self.set_header("Content-Type", content_type);
+        self.body = body;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> HttpResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find( | (n, _) | n.eq_ignore_ascii_case(name)).map( | (_, v) | v.as_str())
+    }
+
+    /// Replaces all headers with this name
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain( | (n, _) | !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+    }
+
+    // 1xx, 204 and 304 responses never have a body
+    fn has_body(&self) -> bool {
+        !(self.status < 200 || self.status == 204 || self.status == 304)
+    }
+
+    /// The status line and headers. HEAD responses get the length of the
+    /// body they would have had
+    pub fn head_bytes(&self, keep_alive: bool) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, status_text(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if self.has_body() && self.header("Content-Length").is_none() {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str(if keep_alive {"Connection: keep-alive\r\n\r\n"} else {"Connection: close\r\n\r\n"});
+        head.into_bytes()
+    }
+
+    pub fn write_to(&self, out: &mut impl Write, head_only: bool, keep_alive: bool) -> std::io::Result<()> {
+        out.write_all(&self.head_bytes(keep_alive))?;
+        if !head_only && self.has_body() {
+            out.write_all(&self.body)?;
+        }
+        out.flush()
+    }
+}
+
+pub fn status_text(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        416 => "Range Not Satisfiable",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown"
+    }
+}
+
+/// Decodes %XX escapes, and `+` as a space in query strings. None when the
+/// escapes aren't valid or don't decode to utf8
+pub fn percent_decode(s: &str, plus_is_space: bool) -> Option<String> {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3)?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+                continue
+            }
+            b'+' if plus_is_space => out.push(b' '),
+            c => out.push(c)
+        }
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+// a line without its CRLF, errors are the status to answer with
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>, u16> {
+    let mut line = Vec::new();
+    match reader.take(MAX_LINE_LEN as u64 + 1).read_until(b'\n', &mut line) {
+        Ok(0) => return Ok(None),
+        Ok(_) => (),
+        Err(_) => return Err(408)
+    }
+    if !line.ends_with(b"\n") {
+        return Err(if line.len() > MAX_LINE_LEN {431} else {400})
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    String::from_utf8(line).map(Some).map_err( | _ | 400)
+}
+
+/// Reads the request line and headers. Ok(None) when the client closed the
+/// connection before sending anything, errors are the status to answer with
+pub fn read_request_head(reader: &mut impl BufRead) -> Result<Option<HttpRequest>, u16> {
+    // empty lines before a request are allowed
+    let line = loop {
+        match read_line(reader) {
+            Ok(Some(line)) if line.is_empty() => continue,
+            Ok(Some(line)) => break line,
+            Ok(None) => return Ok(None),
+            // an idle keep-alive connection that timed out
+            Err(408) => return Ok(None),
+            Err(e) => return Err(e)
+        }
+    };
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(400)
+    };
+    let http_10 = match version {
+        "HTTP/1.1" => false,
+        "HTTP/1.0" => true,
+        _ if version.starts_with("HTTP/") => return Err(505),
+        _ => return Err(400)
+    };
+    if method.is_empty() || !method.bytes().all( | c | c.is_ascii_alphabetic()) {
+        return Err(400)
+    }
+    // absolute form targets, as sent to proxies
+    let target = match target.strip_prefix("http://").or_else( || target.strip_prefix("https://")) {
+        Some(rest) => rest.find('/').map( | i | &rest[i..]).unwrap_or("/"),
+        None => target
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None)
+    };
+    let options_star = method == "OPTIONS" && path == "*";
+    if !path.starts_with('/') && !options_star {
+        return Err(400)
+    }
+    let path = percent_decode(path, false).filter( | p | !p.contains('\0')).ok_or(400u16)?;
+
+    let mut request = HttpRequest::new(method, &path);
+    request.query = query;
+    request.http_10 = http_10;
+    loop {
+        let line = read_line(reader)?.ok_or(400u16)?;
+        if line.is_empty() {
+            break
+        }
+        if request.headers.len() >= MAX_HEADERS {
+            return Err(431)
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(400)
+        };
+        // no whitespace between the name and the colon, and no obsolete folding
+        if name.is_empty() || name.ends_with([' ', '\t']) || name.starts_with([' ', '\t']) {
+            return Err(400)
+        }
+        request.headers.push((name.to_string(), value.trim().to_string()));
+    }
+    if !http_10 && request.header("Host").is_none() {
+        return Err(400)
+    }
+    Ok(Some(request))
+}
+
+/// Whether the client waits for a `100 Continue` before sending the body
+pub fn expects_continue(request: &HttpRequest) -> bool {
+    !request.http_10 && request.header("Expect").map( | e | e.eq_ignore_ascii_case("100-continue")).unwrap_or(false)
+}
+
+/// Reads the body announced by the headers, by length or in chunks
+pub fn read_request_body(reader: &mut impl BufRead, request: &mut HttpRequest, max_size: u64) -> Result<(), u16> {
+    let chunked = match request.header("Transfer-Encoding") {
+        Some(te) if te.eq_ignore_ascii_case("chunked") => true,
+        Some(_) => return Err(501),
+        None => false
+    };
+    let content_length = match request.header("Content-Length") {
+        Some(len) => Some(len.parse::<u64>().map_err( | _ | 400u16)?),
+        None => None
+    };
+    // both of them is how requests get smuggled past proxies
+    if chunked && content_length.is_some() {
+        return Err(400)
+    }
+    if chunked {
+        request.body = read_chunked(reader, max_size)?;
+    }
+    else if let Some(len) = content_length {
+        if len > max_size {
+            return Err(413)
+        }
+        let mut body = vec![0u8; len as usize];
+        reader.read_exact(&mut body).map_err( | _ | 400u16)?;
+        request.body = body;
+    }
+    Ok(())
+}
+
+fn read_chunked(reader: &mut impl BufRead, max_size: u64) -> Result<Vec<u8>, u16> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader)?.ok_or(400u16)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size, 16).map_err( | _ | 400u16)?;
+        if size == 0 {
+            break
+        }
+        if size > max_size.saturating_sub(body.len() as u64) {
+            return Err(413)
+        }
+        let start = body.len();
+        reader.take(size).read_to_end(&mut body).map_err( | _ | 400u16)?;
+        if ((body.len() - start) as u64) < size {
+            return Err(400)
+        }
+        if read_line(reader)?.as_deref() != Some("") {
+            return Err(400)
+        }
+    }
+    // trailers aren't used, but have to be read
+    loop {
+        match read_line(reader)? {
+            Some(line) if line.is_empty() => return Ok(body),
+            Some(_) => (),
+            None => return Err(400)
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use crate::request::*;
+use crate::static_files::StaticFiles;
+
+pub type HttpHandler = Box<dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync>;
+
+/// Runs around every request. `before` can answer a request itself, which
+/// skips the route, `after` sees every response on the way out
+pub trait HttpMiddleware: Send + Sync {
+    fn before(&self, _request: &HttpRequest) -> Option<HttpResponse> {
+        None
+    }
+
+    fn after(&self, _request: &HttpRequest, _response: &mut HttpResponse) {
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    // the rest of the path
+    Rest(String),
+}
+
+/// A route path like `/users/:id` or `/files/*path`
+#[derive(Debug)]
+pub struct PathPattern {
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> PathPattern {
+        let parts: Vec<&str> = pattern.split('/').filter( | s | !s.is_empty()).collect();
+        let mut segments = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            if let Some(name) = part.strip_prefix(':') {
+                segments.push(Segment::Param(name.to_string()));
+            }
+            else if let Some(name) = part.strip_prefix('*') {
+                if i + 1 != parts.len() {
+                    panic!("* has to be the last part of route {}", pattern);
+                }
+                segments.push(Segment::Rest(name.to_string()));
+            }
+            else {
+                segments.push(Segment::Literal(part.to_string()));
+            }
+        }
+        PathPattern {segments}
+    }
+
+    /// The params when `path` matches. Empty parts of the path are ignored,
+    /// except that a rest param keeps a trailing slash
+    pub fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = path.split('/').filter( | s | !s.is_empty()).collect();
+        let mut params = HashMap::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(lit) => if parts.get(i) != Some(&lit.as_str()) {
+                    return None
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), parts.get(i)?.to_string());
+                }
+                Segment::Rest(name) => {
+                    let mut rest = parts[i.min(parts.len())..].join("/");
+                    if path.ends_with('/') && !rest.is_empty() {
+                        rest.push('/');
+                    }
+                    params.insert(name.clone(), rest);
+                    return Some(params)
+                }
+            }
+        }
+        if parts.len() != self.segments.len() {
+            return None
+        }
+        Some(params)
+    }
+}
+
+struct Route {
+    method: String,
+    pattern: PathPattern,
+    handler: HttpHandler,
+}
+
+/// Dispatches requests to the first route that matches their method and
+/// path. HEAD requests go to GET routes
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    middleware: Vec<Box<dyn HttpMiddleware>>,
+    fallback: Option<HttpHandler>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    pub fn route(&mut self, method: &str, pattern: &str, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.routes.push(Route {
+            method: method.to_ascii_uppercase(),
+            pattern: PathPattern::parse(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get(&mut self, pattern: &str, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.route("GET", pattern, handler)
+    }
+
+    pub fn post(&mut self, pattern: &str, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.route("POST", pattern, handler)
+    }
+
+    pub fn put(&mut self, pattern: &str, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.route("PUT", pattern, handler)
+    }
+
+    pub fn delete(&mut self, pattern: &str, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.route("DELETE", pattern, handler)
+    }
+
+    /// Serves the files under `files.root`, `pattern` has to end in a rest
+    /// param like `/static/*path`
+    pub fn files(&mut self, pattern: &str, files: StaticFiles) -> &mut Self {
+        let param = match PathPattern::parse(pattern).segments.last() {
+            Some(Segment::Rest(name)) => name.clone(),
+            _ => panic!("route {} for files has to end in a *param", pattern)
+        };
+        self.get(pattern, move | request | files.serve(request, request.param(&param).unwrap_or("")))
+    }
+
+    /// Answers requests no route matches, instead of a 404
+    pub fn fallback(&mut self, handler: impl Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static) -> &mut Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// Middleware runs in the order it is added, and the other way around on
+    /// the way out
+    pub fn middleware(&mut self, middleware: impl HttpMiddleware + 'static) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn handle(&self, request: &mut HttpRequest) -> HttpResponse {
+        let mut ran = 0;
+        let mut response = None;
+        for middleware in &self.middleware {
+            ran += 1;
+            if let Some(r) = middleware.before(request) {
+                response = Some(r);
+                break
+            }
+        }
+        let mut response = match response {
+            Some(response) => response,
+            None => self.dispatch(request)
+        };
+        for middleware in self.middleware[..ran].iter().rev() {
+            middleware.after(request, &mut response);
+        }
+        response
+    }
+
+    fn dispatch(&self, request: &mut HttpRequest) -> HttpResponse {
+        let method = if request.method == "HEAD" {"GET"} else {request.method.as_str()};
+        let mut allowed: Vec<&str> = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.pattern.matches(&request.path) else {continue};
+            if route.method != method {
+                if !allowed.contains(&route.method.as_str()) {
+                    allowed.push(&route.method);
+                }
+                continue
+            }
+            request.params = params;
+            return (route.handler)(request)
+        }
+        if let Some(fallback) = &self.fallback {
+            return fallback(request)
+        }
+        if !allowed.is_empty() {
+            if allowed.contains(&"GET") {
+                allowed.push("HEAD");
+            }
+            return HttpResponse::text(405, "Method Not Allowed").with_header("Allow", &allowed.join(", "))
+        }
+        HttpResponse::not_found()
+    }
+}
+
+/// Logs a line per request, `METHOD path status time`
+pub struct LogMiddleware {
+    sink: Box<dyn Fn(&str) + Send + Sync>,
+}
+
+impl LogMiddleware {
+    pub fn new() -> LogMiddleware {
+        LogMiddleware::with_sink( | line | println!("{}", line))
+    }
+
+    pub fn with_sink(sink: impl Fn(&str) + Send + Sync + 'static) -> LogMiddleware {
+        LogMiddleware {sink: Box::new(sink)}
+    }
+}
+
+impl Default for LogMiddleware {
+    fn default() -> Self {
+        LogMiddleware::new()
+    }
+}
+
+impl HttpMiddleware for LogMiddleware {
+    fn after(&self, request: &HttpRequest, response: &mut HttpResponse) {
+        let ms = request.received.elapsed().as_secs_f64() * 1000.0;
+        (self.sink)(&format!("{} {} {} {:.1}ms", request.method, request.path, response.status, ms));
+    }
+}
+
+/// Answers CORS preflight requests and adds the allow headers to responses
+/// for allowed origins. An origin of `*` allows all of them
+pub struct CorsMiddleware {
+    pub allow_origins: Vec<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age: Option<u32>,
+}
+
+impl Default for CorsMiddleware {
+    fn default() -> Self {
+        CorsMiddleware {
+            allow_origins: vec!["*".to_string()],
+            allow_methods: ["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS"].iter().map( | m | m.to_string()).collect(),
+            allow_headers: vec!["Content-Type".to_string()],
+            allow_credentials: false,
+            max_age: Some(600),
+        }
+    }
+}
+
+impl CorsMiddleware {
+    // the value for Access-Control-Allow-Origin
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.allow_origins.iter().any( | o | o == origin) {
+            return Some(origin.to_string())
+        }
+        if self.allow_origins.iter().any( | o | o == "*") {
+            // credentials can't be used with a wildcard
+            return Some(if self.allow_credentials {origin.to_string()} else {"*".to_string()})
+        }
+        None
+    }
+
+    fn add_origin(&self, origin: &str, response: &mut HttpResponse) -> bool {
+        let Some(allow) = self.allow_origin(origin) else {return false};
+        response.set_header("Access-Control-Allow-Origin", &allow);
+        if allow != "*" {
+            response.headers.push(("Vary".to_string(), "Origin".to_string()));
+        }
+        if self.allow_credentials {
+            response.set_header("Access-Control-Allow-Credentials", "true");
+        }
+        true
+    }
+}
+
+impl HttpMiddleware for CorsMiddleware {
+    fn before(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        if request.method != "OPTIONS" || request.header("Access-Control-Request-Method").is_none() {
+            return None
+        }
+        let origin = request.header("Origin")?;
+        let mut response = HttpResponse::new(204);
+        if self.add_origin(origin, &mut response) {
+            response.set_header("Access-Control-Allow-Methods", &self.allow_methods.join(", "));
+            response.set_header("Access-Control-Allow-Headers", &self.allow_headers.join(", "));
+            if let Some(max_age) = self.max_age {
+                response.set_header("Access-Control-Max-Age", &max_age.to_string());
+            }
+        }
+        Some(response)
+    }
+
+    fn after(&self, request: &HttpRequest, response: &mut HttpResponse) {
+        if request.method == "OPTIONS" && response.header("Access-Control-Allow-Methods").is_some() {
+            return
+        }
+        if let Some(origin) = request.header("Origin") {
+            self.add_origin(origin, response);
+        }
+    }
+}
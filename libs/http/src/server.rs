@@ -2,10 +2,14 @@
 
 use std::net::{TcpListener, TcpStream, SocketAddr, Shutdown};
 use std::io::prelude::*;
-use std::sync::{mpsc, mpsc::{RecvTimeoutError}};
+use std::io::BufReader;
+use std::sync::{Arc, Mutex, mpsc, mpsc::{RecvTimeoutError}};
 use std::time::Duration;
 pub use crate::websocket::{SERVER_WEB_SOCKET_PONG_MESSAGE, ServerWebSocket, ServerWebSocketMessage, ServerWebSocketMessageFormat, ServerWebSocketMessageHeader, SERVER_WEB_SOCKET_PING_MESSAGE};
 use crate::utils::*;
+use crate::websocket_deflate::WebSocketDeflateParams;
+use crate::request::*;
+use crate::router::Router;
 
 #[derive(Clone)]
 pub struct HttpServer {
@@ -72,7 +76,7 @@ pub fn start_http_server(
                     let headers = headers.unwrap();
                     
                     if headers.sec_websocket_key.is_some() {
-                        return handle_web_socket(http_server, tcp_stream, headers, connection_counter);
+                        return handle_web_socket(&http_server.request, tcp_stream, headers, connection_counter, &[]);
                     }
                     if headers.verb == "POST" {
                         return handle_post(http_server, tcp_stream, headers);
@@ -131,38 +135,62 @@ fn handle_post(http_server: HttpServer, mut tcp_stream: TcpStream, headers: Http
     let _ = tcp_stream.shutdown(Shutdown::Both);
 }
 
-fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders, web_socket_id: u64) {
-    let upgrade_response = ServerWebSocket::create_upgrade_response(headers.sec_websocket_key.as_ref().unwrap());
+// `pending` are bytes of the socket that were read along with the headers
+fn handle_web_socket(request: &mpsc::Sender<HttpServerRequest>, mut tcp_stream: TcpStream, headers: HttpServerHeaders, web_socket_id: u64, pending: &[u8]) {
+    let key = headers.sec_websocket_key.as_ref().unwrap();
+    let deflate = headers.header("Sec-WebSocket-Extensions").and_then(WebSocketDeflateParams::accept_offer);
+    let upgrade_response = match &deflate {
+        Some(deflate) => ServerWebSocket::create_upgrade_response_with_deflate(key, deflate),
+        None => ServerWebSocket::create_upgrade_response(key)
+    };
 
     write_bytes_to_tcp_stream_no_error(&mut tcp_stream, upgrade_response.as_bytes());
     
-    let mut write_tcp_stream = tcp_stream.try_clone().unwrap();
+    // the reader answers pings and closes itself, so writes go through a lock
+    // to keep frames from interleaving
+    let write_tcp_stream = Arc::new(Mutex::new(tcp_stream.try_clone().unwrap()));
     let (tx_socket, rx_socket) = mpsc::channel::<Vec<u8 >> ();
     
-    let _write_thread = std::thread::spawn(move || {
-        // xx
-        loop{
-            match rx_socket.recv_timeout(Duration::from_millis(2000)){
-                Ok(data)=>{
-                    if data.is_empty(){
+    let _write_thread = {
+        let write_tcp_stream = write_tcp_stream.clone();
+        let mut compressor = deflate.map( | d | d.server_deflate());
+        std::thread::spawn(move || {
+            loop{
+                match rx_socket.recv_timeout(Duration::from_millis(2000)){
+                    Ok(data)=>{
+                        if data.is_empty(){
+                            break
+                        }
+                        let frame = match &mut compressor {
+                            // small messages aren't worth it
+                            Some(compressor) if data.len() > 64 => {
+                                let data = compressor.compress(&data);
+                                let mut header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
+                                header.set_compressed();
+                                ServerWebSocket::build_message(header, &data)
+                            }
+                            _ => {
+                                let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
+                                ServerWebSocket::build_message(header, &data)
+                            }
+                        };
+                        if write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream.lock().unwrap(), &frame){
+                            break
+                        }
+                    },
+                    Err(RecvTimeoutError::Timeout)=>{ 
+                        write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream.lock().unwrap(), &SERVER_WEB_SOCKET_PING_MESSAGE);
+                    }
+                    Err(RecvTimeoutError::Disconnected)=>{
                         break
                     }
-                    let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, header.as_slice());
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &data);
-                },
-                Err(RecvTimeoutError::Timeout)=>{ 
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &SERVER_WEB_SOCKET_PING_MESSAGE);
-                }
-                Err(RecvTimeoutError::Disconnected)=>{
-                    break
                 }
             }
-        }
-        let _ = write_tcp_stream.shutdown(Shutdown::Both);
-    });
+            let _ = write_tcp_stream.lock().unwrap().shutdown(Shutdown::Both);
+        })
+    };
     
-    if http_server.request.send(HttpServerRequest::ConnectWebSocket {
+    if request.send(HttpServerRequest::ConnectWebSocket {
         headers,
         web_socket_id,
         response_sender: tx_socket.clone()
@@ -172,56 +200,61 @@ fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers
     };
     
     let mut web_socket = ServerWebSocket::new();
-    loop {
-        let mut data = [0u8; 65535];
-        match tcp_stream.read(&mut data) {
+    web_socket.set_inflate(deflate.map( | d | d.server_inflate()));
+    let mut data = vec![0u8; 65535.max(pending.len())];
+    data[..pending.len()].copy_from_slice(pending);
+    let mut pending = pending.len();
+    let mut done = false;
+    while !done {
+        let read = if pending > 0 {Ok(std::mem::take(&mut pending))} else {tcp_stream.read(&mut data)};
+        match read {
             Ok(n) => {
                 if n == 0 {
-                    let _ = tcp_stream.shutdown(Shutdown::Both);
-                    let _ = tx_socket.send(Vec::new());
                     break 
                 }
                 web_socket.parse(&data[0..n], | result | {
                     match result {
-                        Ok(ServerWebSocketMessage::Ping(_)) => {
-                            let _ = tx_socket.send(SERVER_WEB_SOCKET_PONG_MESSAGE.to_vec());
+                        Ok(ServerWebSocketMessage::Ping(payload)) => {
+                            let header = ServerWebSocketMessageHeader::from_len(payload.len(), ServerWebSocketMessageFormat::Pong, false);
+                            write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream.lock().unwrap(), &ServerWebSocket::build_message(header, payload));
                         },
                         Ok(ServerWebSocketMessage::Pong(_)) => {
                         },
                         Ok(ServerWebSocketMessage::Text(_text)) => {
                         }
                         Ok(ServerWebSocketMessage::Binary(data)) => {
-                            if http_server.request.send(HttpServerRequest::BinaryMessage {
+                            if request.send(HttpServerRequest::BinaryMessage {
                                 web_socket_id,
                                 response_sender: tx_socket.clone(),
                                 data: data.to_vec(),
                             }).is_err() {
                                 eprintln!("Websocket message deserialize error");
-                                let _ = tcp_stream.shutdown(Shutdown::Both);
-                                let _ = tx_socket.send(Vec::new());
+                                done = true;
                             };
                         },
-                        Ok(ServerWebSocketMessage::Close) => {
-                            let _ = tcp_stream.shutdown(Shutdown::Both);
+                        Ok(ServerWebSocketMessage::Close{code, ..}) => {
+                            // echo the code back, that completes the closing handshake
+                            write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream.lock().unwrap(), &ServerWebSocket::build_close(code, "", false));
+                            done = true;
                         }
                         Err(e) => {
                             eprintln!("Websocket error {:?}", e);
-                            let _ = tcp_stream.shutdown(Shutdown::Both);
-                            let _ = tx_socket.send(Vec::new());
+                            write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream.lock().unwrap(), &ServerWebSocket::build_close(Some(e.close_code()), "", false));
+                            done = true;
                         }
                     }
                 });
             }
             Err(_) => {
                 println!("Websocket closed");
-                let _ = tcp_stream.shutdown(Shutdown::Both);
-                let _ = tx_socket.send(Vec::new());
                 break;
             }
         }
     }
+    let _ = tcp_stream.shutdown(Shutdown::Both);
+    let _ = tx_socket.send(Vec::new());
     
-    let _ =  http_server.request.send(HttpServerRequest::DisconnectWebSocket {
+    let _ =  request.send(HttpServerRequest::DisconnectWebSocket {
         web_socket_id,
     });
 }
@@ -241,4 +274,113 @@ fn handle_get(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpS
         write_bytes_to_tcp_stream_no_error(&mut tcp_stream, &response.body);
     }
     let _ = tcp_stream.shutdown(Shutdown::Both);
-}
\ No newline at end of file
+}
+
+/// A server that answers requests through a [`Router`] on the connection
+/// threads, instead of sending them off over a channel
+#[derive(Clone)]
+pub struct RouterServer {
+    pub listen_address: SocketAddr,
+    pub router: Arc<Router>,
+    pub max_body_size: u64,
+    /// How long an idle connection is kept open
+    pub keep_alive_timeout: Duration,
+    /// Where websocket upgrades go, they are turned away without it. The
+    /// messages are the same as those of [`start_http_server`]
+    pub web_sockets: Option<mpsc::Sender<HttpServerRequest>>,
+}
+
+impl RouterServer {
+    pub fn new(listen_address: SocketAddr, router: Router) -> RouterServer {
+        RouterServer {
+            listen_address,
+            router: Arc::new(router),
+            max_body_size: 16 * 1024 * 1024,
+            keep_alive_timeout: Duration::from_secs(5),
+            web_sockets: None,
+        }
+    }
+}
+
+/// Returns the address it is listening on, which tells the port when
+/// binding to port 0
+pub fn start_router_server(
+    server: RouterServer,
+) -> Option<(SocketAddr, std::thread::JoinHandle<() >)> {
+    let listener = if let Ok(listener) = TcpListener::bind(server.listen_address) {listener} else {println!("Cannot bind http server port"); return None};
+    let local_addr = listener.local_addr().ok()?;
+    let listen_thread = std::thread::spawn(move || {
+        let mut connection_counter = 0u64;
+        for tcp_stream in listener.incoming() {
+            let tcp_stream = if let Ok(tcp_stream) = tcp_stream {
+                tcp_stream
+            }
+            else {
+                println!("Incoming stream failure");
+                continue
+            };
+            let server = server.clone();
+            connection_counter += 1;
+            let _read_thread = std::thread::spawn(move || {
+                handle_router_connection(server, tcp_stream, connection_counter);
+            });
+        }
+    });
+    Some((local_addr, listen_thread))
+}
+
+fn handle_router_connection(server: RouterServer, tcp_stream: TcpStream, connection_id: u64) {
+    let _ = tcp_stream.set_read_timeout(Some(server.keep_alive_timeout));
+    let remote_addr = tcp_stream.peer_addr().ok();
+    let mut write_stream = if let Ok(s) = tcp_stream.try_clone() {s} else {return};
+    let mut reader = BufReader::new(tcp_stream);
+    loop {
+        let mut request = match read_request_head(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(status) => {
+                let _ = HttpResponse::text(status, status_text(status)).write_to(&mut write_stream, false, false);
+                break
+            }
+        };
+        if let (Some(web_sockets), Some(key)) = (&server.web_sockets, request.header("Sec-WebSocket-Key")) {
+            let headers = web_socket_headers(&request, key, remote_addr);
+            let pending = reader.buffer().to_vec();
+            let tcp_stream = reader.into_inner();
+            // websockets are quiet for longer than a keep alive
+            let _ = tcp_stream.set_read_timeout(None);
+            return handle_web_socket(web_sockets, tcp_stream, headers, connection_id, &pending)
+        }
+        if expects_continue(&request) {
+            write_bytes_to_tcp_stream_no_error(&mut write_stream, b"HTTP/1.1 100 Continue\r\n\r\n");
+        }
+        if let Err(status) = read_request_body(&mut reader, &mut request, server.max_body_size) {
+            let _ = HttpResponse::text(status, status_text(status)).write_to(&mut write_stream, false, false);
+            break
+        }
+        request.remote_addr = remote_addr;
+        let response = server.router.handle(&mut request);
+        let keep_alive = request.keep_alive();
+        if response.write_to(&mut write_stream, request.method == "HEAD", keep_alive).is_err() || !keep_alive {
+            break
+        }
+    }
+    let _ = write_stream.shutdown(Shutdown::Both);
+}
+
+// the headers `start_http_server` hands out with a websocket
+fn web_socket_headers(request: &HttpRequest, key: &str, remote_addr: Option<SocketAddr>) -> HttpServerHeaders {
+    let mut lines = vec![format!("{} {} HTTP/1.1\r\n", request.method, request.path)];
+    lines.extend(request.headers.iter().map( | (name, value) | format!("{}: {}\r\n", name, value)));
+    HttpServerHeaders {
+        addr: remote_addr.unwrap_or_else( | | SocketAddr::from(([0, 0, 0, 0], 0))),
+        lines,
+        verb: request.method.clone(),
+        path_no_slash: request.path.trim_start_matches('/').to_string(),
+        path: request.path.clone(),
+        search: request.query.as_ref().map( | q | format!("?{}", q)),
+        content_length: None,
+        accept_encoding: request.header("Accept-Encoding").map( | v | v.to_string()),
+        sec_websocket_key: Some(key.to_string()),
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use crate::request::*;
+
+/// Serves files from a directory, with ETags, byte ranges and precompressed
+/// `.br` and `.gz` siblings of a file when the client accepts them
+#[derive(Clone, Debug)]
+pub struct StaticFiles {
+    pub root: PathBuf,
+    /// Served for paths that name a directory
+    pub index: String,
+    pub cache_control: String,
+    /// Added to every response, for things like COEP headers
+    pub headers: Vec<(String, String)>,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> StaticFiles {
+        StaticFiles {
+            root: root.into(),
+            index: "index.html".to_string(),
+            cache_control: "no-cache".to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn with_cache_control(mut self, cache_control: &str) -> StaticFiles {
+        self.cache_control = cache_control.to_string();
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> StaticFiles {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// The file for a path relative to the root, None when it tries to get
+    /// out of it or names a dotfile like `.git` or `.env`
+    pub fn resolve(&self, rel_path: &str) -> Option<PathBuf> {
+        if rel_path.contains(['\\', '\0']) || rel_path.split('/').any( | s | (s.starts_with('.') && s != ".") || s.contains(':')) {
+            return None
+        }
+        let mut path = self.root.clone();
+        for segment in rel_path.split('/').filter( | s | !s.is_empty() && *s != ".") {
+            path.push(segment);
+        }
+        if rel_path.is_empty() || rel_path.ends_with('/') || path.is_dir() {
+            path.push(&self.index);
+        }
+        Some(path)
+    }
+
+    pub fn serve(&self, request: &HttpRequest, rel_path: &str) -> HttpResponse {
+        let Some(path) = self.resolve(rel_path) else {
+            return HttpResponse::text(403, "Forbidden")
+        };
+        let Some((file_path, encoding)) = select_encoding(&path, request.header("Accept-Encoding").unwrap_or("")) else {
+            return HttpResponse::not_found()
+        };
+        let Ok(meta) = file_path.metadata() else {
+            return HttpResponse::not_found()
+        };
+        let len = meta.len();
+        let mtime = meta.modified().ok().and_then( | t | t.duration_since(UNIX_EPOCH).ok()).map( | d | d.as_secs()).unwrap_or(0);
+        let etag = match encoding {
+            Some(encoding) => format!("\"{:x}-{:x}-{}\"", len, mtime, encoding),
+            None => format!("\"{:x}-{:x}\"", len, mtime)
+        };
+
+        let mut response = HttpResponse::new(200);
+        response.headers.push(("Content-Type".to_string(), mime_type(&path).to_string()));
+        response.headers.push(("ETag".to_string(), etag.clone()));
+        response.headers.push(("Cache-Control".to_string(), self.cache_control.clone()));
+        response.headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
+        response.headers.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+        if let Some(encoding) = encoding {
+            response.headers.push(("Content-Encoding".to_string(), encoding.to_string()));
+        }
+        response.headers.extend(self.headers.iter().cloned());
+
+        if let Some(if_none_match) = request.header("If-None-Match") {
+            if etag_matches(if_none_match, &etag) {
+                response.status = 304;
+                return response
+            }
+        }
+
+        // If-Range asks for the whole file when it changed since
+        let range = match request.header("If-Range") {
+            Some(if_range) if if_range != etag => None,
+            _ => request.header("Range").and_then( | r | parse_range(r, len))
+        };
+        let (start, end) = match range {
+            Some(Ok((start, end))) => {
+                response.status = 206;
+                response.headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end - 1, len)));
+                (start, end)
+            }
+            Some(Err(())) => {
+                let mut response = HttpResponse::text(416, "Range Not Satisfiable");
+                response.headers.push(("Content-Range".to_string(), format!("bytes */{}", len)));
+                return response
+            }
+            None => (0, len)
+        };
+
+        // HEAD only needs the length
+        if request.method == "HEAD" {
+            response.headers.push(("Content-Length".to_string(), (end - start).to_string()));
+            return response
+        }
+        match read_file_range(&file_path, start, end) {
+            Ok(body) => {
+                response.body = body;
+                response
+            }
+            Err(_) => HttpResponse::text(500, "Internal Server Error")
+        }
+    }
+}
+
+// the file to send, and its Content-Encoding
+fn select_encoding(path: &Path, accept_encoding: &str) -> Option<(PathBuf, Option<&'static str>)> {
+    let accepts = | name: &str | accept_encoding.split(',').any( | e | {
+        let mut parts = e.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        let q_zero = parts.any( | p | {
+            let p = p.trim();
+            p.strip_prefix("q=").map( | q | q.trim().parse::<f32>().map( | q | q == 0.0).unwrap_or(false)).unwrap_or(false)
+        });
+        coding.eq_ignore_ascii_case(name) && !q_zero
+    });
+    for (encoding, ext) in [("br", "br"), ("gzip", "gz")] {
+        if accepts(encoding) {
+            let mut encoded = path.as_os_str().to_owned();
+            encoded.push(".");
+            encoded.push(ext);
+            let encoded = PathBuf::from(encoded);
+            if encoded.is_file() {
+                return Some((encoded, Some(encoding)))
+            }
+        }
+    }
+    if path.is_file() {
+        return Some((path.to_path_buf(), None))
+    }
+    None
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').any( | tag | {
+        let tag = tag.trim();
+        tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+    })
+}
+
+/// A single `bytes=` range as start and end (exclusive). None when the header
+/// should be ignored, which includes multiple ranges, Err when it can't be
+/// satisfied
+pub fn parse_range(range: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None
+    }
+    let (start, end) = spec.trim().split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        // the last n bytes
+        let n: u64 = end.parse().ok()?;
+        if n == 0 || len == 0 {
+            return Some(Err(()))
+        }
+        return Some(Ok((len - n.min(len), len)))
+    }
+    let start: u64 = start.parse().ok()?;
+    let end = if end.is_empty() {len - 1} else {end.parse::<u64>().ok()?.min(len.saturating_sub(1))};
+    if start > end || start >= len {
+        return Some(Err(()))
+    }
+    Some(Ok((start, end + 1)))
+}
+
+fn read_file_range(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut body = Vec::new();
+    file.take(end - start).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+pub fn mime_type(path: &Path) -> &'static str {
+    let ext = path.extension().and_then( | e | e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream"
+    }
+}
@@ -1,6 +1,9 @@
 use std::net::{TcpStream, Shutdown, SocketAddr};
 use std::io::BufReader;
 use std::io::prelude::*;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub fn write_bytes_to_tcp_stream_no_error(tcp_stream: &mut TcpStream, bytes: &[u8]) -> bool {
     let bytes_total = bytes.len();
@@ -20,6 +23,18 @@ pub fn write_bytes_to_tcp_stream_no_error(tcp_stream: &mut TcpStream, bytes: &[u
     false
 }
 
+/// Fills the buffer with unpredictable bytes, for websocket keys and masks.
+/// The hasher keys of `RandomState` come from the OS random source
+pub fn random_bytes(buf: &mut [u8]) {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    for chunk in buf.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let bytes = hasher.finish().to_le_bytes();
+        chunk.copy_from_slice(&bytes[0..chunk.len()]);
+    }
+}
+
 pub fn http_error_out(mut tcp_stream: TcpStream, code: usize) {
     write_bytes_to_tcp_stream_no_error(&mut tcp_stream, format!("HTTP/1.1 {}\r\n\r\n", code).as_bytes());
     let _ = tcp_stream.shutdown(Shutdown::Both);
@@ -72,6 +87,14 @@ pub struct HttpServerHeaders {
 }
 
 impl HttpServerHeaders {
+    /// The value of the first header line with this name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.lines.iter().skip(1).find_map( | line | {
+            let (n, v) = line.split_once(':')?;
+            if n.trim().eq_ignore_ascii_case(name) {Some(v.trim())} else {None}
+        })
+    }
+
     pub fn from_tcp_stream(tcp_stream: &mut TcpStream) -> Option<HttpServerHeaders> {
         let addr = tcp_stream.peer_addr().unwrap();
         let mut reader = BufReader::new(tcp_stream);
@@ -1,6 +1,7 @@
-
 use crate::digest::{Sha1, base64_encode};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::utils::random_bytes;
+use crate::websocket_deflate::{WebSocketDeflateParams, WebSocketInflate};
+use std::io::Read;
 
 #[derive(Debug, PartialEq)]
 enum State {
@@ -25,6 +26,15 @@ fn head_expected(&self) -> usize {
     }
 }
 
+const OPCODE_CONTINUATION: u8 = 0;
+const OPCODE_TEXT: u8 = 1;
+const OPCODE_BINARY: u8 = 2;
+const OPCODE_CLOSE: u8 = 8;
+const OPCODE_PING: u8 = 9;
+const OPCODE_PONG: u8 = 10;
+
+/// Parses frames into messages, reassembling fragmented messages and
+/// inflating compressed ones. Used on both ends of a connection
 pub struct ServerWebSocket {
     head: [u8; 8],
     head_expected: usize,
@@ -33,12 +43,18 @@ pub struct ServerWebSocket {
     data_len: usize,
     input_read: usize,
     mask_counter: usize,
-    is_ping: bool,
-    is_pong: bool,
-    is_partial: bool,
-    is_text: bool,
+    opcode: u8,
+    is_final: bool,
     is_masked: bool,
-    state: State
+    // the opcode of the fragmented message being reassembled
+    message_opcode: Option<u8>,
+    message_compressed: bool,
+    message: Vec<u8>,
+    inflate: Option<WebSocketInflate>,
+    failed: bool,
+    state: State,
+    /// Messages bigger than this fail the connection
+    pub max_message_size: usize,
 }
 
 pub enum ServerWebSocketMessage<'a> {
@@ -46,13 +62,40 @@ pub enum ServerWebSocketMessage<'a> {
     Pong(&'a [u8]),
     Text(&'a str),
     Binary(&'a [u8]),
-    Close
+    /// The code is None when the close frame didn't have one
+    Close{code: Option<u16>, reason: &'a str}
 }
 
 #[derive(Debug)]
 pub enum ServerWebSocketError<'a> {
     OpcodeNotSupported(u8),
     TextNotUTF8(&'a [u8]),
+    Protocol(&'static str),
+    MessageTooBig(usize),
+    Inflate,
+}
+
+impl ServerWebSocketError<'_> {
+    /// The status code to close the connection with
+    pub fn close_code(&self) -> u16 {
+        match self {
+            ServerWebSocketError::TextNotUTF8(_) => CLOSE_INVALID_DATA,
+            ServerWebSocketError::MessageTooBig(_) => CLOSE_TOO_BIG,
+            _ => CLOSE_PROTOCOL_ERROR
+        }
+    }
+}
+
+pub const CLOSE_NORMAL: u16 = 1000;
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+pub const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Whether a code can be sent in a close frame. 1004-1006 and 1015 are
+/// reserved for reporting, not for the wire
+pub fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
 }
 
 pub const SERVER_WEB_SOCKET_PING_MESSAGE:[u8;2] = [128 | 9,0];
@@ -60,7 +103,10 @@ pub enum ServerWebSocketError<'a> {
 
 pub enum ServerWebSocketMessageFormat {
     Binary,
-    Text
+    Text,
+    Ping,
+    Pong,
+    Close,
 }
 
 pub struct ServerWebSocketMessageHeader {
@@ -73,11 +119,14 @@ pub struct ServerWebSocketMessageHeader {
 impl ServerWebSocketMessageHeader {
     pub fn from_len(len: usize, format: ServerWebSocketMessageFormat, masked: bool)->Self{
         let mut data = [0u8;14];
-        
-        match format {
-            ServerWebSocketMessageFormat::Binary => data[0] = 128 | 2,
-            ServerWebSocketMessageFormat::Text => data[0] = 128 | 1,
-        }
+
+        data[0] = 128 | match format {
+            ServerWebSocketMessageFormat::Binary => OPCODE_BINARY,
+            ServerWebSocketMessageFormat::Text => OPCODE_TEXT,
+            ServerWebSocketMessageFormat::Ping => OPCODE_PING,
+            ServerWebSocketMessageFormat::Pong => OPCODE_PONG,
+            ServerWebSocketMessageFormat::Close => OPCODE_CLOSE,
+        };
 
         if masked {
             data[1] = 128;
@@ -108,15 +157,18 @@ pub fn from_len(len: usize, format: ServerWebSocketMessageFormat, masked: bool)-
         }
 
         if masked {
-            for i in header_len..header_len + 4 {
-                data[i] = Self::random_byte();
-            }
-            return ServerWebSocketMessageHeader{len: header_len + 4, data, format, masked}
+            random_bytes(&mut data[header_len..header_len + 4]);
+            ServerWebSocketMessageHeader{len: header_len + 4, data, format, masked}
         } else {
-            return ServerWebSocketMessageHeader{len: header_len, data, format, masked}
+            ServerWebSocketMessageHeader{len: header_len, data, format, masked}
         }
     }
-    
+
+    /// Marks the message as compressed with permessage-deflate
+    pub fn set_compressed(&mut self) {
+        self.data[0] |= 64;
+    }
+
     pub fn as_slice(&self)->&[u8]{
         &self.data[0..self.len]
     }
@@ -125,7 +177,7 @@ pub fn mask(&mut self)->Option<&[u8]> {
         if self.masked {
             match self.len {
                 6 => Some(&self.data[2..6]),
-                10 => Some(&self.data[6..10]),
+                8 => Some(&self.data[4..8]),
                 14 => Some(&self.data[10..14]),
                 _ => None
             }
@@ -133,11 +185,108 @@ pub fn mask(&mut self)->Option<&[u8]> {
             None
         }
     }
+}
+
+/// A random `Sec-WebSocket-Key` for a client handshake
+pub fn new_web_socket_key() -> String {
+    let mut key = [0u8; 16];
+    random_bytes(&mut key);
+    base64_encode(&key)
+}
+
+/// The `Sec-WebSocket-Accept` a server answers a key with
+pub fn web_socket_accept_key(key: &str) -> String {
+    let to_hash = format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key.trim());
+    let mut sha1 = Sha1::new();
+    sha1.update(to_hash.as_bytes());
+    base64_encode(&sha1.finalise())
+}
+
+/// The client side of the opening handshake
+pub struct ClientWebSocketHandshake {
+    pub key: String,
+}
+
+impl Default for ClientWebSocketHandshake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientWebSocketHandshake {
+    pub fn new() -> Self {
+        ClientWebSocketHandshake {key: new_web_socket_key()}
+    }
+
+    /// The upgrade request, `extra_headers` are complete header lines
+    pub fn request(&self, host: &str, path: &str, extra_headers: &str, deflate: bool) -> String {
+        let mut request = format!(
+            "GET /{} HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\n",
+            path.trim_start_matches('/'),
+            host,
+            self.key
+        );
+        if deflate {
+            request.push_str(&format!("Sec-WebSocket-Extensions: {}\r\n", WebSocketDeflateParams::CLIENT_OFFER));
+        }
+        request.push_str(extra_headers);
+        request.push_str("\r\n");
+        request
+    }
 
-    // TODO Improve this using a proper random number generator
-    fn random_byte() -> u8 {
-        let num = SystemTime::now().duration_since(UNIX_EPOCH).expect("duration_since failed").subsec_nanos();
-        num as u8
+    /// Checks the response head against our key, and returns the compression
+    /// the server agreed to
+    pub fn check_response(&self, head: &str) -> Result<Option<WebSocketDeflateParams>, String> {
+        let mut lines = head.lines();
+        let status = lines.next().unwrap_or("");
+        if status.split(' ').nth(1) != Some("101") {
+            return Err(format!("Websocket upgrade refused: {}", status))
+        }
+        let mut accept = None;
+        let mut upgrade = false;
+        let mut deflate = None;
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {continue};
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("sec-websocket-accept") {
+                accept = Some(value);
+            }
+            else if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            }
+            else if name.eq_ignore_ascii_case("sec-websocket-extensions") {
+                deflate = Some(WebSocketDeflateParams::parse_response(value)?);
+            }
+        }
+        if !upgrade {
+            return Err("Websocket upgrade response without Upgrade: websocket".into())
+        }
+        if accept != Some(web_socket_accept_key(&self.key).as_str()) {
+            return Err("Websocket Sec-WebSocket-Accept doesn't match our key".into())
+        }
+        Ok(deflate)
+    }
+}
+
+/// Reads up to and including the empty line ending an http head. Returns
+/// the head and whatever was read after it
+pub fn read_http_head(stream: &mut impl Read) -> Result<(String, Vec<u8>), String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).map_err( | e | format!("Error reading http head: {}", e))?;
+        if n == 0 {
+            return Err("Connection closed before the http head ended".into())
+        }
+        buffer.extend_from_slice(&chunk[0..n]);
+        if let Some(end) = buffer.windows(4).position( | w | w == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            let head = String::from_utf8(buffer).map_err( | _ | "Http head is not utf8".to_string())?;
+            return Ok((head, rest))
+        }
+        if buffer.len() > 65536 {
+            return Err("Http head too long".into())
+        }
     }
 }
 
@@ -151,41 +300,63 @@ pub fn new() -> Self {
             data_len: 0,
             input_read: 0,
             mask_counter: 0,
-            is_ping: false,
-            is_pong: false,
+            opcode: 0,
+            is_final: false,
             is_masked: false,
-            is_partial: false,
-            is_text: false,
-            state: State::Opcode
+            message_opcode: None,
+            message_compressed: false,
+            message: Vec::new(),
+            inflate: None,
+            failed: false,
+            state: State::Opcode,
+            max_message_size: 64 * 1024 * 1024,
         }
     }
-    
+
+    /// Turns on permessage-deflate for incoming messages
+    pub fn set_inflate(&mut self, inflate: Option<WebSocketInflate>) {
+        self.inflate = inflate;
+    }
+
     pub fn message_to_frame(msg:ServerWebSocketMessage) ->Vec<u8>
     {
         match &msg{
             ServerWebSocketMessage::Text(data)=>{
                 let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Text, false);
-                ServerWebSocket::build_message(header, &data.to_string().into_bytes())
+                ServerWebSocket::build_message(header, data.as_bytes())
             }
             ServerWebSocketMessage::Binary(data)=>{
                 let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                ServerWebSocket::build_message(header, &data)
+                ServerWebSocket::build_message(header, data)
             }
-            _=>panic!()
-        }  
+            ServerWebSocketMessage::Ping(data)=>{
+                let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Ping, false);
+                ServerWebSocket::build_message(header, data)
+            }
+            ServerWebSocketMessage::Pong(data)=>{
+                let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Pong, false);
+                ServerWebSocket::build_message(header, data)
+            }
+            ServerWebSocketMessage::Close{code, reason}=>{
+                ServerWebSocket::build_close(*code, reason, false)
+            }
+        }
     }
 
     pub fn create_upgrade_response(key: &str) -> String {
-        let to_hash = format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key);
-        let mut sha1 = Sha1::new();
-        sha1.update(to_hash.as_bytes());
-        let out_bytes = sha1.finalise();
-        let base64 = base64_encode(&out_bytes);
-        let response_ack = format!(
+        format!(
             "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
-            base64
-        );
-        response_ack
+            web_socket_accept_key(key)
+        )
+    }
+
+    /// The upgrade response with the permessage-deflate parameters we accepted
+    pub fn create_upgrade_response_with_deflate(key: &str, deflate: &WebSocketDeflateParams) -> String {
+        format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\nSec-WebSocket-Extensions: {}\r\n\r\n",
+            web_socket_accept_key(key),
+            deflate.to_header()
+        )
     }
 
     pub fn build_message(mut header: ServerWebSocketMessageHeader, data: &[u8])->Vec<u8>{
@@ -199,7 +370,23 @@ pub fn build_message(mut header: ServerWebSocketMessageHeader, data: &[u8])->Vec
         }
         frame
     }
-    
+
+    /// A close frame, the reason is cut to fit in the 125 bytes a control
+    /// frame can hold
+    pub fn build_close(code: Option<u16>, reason: &str, masked: bool) -> Vec<u8> {
+        let mut payload = Vec::new();
+        if let Some(code) = code {
+            payload.extend_from_slice(&code.to_be_bytes());
+            let mut end = reason.len().min(123);
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+            payload.extend_from_slice(&reason.as_bytes()[0..end]);
+        }
+        let header = ServerWebSocketMessageHeader::from_len(payload.len(), ServerWebSocketMessageFormat::Close, masked);
+        ServerWebSocket::build_message(header, &payload)
+    }
+
     fn parse_head(&mut self, input: &[u8]) -> bool {
         while self.head_expected > 0
             && self.input_read < input.len()
@@ -212,7 +399,7 @@ fn parse_head(&mut self, input: &[u8]) -> bool {
         }
         self.head_expected != 0
     }
-    
+
     fn to_state(&mut self, state: State) {
         match state {
             State::Data => {
@@ -220,10 +407,8 @@ fn to_state(&mut self, state: State) {
                 self.data.clear();
             }
             State::Opcode => {
-                self.is_ping = false;
-                self.is_pong = false;
-                self.is_partial = false;
-                self.is_text = false;
+                self.opcode = 0;
+                self.is_final = false;
                 self.is_masked = false;
             },
             _ => ()
@@ -232,39 +417,81 @@ fn to_state(&mut self, state: State) {
         self.head_expected = state.head_expected();
         self.state = state;
     }
-    
+
+    // the state after the length is known
+    fn begin_payload(&mut self) -> Result<(), ServerWebSocketError<'static>> {
+        if self.data_len > self.max_message_size || self.message.len() + self.data_len > self.max_message_size {
+            return Err(ServerWebSocketError::MessageTooBig(self.data_len))
+        }
+        if self.is_masked {
+            self.to_state(State::Mask);
+        }
+        else {
+            self.to_state(State::Data);
+        }
+        Ok(())
+    }
+
+    // checks the first byte of a frame against what is in flight
+    fn check_opcode(&mut self) -> Result<(), ServerWebSocketError<'static>> {
+        let byte = self.head[0];
+        let opcode = byte & 15;
+        let compressed = byte & 64 != 0;
+        if byte & 48 != 0 {
+            return Err(ServerWebSocketError::Protocol("Reserved bits set"))
+        }
+        if compressed && self.inflate.is_none() {
+            return Err(ServerWebSocketError::Protocol("Compressed frame without permessage-deflate"))
+        }
+        self.opcode = opcode;
+        self.is_final = byte & 128 != 0;
+        match opcode {
+            OPCODE_CONTINUATION => {
+                if self.message_opcode.is_none() {
+                    return Err(ServerWebSocketError::Protocol("Continuation frame without a message"))
+                }
+                if compressed {
+                    return Err(ServerWebSocketError::Protocol("Compressed continuation frame"))
+                }
+            }
+            OPCODE_TEXT | OPCODE_BINARY => {
+                if self.message_opcode.is_some() {
+                    return Err(ServerWebSocketError::Protocol("New message before the previous one ended"))
+                }
+                self.message_opcode = Some(opcode);
+                self.message_compressed = compressed;
+            }
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => {
+                if !self.is_final {
+                    return Err(ServerWebSocketError::Protocol("Fragmented control frame"))
+                }
+                if compressed {
+                    return Err(ServerWebSocketError::Protocol("Compressed control frame"))
+                }
+            }
+            _ => return Err(ServerWebSocketError::OpcodeNotSupported(opcode))
+        }
+        Ok(())
+    }
+
     pub fn parse<F>(&mut self, input: &[u8], mut result: F) where F: FnMut(Result<ServerWebSocketMessage, ServerWebSocketError>){
         self.input_read = 0;
-        // parse a header
+        // after an error the connection is done
+        if self.failed {
+            return
+        }
         loop {
             match self.state {
                 State::Opcode => {
                     if self.parse_head(input) {
                         break;
                     }
-                    let opcode = self.head[0] & 15;
-                    
-                    if opcode <= 2 {
-                        self.is_partial = (self.head[0] & 128) != 0;
-                        self.is_text = opcode == 1;
-                        self.to_state(State::Len1);
-                    }
-                    else if opcode == 8 {
-                        result(Ok(ServerWebSocketMessage::Close));
-                        break;
-                    }
-                    else if opcode == 9 {
-                        self.is_ping = true;
-                        self.to_state(State::Len1);
-                    }
-                    else if opcode == 10 {
-                        self.is_pong = true;
-                        self.to_state(State::Len1);
-                    }
-                    else {
-                        result(Err(ServerWebSocketError::OpcodeNotSupported(opcode)));
+                    if let Err(e) = self.check_opcode() {
+                        self.failed = true;
+                        result(Err(e));
                         break;
                     }
+                    self.to_state(State::Len1);
                 },
                 State::Len1 => {
                     if self.parse_head(input) {
@@ -272,19 +499,23 @@ pub fn parse<F>(&mut self, input: &[u8], mut result: F) where F: FnMut(Result<Se
                     }
                     self.is_masked = (self.head[0] & 128) > 0;
                     let len_type = self.head[0] & 127;
+                    if self.opcode >= OPCODE_CLOSE && len_type > 125 {
+                        self.failed = true;
+                        result(Err(ServerWebSocketError::Protocol("Control frame longer than 125 bytes")));
+                        break;
+                    }
                     if len_type < 126 {
                         self.data_len = len_type as usize;
-                        if !self.is_masked {
-                            self.to_state(State::Data);
-                        }
-                        else {
-                            self.to_state(State::Mask);
+                        if let Err(e) = self.begin_payload() {
+                            self.failed = true;
+                            result(Err(e));
+                            break;
                         }
                     }
                     else if len_type == 126 {
                         self.to_state(State::Len2);
                     }
-                    else if len_type == 127 {
+                    else {
                         self.to_state(State::Len8);
                     }
                 },
@@ -295,25 +526,30 @@ pub fn parse<F>(&mut self, input: &[u8], mut result: F) where F: FnMut(Result<Se
                     self.data_len = u16::from_be_bytes(
                         self.head[0..2].try_into().unwrap()
                     ) as usize;
-                    if self.is_masked {
-                        self.to_state(State::Mask);
-                    }
-                    else {
-                        self.to_state(State::Data);
+                    if let Err(e) = self.begin_payload() {
+                        self.failed = true;
+                        result(Err(e));
+                        break;
                     }
                 },
                 State::Len8 => {
                     if self.parse_head(input) {
                         break;
                     }
-                    self.data_len = u64::from_be_bytes(
+                    let len = u64::from_be_bytes(
                         self.head[0..8].try_into().unwrap()
-                    ) as usize;
-                    if self.is_masked {
-                        self.to_state(State::Mask);
+                    );
+                    // the top bit has to be zero
+                    if len >> 63 != 0 {
+                        self.failed = true;
+                        result(Err(ServerWebSocketError::Protocol("Invalid frame length")));
+                        break;
                     }
-                    else {
-                        self.to_state(State::Data);
+                    self.data_len = usize::try_from(len).unwrap_or(usize::MAX);
+                    if let Err(e) = self.begin_payload() {
+                        self.failed = true;
+                        result(Err(e));
+                        break;
                     }
                 },
                 State::Mask => {
@@ -323,48 +559,104 @@ pub fn parse<F>(&mut self, input: &[u8], mut result: F) where F: FnMut(Result<Se
                     self.to_state(State::Data);
                 },
                 State::Data => {
+                    let take = (self.data_len - self.data.len()).min(input.len() - self.input_read);
+                    let bytes = &input[self.input_read..self.input_read + take];
                     if self.is_masked {
-                        while self.data.len() < self.data_len && self.input_read < input.len() {
-                            self.data.push(input[self.input_read] ^ self.head[self.mask_counter]);
+                        for &byte in bytes {
+                            self.data.push(byte ^ self.head[self.mask_counter]);
                             self.mask_counter = (self.mask_counter + 1) & 3;
-                            self.input_read += 1;
                         }
                     }
                     else {
-                        while self.data.len() < self.data_len && self.input_read < input.len() {
-                            self.data.push(input[self.input_read]);
-                            self.input_read += 1;
-                        }
+                        self.data.extend_from_slice(bytes);
                     }
+                    self.input_read += take;
                     if self.data.len() < self.data_len { // not enough data yet
                         break;
                     }
-                    else {
-                        if self.is_ping {
-                            result(Ok(ServerWebSocketMessage::Ping(&self.data)));
-                        }
-                        else if self.is_pong {
-                            result(Ok(ServerWebSocketMessage::Pong(&self.data)));
-                        }
-                        else if self.is_text{
-                            if let Ok(text) = std::str::from_utf8(&self.data){
-                                result(Ok(ServerWebSocketMessage::Text(text)));
-                            }
-                            else{
-                                result(Err(ServerWebSocketError::TextNotUTF8(&self.data)))
-                            }
+                    if !self.frame_done(&mut result) {
+                        self.failed = true;
+                        break;
+                    }
+                    self.to_state(State::Opcode);
+                },
+            }
+        }
+    }
+
+    // hands out the message a complete frame finished, false on errors
+    fn frame_done<F>(&mut self, result: &mut F) -> bool where F: FnMut(Result<ServerWebSocketMessage, ServerWebSocketError>){
+        match self.opcode {
+            OPCODE_PING => result(Ok(ServerWebSocketMessage::Ping(&self.data))),
+            OPCODE_PONG => result(Ok(ServerWebSocketMessage::Pong(&self.data))),
+            OPCODE_CLOSE => {
+                if self.data.is_empty() {
+                    result(Ok(ServerWebSocketMessage::Close{code: None, reason: ""}));
+                    return true
+                }
+                if self.data.len() == 1 {
+                    result(Err(ServerWebSocketError::Protocol("Close frame with a one byte payload")));
+                    return false
+                }
+                let code = u16::from_be_bytes([self.data[0], self.data[1]]);
+                if !is_valid_close_code(code) {
+                    result(Err(ServerWebSocketError::Protocol("Invalid close code")));
+                    return false
+                }
+                match std::str::from_utf8(&self.data[2..]) {
+                    Ok(reason) => result(Ok(ServerWebSocketMessage::Close{code: Some(code), reason})),
+                    Err(_) => {
+                        result(Err(ServerWebSocketError::TextNotUTF8(&self.data[2..])));
+                        return false
+                    }
+                }
+            }
+            _ => {
+                if self.message.is_empty() {
+                    std::mem::swap(&mut self.message, &mut self.data);
+                }
+                else {
+                    self.message.extend_from_slice(&self.data);
+                }
+                if !self.is_final {
+                    return true
+                }
+                let opcode = self.message_opcode.take();
+                let message = std::mem::take(&mut self.message);
+                let message = if self.message_compressed {
+                    match self.inflate.as_mut().unwrap().decompress(&message, self.max_message_size) {
+                        Ok(message) => message,
+                        Err(_) => {
+                            result(Err(ServerWebSocketError::Inflate));
+                            return false
                         }
-                        else{
-                            result(Ok(ServerWebSocketMessage::Binary(&self.data)));
+                    }
+                }
+                else {
+                    message
+                };
+                if opcode == Some(OPCODE_TEXT) {
+                    match std::str::from_utf8(&message) {
+                        Ok(text) => result(Ok(ServerWebSocketMessage::Text(text))),
+                        Err(_) => {
+                            result(Err(ServerWebSocketError::TextNotUTF8(&message)));
+                            return false
                         }
-                        
-                        self.to_state(State::Opcode);
                     }
-                },
+                }
+                else {
+                    result(Ok(ServerWebSocketMessage::Binary(&message)));
+                }
+                // keep the allocation around for the next message
+                if !self.message_compressed {
+                    self.message = message;
+                    self.message.clear();
+                }
             }
         }
+        true
     }
-    
+
 }
 
 impl Default for ServerWebSocket {
@@ -372,4 +664,3 @@ fn default() -> Self {
         Self::new()
     }
 }
-
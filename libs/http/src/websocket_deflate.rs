@@ -0,0 +1,198 @@
+// permessage-deflate (RFC 7692) on top of miniz. Every message is a raw
+// deflate stream ended with a sync flush, without the trailing 00 00 ff ff.
+// Unless no_context_takeover is negotiated the window carries over between
+// messages, so both sides keep their compressor and decompressor around
+
+use makepad_miniz::deflate::core::{compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus};
+use makepad_miniz::inflate::core::{decompress, inflate_flags, DecompressorOxide};
+use makepad_miniz::inflate::TINFLStatus;
+
+const SYNC_TAIL: [u8; 4] = [0, 0, 0xff, 0xff];
+const WINDOW_SIZE: usize = 32768;
+
+/// The parameters both sides agreed on in the handshake
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WebSocketDeflateParams {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+}
+
+impl WebSocketDeflateParams {
+    /// What a client puts in `Sec-WebSocket-Extensions`. We don't offer
+    /// client_max_window_bits, our compressor always uses the full window
+    pub const CLIENT_OFFER: &'static str = "permessage-deflate";
+
+    /// Picks the first offer from a client's `Sec-WebSocket-Extensions` we
+    /// can honour. Offers that limit our window below 32k are declined
+    pub fn accept_offer(header: &str) -> Option<WebSocketDeflateParams> {
+        'offers: for offer in header.split(',') {
+            let mut parts = offer.split(';').map( | p | p.trim());
+            if parts.next() != Some("permessage-deflate") {
+                continue
+            }
+            let mut params = WebSocketDeflateParams::default();
+            for param in parts {
+                let (name, value) = param.split_once('=').map( | (n, v) | (n.trim(), Some(v.trim().trim_matches('"')))).unwrap_or((param, None));
+                match (name, value) {
+                    ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                    ("client_no_context_takeover", None) => params.client_no_context_takeover = true,
+                    ("server_max_window_bits", Some("15")) => (),
+                    // our decompressor handles any window the client uses
+                    ("client_max_window_bits", None) => (),
+                    ("client_max_window_bits", Some(bits)) if matches!(bits.parse::<u8>(), Ok(8..=15)) => (),
+                    _ => continue 'offers
+                }
+            }
+            return Some(params)
+        }
+        None
+    }
+
+    /// Checks the extension a server answered our offer with
+    pub fn parse_response(header: &str) -> Result<WebSocketDeflateParams, String> {
+        if header.contains(',') {
+            return Err(format!("Server accepted more than one extension: {}", header))
+        }
+        let mut parts = header.split(';').map( | p | p.trim());
+        if parts.next() != Some("permessage-deflate") {
+            return Err(format!("Server accepted an extension we didn't offer: {}", header))
+        }
+        let mut params = WebSocketDeflateParams::default();
+        for param in parts {
+            let (name, value) = param.split_once('=').map( | (n, v) | (n.trim(), Some(v.trim().trim_matches('"')))).unwrap_or((param, None));
+            match (name, value) {
+                ("server_no_context_takeover", None) => params.server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => params.client_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) if matches!(bits.parse::<u8>(), Ok(8..=15)) => (),
+                _ => return Err(format!("Invalid permessage-deflate parameter {}", param))
+            }
+        }
+        Ok(params)
+    }
+
+    /// The `Sec-WebSocket-Extensions` value a server answers with
+    pub fn to_header(&self) -> String {
+        let mut header = "permessage-deflate".to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        header
+    }
+
+    pub fn server_deflate(&self) -> WebSocketDeflate {
+        WebSocketDeflate::new(self.server_no_context_takeover)
+    }
+
+    pub fn server_inflate(&self) -> WebSocketInflate {
+        WebSocketInflate::new(self.client_no_context_takeover)
+    }
+
+    pub fn client_deflate(&self) -> WebSocketDeflate {
+        WebSocketDeflate::new(self.client_no_context_takeover)
+    }
+
+    pub fn client_inflate(&self) -> WebSocketInflate {
+        WebSocketInflate::new(self.server_no_context_takeover)
+    }
+}
+
+pub struct WebSocketDeflate {
+    compressor: Box<CompressorOxide>,
+    no_context_takeover: bool,
+}
+
+impl WebSocketDeflate {
+    pub fn new(no_context_takeover: bool) -> WebSocketDeflate {
+        // negative window bits is a raw stream without zlib header
+        let flags = create_comp_flags_from_zip_params(6, -15, 0);
+        WebSocketDeflate {
+            compressor: Box::new(CompressorOxide::new(flags)),
+            no_context_takeover,
+        }
+    }
+
+    pub fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 16);
+        // a full flush resets the window, so the next message doesn't refer back
+        let flush = if self.no_context_takeover {TDEFLFlush::Full} else {TDEFLFlush::Sync};
+        let (status, _) = compress_to_output(&mut self.compressor, data, flush, | bytes | {
+            out.extend_from_slice(bytes);
+            true
+        });
+        debug_assert!(status == TDEFLStatus::Okay);
+        if out.ends_with(&SYNC_TAIL) {
+            out.truncate(out.len() - 4);
+        }
+        if out.is_empty() {
+            // an empty message still needs a block
+            out.push(0);
+        }
+        out
+    }
+}
+
+pub struct WebSocketInflate {
+    decompressor: Box<DecompressorOxide>,
+    // the last window of output, earlier messages can be referred back to
+    history: Vec<u8>,
+    no_context_takeover: bool,
+}
+
+impl WebSocketInflate {
+    pub fn new(no_context_takeover: bool) -> WebSocketInflate {
+        WebSocketInflate {
+            decompressor: Box::default(),
+            history: Vec::new(),
+            no_context_takeover,
+        }
+    }
+
+    /// Errors when the data isn't valid or inflates to more than `max_size`
+    pub fn decompress(&mut self, data: &[u8], max_size: usize) -> Result<Vec<u8>, TINFLStatus> {
+        let input: Vec<u8> = data.iter().chain(SYNC_TAIL.iter()).copied().collect();
+        let flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF | inflate_flags::TINFL_FLAG_HAS_MORE_INPUT;
+        let start = self.history.len();
+        let mut out = std::mem::take(&mut self.history);
+        out.resize(start + (data.len() * 4).max(1024).min(max_size.max(1)), 0);
+        let mut in_pos = 0;
+        let mut out_pos = start;
+        loop {
+            let (status, in_used, out_used) = decompress(&mut self.decompressor, &input[in_pos..], &mut out, out_pos, flags);
+            in_pos += in_used;
+            out_pos += out_used;
+            if out_pos - start > max_size {
+                return Err(TINFLStatus::HasMoreOutput)
+            }
+            match status {
+                TINFLStatus::NeedsMoreInput if in_pos == input.len() => break,
+                // the sender ended the stream with a final block
+                TINFLStatus::Done => {
+                    *self.decompressor = DecompressorOxide::default();
+                    out.truncate(out_pos);
+                    let message = out[start..].to_vec();
+                    self.history.clear();
+                    return Ok(message)
+                }
+                // one byte past the limit is enough to tell it went over
+                TINFLStatus::HasMoreOutput => {
+                    let len = (out.len() * 2).min(start.saturating_add(max_size).saturating_add(1));
+                    out.resize(len, 0);
+                }
+                status => return Err(status)
+            }
+        }
+        out.truncate(out_pos);
+        let message = out[start..].to_vec();
+        if self.no_context_takeover {
+            *self.decompressor = DecompressorOxide::default();
+        }
+        else {
+            out.drain(..out.len().saturating_sub(WINDOW_SIZE));
+            self.history = out;
+        }
+        Ok(message)
+    }
+}
@@ -0,0 +1,335 @@
+use std::future::Future;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, mpsc};
+use std::task::{Context, Poll, Wake};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use makepad_http::client::*;
+use makepad_http::cookie::*;
+use makepad_http::request::*;
+use makepad_http::router::*;
+use makepad_http::server::*;
+
+fn start(router: Router) -> String {
+    let server = RouterServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), router);
+    format!("http://{}", start_router_server(server).unwrap().0)
+}
+
+fn text(response: &HttpResponse) -> &str {
+    std::str::from_utf8(&response.body).unwrap()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend_from_slice(&makepad_miniz::compress_to_vec(data, 6));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+// a one shot server that answers whatever comes in with `answer`, and hands
+// the request head it got to the test
+fn fake_server(answer: &'static str) -> (SocketAddr, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            if reader.read_line(&mut head).unwrap() == 0 {
+                break
+            }
+        }
+        tx.send(head).unwrap();
+        reader.get_mut().write_all(answer.as_bytes()).unwrap();
+    });
+    (addr, rx)
+}
+
+#[test]
+fn urls() {
+    let url = HttpUrl::parse("HTTP://user:pw@Example.com:8080/a/b/c?x=1#frag").unwrap();
+    assert_eq!(url.scheme, "http");
+    assert_eq!(url.host, "Example.com");
+    assert_eq!(url.port, 8080);
+    assert_eq!(url.target(), "/a/b/c?x=1");
+    assert_eq!(url.userinfo.as_deref(), Some("user:pw"));
+    assert_eq!(url.host_header(), "Example.com:8080");
+    assert_eq!(HttpUrl::parse("https://[::1]/").unwrap().host, "::1");
+    assert_eq!(HttpUrl::parse("https://[::1]/").unwrap().host_header(), "[::1]");
+    assert_eq!(HttpUrl::parse("https://h").unwrap().target(), "/");
+    assert!(HttpUrl::parse("ftp://h/").is_err());
+    assert!(HttpUrl::parse("http://h:port/").is_err());
+
+    let base = HttpUrl::parse("http://h/a/b/c?q").unwrap();
+    assert_eq!(base.join("d").unwrap().to_string(), "http://h/a/b/d");
+    assert_eq!(base.join("../d?x").unwrap().to_string(), "http://h/a/d?x");
+    assert_eq!(base.join("/d/./e/../f").unwrap().to_string(), "http://h/d/f");
+    assert_eq!(base.join("?y").unwrap().to_string(), "http://h/a/b/c?y");
+    assert_eq!(base.join("//other:81/x").unwrap().to_string(), "http://other:81/x");
+    assert_eq!(base.join("https://s/").unwrap().to_string(), "https://s/");
+
+    let proxy = HttpProxy::parse("user:p%40ss@proxy:3128").unwrap();
+    assert_eq!((proxy.host.as_str(), proxy.port), ("proxy", 3128));
+    assert_eq!(proxy.authorization.as_deref(), Some("Basic dXNlcjpwQHNz"));
+    let config = HttpProxyConfig {
+        http: Some(proxy.clone()),
+        https: None,
+        no_proxy: vec![".internal".into(), "localhost".into()],
+    };
+    assert_eq!(config.proxy_for(&HttpUrl::parse("http://example.com/").unwrap()), Some(&proxy));
+    assert_eq!(config.proxy_for(&HttpUrl::parse("https://example.com/").unwrap()), None);
+    assert_eq!(config.proxy_for(&HttpUrl::parse("http://a.internal/").unwrap()), None);
+    assert_eq!(config.proxy_for(&HttpUrl::parse("http://localhost:80/").unwrap()), None);
+}
+
+#[test]
+fn cookie_rules() {
+    let url = HttpUrl::parse("https://www.example.com/shop/cart").unwrap();
+    let mut jar = CookieJar::new();
+    jar.store(&url, "session=abc; Path=/; Secure; HttpOnly");
+    jar.store(&url, "lang=nl; Domain=.example.com; Path=/");
+    jar.store(&url, "cart=3");
+    // a cookie for somebody else's domain is refused
+    jar.store(&url, "evil=1; Domain=other.com");
+    jar.store(&url, "old=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT");
+    assert!(jar.get("evil").is_none() && jar.get("old").is_none());
+    assert_eq!(jar.get("cart").unwrap().path, "/shop");
+
+    assert_eq!(jar.header(&url).as_deref(), Some("cart=3; session=abc; lang=nl"));
+    assert_eq!(jar.header(&HttpUrl::parse("http://api.example.com/").unwrap()).as_deref(), Some("lang=nl"));
+    assert_eq!(jar.header(&HttpUrl::parse("https://www.example.com/shopping").unwrap()).as_deref(), Some("session=abc; lang=nl"));
+    assert_eq!(jar.header(&HttpUrl::parse("https://example.org/").unwrap()), None);
+
+    // max-age 0 removes it
+    jar.store(&url, "lang=; Domain=example.com; Path=/; Max-Age=0");
+    assert!(jar.get("lang").is_none());
+
+    let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    assert_eq!(date.duration_since(UNIX_EPOCH).unwrap().as_secs(), 784111777);
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(date));
+    assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+    let now = UNIX_EPOCH + Duration::from_secs(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+    assert_eq!(parse_http_date(&format_http_date(now)), Some(now));
+    assert_eq!(parse_http_date("yesterday"), None);
+}
+
+#[test]
+fn redirects_and_cookies() {
+    let mut router = Router::new();
+    router.get("/login", | _ | HttpResponse::new(302).with_header("Location", "home").with_header("Set-Cookie", "id=42; Path=/"));
+    router.get("/home", | req | HttpResponse::text(200, req.header("Cookie").unwrap_or("none")));
+    router.get("/loop", | _ | HttpResponse::new(301).with_header("Location", "/loop"));
+    router.post("/see-other", | _ | HttpResponse::new(303).with_header("Location", "/method"));
+    router.post("/temporary", | _ | HttpResponse::new(307).with_header("Location", "/method"));
+    router.route("GET", "/method", | req | HttpResponse::text(200, &format!("GET {}", req.body.len())));
+    router.route("POST", "/method", | req | HttpResponse::text(200, &format!("POST {}", req.body.len())));
+    let base = start(router);
+
+    let client = HttpClient::new();
+    let response = client.get(&format!("{}/login", base)).unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(text(&response), "id=42");
+    // the jar is shared by clones
+    assert_eq!(text(&client.clone().get(&format!("{}/home", base)).unwrap()), "id=42");
+    assert_eq!(text(&HttpClient::new().without_cookies().get(&format!("{}/login", base)).unwrap()), "none");
+
+    assert!(matches!(client.get(&format!("{}/loop", base)), Err(HttpClientError::TooManyRedirects)));
+    let response = client.clone().with_max_redirects(0).get(&format!("{}/loop", base)).unwrap();
+    assert_eq!(response.status, 301);
+
+    let response = client.post(&format!("{}/see-other", base), "text/plain", b"body".to_vec()).unwrap();
+    assert_eq!(text(&response), "GET 0");
+    let response = client.post(&format!("{}/temporary", base), "text/plain", b"body".to_vec()).unwrap();
+    assert_eq!(text(&response), "POST 4");
+}
+
+#[test]
+fn content_encodings() {
+    let data: Vec<u8> = b"squeeze me ".repeat(500);
+    let gzipped = gzip(&data);
+    let zlib = makepad_miniz::compress_to_vec_zlib(&data, 6);
+    let mut router = Router::new();
+    router.get("/gzip", move | req | {
+        HttpResponse::ok()
+            .with_header("Content-Encoding", "gzip")
+            .with_header("X-Accept-Encoding", req.header("Accept-Encoding").unwrap_or("none"))
+            .with_body("text/plain", gzipped.clone())
+    });
+    router.get("/deflate", move | _ | HttpResponse::ok().with_header("Content-Encoding", "deflate").with_body("text/plain", zlib.clone()));
+    router.get("/broken", | _ | HttpResponse::ok().with_header("Content-Encoding", "gzip").with_body("text/plain", b"not gzip at all, really".to_vec()));
+    let base = start(router);
+
+    let client = HttpClient::new();
+    for path in ["/gzip", "/deflate"] {
+        let response = client.get(&format!("{}{}", base, path)).unwrap();
+        assert_eq!(response.body, data);
+        assert_eq!(response.header("Content-Encoding"), None);
+    }
+    assert_eq!(client.get(&format!("{}/gzip", base)).unwrap().header("X-Accept-Encoding"), Some("gzip, deflate"));
+    assert!(matches!(client.get(&format!("{}/broken", base)), Err(HttpClientError::Decode(_))));
+    let mut raw = HttpClient::new();
+    raw.decompress = false;
+    let response = raw.get(&format!("{}/gzip", base)).unwrap();
+    assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(response.header("X-Accept-Encoding"), Some("none"));
+}
+
+#[test]
+fn multipart_and_headers() {
+    let mut router = Router::new();
+    router.post("/echo", | req | {
+        let content_type = req.header("Content-Type").unwrap_or("").to_string();
+        HttpResponse::ok()
+            .with_header("X-Content-Type", &content_type)
+            .with_header("X-Token", req.header("X-Token").unwrap_or(""))
+            .with_body("application/octet-stream", req.body.clone())
+    });
+    let base = start(router);
+
+    let form = Multipart::new()
+        .text("title", "a \"quoted\" name")
+        .file("upload", "data.bin", "application/octet-stream", &[0, 1, 2, 255]);
+    let response = HttpClient::new()
+        .request("POST", &format!("{}/echo", base))
+        .header("X-Token", "secret")
+        .multipart(form)
+        .send()
+        .unwrap();
+    let content_type = response.header("X-Content-Type").unwrap();
+    let boundary = content_type.strip_prefix("multipart/form-data; boundary=").unwrap();
+    assert_eq!(response.header("X-Token"), Some("secret"));
+    let mut expected = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\na \"quoted\" name\r\n\
+        --{b}\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"data.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        b = boundary
+    ).into_bytes();
+    expected.extend_from_slice(&[0, 1, 2, 255]);
+    expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    assert_eq!(response.body, expected);
+}
+
+#[test]
+fn proxies() {
+    // plain http goes to the proxy in absolute form
+    let (proxy_addr, heads) = fake_server("HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nproxied");
+    let client = HttpClient::new().with_proxy(HttpProxy::parse(&format!("http://me:pw@{}", proxy_addr)).unwrap());
+    let response = client.get("http://example.invalid:8080/page?x=1").unwrap();
+    assert_eq!(text(&response), "proxied");
+    let head = heads.recv().unwrap();
+    assert!(head.starts_with("GET http://example.invalid:8080/page?x=1 HTTP/1.1\r\n"));
+    assert!(head.contains("Host: example.invalid:8080\r\n"));
+    assert!(head.contains("Proxy-Authorization: Basic bWU6cHc=\r\n"));
+
+    // https gets a CONNECT tunnel, here with a TLS layer that does nothing
+    struct NoTls;
+    impl HttpTlsConnector for NoTls {
+        fn connect(&self, stream: TcpStream, host: &str, verify: bool) -> Result<Box<dyn HttpStream>, String> {
+            assert_eq!((host, verify), ("secure.invalid", true));
+            Ok(Box::new(stream))
+        }
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut heads = Vec::new();
+        for answer in ["HTTP/1.1 200 Connection established\r\n\r\n", "HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecret"] {
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                reader.read_line(&mut head).unwrap();
+            }
+            heads.push(head);
+            reader.get_mut().write_all(answer.as_bytes()).unwrap();
+        }
+        tx.send(heads).unwrap();
+    });
+    let client = HttpClient::new().with_tls(NoTls).with_proxy(HttpProxy::parse(&proxy_addr.to_string()).unwrap());
+    assert_eq!(text(&client.get("https://secure.invalid/x").unwrap()), "secret");
+    let heads = rx.recv().unwrap();
+    assert!(heads[0].starts_with("CONNECT secure.invalid:443 HTTP/1.1\r\n"));
+    assert!(heads[1].starts_with("GET /x HTTP/1.1\r\n"));
+
+    assert!(matches!(HttpClient::new().get("https://secure.invalid/"), Err(HttpClientError::TlsUnavailable) | Err(HttpClientError::Connect(_))));
+}
+
+#[test]
+fn responses_without_length() {
+    let (addr, _heads) = fake_server("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nTrailer: x\r\n\r\n");
+    assert_eq!(text(&HttpClient::new().get(&format!("http://{}/", addr)).unwrap()), "hello world");
+    let (addr, _heads) = fake_server("HTTP/1.0 200 OK\r\n\r\nuntil the end");
+    assert_eq!(text(&HttpClient::new().get(&format!("http://{}/", addr)).unwrap()), "until the end");
+    let (addr, _heads) = fake_server("HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n");
+    let mut client = HttpClient::new();
+    client.max_body_size = 10;
+    assert!(matches!(client.get(&format!("http://{}/", addr)), Err(HttpClientError::BodyTooLarge)));
+    // a chunk size that doesn't fit next to what came before
+    let mut response = HttpResponse::new(200);
+    response.headers.push(("Transfer-Encoding".into(), "chunked".into()));
+    let mut reader = BufReader::new(&b"1\r\na\r\nffffffffffffffff\r\nb\r\n0\r\n\r\n"[..]);
+    assert!(matches!(read_response_body(&mut reader, &mut response, false, 1024), Err(HttpClientError::BodyTooLarge)));
+    // a Content-Length that promises more than arrives
+    let mut response = HttpResponse::new(200);
+    response.headers.push(("Content-Length".into(), "1000000".into()));
+    let mut reader = BufReader::new(&b"short"[..]);
+    assert!(read_response_body(&mut reader, &mut response, false, 1 << 30).is_err());
+    let (addr, _heads) = fake_server("SSH-2.0-OpenSSH\r\n\r\n");
+    assert!(matches!(HttpClient::new().get(&format!("http://{}/", addr)), Err(HttpClientError::InvalidResponse(_))));
+}
+
+#[test]
+fn timeouts() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // accepts, and then never answers
+    std::thread::spawn(move || {
+        let _stream = listener.accept().unwrap();
+        std::thread::sleep(Duration::from_secs(5));
+    });
+    let client = HttpClient::new().with_timeout(Duration::from_millis(200));
+    let start = std::time::Instant::now();
+    assert!(matches!(client.get(&format!("http://{}/", addr)), Err(HttpClientError::Timeout)));
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output
+        }
+        std::thread::park();
+    }
+}
+
+#[test]
+fn async_requests() {
+    let mut router = Router::new();
+    router.get("/slow", | _ | {
+        std::thread::sleep(Duration::from_millis(50));
+        HttpResponse::text(200, "later")
+    });
+    let base = start(router);
+    let client = HttpClient::new();
+    let futures: Vec<_> = (0..4).map( | _ | client.request("GET", &format!("{}/slow", base)).send_async()).collect();
+    for future in futures {
+        assert_eq!(text(&block_on(future).unwrap()), "later");
+    }
+    let future = client.send_async(&format!("{}/nothing", base), HttpRequest::new("GET", "/"));
+    assert_eq!(block_on(future).unwrap().status, 404);
+}
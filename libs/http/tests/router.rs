@@ -0,0 +1,268 @@
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use makepad_http::request::*;
+use makepad_http::router::*;
+use makepad_http::server::*;
+use makepad_http::static_files::*;
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find( | (n, _) | n.eq_ignore_ascii_case(name)).map( | (_, v) | v.as_str())
+    }
+
+    fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap()
+    }
+}
+
+// reads one response, using Content-Length for the body unless it is to a HEAD
+fn read_reply(reader: &mut impl BufRead, head: bool) -> Reply {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let status = line.split(' ').nth(1).unwrap().parse().unwrap();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+    let mut reply = Reply {status, headers, body: Vec::new()};
+    if !head {
+        if let Some(len) = reply.header("Content-Length") {
+            reply.body = vec![0; len.parse().unwrap()];
+            reader.read_exact(&mut reply.body).unwrap();
+        }
+    }
+    reply
+}
+
+fn request(addr: SocketAddr, raw: &str) -> Reply {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw.as_bytes()).unwrap();
+    read_reply(&mut BufReader::new(stream), raw.starts_with("HEAD "))
+}
+
+fn get(addr: SocketAddr, path: &str, headers: &str) -> Reply {
+    request(addr, &format!("GET {} HTTP/1.1\r\nHost: test\r\n{}Connection: close\r\n\r\n", path, headers))
+}
+
+fn start(router: Router) -> SocketAddr {
+    let server = RouterServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), router);
+    start_router_server(server).unwrap().0
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("makepad_http_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    dir
+}
+
+#[test]
+fn path_patterns() {
+    let pattern = PathPattern::parse("/users/:id/files/*rest");
+    let params = pattern.matches("/users/12/files/a/b.txt").unwrap();
+    assert_eq!(params["id"], "12");
+    assert_eq!(params["rest"], "a/b.txt");
+    assert_eq!(pattern.matches("/users/12/files").unwrap()["rest"], "");
+    assert_eq!(pattern.matches("/users/12/files/dir/").unwrap()["rest"], "dir/");
+    assert!(pattern.matches("/users/12").is_none());
+    assert!(pattern.matches("/people/12/files/x").is_none());
+    let pattern = PathPattern::parse("/a/:b");
+    assert!(pattern.matches("/a/x/y").is_none());
+    assert!(pattern.matches("//a//x").is_some());
+}
+
+#[test]
+fn ranges() {
+    assert_eq!(parse_range("bytes=0-3", 10), Some(Ok((0, 4))));
+    assert_eq!(parse_range("bytes=4-", 10), Some(Ok((4, 10))));
+    assert_eq!(parse_range("bytes=-3", 10), Some(Ok((7, 10))));
+    assert_eq!(parse_range("bytes=5-100", 10), Some(Ok((5, 10))));
+    assert_eq!(parse_range("bytes=10-", 10), Some(Err(())));
+    assert_eq!(parse_range("bytes=0-1,4-5", 10), None);
+    assert_eq!(parse_range("items=0-1", 10), None);
+}
+
+#[test]
+fn routing() {
+    let mut router = Router::new();
+    router.get("/hello/:name", | req | HttpResponse::text(200, &format!("hello {}", req.param("name").unwrap())));
+    router.get("/query", | req | HttpResponse::text(200, &req.query_param("q").unwrap_or_default()));
+    router.post("/echo", | req | HttpResponse::ok().with_body("application/octet-stream", req.body.clone()));
+    let addr = start(router);
+
+    let reply = get(addr, "/hello/w%C3%B6rld", "");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.text(), "hello wörld");
+    assert_eq!(get(addr, "/query?a=1&q=a+b%21", "").text(), "a b!");
+    assert_eq!(get(addr, "/nothing", "").status, 404);
+
+    let reply = get(addr, "/echo", "");
+    assert_eq!(reply.status, 405);
+    assert_eq!(reply.header("Allow"), Some("POST"));
+
+    let reply = request(addr, "HEAD /hello/x HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Length"), Some("7"));
+
+    assert_eq!(request(addr, "GET /x HTTP/1.1\r\nConnection: close\r\n\r\n").status, 400);
+    assert_eq!(request(addr, "GET /x HTTP/2.0\r\nHost: test\r\n\r\n").status, 505);
+}
+
+#[test]
+fn keep_alive_and_chunked_bodies() {
+    let mut router = Router::new();
+    router.post("/echo", | req | HttpResponse::ok().with_body("application/octet-stream", req.body.clone()));
+    let addr = start(router);
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+    let reply = read_reply(&mut reader, false);
+    assert_eq!(reply.text(), "hello");
+    assert_eq!(reply.header("Connection"), Some("keep-alive"));
+
+    // same connection, chunked with an extension and a trailer
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n4;x=y\r\nWiki\r\n6\r\npedia \r\n0\r\nTrailer: 1\r\n\r\n").unwrap();
+    let reply = read_reply(&mut reader, false);
+    assert_eq!(reply.text(), "Wikipedia ");
+
+    stream.write_all(b"POST /echo HTTP/1.1\r\nHost: test\r\nExpect: 100-continue\r\nContent-Length: 2\r\nConnection: close\r\n\r\n").unwrap();
+    assert_eq!(read_reply(&mut reader, false).status, 100);
+    stream.write_all(b"ok").unwrap();
+    let reply = read_reply(&mut reader, false);
+    assert_eq!(reply.text(), "ok");
+    assert_eq!(reply.header("Connection"), Some("close"));
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    let reply = request(addr, "POST /echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\n");
+    assert_eq!(reply.status, 400);
+}
+
+#[test]
+fn body_limit() {
+    let mut router = Router::new();
+    router.post("/echo", | req | HttpResponse::ok().with_body("application/octet-stream", req.body.clone()));
+    let mut server = RouterServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), router);
+    server.max_body_size = 4;
+    let addr = start_router_server(server).unwrap().0;
+    assert_eq!(request(addr, "POST /echo HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\nhello").status, 413);
+    assert_eq!(request(addr, "POST /echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n").status, 413);
+    assert_eq!(request(addr, "POST /echo HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\nb\r\n0\r\n\r\n").status, 413);
+}
+
+#[test]
+fn static_files() {
+    let dir = temp_dir("static");
+    let data: Vec<u8> = (0..100u8).collect();
+    std::fs::write(dir.join("data.bin"), &data).unwrap();
+    std::fs::write(dir.join("app.js"), "plain").unwrap();
+    std::fs::write(dir.join("app.js.br"), "brotli").unwrap();
+    std::fs::write(dir.join("app.js.gz"), "gzip").unwrap();
+    std::fs::write(dir.join("sub/index.html"), "<p>index</p>").unwrap();
+    std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+    std::fs::create_dir_all(dir.join(".git")).unwrap();
+    std::fs::write(dir.join(".git/config"), "[core]").unwrap();
+
+    let mut router = Router::new();
+    router.files("/static/*path", StaticFiles::new(&dir).with_header("Cross-Origin-Opener-Policy", "same-origin"));
+    let addr = start(router);
+
+    let reply = get(addr, "/static/data.bin", "");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body, data);
+    assert_eq!(reply.header("Content-Type"), Some("application/octet-stream"));
+    assert_eq!(reply.header("Cross-Origin-Opener-Policy"), Some("same-origin"));
+    let etag = reply.header("ETag").unwrap().to_string();
+
+    let reply = get(addr, "/static/data.bin", &format!("If-None-Match: W/{}\r\n", etag));
+    assert_eq!(reply.status, 304);
+    assert!(reply.body.is_empty());
+
+    let reply = get(addr, "/static/data.bin", "Range: bytes=10-19\r\n");
+    assert_eq!(reply.status, 206);
+    assert_eq!(reply.body, &data[10..20]);
+    assert_eq!(reply.header("Content-Range"), Some("bytes 10-19/100"));
+    assert_eq!(get(addr, "/static/data.bin", "Range: bytes=-5\r\n").body, &data[95..]);
+    let reply = get(addr, "/static/data.bin", "Range: bytes=200-\r\n");
+    assert_eq!(reply.status, 416);
+    assert_eq!(reply.header("Content-Range"), Some("bytes */100"));
+    assert_eq!(get(addr, "/static/data.bin", "Range: bytes=0-1\r\nIf-Range: \"old\"\r\n").status, 200);
+
+    let reply = get(addr, "/static/app.js", "Accept-Encoding: gzip, br\r\n");
+    assert_eq!(reply.text(), "brotli");
+    assert_eq!(reply.header("Content-Encoding"), Some("br"));
+    assert_eq!(reply.header("Content-Type"), Some("text/javascript"));
+    let reply = get(addr, "/static/app.js", "Accept-Encoding: gzip, br;q=0\r\n");
+    assert_eq!(reply.text(), "gzip");
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    let reply = get(addr, "/static/app.js", "");
+    assert_eq!(reply.text(), "plain");
+    assert_eq!(reply.header("Content-Encoding"), None);
+
+    let reply = request(addr, "HEAD /static/data.bin HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n");
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Length"), Some("100"));
+
+    assert_eq!(get(addr, "/static/sub/", "").text(), "<p>index</p>");
+    assert_eq!(get(addr, "/static/sub", "").text(), "<p>index</p>");
+    assert_eq!(get(addr, "/static/missing", "").status, 404);
+    assert_eq!(get(addr, "/static/sub/../../data.bin", "").status, 403);
+    assert_eq!(get(addr, "/static/%2e%2e/data.bin", "").status, 403);
+    assert_eq!(get(addr, "/static/sub%5c..%5c..%5cx", "").status, 403);
+    assert_eq!(get(addr, "/static/.env", "").status, 403);
+    assert_eq!(get(addr, "/static/.git/config", "").status, 403);
+    assert_eq!(get(addr, "/static/sub/./index.html", "").text(), "<p>index</p>");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn middleware() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut router = Router::new();
+    let sink = log.clone();
+    router.middleware(LogMiddleware::with_sink(move | line | sink.lock().unwrap().push(line.to_string())));
+    router.middleware(CorsMiddleware {
+        allow_origins: vec!["https://makepad.dev".to_string()],
+        ..Default::default()
+    });
+    router.get("/api", | _ | HttpResponse::text(200, "api"));
+    let addr = start(router);
+
+    let reply = request(addr, "OPTIONS /api HTTP/1.1\r\nHost: test\r\nOrigin: https://makepad.dev\r\nAccess-Control-Request-Method: POST\r\nConnection: close\r\n\r\n");
+    assert_eq!(reply.status, 204);
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), Some("https://makepad.dev"));
+    assert!(reply.header("Access-Control-Allow-Methods").unwrap().contains("POST"));
+    assert_eq!(reply.header("Access-Control-Max-Age"), Some("600"));
+
+    let reply = get(addr, "/api", "Origin: https://makepad.dev\r\n");
+    assert_eq!(reply.text(), "api");
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), Some("https://makepad.dev"));
+    assert_eq!(reply.header("Vary"), Some("Origin"));
+
+    let reply = get(addr, "/api", "Origin: https://elsewhere.com\r\n");
+    assert_eq!(reply.header("Access-Control-Allow-Origin"), None);
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 3);
+    assert!(log[0].starts_with("OPTIONS /api 204 "));
+    assert!(log[1].starts_with("GET /api 200 "));
+}
@@ -0,0 +1,479 @@
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use makepad_http::request::*;
+use makepad_http::router::*;
+use makepad_http::server::*;
+use makepad_http::utils::HttpServerHeaders;
+use makepad_http::websocket::*;
+use makepad_http::websocket_deflate::*;
+
+enum Event {
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Text(String),
+    Binary(Vec<u8>),
+    Close(Option<u16>, String),
+    Error(u16),
+}
+
+fn parse_all(ws: &mut ServerWebSocket, input: &[u8]) -> Vec<Event> {
+    let mut events = Vec::new();
+    ws.parse(input, | result | events.push(match result {
+        Ok(ServerWebSocketMessage::Ping(d)) => Event::Ping(d.to_vec()),
+        Ok(ServerWebSocketMessage::Pong(d)) => Event::Pong(d.to_vec()),
+        Ok(ServerWebSocketMessage::Text(t)) => Event::Text(t.to_string()),
+        Ok(ServerWebSocketMessage::Binary(d)) => Event::Binary(d.to_vec()),
+        Ok(ServerWebSocketMessage::Close{code, reason}) => Event::Close(code, reason.to_string()),
+        Err(e) => Event::Error(e.close_code()),
+    }));
+    events
+}
+
+// a raw frame, for the cases the frame builders won't produce
+fn frame(first: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut out = vec![first];
+    let m = if mask.is_some() {128} else {0};
+    if payload.len() < 126 {
+        out.push(m | payload.len() as u8);
+    }
+    else if payload.len() < 65536 {
+        out.push(m | 126);
+        out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    }
+    else {
+        out.push(m | 127);
+        out.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    match mask {
+        Some(mask) => {
+            out.extend_from_slice(&mask);
+            out.extend(payload.iter().enumerate().map( | (i, b) | b ^ mask[i % 4]));
+        }
+        None => out.extend_from_slice(payload)
+    }
+    out
+}
+
+#[test]
+fn handshake_keys() {
+    // the example from RFC 6455
+    assert_eq!(web_socket_accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    assert_ne!(new_web_socket_key(), new_web_socket_key());
+    assert_eq!(new_web_socket_key().len(), 24);
+
+    let handshake = ClientWebSocketHandshake::new();
+    let request = handshake.request("example.com", "/chat", "", true);
+    assert!(request.starts_with("GET /chat HTTP/1.1\r\n"));
+    assert!(request.contains(&format!("Sec-WebSocket-Key: {}\r\n", handshake.key)));
+    assert!(request.contains("Sec-WebSocket-Extensions: permessage-deflate\r\n"));
+
+    let accept = web_socket_accept_key(&handshake.key);
+    let ok = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept);
+    assert_eq!(handshake.check_response(&ok), Ok(None));
+    let deflate = format!("HTTP/1.1 101 OK\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: {}\r\nSec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover\r\n\r\n", accept);
+    assert_eq!(handshake.check_response(&deflate), Ok(Some(WebSocketDeflateParams {server_no_context_takeover: true, client_no_context_takeover: false})));
+    let wrong = "HTTP/1.1 101 OK\r\nUpgrade: websocket\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kWGzzZxOo=\r\n\r\n";
+    assert!(handshake.check_response(wrong).is_err());
+    assert!(handshake.check_response("HTTP/1.1 200 OK\r\n\r\n").is_err());
+}
+
+#[test]
+fn deflate_offers() {
+    assert_eq!(WebSocketDeflateParams::accept_offer("permessage-deflate; client_max_window_bits"), Some(WebSocketDeflateParams::default()));
+    assert_eq!(
+        WebSocketDeflateParams::accept_offer("permessage-deflate; server_max_window_bits=10, permessage-deflate; client_no_context_takeover"),
+        Some(WebSocketDeflateParams {server_no_context_takeover: false, client_no_context_takeover: true})
+    );
+    assert_eq!(WebSocketDeflateParams::accept_offer("x-webkit-deflate-frame"), None);
+    assert!(WebSocketDeflateParams::parse_response("permessage-deflate; client_max_window_bits=10").is_err());
+}
+
+#[test]
+fn fragments_and_control_frames() {
+    let mask = Some([1, 2, 3, 4]);
+    let mut input = Vec::new();
+    input.extend(frame(0x01, "Hel".as_bytes(), mask));
+    // control frames can come in between fragments
+    input.extend(frame(0x89, b"ping", mask));
+    input.extend(frame(0x00, "lo wö".as_bytes(), mask));
+    input.extend(frame(0x80, "rld".as_bytes(), mask));
+    input.extend(frame(0x82, &vec![7u8; 300], mask));
+    input.extend(frame(0x82, &vec![9u8; 70000], mask));
+    input.extend(frame(0x88, &[3, 232, b'b', b'y', b'e'], mask));
+
+    // fed a byte at a time, frames have to survive any split
+    let mut ws = ServerWebSocket::new();
+    let mut events = Vec::new();
+    for byte in &input {
+        events.extend(parse_all(&mut ws, std::slice::from_ref(byte)));
+    }
+    assert_eq!(events.len(), 5);
+    assert!(matches!(&events[0], Event::Ping(p) if p == b"ping"));
+    assert!(matches!(&events[1], Event::Text(t) if t == "Hello wörld"));
+    assert!(matches!(&events[2], Event::Binary(b) if b.len() == 300 && b.iter().all( | b | *b == 7)));
+    assert!(matches!(&events[3], Event::Binary(b) if b.len() == 70000 && b.iter().all( | b | *b == 9)));
+    assert!(matches!(&events[4], Event::Close(Some(1000), r) if r == "bye"));
+
+    // our own builder masks medium sized frames properly
+    let header = ServerWebSocketMessageHeader::from_len(1000, ServerWebSocketMessageFormat::Binary, true);
+    let data = vec![5u8; 1000];
+    let built = ServerWebSocket::build_message(header, &data);
+    assert_eq!(built.len(), 1000 + 8);
+    let events = parse_all(&mut ServerWebSocket::new(), &built);
+    assert!(matches!(&events[0], Event::Binary(b) if *b == data));
+
+    let events = parse_all(&mut ServerWebSocket::new(), &ServerWebSocket::build_close(Some(4000), "app", true));
+    assert!(matches!(&events[0], Event::Close(Some(4000), r) if r == "app"));
+    let events = parse_all(&mut ServerWebSocket::new(), &ServerWebSocket::build_close(None, "", false));
+    assert!(matches!(&events[0], Event::Close(None, _)));
+}
+
+#[test]
+fn protocol_errors() {
+    let fails = | input: Vec<u8> | -> u16 {
+        let events = parse_all(&mut ServerWebSocket::new(), &input);
+        match events.last() {
+            Some(Event::Error(code)) => *code,
+            _ => 0
+        }
+    };
+    // continuation without a message
+    assert_eq!(fails(frame(0x80, b"x", None)), CLOSE_PROTOCOL_ERROR);
+    // a new message while one is fragmented
+    assert_eq!(fails([frame(0x01, b"a", None), frame(0x81, b"b", None)].concat()), CLOSE_PROTOCOL_ERROR);
+    // reserved bits, and a compressed frame without the extension
+    assert_eq!(fails(frame(0x82 | 0x20, b"x", None)), CLOSE_PROTOCOL_ERROR);
+    assert_eq!(fails(frame(0x82 | 0x40, b"x", None)), CLOSE_PROTOCOL_ERROR);
+    // reserved opcodes
+    assert_eq!(fails(frame(0x83, b"", None)), CLOSE_PROTOCOL_ERROR);
+    assert_eq!(fails(frame(0x8b, b"", None)), CLOSE_PROTOCOL_ERROR);
+    // fragmented or too long control frames
+    assert_eq!(fails(frame(0x09, b"", None)), CLOSE_PROTOCOL_ERROR);
+    assert_eq!(fails(frame(0x89, &[0; 126], None)), CLOSE_PROTOCOL_ERROR);
+    // close payloads
+    assert_eq!(fails(frame(0x88, &[3], None)), CLOSE_PROTOCOL_ERROR);
+    assert_eq!(fails(frame(0x88, &1005u16.to_be_bytes(), None)), CLOSE_PROTOCOL_ERROR);
+    assert_eq!(fails(frame(0x88, &[3, 232, 0xff], None)), CLOSE_INVALID_DATA);
+    assert_eq!(fails(frame(0x81, &[0xce, 0xba, 0xe1], None)), CLOSE_INVALID_DATA);
+
+    let mut ws = ServerWebSocket::new();
+    ws.max_message_size = 10;
+    let events = parse_all(&mut ws, &[frame(0x02, &[0; 6], None), frame(0x80, &[0; 6], None)].concat());
+    assert!(matches!(events.last(), Some(Event::Error(CLOSE_TOO_BIG))));
+
+    // nothing after a failure
+    let mut ws = ServerWebSocket::new();
+    assert_eq!(parse_all(&mut ws, &frame(0x83, b"", None)).len(), 1);
+    assert!(parse_all(&mut ws, &frame(0x82, b"ok", None)).is_empty());
+}
+
+#[test]
+fn permessage_deflate() {
+    // the examples from RFC 7692, the second message refers back to the first
+    let mut inflate = WebSocketInflate::new(false);
+    assert_eq!(inflate.decompress(&[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], 1000).unwrap(), b"Hello");
+    assert_eq!(inflate.decompress(&[0xf2, 0x00, 0x11, 0x00, 0x00], 1000).unwrap(), b"Hello");
+    // a stream that ends with a final block
+    assert_eq!(WebSocketInflate::new(false).decompress(&[0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00], 1000).unwrap(), b"Hello");
+    assert!(WebSocketInflate::new(false).decompress(&[0xff, 0xff, 0xff], 1000).is_err());
+
+    for no_context_takeover in [false, true] {
+        let mut deflate = WebSocketDeflate::new(no_context_takeover);
+        let mut inflate = WebSocketInflate::new(no_context_takeover);
+        let messages: Vec<Vec<u8>> = vec![
+            b"the quick brown fox jumps over the lazy dog".repeat(20),
+            Vec::new(),
+            b"the quick brown fox jumps over the lazy dog".to_vec(),
+            (0..100000u32).map( | i | (i * 7 % 251) as u8).collect(),
+        ];
+        for message in &messages {
+            let compressed = deflate.compress(message);
+            assert_eq!(&inflate.decompress(&compressed, usize::MAX).unwrap(), message);
+        }
+        // a repeat compresses to almost nothing with the window carried over
+        deflate.compress(&messages[2]);
+        let small = deflate.compress(&messages[2]).len();
+        assert_eq!(small < 20, !no_context_takeover);
+    }
+    // inflate bombs stop at the limit
+    let compressed = WebSocketDeflate::new(false).compress(&vec![0u8; 1000000]);
+    assert!(WebSocketInflate::new(false).decompress(&compressed, 1000).is_err());
+    let compressed = WebSocketDeflate::new(false).compress(&vec![0u8; 5000]);
+    assert_eq!(WebSocketInflate::new(false).decompress(&compressed, 5000).unwrap().len(), 5000);
+    assert!(WebSocketInflate::new(false).decompress(&compressed, 4999).is_err());
+
+    // compressed messages through the parser, the first one fragmented
+    let params = WebSocketDeflateParams::default();
+    let mut deflate = params.client_deflate();
+    let mut ws = ServerWebSocket::new();
+    ws.set_inflate(Some(params.server_inflate()));
+    let text = "compress me ".repeat(50);
+    let compressed = deflate.compress(text.as_bytes());
+    let (a, b) = compressed.split_at(compressed.len() / 2);
+    let mut input = frame(0x41, a, Some([9, 8, 7, 6]));
+    input.extend(frame(0x80, b, Some([1, 1, 1, 1])));
+    input.extend(frame(0xc2, &deflate.compress(b"binary"), None));
+    input.extend(frame(0x82, b"plain", None));
+    let events = parse_all(&mut ws, &input);
+    assert!(matches!(&events[0], Event::Text(t) if *t == text));
+    assert!(matches!(&events[1], Event::Binary(b) if b == b"binary"));
+    assert!(matches!(&events[2], Event::Binary(b) if b == b"plain"));
+}
+
+// a client connection to the http server, masked like a client has to
+struct Client {
+    stream: TcpStream,
+    ws: ServerWebSocket,
+    deflate: Option<WebSocketDeflate>,
+    pending: Vec<Event>,
+}
+
+impl Client {
+    fn connect(addr: SocketAddr, deflate: bool) -> Client {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let handshake = ClientWebSocketHandshake::new();
+        stream.write_all(handshake.request("localhost", "/ws", "", deflate).as_bytes()).unwrap();
+        let (head, rest) = read_http_head(&mut stream).unwrap();
+        let params = handshake.check_response(&head).unwrap();
+        assert_eq!(params.is_some(), deflate);
+        let mut ws = ServerWebSocket::new();
+        ws.set_inflate(params.map( | p | p.client_inflate()));
+        let pending = parse_all(&mut ws, &rest);
+        Client {stream, ws, deflate: params.map( | p | p.client_deflate()), pending}
+    }
+
+    fn send(&mut self, format: ServerWebSocketMessageFormat, data: &[u8]) {
+        let compress = matches!(format, ServerWebSocketMessageFormat::Binary | ServerWebSocketMessageFormat::Text);
+        let frame = match (&mut self.deflate, compress) {
+            (Some(deflate), true) => {
+                let data = deflate.compress(data);
+                let mut header = ServerWebSocketMessageHeader::from_len(data.len(), format, true);
+                header.set_compressed();
+                ServerWebSocket::build_message(header, &data)
+            }
+            _ => ServerWebSocket::build_message(ServerWebSocketMessageHeader::from_len(data.len(), format, true), data)
+        };
+        self.stream.write_all(&frame).unwrap();
+    }
+
+    // the next event that isn't the server's keepalive ping
+    fn next(&mut self) -> Event {
+        loop {
+            while !self.pending.is_empty() {
+                match self.pending.remove(0) {
+                    Event::Ping(p) if p.is_empty() => continue,
+                    event => return event
+                }
+            }
+            let mut buf = [0u8; 65536];
+            let n = self.stream.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed");
+            self.pending = parse_all(&mut self.ws, &buf[0..n]);
+        }
+    }
+}
+
+#[test]
+fn server_loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    let (tx_request, rx_request) = mpsc::channel::<HttpServerRequest>();
+    start_http_server(HttpServer {listen_address: addr, post_max_size: 1024, request: tx_request}).unwrap();
+    // echo binary messages back
+    std::thread::spawn(move || {
+        while let Ok(message) = rx_request.recv() {
+            if let HttpServerRequest::BinaryMessage {response_sender, data, ..} = message {
+                let _ = response_sender.send(data);
+            }
+        }
+    });
+    // the listener thread may not be accepting yet
+    let mut tries = 0;
+    while TcpStream::connect(addr).is_err() && tries < 100 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        tries += 1;
+    }
+
+    for deflate in [false, true] {
+        let mut client = Client::connect(addr, deflate);
+        let big: Vec<u8> = b"echo this back ".repeat(1000);
+        client.send(ServerWebSocketMessageFormat::Binary, &big);
+        assert!(matches!(client.next(), Event::Binary(b) if b == big));
+        client.send(ServerWebSocketMessageFormat::Binary, b"small");
+        assert!(matches!(client.next(), Event::Binary(b) if b == b"small"));
+        client.send(ServerWebSocketMessageFormat::Ping, b"payload");
+        assert!(matches!(client.next(), Event::Pong(p) if p == b"payload"));
+        client.stream.write_all(&ServerWebSocket::build_close(Some(CLOSE_GOING_AWAY), "done", true)).unwrap();
+        assert!(matches!(client.next(), Event::Close(Some(CLOSE_GOING_AWAY), _)));
+    }
+
+    // protocol errors are closed with their code
+    let mut client = Client::connect(addr, false);
+    client.stream.write_all(&frame(0x83, b"", Some([0; 4]))).unwrap();
+    assert!(matches!(client.next(), Event::Close(Some(CLOSE_PROTOCOL_ERROR), _)));
+}
+
+#[test]
+fn router_server_upgrades() {
+    let mut router = Router::new();
+    router.get("/hello", | _ | HttpResponse::text(200, "hi"));
+    let (tx_request, rx_request) = mpsc::channel::<HttpServerRequest>();
+    let mut server = RouterServer::new(SocketAddr::from(([127, 0, 0, 1], 0)), router);
+    server.web_sockets = Some(tx_request);
+    let addr = start_router_server(server).unwrap().0;
+    let (tx_path, rx_path) = mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(message) = rx_request.recv() {
+            match message {
+                HttpServerRequest::ConnectWebSocket {headers, ..} => tx_path.send(headers.path).unwrap(),
+                HttpServerRequest::BinaryMessage {response_sender, data, ..} => {let _ = response_sender.send(data);}
+                _ => ()
+            }
+        }
+    });
+
+    // a frame sent right behind the handshake isn't lost
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let handshake = ClientWebSocketHandshake::new();
+    let mut request = handshake.request("localhost", "/build/5", "", false).into_bytes();
+    request.extend(ServerWebSocket::build_message(ServerWebSocketMessageHeader::from_len(5, ServerWebSocketMessageFormat::Binary, true), b"early"));
+    stream.write_all(&request).unwrap();
+    let (head, rest) = read_http_head(&mut stream).unwrap();
+    handshake.check_response(&head).unwrap();
+    let mut client = Client {stream, ws: ServerWebSocket::new(), deflate: None, pending: Vec::new()};
+    client.pending = parse_all(&mut client.ws, &rest);
+    assert_eq!(rx_path.recv().unwrap(), "/build/5");
+    assert!(matches!(client.next(), Event::Binary(b) if b == b"early"));
+    client.send(ServerWebSocketMessageFormat::Binary, b"later");
+    assert!(matches!(client.next(), Event::Binary(b) if b == b"later"));
+
+    // plain requests still go to the routes
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /hello HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    assert!(reply.starts_with("HTTP/1.1 200") && reply.ends_with("hi"));
+}
+
+#[test]
+fn server_headers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(b"GET /ws HTTP/1.1\r\nHost: x\r\nsec-websocket-extensions: permessage-deflate\r\n\r\n").unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    let headers = HttpServerHeaders::from_tcp_stream(&mut stream).unwrap();
+    assert_eq!(headers.header("Sec-WebSocket-Extensions"), Some("permessage-deflate"));
+    assert_eq!(headers.header("Host"), Some("x"));
+    assert_eq!(headers.header("Origin"), None);
+}
+
+// Echo loop for the autobahn testsuite, text and binary messages go back as
+// they came in
+fn autobahn_echo(mut stream: TcpStream, mut ws: ServerWebSocket, mut deflate: Option<WebSocketDeflate>, masked: bool, read_ahead: Vec<u8>) {
+    let mut buf = read_ahead;
+    loop {
+        if buf.is_empty() {
+            buf.resize(65536, 0);
+            let n = match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => n
+            };
+            buf.truncate(n);
+        }
+        let mut out = Vec::new();
+        let mut done = false;
+        ws.parse(&buf, | result | {
+            let (format, data) = match result {
+                Ok(ServerWebSocketMessage::Text(t)) => (ServerWebSocketMessageFormat::Text, t.as_bytes()),
+                Ok(ServerWebSocketMessage::Binary(b)) => (ServerWebSocketMessageFormat::Binary, b),
+                Ok(ServerWebSocketMessage::Ping(p)) => {
+                    out.extend(ServerWebSocket::build_message(ServerWebSocketMessageHeader::from_len(p.len(), ServerWebSocketMessageFormat::Pong, masked), p));
+                    return
+                }
+                Ok(ServerWebSocketMessage::Pong(_)) => return,
+                Ok(ServerWebSocketMessage::Close{code, ..}) => {
+                    out.extend(ServerWebSocket::build_close(code, "", masked));
+                    done = true;
+                    return
+                }
+                Err(e) => {
+                    out.extend(ServerWebSocket::build_close(Some(e.close_code()), "", masked));
+                    done = true;
+                    return
+                }
+            };
+            match &mut deflate {
+                Some(deflate) => {
+                    let data = deflate.compress(data);
+                    let mut header = ServerWebSocketMessageHeader::from_len(data.len(), format, masked);
+                    header.set_compressed();
+                    out.extend(ServerWebSocket::build_message(header, &data));
+                }
+                None => out.extend(ServerWebSocket::build_message(ServerWebSocketMessageHeader::from_len(data.len(), format, masked), data))
+            }
+        });
+        buf.clear();
+        if stream.write_all(&out).is_err() || done {
+            return
+        }
+    }
+}
+
+/// With AUTOBAHN_SERVER_PORT set this serves an echo server for the
+/// autobahn fuzzingclient until killed. With AUTOBAHN_CLIENT_ADDR set to a
+/// running fuzzingserver, like `127.0.0.1:9001`, it runs all its cases
+/// as a client and asks it to write the reports
+#[test]
+fn autobahn() {
+    if let Ok(port) = std::env::var("AUTOBAHN_SERVER_PORT") {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", port)).unwrap();
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {continue};
+            std::thread::spawn(move || {
+                let headers = HttpServerHeaders::from_tcp_stream(&mut stream).unwrap();
+                let key = headers.sec_websocket_key.clone().unwrap();
+                let params = headers.header("Sec-WebSocket-Extensions").and_then(WebSocketDeflateParams::accept_offer);
+                let response = match &params {
+                    Some(params) => ServerWebSocket::create_upgrade_response_with_deflate(&key, params),
+                    None => ServerWebSocket::create_upgrade_response(&key)
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                let mut ws = ServerWebSocket::new();
+                ws.set_inflate(params.map( | p | p.server_inflate()));
+                autobahn_echo(stream, ws, params.map( | p | p.server_deflate()), false, Vec::new());
+            });
+        }
+    }
+    if let Ok(addr) = std::env::var("AUTOBAHN_CLIENT_ADDR") {
+        let open = | path: &str | -> (TcpStream, ServerWebSocket, Option<WebSocketDeflateParams>, Vec<u8>) {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            let handshake = ClientWebSocketHandshake::new();
+            stream.write_all(handshake.request(&addr, path, "", true).as_bytes()).unwrap();
+            let (head, rest) = read_http_head(&mut stream).unwrap();
+            let params = handshake.check_response(&head).unwrap();
+            let mut ws = ServerWebSocket::new();
+            ws.set_inflate(params.map( | p | p.client_inflate()));
+            (stream, ws, params, rest)
+        };
+        let (mut stream, mut ws, _, mut buf) = open("/getCaseCount");
+        let mut count = String::new();
+        loop {
+            ws.parse(&buf, | result | if let Ok(ServerWebSocketMessage::Text(t)) = result {count = t.to_string()});
+            if !count.is_empty() {
+                break
+            }
+            buf.resize(1024, 0);
+            let n = stream.read(&mut buf).unwrap();
+            buf.truncate(n);
+        }
+        for case in 1..=count.parse::<u32>().unwrap() {
+            let (stream, ws, params, rest) = open(&format!("/runCase?case={}&agent=makepad", case));
+            autobahn_echo(stream, ws, params.map( | p | p.client_deflate()), true, rest);
+        }
+        let (stream, ws, _, rest) = open("/updateReports?agent=makepad");
+        autobahn_echo(stream, ws, None, true, rest);
+    }
+}
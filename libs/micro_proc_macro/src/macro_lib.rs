@@ -771,14 +771,15 @@ pub fn eat_all_struct_fields(&mut self,) -> Option<Vec<StructField >> {
     
     pub fn eat_generic(&mut self) -> Option<TokenStream> {
         let mut tb = TokenBuilder::new();
-        // if we have a <, keep running and keep a < stack
+        // if we have a <, keep running and keep a < stack. A < right before
+        // a & or a lifetime is joint
         
-        if self.eat_punct_alone('<') {
+        if self.eat_punct_any('<') {
             tb.add("<");
             let mut stack = 1;
             // keep eating things till we are at stack 0 for a ">"
             while stack > 0 {
-                if self.eat_punct_alone('<') {
+                if self.eat_punct_any('<') {
                     tb.add("<");
                     stack += 1;
                 }
@@ -824,7 +825,8 @@ pub fn eat_all_types(&mut self) -> Option<Vec<TokenStream >> {
     
     pub fn eat_type(&mut self) -> Option<TokenStream> {
         let mut tb = TokenBuilder::new();
-        if self.eat_punct_alone('&'){
+        // a & right before a lifetime is joint
+        if self.eat_punct_any('&'){
             tb.add("&");
             if self.eat_punct_any('\''){
                 tb.lifetime_mark();
@@ -0,0 +1,220 @@
+use proc_macro::{TokenStream, TokenTree};
+use makepad_micro_proc_macro::*;
+
+// the serde attributes shared by the json, ron and bin derives
+
+pub struct FieldAttrs {
+    pub rename: Option<String>,
+    // Some(None) for a plain #[default], Some(Some(expr)) for #[default = expr]
+    pub default: Option<Option<TokenStream>>,
+    pub skip: bool,
+    pub flatten: bool,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, TokenStream> {
+        let mut ret = FieldAttrs {rename: None, default: None, skip: false, flatten: false};
+        for attr in attrs {
+            match attr.name.as_ref() {
+                "rename" => ret.rename = Some(attr_string(attr)?),
+                "default" => ret.default = Some(attr.args.clone().filter( | args | !args.is_empty())),
+                "skip" => ret.skip = true,
+                "flatten" => ret.flatten = true,
+                _ => ()
+            }
+        }
+        if ret.flatten && (ret.skip || ret.rename.is_some()) {
+            return Err(error("#[flatten] can't be combined with #[skip] or #[rename]"))
+        }
+        Ok(ret)
+    }
+
+    /// The key in the serialized data
+    pub fn key(&self, name: &str) -> String {
+        if let Some(rename) = &self.rename {
+            return rename.clone()
+        }
+        name.strip_prefix("r#").unwrap_or(name).to_string()
+    }
+
+    /// The key json uses, which drops a leading underscore so fields can be
+    /// named after keywords like `_type`
+    pub fn json_key(&self, name: &str) -> String {
+        if let Some(rename) = &self.rename {
+            return rename.clone()
+        }
+        let name = name.strip_prefix("r#").unwrap_or(name);
+        name.strip_prefix('_').unwrap_or(name).to_string()
+    }
+
+    /// The value of a field that is skipped or missing, if it has one
+    pub fn default_value(&self, tb: &mut TokenBuilder) -> bool {
+        match &self.default {
+            Some(Some(expr)) => {tb.add("(").stream(Some(expr.clone())).add(")");}
+            Some(None) => {tb.add("std :: default :: Default :: default ( )");}
+            None if self.skip => {tb.add("std :: default :: Default :: default ( )");}
+            None => return false
+        }
+        true
+    }
+}
+
+pub struct SerdeField {
+    pub name: String,
+    pub ty: TokenStream,
+    pub attrs: FieldAttrs,
+}
+
+impl SerdeField {
+    pub fn is_option(&self) -> bool {
+        self.ty.clone().into_iter().next().map(|t| t.to_string() == "Option").unwrap_or(false)
+    }
+
+    pub fn key(&self) -> String {
+        self.attrs.key(&self.name)
+    }
+
+    pub fn json_key(&self) -> String {
+        self.attrs.json_key(&self.name)
+    }
+
+    // the local the deserializers collect the field in
+    pub fn local(&self) -> String {
+        format!("_{}", self.name.strip_prefix("r#").unwrap_or(&self.name))
+    }
+}
+
+pub fn serde_fields(fields: Vec<StructField>) -> Result<Vec<SerdeField>, TokenStream> {
+    let mut ret = Vec::new();
+    for field in fields {
+        ret.push(SerdeField {attrs: FieldAttrs::parse(&field.attrs) ?, name: field.name, ty: field.ty});
+    }
+    if ret.iter().filter( | f | f.attrs.flatten).count() > 1 {
+        return Err(error("only one #[flatten] field is supported"))
+    }
+    Ok(ret)
+}
+
+pub enum EnumRepr {
+    // {"Variant":content}
+    External,
+    // {"tag":"Variant", ..fields}
+    Internal {tag: String},
+    // {"tag":"Variant", "content":content}
+    Adjacent {tag: String, content: String},
+    // just the content
+    Untagged,
+}
+
+impl EnumRepr {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, TokenStream> {
+        let mut tag = None;
+        let mut content = None;
+        let mut untagged = false;
+        for attr in attrs {
+            match attr.name.as_ref() {
+                "tag" => tag = Some(attr_string(attr)?),
+                "content" => content = Some(attr_string(attr)?),
+                "untagged" => untagged = true,
+                _ => ()
+            }
+        }
+        match (tag, content, untagged) {
+            (None, None, false) => Ok(EnumRepr::External),
+            (Some(tag), None, false) => Ok(EnumRepr::Internal {tag}),
+            (Some(tag), Some(content), false) => Ok(EnumRepr::Adjacent {tag, content}),
+            (None, None, true) => Ok(EnumRepr::Untagged),
+            (None, Some(_), false) => Err(error("#[content] needs a #[tag]")),
+            _ => Err(error("#[untagged] can't be combined with #[tag] or #[content]"))
+        }
+    }
+}
+
+pub enum VariantKind {
+    Unit,
+    Tuple(usize),
+    Named(Vec<SerdeField>),
+}
+
+pub struct SerdeVariant {
+    pub name: String,
+    pub key: String,
+    pub kind: VariantKind,
+}
+
+/// Parses the braced body of an enum
+pub fn serde_variants(parser: &mut TokenParser) -> Result<Vec<SerdeVariant>, TokenStream> {
+    if !parser.open_brace() {
+        return Err(parser.unexpected())
+    }
+    let mut variants = Vec::new();
+    while !parser.eat_eot() {
+        let attrs = parser.eat_attributes();
+        let Some(name) = parser.eat_any_ident() else {
+            return Err(parser.unexpected())
+        };
+        let key = match attrs.iter().find( | a | a.name == "rename") {
+            Some(attr) => attr_string(attr) ?,
+            None => name.clone()
+        };
+        let kind = if let Some(types) = parser.eat_all_types() {
+            VariantKind::Tuple(types.len())
+        }
+        else if let Some(fields) = parser.eat_all_struct_fields() {
+            VariantKind::Named(serde_fields(fields) ?)
+        }
+        else if parser.is_punct_alone(',') || parser.is_eot() {
+            VariantKind::Unit
+        }
+        else {
+            return Err(parser.unexpected())
+        };
+        variants.push(SerdeVariant {name, key, kind});
+        parser.eat_punct_alone(',');
+    }
+    Ok(variants)
+}
+
+fn attr_string(attr: &Attribute) -> Result<String, TokenStream> {
+    if let Some(args) = &attr.args {
+        let mut iter = args.clone().into_iter();
+        if let (Some(TokenTree::Literal(lit)), None) = (iter.next(), iter.next()) {
+            let lit = lit.to_string();
+            if let Some(s) = lit.strip_prefix('"').and_then( | s | s.strip_suffix('"')) {
+                return Ok(s.to_string())
+            }
+        }
+    }
+    Err(error(&format!("#[{}] expects a string, like #[{} = \"name\"]", attr.name, attr.name)))
+}
+
+/// The lifetimes among the generic params of a type, without the `'`
+pub fn generic_lifetimes(generic: &Option<TokenStream>) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut iter = generic.clone().into_iter().flatten();
+    while let Some(tt) = iter.next() {
+        if let TokenTree::Punct(p) = &tt {
+            if p.as_char() == '\'' {
+                if let Some(TokenTree::Ident(ident)) = iter.next() {
+                    ret.push(ident.to_string());
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// The generic params with `lifetime` added in front, `<T>` becomes `<'de, T>`
+pub fn generic_with_lifetime(generic: &Option<TokenStream>, lifetime: &str) -> TokenStream {
+    let mut tb = TokenBuilder::new();
+    tb.add("<").add(&format!("'{}", lifetime));
+    let params: Vec<TokenTree> = generic.clone().into_iter().flatten().collect();
+    if params.len() > 2 {
+        tb.add(",");
+        for tt in &params[1..params.len() - 1] {
+            tb.extend(tt.clone());
+        }
+    }
+    tb.add(">");
+    tb.end()
+}
@@ -1,5 +1,6 @@
 use proc_macro::{TokenStream};
 use makepad_micro_proc_macro::*;
+use crate::attrs::*;
 
 pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
@@ -24,7 +25,11 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
                 }
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                for field in fields{
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                for field in fields.iter().filter(|f| !f.attrs.skip){
                     tb.add("self .").ident(&field.name).add(". ser_bin ( s ) ;");
                 }
             }
@@ -65,12 +70,16 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
                         tb.add("}");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match serde_fields(fields){
+                            Ok(fields)=>fields,
+                            Err(err)=>return err
+                        };
                         tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter(){
+                        for field in fields.iter().filter(|f| !f.attrs.skip){
                             tb.ident(&field.name).add(",");
                         }
-                        tb.add("} => {").suf_u16(index).add(". ser_bin ( s ) ;");
-                        for field in fields{
+                        tb.add(".. } => {").suf_u16(index).add(". ser_bin ( s ) ;");
+                        for field in fields.iter().filter(|f| !f.attrs.skip){
                             tb.ident(&field.name).add(". ser_bin ( s ) ;");
                         }
                         tb.add("}");
@@ -96,6 +105,22 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
     parser.unexpected()
 } 
 
+// skipped fields aren't in the data and get their default
+fn de_bin_fields(tb: &mut TokenBuilder, fields: &[SerdeField]){
+    tb.add("{");
+    for field in fields{
+        tb.ident(&field.name).add(":");
+        if field.attrs.skip{
+            field.attrs.default_value(tb);
+        }
+        else{
+            tb.add("DeBin :: de_bin ( o , d ) ?");
+        }
+        tb.add(",");
+    }
+    tb.add("}");
+}
+
 pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
@@ -122,11 +147,11 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
                 tb.add(")");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("{");
-                for field in fields{
-                    tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
-                }
-                tb.add("}");
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                de_bin_fields(&mut tb, &fields);
             }
             else{
                 return parser.unexpected()
@@ -165,11 +190,12 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
                         tb.add(")");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.ident(&variant).add("{");
-                        for field in fields.iter(){
-                            tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
-                        }
-                        tb.add("}");
+                        let fields = match serde_fields(fields){
+                            Ok(fields)=>fields,
+                            Err(err)=>return err
+                        };
+                        tb.ident(&variant);
+                        de_bin_fields(&mut tb, &fields);
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
                         tb.ident(&variant);
@@ -0,0 +1,485 @@
+use proc_macro::TokenStream;
+use makepad_micro_proc_macro::*;
+use crate::attrs::*;
+
+// CBOR and MessagePack share their data model, maps with text keys, arrays
+// and null, and their states have the same methods. So one generator does
+// both, only the trait and type names differ.
+
+pub struct Format {
+    ser: &'static str,
+    ser_fn: &'static str,
+    ser_state: &'static str,
+    de: &'static str,
+    de_fn: &'static str,
+    de_state: &'static str,
+    de_err: &'static str,
+}
+
+pub const CBOR: Format = Format {
+    ser: "SerCbor",
+    ser_fn: "ser_cbor",
+    ser_state: "SerCborState",
+    de: "DeCbor",
+    de_fn: "de_cbor",
+    de_state: "DeCborState",
+    de_err: "DeCborErr",
+};
+
+pub const MSGPACK: Format = Format {
+    ser: "SerMsgPack",
+    ser_fn: "ser_msgpack",
+    ser_state: "SerMsgPackState",
+    de: "DeMsgPack",
+    de_fn: "de_msgpack",
+    de_state: "DeMsgPackState",
+    de_err: "DeMsgPackErr",
+};
+
+// writes a map of the fields, `prefix` is `self .` for structs and empty for
+// enum variants whose fields are bound by reference. `tag` is the entry
+// internally tagged enums put first
+fn ser_map(tb: &mut TokenBuilder, f: &Format, fields: &[SerdeField], prefix: &str, tag: Option<(&str, &str)>) {
+    let by_ref = if prefix.is_empty() {""} else {"&"};
+    // maps are length prefixed, so count the options that are there and the
+    // entries of the flattened field first
+    let fixed = fields.iter().filter( | f | !f.attrs.skip && !f.attrs.flatten && !f.is_option()).count();
+    for field in fields {
+        if field.attrs.flatten {
+            tb.add("let flat =").ident(f.ser_state).add(":: flatten_entries (").add(by_ref).add(prefix).ident(&field.name).add(") ;");
+        }
+    }
+    tb.add("let n =").unsuf_usize(fixed + tag.is_some() as usize);
+    for field in fields {
+        if field.attrs.skip {
+            continue;
+        }
+        if field.attrs.flatten {
+            tb.add("+ flat . 0");
+        }
+        else if field.is_option() {
+            tb.add("+ usize :: from (").add(prefix).ident(&field.name).add(". is_some ( ) )");
+        }
+    }
+    tb.add("; s . map ( n ) ;");
+    if let Some((tag, key)) = tag {
+        tb.add("s . str (").string(tag).add(") ; s . str (").string(key).add(") ;");
+    }
+    for field in fields {
+        if field.attrs.skip {
+            continue;
+        }
+        if field.attrs.flatten {
+            tb.add("s . out . extend_from_slice ( & flat . 1 ) ;");
+        }
+        else if field.is_option() {
+            tb.add("if let Some ( t ) =").add(by_ref).add(prefix).ident(&field.name).add("{");
+            tb.add("s . str (").string(&field.key()).add(") ; t .").ident(f.ser_fn).add("( s ) ; }");
+        }
+        else {
+            tb.add("s . str (").string(&field.key()).add(") ;");
+            tb.add(prefix).ident(&field.name).add(".").ident(f.ser_fn).add("( s ) ;");
+        }
+    }
+}
+
+fn ser_tuple(tb: &mut TokenBuilder, f: &Format, len: usize) {
+    tb.add("s . array (").unsuf_usize(len).add(") ;");
+    for i in 0..len {
+        tb.ident(&format!("n{}", i)).add(".").ident(f.ser_fn).add("( s ) ;");
+    }
+}
+
+fn ser_variant_pattern(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    tb.add("Self ::").ident(&variant.name);
+    match &variant.kind {
+        VariantKind::Unit => (),
+        VariantKind::Tuple(len) => {
+            tb.add("(");
+            for i in 0..*len {
+                tb.ident(&format!("n{}", i)).add(",");
+            }
+            tb.add(")");
+        }
+        VariantKind::Named(fields) => {
+            tb.add("{");
+            for field in fields {
+                tb.ident(&field.name);
+                if field.attrs.skip {
+                    tb.add(": _");
+                }
+                tb.add(",");
+            }
+            tb.add("}");
+        }
+    }
+    tb.add("=>");
+}
+
+// the variant data on its own, as used by adjacently tagged and untagged enums
+fn ser_content(tb: &mut TokenBuilder, f: &Format, variant: &SerdeVariant) {
+    match &variant.kind {
+        VariantKind::Unit => {tb.add("s . null ( ) ;");}
+        VariantKind::Tuple(1) => {tb.add("n0 .").ident(f.ser_fn).add("( s ) ;");}
+        VariantKind::Tuple(len) => ser_tuple(tb, f, *len),
+        VariantKind::Named(fields) => ser_map(tb, f, fields, "", None),
+    }
+}
+
+fn ser_header(tb: &mut TokenBuilder, f: &Format, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>) {
+    tb.add("impl").stream(generic.clone());
+    tb.ident(f.ser).add("for").ident(name).stream(generic).stream(where_clause);
+    tb.add("{ fn").ident(f.ser_fn).add("( & self , s : & mut").ident(f.ser_state).add(") {");
+}
+
+pub fn derive_ser_impl(input: TokenStream, f: &Format) -> TokenStream {
+    let mut parser = TokenParser::new(input);
+    let mut tb = TokenBuilder::new();
+
+    let main_attrs = parser.eat_attributes();
+    parser.eat_ident("pub");
+    if parser.eat_ident("struct"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let types = parser.eat_all_types();
+            let where_clause = parser.eat_where_clause(Some(f.ser));
+
+            ser_header(&mut tb, f, &name, generic, where_clause);
+            if let Some(types) = types{
+                tb.add("s . array (").unsuf_usize(types.len()).add(") ;");
+                for i in 0..types.len(){
+                    tb.add("self .").unsuf_usize(i).add(".").ident(f.ser_fn).add("( s ) ;");
+                }
+            }
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                ser_map(&mut tb, f, &fields, "self .", None);
+            }
+            else{
+                return parser.unexpected()
+            }
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    else if parser.eat_ident("enum"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let where_clause = parser.eat_where_clause(Some(f.ser));
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
+
+            ser_header(&mut tb, f, &name, generic, where_clause);
+            tb.add("match self {");
+            for variant in &variants{
+                ser_variant_pattern(&mut tb, variant);
+                tb.add("{");
+                match &repr{
+                    EnumRepr::External=>{
+                        tb.add("s . map ( 1 ) ; s . str (").string(&variant.key).add(") ;");
+                        match &variant.kind{
+                            VariantKind::Unit=>{tb.add("s . array ( 0 ) ;");}
+                            VariantKind::Tuple(len)=>ser_tuple(&mut tb, f, *len),
+                            VariantKind::Named(_)=>ser_content(&mut tb, f, variant),
+                        }
+                    }
+                    EnumRepr::Internal{tag}=>{
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                tb.add("s . map ( 1 ) ;");
+                                tb.add("s . str (").string(tag).add(") ; s . str (").string(&variant.key).add(") ;");
+                            }
+                            VariantKind::Tuple(1)=>{
+                                tb.add("let flat =").ident(f.ser_state).add(":: flatten_entries ( n0 ) ;");
+                                tb.add("s . map ( 1 + flat . 0 ) ;");
+                                tb.add("s . str (").string(tag).add(") ; s . str (").string(&variant.key).add(") ;");
+                                tb.add("s . out . extend_from_slice ( & flat . 1 ) ;");
+                            }
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>ser_map(&mut tb, f, fields, "", Some((tag, &variant.key))),
+                        }
+                    }
+                    EnumRepr::Adjacent{tag, content}=>{
+                        if let VariantKind::Unit = variant.kind{
+                            tb.add("s . map ( 1 ) ;");
+                        }
+                        else{
+                            tb.add("s . map ( 2 ) ;");
+                        }
+                        tb.add("s . str (").string(tag).add(") ; s . str (").string(&variant.key).add(") ;");
+                        if !matches!(variant.kind, VariantKind::Unit){
+                            tb.add("s . str (").string(content).add(") ;");
+                            ser_content(&mut tb, f, variant);
+                        }
+                    }
+                    EnumRepr::Untagged=>ser_content(&mut tb, f, variant)
+                }
+                tb.add("}");
+            }
+            tb.add("}");
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    parser.unexpected()
+}
+
+// reads a map into the field locals, `skip` are keys that belong to the
+// enclosing enum, like its tag. Keys a #[flatten] field takes are collected
+// as ranges of the input
+fn de_fields(tb: &mut TokenBuilder, f: &Format, fields: &[SerdeField], skip: Option<&str>) {
+    let flatten = fields.iter().any( | f | f.attrs.flatten);
+    tb.add("let mut len = s . map_begin ( ) ? ;");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.add("let mut").ident(&field.local()).add("= None ;");
+        }
+    }
+    if flatten {
+        tb.add("let mut rest = Vec :: new ( ) ;");
+    }
+    tb.add("while s . map_next ( & mut len ) ? {");
+    if flatten {
+        tb.add("let start = s . o ;");
+    }
+    tb.add("let key = s . key ( ) ? ;");
+    tb.add("match key . as_ref ( ) {");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.string(&field.key()).add("=> {").ident(&field.local()).add("= Some (");
+            tb.ident(f.de).add("::").ident(f.de_fn).add("( s ) ? ) ; } ,");
+        }
+    }
+    if let Some(skip) = skip {
+        tb.string(skip).add("=> { s . skip_value ( ) ? ; } ,");
+    }
+    if flatten {
+        tb.add("_ => { s . skip_value ( ) ? ; rest . push ( ( start , s . o ) ) ; }");
+    }
+    else {
+        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & key ) )");
+    }
+    tb.add("} }");
+}
+
+// the struct expression for the locals de_fields collected
+fn de_fields_build(tb: &mut TokenBuilder, fields: &[SerdeField]) {
+    tb.add("{");
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.attrs.flatten {
+            tb.add("s . de_rest ( & rest ) ? ,");
+        }
+        else if field.attrs.skip {
+            field.attrs.default_value(tb);
+            tb.add(",");
+        }
+        else {
+            tb.add("if let Some ( t ) =").ident(&field.local()).add("{ t } else {");
+            if !field.attrs.default_value(tb) {
+                if field.is_option() {
+                    tb.add("None");
+                }
+                else {
+                    tb.add("return std :: result :: Result :: Err ( s . err_nf (").string(&field.key()).add(") )");
+                }
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("}");
+}
+
+// an array of `len` items into `ctor`, which is `Self` or a variant
+fn de_tuple(tb: &mut TokenBuilder, f: &Format, ctor: &str, len: usize) {
+    tb.add("let mut len = s . array_begin ( ) ? ;");
+    tb.add("let r =").add(ctor).add("(");
+    for _ in 0..len {
+        tb.add("{ s . array_item ( & mut len ) ? ;").ident(f.de).add("::").ident(f.de_fn).add("( s ) ? } ,");
+    }
+    tb.add(") ;");
+    tb.add("s . array_end ( & mut len ) ? ; r");
+}
+
+// reads the variant data on its own, as used by adjacently tagged and
+// untagged enums, evaluates to the variant
+fn de_content(tb: &mut TokenBuilder, f: &Format, variant: &SerdeVariant) {
+    tb.add("{");
+    match &variant.kind {
+        VariantKind::Unit => {
+            tb.add("s . null ( ) ? ; Self ::").ident(&variant.name);
+        }
+        VariantKind::Tuple(1) => {
+            tb.add("Self ::").ident(&variant.name).add("(").ident(f.de).add("::").ident(f.de_fn).add("( s ) ? )");
+        }
+        VariantKind::Tuple(len) => de_tuple(tb, f, &format!("Self :: {}", variant.name), *len),
+        VariantKind::Named(fields) => {
+            de_fields(tb, f, fields, None);
+            tb.add("Self ::").ident(&variant.name);
+            de_fields_build(tb, fields);
+        }
+    }
+    tb.add("}");
+}
+
+fn de_header(tb: &mut TokenBuilder, f: &Format, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>) {
+    tb.add("impl").stream(generic.clone());
+    tb.ident(f.de).add("for").ident(name).stream(generic).stream(where_clause);
+    tb.add("{ fn").ident(f.de_fn).add("( s : & mut").ident(f.de_state).add(")");
+    tb.add("-> std :: result :: Result < Self ,").ident(f.de_err).add("> {");
+}
+
+pub fn derive_de_impl(input: TokenStream, f: &Format) -> TokenStream {
+    let mut parser = TokenParser::new(input);
+    let mut tb = TokenBuilder::new();
+
+    let main_attrs = parser.eat_attributes();
+    parser.eat_ident("pub");
+    if parser.eat_ident("struct"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let types = parser.eat_all_types();
+            let where_clause = parser.eat_where_clause(Some(f.de));
+
+            de_header(&mut tb, f, &name, generic, where_clause);
+            if let Some(types) = types{
+                tb.add("std :: result :: Result :: Ok ( {");
+                de_tuple(&mut tb, f, "Self", types.len());
+                tb.add("} )");
+            }
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                de_fields(&mut tb, f, &fields, None);
+                tb.add("std :: result :: Result :: Ok ( Self");
+                de_fields_build(&mut tb, &fields);
+                tb.add(")");
+            }
+            else{
+                return parser.unexpected()
+            }
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    else if parser.eat_ident("enum"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let where_clause = parser.eat_where_clause(Some(f.de));
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
+
+            de_header(&mut tb, f, &name, generic, where_clause);
+            match &repr{
+                EnumRepr::External=>{
+                    tb.add("let mut len = s . map_begin ( ) ? ;");
+                    tb.add("if ! s . map_next ( & mut len ) ? { return std :: result :: Result :: Err ( s . err_msg (").string("Expected an enum variant").add(") ) }");
+                    tb.add("let key = s . key ( ) ? ;");
+                    tb.add("let r = match key . as_ref ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                tb.add("s . empty_array ( ) ? ; Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(len)=>de_tuple(&mut tb, f, &format!("Self :: {}", variant.name), *len),
+                            VariantKind::Named(fields)=>{
+                                de_fields(&mut tb, f, fields, None);
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
+                            }
+                        }
+                        tb.add("}");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & key ) )");
+                    tb.add("} ;");
+                    tb.add("if s . map_next ( & mut len ) ? { return std :: result :: Result :: Err ( s . err_msg (").string("Expected one enum variant").add(") ) }");
+                    tb.add("std :: result :: Result :: Ok ( r )");
+                }
+                EnumRepr::Internal{tag}=>{
+                    tb.add("let tag = s . find_tag (").string(tag).add(") ? ;");
+                    tb.add("std :: result :: Result :: Ok ( match tag . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                de_fields(&mut tb, f, &[], Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(1)=>{
+                                tb.add("Self ::").ident(&variant.name).add("( s . de_map_without (").string(tag).add(") ? )");
+                            }
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>{
+                                de_fields(&mut tb, f, fields, Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
+                            }
+                        }
+                        tb.add("}");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} )");
+                }
+                EnumRepr::Adjacent{tag, content}=>{
+                    tb.add("let tag = s . find_tag (").string(tag).add(") ? ;");
+                    tb.add("let mut len = s . map_begin ( ) ? ;");
+                    tb.add("let mut r = None ;");
+                    tb.add("while s . map_next ( & mut len ) ? {");
+                    tb.add("let key = s . key ( ) ? ;");
+                    tb.add("match key . as_ref ( ) {");
+                    tb.string(tag).add("=> { s . skip_value ( ) ? ; } ,");
+                    tb.string(content).add("=> { r = Some ( match tag . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=>");
+                        de_content(&mut tb, f, variant);
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} ) ; } ,");
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & key ) )");
+                    tb.add("} }");
+                    // unit variants leave out the content
+                    tb.add("std :: result :: Result :: Ok ( match r { Some ( r ) => r , None => match tag . as_str ( ) {");
+                    for variant in &variants{
+                        if let VariantKind::Unit = variant.kind{
+                            tb.string(&variant.key).add("=> Self ::").ident(&variant.name).add(",");
+                        }
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_nf (").string(content).add(") )");
+                    tb.add("} } )");
+                }
+                EnumRepr::Untagged=>{
+                    // try the variants in order, rewinding after every miss
+                    for variant in &variants{
+                        tb.add("{ let o = s . o ;");
+                        tb.add("if let Ok ( r ) = ( | s : & mut").ident(f.de_state).add("| -> std :: result :: Result < Self ,").ident(f.de_err).add("> {");
+                        tb.add("std :: result :: Result :: Ok (");
+                        de_content(&mut tb, f, variant);
+                        tb.add(") } ) ( s ) { return std :: result :: Result :: Ok ( r ) } s . o = o ; }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( s . err_untagged (").string(&name).add(") )");
+                }
+            }
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    parser.unexpected()
+}
@@ -1,16 +1,93 @@
 use proc_macro::TokenStream;
 use makepad_micro_proc_macro::*;
+use crate::attrs::*;
+
+// writes the fields of an object, `prefix` is `self .` for structs and empty
+// for enum variants whose fields are bound by reference. Struct fields keep a
+// leading underscore in their key, variant fields drop it
+fn ser_fields(tb: &mut TokenBuilder, fields: &[SerdeField], prefix: &str) {
+    let by_ref = if prefix.is_empty() {""} else {"&"};
+    for field in fields {
+        if field.attrs.skip {
+            continue;
+        }
+        let key = if prefix.is_empty() {field.json_key()} else {field.key()};
+        if field.attrs.flatten {
+            tb.add("s . flatten ( d ,").add(by_ref).add(prefix).ident(&field.name).add(") ;");
+        }
+        else if field.is_option() {
+            tb.add("if let Some ( t ) =").add(by_ref).add(prefix).ident(&field.name).add("{");
+            tb.add("s . field_sep ( ) ; s . field ( d + 1 ,").string(&key).add(") ;");
+            tb.add("t . ser_json ( d + 1 , s ) ; }");
+        }
+        else {
+            tb.add("s . field_sep ( ) ; s . field ( d + 1 ,").string(&key).add(") ;");
+            tb.add(prefix).ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
+        }
+    }
+}
+
+fn ser_tuple(tb: &mut TokenBuilder, len: usize) {
+    tb.add("s . out . push (").chr('[').add(") ;");
+    for i in 0..len {
+        tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
+        if i != len - 1 {
+            tb.add("s . out . push (").chr(',').add(") ;");
+        }
+    }
+    tb.add("s . out . push (").chr(']').add(") ;");
+}
+
+fn ser_variant_pattern(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    tb.add("Self ::").ident(&variant.name);
+    match &variant.kind {
+        VariantKind::Unit => (),
+        VariantKind::Tuple(len) => {
+            tb.add("(");
+            for i in 0..*len {
+                tb.ident(&format!("n{}", i)).add(",");
+            }
+            tb.add(")");
+        }
+        VariantKind::Named(fields) => {
+            tb.add("{");
+            for field in fields {
+                tb.ident(&field.name);
+                if field.attrs.skip {
+                    tb.add(": _");
+                }
+                tb.add(",");
+            }
+            tb.add("}");
+        }
+    }
+    tb.add("=>");
+}
+
+// the variant data on its own, as used by adjacently tagged and untagged enums
+fn ser_content(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    match &variant.kind {
+        VariantKind::Unit => {tb.add("s . out . push_str (").string("null").add(") ;");}
+        VariantKind::Tuple(1) => {tb.add("n0 . ser_json ( d , s ) ;");}
+        VariantKind::Tuple(len) => ser_tuple(tb, *len),
+        VariantKind::Named(fields) => {
+            tb.add("s . st_pre ( ) ;");
+            ser_fields(tb, fields, "");
+            tb.add("s . st_post ( d ) ;");
+        }
+    }
+}
 
 pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
 
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let main_attrs = parser.eat_attributes();
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
-            
+
             let generic = parser.eat_generic();
             let types = parser.eat_all_types();
             let where_clause = parser.eat_where_clause(Some("SerJson"));
@@ -18,7 +95,7 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            
+
             if let Some(types) = types{
                 tb.add("s . out . push (").chr('[').add(") ;");
                 for i in 0..types.len(){
@@ -30,27 +107,13 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . out . push (").chr(']').add(") ;");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){
-                let fields_len = fields.len();
-                tb.add("s . st_pre ( ) ;");
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
                 // named struct
-                for (i, field) in fields.into_iter().enumerate() {
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) = ").add("& self .").ident(&field.name).add("{");
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
-                        tb.add("t . ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                        tb.add("} ;");
-                    }
-                    else{
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(" ) ;");
-                        tb.add("self .").ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                    }
-                }
+                tb.add("s . st_pre ( ) ;");
+                ser_fields(&mut tb, &fields, "self .");
                 tb.add("s . st_post ( d ) ;");
             }
             else{
@@ -64,90 +127,60 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("SerJson"));
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
 
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            tb.add("s . out . push (").chr('{').add(") ;");
             tb.add("match self {");
-            
-            if !parser.open_brace(){
-                return parser.unexpected()
-            }
 
-            while !parser.eat_eot(){
-                // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident(){
-                    if let Some(types) = parser.eat_all_types(){
-                        
-                        tb.add("Self ::").ident(&variant).add("(");
-                        for i in 0..types.len(){
-                            tb.ident(&format!("n{}", i)).add(",");
-                        }
-                        tb.add(") => {");
-                        tb.add("s . label (").string(&variant).add(") ;");
+            for variant in &variants{
+                ser_variant_pattern(&mut tb, variant);
+                tb.add("{");
+                match &repr{
+                    EnumRepr::External=>{
+                        tb.add("s . out . push (").chr('{').add(") ;");
+                        tb.add("s . label (").string(&variant.key).add(") ;");
                         tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . out . push (").chr('[').add(") ;");
-                        
-                        for i in 0..types.len(){
-                            tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
-                            if i != types.len() - 1{
-                                tb.add("s . out . push (").chr(',').add(") ;");
-                            }
+                        match &variant.kind{
+                            VariantKind::Unit=>{tb.add("s . out . push_str (").string("[]").add(") ;");}
+                            VariantKind::Tuple(len)=>ser_tuple(&mut tb, *len),
+                            VariantKind::Named(_)=>ser_content(&mut tb, variant),
                         }
-                        tb.add("s . out . push (").chr(']').add(") ;");
-                        tb.add("}");
+                        tb.add("s . out . push (").chr('}').add(") ;");
                     }
-                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
+                    EnumRepr::Internal{tag}=>{
+                        tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                        tb.add("s . label (").string(&variant.key).add(") ;");
+                        match &variant.kind{
+                            VariantKind::Unit=>(),
+                            VariantKind::Tuple(1)=>{tb.add("s . flatten ( d , n0 ) ;");}
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>ser_fields(&mut tb, fields, ""),
                         }
-                        tb.add("} => {");
-                        
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . st_pre ( ) ;");
-
-                        let fields_len = fields.len();
-                        for (i, field) in fields.into_iter().enumerate() {
-                            let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) = ").ident(&field.name).add("{");
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(") ;");
-                                tb.add("t . ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
-                                tb.add("} ;");
-                            }
-                            else{
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(" ) ;");
-                                tb.ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
-                            }
-                        }
-                        tb.add("s . st_post ( d ) ; }");
+                        tb.add("s . st_post ( d ) ;");
                     }
-                    else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
-                        tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push_str (").string(":[]").add(") ; }");
-                    }
-                    else{
-                        return parser.unexpected();
+                    EnumRepr::Adjacent{tag, content}=>{
+                        tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                        tb.add("s . label (").string(&variant.key).add(") ;");
+                        if !matches!(variant.kind, VariantKind::Unit){
+                            tb.add("s . conl ( ) ; s . field ( d + 1 ,").string(content).add(") ;");
+                            ser_content(&mut tb, variant);
+                        }
+                        tb.add("s . st_post ( d ) ;");
                     }
-                    parser.eat_punct_alone(',');
-                }
-                else{
-                    return parser.unexpected()
+                    EnumRepr::Untagged=>ser_content(&mut tb, variant)
                 }
+                tb.add("}");
             }
             tb.add("}");
-            tb.add("s . out . push (").chr('}').add(") ;");
             tb.add("} } ;");
             return tb.end();
         }
@@ -155,151 +188,303 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
     parser.unexpected()
 }
 
+// reads an object into the field locals, `skip` are keys that belong to the
+// enclosing enum, like its tag
+fn de_fields(tb: &mut TokenBuilder, fields: &[SerdeField], skip: Option<&str>, de: &str) {
+    let flatten = fields.iter().any( | f | f.attrs.flatten);
+    tb.add("s . curly_open ( i ) ? ;");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.add("let mut").ident(&field.local()).add("= None ;");
+        }
+    }
+    if flatten {
+        tb.add("let mut rest = std :: collections :: HashMap :: < String , JsonValue > :: new ( ) ;");
+    }
+    tb.add("while let Some ( _ ) = s . next_str ( ) {");
+    tb.add("match s . strbuf . as_str ( ) {");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            // both spellings of an underscored field are accepted, so what
+            // SerJson writes for structs reads back
+            let key = field.json_key();
+            tb.string(&key);
+            if field.key() != key && !fields.iter().any( | f | f.json_key() == field.key()) {
+                tb.add("|").string(&field.key());
+            }
+            tb.add("=> { s . next_colon ( i ) ? ;");
+            tb.ident(&field.local()).add("= Some (").add(de).add("? ) ; } ,");
+        }
+    }
+    if let Some(skip) = skip {
+        tb.string(skip).add("=> { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; } ,");
+    }
+    if flatten {
+        tb.add("_ => { let k = std :: mem :: take ( & mut s . strbuf ) ; s . next_colon ( i ) ? ;");
+        tb.add("rest . insert ( k , DeJson :: de_json ( s , i ) ? ) ; }");
+    }
+    else {
+        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
+    }
+    tb.add("} ; s . eat_comma_curly ( i ) ? ;");
+    tb.add("} ; s . curly_close ( i ) ? ;");
+}
+
+// the struct expression for the locals de_fields collected
+fn de_fields_build(tb: &mut TokenBuilder, fields: &[SerdeField]) {
+    tb.add("{");
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.attrs.flatten {
+            tb.add("s . de_json_rest ( rest ) ? ,");
+        }
+        else if field.attrs.skip {
+            field.attrs.default_value(tb);
+            tb.add(",");
+        }
+        else {
+            tb.add("if let Some ( t ) =").ident(&field.local()).add("{ t } else {");
+            if !field.attrs.default_value(tb) {
+                if field.is_option() {
+                    tb.add("None");
+                }
+                else {
+                    tb.add("return std :: result :: Result :: Err ( s . err_nf (").string(&field.json_key()).add(") )");
+                }
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("}");
+}
+
+fn de_tuple(tb: &mut TokenBuilder, variant: &str, len: usize, de: &str) {
+    tb.add("s . block_open ( i ) ? ;");
+    tb.add("let r = Self ::").ident(variant).add("(");
+    for _ in 0..len {
+        tb.add("{ let r =").add(de).add("? ; s . eat_comma_block ( i ) ? ; r } ,");
+    }
+    tb.add(") ;");
+    tb.add("s . block_close ( i ) ? ; r");
+}
+
+// parses the variant data on its own, as used by adjacently tagged and
+// untagged enums, evaluates to the variant
+fn de_content(tb: &mut TokenBuilder, variant: &SerdeVariant, de: &str) {
+    tb.add("{");
+    match &variant.kind {
+        VariantKind::Unit => {
+            tb.add("s . null ( i ) ? ; Self ::").ident(&variant.name);
+        }
+        VariantKind::Tuple(1) => {
+            tb.add("Self ::").ident(&variant.name).add("(").add(de).add("? )");
+        }
+        VariantKind::Tuple(len) => de_tuple(tb, &variant.name, *len, de),
+        VariantKind::Named(fields) => {
+            de_fields(tb, fields, None, de);
+            tb.add("Self ::").ident(&variant.name);
+            de_fields_build(tb, fields);
+        }
+    }
+    tb.add("}");
+}
+
+// how a field is read, types with a lifetime read theirs through DeJsonRef so
+// they can borrow from the input
+const DE_JSON: &str = "DeJson :: de_json ( s , i )";
+const DE_JSON_REF: &str = "DeJsonRef :: de_json_ref ( s , i , src )";
+
+// opens the impl and the deserialize fn, a type with a lifetime only gets
+// DeJsonRef. Returns how fields are read
+fn de_json_header(tb: &mut TokenBuilder, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>, lifetime: Option<&String>) -> &'static str {
+    if let Some(lifetime) = lifetime {
+        let lifetime = format!("'{}", lifetime);
+        tb.add("impl").stream(generic.clone());
+        tb.add("DeJsonRef <").add(&lifetime).add("> for").ident(name).stream(generic).stream(where_clause);
+        tb.add("{ fn de_json_ref ( s : & mut DeJsonState , i : & mut std :: str :: Chars , src : &").add(&lifetime).add("str )");
+        tb.add("-> std :: result :: Result < Self , DeJsonErr > {");
+        DE_JSON_REF
+    }
+    else {
+        tb.add("impl").stream(generic.clone());
+        tb.add("DeJson for").ident(name).stream(generic).stream(where_clause);
+        tb.add("{ fn de_json ( s : & mut DeJsonState , i : & mut std :: str :: Chars )");
+        tb.add("-> std :: result :: Result < Self , DeJsonErr > {");
+        DE_JSON
+    }
+}
+
+// owned types can be used in borrowing ones, their DeJsonRef is DeJson
+fn de_json_ref_forward(tb: &mut TokenBuilder, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>) {
+    tb.add("impl").stream(Some(generic_with_lifetime(&generic, "de")));
+    tb.add("DeJsonRef < 'de > for").ident(name).stream(generic).stream(where_clause);
+    tb.add("{ fn de_json_ref ( s : & mut DeJsonState , i : & mut std :: str :: Chars , _src : & 'de str )");
+    tb.add("-> std :: result :: Result < Self , DeJsonErr > { DeJson :: de_json ( s , i ) } }");
+}
+
 pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let main_attrs = parser.eat_attributes();
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
+            let lifetimes = generic_lifetimes(&generic);
+            if lifetimes.len() > 1{
+                return error("DeJson supports types with at most one lifetime")
+            }
             let types = parser.eat_all_types();
-            let where_clause = parser.eat_where_clause(Some("DeJson"));
+            let where_clause = parser.eat_where_clause(if lifetimes.is_empty(){Some("DeJson")}else{None});
 
-            tb.add("impl").stream(generic.clone());
-            tb.add("DeJson for").ident(&name).stream(generic).stream(where_clause);
-            tb.add("{ fn de_json ( s : &  mut  DeJsonState , i : & mut std :: str :: Chars )");
-            tb.add("-> std :: result :: Result < Self ,  DeJsonErr > { ");
+            let de = de_json_header(&mut tb, &name, generic.clone(), where_clause.clone(), lifetimes.first());
 
             if let Some(types) = types{
                 tb.add("s . block_open ( i ) ? ;");
                 tb.add("let r = Self");
                 tb.add("(");
                 for _ in 0..types.len(){
-                     tb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
+                     tb.add("{ let r =").add(de).add("? ; s . eat_comma_block ( i ) ? ; r } ,");
                 }
                 tb.add(") ;");
                 tb.add("s . block_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r )");
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("s . curly_open ( i ) ? ;");
-                for field in &fields{
-                    tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                tb.add("match s . strbuf . as_ref ( ) {");
-                for field in &fields{
-                    let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                    tb.string(&field_strip).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
-                }
-                tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                tb.add("} ; s . eat_comma_curly ( i ) ? ;");
-                tb.add("} ; s . curly_close ( i ) ? ;");
-                
-                tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields{
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { None } ,");
-                    }
-                    else{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
-                tb.add("} )");
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                de_fields(&mut tb, &fields, None, de);
+                tb.add("std :: result :: Result :: Ok ( Self");
+                de_fields_build(&mut tb, &fields);
+                tb.add(")");
             }
             else{
                 return parser.unexpected()
             }
-            tb.add("} } ;"); 
+            tb.add("} }");
+            if lifetimes.is_empty(){
+                de_json_ref_forward(&mut tb, &name, generic, where_clause);
+            }
+            tb.add(";");
             return tb.end();
         }
     }
     else if parser.eat_ident("enum"){
-        
+
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
-            let where_clause = parser.eat_where_clause(Some("DeJson"));
-
-            tb.add("impl").stream(generic.clone());
-            tb.add("DeJson for").ident(&name).stream(generic).stream(where_clause);
-            tb.add("{ fn de_json ( s : & mut  DeJsonState , i : & mut std :: str :: Chars )");
-            tb.add("-> std :: result :: Result < Self , DeJsonErr > { ");
-            tb.add("s . curly_open ( i ) ? ;");
-            tb.add("let _ = s . string ( i ) ? ;");
-            tb.add("s . colon ( i ) ? ;");
-            tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_ref ( ) {");
-            
-            if !parser.open_brace(){
-                return parser.unexpected()
+            let lifetimes = generic_lifetimes(&generic);
+            if lifetimes.len() > 1{
+                return error("DeJson supports types with at most one lifetime")
             }
-            while !parser.eat_eot(){
-                // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident(){
-                    tb.string(&variant).add("=> {");
-                    if let Some(types) = parser.eat_all_types(){
-                        
-                        tb.add("s . block_open ( i ) ? ;");
-                        tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len(){
-                            tb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
+            let where_clause = parser.eat_where_clause(if lifetimes.is_empty(){Some("DeJson")}else{None});
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
+
+            let de = de_json_header(&mut tb, &name, generic.clone(), where_clause.clone(), lifetimes.first());
+            match &repr{
+                EnumRepr::External=>{
+                    tb.add("s . curly_open ( i ) ? ;");
+                    tb.add("let _ = s . string ( i ) ? ;");
+                    tb.add("s . colon ( i ) ? ;");
+                    tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                tb.add("s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(len)=>de_tuple(&mut tb, &variant.name, *len, de),
+                            VariantKind::Named(fields)=>{
+                                de_fields(&mut tb, fields, None, de);
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
+                            }
                         }
-                        tb.add(") ;");
-                        tb.add("s . block_close ( i ) ? ; r");
+                        tb.add("}");
                     }
-                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.add("s . curly_open ( i ) ? ;");
-                        for field in &fields{
-                            tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                        }
-                        tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                        tb.add("match s . strbuf . as_ref ( ) {");
-                        for field in &fields{
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
-                        }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} s . eat_comma_curly ( i ) ? ;");
-                        tb.add("} s . curly_close ( i ) ? ;");
-                        
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields{
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { None } ,");
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & s . strbuf ) )");
+                    tb.add("} ) ; s . curly_close ( i ) ? ; r");
+                }
+                EnumRepr::Internal{tag}=>{
+                    tb.add("let tag = s . find_tag ( i ,").string(tag).add(") ? ;");
+                    tb.add("std :: result :: Result :: Ok ( match tag . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                de_fields(&mut tb, &[], Some(tag), de);
+                                tb.add("Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(1)=>{
+                                tb.add("let mut rest : std :: collections :: HashMap < String , JsonValue > = DeJson :: de_json ( s , i ) ? ;");
+                                tb.add("rest . remove (").string(tag).add(") ;");
+                                tb.add("Self ::").ident(&variant.name).add("( s . de_json_rest ( rest ) ? )");
                             }
-                            else{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>{
+                                de_fields(&mut tb, fields, Some(tag), de);
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
                             }
                         }
                         tb.add("}");
                     }
-                    else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
-                        tb.add("s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant);
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} )");
+                }
+                EnumRepr::Adjacent{tag, content}=>{
+                    tb.add("let tag = s . find_tag ( i ,").string(tag).add(") ? ;");
+                    tb.add("s . curly_open ( i ) ? ;");
+                    tb.add("let mut r = None ;");
+                    tb.add("while let Some ( _ ) = s . next_str ( ) {");
+                    tb.add("match s . strbuf . as_str ( ) {");
+                    tb.string(tag).add("=> { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; } ,");
+                    tb.string(content).add("=> { s . next_colon ( i ) ? ; r = Some ( match tag . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=>");
+                        de_content(&mut tb, variant, de);
                     }
-                    else{
-                        return parser.unexpected();
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} ) ; } ,");
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
+                    tb.add("} ; s . eat_comma_curly ( i ) ? ;");
+                    tb.add("} ; s . curly_close ( i ) ? ;");
+                    // unit variants leave out the content
+                    tb.add("std :: result :: Result :: Ok ( match r { Some ( r ) => r , None => match tag . as_str ( ) {");
+                    for variant in &variants{
+                        if let VariantKind::Unit = variant.kind{
+                            tb.string(&variant.key).add("=> Self ::").ident(&variant.name).add(",");
+                        }
                     }
-                    
-                    tb.add("}");
-                    parser.eat_punct_alone(',');
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_nf (").string(content).add(") )");
+                    tb.add("} } )");
                 }
-                else{
-                    return parser.unexpected()
+                EnumRepr::Untagged=>{
+                    // try the variants in order, rewinding after every miss
+                    for variant in &variants{
+                        tb.add("{ let mut ts = s . clone ( ) ; let mut ti = i . clone ( ) ;");
+                        tb.add("if let Ok ( r ) = ( | s : & mut DeJsonState , i : & mut std :: str :: Chars | -> std :: result :: Result < Self , DeJsonErr > {");
+                        tb.add("std :: result :: Result :: Ok (");
+                        de_content(&mut tb, variant, de);
+                        tb.add(") } ) ( & mut ts , & mut ti ) { * s = ts ; * i = ti ; return std :: result :: Result :: Ok ( r ) } }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( s . err_untagged (").string(&name).add(") )");
                 }
             }
-            tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-            tb.add("} ) ; s . curly_close ( i ) ? ; r } }");
+            tb.add("} }");
+            if lifetimes.is_empty(){
+                de_json_ref_forward(&mut tb, &name, generic, where_clause);
+            }
             return tb.end();
         }
     }
@@ -1,11 +1,92 @@
 use makepad_micro_proc_macro::*;
 use proc_macro::TokenStream;
+use crate::attrs::*;
+
+// writes the fields of a struct, `prefix` is `self .` for structs and empty
+// for enum variants whose fields are bound by reference
+fn ser_fields(tb: &mut TokenBuilder, fields: &[SerdeField], prefix: &str) {
+    let by_ref = if prefix.is_empty() {""} else {"&"};
+    for field in fields {
+        if field.attrs.skip {
+            continue;
+        }
+        if field.attrs.flatten {
+            tb.add("s . flatten ( d ,").add(by_ref).add(prefix).ident(&field.name).add(") ;");
+        } else if field.is_option() {
+            tb.add("if let Some ( t ) =").add(by_ref).add(prefix).ident(&field.name).add("{");
+            tb.add("s . field ( d + 1 ,").string(&field.key()).add(") ;");
+            tb.add("t . ser_ron ( d + 1 , s ) ; s . conl ( ) ; }");
+        } else {
+            tb.add("s . field ( d + 1 ,").string(&field.key()).add(") ;");
+            tb.add(prefix).ident(&field.name).add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
+        }
+    }
+}
+
+fn ser_tuple(tb: &mut TokenBuilder, len: usize) {
+    tb.add("s . out . push (").chr('(').add(") ;");
+    for i in 0..len {
+        tb.ident(&format!("n{}", i)).add(". ser_ron ( d , s ) ;");
+        if i != len - 1 {
+            tb.add("s . out . push_str (").string(", ").add(") ;");
+        }
+    }
+    tb.add("s . out . push (").chr(')').add(") ;");
+}
+
+fn ser_variant_pattern(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    tb.add("Self ::").ident(&variant.name);
+    match &variant.kind {
+        VariantKind::Unit => (),
+        VariantKind::Tuple(len) => {
+            tb.add("(");
+            for i in 0..*len {
+                tb.ident(&format!("n{}", i)).add(",");
+            }
+            tb.add(")");
+        }
+        VariantKind::Named(fields) => {
+            tb.add("{");
+            for field in fields {
+                tb.ident(&field.name);
+                if field.attrs.skip {
+                    tb.add(": _");
+                }
+                tb.add(",");
+            }
+            tb.add("}");
+        }
+    }
+    tb.add("=>");
+}
+
+// the variant data on its own, as used by adjacently tagged and untagged enums
+fn ser_content(tb: &mut TokenBuilder, variant: &SerdeVariant, depth: &str) {
+    if let VariantKind::Unit = variant.kind {
+        tb.add("s . out . push_str (").string("()").add(") ;");
+        return
+    }
+    tb.add("{ let d =").add(depth).add(";");
+    match &variant.kind {
+        VariantKind::Unit => (),
+        VariantKind::Tuple(1) => {
+            tb.add("n0 . ser_ron ( d , s ) ;");
+        }
+        VariantKind::Tuple(len) => ser_tuple(tb, *len),
+        VariantKind::Named(fields) => {
+            tb.add("s . st_pre ( ) ;");
+            ser_fields(tb, fields, "");
+            tb.add("s . st_post ( d ) ;");
+        }
+    }
+    tb.add("}");
+}
 
 pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
 
-    parser.eat_attributes();
+    let main_attrs = parser.eat_attributes();
     parser.eat_ident("pub");
     if parser.eat_ident("struct") {
         if let Some(name) = parser.eat_any_ident() {
@@ -31,26 +112,13 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                 }
                 tb.add("s.out.push(").chr(')').add(");");
             } else if let Some(fields) = parser.eat_all_struct_fields() {
-                tb.add("s.st_pre( ) ;");
-
+                let fields = match serde_fields(fields) {
+                    Ok(fields) => fields,
+                    Err(err) => return err,
+                };
                 // named struct
-                for field in fields {
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                        tb.add("if let Some ( t ) = ")
-                            .add("& self .")
-                            .ident(&field.name)
-                            .add("{");
-                        tb.add("s.field ( d + 1 ,").string(&field.name).add(") ;");
-                        tb.add("t.ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
-                    } else {
-                        tb.add("s . field ( d + 1 ,")
-                            .string(&field.name)
-                            .add(" ) ;");
-                        tb.add("self .")
-                            .ident(&field.name)
-                            .add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
-                    }
-                }
+                tb.add("s.st_pre( ) ;");
+                ser_fields(&mut tb, &fields, "self .");
                 tb.add("s . st_post ( d ) ;");
             } else {
                 return parser.unexpected();
@@ -62,6 +130,14 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
         if let Some(name) = parser.eat_any_ident() {
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("SerRon"));
+            let repr = match EnumRepr::parse(&main_attrs) {
+                Ok(repr) => repr,
+                Err(err) => return err,
+            };
+            let variants = match serde_variants(&mut parser) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
 
             tb.add("impl").stream(generic.clone());
             tb.add("SerRon for")
@@ -71,68 +147,46 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
             tb.add("{ fn ser_ron ( & self , d : usize , s : & mut  SerRonState ) {");
             tb.add("match self {");
 
-            if !parser.open_brace() {
-                return parser.unexpected();
-            }
-
-            while !parser.eat_eot() {
-                // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident() {
-                    if let Some(types) = parser.eat_all_types() {
-                        tb.add("Self ::").ident(&variant).add("(");
-                        for i in 0..types.len() {
-                            tb.ident(&format!("n{}", i)).add(",");
+            for variant in &variants {
+                ser_variant_pattern(&mut tb, variant);
+                tb.add("{");
+                match &repr {
+                    EnumRepr::External => {
+                        tb.add("s . out . push_str (").string(&variant.key).add(") ;");
+                        match &variant.kind {
+                            VariantKind::Unit => (),
+                            VariantKind::Tuple(len) => ser_tuple(&mut tb, *len),
+                            VariantKind::Named(_) => ser_content(&mut tb, variant, "d"),
                         }
-                        tb.add(") => {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr('(').add(") ;");
-
-                        for i in 0..types.len() {
-                            tb.ident(&format!("n{}", i)).add(". ser_ron ( d , s ) ;");
-                            if i != types.len() - 1 {
-                                tb.add("s . out . push_str (").string(", ").add(") ;");
+                    }
+                    EnumRepr::Internal {tag} => {
+                        tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                        tb.add("s . label (").string(&variant.key).add(") ; s . conl ( ) ;");
+                        match &variant.kind {
+                            VariantKind::Unit => (),
+                            VariantKind::Tuple(1) => {
+                                tb.add("s . flatten ( d , n0 ) ;");
                             }
-                        }
-                        tb.add("s . out . push (").chr(')').add(") ;");
-                        tb.add("}");
-                    } else if let Some(fields) = parser.eat_all_struct_fields() {
-                        // named variant
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter() {
-                            tb.ident(&field.name).add(",");
-                        }
-                        tb.add("} => {");
-
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
-                        tb.add("s . st_pre ( ) ;");
-
-                        for field in fields {
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                                tb.add("if ").ident(&field.name).add(". is_some ( ) {");
-                                tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
-                                tb.ident(&field.name)
-                                    .add(" . ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
-                            } else {
-                                tb.add("s . field ( d + 1 ,")
-                                    .string(&field.name)
-                                    .add(" ) ;");
-                                tb.ident(&field.name)
-                                    .add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
+                            VariantKind::Tuple(_) => {
+                                return error("internally tagged enums can't have tuple variants")
                             }
+                            VariantKind::Named(fields) => ser_fields(&mut tb, fields, ""),
                         }
-                        tb.add("s . st_post ( d ) ; }");
-                    } else if parser.is_punct_alone(',') || parser.is_eot() {
-                        // bare variant
-                        tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ; }");
-                    } else {
-                        return parser.unexpected();
+                        tb.add("s . st_post ( d ) ;");
                     }
-                    parser.eat_punct_alone(',');
-                } else {
-                    return parser.unexpected();
+                    EnumRepr::Adjacent {tag, content} => {
+                        tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                        tb.add("s . label (").string(&variant.key).add(") ; s . conl ( ) ;");
+                        if !matches!(variant.kind, VariantKind::Unit) {
+                            tb.add("s . field ( d + 1 ,").string(content).add(") ;");
+                            ser_content(&mut tb, variant, "d + 1");
+                            tb.add("s . conl ( ) ;");
+                        }
+                        tb.add("s . st_post ( d ) ;");
+                    }
+                    EnumRepr::Untagged => ser_content(&mut tb, variant, "d"),
                 }
+                tb.add("}");
             }
             tb.add("}");
             tb.add("} } ;");
@@ -142,10 +196,120 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
     parser.unexpected()
 }
 
+// reads a struct into the field locals, `skip` is a key that belongs to the
+// enclosing enum, like its tag. A struct that is flattened into another one
+// skips the keys of that struct, which it finds in `s.flatten_keys`
+fn de_fields(tb: &mut TokenBuilder, fields: &[SerdeField], skip: Option<&str>) {
+    let flatten = fields.iter().find( | f | f.attrs.flatten);
+    tb.add("let flatten_keys = std :: mem :: take ( & mut s . flatten_keys ) ;");
+    if flatten.is_some() {
+        // the flattened field parses the same struct again afterwards
+        tb.add("let start = ( s . clone ( ) , i . clone ( ) ) ;");
+    }
+    tb.add("s . paren_open ( i ) ? ;");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.add("let mut").ident(&field.local()).add("= None ;");
+        }
+    }
+    tb.add("while let Some ( _ ) = s . next_ident ( ) {");
+    tb.add("match s . identbuf . as_str ( ) {");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.string(&field.key()).add("=> { s . next_colon ( i ) ? ;");
+            tb.ident(&field.local()).add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
+        }
+    }
+    if let Some(skip) = skip {
+        tb.string(skip).add("=> { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; } ,");
+    }
+    if flatten.is_some() {
+        tb.add("_ => { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; }");
+    } else {
+        tb.add("_ => if flatten_keys . iter ( ) . any ( | k | * k == s . identbuf ) {");
+        tb.add("s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; }");
+        tb.add("else { return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) ) }");
+    }
+    tb.add("} ; s . eat_comma_paren ( i ) ? ;");
+    tb.add("} ; s . paren_close ( i ) ? ;");
+    if let Some(field) = flatten {
+        tb.add("let end = ( std :: mem :: replace ( s , start . 0 ) , std :: mem :: replace ( i , start . 1 ) ) ;");
+        tb.add("s . flatten_keys = flatten_keys ;");
+        tb.add("s . flatten_keys . extend_from_slice ( & [");
+        for field in fields {
+            if !field.attrs.skip && !field.attrs.flatten {
+                tb.string(&field.key()).add(",");
+            }
+        }
+        if let Some(skip) = skip {
+            tb.string(skip).add(",");
+        }
+        tb.add("] ) ;");
+        tb.add("let").ident(&field.local()).add("= DeRon :: de_ron ( s , i ) ? ;");
+        tb.add("* s = end . 0 ; * i = end . 1 ;");
+    }
+}
+
+// the struct expression for the locals de_fields collected
+fn de_fields_build(tb: &mut TokenBuilder, fields: &[SerdeField]) {
+    tb.add("{");
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.attrs.flatten {
+            tb.ident(&field.local()).add(",");
+        } else if field.attrs.skip {
+            field.attrs.default_value(tb);
+            tb.add(",");
+        } else {
+            tb.add("if let Some ( t ) =").ident(&field.local()).add("{ t } else {");
+            if !field.attrs.default_value(tb) {
+                if field.is_option() {
+                    tb.add("None");
+                } else {
+                    tb.add("return std :: result :: Result :: Err ( s . err_nf (").string(&field.key()).add(") )");
+                }
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("}");
+}
+
+fn de_tuple(tb: &mut TokenBuilder, variant: &str, len: usize) {
+    tb.add("s . paren_open ( i ) ? ;");
+    tb.add("let r = Self ::").ident(variant).add("(");
+    for _ in 0..len {
+        tb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
+    }
+    tb.add(") ;");
+    tb.add("s . paren_close ( i ) ? ; r");
+}
+
+// parses the variant data on its own, as used by adjacently tagged and
+// untagged enums, evaluates to the variant
+fn de_content(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    tb.add("{");
+    match &variant.kind {
+        VariantKind::Unit => {
+            tb.add("s . paren_open ( i ) ? ; s . paren_close ( i ) ? ; Self ::").ident(&variant.name);
+        }
+        VariantKind::Tuple(1) => {
+            tb.add("Self ::").ident(&variant.name).add("( DeRon :: de_ron ( s , i ) ? )");
+        }
+        VariantKind::Tuple(len) => de_tuple(tb, &variant.name, *len),
+        VariantKind::Named(fields) => {
+            de_fields(tb, fields, None);
+            tb.add("Self ::").ident(&variant.name);
+            de_fields_build(tb, fields);
+        }
+    }
+    tb.add("}");
+}
+
 pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    parser.eat_attributes();
+    let main_attrs = parser.eat_attributes();
     parser.eat_ident("pub");
     if parser.eat_ident("struct") {
         if let Some(name) = parser.eat_any_ident() {
@@ -172,40 +336,14 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . paren_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r ) ");
             } else if let Some(fields) = parser.eat_all_struct_fields() {
-                tb.add("s . paren_open ( i ) ? ;");
-                for field in &fields {
-                    tb.add("let mut")
-                        .ident(&format!("_{}", field.name))
-                        .add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                tb.add("match s . identbuf . as_ref ( ) {");
-                for field in &fields {
-                    tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}", field.name))
-                        .add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
-                }
-                tb.add(
-                    "_ => return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) )",
-                );
-                tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                tb.add("} ; s . paren_close ( i ) ? ;");
-
-                tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields {
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                        tb.add("if let Some ( t ) =")
-                            .ident(&format!("_{}", field.name));
-                        tb.add("{ t } else { None } ,");
-                    } else {
-                        tb.add("if let Some ( t ) =")
-                            .ident(&format!("_{}", field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
-                tb.add("} )");
+                let fields = match serde_fields(fields) {
+                    Ok(fields) => fields,
+                    Err(err) => return err,
+                };
+                de_fields(&mut tb, &fields, None);
+                tb.add("std :: result :: Result :: Ok ( Self");
+                de_fields_build(&mut tb, &fields);
+                tb.add(")");
             } else {
                 return parser.unexpected();
             }
@@ -216,6 +354,14 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
         if let Some(name) = parser.eat_any_ident() {
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("DeRon"));
+            let repr = match EnumRepr::parse(&main_attrs) {
+                Ok(repr) => repr,
+                Err(err) => return err,
+            };
+            let variants = match serde_variants(&mut parser) {
+                Ok(variants) => variants,
+                Err(err) => return err,
+            };
 
             tb.add("impl").stream(generic.clone());
             tb.add("DeRon for")
@@ -224,76 +370,97 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
                 .stream(where_clause);
             tb.add("{ fn de_ron ( s : & mut  DeRonState , i : & mut std :: str :: Chars )");
             tb.add("-> std :: result :: Result < Self , DeRonErr > { ");
-            tb.add("s . ident ( i ) ? ;");
-            tb.add("std :: result :: Result :: Ok ( match s . identbuf . as_ref ( ) {");
 
-            if !parser.open_brace() {
-                return parser.unexpected();
-            }
-            while !parser.eat_eot() {
-                // parse ident
-                parser.eat_attributes();
-                if let Some(variant) = parser.eat_any_ident() {
-                    tb.string(&variant).add("=> {");
-                    if let Some(types) = parser.eat_all_types() {
-                        tb.add("s . paren_open ( i ) ? ;");
-                        tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len() {
-                            tb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
-                        }
-                        tb.add(") ;");
-                        tb.add("s . paren_close ( i ) ? ; r");
-                    } else if let Some(fields) = parser.eat_all_struct_fields() {
-                        // named variant
-                        tb.add("s . paren_open ( i ) ? ;");
-                        for field in &fields {
-                            tb.add("let mut")
-                                .ident(&format!("_{}", field.name))
-                                .add("= None ;");
-                        }
-                        tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                        tb.add("match s . identbuf . as_ref ( ) {");
-                        for field in &fields {
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}", field.name))
-                                .add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
+            match &repr {
+                EnumRepr::External => {
+                    tb.add("s . ident ( i ) ? ;");
+                    tb.add("std :: result :: Result :: Ok ( match s . identbuf . as_str ( ) {");
+                    for variant in &variants {
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind {
+                            VariantKind::Unit => {
+                                tb.add("Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(len) => de_tuple(&mut tb, &variant.name, *len),
+                            VariantKind::Named(fields) => {
+                                de_fields(&mut tb, fields, None);
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
+                            }
                         }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                        tb.add("} ; s . paren_close ( i ) ? ;");
-
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields {
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                                tb.add("if let Some ( t ) =")
-                                    .ident(&format!("_{}", field.name));
-                                tb.add("{ t } else { None } ,");
-                            } else {
-                                tb.add("if let Some ( t ) =")
-                                    .ident(&format!("_{}", field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
+                        tb.add("}");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & s . identbuf ) )");
+                    tb.add("} )");
+                }
+                EnumRepr::Internal {tag} => {
+                    tb.add("let tag = s . find_tag ( i ,").string(tag).add(") ? ;");
+                    tb.add("std :: result :: Result :: Ok ( match tag . as_str ( ) {");
+                    for variant in &variants {
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind {
+                            VariantKind::Unit => {
+                                de_fields(&mut tb, &[], Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(1) => {
+                                tb.add("s . flatten_keys . push (").string(tag).add(") ;");
+                                tb.add("Self ::").ident(&variant.name).add("( DeRon :: de_ron ( s , i ) ? )");
+                            }
+                            VariantKind::Tuple(_) => {
+                                return error("internally tagged enums can't have tuple variants")
+                            }
+                            VariantKind::Named(fields) => {
+                                de_fields(&mut tb, fields, Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields);
                             }
                         }
                         tb.add("}");
-                    } else if parser.is_punct_alone(',') || parser.is_eot() {
-                        // bare variant
-                        tb.add("Self ::").ident(&variant);
-                    } else {
-                        return parser.unexpected();
                     }
-
-                    tb.add("}");
-                    parser.eat_punct_alone(',');
-                } else {
-                    return parser.unexpected();
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} )");
+                }
+                EnumRepr::Adjacent {tag, content} => {
+                    tb.add("let tag = s . find_tag ( i ,").string(tag).add(") ? ;");
+                    tb.add("s . paren_open ( i ) ? ;");
+                    tb.add("let mut r = None ;");
+                    tb.add("while let Some ( _ ) = s . next_ident ( ) {");
+                    tb.add("match s . identbuf . as_str ( ) {");
+                    tb.string(tag).add("=> { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; } ,");
+                    tb.string(content).add("=> { s . next_colon ( i ) ? ; r = Some ( match tag . as_str ( ) {");
+                    for variant in &variants {
+                        tb.string(&variant.key).add("=>");
+                        de_content(&mut tb, variant);
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} ) ; } ,");
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) )");
+                    tb.add("} ; s . eat_comma_paren ( i ) ? ;");
+                    tb.add("} ; s . paren_close ( i ) ? ;");
+                    // unit variants leave out the content
+                    tb.add("std :: result :: Result :: Ok ( match r { Some ( r ) => r , None => match tag . as_str ( ) {");
+                    for variant in &variants {
+                        if let VariantKind::Unit = variant.kind {
+                            tb.string(&variant.key).add("=> Self ::").ident(&variant.name).add(",");
+                        }
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_nf (").string(content).add(") )");
+                    tb.add("} } )");
+                }
+                EnumRepr::Untagged => {
+                    // try the variants in order, rewinding after every miss
+                    for variant in &variants {
+                        tb.add("{ let mut ts = s . clone ( ) ; let mut ti = i . clone ( ) ;");
+                        tb.add("if let Ok ( r ) = ( | s : & mut DeRonState , i : & mut std :: str :: Chars | -> std :: result :: Result < Self , DeRonErr > {");
+                        tb.add("std :: result :: Result :: Ok (");
+                        de_content(&mut tb, variant);
+                        tb.add(") } ) ( & mut ts , & mut ti ) { * s = ts ; * i = ti ; return std :: result :: Result :: Ok ( r ) } }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( s . err_untagged (").string(&name).add(") )");
                 }
             }
-            tb.add(
-                "_ => return std :: result :: Result :: Err ( s . err_enum ( & s . identbuf ) )",
-            );
-            tb.add("} ) } }");
+            tb.add("} }");
             return tb.end();
         }
     }
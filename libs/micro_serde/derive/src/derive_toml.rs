@@ -0,0 +1,472 @@
+use proc_macro::TokenStream;
+use makepad_micro_proc_macro::*;
+use crate::attrs::*;
+
+// TOML has no null, so None fields are left out of tables, and unit
+// variants are written as their name where other formats write null or an
+// empty array. The generated code works on parsed TomlItems, `it` names the
+// local holding the item being read
+
+fn ser_str(tb: &mut TokenBuilder, s: &str) {
+    tb.add("TomlValue :: Str ( String :: from (").string(s).add(") )");
+}
+
+// a block evaluating to a TomlValue::Table of the fields, `prefix` is
+// `self .` for structs and empty for enum variants whose fields are bound by
+// reference. `tag` is the entry internally tagged enums put first
+fn ser_table(tb: &mut TokenBuilder, fields: &[SerdeField], prefix: &str, tag: Option<(&str, &str)>) {
+    let by_ref = if prefix.is_empty() {""} else {"&"};
+    tb.add("{ let mut toml_table = TomlTable :: default ( ) ;");
+    if let Some((tag, key)) = tag {
+        tb.add("toml_table . insert (").string(tag).add(",");
+        ser_str(tb, key);
+        tb.add(") ;");
+    }
+    for field in fields {
+        if field.attrs.skip {
+            continue;
+        }
+        if field.attrs.flatten {
+            tb.add("if let Some ( TomlValue :: Table ( flat ) ) = SerToml :: ser_toml (").add(by_ref).add(prefix).ident(&field.name).add(") {");
+            tb.add("toml_table . entries . extend ( flat . entries ) ; }");
+        }
+        else {
+            tb.add("if let Some ( v ) = SerToml :: ser_toml (").add(by_ref).add(prefix).ident(&field.name).add(") {");
+            tb.add("toml_table . insert (").string(&field.key()).add(", v ) ; }");
+        }
+    }
+    tb.add("TomlValue :: Table ( toml_table ) }");
+}
+
+// a block evaluating to a TomlValue::Array of `items`
+fn ser_array(tb: &mut TokenBuilder, items: &[String]) {
+    tb.add("{ let mut toml_array = Vec :: new ( ) ;");
+    for item in items {
+        tb.add("if let Some ( v ) = SerToml :: ser_toml (").add(item).add(") { toml_array . push ( TomlItem :: from ( v ) ) ; }");
+    }
+    tb.add("TomlValue :: Array ( toml_array ) }");
+}
+
+fn variant_items(len: usize) -> Vec<String> {
+    (0..len).map( | i | format!("n{}", i)).collect()
+}
+
+fn ser_variant_pattern(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    tb.add("Self ::").ident(&variant.name);
+    match &variant.kind {
+        VariantKind::Unit => (),
+        VariantKind::Tuple(len) => {
+            tb.add("(");
+            for i in 0..*len {
+                tb.ident(&format!("n{}", i)).add(",");
+            }
+            tb.add(")");
+        }
+        VariantKind::Named(fields) => {
+            tb.add("{");
+            for field in fields {
+                tb.ident(&field.name);
+                if field.attrs.skip {
+                    tb.add(": _");
+                }
+                tb.add(",");
+            }
+            tb.add("}");
+        }
+    }
+    tb.add("=>");
+}
+
+// the variant data on its own as an Option<TomlValue>, as used by adjacently
+// tagged and untagged enums
+fn ser_content(tb: &mut TokenBuilder, variant: &SerdeVariant) {
+    match &variant.kind {
+        VariantKind::Unit => {
+            tb.add("Some (");
+            ser_str(tb, &variant.key);
+            tb.add(")");
+        }
+        VariantKind::Tuple(1) => {tb.add("SerToml :: ser_toml ( n0 )");}
+        VariantKind::Tuple(len) => {
+            tb.add("Some (");
+            ser_array(tb, &variant_items(*len));
+            tb.add(")");
+        }
+        VariantKind::Named(fields) => {
+            tb.add("Some (");
+            ser_table(tb, fields, "", None);
+            tb.add(")");
+        }
+    }
+}
+
+fn ser_header(tb: &mut TokenBuilder, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>) {
+    tb.add("impl").stream(generic.clone());
+    tb.add("SerToml for").ident(name).stream(generic).stream(where_clause);
+    tb.add("{ fn ser_toml ( & self ) -> std :: option :: Option < TomlValue > {");
+}
+
+pub fn derive_ser_toml_impl(input: TokenStream) -> TokenStream {
+    let mut parser = TokenParser::new(input);
+    let mut tb = TokenBuilder::new();
+
+    let main_attrs = parser.eat_attributes();
+    parser.eat_ident("pub");
+    if parser.eat_ident("struct"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let types = parser.eat_all_types();
+            let where_clause = parser.eat_where_clause(Some("SerToml"));
+
+            ser_header(&mut tb, &name, generic, where_clause);
+            tb.add("Some (");
+            if let Some(types) = types{
+                let items: Vec<String> = (0..types.len()).map( | i | format!("& self . {}", i)).collect();
+                ser_array(&mut tb, &items);
+            }
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                ser_table(&mut tb, &fields, "self .", None);
+            }
+            else{
+                return parser.unexpected()
+            }
+            tb.add(") } } ;");
+            return tb.end();
+        }
+    }
+    else if parser.eat_ident("enum"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let where_clause = parser.eat_where_clause(Some("SerToml"));
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
+
+            ser_header(&mut tb, &name, generic, where_clause);
+            tb.add("match self {");
+            for variant in &variants{
+                ser_variant_pattern(&mut tb, variant);
+                tb.add("{");
+                match &repr{
+                    EnumRepr::External=>{
+                        match &variant.kind{
+                            VariantKind::Unit=>ser_content(&mut tb, variant),
+                            _=>{
+                                tb.add("let mut toml_table = TomlTable :: default ( ) ;");
+                                tb.add("toml_table . insert (").string(&variant.key).add(",");
+                                // tuple variants are always arrays, like in the other formats
+                                if let VariantKind::Tuple(len) = variant.kind{
+                                    ser_array(&mut tb, &variant_items(len));
+                                }
+                                else if let VariantKind::Named(fields) = &variant.kind{
+                                    ser_table(&mut tb, fields, "", None);
+                                }
+                                tb.add(") ; Some ( TomlValue :: Table ( toml_table ) )");
+                            }
+                        }
+                    }
+                    EnumRepr::Internal{tag}=>{
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                tb.add("Some (");
+                                ser_table(&mut tb, &[], "", Some((tag, &variant.key)));
+                                tb.add(")");
+                            }
+                            VariantKind::Tuple(1)=>{
+                                tb.add("let mut toml_table = match SerToml :: ser_toml ( n0 ) { Some ( TomlValue :: Table ( t ) ) => t , _ => TomlTable :: default ( ) } ;");
+                                tb.add("toml_table . entries . insert ( 0 , ( String :: from (").string(tag).add(") , TomlItem :: from (");
+                                ser_str(&mut tb, &variant.key);
+                                tb.add(") ) ) ; Some ( TomlValue :: Table ( toml_table ) )");
+                            }
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>{
+                                tb.add("Some (");
+                                ser_table(&mut tb, fields, "", Some((tag, &variant.key)));
+                                tb.add(")");
+                            }
+                        }
+                    }
+                    EnumRepr::Adjacent{tag, content}=>{
+                        tb.add("let mut toml_table = TomlTable :: default ( ) ;");
+                        tb.add("toml_table . insert (").string(tag).add(",");
+                        ser_str(&mut tb, &variant.key);
+                        tb.add(") ;");
+                        if !matches!(variant.kind, VariantKind::Unit){
+                            tb.add("if let Some ( v ) =");
+                            ser_content(&mut tb, variant);
+                            tb.add("{ toml_table . insert (").string(content).add(", v ) ; }");
+                        }
+                        tb.add("Some ( TomlValue :: Table ( toml_table ) )");
+                    }
+                    EnumRepr::Untagged=>ser_content(&mut tb, variant)
+                }
+                tb.add("}");
+            }
+            tb.add("}");
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    parser.unexpected()
+}
+
+// reads the table in the `toml_table` local into the field locals, `skip`
+// is a key that belongs to the enclosing enum, like its tag. Keys a
+// #[flatten] field takes are collected in a table of their own
+fn de_fields(tb: &mut TokenBuilder, fields: &[SerdeField], skip: Option<&str>) {
+    let flatten = fields.iter().any( | f | f.attrs.flatten);
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.add("let mut").ident(&field.local()).add("= None ;");
+        }
+    }
+    if flatten {
+        tb.add("let mut rest = TomlTable :: default ( ) ;");
+    }
+    tb.add("for ( key , value ) in & toml_table . entries {");
+    tb.add("match key . as_str ( ) {");
+    for field in fields {
+        if !field.attrs.skip && !field.attrs.flatten {
+            tb.string(&field.key()).add("=> {").ident(&field.local()).add("= Some ( DeToml :: de_toml ( value ) ? ) ; } ,");
+        }
+    }
+    if let Some(skip) = skip {
+        tb.string(skip).add("=> { } ,");
+    }
+    if flatten {
+        tb.add("_ => rest . entries . push ( ( key . clone ( ) , value . clone ( ) ) ) ,");
+    }
+    else {
+        tb.add("_ => return std :: result :: Result :: Err ( value . err_exp ( key ) )");
+    }
+    tb.add("} }");
+}
+
+// the struct expression for the locals de_fields collected
+fn de_fields_build(tb: &mut TokenBuilder, fields: &[SerdeField], it: &str) {
+    tb.add("{");
+    for field in fields {
+        tb.ident(&field.name).add(":");
+        if field.attrs.flatten {
+            tb.add("DeToml :: de_toml ( & TomlItem { value : TomlValue :: Table ( rest ) , span :").ident(it).add(". span . clone ( ) } ) ? ,");
+        }
+        else if field.attrs.skip {
+            field.attrs.default_value(tb);
+            tb.add(",");
+        }
+        else {
+            tb.add("if let Some ( t ) =").ident(&field.local()).add("{ t } else {");
+            if !field.attrs.default_value(tb) {
+                if field.is_option() {
+                    tb.add("None");
+                }
+                else {
+                    tb.add("return std :: result :: Result :: Err (").ident(it).add(". err_nf (").string(&field.key()).add(") )");
+                }
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("}");
+}
+
+// a block reading an array of `len` items into `ctor`, which is `Self` or
+// a variant
+fn de_tuple(tb: &mut TokenBuilder, ctor: &str, len: usize, it: &str) {
+    tb.add("{ let toml_array =").ident(it).add(". expect_array ( ) ? ;");
+    tb.add("if toml_array . len ( ) !=").unsuf_usize(len).add("{");
+    tb.add("return std :: result :: Result :: Err (").ident(it).add(". err_msg (").string(&format!("Expected an array of {} items", len)).add(") ) }");
+    tb.add(ctor).add("(");
+    for i in 0..len {
+        tb.add("DeToml :: de_toml ( & toml_array [").unsuf_usize(i).add("] ) ? ,");
+    }
+    tb.add(") }");
+}
+
+// reads a table variant, evaluates to the variant
+fn de_named(tb: &mut TokenBuilder, variant: &SerdeVariant, fields: &[SerdeField], it: &str) {
+    tb.add("{ let toml_table =").ident(it).add(". expect_table ( ) ? ;");
+    de_fields(tb, fields, None);
+    tb.add("Self ::").ident(&variant.name);
+    de_fields_build(tb, fields, it);
+    tb.add("}");
+}
+
+// reads the variant data on its own, as used by adjacently tagged and
+// untagged enums, evaluates to the variant
+fn de_content(tb: &mut TokenBuilder, variant: &SerdeVariant, it: &str) {
+    match &variant.kind {
+        VariantKind::Unit => {
+            tb.add("match").ident(it).add(". value . as_str ( ) { Some (").string(&variant.key).add(") => Self ::").ident(&variant.name).add(",");
+            tb.add("_ => return std :: result :: Result :: Err (").ident(it).add(". err_enum (").string(&variant.key).add(") ) }");
+        }
+        VariantKind::Tuple(1) => {
+            tb.add("Self ::").ident(&variant.name).add("( DeToml :: de_toml (").ident(it).add(") ? )");
+        }
+        VariantKind::Tuple(len) => de_tuple(tb, &format!("Self :: {}", variant.name), *len, it),
+        VariantKind::Named(fields) => de_named(tb, variant, fields, it),
+    }
+}
+
+// the value of the tag of an internally or adjacently tagged enum
+fn de_tag(tb: &mut TokenBuilder, tag: &str) {
+    tb.add("let toml_table = item . expect_table ( ) ? ;");
+    tb.add("let tag = match toml_table . get (").string(tag).add(") { Some ( t ) => t . expect_str ( ) ? ,");
+    tb.add("None => return std :: result :: Result :: Err ( item . err_nf (").string(tag).add(") ) } ;");
+}
+
+fn de_header(tb: &mut TokenBuilder, name: &str, generic: Option<TokenStream>, where_clause: Option<TokenStream>) {
+    tb.add("impl").stream(generic.clone());
+    tb.add("DeToml for").ident(name).stream(generic).stream(where_clause);
+    tb.add("{ fn de_toml ( item : & TomlItem ) -> std :: result :: Result < Self , TomlErr > {");
+}
+
+pub fn derive_de_toml_impl(input: TokenStream) -> TokenStream {
+    let mut parser = TokenParser::new(input);
+    let mut tb = TokenBuilder::new();
+
+    let main_attrs = parser.eat_attributes();
+    parser.eat_ident("pub");
+    if parser.eat_ident("struct"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let types = parser.eat_all_types();
+            let where_clause = parser.eat_where_clause(Some("DeToml"));
+
+            de_header(&mut tb, &name, generic, where_clause);
+            if let Some(types) = types{
+                tb.add("std :: result :: Result :: Ok (");
+                de_tuple(&mut tb, "Self", types.len(), "item");
+                tb.add(")");
+            }
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match serde_fields(fields){
+                    Ok(fields)=>fields,
+                    Err(err)=>return err
+                };
+                tb.add("let toml_table = item . expect_table ( ) ? ;");
+                de_fields(&mut tb, &fields, None);
+                tb.add("std :: result :: Result :: Ok ( Self");
+                de_fields_build(&mut tb, &fields, "item");
+                tb.add(")");
+            }
+            else{
+                return parser.unexpected()
+            }
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    else if parser.eat_ident("enum"){
+        if let Some(name) = parser.eat_any_ident(){
+            let generic = parser.eat_generic();
+            let where_clause = parser.eat_where_clause(Some("DeToml"));
+            let repr = match EnumRepr::parse(&main_attrs){
+                Ok(repr)=>repr,
+                Err(err)=>return err
+            };
+            let variants = match serde_variants(&mut parser){
+                Ok(variants)=>variants,
+                Err(err)=>return err
+            };
+
+            de_header(&mut tb, &name, generic, where_clause);
+            match &repr{
+                EnumRepr::External=>{
+                    // unit variants are written as their name, a table with
+                    // the name as its only key works as well
+                    tb.add("if let Some ( key ) = item . value . as_str ( ) { return match key {");
+                    for variant in &variants{
+                        if let VariantKind::Unit = variant.kind{
+                            tb.string(&variant.key).add("=> std :: result :: Result :: Ok ( Self ::").ident(&variant.name).add(") ,");
+                        }
+                    }
+                    tb.add("_ => std :: result :: Result :: Err ( item . err_enum ( key ) ) } }");
+                    tb.add("let toml_table = item . expect_table ( ) ? ;");
+                    tb.add("if toml_table . entries . len ( ) != 1 { return std :: result :: Result :: Err ( item . err_msg (").string("Expected one enum variant").add(") ) }");
+                    tb.add("let ( key , value ) = & toml_table . entries [ 0 ] ;");
+                    tb.add("std :: result :: Result :: Ok ( match key . as_str ( ) {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=>");
+                        match &variant.kind{
+                            VariantKind::Unit=>{tb.add("Self ::").ident(&variant.name);}
+                            VariantKind::Tuple(len)=>de_tuple(&mut tb, &format!("Self :: {}", variant.name), *len, "value"),
+                            VariantKind::Named(fields)=>de_named(&mut tb, variant, fields, "value"),
+                        }
+                        tb.add(",");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( value . err_enum ( key ) ) } )");
+                }
+                EnumRepr::Internal{tag}=>{
+                    de_tag(&mut tb, tag);
+                    tb.add("std :: result :: Result :: Ok ( match tag {");
+                    for variant in &variants{
+                        tb.string(&variant.key).add("=> {");
+                        match &variant.kind{
+                            VariantKind::Unit=>{
+                                de_fields(&mut tb, &[], Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                            }
+                            VariantKind::Tuple(1)=>{
+                                tb.add("let mut rest = toml_table . clone ( ) ; rest . remove (").string(tag).add(") ;");
+                                tb.add("Self ::").ident(&variant.name).add("( DeToml :: de_toml ( & TomlItem { value : TomlValue :: Table ( rest ) , span : item . span . clone ( ) } ) ? )");
+                            }
+                            VariantKind::Tuple(_)=>return error("internally tagged enums can't have tuple variants"),
+                            VariantKind::Named(fields)=>{
+                                de_fields(&mut tb, fields, Some(tag));
+                                tb.add("Self ::").ident(&variant.name);
+                                de_fields_build(&mut tb, fields, "item");
+                            }
+                        }
+                        tb.add("}");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( item . err_enum ( tag ) ) } )");
+                }
+                EnumRepr::Adjacent{tag, content}=>{
+                    de_tag(&mut tb, tag);
+                    tb.add("let mut content = None ;");
+                    tb.add("for ( key , value ) in & toml_table . entries { match key . as_str ( ) {");
+                    tb.string(tag).add("=> { } ,");
+                    tb.string(content).add("=> content = Some ( value ) ,");
+                    tb.add("_ => return std :: result :: Result :: Err ( value . err_exp ( key ) ) } }");
+                    // unit variants leave out the content
+                    tb.add("std :: result :: Result :: Ok ( match ( tag , content ) {");
+                    for variant in &variants{
+                        if let VariantKind::Unit = variant.kind{
+                            tb.add("(").string(&variant.key).add(", _ ) => Self ::").ident(&variant.name).add(",");
+                        }
+                        else{
+                            tb.add("(").string(&variant.key).add(", Some ( content ) ) =>");
+                            de_content(&mut tb, variant, "content");
+                            tb.add(",");
+                            tb.add("(").string(&variant.key).add(", None ) => return std :: result :: Result :: Err ( item . err_nf (").string(content).add(") ) ,");
+                        }
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( item . err_enum ( tag ) ) } )");
+                }
+                EnumRepr::Untagged=>{
+                    // the first variant that reads without an error wins
+                    for variant in &variants{
+                        tb.add("if let Ok ( r ) = ( | item : & TomlItem | -> std :: result :: Result < Self , TomlErr > {");
+                        tb.add("std :: result :: Result :: Ok (");
+                        de_content(&mut tb, variant, "item");
+                        tb.add(") } ) ( item ) { return std :: result :: Result :: Ok ( r ) }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( item . err_untagged (").string(&name).add(") )");
+                }
+            }
+            tb.add("} } ;");
+            return tb.end();
+        }
+    }
+    parser.unexpected()
+}
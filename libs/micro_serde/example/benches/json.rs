@@ -0,0 +1,128 @@
+use {
+    criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput},
+    makepad_micro_serde::*,
+    std::borrow::Cow,
+};
+
+// shaped after the chat completion payloads the ai chat consumes
+
+#[derive(SerJson, DeJson)]
+struct Message {
+    role: String,
+    content: String,
+    tokens: u32,
+    logprobs: Vec<f64>,
+}
+
+#[derive(SerJson, DeJson)]
+struct Conversation {
+    id: String,
+    model: String,
+    created: u64,
+    messages: Vec<Message>,
+}
+
+// only deserialized
+#[allow(dead_code)]
+#[derive(DeJson)]
+struct MessageRef<'a> {
+    role: &'a str,
+    content: Cow<'a, str>,
+    tokens: u32,
+    logprobs: Vec<f64>,
+}
+
+#[allow(dead_code)]
+#[derive(DeJson)]
+struct ConversationRef<'a> {
+    id: &'a str,
+    model: &'a str,
+    created: u64,
+    messages: Vec<MessageRef<'a>>,
+}
+
+fn conversation(n: usize) -> Conversation {
+    Conversation {
+        id: format!("chatcmpl-{}", n),
+        model: "gpt-4o".to_string(),
+        created: 1700000000 + n as u64,
+        messages: (0..20).map( | i | Message {
+            role: if i % 2 == 0 {"user"} else {"assistant"}.to_string(),
+            // every fourth message has escapes and can't be borrowed
+            content: if i % 4 == 0 {
+                "Here is the code:\n\tfn main() {}\n".repeat(8)
+            }
+            else {
+                "The quick brown fox jumps over the lazy dog. ".repeat(8)
+            },
+            tokens: 80 + i,
+            logprobs: (0..8).map( | j | -0.125 * j as f64).collect(),
+        }).collect(),
+    }
+}
+
+fn payload() -> String {
+    (0..400).map(conversation).collect::<Vec<_>>().serialize_json()
+}
+
+fn ndjson() -> String {
+    (0..400).map( | n | conversation(n).serialize_json() + "\n").collect()
+}
+
+fn deserialize(c: &mut Criterion) {
+    let json = payload();
+    let mut group = c.benchmark_group("deserialize");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("de_json owned", | b | b.iter( || {
+        Vec::<Conversation>::deserialize_json(black_box(&json)).unwrap()
+    }));
+    group.bench_function("de_json_ref borrowed", | b | b.iter( || {
+        Vec::<ConversationRef>::deserialize_json_ref(black_box(&json)).unwrap()
+    }));
+    group.bench_function("de_json JsonValue", | b | b.iter( || {
+        JsonValue::deserialize_json(black_box(&json)).unwrap()
+    }));
+    group.finish();
+}
+
+fn stream(c: &mut Criterion) {
+    let json = payload();
+    let lines = ndjson();
+    let mut group = c.benchmark_group("stream");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("events in 16k chunks", | b | b.iter( || {
+        let mut parser = JsonStreamParser::new();
+        let mut events = 0;
+        for chunk in json.as_bytes().chunks(16384) {
+            parser.feed(black_box(chunk));
+            while let Some(event) = parser.next_event() {
+                event.unwrap();
+                events += 1;
+            }
+        }
+        events
+    }));
+    group.bench_function("ndjson next_de in 16k chunks", | b | b.iter( || {
+        let mut parser = JsonStreamParser::new();
+        let mut out = Vec::new();
+        for chunk in lines.as_bytes().chunks(16384) {
+            parser.feed(black_box(chunk));
+            while let Some(value) = parser.next_de::<Conversation>() {
+                out.push(value.unwrap());
+            }
+        }
+        out
+    }));
+    // what the ai chat does now, wait for all of it and split
+    group.bench_function("ndjson buffered lines", | b | b.iter( || {
+        let mut buf = Vec::new();
+        for chunk in lines.as_bytes().chunks(16384) {
+            buf.extend_from_slice(black_box(chunk));
+        }
+        String::from_utf8(buf).unwrap().lines().map( | line | Conversation::deserialize_json(line).unwrap()).collect::<Vec<_>>()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, deserialize, stream);
+criterion_main!(benches);
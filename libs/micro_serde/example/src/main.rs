@@ -1,8 +1,12 @@
 // lets make tinyserde dep free too!
 
 use makepad_micro_serde::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
-#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, PartialEq)]
+mod vectors;
+
+#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq)]
 struct MyStruct<T> where T: Clone {
     pub a: T,
     b: u32,
@@ -17,7 +21,7 @@ struct MyStruct<T> where T: Clone {
     k: [u32;2]
 } 
 
-#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, PartialEq)]
+#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq)]
 enum MyEnum<T> where T: Clone {
     One,
     Two(T, u32),
@@ -25,6 +29,395 @@ enum MyEnum<T> where T: Clone {
     Four {z: Option<u32>, w: T},
 }
 
+#[derive(SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq, Debug)]
+struct Attributes {
+    #[rename = "userName"]
+    user_name: String,
+    #[default]
+    count: u32,
+    #[default = 0.5]
+    scale: f64,
+    #[skip]
+    cache: Vec<u32>,
+    #[flatten]
+    extra: Extra,
+    shape: Shape,
+    value: Value,
+    event: Event,
+}
+
+#[derive(SerBin, DeBin, SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq, Debug)]
+struct Extra {
+    id: u64,
+    #[rename = "type"]
+    kind: Option<String>,
+}
+
+#[derive(SerBin, DeBin, PartialEq, Debug)]
+struct Cached {
+    value: u32,
+    #[skip]
+    cache: Vec<u32>,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq, Debug)]
+#[tag = "type"]
+enum Shape {
+    Circle {radius: f64},
+    #[rename = "rect"]
+    Rect {w: f64, h: f64},
+    Nested(Extra),
+    Empty,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq, Debug)]
+#[tag = "t"]
+#[content = "c"]
+enum Event {
+    Click(u32, u32),
+    Key {code: u32},
+    Focus,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerCbor, DeCbor, SerMsgPack, DeMsgPack, SerToml, DeToml, PartialEq, Debug)]
+#[untagged]
+enum Value {
+    Number(f64),
+    Text(String),
+    List(Vec<Value>),
+}
+
+fn attributes() {
+    let x = Attributes {
+        user_name: "makepad".to_string(),
+        count: 3,
+        scale: 2.0,
+        cache: vec![1, 2],
+        extra: Extra {id: 7, kind: Some("x".to_string())},
+        shape: Shape::Rect {w: 1.0, h: 2.0},
+        value: Value::List(vec![Value::Number(1.0), Value::Text("a".to_string())]),
+        event: Event::Click(1, 2),
+    };
+    let cleared = Attributes {cache: vec![], ..x};
+
+    let json = cleared.serialize_json();
+    println!("JSON Output {}", json);
+    let y: Attributes = DeJson::deserialize_json(&json).unwrap();
+    assert_eq!(y, cleared);
+
+    let ron = cleared.serialize_ron();
+    println!("RON Output {}", ron);
+    let y: Attributes = DeRon::deserialize_ron(&ron).unwrap();
+    assert_eq!(y, cleared);
+
+    let y: Attributes = DeCbor::deserialize_cbor(&cleared.serialize_cbor()).unwrap();
+    assert_eq!(y, cleared);
+
+    let y: Attributes = DeMsgPack::deserialize_msgpack(&cleared.serialize_msgpack()).unwrap();
+    assert_eq!(y, cleared);
+
+    let y: Attributes = DeToml::deserialize_toml(&cleared.serialize_toml()).unwrap();
+    assert_eq!(y, cleared);
+
+    let y: Cached = DeBin::deserialize_bin(&Cached {value: 1, cache: vec![2]}.serialize_bin()).unwrap();
+    assert_eq!(y, Cached {value: 1, cache: vec![]});
+
+    // defaults for missing keys
+    let y: Attributes = DeJson::deserialize_json(r#"{"userName":"a","id":1,"shape":{"type":"Empty"},"value":"v","event":{"t":"Focus"}}"#).unwrap();
+    assert_eq!((y.count, y.scale, y.extra.kind), (0, 0.5, None));
+    assert_eq!((y.shape, y.value, y.event), (Shape::Empty, Value::Text("v".to_string()), Event::Focus));
+
+    // tags don't have to come first
+    let y: Shape = DeJson::deserialize_json(r#"{"radius":2,"type":"Circle"}"#).unwrap();
+    assert_eq!(y, Shape::Circle {radius: 2.0});
+    let y: Shape = DeJson::deserialize_json(r#"{"id":3,"type":"Nested"}"#).unwrap();
+    assert_eq!(y, Shape::Nested(Extra {id: 3, kind: None}));
+    let y: Event = DeJson::deserialize_json(r#"{"c":{"code":4},"t":"Key"}"#).unwrap();
+    assert_eq!(y, Event::Key {code: 4});
+    let y: Shape = DeRon::deserialize_ron("(w: 1, type: rect, h: 2)").unwrap();
+    assert_eq!(y, Shape::Rect {w: 1.0, h: 2.0});
+
+    for (json, err) in [
+        (r#"{"radius":1}"#, "Key not found type"),
+        (r#"{"type":"Square"}"#, "Enum not defined Square"),
+        (r#"{"type":"Circle","radius":1,"side":2}"#, "Unexpected key side"),
+    ] {
+        assert_eq!(Shape::deserialize_json(json).unwrap_err().msg, err);
+    }
+    assert_eq!(Value::deserialize_json("true").unwrap_err().msg, "Data did not match any variant of untagged enum Value");
+    assert_eq!(Extra::deserialize_json(r#"{"kind":"x","id":1}"#).unwrap_err().msg, "Unexpected key kind");
+    println!("Attribute roundtrips ok");
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, PartialEq, Debug)]
+struct Underscored {
+    _type: String,
+    _id: u32,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, PartialEq, Debug)]
+enum UnderscoredEnum {
+    Item {_type: String},
+}
+
+fn underscored() {
+    // json struct fields keep the underscore, reading takes both spellings
+    let x = Underscored {_type: "a".to_string(), _id: 1};
+    assert_eq!(x.serialize_json(), r#"{"_type":"a","_id":1}"#);
+    assert_eq!(Underscored::deserialize_json(&x.serialize_json()).unwrap(), x);
+    assert_eq!(Underscored::deserialize_json(r#"{"type":"a","id":1}"#).unwrap(), x);
+    assert_eq!(Underscored::deserialize_json(r#"{"type":"a"}"#).unwrap_err().msg, "Key not found id");
+
+    // json variant fields drop it
+    let e = UnderscoredEnum::Item {_type: "b".to_string()};
+    assert_eq!(e.serialize_json(), r#"{"Item":{"type":"b"}}"#);
+    assert_eq!(UnderscoredEnum::deserialize_json(&e.serialize_json()).unwrap(), e);
+
+    // ron uses the field names as they are
+    let ron = x.serialize_ron();
+    assert!(ron.contains("_type:") && ron.contains("_id:"), "{}", ron);
+    assert_eq!(Underscored::deserialize_ron(&ron).unwrap(), x);
+    assert!(Underscored::deserialize_ron("(type:\"a\", id:1)").is_err());
+    let ron = e.serialize_ron();
+    assert!(ron.contains("_type:"), "{}", ron);
+    assert_eq!(UnderscoredEnum::deserialize_ron(&ron).unwrap(), e);
+}
+
+#[derive(DeJson, Debug, PartialEq)]
+struct Borrowed<'a> {
+    name: &'a str,
+    text: Cow<'a, str>,
+    tags: Vec<&'a str>,
+    extra: Extra,
+    part: Option<Part<'a>>,
+}
+
+#[derive(DeJson, Debug, PartialEq)]
+#[tag = "type"]
+enum Part<'a> {
+    Text {text: &'a str},
+    Image {url: Cow<'a, str>},
+}
+
+fn borrowed() {
+    let json = r#"{"name":"a","text":"b\nc","tags":["x","y"],"extra":{"id":1},"part":{"type":"Text","text":"t"}}"#;
+    let y = Borrowed::deserialize_json_ref(json).unwrap();
+    assert_eq!((y.name, y.text.as_ref(), &y.tags[..]), ("a", "b\nc", &["x", "y"][..]));
+    assert!(matches!(y.text, Cow::Owned(_)));
+    assert_eq!(y.part, Some(Part::Text {text: "t"}));
+    // the borrowed strings really point into the input
+    assert!(json.as_bytes().as_ptr_range().contains(&y.name.as_ptr()));
+
+    let y = Borrowed::deserialize_json_ref(r#"{"name":"ü","text":"plain","tags":[],"extra":{"id":1}}"#).unwrap();
+    assert_eq!(y.name, "ü");
+    assert!(matches!(y.text, Cow::Borrowed("plain")));
+    let err = Borrowed::deserialize_json_ref(r#"{"name":"\"q\"","text":"","tags":[],"extra":{"id":1}}"#).unwrap_err();
+    assert_eq!(err.msg, "Can't borrow a string with escapes, use Cow<str>");
+
+    let y: Vec<f64> = DeJson::deserialize_json("[1e3,-2.5E-2,0.5e+1]").unwrap();
+    assert_eq!(y, vec![1000.0, -0.025, 5.0]);
+    println!("Borrowed deserialize ok");
+}
+
+fn streaming() {
+    let json = "{\"a\":[1,-2,3.5e1,true,null],\"s\":\"h\\u00e9\\ud83d\\ude00 ü\",\"o\":{}} 42";
+    let mut parser = JsonStreamParser::new();
+    let mut events = Vec::new();
+    // a byte at a time splits every token and the multi byte characters
+    for byte in json.as_bytes() {
+        parser.feed(&[*byte]);
+        while let Some(event) = parser.next_event() {
+            events.push(format!("{:?}", event.unwrap()));
+        }
+    }
+    // the trailing number only ends with the input
+    assert_eq!(events.len(), 15);
+    parser.finish();
+    events.push(format!("{:?}", parser.next_event().unwrap().unwrap()));
+    assert!(parser.is_done());
+    assert_eq!(events.join(" "), r#"ObjectStart Key("a") ArrayStart U64(1) I64(-2) F64(35.0) Bool(true) Null ArrayEnd Key("s") String("hé😀 ü") Key("o") ObjectStart ObjectEnd ObjectEnd U64(42)"#);
+
+    // a long string picks up where the last chunk left off, even after an
+    // escape that was cut in half
+    let mut parser = JsonStreamParser::new();
+    parser.feed(b"[\"a\\");
+    assert_eq!(parser.next_event().unwrap().unwrap(), JsonEvent::ArrayStart);
+    assert!(parser.next_event().is_none());
+    for chunk in [&b"\"b"[..], b"c".repeat(10000).as_slice(), b"\\n", b"d"] {
+        parser.feed(chunk);
+        assert!(parser.next_event().is_none());
+    }
+    parser.feed(b"\", 12");
+    let expected = format!("a\"b{}\nd", "c".repeat(10000));
+    assert_eq!(parser.next_event().unwrap().unwrap(), JsonEvent::String(expected.into()));
+    assert!(parser.next_event().is_none());
+    parser.feed(b"34]");
+    assert_eq!(parser.next_event().unwrap().unwrap(), JsonEvent::U64(1234));
+
+    let lines = "{\"id\":1}\n{\"id\":2,\"type\":\"k\"}\n{\"id\":3}\n";
+    let mut parser = JsonStreamParser::new();
+    let mut out = Vec::new();
+    for chunk in lines.as_bytes().chunks(5) {
+        parser.feed(chunk);
+        while let Some(value) = parser.next_de::<Extra>() {
+            out.push(value.unwrap());
+        }
+    }
+    assert_eq!(out, vec![Extra {id: 1, kind: None}, Extra {id: 2, kind: Some("k".to_string())}, Extra {id: 3, kind: None}]);
+
+    // errors point into the stream, not into the value
+    let mut parser = JsonStreamParser::new();
+    parser.feed(b"{\"id\":1}\n  {\"id\":\"x\"}\n");
+    assert!(parser.next_de::<Extra>().unwrap().is_ok());
+    let err = parser.next_de::<Extra>().unwrap().unwrap_err();
+    assert_eq!((err.msg.as_str(), err.line), ("Unexpected token Str expected unsigned integer ", 1));
+
+    let mut parser = JsonStreamParser::new();
+    parser.feed(br#"{"a" 1}"#);
+    assert_eq!(parser.next_event().unwrap().unwrap(), JsonEvent::ObjectStart);
+    assert_eq!(parser.next_event().unwrap().unwrap(), JsonEvent::Key("a".into()));
+    assert_eq!(parser.next_event().unwrap().unwrap_err().msg, "Unexpected token, expected :");
+    assert!(parser.next_event().is_none());
+    println!("Streaming parse ok");
+}
+
+fn binary_formats() {
+    let hex = | d: Vec<u8> | d.iter().map( | b | format!("{:02x}", b)).collect::<String>();
+    let unhex = | s: &str | (0..s.len()).step_by(2).map( | i | u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect::<Vec<u8>>();
+
+    // the same attributes as json, in maps with text keys
+    let extra = Extra {id: 7, kind: Some("x".to_string())};
+    assert_eq!(hex(extra.serialize_cbor()), "a26269640764747970656178");
+    assert_eq!(hex(extra.serialize_msgpack()), "82a2696407a474797065a178");
+    assert_eq!(hex(Extra {id: 7, kind: None}.serialize_cbor()), "a162696407");
+    let rect = Shape::Rect {w: 1.0, h: 2.0};
+    assert_eq!(hex(rect.serialize_cbor()), "a3647479706564726563746177f93c006168f94000");
+    assert_eq!(hex(Event::Focus.serialize_msgpack()), "81a174a5466f637573");
+    assert_eq!(hex(Event::Click(1, 2).serialize_cbor()), "a2617465436c69636b6163820102");
+
+    for v in [Shape::Circle {radius: 0.5}, rect, Shape::Nested(Extra {id: 3, kind: None}), Shape::Empty] {
+        assert_eq!(Shape::deserialize_cbor(&v.serialize_cbor()).unwrap(), v);
+        assert_eq!(Shape::deserialize_msgpack(&v.serialize_msgpack()).unwrap(), v);
+    }
+    for v in [Event::Click(1, 2), Event::Key {code: 4}, Event::Focus] {
+        assert_eq!(Event::deserialize_cbor(&v.serialize_cbor()).unwrap(), v);
+        assert_eq!(Event::deserialize_msgpack(&v.serialize_msgpack()).unwrap(), v);
+    }
+    let v = Value::List(vec![Value::Number(1.5), Value::Text("a".to_string()), Value::List(vec![])]);
+    assert_eq!(Value::deserialize_cbor(&v.serialize_cbor()).unwrap(), v);
+    assert_eq!(Value::deserialize_msgpack(&v.serialize_msgpack()).unwrap(), v);
+
+    // other encoders write indefinite lengths and put the tag anywhere
+    let y = Shape::deserialize_cbor(&unhex("bf6177f93c006474797065647265637461681802ff")).unwrap();
+    assert_eq!(y, Shape::Rect {w: 1.0, h: 2.0});
+    let y = Shape::deserialize_msgpack(&unhex("82a26964cc03a474797065a64e6573746564")).unwrap();
+    assert_eq!(y, Shape::Nested(Extra {id: 3, kind: None}));
+
+    for (cbor, err) in [
+        ("a166726164697573f93c00", "Key not found type"),
+        ("a1647479706566537175617265", "Enum not defined Square"),
+        ("a3647479706566436972636c656672616469757301647369646502", "Unexpected key side"),
+    ] {
+        assert_eq!(Shape::deserialize_cbor(&unhex(cbor)).unwrap_err().msg, err);
+    }
+    assert_eq!(Value::deserialize_cbor(&unhex("f5")).unwrap_err().msg, "Data did not match any variant of untagged enum Value");
+    assert_eq!(Extra::deserialize_msgpack(&unhex("82a46b696e64a178a2696401")).unwrap_err().msg, "Unexpected key kind");
+    println!("CBOR and MsgPack roundtrips ok");
+}
+
+#[derive(SerToml, DeToml, PartialEq, Debug)]
+struct Manifest {
+    package: Package,
+    #[default]
+    dependencies: HashMap<String, Dependency>,
+    #[rename = "bin"]
+    #[default]
+    bins: Vec<Bin>,
+}
+
+#[derive(SerToml, DeToml, PartialEq, Debug)]
+struct Package {
+    name: String,
+    version: String,
+    #[default]
+    authors: Vec<String>,
+    metadata: Option<TomlTable>,
+}
+
+#[derive(SerToml, DeToml, PartialEq, Debug)]
+#[untagged]
+enum Dependency {
+    Version(String),
+    Detailed {version: Option<String>, path: Option<String>, #[default] features: Vec<String>},
+}
+
+#[derive(SerToml, DeToml, PartialEq, Debug)]
+struct Bin {
+    name: String,
+    path: Option<String>,
+}
+
+fn toml_format() {
+    let manifest = r#"# a manifest
+[package]
+name = "app"
+version = "0.1.0"
+authors = ["Makepad <info@makepad.nl>"]
+metadata.makepad-check-platform = "desktop"
+
+[dependencies]
+makepad-widgets = { path = "../widgets", version = "1.0.0" }
+log = "0.4"
+
+[[bin]]
+name = "app"
+
+[[bin]]
+name = "tool"
+path = "src/tool.rs"
+"#;
+    let m = Manifest::deserialize_toml(manifest).unwrap();
+    assert_eq!(m.package.version, "0.1.0");
+    assert_eq!(m.dependencies["log"], Dependency::Version("0.4".to_string()));
+    assert_eq!(m.dependencies["makepad-widgets"], Dependency::Detailed {version: Some("1.0.0".to_string()), path: Some("../widgets".to_string()), features: vec![]});
+    assert_eq!(m.bins[1], Bin {name: "tool".to_string(), path: Some("src/tool.rs".to_string())});
+    let metadata = m.package.metadata.as_ref().unwrap();
+    assert_eq!(metadata.get("makepad-check-platform").map( | i | &i.value), Some(&TomlValue::Str("desktop".to_string())));
+    assert_eq!(Manifest::deserialize_toml(&m.serialize_toml()).unwrap(), m);
+
+    let toml = Extra {id: 7, kind: Some("x".to_string())}.serialize_toml();
+    assert_eq!(toml, "id = 7\ntype = \"x\"\n");
+    let toml = Shape::Rect {w: 1.0, h: 2.0}.serialize_toml();
+    assert_eq!(toml, "type = \"rect\"\nw = 1.0\nh = 2.0\n");
+    assert_eq!(Event::Focus.serialize_toml(), "t = \"Focus\"\n");
+    assert_eq!(Event::Click(1, 2).serialize_toml(), "t = \"Click\"\nc = [1, 2]\n");
+    let x: MyEnum<u32> = MyEnum::Two(1, 2);
+    assert_eq!(x.serialize_toml(), "Two = [1, 2]\n");
+    assert_eq!(MyEnum::<u32>::One.serialize_toml(), "\"One\"");
+    // unit variants can be a table with the name as key as well
+    let y: MyStruct<u32> = DeToml::deserialize_toml("a = 1\nb = 2\nj = \"\"\nk = [1, 2]\ne = \"One\"\nf = { One = [] }\ng = { Three = { x = 1, y = 2 } }\nh = \"One\"\ni = \"One\"").unwrap();
+    assert!(y.f == MyEnum::One && y.g == MyEnum::Three {x: 1, y: 2});
+
+    for (toml, err) in [
+        ("radius = 1.0", "Key not found type, line:1 col:1"),
+        ("type = \"Square\"", "Enum not defined Square, line:1 col:1"),
+        ("type = \"Circle\"\nradius = 1.0\nside = 2", "Unexpected key side, line:3 col:8"),
+        ("type = \"Circle\"\nradius = \"big\"", "Expected float got string, line:2 col:10"),
+        ("type = \"Circle\"\nradius = ", "Expected a value, line:2 col:10"),
+    ] {
+        let e = Shape::deserialize_toml(toml).unwrap_err();
+        assert_eq!(format!("{}, line:{} col:{}", e.msg, e.line, e.col), err);
+    }
+    assert_eq!(Extra::deserialize_toml("id = -1").unwrap_err().msg, "Value out of range for u64");
+
+    // editing keeps comments and layout
+    let mut doc = TomlDocument::parse(manifest).unwrap();
+    doc.set("package.version", TomlValue::Str("0.2.0".to_string())).unwrap();
+    doc.set("dependencies.log", TomlValue::Str("0.5".to_string())).unwrap();
+    assert_eq!(doc.as_str(), manifest.replace("0.1.0", "0.2.0").replace("0.4", "0.5"));
+    println!("TOML roundtrips ok");
+}
+
 fn main() {
     //let a = MyStruct{step1:1,step2:None};
     //let x = MyStruct2(1,2);
@@ -55,4 +448,28 @@ fn main() {
     println!("RON Output {}", ron);
     let y:MyStruct<usize> = DeRon::deserialize_ron(&ron).unwrap();
     println!("RON roundtrip equality {}", x == y);
+    
+    let cbor = x.serialize_cbor();
+    println!("CBOR len: {}", cbor.len());
+    let y:MyStruct<usize> = DeCbor::deserialize_cbor(&cbor).unwrap();
+    println!("CBOR roundtrip equality {}", x == y);
+
+    let msgpack = x.serialize_msgpack();
+    println!("MsgPack len: {}", msgpack.len());
+    let y:MyStruct<usize> = DeMsgPack::deserialize_msgpack(&msgpack).unwrap();
+    println!("MsgPack roundtrip equality {}", x == y);
+
+    let toml = x.serialize_toml();
+    println!("TOML Output {}", toml);
+    let y:MyStruct<usize> = DeToml::deserialize_toml(&toml).unwrap();
+    println!("TOML roundtrip equality {}", x == y);
+
+    attributes();
+    underscored();
+    borrowed();
+    streaming();
+    binary_formats();
+    toml_format();
+    vectors::cbor_vectors();
+    vectors::msgpack_vectors();
 }
@@ -0,0 +1,259 @@
+// the CBOR examples of RFC 8949 appendix A and the boundaries of every
+// MessagePack format from the msgpack spec
+
+use makepad_micro_serde::*;
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len()).step_by(2).map( | i | u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+}
+
+pub fn cbor_vectors() {
+    use CborValue::*;
+    let text = | s: &str | Text(s.to_string());
+    let one_to_25 = || Array((1..=25).map(Unsigned).collect());
+    let a_b = || Map(vec![(text("a"), Unsigned(1)), (text("b"), Array(vec![Unsigned(2), Unsigned(3)]))]);
+
+    // encoded exactly like this, the preferred serialization
+    let preferred = [
+        ("00", Unsigned(0)),
+        ("01", Unsigned(1)),
+        ("0a", Unsigned(10)),
+        ("17", Unsigned(23)),
+        ("1818", Unsigned(24)),
+        ("1819", Unsigned(25)),
+        ("1864", Unsigned(100)),
+        ("1903e8", Unsigned(1000)),
+        ("1a000f4240", Unsigned(1000000)),
+        ("1b000000e8d4a51000", Unsigned(1000000000000)),
+        ("1bffffffffffffffff", Unsigned(18446744073709551615)),
+        ("c249010000000000000000", Tag(2, Box::new(Bytes(hex("010000000000000000"))))),
+        ("3bffffffffffffffff", Negative(18446744073709551615)),
+        ("c349010000000000000000", Tag(3, Box::new(Bytes(hex("010000000000000000"))))),
+        ("20", Negative(0)),
+        ("29", Negative(9)),
+        ("3863", Negative(99)),
+        ("3903e7", Negative(999)),
+        ("f90000", Float(0.0)),
+        ("f93c00", Float(1.0)),
+        ("fb3ff199999999999a", Float(1.1)),
+        ("f93e00", Float(1.5)),
+        ("f97bff", Float(65504.0)),
+        ("fa47c35000", Float(100000.0)),
+        ("fa7f7fffff", Float(3.4028234663852886e+38)),
+        ("fb7e37e43c8800759c", Float(1.0e+300)),
+        ("f90001", Float(5.960464477539063e-8)),
+        ("f90400", Float(0.00006103515625)),
+        ("f9c400", Float(-4.0)),
+        ("fbc010666666666666", Float(-4.1)),
+        ("f97c00", Float(f64::INFINITY)),
+        ("f9fc00", Float(f64::NEG_INFINITY)),
+        ("f4", Bool(false)),
+        ("f5", Bool(true)),
+        ("f6", Null),
+        ("f7", Undefined),
+        ("f0", Simple(16)),
+        ("f8ff", Simple(255)),
+        ("c074323031332d30332d32315432303a30343a30305a", Tag(0, Box::new(text("2013-03-21T20:04:00Z")))),
+        ("c11a514b67b0", Tag(1, Box::new(Unsigned(1363896240)))),
+        ("c1fb41d452d9ec200000", Tag(1, Box::new(Float(1363896240.5)))),
+        ("d74401020304", Tag(23, Box::new(Bytes(vec![1, 2, 3, 4])))),
+        ("d818456449455446", Tag(24, Box::new(Bytes(hex("6449455446"))))),
+        ("d82076687474703a2f2f7777772e6578616d706c652e636f6d", Tag(32, Box::new(text("http://www.example.com")))),
+        ("40", Bytes(vec![])),
+        ("4401020304", Bytes(vec![1, 2, 3, 4])),
+        ("60", text("")),
+        ("6161", text("a")),
+        ("6449455446", text("IETF")),
+        ("62225c", text("\"\\")),
+        ("62c3bc", text("\u{00fc}")),
+        ("63e6b0b4", text("\u{6c34}")),
+        ("64f0908591", text("\u{10151}")),
+        ("80", Array(vec![])),
+        ("83010203", Array(vec![Unsigned(1), Unsigned(2), Unsigned(3)])),
+        ("8301820203820405", Array(vec![Unsigned(1), Array(vec![Unsigned(2), Unsigned(3)]), Array(vec![Unsigned(4), Unsigned(5)])])),
+        ("98190102030405060708090a0b0c0d0e0f101112131415161718181819", one_to_25()),
+        ("a0", Map(vec![])),
+        ("a201020304", Map(vec![(Unsigned(1), Unsigned(2)), (Unsigned(3), Unsigned(4))])),
+        ("a26161016162820203", a_b()),
+        ("826161a161626163", Array(vec![text("a"), Map(vec![(text("b"), text("c"))])])),
+        ("a56161614161626142616361436164614461656145", Map(["a", "b", "c", "d", "e"].iter().map( | k | (text(k), text(&k.to_uppercase()))).collect())),
+    ];
+    for (h, v) in &preferred {
+        let d = hex(h);
+        assert_eq!(&CborValue::deserialize_cbor(&d).unwrap(), v, "decoding {}", h);
+        assert_eq!(v.serialize_cbor(), d, "encoding {}", h);
+    }
+    // -0.0 equals 0.0 and NaN nothing, so compare the bits
+    assert_eq!(Float(-0.0).serialize_cbor(), hex("f98000"));
+    assert_eq!(Float(f64::NAN).serialize_cbor(), hex("f97e00"));
+    for h in ["f97e00", "fa7fc00000", "fb7ff8000000000000"] {
+        assert!(matches!(CborValue::deserialize_cbor(&hex(h)).unwrap(), Float(v) if v.is_nan()));
+    }
+
+    // other encodings of the same values, wider floats and indefinite lengths
+    let decodes = [
+        ("fa7f800000", Float(f64::INFINITY)),
+        ("faff800000", Float(f64::NEG_INFINITY)),
+        ("fb7ff0000000000000", Float(f64::INFINITY)),
+        ("fbfff0000000000000", Float(f64::NEG_INFINITY)),
+        ("1800", Unsigned(0)),
+        ("1b0000000000000001", Unsigned(1)),
+        ("5f42010243030405ff", Bytes(vec![1, 2, 3, 4, 5])),
+        ("7f657374726561646d696e67ff", text("streaming")),
+        ("9fff", Array(vec![])),
+        ("9f018202039f0405ffff", Array(vec![Unsigned(1), Array(vec![Unsigned(2), Unsigned(3)]), Array(vec![Unsigned(4), Unsigned(5)])])),
+        ("9f01820203820405ff", Array(vec![Unsigned(1), Array(vec![Unsigned(2), Unsigned(3)]), Array(vec![Unsigned(4), Unsigned(5)])])),
+        ("83018202039f0405ff", Array(vec![Unsigned(1), Array(vec![Unsigned(2), Unsigned(3)]), Array(vec![Unsigned(4), Unsigned(5)])])),
+        ("83019f0203ff820405", Array(vec![Unsigned(1), Array(vec![Unsigned(2), Unsigned(3)]), Array(vec![Unsigned(4), Unsigned(5)])])),
+        ("9f0102030405060708090a0b0c0d0e0f101112131415161718181819ff", one_to_25()),
+        ("bf61610161629f0203ffff", a_b()),
+        ("826161bf61626163ff", Array(vec![text("a"), Map(vec![(text("b"), text("c"))])])),
+        ("bf6346756ef563416d7421ff", Map(vec![(text("Fun"), Bool(true)), (text("Amt"), Negative(1))])),
+    ];
+    for (h, v) in &decodes {
+        assert_eq!(&CborValue::deserialize_cbor(&hex(h)).unwrap(), v, "decoding {}", h);
+    }
+
+    // not well formed, RFC 8949 appendix F
+    for h in [
+        "18", "1901", "62c3", "5f4100", "1c", "5c", "ff", "9f", "9f0102", "bf6161", "f818", "5f6161ff", "7f4100ff", "7f7f6161ffff", "6180", "a16161", "0001",
+    ] {
+        assert!(CborValue::deserialize_cbor(&hex(h)).is_err(), "should fail {}", h);
+    }
+
+    // the typed reads check ranges and look through tags
+    assert_eq!(u64::deserialize_cbor(&hex("1bffffffffffffffff")).unwrap(), u64::MAX);
+    assert_eq!(i64::deserialize_cbor(&hex("3b7fffffffffffffff")).unwrap(), i64::MIN);
+    assert_eq!(i64::deserialize_cbor(&hex("3b8000000000000000")).unwrap_err().msg, "Value out of range -9223372036854775809 for i64");
+    assert_eq!(u8::deserialize_cbor(&hex("190100")).unwrap_err().msg, "Value out of range 256 for u8");
+    assert_eq!(String::deserialize_cbor(&hex("c074323031332d30332d32315432303a30343a30305a")).unwrap(), "2013-03-21T20:04:00Z");
+    assert_eq!(f32::deserialize_cbor(&hex("f93e00")).unwrap(), 1.5);
+    assert_eq!(Vec::<u32>::deserialize_cbor(&hex("9f018202")).unwrap_err().msg, "Expected integer got major type 4 (0x82)");
+    for v in [0i64, 23, 24, 255, 256, 65535, 65536, -1, -24, -25, -256, -257, i64::MIN, i64::MAX] {
+        assert_eq!(i64::deserialize_cbor(&v.serialize_cbor()).unwrap(), v);
+    }
+    for v in [0.1f64, -0.5, 1e-10, 65504.0, 65505.0, f64::MAX, f64::MIN_POSITIVE, 5e-324] {
+        assert_eq!(f64::deserialize_cbor(&v.serialize_cbor()).unwrap(), v);
+    }
+    println!("CBOR vectors ok");
+}
+
+pub fn msgpack_vectors() {
+    use MsgPackValue::*;
+    let s = | n: usize | Str("a".repeat(n));
+    let b = | n: usize | Bin(vec![1; n]);
+    let a = | n: usize | Array(vec![Nil; n]);
+    let m = | n: usize | Map((0..n).map( | i | (UInt(i as u64), Nil)).collect());
+    let items = | n: usize, item: &str | item.repeat(n);
+    let entries = | n: usize | (0..n).map( | i | if i < 128 {format!("{:02x}c0", i)} else {format!("cc{:02x}c0", i)}).collect::<String>();
+
+    let preferred = [
+        ("c0".to_string(), Nil),
+        ("c2".to_string(), Bool(false)),
+        ("c3".to_string(), Bool(true)),
+        ("00".to_string(), UInt(0)),
+        ("7f".to_string(), UInt(127)),
+        ("cc80".to_string(), UInt(128)),
+        ("ccff".to_string(), UInt(255)),
+        ("cd0100".to_string(), UInt(256)),
+        ("cdffff".to_string(), UInt(65535)),
+        ("ce00010000".to_string(), UInt(65536)),
+        ("ceffffffff".to_string(), UInt(4294967295)),
+        ("cf0000000100000000".to_string(), UInt(4294967296)),
+        ("cfffffffffffffffff".to_string(), UInt(u64::MAX)),
+        ("ff".to_string(), Int(-1)),
+        ("e0".to_string(), Int(-32)),
+        ("d0df".to_string(), Int(-33)),
+        ("d080".to_string(), Int(-128)),
+        ("d1ff7f".to_string(), Int(-129)),
+        ("d18000".to_string(), Int(-32768)),
+        ("d2ffff7fff".to_string(), Int(-32769)),
+        ("d280000000".to_string(), Int(-2147483648)),
+        ("d3ffffffff7fffffff".to_string(), Int(-2147483649)),
+        ("d38000000000000000".to_string(), Int(i64::MIN)),
+        ("ca3fc00000".to_string(), F32(1.5)),
+        ("ca7f800000".to_string(), F32(f32::INFINITY)),
+        ("cb3ff199999999999a".to_string(), F64(1.1)),
+        ("cbfff0000000000000".to_string(), F64(f64::NEG_INFINITY)),
+        ("a0".to_string(), s(0)),
+        ("a161".to_string(), s(1)),
+        ("bf".to_string() + &items(31, "61"), s(31)),
+        ("d920".to_string() + &items(32, "61"), s(32)),
+        ("d9ff".to_string() + &items(255, "61"), s(255)),
+        ("da0100".to_string() + &items(256, "61"), s(256)),
+        ("daffff".to_string() + &items(65535, "61"), s(65535)),
+        ("db00010000".to_string() + &items(65536, "61"), s(65536)),
+        ("a2c3bc".to_string(), Str("\u{00fc}".to_string())),
+        ("a4f0908591".to_string(), Str("\u{10151}".to_string())),
+        ("c400".to_string(), b(0)),
+        ("c40101".to_string(), b(1)),
+        ("c4ff".to_string() + &items(255, "01"), b(255)),
+        ("c50100".to_string() + &items(256, "01"), b(256)),
+        ("c600010000".to_string() + &items(65536, "01"), b(65536)),
+        ("90".to_string(), a(0)),
+        ("9f".to_string() + &items(15, "c0"), a(15)),
+        ("dc0010".to_string() + &items(16, "c0"), a(16)),
+        ("dcffff".to_string() + &items(65535, "c0"), a(65535)),
+        ("dd00010000".to_string() + &items(65536, "c0"), a(65536)),
+        ("80".to_string(), m(0)),
+        ("8f".to_string() + &entries(15), m(15)),
+        ("de0010".to_string() + &entries(16), m(16)),
+        ("81a16101".to_string(), Map(vec![(s(1), UInt(1))])),
+        ("9291019102".to_string(), Array(vec![Array(vec![UInt(1)]), Array(vec![UInt(2)])])),
+        ("d40110".to_string(), Ext(1, vec![0x10])),
+        ("d5022020".to_string(), Ext(2, vec![0x20; 2])),
+        ("d6ff00000000".to_string(), Ext(-1, vec![0; 4])),
+        ("d7ff0000000000000000".to_string(), Ext(-1, vec![0; 8])),
+        ("d805".to_string() + &items(16, "30"), Ext(5, vec![0x30; 16])),
+        ("c70001".to_string(), Ext(1, vec![])),
+        ("c70301010203".to_string(), Ext(1, vec![1, 2, 3])),
+        ("c7ff01".to_string() + &items(255, "00"), Ext(1, vec![0; 255])),
+        ("c8010001".to_string() + &items(256, "00"), Ext(1, vec![0; 256])),
+        ("c9000100000a".to_string() + &items(65536, "00"), Ext(10, vec![0; 65536])),
+    ];
+    for (h, v) in &preferred {
+        let d = hex(h);
+        let short = &h[..h.len().min(16)];
+        assert_eq!(&MsgPackValue::deserialize_msgpack(&d).unwrap(), v, "decoding {}", short);
+        assert_eq!(v.serialize_msgpack(), d, "encoding {}", short);
+    }
+
+    // wider formats than needed decode to the same values
+    let decodes = [
+        ("cc01", UInt(1)),
+        ("cd0001", UInt(1)),
+        ("ce00000001", UInt(1)),
+        ("cf0000000000000001", UInt(1)),
+        ("d0ff", Int(-1)),
+        ("d1ffff", Int(-1)),
+        ("d2ffffffff", Int(-1)),
+        ("d3ffffffffffffffff", Int(-1)),
+        ("d90161", s(1)),
+        ("da000161", s(1)),
+        ("db0000000161", s(1)),
+        ("c5000101", b(1)),
+        ("c60000000101", b(1)),
+        ("dc0001c0", a(1)),
+        ("dd00000001c0", a(1)),
+        ("de000100c0", m(1)),
+        ("df0000000100c0", m(1)),
+    ];
+    for (h, v) in &decodes {
+        assert_eq!(&MsgPackValue::deserialize_msgpack(&hex(h)).unwrap(), v, "decoding {}", h);
+    }
+    for h in ["c1", "cc", "cd01", "a2", "d9", "c40201", "92c0", "81a161", "d4", "a1ff", "c0c0"] {
+        assert!(MsgPackValue::deserialize_msgpack(&hex(h)).is_err(), "should fail {}", h);
+    }
+
+    // typed reads take any int format in range
+    assert_eq!(u8::deserialize_msgpack(&hex("c3")).unwrap_err().msg, "Expected int got 0xc3");
+    assert_eq!(u8::deserialize_msgpack(&hex("d30000000000000001")).unwrap(), 1);
+    assert_eq!(i8::deserialize_msgpack(&hex("cc80")).unwrap_err().msg, "Value out of range 128 for i8");
+    assert_eq!(u64::deserialize_msgpack(&hex("ff")).unwrap_err().msg, "Value out of range -1 for u64");
+    assert_eq!(f64::deserialize_msgpack(&hex("ca3fc00000")).unwrap(), 1.5);
+    assert_eq!(f64::deserialize_msgpack(&hex("d0fe")).unwrap(), -2.0);
+    for v in [0i64, 127, 128, -32, -33, 255, 256, -128, -129, i64::MIN, i64::MAX] {
+        assert_eq!(i64::deserialize_msgpack(&v.serialize_msgpack()).unwrap(), v);
+    }
+    println!("MsgPack vectors ok");
+}
@@ -0,0 +1,472 @@
+use std::borrow::Cow;
+use crate::serde_json::*;
+
+// An incremental json parser for data that arrives in chunks, like a streamed
+// http body. Bytes are fed in as they come and events are pulled out, a value
+// split over two chunks just doesn't produce its event until the second one
+// arrived. Any number of whitespace separated values can follow each other so
+// newline delimited json works as is.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonEvent<'a> {
+    ObjectStart,
+    ObjectEnd,
+    ArrayStart,
+    ArrayEnd,
+    // strings borrow from the parser buffer unless they had escapes
+    Key(Cow<'a, str>),
+    String(Cow<'a, str>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    // right after a [
+    ValueOrEnd,
+    Key,
+    // right after a {
+    KeyOrEnd,
+    Colon,
+    CommaOrEnd,
+}
+
+// a token with its byte range in the buffer
+#[derive(Clone, Copy)]
+enum Tok {
+    CurlyOpen,
+    CurlyClose,
+    BlockOpen,
+    BlockClose,
+    Colon,
+    Comma,
+    Str {escaped: bool},
+    Number,
+    True,
+    False,
+    Null,
+}
+
+#[derive(Default)]
+pub struct JsonStreamParser {
+    buf: Vec<u8>,
+    pos: usize,
+    // how far past pos the token that didn't end yet was looked through, so
+    // a long string isn't scanned from its start on every feed
+    tok_scan: usize,
+    tok_escaped: bool,
+    // the buffer can't drop a value next_de is still collecting
+    value_start: Option<usize>,
+    scan: Scan,
+    // true for objects, false for arrays
+    stack: Vec<bool>,
+    expect: Option<Expect>,
+    finished: bool,
+    failed: bool,
+    // columns are in bytes, lines start at an offset in the whole input
+    line: usize,
+    line_start: usize,
+    drained: usize,
+}
+
+impl JsonStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a chunk of input, it doesn't have to end on a token or even a
+    /// utf8 boundary
+    pub fn feed(&mut self, data: &[u8]) {
+        // the consumed bytes only go once they outweigh what is still pending,
+        // so the bytes of a long value are moved a bounded number of times
+        let keep = self.value_start.unwrap_or(self.pos);
+        if keep > 0 && keep >= self.buf.len() - keep {
+            self.buf.drain(..keep);
+            self.pos -= keep;
+            self.drained += keep;
+            if self.value_start.is_some() {
+                self.value_start = Some(0);
+                self.scan.pos -= keep;
+            }
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Marks the end of the input, a number at the very end can't know it
+    /// is complete before this
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// How many objects and arrays the parser is inside of
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Whether all input was consumed and no value is left half done, only
+    /// meaningful after finish
+    pub fn is_done(&mut self) -> bool {
+        self.skip_whitespace();
+        self.stack.is_empty() && self.pos == self.buf.len() && self.finished
+    }
+
+    fn err(&self, msg: &str) -> DeJsonErr {
+        DeJsonErr {msg: msg.to_string(), line: self.line, col: self.drained + self.pos - self.line_start}
+    }
+
+    fn fail(&mut self, msg: &str) -> Option<Result<(Tok, usize), DeJsonErr>> {
+        self.failed = true;
+        Some(Err(self.err(msg)))
+    }
+
+    fn advance(&mut self, to: usize) {
+        for (o, b) in self.buf[self.pos..to].iter().enumerate() {
+            if *b == b'\n' {
+                self.line += 1;
+                self.line_start = self.drained + self.pos + o + 1;
+            }
+        }
+        self.pos = to;
+    }
+
+    fn skip_whitespace(&mut self) {
+        let mut end = self.pos;
+        while end < self.buf.len() && matches!(self.buf[end], b' ' | b'\t' | b'\n' | b'\r') {
+            end += 1;
+        }
+        self.advance(end);
+    }
+
+    // the next complete token and where it starts, None if the buffer ends
+    // inside of it. Leaves pos after the token
+    fn next_tok(&mut self) -> Option<Result<(Tok, usize), DeJsonErr>> {
+        if self.failed {
+            return None
+        }
+        self.skip_whitespace();
+        let start = self.pos;
+        let buf = &self.buf[start..];
+        let first = *buf.first() ?;
+        let (tok, len) = match first {
+            b'{' => (Tok::CurlyOpen, 1),
+            b'}' => (Tok::CurlyClose, 1),
+            b'[' => (Tok::BlockOpen, 1),
+            b']' => (Tok::BlockClose, 1),
+            b':' => (Tok::Colon, 1),
+            b',' => (Tok::Comma, 1),
+            b'"' => {
+                let mut escaped = self.tok_escaped;
+                let mut end = None;
+                let mut o = self.tok_scan.max(1);
+                while let Some(at) = buf.get(o..).and_then( | rest | rest.iter().position( | b | *b == b'"' || *b == b'\\')) {
+                    o += at;
+                    if buf[o] == b'"' {
+                        end = Some(o + 1);
+                        break
+                    }
+                    escaped = true;
+                    o += 2;
+                }
+                if let Some(end) = end {
+                    (Tok::Str {escaped}, end)
+                }
+                else if self.finished {
+                    return self.fail("Unterminated string")
+                }
+                else {
+                    // past the end when the buffer stopped inside an escape
+                    self.tok_scan = o.max(buf.len());
+                    self.tok_escaped = escaped;
+                    return None
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let from = self.tok_scan.min(buf.len());
+                let len = from + buf[from..].iter().position( | b | !matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')).unwrap_or(buf.len() - from);
+                if len == buf.len() && !self.finished {
+                    self.tok_scan = len;
+                    return None
+                }
+                (Tok::Number, len)
+            }
+            b't' | b'f' | b'n' => {
+                let (tok, word): (Tok, &[u8]) = match first {
+                    b't' => (Tok::True, b"true"),
+                    b'f' => (Tok::False, b"false"),
+                    _ => (Tok::Null, b"null")
+                };
+                let have = buf.len().min(word.len());
+                if buf[..have] != word[..have] {
+                    return self.fail("Unexpected character, expected true, false or null")
+                }
+                if have < word.len() {
+                    if self.finished {
+                        return self.fail("Unexpected end of input")
+                    }
+                    return None
+                }
+                (tok, word.len())
+            }
+            _ => return self.fail("Unexpected character")
+        };
+        self.tok_scan = 0;
+        self.tok_escaped = false;
+        self.advance(start + len);
+        Some(Ok((tok, start)))
+    }
+
+    // the next value token with the structure checked, skipping over the
+    // colons and commas
+    fn next_value_tok(&mut self) -> Option<Result<(Tok, usize), DeJsonErr>> {
+        loop {
+            let (tok, start) = match self.next_tok() ? {
+                Ok(tok) => tok,
+                Err(e) => return Some(Err(e))
+            };
+            let expect = self.expect.unwrap_or(Expect::Value);
+            let in_object = self.stack.last().copied();
+            match (tok, expect) {
+                (Tok::Colon, Expect::Colon) => {
+                    self.expect = Some(Expect::Value);
+                    continue
+                }
+                (Tok::Comma, Expect::CommaOrEnd) => {
+                    self.expect = Some(if in_object == Some(true) {Expect::Key} else {Expect::Value});
+                    continue
+                }
+                (Tok::CurlyClose, Expect::KeyOrEnd | Expect::CommaOrEnd) if in_object == Some(true) => {
+                    self.stack.pop();
+                    self.value_done();
+                }
+                (Tok::BlockClose, Expect::ValueOrEnd | Expect::CommaOrEnd) if in_object == Some(false) => {
+                    self.stack.pop();
+                    self.value_done();
+                }
+                (Tok::Str {..}, Expect::Key | Expect::KeyOrEnd) => {
+                    self.expect = Some(Expect::Colon);
+                }
+                (Tok::CurlyOpen, Expect::Value | Expect::ValueOrEnd) => {
+                    self.stack.push(true);
+                    self.expect = Some(Expect::KeyOrEnd);
+                }
+                (Tok::BlockOpen, Expect::Value | Expect::ValueOrEnd) => {
+                    self.stack.push(false);
+                    self.expect = Some(Expect::ValueOrEnd);
+                }
+                (Tok::Str {..} | Tok::Number | Tok::True | Tok::False | Tok::Null, Expect::Value | Expect::ValueOrEnd) => {
+                    self.value_done();
+                }
+                _ => {
+                    return self.fail(match expect {
+                        Expect::Value | Expect::ValueOrEnd => "Unexpected token, expected a value",
+                        Expect::Key | Expect::KeyOrEnd => "Unexpected token, expected a key",
+                        Expect::Colon => "Unexpected token, expected :",
+                        Expect::CommaOrEnd => "Unexpected token, expected , or the end of the object or array",
+                    })
+                }
+            }
+            return Some(Ok((tok, start)))
+        }
+    }
+
+    fn value_done(&mut self) {
+        self.expect = if self.stack.is_empty() {None} else {Some(Expect::CommaOrEnd)};
+    }
+
+    /// The next event, None when the input so far is used up. After an error
+    /// the parser stops producing events
+    pub fn next_event(&mut self) -> Option<Result<JsonEvent<'_>, DeJsonErr>> {
+        let (tok, start) = match self.next_value_tok() ? {
+            Ok(tok) => tok,
+            Err(e) => return Some(Err(e))
+        };
+        // a key is the one string that is followed by a colon
+        let is_key = self.expect == Some(Expect::Colon);
+        let text = &self.buf[start..self.pos];
+        Some(match tok {
+            Tok::CurlyOpen => Ok(JsonEvent::ObjectStart),
+            Tok::CurlyClose => Ok(JsonEvent::ObjectEnd),
+            Tok::BlockOpen => Ok(JsonEvent::ArrayStart),
+            Tok::BlockClose => Ok(JsonEvent::ArrayEnd),
+            Tok::True => Ok(JsonEvent::Bool(true)),
+            Tok::False => Ok(JsonEvent::Bool(false)),
+            Tok::Null => Ok(JsonEvent::Null),
+            Tok::Number => match parse_number(text) {
+                Some(event) => Ok(event),
+                None => Err(self.err("Cannot parse number"))
+            }
+            Tok::Str {escaped} => {
+                let text = &text[1..text.len() - 1];
+                let s = match std::str::from_utf8(text) {
+                    Ok(s) if !escaped => Some(Cow::Borrowed(s)),
+                    Ok(s) => unescape(s).map(Cow::Owned),
+                    Err(_) => None
+                };
+                match s {
+                    Some(s) if is_key => Ok(JsonEvent::Key(s)),
+                    Some(s) => Ok(JsonEvent::String(s)),
+                    None => Err(self.err("Invalid string"))
+                }
+            }
+            Tok::Colon | Tok::Comma => unreachable!()
+        })
+    }
+
+    /// The next complete top level value deserialized as T, None until all
+    /// of it has arrived. Can't be mixed with next_event halfway a value
+    pub fn next_de<T: DeJson>(&mut self) -> Option<Result<T, DeJsonErr>> {
+        if self.failed {
+            return None
+        }
+        if self.value_start.is_none() {
+            if !self.stack.is_empty() {
+                self.failed = true;
+                return Some(Err(self.err("next_de called inside of a value")))
+            }
+            self.skip_whitespace();
+            if self.pos == self.buf.len() {
+                return None
+            }
+            self.value_start = Some(self.pos);
+            self.scan = Scan {pos: self.pos, ..Scan::default()};
+        }
+        let start = self.value_start.unwrap();
+        // only find where the value ends, deserializing checks the rest
+        let end = match self.scan.value_end(&self.buf, start, self.drained) {
+            Some(end) => end,
+            None if self.finished => self.buf.len(),
+            None => return None
+        };
+        self.value_start = None;
+        // errors are reported relative to where the value starts
+        let at = self.err("");
+        // the scan already counted the lines, json only has raw newlines
+        // outside of strings
+        self.line += self.scan.lines;
+        if self.scan.lines > 0 {
+            self.line_start = self.scan.line_start;
+        }
+        self.pos = end;
+        Some(match std::str::from_utf8(&self.buf[start..end]) {
+            Ok(text) => T::deserialize_json(text).map_err( | e | DeJsonErr {
+                msg: e.msg,
+                line: at.line + e.line,
+                col: if e.line == 0 {at.col + e.col} else {e.col}
+            }),
+            Err(_) => Err(self.err("Invalid utf8"))
+        })
+    }
+}
+
+// where next_de got with a value that is still coming in
+#[derive(Default)]
+struct Scan {
+    pos: usize,
+    depth: usize,
+    in_str: bool,
+    escape: bool,
+    lines: usize,
+    line_start: usize,
+}
+
+impl Scan {
+    fn value_end(&mut self, buf: &[u8], start: usize, drained: usize) -> Option<usize> {
+        while self.pos < buf.len() {
+            let b = buf[self.pos];
+            self.pos += 1;
+            if self.in_str {
+                if self.escape {
+                    self.escape = false;
+                }
+                else if b == b'\\' {
+                    self.escape = true;
+                }
+                else if b == b'"' {
+                    self.in_str = false;
+                    if self.depth == 0 {
+                        return Some(self.pos)
+                    }
+                }
+                else {
+                    // skip ahead to the next quote or escape
+                    let rest = &buf[self.pos..];
+                    self.pos += rest.iter().position( | b | *b == b'"' || *b == b'\\').unwrap_or(rest.len());
+                }
+                continue
+            }
+            match b {
+                b'"' => self.in_str = true,
+                b'\n' if self.depth > 0 => {
+                    self.lines += 1;
+                    self.line_start = drained + self.pos;
+                }
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 0 {
+                        return Some(self.pos)
+                    }
+                }
+                // the end of a number or literal at the top level
+                b' ' | b'\t' | b'\r' | b'\n' | b',' if self.depth == 0 && self.pos - 1 > start => {
+                    return Some(self.pos - 1)
+                }
+                _ => ()
+            }
+        }
+        None
+    }
+}
+
+fn parse_number(text: &[u8]) -> Option<JsonEvent<'static>> {
+    let text = std::str::from_utf8(text).ok() ?;
+    if text.contains(['.', 'e', 'E']) {
+        return text.parse().ok().map(JsonEvent::F64)
+    }
+    if text.starts_with('-') {
+        return text.parse().ok().map(JsonEvent::I64)
+    }
+    // too large for a u64 still is a number
+    text.parse().ok().map(JsonEvent::U64).or_else( || text.parse().ok().map(JsonEvent::F64))
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue
+        }
+        match chars.next() ? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            '0' => out.push('\0'),
+            'u' => {
+                let mut code = hex4(&mut chars) ?;
+                // surrogate pairs for the characters outside of the bmp
+                if (0xd800..0xdc00).contains(&code) && chars.as_str().starts_with("\\u") {
+                    chars.nth(1);
+                    let low = hex4(&mut chars) ?;
+                    code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                }
+                out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            c => out.push(c)
+        }
+    }
+    Some(out)
+}
+
+fn hex4(chars: &mut std::str::Chars) -> Option<u32> {
+    let code = u32::from_str_radix(chars.as_str().get(..4) ?, 16).ok() ?;
+    chars.nth(3);
+    Some(code)
+}
@@ -7,4 +7,16 @@
 pub use crate::serde_json::*;
 
 mod serde_ron;
-pub use crate::serde_ron::*;
\ No newline at end of file
+pub use crate::serde_ron::*;
+
+mod json_stream;
+pub use crate::json_stream::*;
+
+mod serde_cbor;
+pub use crate::serde_cbor::*;
+
+mod serde_msgpack;
+pub use crate::serde_msgpack::*;
+
+mod serde_toml;
+pub use crate::serde_toml::*;
@@ -0,0 +1,899 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use makepad_live_id::LiveId;
+
+// CBOR as specified in RFC 8949. Writing uses the preferred serialization,
+// the shortest head for every length and the shortest float that keeps the
+// value. Reading takes any well formed item, including the indefinite length
+// strings, arrays and maps. Typed reads look through tags.
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const CBOR_FALSE: u8 = 0xf4;
+const CBOR_TRUE: u8 = 0xf5;
+const CBOR_NULL: u8 = 0xf6;
+const CBOR_UNDEFINED: u8 = 0xf7;
+const CBOR_FLOAT16: u8 = 0xf9;
+const CBOR_FLOAT32: u8 = 0xfa;
+const CBOR_FLOAT64: u8 = 0xfb;
+const CBOR_BREAK: u8 = 0xff;
+
+// the length of an indefinite length item, and the head argument that says so
+const INDEFINITE: usize = usize::MAX;
+const INDEFINITE_ARG: u64 = u64::MAX;
+
+// nesting deeper than this is refused instead of running out of stack
+const MAX_DEPTH: usize = 512;
+
+pub struct SerCborState {
+    pub out: Vec<u8>
+}
+
+impl SerCborState {
+    pub fn head(&mut self, major: u8, arg: u64) {
+        let m = major << 5;
+        if arg < 24 {
+            self.out.push(m | arg as u8);
+        }
+        else if arg <= u8::MAX as u64 {
+            self.out.push(m | 24);
+            self.out.push(arg as u8);
+        }
+        else if arg <= u16::MAX as u64 {
+            self.out.push(m | 25);
+            self.out.extend_from_slice(&(arg as u16).to_be_bytes());
+        }
+        else if arg <= u32::MAX as u64 {
+            self.out.push(m | 26);
+            self.out.extend_from_slice(&(arg as u32).to_be_bytes());
+        }
+        else {
+            self.out.push(m | 27);
+            self.out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+
+    pub fn map(&mut self, len: usize) {
+        self.head(MAJOR_MAP, len as u64);
+    }
+
+    pub fn array(&mut self, len: usize) {
+        self.head(MAJOR_ARRAY, len as u64);
+    }
+
+    pub fn str(&mut self, s: &str) {
+        self.head(MAJOR_TEXT, s.len() as u64);
+        self.out.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn bytes(&mut self, b: &[u8]) {
+        self.head(MAJOR_BYTES, b.len() as u64);
+        self.out.extend_from_slice(b);
+    }
+
+    pub fn null(&mut self) {
+        self.out.push(CBOR_NULL);
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.head(MAJOR_UNSIGNED, v);
+    }
+
+    pub fn i64(&mut self, v: i64) {
+        if v < 0 {
+            self.head(MAJOR_NEGATIVE, !(v as u64));
+        }
+        else {
+            self.head(MAJOR_UNSIGNED, v as u64);
+        }
+    }
+
+    pub fn f64(&mut self, v: f64) {
+        if v.is_nan() {
+            self.out.push(CBOR_FLOAT16);
+            self.out.extend_from_slice(&0x7e00u16.to_be_bytes());
+        }
+        else if v as f32 as f64 == v {
+            self.f32(v as f32);
+        }
+        else {
+            self.out.push(CBOR_FLOAT64);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    pub fn f32(&mut self, v: f32) {
+        if let Some(half) = f32_to_f16(v) {
+            self.out.push(CBOR_FLOAT16);
+            self.out.extend_from_slice(&half.to_be_bytes());
+        }
+        else {
+            self.out.push(CBOR_FLOAT32);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    /// The entries of a value that serializes to a map and how many there
+    /// are, for #[flatten]
+    pub fn flatten_entries<T: SerCbor + ?Sized>(value: &T) -> (usize, Vec<u8>) {
+        let out = value.serialize_cbor();
+        let mut s = DeCborState::new(&out);
+        match s.map_begin() {
+            Ok(len) if len != INDEFINITE => (len, out[s.o..].to_vec()),
+            _ => (0, Vec::new())
+        }
+    }
+}
+
+// the f16 with exactly the value of v, if there is one
+fn f32_to_f16(v: f32) -> Option<u16> {
+    let bits = v.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+    if exp == 0xff {
+        return Some(sign | if mant == 0 {0x7c00} else {0x7e00})
+    }
+    if exp == 0 {
+        return if mant == 0 {Some(sign)} else {None}
+    }
+    let e = exp - 127;
+    if (-14..=15).contains(&e) {
+        if mant & 0x1fff != 0 {
+            return None
+        }
+        return Some(sign | (((e + 15) as u16) << 10) | (mant >> 13) as u16)
+    }
+    // the f16 subnormals, m * 2^-24
+    if (-24..-14).contains(&e) {
+        let full = mant | 0x80_0000;
+        let shift = -e - 1;
+        if full & ((1 << shift) - 1) != 0 {
+            return None
+        }
+        return Some(sign | (full >> shift) as u16)
+    }
+    None
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f64;
+    let v = match exp {
+        0 => mant * (-24f64).exp2(),
+        0x1f => if mant == 0.0 {f64::INFINITY} else {f64::NAN},
+        _ => (1024.0 + mant) * (exp as f64 - 25.0).exp2()
+    };
+    if half & 0x8000 != 0 {-v} else {v}
+}
+
+pub trait SerCbor {
+    fn serialize_cbor(&self) -> Vec<u8> {
+        let mut s = SerCborState {out: Vec::new()};
+        self.ser_cbor(&mut s);
+        s.out
+    }
+
+    fn ser_cbor(&self, s: &mut SerCborState);
+}
+
+pub trait DeCbor: Sized {
+    fn deserialize_cbor(d: &[u8]) -> Result<Self, DeCborErr> {
+        let mut s = DeCborState::new(d);
+        let r = DeCbor::de_cbor(&mut s) ?;
+        if s.o != d.len() {
+            return Err(s.err_msg("Trailing data after the value"))
+        }
+        Ok(r)
+    }
+
+    fn de_cbor(s: &mut DeCborState) -> Result<Self, DeCborErr>;
+}
+
+pub struct DeCborErr {
+    pub msg: String,
+    pub o: usize,
+}
+
+impl std::fmt::Debug for DeCborErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cbor Deserialize error: {}, offset:{}", self.msg, self.o)
+    }
+}
+
+#[derive(Clone)]
+pub struct DeCborState<'a> {
+    pub d: &'a [u8],
+    pub o: usize,
+}
+
+impl<'a> DeCborState<'a> {
+    pub fn new(d: &'a [u8]) -> Self {
+        Self {d, o: 0}
+    }
+
+    pub fn err_msg(&self, msg: &str) -> DeCborErr {
+        DeCborErr {msg: msg.to_string(), o: self.o}
+    }
+
+    pub fn err_exp(&self, name: &str) -> DeCborErr {
+        DeCborErr {msg: format!("Unexpected key {}", name), o: self.o}
+    }
+
+    pub fn err_nf(&self, name: &str) -> DeCborErr {
+        DeCborErr {msg: format!("Key not found {}", name), o: self.o}
+    }
+
+    pub fn err_enum(&self, name: &str) -> DeCborErr {
+        DeCborErr {msg: format!("Enum not defined {}", name), o: self.o}
+    }
+
+    pub fn err_untagged(&self, name: &str) -> DeCborErr {
+        DeCborErr {msg: format!("Data did not match any variant of untagged enum {}", name), o: self.o}
+    }
+
+    pub fn err_range(&self, what: &str) -> DeCborErr {
+        DeCborErr {msg: format!("Value out of range {}", what), o: self.o}
+    }
+
+    pub fn err_type(&self, what: &str) -> DeCborErr {
+        match self.d.get(self.o) {
+            Some(b) => DeCborErr {msg: format!("Expected {} got major type {} ({:#04x})", what, b >> 5, b), o: self.o},
+            None => self.err_eof()
+        }
+    }
+
+    fn err_eof(&self) -> DeCborErr {
+        DeCborErr {msg: "Unexpected end of data".to_string(), o: self.o}
+    }
+
+    pub fn peek(&self) -> Result<u8, DeCborErr> {
+        self.d.get(self.o).copied().ok_or_else( || self.err_eof())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DeCborErr> {
+        if len > self.d.len() - self.o {
+            return Err(self.err_eof())
+        }
+        let d = self.d;
+        self.o += len;
+        Ok(&d[self.o - len..self.o])
+    }
+
+    /// Reads the head of an item, its major type and argument. The argument
+    /// is INDEFINITE_ARG for the indefinite length items
+    pub fn head(&mut self) -> Result<(u8, u64), DeCborErr> {
+        let b = self.peek() ?;
+        let (major, info) = (b >> 5, b & 0x1f);
+        let arg = match info {
+            0..=23 => {self.o += 1; info as u64}
+            24..=27 => {
+                let len = 1 << (info - 24);
+                let bytes = self.d.get(self.o + 1..self.o + 1 + len).ok_or_else( || self.err_eof()) ?;
+                let arg = bytes.iter().fold(0u64, | a, b | (a << 8) | *b as u64);
+                self.o += 1 + len;
+                arg
+            }
+            31 if matches!(major, MAJOR_BYTES | MAJOR_TEXT | MAJOR_ARRAY | MAJOR_MAP) => {self.o += 1; INDEFINITE_ARG}
+            _ => return Err(self.err_msg("Malformed item head"))
+        };
+        Ok((major, arg))
+    }
+
+    // the head of the item under any tags
+    fn value_head(&mut self) -> Result<(u8, u64), DeCborErr> {
+        loop {
+            let start = self.o;
+            let (major, arg) = self.head() ?;
+            if major != MAJOR_TAG {
+                self.o = start;
+                return Ok((major, arg))
+            }
+        }
+    }
+
+    fn expect_head(&mut self, major: u8, what: &str) -> Result<u64, DeCborErr> {
+        let (m, _) = self.value_head() ?;
+        if m != major {
+            return Err(self.err_type(what))
+        }
+        Ok(self.head() ?.1)
+    }
+
+    fn len(&self, arg: u64) -> Result<usize, DeCborErr> {
+        if arg == INDEFINITE_ARG {
+            return Ok(INDEFINITE)
+        }
+        usize::try_from(arg).map_err( | _ | self.err_msg("Length too large"))
+    }
+
+    /// Starts reading a map, returns its length which is INDEFINITE for an
+    /// indefinite length map. Step through it with map_next
+    pub fn map_begin(&mut self) -> Result<usize, DeCborErr> {
+        let arg = self.expect_head(MAJOR_MAP, "map") ?;
+        self.len(arg)
+    }
+
+    /// Whether there is another entry in the map map_begin started
+    pub fn map_next(&mut self, len: &mut usize) -> Result<bool, DeCborErr> {
+        self.next_item(len)
+    }
+
+    pub fn array_begin(&mut self) -> Result<usize, DeCborErr> {
+        let arg = self.expect_head(MAJOR_ARRAY, "array") ?;
+        self.len(arg)
+    }
+
+    pub fn array_next(&mut self, len: &mut usize) -> Result<bool, DeCborErr> {
+        self.next_item(len)
+    }
+
+    /// Moves to the next item of an array, which has to be there
+    pub fn array_item(&mut self, len: &mut usize) -> Result<(), DeCborErr> {
+        if !self.next_item(len) ? {
+            return Err(self.err_msg("Array too short"))
+        }
+        Ok(())
+    }
+
+    /// Checks that the array has no items left
+    pub fn array_end(&mut self, len: &mut usize) -> Result<(), DeCborErr> {
+        if self.next_item(len) ? {
+            return Err(self.err_msg("Array too long"))
+        }
+        Ok(())
+    }
+
+    pub fn empty_array(&mut self) -> Result<(), DeCborErr> {
+        let mut len = self.array_begin() ?;
+        self.array_end(&mut len)
+    }
+
+    fn next_item(&mut self, len: &mut usize) -> Result<bool, DeCborErr> {
+        if *len == INDEFINITE {
+            if self.peek() ? == CBOR_BREAK {
+                self.o += 1;
+                return Ok(false)
+            }
+            return Ok(true)
+        }
+        if *len == 0 {
+            return Ok(false)
+        }
+        *len -= 1;
+        Ok(true)
+    }
+
+    // the payload of a definite or indefinite length byte or text string
+    fn string_bytes(&mut self, major: u8, what: &str) -> Result<Cow<'a, [u8]>, DeCborErr> {
+        let arg = self.expect_head(major, what) ?;
+        if arg != INDEFINITE_ARG {
+            let len = self.len(arg) ?;
+            return Ok(Cow::Borrowed(self.take(len) ?))
+        }
+        let mut out = Vec::new();
+        while self.peek() ? != CBOR_BREAK {
+            let (m, arg) = self.head() ?;
+            if m != major || arg == INDEFINITE_ARG {
+                return Err(self.err_msg("Malformed indefinite length string"))
+            }
+            let len = self.len(arg) ?;
+            out.extend_from_slice(self.take(len) ?);
+        }
+        self.o += 1;
+        Ok(Cow::Owned(out))
+    }
+
+    pub fn bytes(&mut self) -> Result<Cow<'a, [u8]>, DeCborErr> {
+        self.string_bytes(MAJOR_BYTES, "byte string")
+    }
+
+    pub fn text(&mut self) -> Result<Cow<'a, str>, DeCborErr> {
+        let start = self.o;
+        let err = | s: &Self | DeCborErr {msg: "Invalid utf8 in text string".to_string(), o: s.o.max(start)};
+        match self.string_bytes(MAJOR_TEXT, "text string") ? {
+            Cow::Borrowed(b) => std::str::from_utf8(b).map(Cow::Borrowed).map_err( | _ | err(self)),
+            Cow::Owned(b) => String::from_utf8(b).map(Cow::Owned).map_err( | _ | err(self))
+        }
+    }
+
+    /// A map key, the derives only use text keys
+    pub fn key(&mut self) -> Result<Cow<'a, str>, DeCborErr> {
+        self.text()
+    }
+
+    /// Any integer, as i128 to hold the full range from -2^64 to 2^64-1
+    pub fn int(&mut self) -> Result<i128, DeCborErr> {
+        match self.value_head() ? {
+            (MAJOR_UNSIGNED, _) => Ok(self.head() ?.1 as i128),
+            (MAJOR_NEGATIVE, _) => Ok(-1 - self.head() ?.1 as i128),
+            _ => Err(self.err_type("integer"))
+        }
+    }
+
+    pub fn f64(&mut self) -> Result<f64, DeCborErr> {
+        let (major, _) = self.value_head() ?;
+        if major != MAJOR_SIMPLE {
+            return self.int().map( | v | v as f64).map_err( | _ | self.err_type("float"))
+        }
+        let b = self.peek() ?;
+        let len = match b {CBOR_FLOAT16 => 2, CBOR_FLOAT32 => 4, CBOR_FLOAT64 => 8, _ => return Err(self.err_type("float"))};
+        self.o += 1;
+        let bytes = self.take(len) ?;
+        Ok(match len {
+            2 => f16_to_f64(u16::from_be_bytes(bytes.try_into().unwrap())),
+            4 => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+            _ => f64::from_be_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DeCborErr> {
+        self.value_head() ?;
+        match self.peek() ? {
+            CBOR_FALSE => {self.o += 1; Ok(false)}
+            CBOR_TRUE => {self.o += 1; Ok(true)}
+            _ => Err(self.err_type("bool"))
+        }
+    }
+
+    /// Whether the next item is null or undefined
+    pub fn is_null(&self) -> bool {
+        matches!(self.d.get(self.o), Some(&CBOR_NULL) | Some(&CBOR_UNDEFINED))
+    }
+
+    pub fn null(&mut self) -> Result<(), DeCborErr> {
+        if self.is_null() {
+            self.o += 1;
+            return Ok(())
+        }
+        Err(self.err_type("null"))
+    }
+
+    pub fn skip_value(&mut self) -> Result<(), DeCborErr> {
+        self.skip_depth(0)
+    }
+
+    fn skip_depth(&mut self, depth: usize) -> Result<(), DeCborErr> {
+        if depth > MAX_DEPTH {
+            return Err(self.err_msg("Nested too deep"))
+        }
+        let b = self.peek() ?;
+        match b >> 5 {
+            MAJOR_BYTES | MAJOR_TEXT => {
+                self.string_bytes(b >> 5, "string") ?;
+            }
+            MAJOR_ARRAY | MAJOR_MAP => {
+                let (major, arg) = self.head() ?;
+                let mut len = self.len(arg) ?;
+                let per = if major == MAJOR_MAP {2} else {1};
+                while self.next_item(&mut len) ? {
+                    for _ in 0..per {
+                        self.skip_depth(depth + 1) ?;
+                    }
+                }
+            }
+            MAJOR_TAG => {
+                self.head() ?;
+                self.skip_depth(depth + 1) ?;
+            }
+            MAJOR_SIMPLE => {
+                let len = match b & 0x1f {
+                    0..=23 => 0,
+                    24 => 1,
+                    25 => 2,
+                    26 => 4,
+                    27 => 8,
+                    _ => return Err(self.err_msg("Unexpected break"))
+                };
+                self.o += 1;
+                self.take(len) ?;
+            }
+            _ => {
+                self.head() ?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks ahead in the map for the text value of `tag`, for tagged enums
+    pub fn find_tag(&self, tag: &str) -> Result<String, DeCborErr> {
+        let mut s = self.clone();
+        let mut len = s.map_begin() ?;
+        while s.map_next(&mut len) ? {
+            if s.key() ? == tag {
+                return Ok(s.text() ?.into_owned())
+            }
+            s.skip_value() ?;
+        }
+        Err(self.err_nf(tag))
+    }
+
+    /// Deserializes a map made of the given entries, as ranges in the input,
+    /// for the keys a struct didn't know that go into its #[flatten] field
+    pub fn de_rest<T: DeCbor>(&self, rest: &[(usize, usize)]) -> Result<T, DeCborErr> {
+        let mut s = SerCborState {out: Vec::new()};
+        s.map(rest.len());
+        for (start, end) in rest {
+            s.out.extend_from_slice(&self.d[*start..*end]);
+        }
+        T::deserialize_cbor(&s.out).map_err( | e | DeCborErr {msg: e.msg, o: self.o})
+    }
+
+    /// Deserializes the map at the cursor without the `tag` entry
+    pub fn de_map_without<T: DeCbor>(&mut self, tag: &str) -> Result<T, DeCborErr> {
+        let mut rest = Vec::new();
+        let mut len = self.map_begin() ?;
+        while self.map_next(&mut len) ? {
+            let start = self.o;
+            let key = self.key() ?;
+            self.skip_value() ?;
+            if key != tag {
+                rest.push((start, self.o));
+            }
+        }
+        self.de_rest(&rest)
+    }
+}
+
+macro_rules!impl_ser_de_cbor_int {
+    ( $ ty: ident) => {
+        impl SerCbor for $ ty {
+            fn ser_cbor(&self, s: &mut SerCborState) {
+                s.i64(*self as i64);
+            }
+        }
+
+        impl DeCbor for $ ty {
+            fn de_cbor(s: &mut DeCborState) -> Result< $ ty, DeCborErr> {
+                let start = s.o;
+                let v = s.int() ?;
+                $ ty::try_from(v).map_err( | _ | DeCborErr {o: start, ..s.err_range(&format!("{} for {}", v, stringify!( $ ty)))})
+            }
+        }
+    }
+}
+
+impl_ser_de_cbor_int!(i64);
+impl_ser_de_cbor_int!(i32);
+impl_ser_de_cbor_int!(i16);
+impl_ser_de_cbor_int!(i8);
+impl_ser_de_cbor_int!(u32);
+impl_ser_de_cbor_int!(u16);
+impl_ser_de_cbor_int!(u8);
+
+impl SerCbor for u64 {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.u64(*self);
+    }
+}
+
+impl DeCbor for u64 {
+    fn de_cbor(s: &mut DeCborState) -> Result<u64, DeCborErr> {
+        let v = s.int() ?;
+        u64::try_from(v).map_err( | _ | s.err_range(&format!("{} for u64", v)))
+    }
+}
+
+impl SerCbor for usize {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.u64(*self as u64);
+    }
+}
+
+impl DeCbor for usize {
+    fn de_cbor(s: &mut DeCborState) -> Result<usize, DeCborErr> {
+        let v = s.int() ?;
+        usize::try_from(v).map_err( | _ | s.err_range(&format!("{} for usize", v)))
+    }
+}
+
+impl SerCbor for f64 {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.f64(*self);
+    }
+}
+
+impl DeCbor for f64 {
+    fn de_cbor(s: &mut DeCborState) -> Result<f64, DeCborErr> {
+        s.f64()
+    }
+}
+
+impl SerCbor for f32 {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.f32(*self);
+    }
+}
+
+impl DeCbor for f32 {
+    fn de_cbor(s: &mut DeCborState) -> Result<f32, DeCborErr> {
+        Ok(s.f64() ? as f32)
+    }
+}
+
+impl SerCbor for bool {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.out.push(if *self {CBOR_TRUE} else {CBOR_FALSE});
+    }
+}
+
+impl DeCbor for bool {
+    fn de_cbor(s: &mut DeCborState) -> Result<bool, DeCborErr> {
+        s.bool()
+    }
+}
+
+impl SerCbor for LiveId {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.u64(self.0);
+    }
+}
+
+impl DeCbor for LiveId {
+    fn de_cbor(s: &mut DeCborState) -> Result<LiveId, DeCborErr> {
+        Ok(LiveId(u64::de_cbor(s) ?))
+    }
+}
+
+impl SerCbor for str {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.str(self);
+    }
+}
+
+impl SerCbor for String {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.str(self);
+    }
+}
+
+impl DeCbor for String {
+    fn de_cbor(s: &mut DeCborState) -> Result<String, DeCborErr> {
+        Ok(s.text() ?.into_owned())
+    }
+}
+
+impl<T> SerCbor for Option<T> where T: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        if let Some(v) = self {
+            v.ser_cbor(s);
+        }
+        else {
+            s.null();
+        }
+    }
+}
+
+impl<T> DeCbor for Option<T> where T: DeCbor {
+    fn de_cbor(s: &mut DeCborState) -> Result<Self, DeCborErr> {
+        if s.is_null() {
+            s.null() ?;
+            return Ok(None)
+        }
+        Ok(Some(DeCbor::de_cbor(s) ?))
+    }
+}
+
+impl<T> SerCbor for [T] where T: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.array(self.len());
+        for item in self {
+            item.ser_cbor(s);
+        }
+    }
+}
+
+impl<T> SerCbor for Vec<T> where T: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        self.as_slice().ser_cbor(s);
+    }
+}
+
+impl<T> DeCbor for Vec<T> where T: DeCbor {
+    fn de_cbor(s: &mut DeCborState) -> Result<Vec<T>, DeCborErr> {
+        let mut len = s.array_begin() ?;
+        let mut out = Vec::with_capacity(len.min(s.d.len() - s.o));
+        while s.array_next(&mut len) ? {
+            out.push(DeCbor::de_cbor(s) ?);
+        }
+        Ok(out)
+    }
+}
+
+impl<T, const N: usize> SerCbor for [T; N] where T: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        self.as_slice().ser_cbor(s);
+    }
+}
+
+impl<T, const N: usize> DeCbor for [T; N] where T: DeCbor {
+    fn de_cbor(s: &mut DeCborState) -> Result<Self, DeCborErr> {
+        let v: Vec<T> = DeCbor::de_cbor(s) ?;
+        let len = v.len();
+        v.try_into().map_err( | _ | s.err_msg(&format!("Expected an array of {} items got {}", N, len)))
+    }
+}
+
+macro_rules!impl_ser_de_cbor_tuple {
+    ( $ ( $ t: ident $ n: tt), *) => {
+        impl< $ ( $ t), *> SerCbor for ( $ ( $ t, ) *) where $ ( $ t: SerCbor), * {
+            fn ser_cbor(&self, s: &mut SerCborState) {
+                s.array([ $ ( $ n), *].len());
+                $ (self. $ n.ser_cbor(s);) *
+            }
+        }
+
+        impl< $ ( $ t), *> DeCbor for ( $ ( $ t, ) *) where $ ( $ t: DeCbor), * {
+            fn de_cbor(s: &mut DeCborState) -> Result<Self, DeCborErr> {
+                let mut len = s.array_begin() ?;
+                let r = ( $ ({
+                    let _ = $ n;
+                    s.array_item(&mut len) ?;
+                    $ t::de_cbor(s) ?
+                }, ) *);
+                s.array_end(&mut len) ?;
+                Ok(r)
+            }
+        }
+    }
+}
+
+impl_ser_de_cbor_tuple!(A 0, B 1);
+impl_ser_de_cbor_tuple!(A 0, B 1, C 2);
+impl_ser_de_cbor_tuple!(A 0, B 1, C 2, D 3);
+
+impl<K, V> SerCbor for HashMap<K, V> where K: SerCbor,
+V: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        s.map(self.len());
+        for (k, v) in self {
+            k.ser_cbor(s);
+            v.ser_cbor(s);
+        }
+    }
+}
+
+impl<K, V> DeCbor for HashMap<K, V> where K: DeCbor + Eq + Hash,
+V: DeCbor {
+    fn de_cbor(s: &mut DeCborState) -> Result<Self, DeCborErr> {
+        let mut len = s.map_begin() ?;
+        let mut h = HashMap::new();
+        while s.map_next(&mut len) ? {
+            let k = DeCbor::de_cbor(s) ?;
+            let v = DeCbor::de_cbor(s) ?;
+            h.insert(k, v);
+        }
+        Ok(h)
+    }
+}
+
+impl<T> SerCbor for Box<T> where T: SerCbor {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        (**self).ser_cbor(s)
+    }
+}
+
+impl<T> DeCbor for Box<T> where T: DeCbor {
+    fn de_cbor(s: &mut DeCborState) -> Result<Box<T>, DeCborErr> {
+        Ok(Box::new(DeCbor::de_cbor(s) ?))
+    }
+}
+
+/// Any CBOR item, keeps everything the encoding can express
+#[derive(Clone, Debug, PartialEq)]
+pub enum CborValue {
+    Unsigned(u64),
+    // the value is -1 - n
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<CborValue>),
+    Map(Vec<(CborValue, CborValue)>),
+    Tag(u64, Box<CborValue>),
+    Bool(bool),
+    Null,
+    Undefined,
+    Simple(u8),
+    Float(f64),
+}
+
+impl SerCbor for CborValue {
+    fn ser_cbor(&self, s: &mut SerCborState) {
+        match self {
+            CborValue::Unsigned(v) => s.head(MAJOR_UNSIGNED, *v),
+            CborValue::Negative(v) => s.head(MAJOR_NEGATIVE, *v),
+            CborValue::Bytes(v) => s.bytes(v),
+            CborValue::Text(v) => s.str(v),
+            CborValue::Array(v) => v.ser_cbor(s),
+            CborValue::Map(v) => {
+                s.map(v.len());
+                for (k, v) in v {
+                    k.ser_cbor(s);
+                    v.ser_cbor(s);
+                }
+            }
+            CborValue::Tag(tag, v) => {
+                s.head(MAJOR_TAG, *tag);
+                v.ser_cbor(s);
+            }
+            CborValue::Bool(v) => v.ser_cbor(s),
+            CborValue::Null => s.null(),
+            CborValue::Undefined => s.out.push(CBOR_UNDEFINED),
+            CborValue::Simple(v) => {
+                if *v < 24 {
+                    s.out.push(MAJOR_SIMPLE << 5 | v);
+                }
+                else {
+                    s.out.push(MAJOR_SIMPLE << 5 | 24);
+                    s.out.push(*v);
+                }
+            }
+            CborValue::Float(v) => s.f64(*v),
+        }
+    }
+}
+
+impl DeCbor for CborValue {
+    fn de_cbor(s: &mut DeCborState) -> Result<CborValue, DeCborErr> {
+        de_cbor_value(s, 0)
+    }
+}
+
+fn de_cbor_value(s: &mut DeCborState, depth: usize) -> Result<CborValue, DeCborErr> {
+    if depth > MAX_DEPTH {
+        return Err(s.err_msg("Nested too deep"))
+    }
+    let b = s.peek() ?;
+    Ok(match b >> 5 {
+        MAJOR_UNSIGNED => CborValue::Unsigned(s.head() ?.1),
+        MAJOR_NEGATIVE => CborValue::Negative(s.head() ?.1),
+        MAJOR_BYTES => CborValue::Bytes(s.bytes() ?.into_owned()),
+        MAJOR_TEXT => CborValue::Text(s.text() ?.into_owned()),
+        MAJOR_ARRAY => {
+            let mut len = s.array_begin() ?;
+            let mut out = Vec::new();
+            while s.array_next(&mut len) ? {
+                out.push(de_cbor_value(s, depth + 1) ?);
+            }
+            CborValue::Array(out)
+        }
+        MAJOR_MAP => {
+            let mut len = s.map_begin() ?;
+            let mut out = Vec::new();
+            while s.map_next(&mut len) ? {
+                out.push((de_cbor_value(s, depth + 1) ?, de_cbor_value(s, depth + 1) ?));
+            }
+            CborValue::Map(out)
+        }
+        MAJOR_TAG => {
+            let tag = s.head() ?.1;
+            CborValue::Tag(tag, Box::new(de_cbor_value(s, depth + 1) ?))
+        }
+        _ => match b {
+            CBOR_FALSE => {s.o += 1; CborValue::Bool(false)}
+            CBOR_TRUE => {s.o += 1; CborValue::Bool(true)}
+            CBOR_NULL => {s.o += 1; CborValue::Null}
+            CBOR_UNDEFINED => {s.o += 1; CborValue::Undefined}
+            CBOR_FLOAT16 | CBOR_FLOAT32 | CBOR_FLOAT64 => CborValue::Float(s.f64() ?),
+            0xe0..=0xf3 => {s.o += 1; CborValue::Simple(b & 0x1f)}
+            0xf8 => {
+                s.o += 1;
+                let v = *s.take(1) ?.first().unwrap();
+                // the two byte form only holds the values one byte can't
+                if v < 32 {
+                    return Err(s.err_msg("Malformed simple value"))
+                }
+                CborValue::Simple(v)
+            }
+            _ => return Err(s.err_msg("Unexpected break"))
+        }
+    })
+}
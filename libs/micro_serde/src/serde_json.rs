@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::{HashMap};
 use std::hash::Hash;
 use std::str::Chars;
@@ -32,6 +33,30 @@ pub fn conl(&mut self) {
         self.out.push(',')
     }
     
+    // the comma before a field, optional and skipped fields mean we can't
+    // know up front which field comes first
+    pub fn field_sep(&mut self) {
+        if !self.out.ends_with('{') {
+            self.out.push(',')
+        }
+    }
+    
+    /// Writes the fields of an object into the object being written, for #[flatten]
+    pub fn flatten<T: SerJson + ?Sized>(&mut self, d: usize, value: &T) {
+        let sep = !self.out.ends_with('{');
+        let start = self.out.len();
+        value.ser_json(d, self);
+        if self.out[start..].starts_with('{') && self.out.ends_with('}') {
+            self.out.pop();
+            if !sep || self.out.len() == start + 1 {
+                self.out.remove(start);
+            }
+            else {
+                self.out.replace_range(start..start + 1, ",");
+            }
+        }
+    }
+    
     pub fn st_pre(&mut self) {
         self.out.push('{');
     }
@@ -71,7 +96,26 @@ fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self,
     DeJsonErr>;
 }
 
-#[derive(PartialEq, Debug)]
+/// Deserializes borrowing from the input, `&'a str` fields point into the
+/// json instead of being allocated. Strings with escapes can't be borrowed,
+/// use `Cow<'a, str>` where those can occur.
+pub trait DeJsonRef<'a>: Sized {
+    
+    fn deserialize_json_ref(input: &'a str) -> Result<Self,
+    DeJsonErr> {
+        let mut state = DeJsonState::default();
+        let mut chars = input.chars();
+        state.next(&mut chars);
+        state.next_tok(&mut chars) ?;
+        DeJsonRef::de_json_ref(&mut state, &mut chars, input)
+    }
+    
+    // `src` is the input `i` was made from
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self,
+    DeJsonErr>;
+}
+
+#[derive(Clone, PartialEq, Debug)]
 #[derive(Default)]
 pub enum DeJsonTok {
     Str,
@@ -93,13 +137,17 @@ pub enum DeJsonTok {
     Eof
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct DeJsonState {
     pub cur: char,
     pub tok: DeJsonTok,
     pub strbuf:String,
     pub numbuf:String,
     pub identbuf:String,
+    // where the last string token sits in the input, as the remaining input
+    // lengths at its start and end. None if it had escapes, the borrowing
+    // deserializers need it to hand out slices of the input
+    pub strspan: Option<(usize, usize)>,
     pub line: usize,
     pub col: usize
 }
@@ -149,6 +197,10 @@ pub fn err_enum(&self, name: &str) -> DeJsonErr {
         DeJsonErr{msg:format!("Enum not defined {}", name), line:self.line, col:self.col}
     }
 
+    pub fn err_untagged(&self, name: &str) -> DeJsonErr {
+        DeJsonErr{msg:format!("Data did not match any variant of untagged enum {}", name), line:self.line, col:self.col}
+    }
+
     pub fn err_token(&self, what:&str) -> DeJsonErr {
         DeJsonErr{msg:format!("Unexpected token {:?} expected {} ", self.tok, what), line:self.line, col:self.col}
     }
@@ -220,6 +272,42 @@ pub fn string(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
         }
     }
     
+    pub fn null(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
+        if self.tok == DeJsonTok::Null {
+            self.next_tok(i) ?;
+            return Ok(())
+        }
+        Err(self.err_token("null"))
+    }
+    
+    pub fn skip_value(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
+        JsonValue::de_json(self, i) ?;
+        Ok(())
+    }
+    
+    /// Looks ahead in the object for the string value of `tag`, for tagged enums
+    pub fn find_tag(&self, i: &Chars, tag: &str) -> Result<String, DeJsonErr> {
+        let mut s = self.clone();
+        let mut i = i.clone();
+        s.curly_open(&mut i) ?;
+        while s.next_str().is_some() {
+            if s.strbuf == tag {
+                s.next_colon(&mut i) ?;
+                return s.as_string()
+            }
+            s.next_colon(&mut i) ?;
+            s.skip_value(&mut i) ?;
+            s.eat_comma_curly(&mut i) ?;
+        }
+        Err(self.err_nf(tag))
+    }
+    
+    /// Deserializes the keys a struct didn't know into its #[flatten] field
+    pub fn de_json_rest<T: DeJson>(&self, rest: HashMap<String, JsonValue>) -> Result<T, DeJsonErr> {
+        let json = JsonValue::Object(rest).serialize_json();
+        T::deserialize_json(&json).map_err( | e | DeJsonErr{msg:e.msg, line:self.line, col:self.col})
+    }
+    
     pub fn next_colon(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
         self.next_tok(i) ?;
         self.colon(i) ?;
@@ -326,6 +414,14 @@ pub fn as_string(&mut self) -> Result<String, DeJsonErr> {
         Err(self.err_token("string"))
     }
     
+    /// The current string token as a slice of `src`, None if it had escapes
+    pub fn as_str_ref<'a>(&mut self, src: &'a str) -> Result<Option<&'a str>, DeJsonErr> {
+        if let DeJsonTok::Str = &mut self.tok {
+            return Ok(self.strspan.map( | (start, end) | &src[src.len() - start..src.len() - end]))
+        }
+        Err(self.err_token("string"))
+    }
+    
     pub fn as_ident(&mut self) -> Result<String, DeJsonErr> {
         if let DeJsonTok::BareIdent = &mut self.tok {
             let mut val = String::new();
@@ -388,13 +484,30 @@ pub fn next_tok(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
                     self.numbuf.push(self.cur);
                     self.next(i);
                 }
+                let mut is_float = false;
                 if self.cur == '.' {
+                    is_float = true;
                     self.numbuf.push(self.cur);
                     self.next(i);
                     while self.cur >= '0' && self.cur <= '9' {
                         self.numbuf.push(self.cur);
                         self.next(i);
                     }
+                }
+                if self.cur == 'e' || self.cur == 'E' {
+                    is_float = true;
+                    self.numbuf.push(self.cur);
+                    self.next(i);
+                    if self.cur == '-' || self.cur == '+' {
+                        self.numbuf.push(self.cur);
+                        self.next(i);
+                    }
+                    while self.cur >= '0' && self.cur <= '9' {
+                        self.numbuf.push(self.cur);
+                        self.next(i);
+                    }
+                }
+                if is_float {
                     if let Ok(num) = self.numbuf.parse() {
                         self.tok = DeJsonTok::F64(num);
                         Ok(())
@@ -447,6 +560,21 @@ pub fn next_tok(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
             }
             '"' => {
                 self.strbuf.clear();
+                // copy the run up to the first escape in one go, if that
+                // ends the string it is also a slice of the input
+                let rest = i.as_str();
+                let end = rest.bytes().position( | b | b == b'"' || b == b'\\' || b == b'$').unwrap_or(rest.len());
+                let run = &rest[..end];
+                self.strbuf.push_str(run);
+                match run.rfind('\n') {
+                    Some(nl) => {
+                        self.line += run.matches('\n').count();
+                        self.col = run[nl + 1..].chars().count();
+                    }
+                    None => self.col += run.chars().count()
+                }
+                self.strspan = if rest[end..].starts_with('"') {Some((rest.len(), rest.len() - end))} else {None};
+                *i = rest[end..].chars();
                 self.next(i);
                 while self.cur != '"' {
                     if self.cur == '\\' {
@@ -940,7 +1068,7 @@ impl<K, V> SerJson for HashMap<K, V> where K: SerJson,
 V: SerJson {
     fn ser_json(&self, d: usize, s: &mut SerJsonState) {
         s.out.push('{');
-        let last = self.len() - 1;
+        let last = self.len().saturating_sub(1);
         for (index, (k, v)) in self.iter().enumerate() {
             s.indent(d + 1);
             k.ser_json(d + 1, s);
@@ -984,3 +1112,124 @@ fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Box<T>, DeJsonErr> {
         Ok(Box::new(DeJson::de_json(s, i) ?))
     }
 }
+
+// types that never borrow just take the owned path
+macro_rules!impl_de_json_ref_owned {
+    ( $ ( $ ty: ty), *) => {
+        $ (
+            impl<'a> DeJsonRef<'a> for $ ty {
+                fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, _src: &'a str) -> Result<Self, DeJsonErr> {
+                    DeJson::de_json(s, i)
+                }
+            }
+        ) *
+    }
+}
+
+impl_de_json_ref_owned!(usize, u64, u32, u16, u8, i64, i32, i16, i8, f64, f32, bool, String, LiveId, JsonValue);
+
+impl<'a> DeJsonRef<'a> for &'a str {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        let Some(val) = s.as_str_ref(src) ? else {
+            return Err(s.err_msg("Can't borrow a string with escapes, use Cow<str>"))
+        };
+        s.next_tok(i) ?;
+        Ok(val)
+    }
+}
+
+impl<'a> DeJsonRef<'a> for Cow<'a, str> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        let val = match s.as_str_ref(src) ? {
+            Some(val) => Cow::Borrowed(val),
+            None => Cow::Owned(s.as_string() ?)
+        };
+        s.next_tok(i) ?;
+        Ok(val)
+    }
+}
+
+impl<'a, T> DeJsonRef<'a> for Option<T> where T: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        if let DeJsonTok::Null = s.tok {
+            s.next_tok(i) ?;
+            return Ok(None)
+        }
+        Ok(Some(DeJsonRef::de_json_ref(s, i, src) ?))
+    }
+}
+
+impl<'a, T> DeJsonRef<'a> for Vec<T> where T: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        let mut out = Vec::new();
+        s.block_open(i) ?;
+        while s.tok != DeJsonTok::BlockClose {
+            out.push(DeJsonRef::de_json_ref(s, i, src) ?);
+            s.eat_comma_block(i) ?;
+        }
+        s.block_close(i) ?;
+        Ok(out)
+    }
+}
+
+impl<'a, T> DeJsonRef<'a> for Box<T> where T: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        Ok(Box::new(DeJsonRef::de_json_ref(s, i, src) ?))
+    }
+}
+
+impl<'a, K, V> DeJsonRef<'a> for HashMap<K, V> where K: DeJsonRef<'a> + Eq + Hash,
+V: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        let mut h = HashMap::new();
+        s.curly_open(i) ?;
+        while s.tok != DeJsonTok::CurlyClose {
+            let k = DeJsonRef::de_json_ref(s, i, src) ?;
+            s.colon(i) ?;
+            let v = DeJsonRef::de_json_ref(s, i, src) ?;
+            s.eat_comma_curly(i) ?;
+            h.insert(k, v);
+        }
+        s.curly_close(i) ?;
+        Ok(h)
+    }
+}
+
+fn de_json_ref_comma_block<'a, T>(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<T, DeJsonErr> where T: DeJsonRef<'a> {
+    let t = DeJsonRef::de_json_ref(s, i, src);
+    s.eat_comma_block(i) ?;
+    t
+}
+
+impl<'a, A, B> DeJsonRef<'a> for (A, B) where A: DeJsonRef<'a>,
+B: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        s.block_open(i) ?;
+        let r = (de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?);
+        s.block_close(i) ?;
+        Ok(r)
+    }
+}
+
+impl<'a, A, B, C> DeJsonRef<'a> for (A, B, C) where A: DeJsonRef<'a>,
+B: DeJsonRef<'a>,
+C: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        s.block_open(i) ?;
+        let r = (de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?);
+        s.block_close(i) ?;
+        Ok(r)
+    }
+}
+
+impl<'a, A, B, C, D> DeJsonRef<'a> for (A, B, C, D) where A: DeJsonRef<'a>,
+B: DeJsonRef<'a>,
+C: DeJsonRef<'a>,
+D: DeJsonRef<'a> {
+    fn de_json_ref(s: &mut DeJsonState, i: &mut Chars, src: &'a str) -> Result<Self, DeJsonErr> {
+        s.block_open(i) ?;
+        let r = (de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?, de_json_ref_comma_block(s, i, src) ?);
+        s.block_close(i) ?;
+        Ok(r)
+    }
+}
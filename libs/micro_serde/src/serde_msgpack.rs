@@ -0,0 +1,822 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use makepad_live_id::LiveId;
+
+// MessagePack as in the msgpack spec. Integers and lengths are written in
+// their smallest format, reading accepts every format of the right family.
+
+const MSGPACK_NIL: u8 = 0xc0;
+const MSGPACK_FALSE: u8 = 0xc2;
+const MSGPACK_TRUE: u8 = 0xc3;
+const MSGPACK_BIN8: u8 = 0xc4;
+const MSGPACK_BIN16: u8 = 0xc5;
+const MSGPACK_BIN32: u8 = 0xc6;
+const MSGPACK_EXT8: u8 = 0xc7;
+const MSGPACK_EXT16: u8 = 0xc8;
+const MSGPACK_EXT32: u8 = 0xc9;
+const MSGPACK_FLOAT32: u8 = 0xca;
+const MSGPACK_FLOAT64: u8 = 0xcb;
+const MSGPACK_UINT8: u8 = 0xcc;
+const MSGPACK_UINT16: u8 = 0xcd;
+const MSGPACK_UINT32: u8 = 0xce;
+const MSGPACK_UINT64: u8 = 0xcf;
+const MSGPACK_INT8: u8 = 0xd0;
+const MSGPACK_INT16: u8 = 0xd1;
+const MSGPACK_INT32: u8 = 0xd2;
+const MSGPACK_INT64: u8 = 0xd3;
+const MSGPACK_FIXEXT1: u8 = 0xd4;
+const MSGPACK_FIXEXT16: u8 = 0xd8;
+const MSGPACK_STR8: u8 = 0xd9;
+const MSGPACK_STR16: u8 = 0xda;
+const MSGPACK_STR32: u8 = 0xdb;
+const MSGPACK_ARRAY16: u8 = 0xdc;
+const MSGPACK_ARRAY32: u8 = 0xdd;
+const MSGPACK_MAP16: u8 = 0xde;
+const MSGPACK_MAP32: u8 = 0xdf;
+
+// nesting deeper than this is refused instead of running out of stack
+const MAX_DEPTH: usize = 512;
+
+pub struct SerMsgPackState {
+    pub out: Vec<u8>
+}
+
+impl SerMsgPackState {
+    // a length in its fix form if it fits, otherwise after the 8, 16 or 32
+    // bit length marker
+    fn len_head(&mut self, fix: Option<(u8, usize)>, m8: Option<u8>, m16: u8, m32: u8, len: usize) {
+        match (fix, m8) {
+            (Some((fix, max)), _) if len <= max => self.out.push(fix | len as u8),
+            (_, Some(m8)) if len <= u8::MAX as usize => {
+                self.out.push(m8);
+                self.out.push(len as u8);
+            }
+            _ if len <= u16::MAX as usize => {
+                self.out.push(m16);
+                self.out.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                self.out.push(m32);
+                self.out.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+        }
+    }
+
+    pub fn map(&mut self, len: usize) {
+        self.len_head(Some((0x80, 15)), None, MSGPACK_MAP16, MSGPACK_MAP32, len);
+    }
+
+    pub fn array(&mut self, len: usize) {
+        self.len_head(Some((0x90, 15)), None, MSGPACK_ARRAY16, MSGPACK_ARRAY32, len);
+    }
+
+    pub fn str(&mut self, s: &str) {
+        self.len_head(Some((0xa0, 31)), Some(MSGPACK_STR8), MSGPACK_STR16, MSGPACK_STR32, s.len());
+        self.out.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn bytes(&mut self, b: &[u8]) {
+        self.len_head(None, Some(MSGPACK_BIN8), MSGPACK_BIN16, MSGPACK_BIN32, b.len());
+        self.out.extend_from_slice(b);
+    }
+
+    pub fn ext(&mut self, ty: i8, data: &[u8]) {
+        match data.len() {
+            1 | 2 | 4 | 8 | 16 => self.out.push(MSGPACK_FIXEXT1 + data.len().trailing_zeros() as u8),
+            len => self.len_head(None, Some(MSGPACK_EXT8), MSGPACK_EXT16, MSGPACK_EXT32, len)
+        }
+        self.out.push(ty as u8);
+        self.out.extend_from_slice(data);
+    }
+
+    pub fn null(&mut self) {
+        self.out.push(MSGPACK_NIL);
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        if v < 0x80 {
+            self.out.push(v as u8);
+        }
+        else if v <= u8::MAX as u64 {
+            self.out.push(MSGPACK_UINT8);
+            self.out.push(v as u8);
+        }
+        else if v <= u16::MAX as u64 {
+            self.out.push(MSGPACK_UINT16);
+            self.out.extend_from_slice(&(v as u16).to_be_bytes());
+        }
+        else if v <= u32::MAX as u64 {
+            self.out.push(MSGPACK_UINT32);
+            self.out.extend_from_slice(&(v as u32).to_be_bytes());
+        }
+        else {
+            self.out.push(MSGPACK_UINT64);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    pub fn i64(&mut self, v: i64) {
+        if v >= 0 {
+            self.u64(v as u64);
+        }
+        else if v >= -32 {
+            self.out.push(v as u8);
+        }
+        else if v >= i8::MIN as i64 {
+            self.out.push(MSGPACK_INT8);
+            self.out.push(v as u8);
+        }
+        else if v >= i16::MIN as i64 {
+            self.out.push(MSGPACK_INT16);
+            self.out.extend_from_slice(&(v as i16).to_be_bytes());
+        }
+        else if v >= i32::MIN as i64 {
+            self.out.push(MSGPACK_INT32);
+            self.out.extend_from_slice(&(v as i32).to_be_bytes());
+        }
+        else {
+            self.out.push(MSGPACK_INT64);
+            self.out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+
+    /// The entries of a value that serializes to a map and how many there
+    /// are, for #[flatten]
+    pub fn flatten_entries<T: SerMsgPack + ?Sized>(value: &T) -> (usize, Vec<u8>) {
+        let out = value.serialize_msgpack();
+        let mut s = DeMsgPackState::new(&out);
+        match s.map_begin() {
+            Ok(len) => (len, out[s.o..].to_vec()),
+            _ => (0, Vec::new())
+        }
+    }
+}
+
+pub trait SerMsgPack {
+    fn serialize_msgpack(&self) -> Vec<u8> {
+        let mut s = SerMsgPackState {out: Vec::new()};
+        self.ser_msgpack(&mut s);
+        s.out
+    }
+
+    fn ser_msgpack(&self, s: &mut SerMsgPackState);
+}
+
+pub trait DeMsgPack: Sized {
+    fn deserialize_msgpack(d: &[u8]) -> Result<Self, DeMsgPackErr> {
+        let mut s = DeMsgPackState::new(d);
+        let r = DeMsgPack::de_msgpack(&mut s) ?;
+        if s.o != d.len() {
+            return Err(s.err_msg("Trailing data after the value"))
+        }
+        Ok(r)
+    }
+
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Self, DeMsgPackErr>;
+}
+
+pub struct DeMsgPackErr {
+    pub msg: String,
+    pub o: usize,
+}
+
+impl std::fmt::Debug for DeMsgPackErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MsgPack Deserialize error: {}, offset:{}", self.msg, self.o)
+    }
+}
+
+#[derive(Clone)]
+pub struct DeMsgPackState<'a> {
+    pub d: &'a [u8],
+    pub o: usize,
+}
+
+impl<'a> DeMsgPackState<'a> {
+    pub fn new(d: &'a [u8]) -> Self {
+        Self {d, o: 0}
+    }
+
+    pub fn err_msg(&self, msg: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: msg.to_string(), o: self.o}
+    }
+
+    pub fn err_exp(&self, name: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: format!("Unexpected key {}", name), o: self.o}
+    }
+
+    pub fn err_nf(&self, name: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: format!("Key not found {}", name), o: self.o}
+    }
+
+    pub fn err_enum(&self, name: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: format!("Enum not defined {}", name), o: self.o}
+    }
+
+    pub fn err_untagged(&self, name: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: format!("Data did not match any variant of untagged enum {}", name), o: self.o}
+    }
+
+    pub fn err_range(&self, what: &str) -> DeMsgPackErr {
+        DeMsgPackErr {msg: format!("Value out of range {}", what), o: self.o}
+    }
+
+    pub fn err_type(&self, what: &str) -> DeMsgPackErr {
+        match self.d.get(self.o) {
+            Some(b) => DeMsgPackErr {msg: format!("Expected {} got {:#04x}", what, b), o: self.o},
+            None => self.err_eof()
+        }
+    }
+
+    fn err_eof(&self) -> DeMsgPackErr {
+        DeMsgPackErr {msg: "Unexpected end of data".to_string(), o: self.o}
+    }
+
+    pub fn peek(&self) -> Result<u8, DeMsgPackErr> {
+        self.d.get(self.o).copied().ok_or_else( || self.err_eof())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DeMsgPackErr> {
+        if len > self.d.len() - self.o {
+            return Err(self.err_eof())
+        }
+        let d = self.d;
+        self.o += len;
+        Ok(&d[self.o - len..self.o])
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64, DeMsgPackErr> {
+        Ok(self.take(len) ?.iter().fold(0u64, | a, b | (a << 8) | *b as u64))
+    }
+
+    // the marker and length of a map, array, str, bin or ext
+    fn len_of(&mut self, marker: u8) -> Result<usize, DeMsgPackErr> {
+        let len = match marker {
+            0x80..=0x9f => (marker & 0x0f) as u64,
+            0xa0..=0xbf => (marker & 0x1f) as u64,
+            MSGPACK_STR8 | MSGPACK_BIN8 | MSGPACK_EXT8 => self.uint(1) ?,
+            MSGPACK_STR16 | MSGPACK_BIN16 | MSGPACK_EXT16 | MSGPACK_ARRAY16 | MSGPACK_MAP16 => self.uint(2) ?,
+            MSGPACK_STR32 | MSGPACK_BIN32 | MSGPACK_EXT32 | MSGPACK_ARRAY32 | MSGPACK_MAP32 => self.uint(4) ?,
+            MSGPACK_FIXEXT1..=MSGPACK_FIXEXT16 => 1 << (marker - MSGPACK_FIXEXT1),
+            _ => unreachable!()
+        };
+        Ok(len as usize)
+    }
+
+    /// Starts reading a map and returns its length, step through it with
+    /// map_next
+    pub fn map_begin(&mut self) -> Result<usize, DeMsgPackErr> {
+        match self.peek() ? {
+            marker @ (0x80..=0x8f | MSGPACK_MAP16 | MSGPACK_MAP32) => {
+                self.o += 1;
+                self.len_of(marker)
+            }
+            _ => Err(self.err_type("map"))
+        }
+    }
+
+    /// Whether there is another entry in the map map_begin started
+    pub fn map_next(&mut self, len: &mut usize) -> Result<bool, DeMsgPackErr> {
+        self.array_next(len)
+    }
+
+    pub fn array_begin(&mut self) -> Result<usize, DeMsgPackErr> {
+        match self.peek() ? {
+            marker @ (0x90..=0x9f | MSGPACK_ARRAY16 | MSGPACK_ARRAY32) => {
+                self.o += 1;
+                self.len_of(marker)
+            }
+            _ => Err(self.err_type("array"))
+        }
+    }
+
+    pub fn array_next(&mut self, len: &mut usize) -> Result<bool, DeMsgPackErr> {
+        if *len == 0 {
+            return Ok(false)
+        }
+        *len -= 1;
+        Ok(true)
+    }
+
+    /// Moves to the next item of an array, which has to be there
+    pub fn array_item(&mut self, len: &mut usize) -> Result<(), DeMsgPackErr> {
+        if !self.array_next(len) ? {
+            return Err(self.err_msg("Array too short"))
+        }
+        Ok(())
+    }
+
+    /// Checks that the array has no items left
+    pub fn array_end(&mut self, len: &mut usize) -> Result<(), DeMsgPackErr> {
+        if *len != 0 {
+            return Err(self.err_msg("Array too long"))
+        }
+        Ok(())
+    }
+
+    pub fn empty_array(&mut self) -> Result<(), DeMsgPackErr> {
+        let mut len = self.array_begin() ?;
+        self.array_end(&mut len)
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], DeMsgPackErr> {
+        match self.peek() ? {
+            marker @ (MSGPACK_BIN8 | MSGPACK_BIN16 | MSGPACK_BIN32) => {
+                self.o += 1;
+                let len = self.len_of(marker) ?;
+                self.take(len)
+            }
+            _ => Err(self.err_type("bin"))
+        }
+    }
+
+    pub fn text(&mut self) -> Result<Cow<'a, str>, DeMsgPackErr> {
+        match self.peek() ? {
+            marker @ (0xa0..=0xbf | MSGPACK_STR8 | MSGPACK_STR16 | MSGPACK_STR32) => {
+                let start = self.o;
+                self.o += 1;
+                let len = self.len_of(marker) ?;
+                let b = self.take(len) ?;
+                std::str::from_utf8(b).map(Cow::Borrowed).map_err( | _ | DeMsgPackErr {msg: "Invalid utf8 in str".to_string(), o: start})
+            }
+            _ => Err(self.err_type("str"))
+        }
+    }
+
+    /// A map key, the derives only use str keys
+    pub fn key(&mut self) -> Result<Cow<'a, str>, DeMsgPackErr> {
+        self.text()
+    }
+
+    /// Any integer, as i128 to hold both u64 and i64
+    pub fn int(&mut self) -> Result<i128, DeMsgPackErr> {
+        let marker = self.peek() ?;
+        let v = match marker {
+            0x00..=0x7f => marker as i128,
+            0xe0..=0xff => marker as i8 as i128,
+            MSGPACK_UINT8..=MSGPACK_UINT64 => {
+                self.o += 1;
+                return Ok(self.uint(1 << (marker - MSGPACK_UINT8)) ? as i128)
+            }
+            MSGPACK_INT8..=MSGPACK_INT64 => {
+                self.o += 1;
+                let len = 1 << (marker - MSGPACK_INT8);
+                let v = self.uint(len) ?;
+                // sign extend from the top bit of the stored width
+                let shift = 64 - len * 8;
+                return Ok((((v << shift) as i64) >> shift) as i128)
+            }
+            _ => return Err(self.err_type("int"))
+        };
+        self.o += 1;
+        Ok(v)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, DeMsgPackErr> {
+        match self.peek() ? {
+            MSGPACK_FLOAT32 => {
+                self.o += 1;
+                Ok(f32::from_bits(self.uint(4) ? as u32) as f64)
+            }
+            MSGPACK_FLOAT64 => {
+                self.o += 1;
+                Ok(f64::from_bits(self.uint(8) ?))
+            }
+            _ => self.int().map( | v | v as f64).map_err( | _ | self.err_type("float"))
+        }
+    }
+
+    pub fn bool(&mut self) -> Result<bool, DeMsgPackErr> {
+        match self.peek() ? {
+            MSGPACK_FALSE => {self.o += 1; Ok(false)}
+            MSGPACK_TRUE => {self.o += 1; Ok(true)}
+            _ => Err(self.err_type("bool"))
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.d.get(self.o) == Some(&MSGPACK_NIL)
+    }
+
+    pub fn null(&mut self) -> Result<(), DeMsgPackErr> {
+        if self.is_null() {
+            self.o += 1;
+            return Ok(())
+        }
+        Err(self.err_type("nil"))
+    }
+
+    pub fn skip_value(&mut self) -> Result<(), DeMsgPackErr> {
+        self.skip_depth(0)
+    }
+
+    fn skip_depth(&mut self, depth: usize) -> Result<(), DeMsgPackErr> {
+        if depth > MAX_DEPTH {
+            return Err(self.err_msg("Nested too deep"))
+        }
+        let marker = self.peek() ?;
+        match marker {
+            0x80..=0x8f | MSGPACK_MAP16 | MSGPACK_MAP32 => {
+                let len = self.map_begin() ?;
+                for _ in 0..len {
+                    self.skip_depth(depth + 1) ?;
+                    self.skip_depth(depth + 1) ?;
+                }
+            }
+            0x90..=0x9f | MSGPACK_ARRAY16 | MSGPACK_ARRAY32 => {
+                let len = self.array_begin() ?;
+                for _ in 0..len {
+                    self.skip_depth(depth + 1) ?;
+                }
+            }
+            0xa0..=0xbf | MSGPACK_STR8 | MSGPACK_STR16 | MSGPACK_STR32 | MSGPACK_BIN8 | MSGPACK_BIN16 | MSGPACK_BIN32 => {
+                self.o += 1;
+                let len = self.len_of(marker) ?;
+                self.take(len) ?;
+            }
+            MSGPACK_EXT8 | MSGPACK_EXT16 | MSGPACK_EXT32 | MSGPACK_FIXEXT1..=MSGPACK_FIXEXT16 => {
+                self.o += 1;
+                let len = self.len_of(marker) ?;
+                self.take(len + 1) ?;
+            }
+            MSGPACK_FLOAT32 => {self.o += 1; self.take(4) ?;}
+            MSGPACK_FLOAT64 => {self.o += 1; self.take(8) ?;}
+            MSGPACK_NIL | MSGPACK_FALSE | MSGPACK_TRUE => self.o += 1,
+            0xc1 => return Err(self.err_msg("Invalid marker 0xc1")),
+            _ => {self.int() ?;}
+        }
+        Ok(())
+    }
+
+    /// Looks ahead in the map for the str value of `tag`, for tagged enums
+    pub fn find_tag(&self, tag: &str) -> Result<String, DeMsgPackErr> {
+        let mut s = self.clone();
+        let mut len = s.map_begin() ?;
+        while s.map_next(&mut len) ? {
+            if s.key() ? == tag {
+                return Ok(s.text() ?.into_owned())
+            }
+            s.skip_value() ?;
+        }
+        Err(self.err_nf(tag))
+    }
+
+    /// Deserializes a map made of the given entries, as ranges in the input,
+    /// for the keys a struct didn't know that go into its #[flatten] field
+    pub fn de_rest<T: DeMsgPack>(&self, rest: &[(usize, usize)]) -> Result<T, DeMsgPackErr> {
+        let mut s = SerMsgPackState {out: Vec::new()};
+        s.map(rest.len());
+        for (start, end) in rest {
+            s.out.extend_from_slice(&self.d[*start..*end]);
+        }
+        T::deserialize_msgpack(&s.out).map_err( | e | DeMsgPackErr {msg: e.msg, o: self.o})
+    }
+
+    /// Deserializes the map at the cursor without the `tag` entry
+    pub fn de_map_without<T: DeMsgPack>(&mut self, tag: &str) -> Result<T, DeMsgPackErr> {
+        let mut rest = Vec::new();
+        let mut len = self.map_begin() ?;
+        while self.map_next(&mut len) ? {
+            let start = self.o;
+            let key = self.key() ?;
+            self.skip_value() ?;
+            if key != tag {
+                rest.push((start, self.o));
+            }
+        }
+        self.de_rest(&rest)
+    }
+}
+
+macro_rules!impl_ser_de_msgpack_int {
+    ( $ ty: ident) => {
+        impl SerMsgPack for $ ty {
+            fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+                s.i64(*self as i64);
+            }
+        }
+
+        impl DeMsgPack for $ ty {
+            fn de_msgpack(s: &mut DeMsgPackState) -> Result< $ ty, DeMsgPackErr> {
+                let start = s.o;
+                let v = s.int() ?;
+                $ ty::try_from(v).map_err( | _ | DeMsgPackErr {o: start, ..s.err_range(&format!("{} for {}", v, stringify!( $ ty)))})
+            }
+        }
+    }
+}
+
+impl_ser_de_msgpack_int!(i64);
+impl_ser_de_msgpack_int!(i32);
+impl_ser_de_msgpack_int!(i16);
+impl_ser_de_msgpack_int!(i8);
+impl_ser_de_msgpack_int!(u32);
+impl_ser_de_msgpack_int!(u16);
+impl_ser_de_msgpack_int!(u8);
+
+impl SerMsgPack for u64 {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.u64(*self);
+    }
+}
+
+impl DeMsgPack for u64 {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<u64, DeMsgPackErr> {
+        let v = s.int() ?;
+        u64::try_from(v).map_err( | _ | s.err_range(&format!("{} for u64", v)))
+    }
+}
+
+impl SerMsgPack for usize {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.u64(*self as u64);
+    }
+}
+
+impl DeMsgPack for usize {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<usize, DeMsgPackErr> {
+        let v = s.int() ?;
+        usize::try_from(v).map_err( | _ | s.err_range(&format!("{} for usize", v)))
+    }
+}
+
+impl SerMsgPack for f64 {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.out.push(MSGPACK_FLOAT64);
+        s.out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl DeMsgPack for f64 {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<f64, DeMsgPackErr> {
+        s.f64()
+    }
+}
+
+impl SerMsgPack for f32 {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.out.push(MSGPACK_FLOAT32);
+        s.out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl DeMsgPack for f32 {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<f32, DeMsgPackErr> {
+        Ok(s.f64() ? as f32)
+    }
+}
+
+impl SerMsgPack for bool {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.out.push(if *self {MSGPACK_TRUE} else {MSGPACK_FALSE});
+    }
+}
+
+impl DeMsgPack for bool {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<bool, DeMsgPackErr> {
+        s.bool()
+    }
+}
+
+impl SerMsgPack for LiveId {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.u64(self.0);
+    }
+}
+
+impl DeMsgPack for LiveId {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<LiveId, DeMsgPackErr> {
+        Ok(LiveId(u64::de_msgpack(s) ?))
+    }
+}
+
+impl SerMsgPack for str {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.str(self);
+    }
+}
+
+impl SerMsgPack for String {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.str(self);
+    }
+}
+
+impl DeMsgPack for String {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<String, DeMsgPackErr> {
+        Ok(s.text() ?.into_owned())
+    }
+}
+
+impl<T> SerMsgPack for Option<T> where T: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        if let Some(v) = self {
+            v.ser_msgpack(s);
+        }
+        else {
+            s.null();
+        }
+    }
+}
+
+impl<T> DeMsgPack for Option<T> where T: DeMsgPack {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Self, DeMsgPackErr> {
+        if s.is_null() {
+            s.null() ?;
+            return Ok(None)
+        }
+        Ok(Some(DeMsgPack::de_msgpack(s) ?))
+    }
+}
+
+impl<T> SerMsgPack for [T] where T: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.array(self.len());
+        for item in self {
+            item.ser_msgpack(s);
+        }
+    }
+}
+
+impl<T> SerMsgPack for Vec<T> where T: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        self.as_slice().ser_msgpack(s);
+    }
+}
+
+impl<T> DeMsgPack for Vec<T> where T: DeMsgPack {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Vec<T>, DeMsgPackErr> {
+        let mut len = s.array_begin() ?;
+        let mut out = Vec::with_capacity(len.min(s.d.len() - s.o));
+        while s.array_next(&mut len) ? {
+            out.push(DeMsgPack::de_msgpack(s) ?);
+        }
+        Ok(out)
+    }
+}
+
+impl<T, const N: usize> SerMsgPack for [T; N] where T: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        self.as_slice().ser_msgpack(s);
+    }
+}
+
+impl<T, const N: usize> DeMsgPack for [T; N] where T: DeMsgPack {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Self, DeMsgPackErr> {
+        let v: Vec<T> = DeMsgPack::de_msgpack(s) ?;
+        let len = v.len();
+        v.try_into().map_err( | _ | s.err_msg(&format!("Expected an array of {} items got {}", N, len)))
+    }
+}
+
+macro_rules!impl_ser_de_msgpack_tuple {
+    ( $ ( $ t: ident $ n: tt), *) => {
+        impl< $ ( $ t), *> SerMsgPack for ( $ ( $ t, ) *) where $ ( $ t: SerMsgPack), * {
+            fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+                s.array([ $ ( $ n), *].len());
+                $ (self. $ n.ser_msgpack(s);) *
+            }
+        }
+
+        impl< $ ( $ t), *> DeMsgPack for ( $ ( $ t, ) *) where $ ( $ t: DeMsgPack), * {
+            fn de_msgpack(s: &mut DeMsgPackState) -> Result<Self, DeMsgPackErr> {
+                let mut len = s.array_begin() ?;
+                let r = ( $ ({
+                    let _ = $ n;
+                    s.array_item(&mut len) ?;
+                    $ t::de_msgpack(s) ?
+                }, ) *);
+                s.array_end(&mut len) ?;
+                Ok(r)
+            }
+        }
+    }
+}
+
+impl_ser_de_msgpack_tuple!(A 0, B 1);
+impl_ser_de_msgpack_tuple!(A 0, B 1, C 2);
+impl_ser_de_msgpack_tuple!(A 0, B 1, C 2, D 3);
+
+impl<K, V> SerMsgPack for HashMap<K, V> where K: SerMsgPack,
+V: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        s.map(self.len());
+        for (k, v) in self {
+            k.ser_msgpack(s);
+            v.ser_msgpack(s);
+        }
+    }
+}
+
+impl<K, V> DeMsgPack for HashMap<K, V> where K: DeMsgPack + Eq + Hash,
+V: DeMsgPack {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Self, DeMsgPackErr> {
+        let mut len = s.map_begin() ?;
+        let mut h = HashMap::new();
+        while s.map_next(&mut len) ? {
+            let k = DeMsgPack::de_msgpack(s) ?;
+            let v = DeMsgPack::de_msgpack(s) ?;
+            h.insert(k, v);
+        }
+        Ok(h)
+    }
+}
+
+impl<T> SerMsgPack for Box<T> where T: SerMsgPack {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        (**self).ser_msgpack(s)
+    }
+}
+
+impl<T> DeMsgPack for Box<T> where T: DeMsgPack {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<Box<T>, DeMsgPackErr> {
+        Ok(Box::new(DeMsgPack::de_msgpack(s) ?))
+    }
+}
+
+/// Any MessagePack value, floats keep their width
+#[derive(Clone, Debug, PartialEq)]
+pub enum MsgPackValue {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    F32(f32),
+    F64(f64),
+    Str(String),
+    Bin(Vec<u8>),
+    Array(Vec<MsgPackValue>),
+    Map(Vec<(MsgPackValue, MsgPackValue)>),
+    Ext(i8, Vec<u8>),
+}
+
+impl SerMsgPack for MsgPackValue {
+    fn ser_msgpack(&self, s: &mut SerMsgPackState) {
+        match self {
+            MsgPackValue::Nil => s.null(),
+            MsgPackValue::Bool(v) => v.ser_msgpack(s),
+            MsgPackValue::Int(v) => s.i64(*v),
+            MsgPackValue::UInt(v) => s.u64(*v),
+            MsgPackValue::F32(v) => v.ser_msgpack(s),
+            MsgPackValue::F64(v) => v.ser_msgpack(s),
+            MsgPackValue::Str(v) => s.str(v),
+            MsgPackValue::Bin(v) => s.bytes(v),
+            MsgPackValue::Array(v) => v.ser_msgpack(s),
+            MsgPackValue::Map(v) => {
+                s.map(v.len());
+                for (k, v) in v {
+                    k.ser_msgpack(s);
+                    v.ser_msgpack(s);
+                }
+            }
+            MsgPackValue::Ext(ty, data) => s.ext(*ty, data),
+        }
+    }
+}
+
+impl DeMsgPack for MsgPackValue {
+    fn de_msgpack(s: &mut DeMsgPackState) -> Result<MsgPackValue, DeMsgPackErr> {
+        de_msgpack_value(s, 0)
+    }
+}
+
+fn de_msgpack_value(s: &mut DeMsgPackState, depth: usize) -> Result<MsgPackValue, DeMsgPackErr> {
+    if depth > MAX_DEPTH {
+        return Err(s.err_msg("Nested too deep"))
+    }
+    let marker = s.peek() ?;
+    Ok(match marker {
+        MSGPACK_NIL => {s.o += 1; MsgPackValue::Nil}
+        MSGPACK_FALSE | MSGPACK_TRUE => MsgPackValue::Bool(s.bool() ?),
+        MSGPACK_FLOAT32 => MsgPackValue::F32(s.f64() ? as f32),
+        MSGPACK_FLOAT64 => MsgPackValue::F64(s.f64() ?),
+        0x80..=0x8f | MSGPACK_MAP16 | MSGPACK_MAP32 => {
+            let len = s.map_begin() ?;
+            let mut out = Vec::with_capacity(len.min(s.d.len() - s.o));
+            for _ in 0..len {
+                out.push((de_msgpack_value(s, depth + 1) ?, de_msgpack_value(s, depth + 1) ?));
+            }
+            MsgPackValue::Map(out)
+        }
+        0x90..=0x9f | MSGPACK_ARRAY16 | MSGPACK_ARRAY32 => {
+            let len = s.array_begin() ?;
+            let mut out = Vec::with_capacity(len.min(s.d.len() - s.o));
+            for _ in 0..len {
+                out.push(de_msgpack_value(s, depth + 1) ?);
+            }
+            MsgPackValue::Array(out)
+        }
+        0xa0..=0xbf | MSGPACK_STR8 | MSGPACK_STR16 | MSGPACK_STR32 => MsgPackValue::Str(s.text() ?.into_owned()),
+        MSGPACK_BIN8 | MSGPACK_BIN16 | MSGPACK_BIN32 => MsgPackValue::Bin(s.bytes() ?.to_vec()),
+        MSGPACK_EXT8 | MSGPACK_EXT16 | MSGPACK_EXT32 | MSGPACK_FIXEXT1..=MSGPACK_FIXEXT16 => {
+            s.o += 1;
+            let len = s.len_of(marker) ?;
+            let ty = s.take(1) ?[0] as i8;
+            MsgPackValue::Ext(ty, s.take(len) ?.to_vec())
+        }
+        0xc1 => return Err(s.err_msg("Invalid marker 0xc1")),
+        0x00..=0x7f | MSGPACK_UINT8..=MSGPACK_UINT64 => MsgPackValue::UInt(s.int() ? as u64),
+        _ => MsgPackValue::Int(s.int() ? as i64),
+    })
+}
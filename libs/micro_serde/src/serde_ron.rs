@@ -20,10 +20,28 @@ pub fn field(&mut self, d: usize, field: &str) {
         self.out.push(':');
     }
     
+    pub fn label(&mut self, label: &str) {
+        self.out.push('"');
+        self.out.push_str(label);
+        self.out.push('"');
+    }
+    
     pub fn conl(&mut self) {
         self.out.push_str(",\n")
     }
     
+    /// Writes the fields of a struct into the struct being written, for #[flatten]
+    pub fn flatten<T: SerRon + ?Sized>(&mut self, d: usize, value: &T) {
+        let start = self.out.len();
+        value.ser_ron(d, self);
+        if self.out[start..].starts_with("(\n") && self.out.ends_with(')') {
+            // keep the fields, they already sit at our depth
+            let end = self.out.rfind('\n').unwrap() + 1;
+            self.out.truncate(end);
+            self.out.replace_range(start..start + 2, "");
+        }
+    }
+    
     pub fn st_pre(&mut self) {
         self.out.push_str("(\n");
     }
@@ -63,7 +81,7 @@ fn de_ron(s: &mut DeRonState, i: &mut Chars) -> Result<Self,
     DeRonErr>;
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 #[derive(Default)]
 pub enum DeRonTok {
     Ident,
@@ -88,7 +106,7 @@ pub enum DeRonTok {
 
 
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct DeRonState {
     pub cur: char,
     pub tok: DeRonTok,
@@ -96,7 +114,9 @@ pub struct DeRonState {
     pub numbuf: String,
     pub identbuf: String,
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    // keys a struct skips because the struct it is flattened into read them
+    pub flatten_keys: Vec<&'static str>,
 }
 
 pub struct DeRonErr {
@@ -140,6 +160,10 @@ pub fn err_enum(&self, name: &str) -> DeRonErr {
         DeRonErr {msg: format!("Enum not defined {}", name), line: self.line, col: self.col}
     }
     
+    pub fn err_untagged(&self, name: &str) -> DeRonErr {
+        DeRonErr {msg: format!("Data did not match any variant of untagged enum {}", name), line: self.line, col: self.col}
+    }
+    
     pub fn err_token(&self, what: &str) -> DeRonErr {
         DeRonErr {msg: format!("Unexpected token {:?} expected {} ", self.tok, what), line: self.line, col: self.col}
     }
@@ -225,6 +249,52 @@ pub fn ident(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
         }
     }
     
+    pub fn skip_value(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
+        let mut depth = 0;
+        loop {
+            match self.tok {
+                DeRonTok::ParenOpen | DeRonTok::BlockOpen | DeRonTok::CurlyOpen => depth += 1,
+                DeRonTok::ParenClose | DeRonTok::BlockClose | DeRonTok::CurlyClose if depth > 0 => depth -= 1,
+                DeRonTok::ParenClose | DeRonTok::BlockClose | DeRonTok::CurlyClose | DeRonTok::Eof => {
+                    return Err(self.err_token("value"))
+                }
+                DeRonTok::Ident => {
+                    // a named struct or enum variant carries on with its data
+                    self.next_tok(i) ?;
+                    if depth == 0 && self.tok != DeRonTok::ParenOpen {
+                        return Ok(())
+                    }
+                    continue;
+                }
+                _ => ()
+            }
+            self.next_tok(i) ?;
+            if depth == 0 {
+                return Ok(())
+            }
+        }
+    }
+    
+    /// Looks ahead in the struct for the value of `tag`, for tagged enums
+    pub fn find_tag(&self, i: &Chars, tag: &str) -> Result<String, DeRonErr> {
+        let mut s = self.clone();
+        let mut i = i.clone();
+        s.paren_open(&mut i) ?;
+        while s.next_ident().is_some() {
+            if s.identbuf == tag {
+                s.next_colon(&mut i) ?;
+                if s.tok == DeRonTok::Ident {
+                    return Ok(std::mem::take(&mut s.identbuf))
+                }
+                return s.as_string()
+            }
+            s.next_colon(&mut i) ?;
+            s.skip_value(&mut i) ?;
+            s.eat_comma_paren(&mut i) ?;
+        }
+        Err(self.err_nf(tag))
+    }
+    
     pub fn next_colon(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
         self.next_tok(i) ?;
         self.colon(i) ?;
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use makepad_live_id::LiveId;
+pub use makepad_toml_parser::{
+    parse_toml_table,
+    TomlDate,
+    TomlDatetime,
+    TomlDocument,
+    TomlErr,
+    TomlItem,
+    TomlOffset,
+    TomlSpan,
+    TomlTable,
+    TomlTime,
+    TomlValue,
+};
+
+pub trait SerToml {
+    /// Writes a document, tables as [sections] and everything else inline
+    fn serialize_toml(&self) -> String {
+        match self.ser_toml() {
+            Some(TomlValue::Table(table)) => table.to_toml(),
+            Some(value) => value.to_string(),
+            None => String::new()
+        }
+    }
+
+    /// None for values TOML can't write, like a None option. Table fields
+    /// holding them are left out
+    fn ser_toml(&self) -> Option<TomlValue>;
+}
+
+pub trait DeToml: Sized {
+    fn deserialize_toml(input: &str) -> Result<Self, TomlErr> {
+        let root = TomlItem {value: TomlValue::Table(parse_toml_table(input) ?), span: TomlSpan {start: 0, len: 0}};
+        DeToml::de_toml(&root).map_err( | e: TomlErr | e.locate(input))
+    }
+
+    fn de_toml(item: &TomlItem) -> Result<Self, TomlErr>;
+}
+
+macro_rules!impl_ser_de_toml_int {
+    ( $ ty: ident) => {
+        impl SerToml for $ ty {
+            fn ser_toml(&self) -> Option<TomlValue> {
+                Some(TomlValue::Int(*self as i64))
+            }
+        }
+
+        impl DeToml for $ ty {
+            fn de_toml(item: &TomlItem) -> Result< $ ty, TomlErr> {
+                match item.value {
+                    TomlValue::Int(v) => $ ty::try_from(v).map_err( | _ | item.err_msg(&format!("Value out of range {} for {}", v, stringify!( $ ty)))),
+                    _ => Err(item.err_type("integer"))
+                }
+            }
+        }
+    }
+}
+
+impl_ser_de_toml_int!(i64);
+impl_ser_de_toml_int!(i32);
+impl_ser_de_toml_int!(i16);
+impl_ser_de_toml_int!(i8);
+impl_ser_de_toml_int!(u32);
+impl_ser_de_toml_int!(u16);
+impl_ser_de_toml_int!(u8);
+
+// TOML integers are i64, bigger ones are written as floats
+impl SerToml for u64 {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        match i64::try_from(*self) {
+            Ok(v) => Some(TomlValue::Int(v)),
+            Err(_) => Some(TomlValue::Float(*self as f64))
+        }
+    }
+}
+
+impl DeToml for u64 {
+    fn de_toml(item: &TomlItem) -> Result<u64, TomlErr> {
+        match item.value {
+            TomlValue::Int(v) if v >= 0 => Ok(v as u64),
+            TomlValue::Float(v) if v >= 0.0 && v.fract() == 0.0 && v < u64::MAX as f64 => Ok(v as u64),
+            TomlValue::Int(_) | TomlValue::Float(_) => Err(item.err_msg("Value out of range for u64")),
+            _ => Err(item.err_type("integer"))
+        }
+    }
+}
+
+impl SerToml for usize {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        (*self as u64).ser_toml()
+    }
+}
+
+impl DeToml for usize {
+    fn de_toml(item: &TomlItem) -> Result<usize, TomlErr> {
+        let v = u64::de_toml(item) ?;
+        usize::try_from(v).map_err( | _ | item.err_msg("Value out of range for usize"))
+    }
+}
+
+impl SerToml for f64 {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Float(*self))
+    }
+}
+
+impl DeToml for f64 {
+    fn de_toml(item: &TomlItem) -> Result<f64, TomlErr> {
+        match item.value {
+            TomlValue::Float(v) => Ok(v),
+            TomlValue::Int(v) => Ok(v as f64),
+            _ => Err(item.err_type("float"))
+        }
+    }
+}
+
+impl SerToml for f32 {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Float(*self as f64))
+    }
+}
+
+impl DeToml for f32 {
+    fn de_toml(item: &TomlItem) -> Result<f32, TomlErr> {
+        Ok(f64::de_toml(item)? as f32)
+    }
+}
+
+impl SerToml for bool {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Bool(*self))
+    }
+}
+
+impl DeToml for bool {
+    fn de_toml(item: &TomlItem) -> Result<bool, TomlErr> {
+        match item.value {
+            TomlValue::Bool(v) => Ok(v),
+            _ => Err(item.err_type("bool"))
+        }
+    }
+}
+
+impl SerToml for LiveId {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        self.0.ser_toml()
+    }
+}
+
+impl DeToml for LiveId {
+    fn de_toml(item: &TomlItem) -> Result<LiveId, TomlErr> {
+        Ok(LiveId(u64::de_toml(item) ?))
+    }
+}
+
+impl SerToml for str {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Str(self.to_string()))
+    }
+}
+
+impl SerToml for String {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Str(self.clone()))
+    }
+}
+
+impl DeToml for String {
+    fn de_toml(item: &TomlItem) -> Result<String, TomlErr> {
+        item.expect_str().map( | s | s.to_string())
+    }
+}
+
+impl<T> SerToml for Option<T> where T: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        self.as_ref().and_then( | v | v.ser_toml())
+    }
+}
+
+// a key that isn't there is None, that is up to the table holding it
+impl<T> DeToml for Option<T> where T: DeToml {
+    fn de_toml(item: &TomlItem) -> Result<Self, TomlErr> {
+        Ok(Some(DeToml::de_toml(item) ?))
+    }
+}
+
+impl<T> SerToml for [T] where T: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Array(self.iter().filter_map( | v | v.ser_toml()).map(TomlItem::from).collect()))
+    }
+}
+
+impl<T> SerToml for Vec<T> where T: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        self.as_slice().ser_toml()
+    }
+}
+
+impl<T> DeToml for Vec<T> where T: DeToml {
+    fn de_toml(item: &TomlItem) -> Result<Vec<T>, TomlErr> {
+        item.expect_array()?.iter().map(DeToml::de_toml).collect()
+    }
+}
+
+impl<T, const N: usize> SerToml for [T; N] where T: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        self.as_slice().ser_toml()
+    }
+}
+
+impl<T, const N: usize> DeToml for [T; N] where T: DeToml {
+    fn de_toml(item: &TomlItem) -> Result<Self, TomlErr> {
+        let v: Vec<T> = DeToml::de_toml(item) ?;
+        let len = v.len();
+        v.try_into().map_err( | _ | item.err_msg(&format!("Expected an array of {} items got {}", N, len)))
+    }
+}
+
+macro_rules!impl_ser_de_toml_tuple {
+    ( $ ( $ t: ident $ n: tt), *) => {
+        impl< $ ( $ t), *> SerToml for ( $ ( $ t, ) *) where $ ( $ t: SerToml), * {
+            fn ser_toml(&self) -> Option<TomlValue> {
+                let mut a = Vec::new();
+                $ (if let Some(v) = self. $ n.ser_toml() {a.push(TomlItem::from(v))}) *
+                Some(TomlValue::Array(a))
+            }
+        }
+
+        impl< $ ( $ t), *> DeToml for ( $ ( $ t, ) *) where $ ( $ t: DeToml), * {
+            fn de_toml(item: &TomlItem) -> Result<Self, TomlErr> {
+                let a = item.expect_array() ?;
+                let len = [ $ ( $ n), *].len();
+                if a.len() != len {
+                    return Err(item.err_msg(&format!("Expected an array of {} items got {}", len, a.len())))
+                }
+                Ok(( $ ( $ t::de_toml(&a[ $ n]) ?, ) *))
+            }
+        }
+    }
+}
+
+impl_ser_de_toml_tuple!(A 0, B 1);
+impl_ser_de_toml_tuple!(A 0, B 1, C 2);
+impl_ser_de_toml_tuple!(A 0, B 1, C 2, D 3);
+
+// keys are sorted so the output doesn't change between runs
+impl<V> SerToml for HashMap<String, V> where V: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        let mut keys: Vec<&String> = self.keys().collect();
+        keys.sort();
+        let mut table = TomlTable::default();
+        for key in keys {
+            if let Some(v) = self[key].ser_toml() {
+                table.insert(key, v);
+            }
+        }
+        Some(TomlValue::Table(table))
+    }
+}
+
+impl<V> DeToml for HashMap<String, V> where V: DeToml {
+    fn de_toml(item: &TomlItem) -> Result<Self, TomlErr> {
+        let mut h = HashMap::new();
+        for (k, v) in &item.expect_table()?.entries {
+            h.insert(k.clone(), DeToml::de_toml(v) ?);
+        }
+        Ok(h)
+    }
+}
+
+impl<T> SerToml for Box<T> where T: SerToml {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        (**self).ser_toml()
+    }
+}
+
+impl<T> DeToml for Box<T> where T: DeToml {
+    fn de_toml(item: &TomlItem) -> Result<Box<T>, TomlErr> {
+        Ok(Box::new(DeToml::de_toml(item) ?))
+    }
+}
+
+impl SerToml for TomlDatetime {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Datetime(self.clone()))
+    }
+}
+
+impl DeToml for TomlDatetime {
+    fn de_toml(item: &TomlItem) -> Result<TomlDatetime, TomlErr> {
+        match &item.value {
+            TomlValue::Datetime(v) => Ok(v.clone()),
+            _ => Err(item.err_type("datetime"))
+        }
+    }
+}
+
+impl SerToml for TomlTable {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(TomlValue::Table(self.clone()))
+    }
+}
+
+impl DeToml for TomlTable {
+    fn de_toml(item: &TomlItem) -> Result<TomlTable, TomlErr> {
+        item.expect_table().cloned()
+    }
+}
+
+impl SerToml for TomlValue {
+    fn ser_toml(&self) -> Option<TomlValue> {
+        Some(self.clone())
+    }
+}
+
+impl DeToml for TomlValue {
+    fn de_toml(item: &TomlItem) -> Result<TomlValue, TomlErr> {
+        Ok(item.value.clone())
+    }
+}
@@ -4,6 +4,7 @@
 use crate::makepad_live_id::*;
 use crate::methods::*;
 use crate::object::*;
+use std::cmp::Ordering;
 use crate::*;
 
 #[derive(Default)]
@@ -180,6 +181,58 @@ pub fn to_string(&self, heap:&ScriptHeap, s:&mut String){
     }
 }
 
+// Resolves script slice arguments to a clamped range, negative indices
+// count from the end and nil means the start or end.
+pub fn script_slice_range(heap:&ScriptHeap, len:usize, start:ScriptValue, end:ScriptValue)->(usize, usize){
+    let resolve = |v:ScriptValue, def:usize|{
+        if v.is_nil(){
+            return def
+        }
+        let v = heap.cast_to_f64(v, ScriptIp::default()) as i64;
+        if v < 0{(len as i64 + v).max(0) as usize} else{(v as usize).min(len)}
+    };
+    let start = resolve(start, 0);
+    let end = resolve(end, len);
+    (start, end.max(start))
+}
+
+fn default_cmp(heap:&ScriptHeap, a:ScriptValue, b:ScriptValue)->Ordering{
+    match (a.as_f64(), b.as_f64()){
+        (Some(a), Some(b))=>a.total_cmp(&b),
+        (Some(_), None)=>Ordering::Less,
+        (None, Some(_))=>Ordering::Greater,
+        (None, None)=>{
+            heap.string_with(a, |heap, a|{
+                heap.string_with(b, |_, b| a.cmp(b))
+            }).flatten().unwrap_or(Ordering::Equal)
+        }
+    }
+}
+
+// a plain merge sort, the comparator runs script so it may well not be a
+// total order and slice::sort_by is allowed to panic on that
+fn merge_sort(values:&mut Vec<ScriptValue>, cmp:&mut dyn FnMut(ScriptValue, ScriptValue)->Ordering){
+    if values.len() <= 1{
+        return
+    }
+    let mut right = values.split_off(values.len() / 2);
+    merge_sort(values, cmp);
+    merge_sort(&mut right, cmp);
+    let left = std::mem::take(values);
+    values.reserve(left.len() + right.len());
+    let (mut l, mut r) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (l.peek(), r.peek()){
+        if cmp(*b, *a) == Ordering::Less{
+            values.push(r.next().unwrap());
+        }
+        else{
+            values.push(l.next().unwrap());
+        }
+    }
+    values.extend(l);
+    values.extend(r);
+}
+
 pub struct ScriptArrayData{
     pub tag: ScriptArrayTag,
     pub storage: ScriptArrayStorage
@@ -289,7 +342,136 @@ pub fn add_type_methods(tm: &mut ScriptTypeMethods, h: &mut ScriptHeap, native:&
             vm.thread.trap.err_not_impl()
         });
         
+        tm.add(h, native, script_args_def!(cb=NIL), ScriptValueType::REDUX_ARRAY, id!(map), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let fnptr = script_value!(vm, args.cb);
+                let mut out = Vec::new();
+                let mut i = 0;
+                while i < vm.heap.array_len(this){
+                    let value = script_array_index!(vm, this[i]);
+                    let ret = vm.call(fnptr, &[value]);
+                    if ret.is_err(){
+                        return ret;
+                    }
+                    out.push(ret);
+                    i += 1;
+                }
+                return vm.heap.new_array_from_vec(out).into()
+            }
+            vm.thread.trap.err_unexpected()
+        });
         
+        tm.add(h, native, script_args_def!(cb=NIL), ScriptValueType::REDUX_ARRAY, id!(filter), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let fnptr = script_value!(vm, args.cb);
+                let mut out = Vec::new();
+                let mut i = 0;
+                while i < vm.heap.array_len(this){
+                    let value = script_array_index!(vm, this[i]);
+                    let ret = vm.call(fnptr, &[value]);
+                    if ret.is_err(){
+                        return ret;
+                    }
+                    if vm.heap.cast_to_bool(ret){
+                        out.push(value);
+                    }
+                    i += 1;
+                }
+                return vm.heap.new_array_from_vec(out).into()
+            }
+            vm.thread.trap.err_unexpected()
+        });
+        
+        // without an initial value the first item is used as the accumulator
+        tm.add(h, native, script_args_def!(cb=NIL, init=NIL), ScriptValueType::REDUX_ARRAY, id!(reduce), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let fnptr = script_value!(vm, args.cb);
+                let mut acc = script_value!(vm, args.init);
+                let mut i = 0;
+                if acc.is_nil(){
+                    if vm.heap.array_len(this) == 0{
+                        return NIL
+                    }
+                    acc = script_array_index!(vm, this[0]);
+                    i = 1;
+                }
+                while i < vm.heap.array_len(this){
+                    let value = script_array_index!(vm, this[i]);
+                    acc = vm.call(fnptr, &[acc, value]);
+                    if acc.is_err(){
+                        return acc;
+                    }
+                    i += 1;
+                }
+                return acc
+            }
+            vm.thread.trap.err_unexpected()
+        });
+        
+        // sorts in place, stable. The comparator returns a number like a - b,
+        // without one numbers sort before strings and each sorts naturally
+        tm.add(h, native, script_args_def!(cb=NIL), ScriptValueType::REDUX_ARRAY, id!(sort), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let fnptr = script_value!(vm, args.cb);
+                let len = vm.heap.array_len(this);
+                let mut values:Vec<ScriptValue> = (0..len).map(|i| script_array_index!(vm, this[i])).collect();
+                let mut err = None;
+                merge_sort(&mut values, &mut |a, b|{
+                    if err.is_some(){
+                        return Ordering::Equal
+                    }
+                    if fnptr.is_nil(){
+                        return default_cmp(vm.heap, a, b)
+                    }
+                    let ret = vm.call(fnptr, &[a, b]);
+                    if ret.is_err(){
+                        err = Some(ret);
+                        return Ordering::Equal
+                    }
+                    vm.heap.cast_to_f64(ret, vm.thread.trap.ip).partial_cmp(&0.0).unwrap_or(Ordering::Equal)
+                });
+                if let Some(err) = err{
+                    return err
+                }
+                if let Some(storage) = vm.heap.array_mut(this, &vm.thread.trap){
+                    for (i, value) in values.into_iter().enumerate(){
+                        storage.set_index(i, value);
+                    }
+                    return this.into()
+                }
+                return NIL
+            }
+            vm.thread.trap.err_unexpected()
+        });
+        
+        tm.add(h, native, script_args_def!(start=NIL, end=NIL), ScriptValueType::REDUX_ARRAY, id!(slice), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let start = script_value!(vm, args.start);
+                let end = script_value!(vm, args.end);
+                let (start, end) = script_slice_range(vm.heap, vm.heap.array_len(this), start, end);
+                let values = (start..end).map(|i| script_array_index!(vm, this[i])).collect();
+                return vm.heap.new_array_from_vec(values).into()
+            }
+            vm.thread.trap.err_unexpected()
+        });
+        
+        tm.add(h, native, script_args_def!(sep=NIL), ScriptValueType::REDUX_ARRAY, id!(join), |vm, args|{
+            if let Some(this) = script_value!(vm, args.this).as_array(){
+                let sep = script_value!(vm, args.sep);
+                let sep = vm.heap.string_with(sep, |_, s| s.to_string()).unwrap_or_default();
+                return vm.heap.new_string_with(|heap, out|{
+                    for i in 0..heap.array_len(this){
+                        if i > 0{
+                            out.push_str(&sep);
+                        }
+                        if let Some(value) = heap.array_ref(this).index(i){
+                            heap.cast_to_string(value, out);
+                        }
+                    }
+                })
+            }
+            vm.thread.trap.err_unexpected()
+        });
     }
     
     pub fn clear(&mut self){
@@ -0,0 +1,224 @@
+use crate::value::*;
+use crate::heap::*;
+use crate::thread::*;
+use crate::object::*;
+use crate::array::*;
+use crate::vm::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Resource limits for running untrusted scripts. Running out of budget or
+// memory, or being cancelled, aborts the whole run back to the host: these
+// errors can't be caught with try, otherwise a script could simply retry.
+
+// how many instructions we run between checking the cancel flag
+const CANCEL_CHECK_INTERVAL: u64 = 1024;
+// walking the heap to measure it is O(heap), so we at least do this much
+// work between measurements
+const MEMORY_CHECK_INTERVAL: u64 = 64 * 1024;
+
+/// Cancels a running script from any thread, for instance a watchdog timer.
+#[derive(Clone, Default)]
+pub struct ScriptCancel(Arc<AtomicBool>);
+
+impl ScriptCancel{
+    pub fn cancel(&self){
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self)->bool{
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset(&self){
+        self.0.store(false, Ordering::Relaxed)
+    }
+}
+
+#[derive(Default)]
+pub struct ScriptBudget{
+    pub max_instructions: Option<u64>,
+    pub(crate) used: u64,
+    pub(crate) next_check: u64,
+    pub(crate) next_memory_check: u64,
+    pub(crate) run_depth: usize,
+    pub(crate) abort: Option<ScriptValue>,
+    pub(crate) cancel: ScriptCancel,
+}
+
+impl ScriptBudget{
+    fn start(&mut self){
+        self.used = 0;
+        self.next_memory_check = 0;
+        self.abort = None;
+        self.cancel.reset();
+        self.schedule();
+    }
+
+    fn schedule(&mut self){
+        let next = self.used + CANCEL_CHECK_INTERVAL;
+        self.next_check = match self.max_instructions{
+            Some(max)=>next.min(max),
+            None=>next
+        };
+    }
+
+    // counts an instruction, returns true when the slow path has to run
+    #[inline(always)]
+    pub(crate) fn tick(&mut self)->bool{
+        self.used += 1;
+        self.used >= self.next_check
+    }
+}
+
+#[derive(Default)]
+pub struct ScriptHeapLimits{
+    pub max_objects: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub(crate) exceeded: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScriptHeapUsage{
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub bytes: usize,
+}
+
+impl ScriptHeap{
+    pub fn set_limits(&mut self, max_objects:Option<usize>, max_bytes:Option<usize>){
+        self.limits.max_objects = max_objects;
+        self.limits.max_bytes = max_bytes;
+        self.limits.exceeded = false;
+    }
+
+    // called when the object table grows, so the free list is empty and
+    // the table length is the live count
+    pub(crate) fn check_object_limit(&mut self){
+        if let Some(max) = self.limits.max_objects{
+            if self.objects.len() - 1 > max{
+                self.limits.exceeded = true;
+            }
+        }
+    }
+
+    /// An estimate of the live heap, it walks every allocation
+    pub fn usage(&self)->ScriptHeapUsage{
+        let mut usage = ScriptHeapUsage::default();
+        for object in &self.objects{
+            if object.tag.is_alloced(){
+                usage.objects += 1;
+                usage.bytes += std::mem::size_of::<ScriptObjectData>()
+                    + object.map.capacity() * std::mem::size_of::<(ScriptValue, ScriptMapValue)>()
+                    + object.vec.capacity() * std::mem::size_of::<ScriptVecValue>();
+            }
+        }
+        for array in &self.arrays{
+            if array.tag.is_alloced(){
+                usage.arrays += 1;
+                usage.bytes += std::mem::size_of::<ScriptArrayData>() + match &array.storage{
+                    ScriptArrayStorage::ScriptValue(v)=>v.capacity() * 8,
+                    ScriptArrayStorage::F32(v)=>v.capacity() * 4,
+                    ScriptArrayStorage::U32(v)=>v.capacity() * 4,
+                    ScriptArrayStorage::U16(v)=>v.capacity() * 2,
+                    ScriptArrayStorage::U8(v)=>v.capacity(),
+                };
+            }
+        }
+        for string in self.strings.iter().flatten(){
+            usage.strings += 1;
+            usage.bytes += string.string.0.len();
+        }
+        usage
+    }
+
+    fn over_limits(&mut self)->bool{
+        if self.limits.exceeded{
+            return true
+        }
+        if let Some(max) = self.limits.max_objects{
+            if self.objects.len() - 1 - self.objects_free.len() > max{
+                self.limits.exceeded = true;
+            }
+        }
+        if let Some(max) = self.limits.max_bytes{
+            if self.usage().bytes > max{
+                self.limits.exceeded = true;
+            }
+        }
+        self.limits.exceeded
+    }
+}
+
+impl ScriptThread{
+    /// Limits the number of instructions a single run from the host can
+    /// execute, None means unlimited.
+    pub fn set_instruction_budget(&mut self, max_instructions:Option<u64>){
+        self.budget.max_instructions = max_instructions;
+        self.budget.schedule();
+    }
+
+    pub fn instructions_used(&self)->u64{
+        self.budget.used
+    }
+
+    /// A handle that aborts the script currently running on this thread
+    pub fn cancel_handle(&self)->ScriptCancel{
+        self.budget.cancel.clone()
+    }
+
+    pub(crate) fn budget_enter(&mut self, heap:&mut ScriptHeap){
+        if self.budget.run_depth == 0{
+            self.budget.start();
+            // measured again on the first check
+            heap.limits.exceeded = false;
+        }
+        self.budget.run_depth += 1;
+    }
+
+    pub(crate) fn budget_leave(&mut self, entry_calls:usize, heap:&mut ScriptHeap){
+        self.budget.run_depth -= 1;
+        // unwind everything the aborted run left behind
+        if self.budget.run_depth == 0 && self.budget.abort.is_some() && entry_calls > 0 && self.calls.len() >= entry_calls{
+            self.calls.truncate(entry_calls);
+            let call = self.calls.pop().unwrap();
+            self.truncate_bases(call.bases, heap);
+        }
+    }
+
+    // the slow path of the instruction counter, returns the error to abort with
+    pub(crate) fn budget_check(&mut self, heap:&mut ScriptHeap, code:&ScriptCode)->Option<ScriptValue>{
+        if let Some(abort) = self.budget.abort{
+            return Some(abort)
+        }
+        let ip = self.trap.ip;
+        let exhausted = self.budget.cancel.is_cancelled() ||
+            self.budget.max_instructions.map(|max| self.budget.used >= max).unwrap_or(false);
+        let abort = if exhausted{
+            Some(ScriptValue::err_budget_exhausted(ip))
+        }
+        else if heap.limits.exceeded || (self.budget.used >= self.budget.next_memory_check && {
+            // space out the measurements by the heap size so this stays
+            // amortised O(1) per instruction
+            self.budget.next_memory_check = self.budget.used + MEMORY_CHECK_INTERVAL.max(heap.objects.len() as u64);
+            heap.over_limits()
+        }){
+            Some(ScriptValue::err_out_of_memory(ip))
+        }
+        else{
+            None
+        };
+        if let Some(value) = abort{
+            if let Some(loc) = code.ip_to_loc(ip){
+                println!("{} {}", value, loc);
+            }
+            self.budget.abort = abort;
+            self.budget.next_check = 0;
+        }
+        else{
+            self.budget.schedule();
+        }
+        abort
+    }
+}
@@ -0,0 +1,361 @@
+use crate::value::*;
+use crate::heap::*;
+use crate::thread::*;
+use crate::vm::*;
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The script step debugger. A thread with a ScriptDebug attached checks every
+// opcode against its breakpoints and step state, and calls the handler
+// synchronously when it needs to pause. The handler decides how to resume,
+// so a host can block on a UI, a socket or a test script while the VM waits.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptBreakpoint{
+    pub file: String,
+    pub line: u32,
+}
+
+impl ScriptBreakpoint{
+    // files coming from studio are workspace relative, the ones in the
+    // script blocks come from file!() so we match on whole trailing segments
+    fn matches(&self, file:&str, line:u32)->bool{
+        self.line == line && (Self::path_ends_with(file, &self.file) || Self::path_ends_with(&self.file, file))
+    }
+
+    fn path_ends_with(path:&str, suffix:&str)->bool{
+        if suffix.is_empty() || !path.ends_with(suffix){
+            return false
+        }
+        let rest = &path[..path.len() - suffix.len()];
+        rest.is_empty() || rest.ends_with('/') || suffix.starts_with('/')
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptDebugCommand{
+    Continue,
+    StepInto,
+    StepOver,
+    StepOut,
+    Detach,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptPauseReason{
+    Breakpoint,
+    Step,
+    PauseRequest,
+    Error(ScriptValue),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScriptStep{
+    Run,
+    Into,
+    Over{depth:usize},
+    Out{depth:usize},
+}
+
+pub struct ScriptStackFrame{
+    pub depth: usize,
+    pub ip: Option<ScriptIp>,
+    pub loc: Option<ScriptLoc>,
+}
+
+pub struct ScriptDebugVar{
+    pub name: String,
+    pub value: String,
+}
+
+pub trait ScriptDebugHandler{
+    fn on_pause(&mut self, paused:&mut ScriptPaused)->ScriptDebugCommand;
+}
+
+// opcode index to source line, per body. ip_to_loc rescans the source on
+// every call which is fine for an error but not for every opcode
+struct ScriptBodyLines{
+    file: String,
+    lines: Vec<u32>,
+}
+
+impl ScriptBodyLines{
+    fn new(body:&ScriptBody)->Self{
+        let token_lines = body.tokenizer.token_lines();
+        let (file, offset) = match &body.source{
+            ScriptSource::Block{block}=>(block.file.clone(), block.line as u32 + 1),
+            ScriptSource::Streaming{..}=>("generated".into(), 0)
+        };
+        let mut lines:Vec<u32> = body.parser.source_map.iter().map(|index|{
+            index.and_then(|index| token_lines.get(index as usize)).map(|line| line + offset).unwrap_or(0)
+        }).collect();
+        // the parser sometimes maps a single opcode to the lookahead token on
+        // the next line, fold those back so stepping doesnt bounce
+        for i in 1..lines.len().saturating_sub(1){
+            if lines[i - 1] == lines[i + 1] && lines[i] != lines[i - 1]{
+                lines[i] = lines[i - 1];
+            }
+        }
+        Self{file, lines}
+    }
+}
+
+pub struct ScriptDebug{
+    pub breakpoints: Vec<ScriptBreakpoint>,
+    pub pause_on_error: bool,
+    pause_request: Arc<AtomicBool>,
+    step: ScriptStep,
+    frame_lines: Vec<Option<(u16, u32)>>,
+    body_lines: Vec<Option<ScriptBodyLines>>,
+    handler: Box<dyn ScriptDebugHandler>,
+}
+
+impl ScriptDebug{
+    pub fn new(handler:Box<dyn ScriptDebugHandler>)->Self{
+        Self{
+            breakpoints: Vec::new(),
+            pause_on_error: true,
+            pause_request: Default::default(),
+            step: ScriptStep::Run,
+            frame_lines: Vec::new(),
+            body_lines: Vec::new(),
+            handler,
+        }
+    }
+
+    /// A flag that pauses the thread at the next opcode when set, it can be
+    /// set from any thread.
+    pub fn pause_handle(&self)->Arc<AtomicBool>{
+        self.pause_request.clone()
+    }
+
+    pub fn request_pause(&self){
+        self.pause_request.store(true, Ordering::Relaxed);
+    }
+
+    /// Forget the cached line tables, call this when bodies are reparsed
+    pub fn invalidate_lines(&mut self){
+        self.body_lines.clear();
+    }
+
+    fn line_of<'a>(body_lines:&'a mut Vec<Option<ScriptBodyLines>>, code:&ScriptCode, ip:ScriptIp)->Option<(&'a str, u32)>{
+        let index = ip.body as usize;
+        if index >= body_lines.len(){
+            body_lines.resize_with(index + 1, ||None);
+        }
+        let bodies = code.bodies.borrow();
+        let body = bodies.get(index)?;
+        let stale = match &body_lines[index]{
+            Some(lines)=>lines.lines.len() != body.parser.source_map.len(),
+            None=>true
+        };
+        if stale{
+            body_lines[index] = Some(ScriptBodyLines::new(body));
+        }
+        let lines = body_lines[index].as_ref().unwrap();
+        match lines.lines.get(ip.index as usize){
+            Some(0) | None=>None,
+            Some(line)=>Some((&lines.file, *line))
+        }
+    }
+
+    fn should_pause(&mut self, code:&ScriptCode, ip:ScriptIp, depth:usize)->Option<ScriptPauseReason>{
+        if depth == 0{
+            return None
+        }
+        self.frame_lines.resize(depth, None);
+        let request = self.pause_request.swap(false, Ordering::Relaxed);
+        let step_out = matches!(self.step, ScriptStep::Out{depth:out_depth} if depth < out_depth);
+        if !request && !step_out && self.step == ScriptStep::Run && self.breakpoints.is_empty(){
+            return None
+        }
+        let Some((file, line)) = Self::line_of(&mut self.body_lines, code, ip) else{
+            return if request{Some(ScriptPauseReason::PauseRequest)}
+            else if step_out{Some(ScriptPauseReason::Step)}
+            else{None}
+        };
+        let breakpoint = self.breakpoints.iter().any(|bp| bp.matches(file, line));
+        // we only stop when a frame moves to a new line, so returning into a
+        // half evaluated line doesnt count as arriving on it
+        let slot = &mut self.frame_lines[depth - 1];
+        let new_line = *slot != Some((ip.body, line));
+        *slot = Some((ip.body, line));
+        if request{
+            return Some(ScriptPauseReason::PauseRequest)
+        }
+        if step_out{
+            return Some(ScriptPauseReason::Step)
+        }
+        if !new_line{
+            return None
+        }
+        match self.step{
+            ScriptStep::Into=>Some(ScriptPauseReason::Step),
+            ScriptStep::Over{depth:over_depth} if depth <= over_depth=>Some(ScriptPauseReason::Step),
+            _ if breakpoint=>Some(ScriptPauseReason::Breakpoint),
+            _=>None
+        }
+    }
+    
+    fn pause(&mut self, reason:ScriptPauseReason, thread:&ScriptThread, heap:&ScriptHeap, code:&ScriptCode, host:&mut dyn Any)->ScriptDebugCommand{
+        let cmd = self.handler.on_pause(&mut ScriptPaused{
+            reason,
+            host,
+            thread,
+            heap,
+            code,
+            breakpoints: &mut self.breakpoints,
+        });
+        let depth = thread.calls.len();
+        self.step = match cmd{
+            ScriptDebugCommand::Continue | ScriptDebugCommand::Detach=>ScriptStep::Run,
+            ScriptDebugCommand::StepInto=>ScriptStep::Into,
+            ScriptDebugCommand::StepOver=>ScriptStep::Over{depth},
+            ScriptDebugCommand::StepOut=>ScriptStep::Out{depth},
+        };
+        cmd
+    }
+}
+
+impl ScriptThread{
+    pub fn attach_debugger(&mut self, debug:ScriptDebug){
+        self.debug = Some(Box::new(debug));
+    }
+
+    pub fn detach_debugger(&mut self)->Option<Box<ScriptDebug>>{
+        self.debug.take()
+    }
+
+    pub fn debugger(&mut self)->Option<&mut ScriptDebug>{
+        self.debug.as_deref_mut()
+    }
+
+    pub(crate) fn debug_check(&mut self, heap:&ScriptHeap, code:&ScriptCode, host:&mut dyn Any){
+        let Some(mut debug) = self.debug.take() else{return};
+        let depth = self.calls.len();
+        if let Some(reason) = debug.should_pause(code, self.trap.ip, depth){
+            if debug.pause(reason, self, heap, code, host) == ScriptDebugCommand::Detach{
+                return
+            }
+        }
+        self.debug = Some(debug);
+    }
+
+    pub(crate) fn debug_error(&mut self, heap:&ScriptHeap, code:&ScriptCode, host:&mut dyn Any, value:ScriptValue){
+        let Some(mut debug) = self.debug.take() else{return};
+        if debug.pause_on_error && debug.pause(ScriptPauseReason::Error(value), self, heap, code, host) == ScriptDebugCommand::Detach{
+            return
+        }
+        self.debug = Some(debug);
+    }
+}
+
+/// The view a debug handler gets of a paused thread.
+pub struct ScriptPaused<'a>{
+    pub reason: ScriptPauseReason,
+    pub host: &'a mut dyn Any,
+    pub thread: &'a ScriptThread,
+    pub heap: &'a ScriptHeap,
+    pub code: &'a ScriptCode,
+    pub breakpoints: &'a mut Vec<ScriptBreakpoint>,
+}
+
+impl<'a> ScriptPaused<'a>{
+
+    pub fn ip(&self)->ScriptIp{
+        self.thread.trap.ip
+    }
+
+    pub fn loc(&self)->Option<ScriptLoc>{
+        self.code.ip_to_loc(self.thread.trap.ip)
+    }
+
+    pub fn depth(&self)->usize{
+        self.thread.calls.len()
+    }
+
+    /// The call stack, innermost frame first. Frames that were entered from
+    /// a native function have no known ip.
+    pub fn call_stack(&self)->Vec<ScriptStackFrame>{
+        let calls = &self.thread.calls;
+        let mut frames = Vec::new();
+        for depth in (0..calls.len()).rev(){
+            let ip = if depth + 1 == calls.len(){
+                Some(self.thread.trap.ip)
+            }
+            else{
+                // the return ip points past the call opcode
+                calls[depth + 1].return_ip.map(|ip| ScriptIp{body:ip.body, index:ip.index.saturating_sub(1)})
+            };
+            frames.push(ScriptStackFrame{
+                depth,
+                ip,
+                loc: ip.and_then(|ip| self.code.ip_to_loc(ip)),
+            });
+        }
+        frames
+    }
+
+    fn frame_top(&self, depth:usize, f:impl Fn(&StackBases)->usize)->Option<usize>{
+        let calls = &self.thread.calls;
+        if depth >= calls.len(){
+            return None
+        }
+        if depth + 1 == calls.len(){
+            return Some(usize::MAX)
+        }
+        f(&calls[depth + 1].bases).checked_sub(1)
+    }
+
+    /// The innermost scope object of the frame at `depth`
+    pub fn scope(&self, depth:usize)->Option<ScriptValue>{
+        let top = self.frame_top(depth, |b| b.scope)?;
+        let scopes = &self.thread.scopes;
+        scopes.get(top.min(scopes.len().wrapping_sub(1))).map(|s| (*s).into())
+    }
+
+    /// The `me` object of the frame at `depth`
+    pub fn me(&self, depth:usize)->Option<ScriptValue>{
+        let top = self.frame_top(depth, |b| b.mes)?;
+        let mes = &self.thread.mes;
+        mes.get(top.min(mes.len().wrapping_sub(1))).map(|me| match me{
+            ScriptMe::Object(v)=>(*v).into(),
+            ScriptMe::Call{args,..}=>(*args).into(),
+            ScriptMe::Array(v)=>(*v).into(),
+        })
+    }
+
+    /// All variables visible from the frame at `depth`, walking up the scope
+    /// chain. Shadowed names and functions are left out.
+    pub fn scope_vars(&self, depth:usize)->Vec<ScriptDebugVar>{
+        let mut vars = Vec::new();
+        if let Some(scope) = self.scope(depth){
+            self.heap.debug_fields(scope, true, &mut |key, value|{
+                let name = key.to_string();
+                if vars.iter().any(|v:&ScriptDebugVar| v.name == name){
+                    return
+                }
+                vars.push(ScriptDebugVar{name, value: self.value_to_string(value)});
+            });
+        }
+        vars
+    }
+
+    /// The fields of the `me` object of the frame at `depth`
+    pub fn me_vars(&self, depth:usize)->Vec<ScriptDebugVar>{
+        let mut vars = Vec::new();
+        if let Some(me) = self.me(depth){
+            self.heap.debug_fields(me, false, &mut |key, value|{
+                vars.push(ScriptDebugVar{name: key.to_string(), value: self.value_to_string(value)});
+            });
+        }
+        vars
+    }
+
+    pub fn value_to_string(&self, value:ScriptValue)->String{
+        let mut out = String::new();
+        self.heap.to_debug_string(value, 2, &mut out);
+        out
+    }
+}
@@ -6,6 +6,7 @@
 use crate::traits::*;
 use crate::array::*;
 use crate::gc::*;
+use crate::budget::*;
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::fmt::Write;
@@ -30,6 +31,7 @@ pub struct ScriptHeap{
     pub(crate) type_check: Vec<ScriptTypeCheck>,
     pub(crate) type_index: HashMap<ScriptTypeId, ScriptTypeIndex>,
     
+    pub(crate) limits: ScriptHeapLimits,
 }
 
 impl ScriptHeap{
@@ -72,6 +74,7 @@ pub fn new_object(&mut self)->ScriptObject{
             object.tag.set_alloced();
             object.proto = id!(object).into();
             self.objects.push(object);
+            self.check_object_limit();
             ScriptObject{index: index as _}
         }
     }
@@ -125,6 +128,7 @@ pub fn new_with_proto(&mut self, proto:ScriptValue)->ScriptObject{
                 object.vec.extend_from_slice(&proto_object.vec);
             }
             self.objects.push(object);
+            self.check_object_limit();
             ScriptObject{index: index as _}
         }
     }
@@ -403,6 +407,14 @@ pub fn new_array_from_vec_u8(&mut self, data:Vec<u8>)->ScriptArray{
         ptr
     }
     
+    pub fn new_array_from_vec(&mut self, data:Vec<ScriptValue>)->ScriptArray{
+        let ptr = self.new_array();
+        let array = &mut self.arrays[ptr.index as usize];
+        array.tag.set_dirty();
+        array.storage = ScriptArrayStorage::ScriptValue(data);
+        ptr
+    }
+
     pub fn array_mut(&mut self, array:ScriptArray,trap:&ScriptTrap)->Option<&mut ScriptArrayStorage>{
         let array = &mut self.arrays[array.index as usize];
         if array.tag.is_frozen(){
@@ -1560,6 +1572,83 @@ pub fn print(&self, value:ScriptValue){
         }
     }
     
+    /// Like print but into a string and cut off at `depth` levels of nesting,
+    /// used by the debugger to show values.
+    pub fn to_debug_string(&self, value:ScriptValue, depth:usize, out:&mut String){
+        if let Some(obj) = value.as_object(){
+            let object = &self.objects[obj.index as usize];
+            if object.tag.is_script_fn(){
+                out.push_str("Fn");
+            }
+            else if object.tag.is_native_fn(){
+                out.push_str("Native");
+            }
+            if depth == 0{
+                out.push_str("{..}");
+                return
+            }
+            out.push('{');
+            let mut first = true;
+            self.debug_fields(value, false, &mut |key, value|{
+                if !first{out.push_str(", ")}
+                if key != NIL{
+                    write!(out, "{}:", key).ok();
+                }
+                self.to_debug_string(value, depth - 1, out);
+                first = false;
+            });
+            out.push('}');
+        }
+        else if let Some(arr) = value.as_array(){
+            let array = &self.arrays[arr.index as usize];
+            if depth == 0{
+                out.push_str("[..]");
+                return
+            }
+            out.push('[');
+            for i in 0..array.storage.len(){
+                if i!=0{out.push_str(", ")}
+                self.to_debug_string(array.storage.index(i).unwrap(), depth - 1, out);
+            }
+            out.push(']');
+        }
+        else if let Some(s) = value.as_string(){
+            let s = if let Some(s) = &self.strings[s.index as usize]{&s.string.0}else{""};
+            write!(out, "\"{}\"", s).ok();
+        }
+        else if value.as_inline_string(|s|{
+            write!(out, "\"{}\"", s).ok();
+        }).is_some(){}
+        else {
+            write!(out, "{}", value).ok();
+        }
+    }
+    
+    /// Calls `f` for every field of an object, for a scope also walking up
+    /// the proto chain. Functions are skipped.
+    pub fn debug_fields(&self, value:ScriptValue, walk_protos:bool, f:&mut dyn FnMut(ScriptValue, ScriptValue)){
+        let Some(mut ptr) = value.as_object() else{return};
+        loop{
+            let object = &self.objects[ptr.index as usize];
+            let is_fn = |value:ScriptValue| value.as_object().map(|obj| self.objects[obj.index as usize].tag.is_fn()).unwrap_or(false);
+            object.map_iter(|key, value|{
+                if !is_fn(value){f(key, value)}
+            });
+            for kv in object.vec.iter(){
+                if !is_fn(kv.value){f(kv.key, kv.value)}
+            }
+            if !walk_protos{
+                break
+            }
+            if let Some(next_ptr) = object.proto.as_object(){
+                ptr = next_ptr
+            }
+            else{
+                break
+            }
+        }
+    }
+    
     pub fn to_json(&mut self, value:ScriptValue)->ScriptValue{
         self.new_string_with(|heap, s|{
             heap.to_json_inner(value, s);
@@ -0,0 +1,255 @@
+use crate::value::*;
+use crate::heap::*;
+use crate::thread::*;
+use crate::vm::*;
+use crate::opcode::*;
+use crate::gc::*;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+// File based script modules. `use "./util.mps".lerp` loads util.mps once, runs
+// it in its own scope and binds `lerp` from that scope. Imports are resolved
+// and run before the importing script starts, so a script never runs with a
+// missing import and the code bodies are never touched while a thread is
+// running them.
+
+/// Finds and reads module sources for the VM, the platform implements this
+/// on top of the filesystem.
+pub trait ScriptModuleLoader{
+    /// Turns an import path into a canonical module path. `package` is the
+    /// package root of the importing script and `from` its file.
+    fn resolve(&mut self, package:&str, from:&str, path:&str)->Option<String>;
+    fn load(&mut self, path:&str)->Option<String>;
+    /// Changes whenever the source changes, for instance the modification time
+    fn stamp(&mut self, path:&str)->Option<u64>;
+}
+
+pub struct ScriptModule{
+    pub path: String,
+    pub package: String,
+    pub body_id: u16,
+    pub stamp: Option<u64>,
+    pub imports: Vec<String>,
+    pub(crate) exports: Option<ScriptObjectRef>,
+    pub(crate) stale: bool,
+}
+
+#[derive(Default)]
+pub struct ScriptModules{
+    pub(crate) loader: Option<Box<dyn ScriptModuleLoader>>,
+    pub(crate) modules: HashMap<String, ScriptModule>,
+    // which module every use site imports from
+    pub(crate) imports: BTreeMap<ScriptIp, String>,
+    loading: Vec<String>,
+}
+
+impl ScriptModules{
+    pub fn module(&self, path:&str)->Option<&ScriptModule>{
+        self.modules.get(path)
+    }
+
+    pub(crate) fn exports_for(&self, ip:ScriptIp)->Option<ScriptObject>{
+        let path = self.imports.get(&ip)?;
+        self.modules.get(path)?.exports.as_ref().map(|e| e.as_obj())
+    }
+}
+
+impl ScriptThread{
+    // runs a module body to completion and returns its top level scope,
+    // leaving the thread as we found it
+    pub(crate) fn run_module(&mut self, heap:&mut ScriptHeap, code:&ScriptCode, host:&mut dyn std::any::Any, body_id:u16)->(ScriptValue, ScriptObject){
+        let calls = self.calls.len();
+        let scopes = self.scopes.len();
+        let mes = self.mes.len();
+        let stack = self.stack.len();
+        let ip = self.trap.ip;
+        let value = self.run_root(heap, code, host, body_id);
+        let scope = if self.scopes.len() > scopes{
+            *self.scopes.last().unwrap()
+        }
+        else{
+            code.bodies.borrow()[body_id as usize].scope
+        };
+        self.calls.truncate(calls);
+        self.scopes.truncate(scopes);
+        self.mes.truncate(mes);
+        self.stack.truncate(stack);
+        self.trap.ip = ip;
+        (value, scope)
+    }
+}
+
+impl<'a> ScriptVm<'a>{
+    pub fn set_module_loader(&mut self, loader:Box<dyn ScriptModuleLoader>){
+        self.code.modules.borrow_mut().loader = Some(loader);
+    }
+
+    // a use with a string as its object is an import
+    fn import_sites(&self, body_id:u16)->Vec<(ScriptIp, String)>{
+        let bodies = self.code.bodies.borrow();
+        let opcodes = &bodies[body_id as usize].parser.opcodes;
+        let mut sites = Vec::new();
+        for i in 2..opcodes.len(){
+            if let Some((Opcode::USE, _)) = opcodes[i].as_opcode(){
+                if let Some(path) = self.heap.string_with(opcodes[i - 2], |_, s| s.to_string()){
+                    sites.push((ScriptIp{body: body_id, index: i as u32}, path));
+                }
+            }
+        }
+        sites
+    }
+
+    fn import_error(&self, ip:ScriptIp, err:ScriptValue, msg:&str)->ScriptValue{
+        if let Some(loc) = self.code.ip_to_loc(ip){
+            println!("{}: {} {}", err, msg, loc);
+        }
+        err
+    }
+
+    /// Loads everything the body imports, running modules that aren't loaded yet
+    pub(crate) fn import_modules(&mut self, body_id:u16)->Result<(), ScriptValue>{
+        let sites = self.import_sites(body_id);
+        self.code.modules.borrow_mut().imports.retain(|ip, _| ip.body != body_id);
+        if sites.is_empty(){
+            return Ok(())
+        }
+        let (package, from) = match &self.code.bodies.borrow()[body_id as usize].source{
+            ScriptSource::Block{block}=>(block.cargo_manifest_path.clone(), block.file.clone()),
+            ScriptSource::Streaming{..}=>(String::new(), String::new())
+        };
+        for (ip, path) in sites{
+            let resolved = match &mut self.code.modules.borrow_mut().loader{
+                Some(loader)=>loader.resolve(&package, &from, &path),
+                None=>None
+            };
+            let Some(resolved) = resolved else{
+                return Err(self.import_error(ip, ScriptValue::err_not_found(ip), &format!("script module {} not found", path)))
+            };
+            self.load_module(&resolved, &package, ip)?;
+            self.code.modules.borrow_mut().imports.insert(ip, resolved);
+        }
+        Ok(())
+    }
+
+    fn load_module(&mut self, path:&str, package:&str, ip:ScriptIp)->Result<(), ScriptValue>{
+        let mut modules = self.code.modules.borrow_mut();
+        if let Some(pos) = modules.loading.iter().position(|p| p == path){
+            let chain = modules.loading[pos..].join(" -> ");
+            drop(modules);
+            return Err(self.import_error(ip, ScriptValue::err_import_cycle(ip), &format!("{} -> {}", chain, path)))
+        }
+        let (package, body_id) = match modules.modules.get(path){
+            Some(module) if !module.stale && module.exports.is_some()=>return Ok(()),
+            Some(module)=>(module.package.clone(), Some(module.body_id)),
+            None=>(package.to_string(), None)
+        };
+        let loader = modules.loader.as_mut().unwrap();
+        let stamp = loader.stamp(path);
+        let Some(mut source) = loader.load(path) else{
+            drop(modules);
+            return Err(self.import_error(ip, ScriptValue::err_file_system(ip), &format!("can't read script module {}", path)))
+        };
+        // the parser needs a terminator to flush the last statement, the
+        // script! macro appends the same
+        source.push(';');
+        modules.loading.push(path.to_string());
+        drop(modules);
+
+        let block = ScriptBlock{
+            cargo_manifest_path: package.clone(),
+            module_path: path.to_string(),
+            file: path.to_string(),
+            code: source,
+            ..Default::default()
+        };
+        // a reload parses into the body of the previous version
+        let body_id = match body_id{
+            Some(body_id)=>{
+                self.replace_script_block(body_id, block);
+                body_id
+            }
+            None=>self.add_script_block(block)
+        };
+        self.parse_body(body_id);
+        let result = self.import_modules(body_id).map(|_|{
+            self.thread.run_module(self.heap, self.code, self.host, body_id)
+        });
+
+        let mut modules = self.code.modules.borrow_mut();
+        modules.loading.pop();
+        let imports = modules.imports.range(ScriptIp{body: body_id, index: 0}..=ScriptIp{body: body_id, index: u32::MAX}).map(|(_, p)| p.clone()).collect();
+        let (value, scope) = match result{
+            Ok(r)=>r,
+            Err(err)=>(err, ScriptObject::default()),
+        };
+        if value.is_err(){
+            // keep serving the old version if there is one, until the
+            // source changes again
+            if let Some(module) = modules.modules.get_mut(path){
+                module.stale = false;
+                module.stamp = stamp;
+            }
+            return Err(value)
+        }
+        let exports = Some(self.heap.new_object_ref(scope));
+        modules.modules.insert(path.to_string(), ScriptModule{
+            path: path.to_string(),
+            package,
+            body_id,
+            stamp,
+            imports,
+            exports,
+            stale: false,
+        });
+        Ok(())
+    }
+
+    /// Reloads the modules among the `changed` files whose source changed,
+    /// together with the modules importing them. A changed file matches a
+    /// module when it is its path or a trailing part of it. Scripts that
+    /// aren't modules keep the values they imported until they run again.
+    /// Returns the reloaded module paths.
+    pub fn reload_modules(&mut self, changed:&[String])->Vec<String>{
+        let stale = {
+            let mut modules = self.code.modules.borrow_mut();
+            let modules = &mut *modules;
+            let Some(loader) = &mut modules.loader else{
+                return Vec::new()
+            };
+            let mut stale:Vec<String> = modules.modules.values()
+                .filter(|module| changed.iter().any(|file| module_matches(&module.path, file)))
+                .filter(|module| loader.stamp(&module.path) != module.stamp)
+                .map(|module| module.path.clone())
+                .collect();
+            loop{
+                let dependents:Vec<String> = modules.modules.values().filter(|module|{
+                    !stale.contains(&module.path) && module.imports.iter().any(|i| stale.contains(i))
+                }).map(|module| module.path.clone()).collect();
+                if dependents.is_empty(){
+                    break
+                }
+                stale.extend(dependents);
+            }
+            for path in &stale{
+                modules.modules.get_mut(path).unwrap().stale = true;
+            }
+            stale
+        };
+        // loading a module loads its stale imports first, so this goes in
+        // dependency order
+        for path in &stale{
+            let (body_id, package) = {
+                let modules = self.code.modules.borrow();
+                let module = &modules.modules[path];
+                (module.body_id, module.package.clone())
+            };
+            let _ = self.load_module(path, &package, ScriptIp{body: body_id, index: 0});
+        }
+        stale
+    }
+}
+
+fn module_matches(path:&str, file:&str)->bool{
+    let file = file.replace('\\', "/");
+    path == file || path.strip_suffix(file.as_str()).is_some_and(|dir| dir.ends_with('/'))
+}
@@ -23,6 +23,10 @@
 pub mod trap;
 pub mod vec_prims;
 pub mod json;
+pub mod debug;
+pub mod budget;
+pub mod regex;
+pub mod import;
 
 pub use gc::*;
 pub use makepad_live_id::*;
@@ -32,6 +36,10 @@
 pub use traits::*;
 pub use thread::*;
 pub use heap::*;
+pub use debug::*;
+pub use budget::*;
+pub use regex::*;
+pub use import::*;
 
 
 pub fn test(){
@@ -248,6 +256,54 @@ fn x(a = 2){a + 2}
         assert(x() == 4)
         fn test(a,b){a+b}
         assert(test(2 3) == 5)
+        
+        // math
+        use mod.math
+        assert(math.floor(2.7) == 2) assert(math.ceil(2.1) == 3)
+        assert(math.round(2.5) == 3) assert(math.abs(-2) == 2)
+        assert(math.pow(2 10) == 1024) assert(math.sqrt(16) == 4)
+        assert(math.clamp(5 0 3) == 3) assert(math.min(1 2) == 1) assert(math.max(1 2) == 2)
+        assert(math.log(8 2) == 3) assert(math.sin(0) == 0) assert(math.floor(math.PI) == 3)
+        
+        // string methods
+        assert("  hi ".trim() == "hi")
+        assert("hello".find("ll") == 2) assert("hello".find("x") == nil)
+        assert("a-b-c".replace("-" "+") == "a+b+c")
+        assert("hello".slice(1 3) == "el") assert("hello".slice(-3) == "llo")
+        assert("Hi".to_upper() == "HI") assert("Hi".to_lower() == "hi")
+        assert("hello".starts_with("he")) assert("hello".ends_with("lo"))
+        assert("{} is {}".format("x" 1) == "x is 1")
+        assert("{1}{0}".format("a" "b") == "ba")
+        assert("{x}-{y}".format({x:1 y:2}) == "1-2")
+        
+        // array methods
+        assert([1 2 3].map(|x| x*2) == [2 4 6])
+        assert([1 2 3 4].filter(|x| x > 2) == [3 4])
+        assert([1 2 3].reduce(|a b| a+b) == 6)
+        assert([1 2 3].reduce(|a b| a+b, 10) == 16)
+        assert([3 1 2].sort() == [1 2 3])
+        assert([3 1 2].sort(|a b| b-a) == [3 2 1])
+        assert(["b" "a"].sort() == ["a" "b"])
+        assert([1 2 3 4].slice(1 3) == [2 3])
+        assert([1 2 3].join(",") == "1,2,3")
+        
+        // regex
+        use mod.regex
+        assert(regex.test("^a\\d+$" "a123")) assert(regex.test("^a\\d+$" "a12b") == false)
+        let m = regex.find("(\\w+)@(\\w+)" "mail bob@host now")
+        assert(m.start == 5) assert(m.text == "bob@host") assert(m.groups == ["bob" "host"])
+        assert(regex.find_all("\\d+" "a1b22c333").len() == 3)
+        assert(regex.replace("(\\w+) (\\w+)" "hello world" "$2 $1") == "world hello")
+        assert(regex.split("\\s*,\\s*" "a , b,c") == ["a" "b" "c"])
+        assert(regex.find("a+?" "aaa").text == "a")
+        try{regex.test("(" "")} assert(true) ok assert(false)
+        
+        // time
+        use mod.time
+        assert(time.format(0) == "1970-01-01 00:00:00")
+        assert(time.format(951782400 "%Y/%m/%d %a %j") == "2000/02/29 Tue 060")
+        assert(time.date(time.timestamp(2024 12 31 23 59 58)).second == 58)
+        assert(time.now() >= 0)
         ~"Test done"
     };
     
@@ -259,6 +315,43 @@ fn test(a,b){a+b}
     let dt = std::time::Instant::now();
     
     cx.eval(code);
-    println!("Duration {}", dt.elapsed().as_secs_f64())
+    println!("Duration {}", dt.elapsed().as_secs_f64());
+    
+    // runaway scripts are aborted, and try can't swallow that
+    cx.thread.set_instruction_budget(Some(100_000));
+    let value = cx.eval(script!{
+        let c = 0 try{ loop{ c += 1 } } ok{ c = 1 }
+    });
+    assert!(value.as_err().map(|e| e.ty) == Some(ScriptValueType::ERR_BUDGET_EXHAUSTED));
+    cx.thread.set_instruction_budget(None);
     
+    // script modules, loaded from memory here
+    struct MemLoader(std::collections::HashMap<&'static str, &'static str>);
+    impl ScriptModuleLoader for MemLoader{
+        fn resolve(&mut self, _package:&str, _from:&str, path:&str)->Option<String>{
+            let path = path.trim_start_matches("./");
+            self.0.contains_key(path).then(|| path.to_string())
+        }
+        fn load(&mut self, path:&str)->Option<String>{
+            self.0.get(path).map(|s| s.to_string())
+        }
+        fn stamp(&mut self, _path:&str)->Option<u64>{
+            Some(0)
+        }
+    }
+    cx.set_module_loader(Box::new(MemLoader([
+        ("base.mps", "let two = 2"),
+        ("util.mps", "use \"./base.mps\".two fn add(a b){a + b + two}"),
+        ("a.mps", "use \"./b.mps\".b let a = 1"),
+        ("b.mps", "use \"./a.mps\".a let b = 1"),
+    ].into_iter().collect())));
+    cx.eval(script!{
+        use mod.std.assert
+        use "./util.mps".add
+        assert(add(1 2) == 5)
+    });
+    let value = cx.eval(script!{use "./a.mps".a});
+    assert!(value.as_err().map(|e| e.ty) == Some(ScriptValueType::ERR_IMPORT_CYCLE));
+    let value = cx.eval(script!{use "./missing.mps".a});
+    assert!(value.as_err().map(|e| e.ty) == Some(ScriptValueType::ERR_NOT_FOUND));
 }
@@ -3,13 +3,366 @@
 use crate::value::*;
 use crate::makepad_live_id_macros::*;
 use crate::native::*;
+use crate::regex::*;
+use crate::vm::*;
+use std::fmt::Write;
 use crate::*;
 
 pub fn define_math_module(heap:&mut ScriptHeap, native:&mut ScriptNative){
     let math = heap.new_module(id!(math));
     
-    native.add_fn(heap, math, id!(sin), script_args!(x=0.0), |vm, args|{
-        script_value_f64!(vm, args.x).sin().into()
+    heap.set_value_def(math, id!(PI).into(), std::f64::consts::PI.into());
+    heap.set_value_def(math, id!(TAU).into(), std::f64::consts::TAU.into());
+    heap.set_value_def(math, id!(E).into(), std::f64::consts::E.into());
+    heap.set_value_def(math, id!(SQRT2).into(), std::f64::consts::SQRT_2.into());
+    heap.set_value_def(math, id!(LN2).into(), std::f64::consts::LN_2.into());
+    heap.set_value_def(math, id!(LN10).into(), std::f64::consts::LN_10.into());
+    heap.set_value_def(math, id!(INFINITY).into(), f64::INFINITY.into());
+    heap.set_value_def(math, id!(EPSILON).into(), f64::EPSILON.into());
+    
+    let unary = [
+        (id!(sin), f64::sin as fn(f64)->f64),
+        (id!(cos), f64::cos),
+        (id!(tan), f64::tan),
+        (id!(asin), f64::asin),
+        (id!(acos), f64::acos),
+        (id!(atan), f64::atan),
+        (id!(sinh), f64::sinh),
+        (id!(cosh), f64::cosh),
+        (id!(tanh), f64::tanh),
+        (id!(sqrt), f64::sqrt),
+        (id!(cbrt), f64::cbrt),
+        (id!(exp), f64::exp),
+        (id!(ln), f64::ln),
+        (id!(log2), f64::log2),
+        (id!(log10), f64::log10),
+        (id!(abs), f64::abs),
+        (id!(floor), f64::floor),
+        (id!(ceil), f64::ceil),
+        (id!(round), f64::round),
+        (id!(trunc), f64::trunc),
+        (id!(fract), f64::fract),
+        (id!(sign), |x:f64| if x == 0.0 || x.is_nan(){x} else{x.signum()}),
+    ];
+    for (name, f) in unary{
+        native.add_fn(heap, math, name, script_args!(x=0.0), move |vm, args|{
+            f(script_value_f64!(vm, args.x)).into()
+        });
+    }
+    
+    native.add_fn(heap, math, id!(atan2), script_args!(y=0.0, x=0.0), |vm, args|{
+        script_value_f64!(vm, args.y).atan2(script_value_f64!(vm, args.x)).into()
+    });
+    native.add_fn(heap, math, id!(pow), script_args!(x=0.0, y=0.0), |vm, args|{
+        script_value_f64!(vm, args.x).powf(script_value_f64!(vm, args.y)).into()
+    });
+    native.add_fn(heap, math, id!(log), script_args!(x=0.0, base=std::f64::consts::E), |vm, args|{
+        script_value_f64!(vm, args.x).log(script_value_f64!(vm, args.base)).into()
+    });
+    native.add_fn(heap, math, id!(hypot), script_args!(x=0.0, y=0.0), |vm, args|{
+        script_value_f64!(vm, args.x).hypot(script_value_f64!(vm, args.y)).into()
+    });
+    native.add_fn(heap, math, id!(min), script_args!(a=0.0, b=0.0), |vm, args|{
+        script_value_f64!(vm, args.a).min(script_value_f64!(vm, args.b)).into()
+    });
+    native.add_fn(heap, math, id!(max), script_args!(a=0.0, b=0.0), |vm, args|{
+        script_value_f64!(vm, args.a).max(script_value_f64!(vm, args.b)).into()
+    });
+    native.add_fn(heap, math, id!(clamp), script_args!(x=0.0, min=0.0, max=1.0), |vm, args|{
+        let min = script_value_f64!(vm, args.min);
+        let max = script_value_f64!(vm, args.max);
+        if min > max{
+            return vm.thread.trap.err_invalid_args()
+        }
+        script_value_f64!(vm, args.x).clamp(min, max).into()
+    });
+    native.add_fn(heap, math, id!(lerp), script_args!(a=0.0, b=0.0, t=0.0), |vm, args|{
+        let a = script_value_f64!(vm, args.a);
+        (a + (script_value_f64!(vm, args.b) - a) * script_value_f64!(vm, args.t)).into()
+    });
+    native.add_fn(heap, math, id!(is_nan), script_args!(x=0.0), |vm, args|{
+        script_value_f64!(vm, args.x).is_nan().into()
+    });
+}
+
+// Calendar fields of a unix timestamp in UTC, using the days-from-civil
+// algorithms from Howard Hinnant's date library.
+struct ScriptDate{
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: f64,
+    weekday: u32,
+    yearday: u32,
+}
+
+impl ScriptDate{
+    const WEEKDAYS:[&'static str;7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+    const MONTHS:[&'static str;12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+    
+    fn days_from_civil(year:i64, month:u32, day:u32)->i64{
+        let y = if month <= 2{year - 1} else{year};
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let mp = (month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+    
+    fn from_unix(time:f64)->Self{
+        let days = (time / 86400.0).floor() as i64;
+        let secs = time - days as f64 * 86400.0;
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10{mp + 3} else{mp - 9} as u32;
+        let year = yoe + era * 400 + if month <= 2{1} else{0};
+        Self{
+            year,
+            month,
+            day,
+            hour: (secs / 3600.0) as u32,
+            minute: (secs % 3600.0 / 60.0) as u32,
+            second: secs % 60.0,
+            weekday: (days + 4).rem_euclid(7) as u32,
+            yearday: (days - Self::days_from_civil(year, 1, 1) + 1) as u32,
+        }
+    }
+    
+    // a strftime subset: %Y %m %d %H %M %S %f %j %a %A %b %B %%
+    fn format(&self, fmt:&str, out:&mut String){
+        let mut chars = fmt.chars();
+        while let Some(c) = chars.next(){
+            if c != '%'{
+                out.push(c);
+                continue
+            }
+            match chars.next(){
+                Some('Y')=>write!(out, "{:04}", self.year),
+                Some('m')=>write!(out, "{:02}", self.month),
+                Some('d')=>write!(out, "{:02}", self.day),
+                Some('H')=>write!(out, "{:02}", self.hour),
+                Some('M')=>write!(out, "{:02}", self.minute),
+                Some('S')=>write!(out, "{:02}", self.second as u32),
+                Some('f')=>write!(out, "{:03}", (self.second.fract() * 1000.0) as u32),
+                Some('j')=>write!(out, "{:03}", self.yearday),
+                Some('a')=>write!(out, "{}", &Self::WEEKDAYS[self.weekday as usize][0..3]),
+                Some('A')=>write!(out, "{}", Self::WEEKDAYS[self.weekday as usize]),
+                Some('b')=>write!(out, "{}", &Self::MONTHS[self.month as usize - 1][0..3]),
+                Some('B')=>write!(out, "{}", Self::MONTHS[self.month as usize - 1]),
+                Some('%')=>write!(out, "%"),
+                Some(c)=>write!(out, "%{}", c),
+                None=>write!(out, "%"),
+            }.ok();
+        }
+    }
+}
+
+// std has no clock on wasm, the js side of the bridge hands out Date.now() in seconds
+#[cfg(target_arch = "wasm32")]
+extern "C" {
+    fn js_time_now()->f64;
+}
+
+pub fn define_time_module(heap:&mut ScriptHeap, native:&mut ScriptNative){
+    let time = heap.new_module(id!(time));
+    
+    // seconds since the first call, only useful for measuring intervals
+    native.add_fn(heap, time, id!(now), script_args!(), |_vm, _args|{
+        #[cfg(not(target_arch = "wasm32"))]{
+            static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+            START.get_or_init(std::time::Instant::now).elapsed().as_secs_f64().into()
+        }
+        #[cfg(target_arch = "wasm32")]{
+            static START: std::sync::OnceLock<f64> = std::sync::OnceLock::new();
+            let now = unsafe{js_time_now()};
+            (now - *START.get_or_init(|| now)).max(0.0).into()
+        }
+    });
+    
+    // seconds since the unix epoch
+    native.add_fn(heap, time, id!(wall), script_args!(), |_vm, _args|{
+        #[cfg(not(target_arch = "wasm32"))]{
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0).into()
+        }
+        #[cfg(target_arch = "wasm32")]{
+            unsafe{js_time_now()}.into()
+        }
+    });
+    
+    native.add_fn(heap, time, id!(date), script_args!(t=0.0), |vm, args|{
+        let date = ScriptDate::from_unix(script_value_f64!(vm, args.t));
+        let obj = vm.heap.new_object();
+        vm.heap.set_value_def(obj, id!(year).into(), (date.year as f64).into());
+        vm.heap.set_value_def(obj, id!(month).into(), date.month.into());
+        vm.heap.set_value_def(obj, id!(day).into(), date.day.into());
+        vm.heap.set_value_def(obj, id!(hour).into(), date.hour.into());
+        vm.heap.set_value_def(obj, id!(minute).into(), date.minute.into());
+        vm.heap.set_value_def(obj, id!(second).into(), date.second.into());
+        vm.heap.set_value_def(obj, id!(weekday).into(), date.weekday.into());
+        vm.heap.set_value_def(obj, id!(yearday).into(), date.yearday.into());
+        obj.into()
+    });
+    
+    native.add_fn(heap, time, id!(timestamp), script_args!(year=1970.0, month=1.0, day=1.0, hour=0.0, minute=0.0, second=0.0), |vm, args|{
+        let month = script_value_f64!(vm, args.month);
+        let day = script_value_f64!(vm, args.day);
+        if !(1.0..=12.0).contains(&month) || !(1.0..=31.0).contains(&day){
+            return vm.thread.trap.err_invalid_args()
+        }
+        let days = ScriptDate::days_from_civil(script_value_f64!(vm, args.year) as i64, month as u32, day as u32);
+        (days as f64 * 86400.0 + script_value_f64!(vm, args.hour) * 3600.0 + script_value_f64!(vm, args.minute) * 60.0 + script_value_f64!(vm, args.second)).into()
+    });
+    
+    native.add_fn(heap, time, id!(format), script_args!(t=0.0, fmt=NIL), |vm, args|{
+        let date = ScriptDate::from_unix(script_value_f64!(vm, args.t));
+        let fmt = script_value!(vm, args.fmt);
+        let fmt = if fmt.is_nil(){
+            "%Y-%m-%d %H:%M:%S".to_string()
+        }
+        else if let Some(fmt) = vm.heap.string_with(fmt, |_, s| s.to_string()){
+            fmt
+        }
+        else{
+            return vm.thread.trap.err_invalid_arg_type()
+        };
+        vm.heap.new_string_with(|_, out| date.format(&fmt, out))
+    });
+}
+
+pub fn define_regex_module(heap:&mut ScriptHeap, native:&mut ScriptNative){
+    let regex = heap.new_module(id!(regex));
+    
+    // compiles the pattern and turns the subject into chars, positions
+    // we hand back to script are char indices like the string methods use
+    fn prepare(vm:&mut ScriptVm, args:ScriptObject)->Result<(ScriptRegex, Vec<char>), ScriptValue>{
+        let pat = script_value!(vm, args.pat);
+        let text = script_value!(vm, args.text);
+        let Some(pat) = vm.heap.string_with(pat, |_, s| ScriptRegex::new(s)) else{
+            return Err(vm.thread.trap.err_invalid_arg_type())
+        };
+        let Some(text) = vm.heap.string_with(text, |_, s| s.chars().collect()) else{
+            return Err(vm.thread.trap.err_invalid_arg_type())
+        };
+        match pat{
+            Ok(pat)=>Ok((pat, text)),
+            Err(e)=>{
+                if let Some(loc) = vm.code.ip_to_loc(vm.thread.trap.ip){
+                    println!("Invalid regex: {} {}", e, loc);
+                }
+                Err(vm.thread.trap.err_invalid_args())
+            }
+        }
+    }
+    
+    fn match_to_object(vm:&mut ScriptVm, text:&[char], m:&ScriptRegexMatch)->ScriptValue{
+        let obj = vm.heap.new_object();
+        let whole:String = text[m.start()..m.end()].iter().collect();
+        let whole = vm.heap.new_string_from_str(&whole);
+        let groups = m.groups[1..].iter().map(|g| match g{
+            Some((s, e))=>{
+                let s:String = text[*s..*e].iter().collect();
+                vm.heap.new_string_from_str(&s)
+            }
+            None=>NIL
+        }).collect();
+        let groups = vm.heap.new_array_from_vec(groups);
+        vm.heap.set_value_def(obj, id!(start).into(), m.start().into());
+        vm.heap.set_value_def(obj, id!(end).into(), m.end().into());
+        vm.heap.set_value_def(obj, id!(text).into(), whole);
+        vm.heap.set_value_def(obj, id!(groups).into(), groups.into());
+        obj.into()
+    }
+    
+    native.add_fn(heap, regex, id!(test), script_args!(pat=NIL, text=NIL), |vm, args|{
+        match prepare(vm, args){
+            Ok((pat, text))=>pat.find(&text).is_some().into(),
+            Err(e)=>e
+        }
+    });
+    
+    native.add_fn(heap, regex, id!(find), script_args!(pat=NIL, text=NIL), |vm, args|{
+        match prepare(vm, args){
+            Ok((pat, text))=>match pat.find(&text){
+                Some(m)=>match_to_object(vm, &text, &m),
+                None=>NIL
+            }
+            Err(e)=>e
+        }
+    });
+    
+    native.add_fn(heap, regex, id!(find_all), script_args!(pat=NIL, text=NIL), |vm, args|{
+        match prepare(vm, args){
+            Ok((pat, text))=>{
+                let found = pat.find_all(&text).iter().map(|m| match_to_object(vm, &text, m)).collect();
+                vm.heap.new_array_from_vec(found).into()
+            }
+            Err(e)=>e
+        }
+    });
+    
+    // $0 is the whole match, $1..$9 are groups and $$ is a dollar sign
+    native.add_fn(heap, regex, id!(replace), script_args!(pat=NIL, text=NIL, with=NIL), |vm, args|{
+        let (pat, text) = match prepare(vm, args){
+            Ok(v)=>v,
+            Err(e)=>return e
+        };
+        let with = script_value!(vm, args.with);
+        let Some(with) = vm.heap.string_with(with, |_, s| s.to_string()) else{
+            return vm.thread.trap.err_invalid_arg_type()
+        };
+        let mut out = String::new();
+        let mut last = 0;
+        for m in pat.find_all(&text){
+            out.extend(&text[last..m.start()]);
+            let mut chars = with.chars().peekable();
+            while let Some(c) = chars.next(){
+                if c == '$'{
+                    if let Some(d) = chars.peek().and_then(|c| c.to_digit(10)){
+                        chars.next();
+                        if let Some(Some((s, e))) = m.groups.get(d as usize){
+                            out.extend(&text[*s..*e]);
+                        }
+                        continue
+                    }
+                    if chars.peek() == Some(&'$'){
+                        chars.next();
+                    }
+                }
+                out.push(c);
+            }
+            last = m.end();
+        }
+        out.extend(&text[last..]);
+        vm.heap.new_string_from_str(&out)
+    });
+    
+    native.add_fn(heap, regex, id!(split), script_args!(pat=NIL, text=NIL), |vm, args|{
+        let (pat, text) = match prepare(vm, args){
+            Ok(v)=>v,
+            Err(e)=>return e
+        };
+        let mut parts = Vec::new();
+        let mut last = 0;
+        for m in pat.find_all(&text){
+            // an empty match at the very start or end doesn't split anything
+            if m.end() == m.start() && (m.start() == 0 || m.start() == text.len()){
+                continue
+            }
+            let part:String = text[last..m.start()].iter().collect();
+            parts.push(vm.heap.new_string_from_str(&part));
+            last = m.end();
+        }
+        let part:String = text[last..].iter().collect();
+        parts.push(vm.heap.new_string_from_str(&part));
+        vm.heap.new_array_from_vec(parts).into()
     });
 }
 
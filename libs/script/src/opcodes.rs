@@ -919,6 +919,16 @@ pub fn opcode(&mut self,opcode: Opcode, opargs:OpcodeArgs, heap:&mut ScriptHeap,
             Opcode::USE=>{
                 let field = self.pop_stack_value();
                 let object = self.pop_stack_resolved(heap);
+                // importing from a script module, loaded before we started
+                let object = if object.is_string_like(){
+                    match code.modules.borrow().exports_for(self.trap.ip){
+                        Some(exports)=>exports.into(),
+                        None=>self.trap.err_not_found()
+                    }
+                }
+                else{
+                    object
+                };
                 if let Some(obj) = object.as_object(){
                     let value = heap.value(obj, field, &self.trap);
                     if !value.is_nil(){
@@ -0,0 +1,496 @@
+// A small regex engine for the script stdlib. Patterns compile to a program
+// for a backtracking matcher, and every (instruction, position) pair is only
+// ever explored once so matching stays linear in the text for any pattern.
+//
+// Supported: literals, ., [classes] with ranges and ^, \d \w \s \D \W \S,
+// \b \B, ^ $, (groups), (?:groups), | and * + ? {m} {m,} {m,n} with lazy
+// variants. Positions are in chars, not bytes.
+
+// expanding counted repeats copies the body, so we cap them
+const MAX_REPEAT: usize = 1000;
+const MAX_PROGRAM: usize = 64 * 1024;
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+#[derive(Debug)]
+enum Ast{
+    Empty,
+    Char(char),
+    Any,
+    Class{ranges: Vec<(char, char)>, negated: bool},
+    Start,
+    End,
+    WordBoundary(bool),
+    Group{index: Option<usize>, inner: Box<Ast>},
+    Concat(Vec<Ast>),
+    Alt(Vec<Ast>),
+    Repeat{inner: Box<Ast>, min: usize, max: Option<usize>, greedy: bool},
+}
+
+#[derive(Debug)]
+enum Inst{
+    Char(char),
+    Any,
+    Class{ranges: Vec<(char, char)>, negated: bool},
+    Start,
+    End,
+    WordBoundary(bool),
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    Match,
+}
+
+struct Parser<'a>{
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    groups: usize,
+}
+
+impl<'a> Parser<'a>{
+    fn parse_alt(&mut self)->Result<Ast, String>{
+        let mut alts = vec![self.parse_concat()?];
+        while self.chars.peek() == Some(&'|'){
+            self.chars.next();
+            alts.push(self.parse_concat()?);
+        }
+        Ok(if alts.len() == 1{alts.pop().unwrap()} else{Ast::Alt(alts)})
+    }
+
+    fn parse_concat(&mut self)->Result<Ast, String>{
+        let mut items = Vec::new();
+        while let Some(&c) = self.chars.peek(){
+            if c == '|' || c == ')'{
+                break
+            }
+            let atom = self.parse_atom()?;
+            items.push(self.parse_repeat(atom)?);
+        }
+        Ok(match items.len(){
+            0=>Ast::Empty,
+            1=>items.pop().unwrap(),
+            _=>Ast::Concat(items)
+        })
+    }
+
+    fn parse_number(&mut self)->Option<usize>{
+        let mut value = None;
+        while let Some(d) = self.chars.peek().and_then(|c| c.to_digit(10)){
+            self.chars.next();
+            value = Some(value.unwrap_or(0usize).saturating_mul(10).saturating_add(d as usize));
+        }
+        value
+    }
+
+    fn parse_repeat(&mut self, atom:Ast)->Result<Ast, String>{
+        let (min, max) = match self.chars.peek(){
+            Some('*')=>(0, None),
+            Some('+')=>(1, None),
+            Some('?')=>(0, Some(1)),
+            Some('{')=>{
+                self.chars.next();
+                let min = self.parse_number().ok_or("expected a number after {")?;
+                let max = if self.chars.peek() == Some(&','){
+                    self.chars.next();
+                    self.parse_number()
+                }
+                else{
+                    Some(min)
+                };
+                if self.chars.peek() != Some(&'}'){
+                    return Err("expected } to close the repeat".into())
+                }
+                if max.map(|max| max < min).unwrap_or(false){
+                    return Err("repeat maximum is smaller than the minimum".into())
+                }
+                if min.max(max.unwrap_or(0)) > MAX_REPEAT{
+                    return Err(format!("repeat count over {}", MAX_REPEAT))
+                }
+                (min, max)
+            }
+            _=>return Ok(atom)
+        };
+        self.chars.next();
+        if matches!(atom, Ast::Start | Ast::End | Ast::WordBoundary(_) | Ast::Empty){
+            return Err("nothing to repeat".into())
+        }
+        let greedy = if self.chars.peek() == Some(&'?'){
+            self.chars.next();
+            false
+        }
+        else{
+            true
+        };
+        Ok(Ast::Repeat{inner: Box::new(atom), min, max, greedy})
+    }
+
+    fn parse_atom(&mut self)->Result<Ast, String>{
+        let c = self.chars.next().unwrap();
+        Ok(match c{
+            '('=>{
+                let index = if self.chars.peek() == Some(&'?'){
+                    self.chars.next();
+                    if self.chars.next() != Some(':'){
+                        return Err("only (?: groups are supported".into())
+                    }
+                    None
+                }
+                else{
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let inner = self.parse_alt()?;
+                if self.chars.next() != Some(')'){
+                    return Err("missing )".into())
+                }
+                Ast::Group{index, inner: Box::new(inner)}
+            }
+            '['=>self.parse_class()?,
+            '.'=>Ast::Any,
+            '^'=>Ast::Start,
+            '$'=>Ast::End,
+            '*' | '+' | '?' | '{'=>return Err("nothing to repeat".into()),
+            '\\'=>{
+                let c = self.chars.next().ok_or("trailing \\")?;
+                match c{
+                    'd'=>Ast::Class{ranges: DIGIT.to_vec(), negated: false},
+                    'D'=>Ast::Class{ranges: DIGIT.to_vec(), negated: true},
+                    'w'=>Ast::Class{ranges: WORD.to_vec(), negated: false},
+                    'W'=>Ast::Class{ranges: WORD.to_vec(), negated: true},
+                    's'=>Ast::Class{ranges: SPACE.to_vec(), negated: false},
+                    'S'=>Ast::Class{ranges: SPACE.to_vec(), negated: true},
+                    'b'=>Ast::WordBoundary(true),
+                    'B'=>Ast::WordBoundary(false),
+                    c=>Ast::Char(Self::escape(c)?)
+                }
+            }
+            c=>Ast::Char(c)
+        })
+    }
+
+    fn escape(c:char)->Result<char, String>{
+        Ok(match c{
+            'n'=>'\n',
+            't'=>'\t',
+            'r'=>'\r',
+            '0'=>'\0',
+            c if c.is_ascii_alphanumeric()=>return Err(format!("unknown escape \\{}", c)),
+            c=>c
+        })
+    }
+
+    fn parse_class(&mut self)->Result<Ast, String>{
+        let negated = if self.chars.peek() == Some(&'^'){
+            self.chars.next();
+            true
+        }
+        else{
+            false
+        };
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop{
+            let c = self.chars.next().ok_or("missing ]")?;
+            if c == ']' && !first{
+                break
+            }
+            first = false;
+            let lo = if c == '\\'{
+                let c = self.chars.next().ok_or("missing ]")?;
+                match c{
+                    'd'=>{ranges.extend_from_slice(DIGIT); continue}
+                    'w'=>{ranges.extend_from_slice(WORD); continue}
+                    's'=>{ranges.extend_from_slice(SPACE); continue}
+                    'D' | 'W' | 'S'=>return Err(format!("\\{} is not supported in a class", c)),
+                    c=>Self::escape(c)?
+                }
+            }
+            else{
+                c
+            };
+            let mut ahead = self.chars.clone();
+            if ahead.next() == Some('-') && ahead.peek().map(|c| *c != ']').unwrap_or(false){
+                self.chars.next();
+                let mut hi = self.chars.next().unwrap();
+                if hi == '\\'{
+                    hi = Self::escape(self.chars.next().ok_or("missing ]")?)?;
+                }
+                if hi < lo{
+                    return Err("invalid class range".into())
+                }
+                ranges.push((lo, hi));
+            }
+            else{
+                ranges.push((lo, lo));
+            }
+        }
+        Ok(Ast::Class{ranges, negated})
+    }
+}
+
+#[derive(Debug)]
+pub struct ScriptRegex{
+    prog: Vec<Inst>,
+    groups: usize,
+}
+
+// the buffers of a search, made once and reused for every match of a find_all
+struct RegexSearch{
+    visited: Vec<u64>,
+    // the words of visited that have bits set
+    touched: Vec<usize>,
+    slots: Vec<Option<usize>>,
+    stack: Vec<(usize, usize, Option<usize>)>,
+}
+
+/// Char positions of a match, index 0 is the whole match and the rest
+/// are the capture groups.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptRegexMatch{
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+impl ScriptRegexMatch{
+    pub fn start(&self)->usize{
+        self.groups[0].unwrap().0
+    }
+
+    pub fn end(&self)->usize{
+        self.groups[0].unwrap().1
+    }
+}
+
+impl ScriptRegex{
+    pub fn new(pattern:&str)->Result<Self, String>{
+        let mut parser = Parser{chars: pattern.chars().peekable(), groups: 0};
+        let ast = parser.parse_alt()?;
+        if parser.chars.next().is_some(){
+            return Err("unmatched )".into())
+        }
+        let mut regex = Self{prog: Vec::new(), groups: parser.groups};
+        regex.prog.push(Inst::Save(0));
+        regex.compile(&ast)?;
+        regex.prog.push(Inst::Save(1));
+        regex.prog.push(Inst::Match);
+        Ok(regex)
+    }
+
+    pub fn group_count(&self)->usize{
+        self.groups
+    }
+
+    fn emit(&mut self, inst:Inst)->Result<usize, String>{
+        if self.prog.len() >= MAX_PROGRAM{
+            return Err("pattern too large".into())
+        }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    // splits are emitted with placeholder targets and patched once we know
+    // where the branches end up
+    fn split(&mut self, pc:usize, prefer:usize, other:usize, greedy:bool){
+        self.prog[pc] = if greedy{Inst::Split(prefer, other)} else{Inst::Split(other, prefer)};
+    }
+
+    fn compile(&mut self, ast:&Ast)->Result<(), String>{
+        match ast{
+            Ast::Empty=>(),
+            Ast::Char(c)=>{self.emit(Inst::Char(*c))?;}
+            Ast::Any=>{self.emit(Inst::Any)?;}
+            Ast::Class{ranges, negated}=>{self.emit(Inst::Class{ranges: ranges.clone(), negated: *negated})?;}
+            Ast::Start=>{self.emit(Inst::Start)?;}
+            Ast::End=>{self.emit(Inst::End)?;}
+            Ast::WordBoundary(b)=>{self.emit(Inst::WordBoundary(*b))?;}
+            Ast::Group{index, inner}=>{
+                if let Some(index) = index{
+                    self.emit(Inst::Save(index * 2))?;
+                    self.compile(inner)?;
+                    self.emit(Inst::Save(index * 2 + 1))?;
+                }
+                else{
+                    self.compile(inner)?;
+                }
+            }
+            Ast::Concat(items)=>for item in items{
+                self.compile(item)?;
+            }
+            Ast::Alt(alts)=>{
+                let mut jumps = Vec::new();
+                for (i, alt) in alts.iter().enumerate(){
+                    if i + 1 < alts.len(){
+                        let split = self.emit(Inst::Match)?;
+                        self.compile(alt)?;
+                        jumps.push(self.emit(Inst::Match)?);
+                        let next = self.prog.len();
+                        self.split(split, split + 1, next, true);
+                    }
+                    else{
+                        self.compile(alt)?;
+                    }
+                }
+                let end = self.prog.len();
+                for jump in jumps{
+                    self.prog[jump] = Inst::Jmp(end);
+                }
+            }
+            Ast::Repeat{inner, min, max, greedy}=>{
+                for _ in 0..*min{
+                    self.compile(inner)?;
+                }
+                if let Some(max) = max{
+                    let mut splits = Vec::new();
+                    for _ in *min..*max{
+                        splits.push(self.emit(Inst::Match)?);
+                        self.compile(inner)?;
+                    }
+                    let end = self.prog.len();
+                    for split in splits{
+                        self.split(split, split + 1, end, *greedy);
+                    }
+                }
+                else{
+                    let split = self.emit(Inst::Match)?;
+                    self.compile(inner)?;
+                    self.emit(Inst::Jmp(split))?;
+                    let end = self.prog.len();
+                    self.split(split, split + 1, end, *greedy);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the leftmost match at or after char position `start`
+    pub fn find_at(&self, text:&[char], start:usize)->Option<ScriptRegexMatch>{
+        self.search_at(&mut self.new_search(text), text, start)
+    }
+
+    pub fn find(&self, text:&[char])->Option<ScriptRegexMatch>{
+        self.find_at(text, 0)
+    }
+
+    /// All non-overlapping matches, an empty match advances by one char
+    pub fn find_all(&self, text:&[char])->Vec<ScriptRegexMatch>{
+        let mut search = self.new_search(text);
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while pos <= text.len(){
+            let Some(m) = self.search_at(&mut search, text, pos) else{break};
+            pos = if m.end() == m.start(){m.end() + 1} else{m.end()};
+            out.push(m);
+        }
+        out
+    }
+
+    fn new_search(&self, text:&[char])->RegexSearch{
+        RegexSearch{
+            visited: vec![0u64; (self.prog.len() * (text.len() + 1)).div_ceil(64)],
+            touched: Vec::new(),
+            slots: vec![None; (self.groups + 1) * 2],
+            stack: Vec::new(),
+        }
+    }
+
+    fn search_at(&self, search:&mut RegexSearch, text:&[char], start:usize)->Option<ScriptRegexMatch>{
+        // what the last match marked is no failure for this one, clearing what
+        // it touched keeps a find_all linear instead of wiping the whole set
+        for word in search.touched.drain(..){
+            search.visited[word] = 0;
+        }
+        search.slots.fill(None);
+        // failing from a (pc, pos) doesn't depend on where we started, so the
+        // visited set is shared between start positions
+        for pos in start..=text.len(){
+            if self.backtrack(text, pos, search){
+                let slots = &search.slots;
+                let groups = (0..=self.groups).map(|i|{
+                    match (slots[i * 2], slots[i * 2 + 1]){
+                        (Some(s), Some(e))=>Some((s, e)),
+                        _=>None
+                    }
+                }).collect();
+                return Some(ScriptRegexMatch{groups})
+            }
+        }
+        None
+    }
+
+    fn is_word(c:Option<&char>)->bool{
+        c.map(|c| c.is_alphanumeric() || *c == '_').unwrap_or(false)
+    }
+
+    fn backtrack(&self, text:&[char], start:usize, search:&mut RegexSearch)->bool{
+        const RUN:usize = usize::MAX;
+        let width = text.len() + 1;
+        let RegexSearch{visited, touched, slots, stack} = search;
+        stack.clear();
+        // a job either runs from (pc, pos) or restores a capture slot
+        stack.push((RUN, 0, Some(start)));
+        while let Some((slot, mut pc, value)) = stack.pop(){
+            if slot != RUN{
+                slots[slot] = value;
+                continue
+            }
+            let mut pos = value.unwrap();
+            loop{
+                let bit = pc * width + pos;
+                let word = &mut visited[bit / 64];
+                if *word & (1 << (bit % 64)) != 0{
+                    break
+                }
+                if *word == 0{
+                    touched.push(bit / 64);
+                }
+                *word |= 1 << (bit % 64);
+                match &self.prog[pc]{
+                    Inst::Char(c)=>{
+                        if text.get(pos) != Some(c){break}
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Any=>{
+                        if pos >= text.len() || text[pos] == '\n'{break}
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Class{ranges, negated}=>{
+                        let Some(c) = text.get(pos) else{break};
+                        let hit = ranges.iter().any(|(lo, hi)| c >= lo && c <= hi);
+                        if hit == *negated{break}
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Start=>{
+                        if pos != 0{break}
+                        pc += 1;
+                    }
+                    Inst::End=>{
+                        if pos != text.len(){break}
+                        pc += 1;
+                    }
+                    Inst::WordBoundary(want)=>{
+                        let at = Self::is_word(pos.checked_sub(1).and_then(|p| text.get(p))) != Self::is_word(text.get(pos));
+                        if at != *want{break}
+                        pc += 1;
+                    }
+                    Inst::Split(a, b)=>{
+                        stack.push((RUN, *b, Some(pos)));
+                        pc = *a;
+                    }
+                    Inst::Jmp(to)=>{
+                        pc = *to;
+                    }
+                    Inst::Save(slot)=>{
+                        stack.push((*slot, 0, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Match=>return true
+                }
+            }
+        }
+        false
+    }
+}
@@ -4,6 +4,7 @@
 use crate::native::*;
 use crate::makepad_live_id::*;
 use crate::methods::*;
+use crate::vm::*;
 use std::rc::Rc;
 use crate::*;
 use std::borrow::Borrow;
@@ -101,5 +102,146 @@ pub fn add_type_methods(tm: &mut ScriptTypeMethods, h: &mut ScriptHeap, native:&
             
             vm.thread.trap.err_unexpected()
         });
+        
+        // indices and lengths are in chars, not bytes
+        tm.add(h, native, &[], ScriptValueType::REDUX_STRING, id!(len), |vm, args|{
+            let this = script_value!(vm, args.this);
+            vm.heap.string_with(this, |_, s| s.chars().count().into()).unwrap_or(NIL)
+        });
+        tm.add(h, native, script_args_def!(pat = NIL), ScriptValueType::REDUX_STRING, id!(find), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let Some(pat) = string_arg(vm, script_value!(vm, args.pat)) else{
+                return vm.thread.trap.err_invalid_arg_type()
+            };
+            vm.heap.string_with(this, |_, s|{
+                match s.find(&pat){
+                    Some(pos)=>s[0..pos].chars().count().into(),
+                    None=>NIL
+                }
+            }).unwrap_or(NIL)
+        });
+        tm.add(h, native, script_args_def!(pat = NIL), ScriptValueType::REDUX_STRING, id!(contains), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let Some(pat) = string_arg(vm, script_value!(vm, args.pat)) else{
+                return vm.thread.trap.err_invalid_arg_type()
+            };
+            vm.heap.string_with(this, |_, s| s.contains(&pat).into()).unwrap_or(NIL)
+        });
+        tm.add(h, native, script_args_def!(pat = NIL), ScriptValueType::REDUX_STRING, id!(starts_with), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let Some(pat) = string_arg(vm, script_value!(vm, args.pat)) else{
+                return vm.thread.trap.err_invalid_arg_type()
+            };
+            vm.heap.string_with(this, |_, s| s.starts_with(&pat).into()).unwrap_or(NIL)
+        });
+        tm.add(h, native, script_args_def!(pat = NIL), ScriptValueType::REDUX_STRING, id!(ends_with), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let Some(pat) = string_arg(vm, script_value!(vm, args.pat)) else{
+                return vm.thread.trap.err_invalid_arg_type()
+            };
+            vm.heap.string_with(this, |_, s| s.ends_with(&pat).into()).unwrap_or(NIL)
+        });
+        tm.add(h, native, script_args_def!(from = NIL, to = NIL), ScriptValueType::REDUX_STRING, id!(replace), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let (Some(from), Some(to)) = (string_arg(vm, script_value!(vm, args.from)), string_arg(vm, script_value!(vm, args.to))) else{
+                return vm.thread.trap.err_invalid_arg_type()
+            };
+            if from.is_empty(){
+                return vm.thread.trap.err_invalid_args()
+            }
+            vm.heap.string_mut_self_with(this, |heap, s|{
+                heap.new_string_from_str(&s.replace(&from, &to))
+            }).unwrap_or(NIL)
+        });
+        let transforms = [
+            (id!(trim), (|s| s.trim().to_string()) as fn(&str)->String),
+            (id!(trim_start), |s| s.trim_start().to_string()),
+            (id!(trim_end), |s| s.trim_end().to_string()),
+            (id!(to_upper), |s| s.to_uppercase()),
+            (id!(to_lower), |s| s.to_lowercase()),
+        ];
+        for (name, f) in transforms{
+            tm.add(h, native, &[], ScriptValueType::REDUX_STRING, name, move |vm, args|{
+                let this = script_value!(vm, args.this);
+                vm.heap.string_mut_self_with(this, |heap, s|{
+                    heap.new_string_from_str(&f(s))
+                }).unwrap_or(NIL)
+            });
+        }
+        // negative indices count from the end, a missing end slices to the end
+        tm.add(h, native, script_args_def!(start = NIL, end = NIL), ScriptValueType::REDUX_STRING, id!(slice), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let start = script_value!(vm, args.start);
+            let end = script_value!(vm, args.end);
+            vm.heap.string_mut_self_with(this, |heap, s|{
+                let (start, end) = script_slice_range(heap, s.chars().count(), start, end);
+                let s:String = s.chars().skip(start).take(end - start).collect();
+                heap.new_string_from_str(&s)
+            }).unwrap_or(NIL)
+        });
+        // "{} and {}".format(a, b), "{1} {0}".format(a, b) or "{x}".format({x:1})
+        tm.add(h, native, &[], ScriptValueType::REDUX_STRING, id!(format), |vm, args|{
+            let this = script_value!(vm, args.this);
+            let Some(fmt) = string_arg(vm, this) else{
+                return vm.thread.trap.err_unexpected()
+            };
+            let values:Vec<ScriptValue> = vm.heap.vec_ref(args).iter().map(|v| v.value).collect();
+            let mut out = String::new();
+            let mut next = 0;
+            let mut chars = fmt.chars().peekable();
+            while let Some(c) = chars.next(){
+                if c == '}'{
+                    if chars.peek() == Some(&'}'){
+                        chars.next();
+                    }
+                    out.push('}');
+                    continue
+                }
+                if c != '{'{
+                    out.push(c);
+                    continue
+                }
+                if chars.peek() == Some(&'{'){
+                    chars.next();
+                    out.push('{');
+                    continue
+                }
+                let mut name = String::new();
+                loop{
+                    match chars.next(){
+                        Some('}')=>break,
+                        Some(c)=>name.push(c),
+                        None=>return vm.thread.trap.err_invalid_args()
+                    }
+                }
+                let value = if name.is_empty(){
+                    next += 1;
+                    values.get(next - 1).copied()
+                }
+                else if let Ok(index) = name.parse::<usize>(){
+                    values.get(index).copied()
+                }
+                else if let Some(obj) = values.first().and_then(|v| v.as_object()){
+                    Some(vm.heap.value(obj, LiveId::from_str(&name).into(), &vm.thread.trap))
+                }
+                else{
+                    None
+                };
+                let Some(value) = value else{
+                    return vm.thread.trap.err_invalid_args()
+                };
+                if value.is_object() || value.is_array(){
+                    vm.heap.to_debug_string(value, 2, &mut out);
+                }
+                else{
+                    vm.heap.cast_to_string(value, &mut out);
+                }
+            }
+            vm.heap.new_string_from_str(&out)
+        });
     }
+}
+
+fn string_arg(vm:&mut ScriptVm, value:ScriptValue)->Option<String>{
+    vm.heap.string_with(value, |_, s| s.to_string())
 }
\ No newline at end of file
@@ -6,6 +6,8 @@
 use crate::object::*;
 use crate::trap::*;
 use crate::json::*;
+use crate::debug::*;
+use crate::budget::*;
 use std::any::Any;
 
 #[derive(Debug, Default)]
@@ -78,7 +80,9 @@ pub struct ScriptThread{
     pub(crate) mes: Vec<ScriptMe>,
     pub trap: ScriptTrap,
     pub(crate) last_err: ScriptValue,
-    pub(crate) json_parser: JsonParserThread
+    pub(crate) json_parser: JsonParserThread,
+    pub(crate) debug: Option<Box<ScriptDebug>>,
+    pub(crate) budget: ScriptBudget,
 }
 
 impl ScriptThread{
@@ -95,6 +99,8 @@ pub fn new()->Self{
             mes: vec![],
             trap: ScriptTrap::default(),
             json_parser: Default::default(),
+            debug: None,
+            budget: Default::default(),
         }
     }
     
@@ -281,10 +287,26 @@ pub fn call(&mut self, heap:&mut ScriptHeap, code:&ScriptCode, host:&mut dyn Any
     }
     
     pub fn run_core(&mut self, heap:&mut ScriptHeap, code:&ScriptCode, host:&mut dyn Any)->ScriptValue{
+        let entry_calls = self.calls.len();
+        self.budget_enter(heap);
+        let value = self.run_core_inner(heap, code, host);
+        self.budget_leave(entry_calls, heap);
+        value
+    }
+    
+    fn run_core_inner(&mut self, heap:&mut ScriptHeap, code:&ScriptCode, host:&mut dyn Any)->ScriptValue{
         self.trap.in_rust = false;
         let bodies = code.bodies.borrow();
         let mut body = &bodies[self.trap.ip.body as usize];
         while (self.trap.ip.index as usize) < body.parser.opcodes.len(){
+            if self.budget.tick(){
+                if let Some(abort) = self.budget_check(heap, code){
+                    return abort
+                }
+            }
+            if self.debug.is_some(){
+                self.debug_check(heap, code, host);
+            }
             let opcode = body.parser.opcodes[self.trap.ip.index as usize];
             if let Some((opcode, args)) = opcode.as_opcode(){
                 self.opcode(opcode, args, heap, code, host);
@@ -313,6 +335,9 @@ pub fn run_core(&mut self, heap:&mut ScriptHeap, code:&ScriptCode, host:&mut dyn
                                         }
                                     }
                                 }
+                                if self.debug.is_some(){
+                                    self.debug_error(heap, code, host, value);
+                                }
                             }
                         }
                         ScriptTrapOn::Return(value)=>{
@@ -121,6 +121,29 @@ pub fn  token_index_to_row_col(&self, tok_index:u32)->Option<(u32,u32)>{
         None
     }
     
+    /// The 0 based line of every token, in one pass over the source
+    pub fn token_lines(&self)->Vec<u32>{
+        let mut lines = Vec::with_capacity(self.tokens.len());
+        let mut chars = self.original.chars();
+        let mut char_index = 0;
+        let mut line = 0;
+        for tok in &self.tokens{
+            while char_index < tok.pos{
+                if let Some(c) = chars.next(){
+                    if c == '\n'{
+                        line += 1;
+                    }
+                    char_index += 1;
+                }
+                else{
+                    break
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+    
     pub fn dump_tokens(&self, heap: &ScriptHeap){
         for i in 0..self.tokens.len(){
             match self.tokens[i].token{
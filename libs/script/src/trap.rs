@@ -76,5 +76,8 @@ pub fn err(&self, err:ScriptValue)->ScriptValue{
     err_fwd!(err_wrong_type_in_apply);
     err_fwd!(err_file_system);
     err_fwd!(err_child_process);
+    err_fwd!(err_budget_exhausted);
+    err_fwd!(err_out_of_memory);
+    err_fwd!(err_import_cycle);
 }
 
@@ -251,7 +251,10 @@ impl ScriptValueType{
     pub const ERR_WRONG_TYPE_IN_APPLY: Self = Self(46);
     pub const ERR_FILE_SYSTEM: Self = Self(47);
     pub const ERR_CHILD_PROCESS: Self = Self(48);
-    pub const ERR_LAST: Self = Self(48);
+    pub const ERR_BUDGET_EXHAUSTED: Self = Self(49);
+    pub const ERR_OUT_OF_MEMORY: Self = Self(50);
+    pub const ERR_IMPORT_CYCLE: Self = Self(51);
+    pub const ERR_LAST: Self = Self(51);
     
     pub const ID: Self = Self(0x80);
         
@@ -355,6 +358,9 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Self::ERR_USER=>write!(f,"UserGenerated"),
             Self::ERR_FILE_SYSTEM=>write!(f,"FileSystemError"),
             Self::ERR_CHILD_PROCESS=>write!(f,"ChildProcessError"),
+            Self::ERR_BUDGET_EXHAUSTED=>write!(f,"BudgetExhausted"),
+            Self::ERR_OUT_OF_MEMORY=>write!(f,"OutOfMemory"),
+            Self::ERR_IMPORT_CYCLE=>write!(f,"ImportCycle"),
             x if x.0 >= Self::ID.0=>write!(f,"id"),
             _=>write!(f,"ScriptValueType?")
         }
@@ -458,6 +464,9 @@ pub const fn value_type(&self)->ScriptValueType{
     err_fn!(err_wrong_type_in_apply, ERR_WRONG_TYPE_IN_APPLY);
     err_fn!(err_file_system, ERR_FILE_SYSTEM);
     err_fn!(err_child_process, ERR_CHILD_PROCESS);
+    err_fn!(err_budget_exhausted, ERR_BUDGET_EXHAUSTED);
+    err_fn!(err_out_of_memory, ERR_OUT_OF_MEMORY);
+    err_fn!(err_import_cycle, ERR_IMPORT_CYCLE);
             
     pub const fn is_err(&self)->bool{(self.0&Self::TYPE_MASK) >=ScriptValueType::ERR_FIRST.to_u64() &&(self.0&Self::TYPE_MASK) <= ScriptValueType::ERR_LAST.to_u64()}
     
@@ -8,6 +8,7 @@
 use crate::native::*;
 use crate::modules::*;
 use crate::object::*;
+use crate::import::*;
 use std::cell::RefCell;
 use std::any::Any;
 
@@ -44,6 +45,7 @@ pub struct ScriptCode{
     pub builtins: ScriptBuiltins,
     pub native: RefCell<ScriptNative>,
     pub bodies: RefCell<Vec<ScriptBody>>,
+    pub modules: RefCell<ScriptModules>,
 }
 
 pub struct ScriptLoc{
@@ -140,19 +142,29 @@ pub fn add_fn<F>(&mut self, module:ScriptObject, method:LiveId, args:&[(LiveId,
     }
     
     
-    pub fn add_script_block(&mut self, new_block:ScriptBlock)->u16{
+    fn new_script_body(&mut self, new_block:ScriptBlock)->ScriptBody{
         let scope = self.heap.new_with_proto(id!(scope).into());
         self.heap.set_object_deep(scope);
         self.heap.set_value_def(scope, id!(mod).into(), self.heap.modules.into());
         let me = self.heap.new_with_proto(id!(root_me).into());
                 
-        let new_body = ScriptBody{
+        ScriptBody{
             source: ScriptSource::Block{block:new_block},
             tokenizer: ScriptTokenizer::default(),
             parser: ScriptParser::default(),
             scope,
             me,
-        };
+        }
+    }
+    
+    /// Swaps the source of an existing body, reusing its id
+    pub(crate) fn replace_script_block(&mut self, body_id:u16, new_block:ScriptBlock){
+        let new_body = self.new_script_body(new_block);
+        self.code.bodies.borrow_mut()[body_id as usize] = new_body;
+    }
+    
+    pub fn add_script_block(&mut self, new_block:ScriptBlock)->u16{
+        let new_body = self.new_script_body(new_block);
         let mut bodies = self.code.bodies.borrow_mut();
         for (i, body) in bodies.iter_mut().enumerate(){
             if let ScriptSource::Block{block} = &body.source{
@@ -171,21 +183,23 @@ pub fn add_script_block(&mut self, new_block:ScriptBlock)->u16{
         i as u16
     }
         
-    pub fn eval(&mut self, block: ScriptBlock)->ScriptValue{
-        let body_id = self.add_script_block(block);
+    pub(crate) fn parse_body(&mut self, body_id:u16){
         let mut bodies = self.code.bodies.borrow_mut();
         let body = &mut bodies[body_id as usize];
-                
         if let ScriptSource::Block{block} = &body.source{
-            body.tokenizer.tokenize(&block.code, &mut self.heap);
+            body.tokenizer.tokenize(&block.code, self.heap);
             body.parser.parse(&body.tokenizer.tokens, &block.values);
-            drop(bodies);
-            // lets point our thread to it
-            self.thread.run_root(&mut self.heap, &self.code, self.host, body_id)
         }
-        else{
-            NIL
+    }
+        
+    pub fn eval(&mut self, block: ScriptBlock)->ScriptValue{
+        let body_id = self.add_script_block(block);
+        self.parse_body(body_id);
+        if let Err(err) = self.import_modules(body_id){
+            return err
         }
+        // lets point our thread to it
+        self.thread.run_root(self.heap, self.code, self.host, body_id)
     }
     
 }
@@ -222,6 +236,8 @@ pub fn new()->Self{
         let type_methods = ScriptTypeMethods::new(&mut heap, &mut native);
         define_math_module(&mut heap, &mut native);
         define_std_module(&mut heap, &mut native);
+        define_time_module(&mut heap, &mut native);
+        define_regex_module(&mut heap, &mut native);
     
         let builtins = ScriptBuiltins::new(&mut heap);
         
@@ -232,6 +248,7 @@ pub fn new()->Self{
                 type_methods,
                 native: RefCell::new(native),
                 bodies: Default::default(),
+                modules: Default::default(),
             },
             threads: vec![ScriptThread::new()],
             heap: heap,
@@ -0,0 +1,164 @@
+use makepad_script::*;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+#[derive(Debug)]
+struct Pause{
+    reason: ScriptPauseReason,
+    line: u32,
+    depth: usize,
+    vars: Vec<(String, String)>,
+}
+
+// records every pause and answers with the next scripted command
+struct TestDebugger{
+    pauses: Rc<RefCell<Vec<Pause>>>,
+    commands: VecDeque<ScriptDebugCommand>,
+}
+
+impl ScriptDebugHandler for TestDebugger{
+    fn on_pause(&mut self, paused:&mut ScriptPaused)->ScriptDebugCommand{
+        let depth = paused.depth();
+        self.pauses.borrow_mut().push(Pause{
+            reason: paused.reason,
+            line: paused.loc().map(|loc| loc.line).unwrap_or(0),
+            depth,
+            vars: paused.scope_vars(depth - 1).into_iter().map(|v| (v.name, v.value)).collect(),
+        });
+        self.commands.pop_front().unwrap_or(ScriptDebugCommand::Continue)
+    }
+}
+
+const FILE: &str = "tests/debug.mps";
+
+// script! only keeps the line breaks of its source with MAKEPAD=lines, so
+// the tests build their blocks by hand. Lines count from 1
+fn block(code:&str)->ScriptBlock{
+    ScriptBlock{
+        file: FILE.to_string(),
+        code: code.to_string(),
+        ..Default::default()
+    }
+}
+
+const COUNTING: &str = "let total = 0
+fn add(a b){
+    let sum = a + b
+    sum
+}
+for i in [1 2 3]{
+    total = add(total i)
+}
+total";
+
+fn attach(vm:&mut ScriptVm, breakpoints:&[u32], commands:&[ScriptDebugCommand])->Rc<RefCell<Vec<Pause>>>{
+    let breakpoints = breakpoints.iter().map(|line| ScriptBreakpoint{file: FILE.to_string(), line: *line}).collect();
+    attach_at(vm, breakpoints, commands)
+}
+
+fn attach_at(vm:&mut ScriptVm, breakpoints:Vec<ScriptBreakpoint>, commands:&[ScriptDebugCommand])->Rc<RefCell<Vec<Pause>>>{
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut debug = ScriptDebug::new(Box::new(TestDebugger{
+        pauses: pauses.clone(),
+        commands: commands.iter().copied().collect(),
+    }));
+    debug.breakpoints = breakpoints;
+    vm.thread.attach_debugger(debug);
+    pauses
+}
+
+fn var<'a>(pause:&'a Pause, name:&str)->Option<&'a str>{
+    pause.vars.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn stops_on_breakpoints(){
+    let mut base = ScriptVmBase::new();
+    let vm = &mut base.as_ref();
+    let pauses = attach(vm, &[7], &[]);
+    vm.eval(block(COUNTING));
+
+    let pauses = pauses.borrow();
+    assert_eq!(pauses.len(), 3, "{:?}", pauses);
+    for (pause, i) in pauses.iter().zip(["1", "2", "3"]){
+        assert_eq!(pause.reason, ScriptPauseReason::Breakpoint);
+        assert_eq!(pause.line, 7);
+        assert_eq!(var(pause, "i"), Some(i));
+    }
+    assert_eq!(var(&pauses[0], "total"), Some("0"));
+    assert_eq!(var(&pauses[2], "total"), Some("3"));
+}
+
+#[test]
+fn breakpoint_files_match_whole_segments(){
+    for (file, hits) in [("debug.mps", 3), ("/repo/tests/debug.mps", 3), ("g.mps", 0), ("ts/debug.mps", 0), ("", 0)]{
+        let mut base = ScriptVmBase::new();
+        let vm = &mut base.as_ref();
+        let pauses = attach_at(vm, vec![ScriptBreakpoint{file: file.to_string(), line: 7}], &[]);
+        vm.eval(block(COUNTING));
+        assert_eq!(pauses.borrow().len(), hits, "{}", file);
+    }
+}
+
+#[test]
+fn steps_into_over_and_out(){
+    let mut base = ScriptVmBase::new();
+    let vm = &mut base.as_ref();
+    let pauses = attach(vm, &[7], &[
+        ScriptDebugCommand::StepInto,
+        ScriptDebugCommand::StepOver,
+        ScriptDebugCommand::StepOut,
+        ScriptDebugCommand::Detach,
+    ]);
+    vm.eval(block(COUNTING));
+    assert!(vm.thread.debugger().is_none());
+
+    let pauses = pauses.borrow();
+    let steps:Vec<_> = pauses.iter().map(|p| (p.reason, p.line)).collect();
+    assert_eq!(steps, [
+        (ScriptPauseReason::Breakpoint, 7),
+        (ScriptPauseReason::Step, 3),
+        (ScriptPauseReason::Step, 4),
+        (ScriptPauseReason::Step, 7),
+    ]);
+    assert_eq!(pauses[1].depth, pauses[0].depth + 1);
+    assert_eq!(pauses[2].depth, pauses[1].depth);
+    assert_eq!(pauses[3].depth, pauses[0].depth);
+    assert_eq!(var(&pauses[1], "a"), Some("0"));
+    assert_eq!(var(&pauses[1], "b"), Some("1"));
+    assert_eq!(var(&pauses[2], "sum"), Some("1"));
+}
+
+#[test]
+fn pause_request_and_step_over(){
+    let mut base = ScriptVmBase::new();
+    let vm = &mut base.as_ref();
+    let pauses = attach(vm, &[], &[ScriptDebugCommand::StepOver; 4]);
+    vm.thread.debugger().unwrap().request_pause();
+    vm.eval(block(COUNTING));
+
+    let pauses = pauses.borrow();
+    let steps:Vec<_> = pauses.iter().take(4).map(|p| (p.reason, p.line)).collect();
+    assert_eq!(steps, [
+        (ScriptPauseReason::PauseRequest, 1),
+        (ScriptPauseReason::Step, 2),
+        (ScriptPauseReason::Step, 6),
+        (ScriptPauseReason::Step, 7),
+    ]);
+    // stepping over the call to add never goes into it
+    assert!(pauses.iter().all(|p| p.line != 3));
+}
+
+#[test]
+fn pauses_on_errors(){
+    let mut base = ScriptVmBase::new();
+    let vm = &mut base.as_ref();
+    let pauses = attach(vm, &[], &[]);
+    vm.eval(block("let x = {t:1}\nx.y.z;"));
+
+    // the error it causes further up pauses again, the first one is where it happened
+    let pauses = pauses.borrow();
+    assert!(!pauses.is_empty());
+    assert!(matches!(pauses[0].reason, ScriptPauseReason::Error(_)));
+    assert_eq!(pauses[0].line, 2);
+    assert_eq!(var(&pauses[0], "x"), Some("{t:1}"));
+}
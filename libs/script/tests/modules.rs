@@ -0,0 +1,87 @@
+use makepad_script::*;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+#[derive(Default)]
+struct Files{
+    sources: HashMap<String, (String, u64)>,
+    stamped: Vec<String>,
+}
+
+// serves modules from memory, the stamp is bumped on every write
+struct MemLoader(Rc<RefCell<Files>>);
+
+impl ScriptModuleLoader for MemLoader{
+    fn resolve(&mut self, _package:&str, _from:&str, path:&str)->Option<String>{
+        let path = format!("/project/{}", path.trim_start_matches("./"));
+        self.0.borrow().sources.contains_key(&path).then_some(path)
+    }
+    fn load(&mut self, path:&str)->Option<String>{
+        self.0.borrow().sources.get(path).map(|(source, _)| source.clone())
+    }
+    fn stamp(&mut self, path:&str)->Option<u64>{
+        let mut files = self.0.borrow_mut();
+        files.stamped.push(path.to_string());
+        files.sources.get(path).map(|(_, stamp)| *stamp)
+    }
+}
+
+fn write(files:&Rc<RefCell<Files>>, path:&str, source:&str){
+    let mut files = files.borrow_mut();
+    let stamp = files.sources.get(path).map(|(_, stamp)| stamp + 1).unwrap_or(0);
+    files.sources.insert(path.to_string(), (source.to_string(), stamp));
+}
+
+fn eval(vm:&mut ScriptVm, code:&str)->ScriptValue{
+    vm.eval(ScriptBlock{
+        file: "tests/modules.mps".to_string(),
+        code: code.to_string(),
+        ..Default::default()
+    })
+}
+
+fn setup()->(ScriptVmBase, Rc<RefCell<Files>>){
+    let files = Rc::new(RefCell::new(Files::default()));
+    write(&files, "/project/base.mps", "let two = 2");
+    write(&files, "/project/util.mps", "use \"./base.mps\".two fn add(a b){a + b + two}");
+    write(&files, "/project/other.mps", "let one = 1");
+    let mut base = ScriptVmBase::new();
+    base.as_ref().set_module_loader(Box::new(MemLoader(files.clone())));
+    (base, files)
+}
+
+#[test]
+fn reload_checks_only_changed_files(){
+    let (mut base, files) = setup();
+    let vm = &mut base.as_ref();
+    assert_eq!(eval(vm, "use \"./util.mps\".add use \"./other.mps\".one return add(1 one);").as_f64(), Some(4.0));
+
+    files.borrow_mut().stamped.clear();
+    assert!(vm.reload_modules(&[]).is_empty());
+    assert!(vm.reload_modules(&["src/unrelated.rs".to_string()]).is_empty());
+    assert!(files.borrow().stamped.is_empty());
+
+    // an unchanged file is checked but not reloaded
+    assert!(vm.reload_modules(&["project/base.mps".to_string()]).is_empty());
+    assert_eq!(files.borrow().stamped, ["/project/base.mps"]);
+
+    // a changed module reloads with the modules importing it
+    write(&files, "/project/base.mps", "let two = 20");
+    let mut reloaded = vm.reload_modules(&["project/base.mps".to_string()]);
+    reloaded.sort();
+    assert_eq!(reloaded, ["/project/base.mps", "/project/util.mps"]);
+    assert_eq!(eval(vm, "use \"./util.mps\".add return add(1 1);").as_f64(), Some(22.0));
+}
+
+#[test]
+fn reload_reuses_the_module_body(){
+    let (mut base, files) = setup();
+    let vm = &mut base.as_ref();
+    eval(vm, "use \"./util.mps\".add add(1 1);");
+    let bodies = vm.code.bodies.borrow().len();
+    for i in 0..5{
+        write(&files, "/project/base.mps", &format!("let two = {}", i));
+        assert_eq!(vm.reload_modules(&["/project/base.mps".to_string()]).len(), 2);
+    }
+    eval(vm, "use \"./util.mps\".add add(1 1);");
+    assert_eq!(vm.code.bodies.borrow().len(), bodies);
+}
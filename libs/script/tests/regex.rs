@@ -0,0 +1,23 @@
+use makepad_script::*;
+
+fn chars(s:&str)->Vec<char>{
+    s.chars().collect()
+}
+
+fn spans(pat:&str, text:&str)->Vec<(usize, usize)>{
+    ScriptRegex::new(pat).unwrap().find_all(&chars(text)).iter().map(|m| (m.start(), m.end())).collect()
+}
+
+#[test]
+fn find_all_reuses_the_search(){
+    // every match starts on states the one before it went through
+    assert_eq!(spans("a+b?", "aab ab a aab"), vec![(0, 3), (4, 6), (7, 8), (9, 12)]);
+    assert_eq!(spans("(\\w+)@(\\w+)", "x@y, bob@host"), vec![(0, 3), (5, 13)]);
+    assert_eq!(spans("x*", "ax"), vec![(0, 0), (1, 2), (2, 2)]);
+    let re = ScriptRegex::new("(a)|(b)").unwrap();
+    let found = re.find_all(&chars("ab"));
+    assert_eq!(found[1].groups, vec![Some((1, 2)), None, Some((1, 2))]);
+    // a search per match would clear the whole set each time
+    let text = "x,".repeat(50000);
+    assert_eq!(spans("x", &text).len(), 50000);
+}
@@ -613,6 +613,12 @@ fn visit_loop(&mut self, type_: BlockType) -> Result<(), Self::Error> {
 
         self.push_block(BlockKind::Loop, type_);
 
+        // Every branch back to the start of the loop goes through this, so a loop that never
+        // ends runs out of fuel.
+        if !self.block(1).is_unreachable {
+            self.emit(exec::consume_fuel as ThreadedInstr);
+        }
+
         Ok(())
     }
 
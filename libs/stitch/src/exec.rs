@@ -362,6 +362,28 @@ macro_rules! r#try {
     ControlFlow::Trap(Trap::Unreachable).to_bits()
 });
 
+threaded_instr!(consume_fuel(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Use up one unit of fuel, if execution is limited
+    if let Some(fuel) = &mut (*(*cx).store).fuel {
+        if *fuel == 0 {
+            return ControlFlow::Trap(Trap::OutOfFuel).to_bits();
+        }
+        *fuel -= 1;
+    }
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
 threaded_instr!(br(
     ip: Ip,
     sp: Sp,
@@ -48,5 +48,6 @@
     ref_::{Ref, RefType},
     store::Store,
     table::{Table, TableError, TableType},
+    trap::Trap,
     val::{Val, ValType},
 };
@@ -31,6 +31,7 @@ pub struct Store {
     elems: Vec<AliasableBox<ElemEntity>>,
     datas: Vec<AliasableBox<DataEntity>>,
     externs: Vec<AliasableBox<ExternEntity>>,
+    pub(crate) fuel: Option<u64>,
 }
 
 impl Store {
@@ -47,6 +48,7 @@ pub fn new(engine: Engine) -> Self {
             elems: Vec::new(),
             datas: Vec::new(),
             externs: Vec::new(),
+            fuel: None,
         }
     }
 
@@ -54,6 +56,21 @@ pub fn engine(&self) -> &Engine {
         &self.engine
     }
 
+    /// Returns the fuel left in this [`Store`], or `None` if execution is not limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Limits how long Wasm code in this [`Store`] can run.
+    ///
+    /// Every iteration of a loop uses up one unit of fuel. Once the fuel is gone, execution traps
+    /// with [`Trap::OutOfFuel`]. `None` removes the limit.
+    ///
+    /// [`Trap::OutOfFuel`]: crate::Trap::OutOfFuel
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
     pub(crate) fn id(&self) -> StoreId {
         self.id
     }
@@ -1,5 +1,6 @@
 use std::{error::Error, fmt};
 
+/// A trap that occurred during the execution of a Wasm function.
 #[derive(Clone, Copy, Debug)]
 pub enum Trap {
     Unreachable,
@@ -11,6 +12,7 @@ pub enum Trap {
     TableAccessOutOfBounds,
     MemAccessOutOfBounds,
     StackOverflow,
+    OutOfFuel,
 }
 
 impl Trap {
@@ -25,6 +27,7 @@ pub(crate) fn from_usize(val: usize) -> Option<Self> {
             6 => Some(Self::TableAccessOutOfBounds),
             7 => Some(Self::MemAccessOutOfBounds),
             8 => Some(Self::StackOverflow),
+            9 => Some(Self::OutOfFuel),
             _ => None,
         }
     }
@@ -46,6 +49,7 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Self::TableAccessOutOfBounds => write!(f, "table access out of bounds"),
             Self::MemAccessOutOfBounds => write!(f, "memory access out of bounds"),
             Self::StackOverflow => write!(f, "stack overflow"),
+            Self::OutOfFuel => write!(f, "out of fuel"),
         }
     }
 }
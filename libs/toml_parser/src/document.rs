@@ -0,0 +1,140 @@
+use std::fmt;
+use crate::parser::{parse, parse_key_path, TomlLayout};
+use crate::toml::{TomlErr, TomlSpan};
+use crate::value::*;
+
+/// A document that is edited in place. Edits only touch the text of the
+/// values they change, comments and formatting everywhere else stay as is
+pub struct TomlDocument {
+    src: String,
+    root: TomlTable,
+    layout: TomlLayout,
+}
+
+impl TomlDocument {
+    pub fn parse(src: &str) -> Result<TomlDocument, TomlErr> {
+        let (root, layout) = parse(src) ?;
+        Ok(TomlDocument {src: src.to_string(), root, layout})
+    }
+
+    pub fn root(&self) -> &TomlTable {
+        &self.root
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+
+    /// Looks up a dotted key like `package.version` or `bin.0.name`
+    pub fn get(&self, path: &str) -> Option<&TomlItem> {
+        self.root.get_path(&parse_key_path(path).ok() ?)
+    }
+
+    /// Replaces the value at `path`, or adds it to the table it belongs in.
+    /// Missing tables are written as dotted keys in the nearest section
+    /// that exists, or as a new section at the end of the document
+    pub fn set(&mut self, path: &str, value: TomlValue) -> Result<(), TomlErr> {
+        let path = parse_key_path(path) ?;
+        if let Some(item) = self.root.get_path(&path) {
+            if matches!(item.value, TomlValue::Table(_)) && !self.is_inline(item) {
+                return Err(self.err(&format!("Can't replace table {}", path.join("."))))
+            }
+            let span = item.span.clone();
+            return self.splice(span.start, span.start + span.len, &value.to_string())
+        }
+        // the deepest table that is already there
+        let mut n = path.len() - 1;
+        while n > 0 && self.root.get_path(&path[..n]).is_none() {
+            n -= 1;
+        }
+        let rest = &path[n..];
+        if n == 0 {
+            if rest.len() > 1 {
+                let (key, table) = rest.split_last().unwrap();
+                let sep = if self.src.is_empty() {""} else if self.src.ends_with('\n') {"\n"} else {"\n\n"};
+                let text = format!("{}[{}]\n{} = {}\n", sep, dotted(table), TomlKey(key), value);
+                let end = self.src.len();
+                return self.splice(end, end, &text)
+            }
+            return self.insert_entry(&[], rest, &value)
+        }
+        let item = self.root.get_path(&path[..n]).unwrap();
+        if !matches!(item.value, TomlValue::Table(_)) {
+            return Err(self.err(&format!("Key {} is not a table", path[..n].join("."))))
+        }
+        if self.is_inline(item) {
+            let span = item.span.clone();
+            let close = span.start + span.len - 1;
+            let inner = self.src[span.start + 1..close].trim_end();
+            if inner.trim_start().is_empty() {
+                return self.splice(span.start, span.start + span.len, &format!("{{ {} = {} }}", dotted(rest), value))
+            }
+            let at = span.start + 1 + inner.len();
+            return self.splice(at, at, &format!(", {} = {}", dotted(rest), value))
+        }
+        self.insert_entry(&path[..n], rest, &value)
+    }
+
+    /// Removes a key with the lines it is on, or a [section] up to the next
+    /// one. Returns false when the key isn't there
+    pub fn remove(&mut self, path: &str) -> Result<bool, TomlErr> {
+        let path = parse_key_path(path) ?;
+        if let Some(entry) = self.layout.entries.iter().find( | e | e.path == path) {
+            let (start, end) = (entry.start, entry.end);
+            self.splice(start, end, "")?;
+            return Ok(true)
+        }
+        if let Some(i) = self.layout.sections.iter().position( | s | !s.path.is_empty() && s.path == path) {
+            let start = self.layout.sections[i].start;
+            let end = self.layout.sections.get(i + 1).map( | s | s.start).unwrap_or(self.src.len());
+            self.splice(start, end, "")?;
+            return Ok(true)
+        }
+        if self.root.get_path(&path).is_some() {
+            return Err(self.err(&format!("Can't remove {}, it isn't on a line of its own", path.join("."))))
+        }
+        Ok(false)
+    }
+
+    // `table` exists and is defined by a section or dotted keys
+    fn insert_entry(&mut self, table: &[String], rest: &[String], value: &TomlValue) -> Result<(), TomlErr> {
+        let section = self.layout.sections.iter().rev()
+            .filter( | s | table.starts_with(&s.path))
+            .max_by_key( | s | s.path.len())
+            .unwrap();
+        let mut key = table[section.path.len()..].to_vec();
+        key.extend_from_slice(rest);
+        let at = section.end;
+        let sep = if at > 0 && !self.src[..at].ends_with('\n') {"\n"} else {""};
+        self.splice(at, at, &format!("{}{} = {}\n", sep, dotted(&key), value))
+    }
+
+    fn is_inline(&self, item: &TomlItem) -> bool {
+        item.span.len > 0 && self.src.as_bytes().get(item.span.start) == Some(&b'{')
+    }
+
+    fn err(&self, msg: &str) -> TomlErr {
+        TomlErr {msg: msg.to_string(), span: TomlSpan {start: 0, len: 0}, line: 0, col: 0}
+    }
+
+    // edits the text and parses it again, nothing changes if that fails
+    fn splice(&mut self, start: usize, end: usize, with: &str) -> Result<(), TomlErr> {
+        let mut src = String::with_capacity(self.src.len() + with.len());
+        src.push_str(&self.src[..start]);
+        src.push_str(with);
+        src.push_str(&self.src[end..]);
+        let (root, layout) = parse(&src) ?;
+        *self = TomlDocument {src, root, layout};
+        Ok(())
+    }
+}
+
+fn dotted(path: &[String]) -> String {
+    path.iter().map( | k | TomlKey(k).to_string()).collect::<Vec<_>>().join(".")
+}
+
+impl fmt::Display for TomlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.src)
+    }
+}
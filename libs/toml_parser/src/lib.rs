@@ -1,2 +1,8 @@
 mod toml;
-pub use crate::toml::*;
\ No newline at end of file
+mod value;
+mod parser;
+mod document;
+
+pub use crate::toml::*;
+pub use crate::value::*;
+pub use crate::document::*;
@@ -0,0 +1,678 @@
+use std::collections::HashMap;
+use crate::toml::{TomlErr, TomlSpan};
+use crate::value::*;
+
+// A TOML 1.0 parser. Which tables can still be extended follows the flags of
+// Python's tomllib: FROZEN for inline tables and arrays written as values,
+// EXPLICIT_NEST for tables that have been defined by a header or by dotted
+// keys in an earlier section
+
+const FROZEN: u8 = 1;
+const EXPLICIT_NEST: u8 = 2;
+
+// values nested deeper than this are refused instead of running out of stack
+const MAX_DEPTH: usize = 128;
+
+#[derive(Default)]
+struct Flags {
+    flags: u8,
+    recursive: u8,
+    nested: HashMap<String, Flags>,
+}
+
+impl Flags {
+    fn set(&mut self, key: &[String], flag: u8, recursive: bool) {
+        let mut f = self;
+        for k in key {
+            f = f.nested.entry(k.clone()).or_default();
+        }
+        if recursive {
+            f.recursive |= flag;
+        }
+        else {
+            f.flags |= flag;
+        }
+    }
+
+    fn unset_all(&mut self, key: &[String]) {
+        let Some((last, parent)) = key.split_last() else {return};
+        let mut f = self;
+        for k in parent {
+            match f.nested.get_mut(k) {
+                Some(n) => f = n,
+                None => return
+            }
+        }
+        f.nested.remove(last);
+    }
+
+    fn is(&self, key: &[String], flag: u8) -> bool {
+        let Some((last, parent)) = key.split_last() else {return false};
+        let mut f = self;
+        for k in parent {
+            match f.nested.get(k) {
+                Some(n) if n.recursive & flag != 0 => return true,
+                Some(n) => f = n,
+                None => return false
+            }
+        }
+        f.nested.get(last).map( | n | (n.flags | n.recursive) & flag != 0).unwrap_or(false)
+    }
+}
+
+/// Where the sections and their entries are, so documents can be edited
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TomlLayout {
+    pub sections: Vec<TomlSection>,
+    pub entries: Vec<TomlEntry>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TomlSection {
+    // the table of the section, with the index of array of tables items
+    pub path: Vec<String>,
+    // from the start of the header line to the end of the last entry
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TomlEntry {
+    pub path: Vec<String>,
+    // the lines of the key and its value, with the last newline
+    pub start: usize,
+    pub end: usize,
+}
+
+pub(crate) struct TomlParser<'a> {
+    src: &'a str,
+    b: &'a [u8],
+    o: usize,
+    root: TomlTable,
+    flags: Flags,
+    pending: Vec<Vec<String>>,
+    header: Vec<String>,
+    layout: TomlLayout,
+}
+
+pub(crate) fn parse(src: &str) -> Result<(TomlTable, TomlLayout), TomlErr> {
+    let mut p = TomlParser {
+        src,
+        b: src.as_bytes(),
+        o: 0,
+        root: TomlTable::default(),
+        flags: Flags::default(),
+        pending: Vec::new(),
+        header: Vec::new(),
+        layout: TomlLayout::default(),
+    };
+    p.layout.sections.push(TomlSection {path: Vec::new(), start: 0, end: 0});
+    p.document()?;
+    Ok((p.root, p.layout))
+}
+
+/// Parses a dotted key like `package.metadata."my key"` into its parts
+pub(crate) fn parse_key_path(src: &str) -> Result<Vec<String>, TomlErr> {
+    let (root, layout) = (TomlTable::default(), TomlLayout::default());
+    let mut p = TomlParser {src, b: src.as_bytes(), o: 0, root, flags: Flags::default(), pending: Vec::new(), header: Vec::new(), layout};
+    let (key, _) = p.key()?;
+    p.ws();
+    if p.o != p.b.len() {
+        return Err(p.err_at("Expected the end of the key", p.o))
+    }
+    Ok(key)
+}
+
+impl<'a> TomlParser<'a> {
+    fn err_at(&self, msg: &str, at: usize) -> TomlErr {
+        TomlErr::new(msg, TomlSpan {start: at, len: 0}, self.src)
+    }
+
+    fn err_span(&self, msg: &str, span: &TomlSpan) -> TomlErr {
+        TomlErr::new(msg, span.clone(), self.src)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.b.get(self.o).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.b[self.o..].starts_with(s.as_bytes())
+    }
+
+    fn ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.o += 1;
+        }
+    }
+
+    fn newline(&mut self) -> bool {
+        match self.peek() {
+            Some(b'\n') => {self.o += 1; true}
+            Some(b'\r') if self.b.get(self.o + 1) == Some(&b'\n') => {self.o += 2; true}
+            _ => false
+        }
+    }
+
+    fn comment(&mut self) -> Result<(), TomlErr> {
+        if self.peek() != Some(b'#') {
+            return Ok(())
+        }
+        while let Some(c) = self.peek() {
+            if c == b'\n' || c == b'\r' && self.b.get(self.o + 1) == Some(&b'\n') {
+                break
+            }
+            if is_control(c) {
+                return Err(self.err_at("Control characters aren't allowed in comments", self.o))
+            }
+            self.o += 1;
+        }
+        Ok(())
+    }
+
+    // whitespace, a comment and the end of the line
+    fn eol(&mut self) -> Result<(), TomlErr> {
+        self.ws();
+        self.comment()?;
+        if self.o == self.b.len() || self.newline() {
+            return Ok(())
+        }
+        Err(self.err_at("Expected the end of the line", self.o))
+    }
+
+    // whitespace, comments and newlines, as allowed in arrays
+    fn ws_comments_newlines(&mut self) -> Result<(), TomlErr> {
+        loop {
+            self.ws();
+            self.comment()?;
+            if !self.newline() {
+                return Ok(())
+            }
+        }
+    }
+
+    fn line_start(&self, at: usize) -> usize {
+        self.b[..at].iter().rposition( | c | *c == b'\n').map( | p | p + 1).unwrap_or(0)
+    }
+
+    fn document(&mut self) -> Result<(), TomlErr> {
+        loop {
+            self.ws();
+            match self.peek() {
+                None => return Ok(()),
+                Some(b'#') => self.eol()?,
+                Some(b'\n' | b'\r') => {
+                    if !self.newline() {
+                        return Err(self.err_at("A carriage return has to be followed by a newline", self.o))
+                    }
+                }
+                Some(b'[') => self.table_header()?,
+                Some(_) => self.key_value()?,
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<(Vec<String>, TomlSpan), TomlErr> {
+        let mut key = Vec::new();
+        self.ws();
+        let start = self.o;
+        loop {
+            self.ws();
+            if self.starts_with("\"\"\"") || self.starts_with("'''") {
+                return Err(self.err_at("Keys can't be multiline strings", self.o))
+            }
+            let part = match self.peek() {
+                Some(b'"') => self.basic_string()?,
+                Some(b'\'') => self.literal_string()?,
+                _ => {
+                    let s = self.o;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'-') {
+                        self.o += 1;
+                    }
+                    if s == self.o {
+                        return Err(self.err_at("Expected a key", self.o))
+                    }
+                    self.src[s..self.o].to_string()
+                }
+            };
+            key.push(part);
+            let end = self.o;
+            self.ws();
+            if self.peek() == Some(b'.') {
+                self.o += 1;
+                continue
+            }
+            return Ok((key, TomlSpan {start, len: end - start}))
+        }
+    }
+
+    fn table_header(&mut self) -> Result<(), TomlErr> {
+        let start = self.o;
+        for key in std::mem::take(&mut self.pending) {
+            self.flags.set(&key, EXPLICIT_NEST, false);
+        }
+        self.o += 1;
+        let array = self.peek() == Some(b'[');
+        if array {
+            self.o += 1;
+        }
+        let (key, span) = self.key()?;
+        let close = if array {"]]"} else {"]"};
+        if !self.starts_with(close) {
+            return Err(self.err_at(&format!("Expected {}", close), self.o))
+        }
+        self.o += close.len();
+        let mut path = Vec::new();
+        let name = key.join(".");
+        if array {
+            if self.flags.is(&key, FROZEN) {
+                return Err(self.err_span(&format!("Can't append to {}, it is an inline array", name), &span))
+            }
+            self.flags.unset_all(&key);
+            self.flags.set(&key, EXPLICIT_NEST, false);
+            let (stem, parent) = key.split_last().unwrap();
+            let table = nest(&mut self.root, parent, &mut path, true, &span).map_err( | msg | TomlErr::new(&msg, span.clone(), self.src))?;
+            let item = TomlItem {value: TomlValue::Table(TomlTable::default()), span: span.clone()};
+            let index = match table.get_mut(stem) {
+                Some(TomlItem {value: TomlValue::Array(items), ..}) => {
+                    items.push(item);
+                    items.len() - 1
+                }
+                Some(_) => return Err(self.err_span(&format!("Can't define {} as an array of tables, it is already defined", name), &span)),
+                None => {
+                    table.entries.push((stem.clone(), TomlItem {value: TomlValue::Array(vec![item]), span: span.clone()}));
+                    0
+                }
+            };
+            path.push(stem.clone());
+            path.push(index.to_string());
+        }
+        else {
+            if self.flags.is(&key, EXPLICIT_NEST) || self.flags.is(&key, FROZEN) {
+                return Err(self.err_span(&format!("Table {} is already defined", name), &span))
+            }
+            self.flags.set(&key, EXPLICIT_NEST, false);
+            nest(&mut self.root, &key, &mut path, true, &span).map_err( | msg | TomlErr::new(&msg, span.clone(), self.src))?;
+        }
+        self.header = key;
+        self.eol()?;
+        self.layout.sections.push(TomlSection {path, start, end: self.o});
+        Ok(())
+    }
+
+    fn key_value(&mut self) -> Result<(), TomlErr> {
+        let start = self.line_start(self.o);
+        let (key, span) = self.key()?;
+        if self.peek() != Some(b'=') {
+            return Err(self.err_at("Expected =", self.o))
+        }
+        self.o += 1;
+        self.ws();
+        let value = self.value(0)?;
+
+        let mut abs = self.header.clone();
+        for (i, part) in key.iter().enumerate() {
+            if i + 1 < key.len() {
+                abs.push(part.clone());
+                if self.flags.is(&abs, EXPLICIT_NEST) {
+                    return Err(self.err_span(&format!("Table {} is already defined", abs.join(".")), &span))
+                }
+                self.pending.push(abs.clone());
+            }
+        }
+        if self.flags.is(&abs, FROZEN) {
+            return Err(self.err_span(&format!("Can't add keys to {}, it is an inline table", abs.join(".")), &span))
+        }
+        let stem = key.last().unwrap();
+        let mut path = Vec::new();
+        let table = nest(&mut self.root, &abs, &mut path, true, &span).map_err( | msg | TomlErr::new(&msg, span.clone(), self.src))?;
+        if table.get(stem).is_some() {
+            return Err(self.err_span(&format!("Duplicate key {}", stem), &span))
+        }
+        abs.push(stem.clone());
+        if matches!(value.value, TomlValue::Table(_) | TomlValue::Array(_)) {
+            self.flags.set(&abs, FROZEN, true);
+        }
+        table.entries.push((stem.clone(), value));
+        path.push(stem.clone());
+        self.eol()?;
+        self.layout.entries.push(TomlEntry {path, start, end: self.o});
+        self.layout.sections.last_mut().unwrap().end = self.o;
+        Ok(())
+    }
+
+    fn value(&mut self, depth: usize) -> Result<TomlItem, TomlErr> {
+        if depth > MAX_DEPTH {
+            return Err(self.err_at("Values are nested too deep", self.o))
+        }
+        let start = self.o;
+        let value = match self.peek() {
+            Some(b'"') => TomlValue::Str(self.basic_string()?),
+            Some(b'\'') => TomlValue::Str(self.literal_string()?),
+            Some(b'[') => self.array(depth)?,
+            Some(b'{') => self.inline_table(depth)?,
+            Some(b't') if self.starts_with("true") => {self.o += 4; TomlValue::Bool(true)}
+            Some(b'f') if self.starts_with("false") => {self.o += 5; TomlValue::Bool(false)}
+            Some(c) if c.is_ascii_digit() || c == b'+' || c == b'-' || c == b'i' || c == b'n' => self.number_or_datetime()?,
+            _ => return Err(self.err_at("Expected a value", self.o))
+        };
+        Ok(TomlItem {value, span: TomlSpan {start, len: self.o - start}})
+    }
+
+    fn array(&mut self, depth: usize) -> Result<TomlValue, TomlErr> {
+        self.o += 1;
+        let mut items = Vec::new();
+        loop {
+            self.ws_comments_newlines()?;
+            if self.peek() == Some(b']') {
+                self.o += 1;
+                return Ok(TomlValue::Array(items))
+            }
+            items.push(self.value(depth + 1)?);
+            self.ws_comments_newlines()?;
+            match self.peek() {
+                Some(b',') => self.o += 1,
+                Some(b']') => (),
+                _ => return Err(self.err_at("Expected , or ] in array", self.o))
+            }
+        }
+    }
+
+    fn inline_table(&mut self, depth: usize) -> Result<TomlValue, TomlErr> {
+        self.o += 1;
+        let mut table = TomlTable::default();
+        let mut flags = Flags::default();
+        self.ws();
+        if self.peek() == Some(b'}') {
+            self.o += 1;
+            return Ok(TomlValue::Table(table))
+        }
+        loop {
+            let (key, span) = self.key()?;
+            if self.peek() != Some(b'=') {
+                return Err(self.err_at("Expected =", self.o))
+            }
+            self.o += 1;
+            self.ws();
+            let value = self.value(depth + 1)?;
+            if flags.is(&key, FROZEN) {
+                return Err(self.err_span(&format!("Can't add keys to {}, it is an inline table", key.join(".")), &span))
+            }
+            let (stem, parent) = key.split_last().unwrap();
+            let nest = nest(&mut table, parent, &mut Vec::new(), false, &span).map_err( | msg | TomlErr::new(&msg, span.clone(), self.src))?;
+            if nest.get(stem).is_some() {
+                return Err(self.err_span(&format!("Duplicate key {}", stem), &span))
+            }
+            if matches!(value.value, TomlValue::Table(_) | TomlValue::Array(_)) {
+                flags.set(&key, FROZEN, true);
+            }
+            nest.entries.push((stem.clone(), value));
+            self.ws();
+            match self.peek() {
+                Some(b',') => self.o += 1,
+                Some(b'}') => {
+                    self.o += 1;
+                    return Ok(TomlValue::Table(table))
+                }
+                _ => return Err(self.err_at("Expected , or } in inline table", self.o))
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlErr> {
+        let multiline = self.starts_with("\"\"\"");
+        if multiline {
+            self.o += 3;
+            self.newline();
+        }
+        else {
+            self.o += 1;
+        }
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.err_at("Unterminated string", self.o))
+            };
+            match c {
+                b'"' if !multiline => {
+                    self.o += 1;
+                    return Ok(out)
+                }
+                b'"' if self.starts_with("\"\"\"") => {
+                    if self.close_quotes(b'"', &mut out)? {
+                        return Ok(out)
+                    }
+                }
+                b'\\' => {
+                    self.o += 1;
+                    if multiline && self.line_ending_backslash() {
+                        continue
+                    }
+                    self.escape(&mut out)?;
+                }
+                b'\n' | b'\r' if multiline => {
+                    if !self.newline() {
+                        return Err(self.err_at("A carriage return has to be followed by a newline", self.o))
+                    }
+                    out.push('\n');
+                }
+                c if is_control(c) => return Err(self.err_at("Control characters have to be escaped in strings", self.o)),
+                _ => self.push_char(&mut out)
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlErr> {
+        let multiline = self.starts_with("'''");
+        if multiline {
+            self.o += 3;
+            self.newline();
+        }
+        else {
+            self.o += 1;
+        }
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.err_at("Unterminated string", self.o))
+            };
+            match c {
+                b'\'' if !multiline => {
+                    self.o += 1;
+                    return Ok(out)
+                }
+                b'\'' if self.starts_with("'''") => {
+                    if self.close_quotes(b'\'', &mut out)? {
+                        return Ok(out)
+                    }
+                }
+                b'\n' | b'\r' if multiline => {
+                    if !self.newline() {
+                        return Err(self.err_at("A carriage return has to be followed by a newline", self.o))
+                    }
+                    out.push('\n');
+                }
+                c if is_control(c) => return Err(self.err_at("Control characters aren't allowed in literal strings", self.o)),
+                _ => self.push_char(&mut out)
+            }
+        }
+    }
+
+    // three to five quotes end a multiline string, the ones past three are
+    // part of it
+    fn close_quotes(&mut self, quote: u8, out: &mut String) -> Result<bool, TomlErr> {
+        let mut n = 0;
+        while self.peek() == Some(quote) {
+            n += 1;
+            self.o += 1;
+        }
+        if n > 5 {
+            return Err(self.err_at("Too many quotes at the end of a multiline string", self.o))
+        }
+        for _ in 3..n {
+            out.push(quote as char);
+        }
+        Ok(true)
+    }
+
+    fn push_char(&mut self, out: &mut String) {
+        let c = self.src[self.o..].chars().next().unwrap();
+        out.push(c);
+        self.o += c.len_utf8();
+    }
+
+    // a backslash at the end of a line trims the whitespace after it
+    fn line_ending_backslash(&mut self) -> bool {
+        let save = self.o;
+        self.ws();
+        if !self.newline() {
+            self.o = save;
+            return false
+        }
+        loop {
+            self.ws();
+            if !self.newline() {
+                return true
+            }
+        }
+    }
+
+    fn escape(&mut self, out: &mut String) -> Result<(), TomlErr> {
+        let at = self.o - 1;
+        let c = match self.peek() {
+            Some(b'b') => '\u{8}',
+            Some(b't') => '\t',
+            Some(b'n') => '\n',
+            Some(b'f') => '\u{c}',
+            Some(b'r') => '\r',
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(u @ (b'u' | b'U')) => {
+                let len = if u == b'u' {4} else {8};
+                let hex = self.src.get(self.o + 1..self.o + 1 + len).filter( | h | h.bytes().all( | c | c.is_ascii_hexdigit()));
+                let c = hex.and_then( | h | u32::from_str_radix(h, 16).ok()).and_then(char::from_u32);
+                let Some(c) = c else {
+                    return Err(self.err_at("Invalid unicode escape", at))
+                };
+                self.o += len;
+                c
+            }
+            _ => return Err(self.err_at("Invalid escape in string", at))
+        };
+        self.o += 1;
+        out.push(c);
+        Ok(())
+    }
+
+    fn number_or_datetime(&mut self) -> Result<TomlValue, TomlErr> {
+        let start = self.o;
+        let run = | p: &Self, from: usize | {
+            let mut o = from;
+            while matches!(p.b.get(o), Some(c) if c.is_ascii_alphanumeric() || matches!(c, b'_' | b'+' | b'-' | b'.' | b':')) {
+                o += 1;
+            }
+            o
+        };
+        let mut end = run(self, start);
+        let tok = &self.src[start..end];
+        // a date and a time can be separated by a space
+        if tok.len() == 10 && tok.as_bytes()[4] == b'-' && self.b.get(end) == Some(&b' ')
+            && matches!(self.b.get(end + 1..end + 4), Some([h1, h2, b':']) if h1.is_ascii_digit() && h2.is_ascii_digit()) {
+            end = run(self, end + 1);
+        }
+        let tok = &self.src[start..end];
+        self.o = end;
+        let b = tok.as_bytes();
+        let is_datetime = b.len() >= 8 && (b[2] == b':' || b.len() >= 10 && b[4] == b'-' && b[..4].iter().all( | c | c.is_ascii_digit()));
+        if is_datetime {
+            return match TomlDatetime::parse(tok) {
+                Some(dt) => Ok(TomlValue::Datetime(dt)),
+                None => Err(self.err_at(&format!("Invalid datetime {}", tok), start))
+            }
+        }
+        parse_number(tok).ok_or_else( || self.err_at(&format!("Invalid number {}", tok), start))
+    }
+}
+
+// walks `path` from `table` creating tables, arrays of tables go to their
+// last table when `access_lists` is set. `index_path` gets the path with the
+// index of those tables
+fn nest<'t>(mut table: &'t mut TomlTable, path: &[String], index_path: &mut Vec<String>, access_lists: bool, span: &TomlSpan) -> Result<&'t mut TomlTable, String> {
+    for key in path {
+        index_path.push(key.clone());
+        if table.get(key).is_none() {
+            table.entries.push((key.clone(), TomlItem {value: TomlValue::Table(TomlTable::default()), span: span.clone()}));
+        }
+        let item = table.get_mut(key).unwrap();
+        table = match &mut item.value {
+            TomlValue::Table(t) => t,
+            TomlValue::Array(items) if access_lists => {
+                index_path.push(items.len().saturating_sub(1).to_string());
+                match items.last_mut() {
+                    Some(TomlItem {value: TomlValue::Table(t), ..}) => t,
+                    _ => return Err(format!("Key {} is not a table", key))
+                }
+            }
+            _ => return Err(format!("Key {} is not a table", key))
+        };
+    }
+    Ok(table)
+}
+
+fn is_control(c: u8) -> bool {
+    c < 0x20 && c != b'\t' || c == 0x7f
+}
+
+// underscores have to be between digits
+fn digits_ok(s: &str, radix: u32) -> bool {
+    !s.is_empty() && !s.starts_with('_') && !s.ends_with('_') && !s.contains("__")
+        && s.chars().all( | c | c == '_' || c.is_digit(radix))
+}
+
+fn parse_number(tok: &str) -> Option<TomlValue> {
+    let (sign, body) = match tok.as_bytes().first() {
+        Some(b'+') => ("", &tok[1..]),
+        Some(b'-') => ("-", &tok[1..]),
+        _ => ("", tok)
+    };
+    match body {
+        "inf" => return Some(TomlValue::Float(if sign == "-" {f64::NEG_INFINITY} else {f64::INFINITY})),
+        "nan" => return Some(TomlValue::Float(if sign == "-" {-f64::NAN} else {f64::NAN})),
+        _ => ()
+    }
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = body.strip_prefix(prefix) {
+            if tok.len() != body.len() || !digits_ok(digits, radix) {
+                return None
+            }
+            return i64::from_str_radix(&digits.replace('_', ""), radix).ok().map(TomlValue::Int)
+        }
+    }
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(e) => (&body[..e], Some(&body[e + 1..])),
+        None => (body, None)
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None)
+    };
+    if !digits_ok(int, 10) || int.len() > 1 && int.starts_with('0') {
+        return None
+    }
+    if let Some(frac) = frac {
+        if !digits_ok(frac, 10) {
+            return None
+        }
+    }
+    if let Some(exponent) = exponent {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if !digits_ok(exponent, 10) {
+            return None
+        }
+    }
+    let clean = format!("{}{}", sign, body.replace('_', ""));
+    if frac.is_none() && exponent.is_none() {
+        return clean.parse().ok().map(TomlValue::Int)
+    }
+    clean.parse().ok().map(TomlValue::Float)
+}
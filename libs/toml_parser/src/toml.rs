@@ -1,46 +1,14 @@
 use std::collections::{HashMap};
-use std::str::Chars;
-
-#[derive(Default)]
-pub struct TomlParser {
-    pub cur: char,
-    pub pos: usize,
-}
+use crate::parser::parse;
+use crate::value::*;
 
+/// A byte range in the source
 #[derive(PartialEq, Debug, Clone)]
 pub struct TomlSpan {
     pub start: usize,
     pub len: usize
 }
 
-#[derive(PartialEq, Debug)]
-pub struct TomlTokWithSpan {
-    pub span: TomlSpan,
-    pub tok: TomlTok
-}
-
-#[derive(PartialEq, Debug)]
-pub enum TomlTok {
-    Ident(String),
-    Str(String),
-    U64(u64),
-    I64(i64),
-    F64(f64),
-    Bool(bool),
-    Nan(bool),
-    Inf(bool),
-    Date(String),
-    Equals,
-    BlockOpen,
-    BlockClose,
-    ObjectOpen,
-    ObjectClose,
-    Comma,
-    Bof,
-    Eof
-}
-
-
 #[derive(PartialEq, Debug, Clone)]
 pub enum Toml {
     Str(String, TomlSpan),
@@ -62,352 +30,83 @@ pub fn into_str(self) -> Option<String> {
 pub struct TomlErr {
     pub msg: String,
     pub span: TomlSpan,
+    // 1 based, 0 when the error isn't tied to a source
+    pub line: usize,
+    pub col: usize,
+}
+
+impl TomlErr {
+    /// An error at `span` in `src`, with its line and column
+    pub fn new(msg: &str, span: TomlSpan, src: &str) -> TomlErr {
+        TomlErr {msg: msg.to_string(), span, line: 0, col: 0}.locate(src)
+    }
+
+    /// Fills in the line and column of the span in `src`
+    pub fn locate(mut self, src: &str) -> TomlErr {
+        let Some(before) = src.get(..self.span.start) else {return self};
+        self.line = before.matches('\n').count() + 1;
+        self.col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        self
+    }
 }
 
 impl std::fmt::Debug for TomlErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Toml error: {}, start:{} len:{}", self.msg, self.span.start, self.span.len)
+        write!(f, "Toml error: {}, line:{} col:{}", self.msg, self.line, self.col)
     }
 }
 
+/// Parses a document into its root table
+pub fn parse_toml_table(data: &str) -> Result<TomlTable, TomlErr> {
+    parse(data).map( | (root, _) | root)
+}
+
+/// Parses a document into a flat map of dotted keys. Keys that aren't bare
+/// are quoted, integers become numbers, arrays of tables keep the keys of
+/// their last table and tables inside arrays are left out
 pub fn parse_toml(data: &str) -> Result<HashMap<String, Toml>, TomlErr> {
-    let i = &mut data.chars();
-    let mut t = TomlParser::default();
-    t.next(i);
+    let root = parse_toml_table(data) ?;
     let mut out = HashMap::new();
-    let mut local_scope = String::new();
-    loop {
-        let tok = t.next_tok(i) ?;
-        match tok.tok {
-            TomlTok::Eof => { // at eof.
-                return Ok(out);
-            },
-            TomlTok::BlockOpen => { // its a scope
-                // we should expect an ident or a string
-                let tok = t.next_tok(i) ?;
-                let (tok, double_block) = if let TomlTok::BlockOpen = tok.tok {
-                    (t.next_tok(i) ?, true)
-                }
-                else {(tok, false)};
-                
-                match tok.tok {
-                    TomlTok::Str(key) => { // a key
-                        local_scope = key;
-                    },
-                    TomlTok::Ident(key) => { // also a key
-                        local_scope = key;
-                    },
-                    _ => return Err(t.err_token(tok))
-                }
-                let tok = t.next_tok(i) ?;
-                
-                if tok.tok != TomlTok::BlockClose {
-                    return Err(t.err_token(tok))
-                }
-                if double_block {
-                    let tok = t.next_tok(i) ?;
-                    if tok.tok != TomlTok::BlockClose {
-                        return Err(t.err_token(tok))
-                    }
-                }
-            },
-            TomlTok::Str(key) => { // a key
-                t.parse_key_value(&local_scope, key, i, &mut out) ?;
-            },
-            TomlTok::Ident(key) => { // also a key
-                t.parse_key_value(&local_scope, key, i, &mut out) ?;
-            },
-            _ => return Err(t.err_token(tok))
-        }
-    }
+    flatten_table("", &root, &mut out);
+    Ok(out)
 }
 
-impl TomlParser {
-    pub fn to_val(&mut self, tok: TomlTokWithSpan, i: &mut Chars) -> Result<Toml, TomlErr> {
-        match tok.tok {
-            TomlTok::BlockOpen => {
-                let mut vals = Vec::new();
-                loop {
-                    let tok = self.next_tok(i) ?;
-                    match tok.tok {
-                        TomlTok::BlockClose | TomlTok::Eof => {
-                            break;
-                        }
-                        TomlTok::Comma => {}
-                        _ => {
-                            vals.push(self.to_val(tok, i) ?);
-                        }
-                    }
-                }
-                Ok(Toml::Array(vals))
-            },
-            TomlTok::Str(v) => Ok(Toml::Str(v, tok.span)),
-            TomlTok::U64(v) => Ok(Toml::Num(v as f64, tok.span)),
-            TomlTok::I64(v) => Ok(Toml::Num(v as f64, tok.span)),
-            TomlTok::F64(v) => Ok(Toml::Num(v, tok.span)),
-            TomlTok::Bool(v) => Ok(Toml::Bool(v, tok.span)),
-            TomlTok::Nan(v) => Ok(Toml::Num(if v {-std::f64::NAN}else {std::f64::NAN}, tok.span)),
-            TomlTok::Inf(v) => Ok(Toml::Num(if v {-std::f64::INFINITY}else {std::f64::INFINITY}, tok.span)),
-            TomlTok::Date(v) => Ok(Toml::Date(v, tok.span)),
-            _ => Err(self.err_token(tok))
-        }
-    }
-    
-    pub fn parse_key_value(&mut self, local_scope: &str, key: String, i: &mut Chars, out: &mut HashMap<String, Toml>) -> Result<(), TomlErr> {
-        let tok = self.next_tok(i) ?;
-        if tok.tok != TomlTok::Equals {
-            return Err(self.err_token(tok));
-        }
-        let tok = self.next_tok(i) ?;
-        // if we are an ObjectOpen we do a subscope
-        if let TomlTok::ObjectOpen = tok.tok {
-            let local_scope = if !local_scope.is_empty() {
-                format!("{}.{}", local_scope, key)
-            }
-            else {
-                key
-            };
-            loop {
-                let tok = self.next_tok(i) ?;
-                match tok.tok {
-                    TomlTok::ObjectClose | TomlTok::Eof => {
-                        break;
+fn flat_key(prefix: &str, key: &str) -> String {
+    let bare = !key.is_empty() && key.bytes().all( | c | c.is_ascii_alphanumeric() || c == b'_' || c == b'-');
+    let key = if bare {key.to_string()}
+    else if key.contains('\'') {format!("\"{}\"", key)}
+    else {format!("'{}'", key)};
+    if prefix.is_empty() {key} else {format!("{}.{}", prefix, key)}
+}
+
+fn flatten_table(prefix: &str, table: &TomlTable, out: &mut HashMap<String, Toml>) {
+    for (key, item) in &table.entries {
+        let key = flat_key(prefix, key);
+        match &item.value {
+            TomlValue::Table(table) => flatten_table(&key, table, out),
+            TomlValue::Array(items) if !items.is_empty() && items.iter().all( | i | matches!(i.value, TomlValue::Table(_))) => {
+                for item in items {
+                    if let TomlValue::Table(table) = &item.value {
+                        flatten_table(&key, table, out);
                     }
-                    TomlTok::Comma => {}
-                    TomlTok::Str(key) => { // a key
-                        self.parse_key_value(&local_scope, key, i, out) ?;
-                    },
-                    TomlTok::Ident(key) => { // also a key
-                        self.parse_key_value(&local_scope, key, i, out) ?;
-                    },
-                    _ => return Err(self.err_token(tok))
                 }
             }
-        }
-        else {
-            let val = self.to_val(tok, i) ?;
-            let key = if !local_scope.is_empty() {
-                format!("{}.{}", local_scope, key)
+            _ => if let Some(value) = flat_value(item) {
+                out.insert(key, value);
             }
-            else {
-                key
-            };
-            out.insert(key, val);
         }
-        Ok(())
     }
-    
-    pub fn next(&mut self, i: &mut Chars) {
-        if let Some(c) = i.next() {
-            self.cur = c;
-            self.pos += 1;
-        }
-        else {
-            self.cur = '\0';
-        }
-    }
-    
-    pub fn err_token(&self, tok: TomlTokWithSpan) -> TomlErr {
-        TomlErr {msg: format!("Unexpected token {:?} ", tok), span: tok.span}
-    }
-    
-    pub fn err_parse(&self, what: &str) -> TomlErr {
-        TomlErr {msg: format!("Cannot parse toml {} ", what), span: TomlSpan {start: self.pos, len: 0}}
-    }
-    
-    pub fn next_tok(&mut self, i: &mut Chars) -> Result<TomlTokWithSpan, TomlErr> {
-        
-        while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' || self.cur == '#' {
-            if self.cur == '#' {
-                while self.cur != '\n' && self.cur != '\0' {
-                    self.next(i);
-                }
-                continue
-            }
-            self.next(i);
-        }
-        let start = self.pos;
-        match self.cur {
-            '\0' => {
-                Ok(TomlTokWithSpan {tok: TomlTok::Eof, span: TomlSpan {start, len: 0}})
-            }
-            ',' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Comma, span: TomlSpan {start, len: 1}})
-            }
-            '[' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::BlockOpen, span: TomlSpan {start, len: 1}})
-            }
-            ']' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::BlockClose, span: TomlSpan {start, len: 1}})
-            }
-            '{' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::ObjectOpen, span: TomlSpan {start, len: 1}})
-            }
-            '}' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::ObjectClose, span: TomlSpan {start, len: 1}})
-            }
-            '=' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Equals, span: TomlSpan {start, len: 1}})
-            }
-            '+' | '-' | '0'..='9' => {
-                let mut num = String::new();
-                let is_neg = if self.cur == '-' {
-                    num.push(self.cur);
-                    self.next(i);
-                    true
-                }
-                else {
-                    if self.cur == '+' {
-                        self.next(i);
-                    }
-                    false
-                };
-                if self.cur == 'n' {
-                    self.next(i);
-                    if self.cur == 'a' {
-                        self.next(i);
-                        if self.cur == 'n' {
-                            self.next(i);
-                            return Ok(TomlTokWithSpan {tok: TomlTok::Nan(is_neg), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("nan"))
-                        }
-                    }
-                    else {
-                        return Err(self.err_parse("nan"))
-                    }
-                }
-                if self.cur == 'i' {
-                    self.next(i);
-                    if self.cur == 'n' {
-                        self.next(i);
-                        if self.cur == 'f' {
-                            self.next(i);
-                            return Ok(TomlTokWithSpan {tok: TomlTok::Inf(is_neg), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("inf"))
-                        }
-                    }
-                    else {
-                        return Err(self.err_parse("nan"))
-                    }
-                }
-                while self.cur >= '0' && self.cur <= '9' || self.cur == '_' {
-                    if self.cur != '_' {
-                        num.push(self.cur);
-                    }
-                    self.next(i);
-                }
-                if self.cur == '.' {
-                    num.push(self.cur);
-                    self.next(i);
-                    while self.cur >= '0' && self.cur <= '9' || self.cur == '_' {
-                        if self.cur != '_' {
-                            num.push(self.cur);
-                        }
-                        self.next(i);
-                    }
-                    if let Ok(num) = num.parse() {
-                        Ok(TomlTokWithSpan {tok: TomlTok::F64(num), span: TomlSpan {start, len: self.pos - start}})
-                    }
-                    else {
-                        Err(self.err_parse("number"))
-                    }
-                }
-                else if self.cur == '-' { // lets assume its a date. whatever. i don't feel like more parsing today
-                    num.push(self.cur);
-                    self.next(i);
-                    while self.cur >= '0' && self.cur <= '9' || self.cur == ':' || self.cur == '-' || self.cur == 'T' {
-                        num.push(self.cur);
-                        self.next(i);
-                    }
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Date(num), span: TomlSpan {start, len: self.pos - start}})
-                }
-                else {
-                    if is_neg {
-                        if let Ok(num) = num.parse() {
-                            return Ok(TomlTokWithSpan {tok: TomlTok::I64(num), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("number"));
-                        }
-                    }
-                    if let Ok(num) = num.parse() {
-                        return Ok(TomlTokWithSpan {tok: TomlTok::U64(num), span: TomlSpan {start, len: self.pos - start}})
-                    }
-                    else {
-                        return Err(self.err_parse("number"));
-                    }
-                }
-            },
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let mut ident = String::new();
-                while self.cur >= 'a' && self.cur <= 'z'
-                    || self.cur >= 'A' && self.cur <= 'Z'
-                    || self.cur >= '0' && self.cur <= '9'
-                    || self.cur == '_' || self.cur == '-' || self.cur == '.'
-                    || self.cur == '\'' || self.cur == '(' || self.cur == ')' {
-                    ident.push(self.cur);
-                    self.next(i);
-                }
-                if ident == "true" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Bool(true), span: TomlSpan {start, len: self.pos - start}})
-                }
-                if ident == "false" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Bool(false), span: TomlSpan {start, len: self.pos - start}})
-                }
-                if ident == "inf" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Inf(false), span: TomlSpan {start, len: self.pos - start}})
-                }
-                if ident == "nan" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Nan(false), span: TomlSpan {start, len: self.pos - start}})
-                }
-                Ok(TomlTokWithSpan {tok: TomlTok::Ident(ident), span: TomlSpan {start, len: self.pos - start}})
-            },
-            
-            '"' => {
-                let mut val = String::new();
-                self.next(i);
-                while self.cur != '"' {
-                    if self.cur == '\\' {
-                        self.next(i);
-                    }
-                    if self.cur == '\0' {
-                        return Err(self.err_parse("string"));
-                    }
-                    val.push(self.cur);
-                    self.next(i);
-                }
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Str(val), span: TomlSpan {start, len: self.pos - start}})
-            },
-            '\'' => {
-                let mut val = String::new();
-                self.next(i);
-                while self.cur != '\'' {
-                    if self.cur == '\\' {
-                        self.next(i);
-                    }
-                    if self.cur == '\0' {
-                        return Err(self.err_parse("string"));
-                    }
-                    val.push(self.cur);
-                    self.next(i);
-                }
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Str(val), span: TomlSpan {start, len: self.pos - start}})
-            },
-            _ => {
-                Err(self.err_parse("tokenizer"))
-            }
-        }
-    }
-}
\ No newline at end of file
+}
+
+fn flat_value(item: &TomlItem) -> Option<Toml> {
+    let span = item.span.clone();
+    Some(match &item.value {
+        TomlValue::Str(v) => Toml::Str(v.clone(), span),
+        TomlValue::Int(v) => Toml::Num(*v as f64, span),
+        TomlValue::Float(v) => Toml::Num(*v, span),
+        TomlValue::Bool(v) => Toml::Bool(*v, span),
+        TomlValue::Datetime(v) => Toml::Date(v.to_string(), span),
+        TomlValue::Array(items) => Toml::Array(items.iter().filter_map(flat_value).collect()),
+        TomlValue::Table(_) => return None
+    })
+}
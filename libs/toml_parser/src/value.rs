@@ -0,0 +1,427 @@
+use std::fmt;
+use crate::toml::{TomlErr, TomlSpan};
+
+/// A parsed TOML value
+#[derive(Clone, Debug, PartialEq)]
+pub enum TomlValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Datetime(TomlDatetime),
+    Array(Vec<TomlItem>),
+    Table(TomlTable),
+}
+
+/// A value and where it was in the source. Values that weren't parsed, like
+/// the ones built to serialize, have an empty span. Items compare by value
+#[derive(Clone, Debug)]
+pub struct TomlItem {
+    pub value: TomlValue,
+    pub span: TomlSpan,
+}
+
+impl PartialEq for TomlItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+/// The entries of a table in the order they were defined
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TomlTable {
+    pub entries: Vec<(String, TomlItem)>,
+}
+
+impl From<TomlValue> for TomlItem {
+    fn from(value: TomlValue) -> Self {
+        TomlItem {value, span: TomlSpan {start: 0, len: 0}}
+    }
+}
+
+impl TomlValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::Str(_) => "string",
+            TomlValue::Int(_) => "integer",
+            TomlValue::Float(_) => "float",
+            TomlValue::Bool(_) => "bool",
+            TomlValue::Datetime(_) => "datetime",
+            TomlValue::Array(_) => "array",
+            TomlValue::Table(_) => "table",
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let TomlValue::Str(v) = self {Some(v)} else {None}
+    }
+
+    pub fn as_table(&self) -> Option<&TomlTable> {
+        if let TomlValue::Table(v) = self {Some(v)} else {None}
+    }
+
+    pub fn as_array(&self) -> Option<&[TomlItem]> {
+        if let TomlValue::Array(v) = self {Some(v)} else {None}
+    }
+}
+
+// errors at the span of the item, for typed deserializers
+impl TomlItem {
+    pub fn err_msg(&self, msg: &str) -> TomlErr {
+        TomlErr {msg: msg.to_string(), span: self.span.clone(), line: 0, col: 0}
+    }
+
+    pub fn err_type(&self, what: &str) -> TomlErr {
+        self.err_msg(&format!("Expected {} got {}", what, self.value.type_name()))
+    }
+
+    pub fn err_nf(&self, key: &str) -> TomlErr {
+        self.err_msg(&format!("Key not found {}", key))
+    }
+
+    pub fn err_exp(&self, key: &str) -> TomlErr {
+        self.err_msg(&format!("Unexpected key {}", key))
+    }
+
+    pub fn err_enum(&self, name: &str) -> TomlErr {
+        self.err_msg(&format!("Enum not defined {}", name))
+    }
+
+    pub fn err_untagged(&self, name: &str) -> TomlErr {
+        self.err_msg(&format!("Data did not match any variant of {}", name))
+    }
+
+    pub fn expect_table(&self) -> Result<&TomlTable, TomlErr> {
+        self.value.as_table().ok_or_else( || self.err_type("table"))
+    }
+
+    pub fn expect_array(&self) -> Result<&[TomlItem], TomlErr> {
+        self.value.as_array().ok_or_else( || self.err_type("array"))
+    }
+
+    pub fn expect_str(&self) -> Result<&str, TomlErr> {
+        self.value.as_str().ok_or_else( || self.err_type("string"))
+    }
+}
+
+impl TomlTable {
+    pub fn get(&self, key: &str) -> Option<&TomlItem> {
+        self.entries.iter().find( | (k, _) | k == key).map( | (_, v) | v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut TomlItem> {
+        self.entries.iter_mut().find( | (k, _) | k == key).map( | (_, v) | v)
+    }
+
+    /// Walks into nested tables and arrays, array items are addressed by
+    /// their index like `bin.0.name`
+    pub fn get_path(&self, path: &[String]) -> Option<&TomlItem> {
+        let (first, rest) = path.split_first()?;
+        let mut item = self.get(first)?;
+        for key in rest {
+            item = match &item.value {
+                TomlValue::Table(t) => t.get(key)?,
+                TomlValue::Array(a) => a.get(key.parse::<usize>().ok()?)?,
+                _ => return None
+            };
+        }
+        Some(item)
+    }
+
+    /// Adds or replaces an entry, new keys go at the end
+    pub fn insert(&mut self, key: &str, value: impl Into<TomlItem>) {
+        let value = value.into();
+        if let Some(item) = self.get_mut(key) {
+            *item = value;
+        }
+        else {
+            self.entries.push((key.to_string(), value));
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<TomlItem> {
+        let index = self.entries.iter().position( | (k, _) | k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Writes the table as a TOML document, plain values first, then the
+    /// tables as [sections] and arrays of tables as [[sections]]
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        self.write_section(&mut out, &mut Vec::new());
+        out
+    }
+
+    fn write_section(&self, out: &mut String, path: &mut Vec<String>) {
+        for (key, item) in &self.entries {
+            if !is_section(&item.value) {
+                out.push_str(&format!("{} = {}\n", TomlKey(key), item.value));
+            }
+        }
+        for (key, item) in &self.entries {
+            path.push(key.clone());
+            match &item.value {
+                TomlValue::Table(table) => {
+                    // a table of only tables doesn't need its own header
+                    if table.entries.is_empty() || table.entries.iter().any( | (_, v) | !is_section(&v.value)) {
+                        write_header(out, path, "[", "]");
+                    }
+                    table.write_section(out, path);
+                }
+                TomlValue::Array(items) if is_section(&item.value) => {
+                    for item in items {
+                        write_header(out, path, "[[", "]]");
+                        if let TomlValue::Table(table) = &item.value {
+                            table.write_section(out, path);
+                        }
+                    }
+                }
+                _ => ()
+            }
+            path.pop();
+        }
+    }
+}
+
+fn write_header(out: &mut String, path: &[String], open: &str, close: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(open);
+    for (i, key) in path.iter().enumerate() {
+        if i != 0 {
+            out.push('.');
+        }
+        out.push_str(&TomlKey(key).to_string());
+    }
+    out.push_str(close);
+    out.push('\n');
+}
+
+// tables and non empty arrays of only tables are written as sections
+fn is_section(value: &TomlValue) -> bool {
+    match value {
+        TomlValue::Table(_) => true,
+        TomlValue::Array(items) => !items.is_empty() && items.iter().all( | v | matches!(v.value, TomlValue::Table(_))),
+        _ => false
+    }
+}
+
+/// A key as written in TOML, bare when it can be and quoted otherwise
+pub struct TomlKey<'a>(pub &'a str);
+
+impl fmt::Display for TomlKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.0.is_empty() && self.0.bytes().all( | b | b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+            write!(f, "{}", self.0)
+        }
+        else {
+            write_basic_string(f, self.0)
+        }
+    }
+}
+
+fn write_basic_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{8}' => write!(f, "\\b")?,
+            '\u{c}' => write!(f, "\\f")?,
+            c if c.is_control() => write!(f, "\\u{:04X}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// values written inline, tables as { inline tables }
+impl fmt::Display for TomlValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TomlValue::Str(v) => write_basic_string(f, v),
+            TomlValue::Int(v) => write!(f, "{}", v),
+            TomlValue::Float(v) => {
+                if v.is_nan() {
+                    write!(f, "nan")
+                }
+                else if v.is_infinite() {
+                    write!(f, "{}inf", if *v < 0.0 {"-"} else {""})
+                }
+                else if v.fract() == 0.0 && v.abs() < 1e16 {
+                    write!(f, "{:.1}", v)
+                }
+                else {
+                    write!(f, "{:?}", v)
+                }
+            }
+            TomlValue::Bool(v) => write!(f, "{}", v),
+            TomlValue::Datetime(v) => write!(f, "{}", v),
+            TomlValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 {""} else {", "}, item.value)?;
+                }
+                write!(f, "]")
+            }
+            TomlValue::Table(table) => {
+                if table.entries.is_empty() {
+                    return write!(f, "{{}}")
+                }
+                write!(f, "{{ ")?;
+                for (i, (key, item)) in table.entries.iter().enumerate() {
+                    write!(f, "{}{} = {}", if i == 0 {""} else {", "}, TomlKey(key), item.value)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// An offset date-time, local date-time, local date or local time. Which of
+/// the parts are there says which one it is
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TomlDatetime {
+    pub date: Option<TomlDate>,
+    pub time: Option<TomlTime>,
+    pub offset: Option<TomlOffset>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TomlDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TomlTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub nanosecond: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TomlOffset {
+    Z,
+    Minutes(i16),
+}
+
+impl TomlDatetime {
+    /// Parses an RFC 3339 date-time, or the date or time on its own
+    pub fn parse(s: &str) -> Option<TomlDatetime> {
+        let b = s.as_bytes();
+        let mut dt = TomlDatetime::default();
+        let mut o = 0;
+        if b.len() >= 10 && b[4] == b'-' {
+            let date = TomlDate {
+                year: digits(b, 0, 4)? as u16,
+                month: digits(b, 5, 2)? as u8,
+                day: digits(b, 8, 2)? as u8,
+            };
+            if b[7] != b'-' || date.month == 0 || date.month > 12 || date.day == 0 || date.day > days_in_month(date.year, date.month) {
+                return None
+            }
+            dt.date = Some(date);
+            o = 10;
+            if o == b.len() {
+                return Some(dt)
+            }
+            if !matches!(b[o], b'T' | b't' | b' ') {
+                return None
+            }
+            o += 1;
+        }
+        if b.len() < o + 8 || b[o + 2] != b':' || b[o + 5] != b':' {
+            return None
+        }
+        let mut time = TomlTime {
+            hour: digits(b, o, 2)? as u8,
+            minute: digits(b, o + 3, 2)? as u8,
+            second: digits(b, o + 6, 2)? as u8,
+            nanosecond: 0,
+        };
+        if time.hour > 23 || time.minute > 59 || time.second > 60 {
+            return None
+        }
+        o += 8;
+        if b.get(o) == Some(&b'.') {
+            o += 1;
+            let start = o;
+            let mut scale = 100_000_000;
+            while o < b.len() && b[o].is_ascii_digit() {
+                // past nanoseconds the digits are truncated
+                time.nanosecond += (b[o] - b'0') as u32 * scale;
+                scale /= 10;
+                o += 1;
+            }
+            if o == start {
+                return None
+            }
+        }
+        dt.time = Some(time);
+        if o < b.len() {
+            // only date-times have an offset
+            dt.date?;
+            match b[o] {
+                b'Z' | b'z' if o + 1 == b.len() => dt.offset = Some(TomlOffset::Z),
+                sign @ (b'+' | b'-') if b.len() == o + 6 && b[o + 3] == b':' => {
+                    let (h, m) = (digits(b, o + 1, 2)?, digits(b, o + 4, 2)?);
+                    if h > 23 || m > 59 {
+                        return None
+                    }
+                    let minutes = (h * 60 + m) as i16;
+                    dt.offset = Some(TomlOffset::Minutes(if sign == b'-' {-minutes} else {minutes}));
+                }
+                _ => return None
+            }
+        }
+        Some(dt)
+    }
+}
+
+fn digits(b: &[u8], start: usize, len: usize) -> Option<u32> {
+    let d = b.get(start..start + len)?;
+    if !d.iter().all( | c | c.is_ascii_digit()) {
+        return None
+    }
+    Some(d.iter().fold(0, | a, c | a * 10 + (c - b'0') as u32))
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+// RFC 3339 with a T and the fraction as far as it has digits
+impl fmt::Display for TomlDatetime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(d) = &self.date {
+            write!(f, "{:04}-{:02}-{:02}", d.year, d.month, d.day)?;
+            if self.time.is_some() {
+                write!(f, "T")?;
+            }
+        }
+        if let Some(t) = &self.time {
+            write!(f, "{:02}:{:02}:{:02}", t.hour, t.minute, t.second)?;
+            if t.nanosecond != 0 {
+                let frac = format!("{:09}", t.nanosecond);
+                write!(f, ".{}", frac.trim_end_matches('0'))?;
+            }
+        }
+        match self.offset {
+            Some(TomlOffset::Z) => write!(f, "Z"),
+            Some(TomlOffset::Minutes(m)) => write!(f, "{}{:02}:{:02}", if m < 0 {'-'} else {'+'}, m.abs() / 60, m.abs() % 60),
+            None => Ok(())
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Cases in the format of the toml-test suite: valid documents with the
+// tagged JSON they decode to, and invalid ones that have to fail. Set
+// TOML_TEST_DIR to a checkout of github.com/toml-lang/toml-test/tests to
+// also run the suite's documents
+use makepad_toml_parser::*;
+use std::path::Path;
+
+fn tagged(value: &TomlValue) -> String {
+    match value {
+        TomlValue::Str(v) => format!("{{\"type\":\"string\",\"value\":{}}}", json_str(v)),
+        TomlValue::Int(v) => format!("{{\"type\":\"integer\",\"value\":\"{}\"}}", v),
+        TomlValue::Float(v) => {
+            let v = if v.is_nan() {"nan".to_string()} else if v.is_infinite() {
+                format!("{}inf", if *v < 0.0 {"-"} else {"+"})
+            } else {format!("{}", v)};
+            format!("{{\"type\":\"float\",\"value\":\"{}\"}}", v)
+        }
+        TomlValue::Bool(v) => format!("{{\"type\":\"bool\",\"value\":\"{}\"}}", v),
+        TomlValue::Datetime(v) => {
+            let kind = match (&v.date, &v.time, &v.offset) {
+                (Some(_), Some(_), Some(_)) => "datetime",
+                (Some(_), Some(_), None) => "datetime-local",
+                (Some(_), None, _) => "date-local",
+                _ => "time-local",
+            };
+            format!("{{\"type\":\"{}\",\"value\":\"{}\"}}", kind, v)
+        }
+        TomlValue::Array(items) => format!("[{}]", items.iter().map( | i | tagged(&i.value)).collect::<Vec<_>>().join(",")),
+        TomlValue::Table(table) => {
+            let mut entries: Vec<_> = table.entries.iter().collect();
+            entries.sort_by( | a, b | a.0.cmp(&b.0));
+            let entries: Vec<_> = entries.iter().map( | (k, v) | format!("{}:{}", json_str(k), tagged(&v.value))).collect();
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn decode(src: &str) -> String {
+    match parse_toml_table(src) {
+        Ok(table) => tagged(&TomlValue::Table(table)),
+        Err(e) => panic!("{:?} in\n{}", e, src)
+    }
+}
+
+fn s(v: &str) -> String {
+    format!("{{\"type\":\"string\",\"value\":\"{}\"}}", v)
+}
+
+fn i(v: i64) -> String {
+    format!("{{\"type\":\"integer\",\"value\":\"{}\"}}", v)
+}
+
+#[test]
+fn valid() {
+    let cases = [
+        ("a = 1\nb = \"x\"", format!("{{\"a\":{},\"b\":{}}}", i(1), s("x"))),
+        ("a.b.c = 1\na.d = 2", format!("{{\"a\":{{\"b\":{{\"c\":{}}},\"d\":{}}}}}", i(1), i(2))),
+        ("\"a b\".'c d' = 1", format!("{{\"a b\":{{\"c d\":{}}}}}", i(1))),
+        ("[a.b]\nc = 1\n[a]\nd = 2", format!("{{\"a\":{{\"b\":{{\"c\":{}}},\"d\":{}}}}}", i(1), i(2))),
+        ("[[p]]\nn = 1\n[[p]]\nn = 2\n[p.q]\nx = 3", format!("{{\"p\":[{{\"n\":{}}},{{\"n\":{},\"q\":{{\"x\":{}}}}}]}}", i(1), i(2), i(3))),
+        ("[[a.b]]\n[[a.b]]\nx = 1", format!("{{\"a\":{{\"b\":[{{}},{{\"x\":{}}}]}}}}", i(1))),
+        ("t = { a = 1, b.c = 2 }", format!("{{\"t\":{{\"a\":{},\"b\":{{\"c\":{}}}}}}}", i(1), i(2))),
+        ("x = [1, [2, 3], { a = 4 }, ]", format!("{{\"x\":[{},[{},{}],{{\"a\":{}}}]}}", i(1), i(2), i(3), i(4))),
+        ("x = [\n  1, # one\n  2\n]", format!("{{\"x\":[{},{}]}}", i(1), i(2))),
+        ("a = 0xff\nb = 0o17\nc = 0b101\nd = -9_223_372_036_854_775_808\ne = +7",
+            format!("{{\"a\":{},\"b\":{},\"c\":{},\"d\":{},\"e\":{}}}", i(255), i(15), i(5), i(i64::MIN), i(7))),
+        ("a = 1.5\nb = -2e-3\nc = 1_000.0\nd = inf\ne = -inf\nf = nan\ng = 6.02E23",
+            "{\"a\":{\"type\":\"float\",\"value\":\"1.5\"},\"b\":{\"type\":\"float\",\"value\":\"-0.002\"},\"c\":{\"type\":\"float\",\"value\":\"1000\"},\"d\":{\"type\":\"float\",\"value\":\"+inf\"},\"e\":{\"type\":\"float\",\"value\":\"-inf\"},\"f\":{\"type\":\"float\",\"value\":\"nan\"},\"g\":{\"type\":\"float\",\"value\":\"602000000000000000000000\"}}".to_string()),
+        ("a = true\nb = false", "{\"a\":{\"type\":\"bool\",\"value\":\"true\"},\"b\":{\"type\":\"bool\",\"value\":\"false\"}}".to_string()),
+        ("a = \"\\t\\u00e9\\U0001F600\\\"\"", format!("{{\"a\":{}}}", s("\\u0009é😀\\\""))),
+        ("a = 'C:\\path'", format!("{{\"a\":{}}}", s("C:\\\\path"))),
+        ("a = \"\"\"\none\ntwo\"\"\"", format!("{{\"a\":{}}}", s("one\\u000atwo"))),
+        ("a = \"\"\"x \\\n    y\"\"\"", format!("{{\"a\":{}}}", s("x y"))),
+        ("a = \"\"\"\"\"quoted\"\"\"\"\"", format!("{{\"a\":{}}}", s("\\\"\\\"quoted\\\"\\\""))),
+        ("a = '''\nraw\\n'''", format!("{{\"a\":{}}}", s("raw\\\\n"))),
+        ("a = 1979-05-27T07:32:00Z\nb = 1979-05-27 00:32:00.999999-07:00\nc = 1979-05-27T07:32:00\nd = 1979-05-27\ne = 07:32:00",
+            "{\"a\":{\"type\":\"datetime\",\"value\":\"1979-05-27T07:32:00Z\"},\"b\":{\"type\":\"datetime\",\"value\":\"1979-05-27T00:32:00.999999-07:00\"},\"c\":{\"type\":\"datetime-local\",\"value\":\"1979-05-27T07:32:00\"},\"d\":{\"type\":\"date-local\",\"value\":\"1979-05-27\"},\"e\":{\"type\":\"time-local\",\"value\":\"07:32:00\"}}".to_string()),
+        ("a = 1\r\n# comment\r\n\r\nb = 2 # end", format!("{{\"a\":{},\"b\":{}}}", i(1), i(2))),
+        ("", "{}".to_string()),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(decode(src), expected, "in\n{}", src);
+    }
+}
+
+#[test]
+fn invalid() {
+    let cases = [
+        "a = 1\na = 2",
+        "a.b = 1\na.b.c = 2",
+        "[a]\n[a]",
+        "[a]\nb = 1\n[a.b]",
+        "a = {}\n[a]",
+        "a = { b = 1 }\na.c = 2",
+        "a = [1]\n[[a]]",
+        "[[a]]\n[a]",
+        "[a.b]\n[[a]]",
+        "[a]\nb.c = 1\n[a.b]",
+        "t = { a = 1, a = 2 }",
+        "t = { a = 1, }",
+        "t = { a = 1\n}",
+        "a = 01",
+        "a = 1__0",
+        "a = _1",
+        "a = 1.",
+        "a = .5",
+        "a = 1e",
+        "a = +0x10",
+        "a = 9223372036854775808",
+        "a = 0x8000000000000000",
+        "a = \"\\q\"",
+        "a = \"\\uD800\"",
+        "a = \"unterminated",
+        "a = \"tab\u{1}\"",
+        "a = \"\"\"x\"\"\"\"\"\"",
+        "a = 1979-02-29",
+        "a = 1979-13-01",
+        "a = 25:00:00",
+        "a = 1 b = 2",
+        "a = [1 2]",
+        "a = [,]",
+        "a =",
+        "= 1",
+        "a = true1",
+        "[a b]",
+        "[[a]",
+        "a = 1\r",
+        "# \u{7f}",
+        "\"\"\"a\"\"\" = 1",
+    ];
+    for src in cases {
+        assert!(parse_toml_table(src).is_err(), "should fail:\n{}", src);
+    }
+}
+
+#[test]
+fn error_position() {
+    let e = parse_toml_table("a = 1\nb = 2\nb = 3").unwrap_err();
+    assert_eq!((e.line, e.col), (3, 1));
+    let e = parse_toml_table("a = 1\nb = \"é\\q\"").unwrap_err();
+    assert_eq!((e.line, e.col), (2, 7));
+}
+
+#[test]
+fn flat() {
+    let toml = parse_toml("[package]\nname = \"x\"\n[target.'cfg(windows)'.dependencies]\nw = { version = \"0.1\" }\n[[bin]]\nname = \"b\"").unwrap();
+    assert_eq!(toml.get("package.name").cloned().and_then(Toml::into_str), Some("x".into()));
+    assert!(toml.contains_key("target.'cfg(windows)'.dependencies.w.version"));
+    assert!(toml.contains_key("bin.name"));
+}
+
+#[test]
+fn writer_roundtrip() {
+    let src = "a = 1\nf = 2.0\ns = \"q\\\"\"\n\n[t]\nx = [1, 2]\n\n[b]\nc = 1979-05-27\n\n[d.e]\nf = true\n\n[[g]]\nh = 1\n\n[[g]]\nh = 2\n";
+    let table = parse_toml_table(src).unwrap();
+    assert_eq!(table.to_toml(), src);
+    assert_eq!(parse_toml_table(&table.to_toml()).unwrap(), table);
+}
+
+#[test]
+fn document_edits() {
+    let src = "# manifest\n[package]\nname = \"x\" # the name\nversion = \"0.1.0\"\n\n[dependencies]\nfoo = { path = \"../foo\" }\n";
+    let mut doc = TomlDocument::parse(src).unwrap();
+    doc.set("package.version", TomlValue::Str("0.2.0".into())).unwrap();
+    doc.set("dependencies.foo.version", TomlValue::Str("1.0".into())).unwrap();
+    doc.set("dependencies.bar", TomlValue::Str("2".into())).unwrap();
+    doc.set("package.metadata.check", TomlValue::Bool(true)).unwrap();
+    doc.set("features.default", TomlValue::Array(vec![])).unwrap();
+    assert_eq!(doc.as_str(), "# manifest\n[package]\nname = \"x\" # the name\nversion = \"0.2.0\"\nmetadata.check = true\n\n[dependencies]\nfoo = { path = \"../foo\", version = \"1.0\" }\nbar = \"2\"\n\n[features]\ndefault = []\n");
+    assert!(doc.remove("package.metadata.check").unwrap());
+    assert!(!doc.remove("package.missing").unwrap());
+    assert!(doc.remove("dependencies.foo.path").is_err());
+    assert!(doc.set("package", TomlValue::Int(1)).is_err());
+    assert!(doc.set("package.name.x", TomlValue::Int(1)).is_err());
+    assert_eq!(doc.get("dependencies.bar").map( | i | &i.value), Some(&TomlValue::Str("2".into())));
+    assert!(doc.remove("dependencies").unwrap());
+    assert_eq!(doc.as_str(), "# manifest\n[package]\nname = \"x\" # the name\nversion = \"0.2.0\"\n\n[features]\ndefault = []\n");
+
+    let mut doc = TomlDocument::parse("[[bin]]\nname = \"a\"\n[[bin]]\nname = \"b\"\n").unwrap();
+    doc.set("bin.1.path", TomlValue::Str("b.rs".into())).unwrap();
+    assert_eq!(doc.as_str(), "[[bin]]\nname = \"a\"\n[[bin]]\nname = \"b\"\npath = \"b.rs\"\n");
+}
+
+#[test]
+fn toml_test_suite() {
+    let Ok(dir) = std::env::var("TOML_TEST_DIR") else {return};
+    let mut failed = Vec::new();
+    for (sub, should_parse) in [("valid", true), ("invalid", false)] {
+        let mut files = Vec::new();
+        collect(&Path::new(&dir).join(sub), &mut files);
+        for file in files {
+            let Ok(src) = std::fs::read(&file) else {continue};
+            // invalid utf8 is one of the invalid cases
+            let parsed = String::from_utf8(src).ok().map( | src | parse_toml_table(&src).is_ok()).unwrap_or(false);
+            if parsed != should_parse {
+                failed.push(file);
+            }
+        }
+    }
+    assert!(failed.is_empty(), "failed {:#?}", failed);
+}
+
+fn collect(dir: &Path, out: &mut Vec<std::path::PathBuf>) {
+    let Ok(iter) = std::fs::read_dir(dir) else {return};
+    for entry in iter.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, out);
+        }
+        else if path.extension().map( | e | e == "toml").unwrap_or(false) {
+            out.push(path);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::makepad_widgets::*;
+
+// The host ABI a plugin is linked against. Every import lives in a module
+// named after the ABI version, so a future host can offer `makepad_v2` next to
+// `makepad_v1` and keep old plugins running.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+pub const PLUGIN_HOST_MODULE: &str = "makepad_v1";
+
+// Exports a plugin provides. Only `memory` and the version export are required
+pub const EXPORT_MEMORY: &str = "memory";
+pub const EXPORT_ABI_VERSION: &str = "makepad_abi_version";
+pub const EXPORT_INIT: &str = "makepad_init";
+pub const EXPORT_EVENT_BUFFER: &str = "makepad_event_buffer";
+pub const EXPORT_HANDLE_EVENT: &str = "makepad_handle_event";
+pub const EXPORT_DRAW: &str = "makepad_draw";
+
+// The buffer returned by `makepad_event_buffer` must be at least this large.
+pub const PLUGIN_EVENT_BUFFER_SIZE: usize = 4096;
+
+// Host imports, all in the `makepad_v1` module:
+//
+// log(ptr: u32, len: u32)
+// redraw()
+// draw_rect(x: f32, y: f32, w: f32, h: f32, color: u32)
+// draw_line(x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: u32)
+// draw_text(x: f32, y: f32, ptr: u32, len: u32, color: u32)
+// emit_action(name_ptr: u32, name_len: u32, data_ptr: u32, data_len: u32)
+//
+// colors are packed as 0xRRGGBBAA, coordinates are relative to the
+// top left of the plugin view.
+
+#[derive(Clone, Debug)]
+pub enum PluginDrawCmd {
+    Rect {rect: Rect, color: Vec4},
+    Line {start: DVec2, end: DVec2, width: f64, color: Vec4},
+    Text {pos: DVec2, text: String, color: Vec4},
+}
+
+#[derive(Clone, Debug)]
+pub struct PluginActionItem {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u32)]
+pub enum PluginEventKind {
+    FingerDown = 1,
+    FingerMove = 2,
+    FingerUp = 3,
+    FingerHoverIn = 4,
+    FingerHoverOver = 5,
+    FingerHoverOut = 6,
+    FingerScroll = 7,
+    KeyDown = 8,
+    KeyUp = 9,
+    TextInput = 10,
+    KeyFocus = 11,
+    KeyFocusLost = 12,
+}
+
+// Events are delivered to a plugin as a little-endian record written into its
+// event buffer, starting with the u32 event kind:
+//
+// Finger*      : x: f32, y: f32, digit: u32, modifiers: u32
+// FingerScroll : x: f32, y: f32, scroll_x: f32, scroll_y: f32, modifiers: u32
+// KeyDown/Up   : key_code: u32, modifiers: u32, is_repeat: u32
+// TextInput    : len: u32, utf8 bytes
+// KeyFocus*    : no payload
+#[derive(Clone, Debug)]
+pub enum PluginEvent {
+    Finger {kind: PluginEventKind, pos: DVec2, digit: u32, modifiers: KeyModifiers},
+    Scroll {pos: DVec2, scroll: DVec2, modifiers: KeyModifiers},
+    Key {kind: PluginEventKind, key_code: KeyCode, modifiers: KeyModifiers, is_repeat: bool},
+    TextInput (String),
+    KeyFocus,
+    KeyFocusLost,
+}
+
+fn modifiers_to_u32(m: &KeyModifiers) -> u32 {
+    (m.shift as u32) | (m.control as u32) << 1 | (m.alt as u32) << 2 | (m.logo as u32) << 3
+}
+
+impl PluginEvent {
+    pub fn from_hit(hit: &Hit, origin: DVec2) -> Option<Self> {
+        let finger = |kind, abs: DVec2, digit: u64, modifiers: &KeyModifiers| {
+            Some(PluginEvent::Finger {kind, pos: abs - origin, digit: digit as u32, modifiers: *modifiers})
+        };
+        match hit {
+            Hit::FingerDown(fe) => finger(PluginEventKind::FingerDown, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerMove(fe) => finger(PluginEventKind::FingerMove, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerUp(fe) => finger(PluginEventKind::FingerUp, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerHoverIn(fe) => finger(PluginEventKind::FingerHoverIn, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerHoverOver(fe) => finger(PluginEventKind::FingerHoverOver, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerHoverOut(fe) => finger(PluginEventKind::FingerHoverOut, fe.abs, fe.digit_id.0 .0, &fe.modifiers),
+            Hit::FingerScroll(fe) => Some(PluginEvent::Scroll {pos: fe.abs - origin, scroll: fe.scroll, modifiers: fe.modifiers}),
+            Hit::KeyDown(ke) => Some(PluginEvent::Key {kind: PluginEventKind::KeyDown, key_code: ke.key_code, modifiers: ke.modifiers, is_repeat: ke.is_repeat}),
+            Hit::KeyUp(ke) => Some(PluginEvent::Key {kind: PluginEventKind::KeyUp, key_code: ke.key_code, modifiers: ke.modifiers, is_repeat: ke.is_repeat}),
+            Hit::TextInput(te) => Some(PluginEvent::TextInput(te.input.clone())),
+            Hit::KeyFocus(_) => Some(PluginEvent::KeyFocus),
+            Hit::KeyFocusLost(_) => Some(PluginEvent::KeyFocusLost),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> PluginEventKind {
+        match self {
+            Self::Finger {kind, ..} | Self::Key {kind, ..} => *kind,
+            Self::Scroll {..} => PluginEventKind::FingerScroll,
+            Self::TextInput(_) => PluginEventKind::TextInput,
+            Self::KeyFocus => PluginEventKind::KeyFocus,
+            Self::KeyFocusLost => PluginEventKind::KeyFocusLost,
+        }
+    }
+
+    /// Serializes the event into the record layout documented above.
+    /// Text that does not fit the event buffer is truncated on a char boundary.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        out.clear();
+        out.extend_from_slice(&(self.kind() as u32).to_le_bytes());
+        match self {
+            Self::Finger {pos, digit, modifiers, ..} => {
+                out.extend_from_slice(&(pos.x as f32).to_le_bytes());
+                out.extend_from_slice(&(pos.y as f32).to_le_bytes());
+                out.extend_from_slice(&digit.to_le_bytes());
+                out.extend_from_slice(&modifiers_to_u32(modifiers).to_le_bytes());
+            }
+            Self::Scroll {pos, scroll, modifiers} => {
+                out.extend_from_slice(&(pos.x as f32).to_le_bytes());
+                out.extend_from_slice(&(pos.y as f32).to_le_bytes());
+                out.extend_from_slice(&(scroll.x as f32).to_le_bytes());
+                out.extend_from_slice(&(scroll.y as f32).to_le_bytes());
+                out.extend_from_slice(&modifiers_to_u32(modifiers).to_le_bytes());
+            }
+            Self::Key {key_code, modifiers, is_repeat, ..} => {
+                out.extend_from_slice(&(*key_code as u32).to_le_bytes());
+                out.extend_from_slice(&modifiers_to_u32(modifiers).to_le_bytes());
+                out.extend_from_slice(&(*is_repeat as u32).to_le_bytes());
+            }
+            Self::TextInput(text) => {
+                let max = PLUGIN_EVENT_BUFFER_SIZE - 8;
+                let mut end = text.len().min(max);
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                out.extend_from_slice(&(end as u32).to_le_bytes());
+                out.extend_from_slice(&text.as_bytes()[0..end]);
+            }
+            Self::KeyFocus | Self::KeyFocusLost => ()
+        }
+    }
+}
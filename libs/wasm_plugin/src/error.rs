@@ -0,0 +1,59 @@
+use {
+    makepad_stitch::{Error, Trap},
+    std::{fmt, io},
+};
+
+/// Everything that can go wrong while loading or running a plugin.
+/// None of these take down the host: a plugin that fails is parked in an
+/// error state until it is reloaded.
+#[derive(Debug)]
+pub enum PluginError {
+    Io(io::Error),
+    Decode(String),
+    Instantiate(String),
+    MissingExport(&'static str),
+    AbiVersionMismatch {expected: u32, found: u32},
+    Trap(Trap),
+    Host(String),
+    Faulted,
+}
+
+impl PluginError {
+    pub fn is_trap(&self) -> bool {
+        matches!(self, Self::Trap(_))
+    }
+}
+
+impl From<Error> for PluginError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Trap(trap) => Self::Trap(trap),
+            Error::Decode(e) => Self::Decode(e.to_string()),
+            Error::Instantiate(e) => Self::Instantiate(e.to_string()),
+            e => Self::Host(e.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for PluginError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Decode(e) => write!(f, "decode error: {}", e),
+            Self::Instantiate(e) => write!(f, "instantiate error: {}", e),
+            Self::MissingExport(name) => write!(f, "missing export: {}", name),
+            Self::AbiVersionMismatch {expected, found} => write!(f, "abi version mismatch: host {} plugin {}", expected, found),
+            Self::Trap(trap) => write!(f, "trap: {}", trap),
+            Self::Host(e) => write!(f, "host error: {}", e),
+            Self::Faulted => write!(f, "plugin faulted, waiting for reload"),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
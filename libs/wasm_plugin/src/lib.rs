@@ -0,0 +1,19 @@
+pub use makepad_widgets;
+pub use makepad_stitch;
+use makepad_widgets::*;
+
+pub mod abi;
+pub mod error;
+pub mod plugin;
+pub mod plugin_view;
+
+pub use crate::{
+    abi::{PluginEvent, PluginDrawCmd, PluginActionItem, PLUGIN_ABI_VERSION},
+    error::PluginError,
+    plugin::WasmPlugin,
+    plugin_view::{PluginView, PluginViewRef, PluginViewAction},
+};
+
+pub fn live_design(cx: &mut Cx) {
+    crate::plugin_view::live_design(cx);
+}
@@ -0,0 +1,305 @@
+use {
+    crate::{
+        abi::*,
+        error::PluginError,
+        makepad_widgets::*,
+    },
+    makepad_stitch::{Engine, Error, Func, Instance, Linker, Mem, Module, Store, Trap, Val},
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    },
+};
+
+// A memory handle can only be dereferenced through the Store that owns it, and
+// host functions only ever run on the thread that is driving that Store.
+#[derive(Clone, Copy)]
+struct GuestMem(Mem);
+unsafe impl Send for GuestMem {}
+unsafe impl Sync for GuestMem {}
+
+// Everything the host functions write to while a plugin call runs. The host
+// functions have to be 'static + Send + Sync, so they cannot borrow the Cx;
+// instead they queue up their side effects here and the host flushes them
+// once the call returns.
+#[derive(Default)]
+struct PluginHostState {
+    mem: Option<GuestMem>,
+    draw_cmds: Vec<PluginDrawCmd>,
+    actions: Vec<PluginActionItem>,
+    logs: Vec<String>,
+    redraw: bool,
+}
+
+fn read_guest_bytes(store: &Store, mem: Option<GuestMem>, ptr: u32, len: u32) -> Result<Vec<u8>, Error> {
+    let Some(GuestMem(mem)) = mem else {
+        return Err(Trap::MemAccessOutOfBounds.into())
+    };
+    let bytes = mem.bytes(store);
+    let start = ptr as usize;
+    let end = start.checked_add(len as usize).ok_or(Trap::MemAccessOutOfBounds)?;
+    if end > bytes.len() {
+        return Err(Trap::MemAccessOutOfBounds.into())
+    }
+    Ok(bytes[start..end].to_vec())
+}
+
+fn read_guest_string(store: &Store, mem: Option<GuestMem>, ptr: u32, len: u32) -> Result<String, Error> {
+    let bytes = read_guest_bytes(store, mem, ptr, len)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// A plugin call that runs more loop iterations than this traps, so a plugin
+// stuck in a loop faults instead of hanging the UI thread.
+const PLUGIN_CALL_FUEL: u64 = 50_000_000;
+
+struct PluginInstance {
+    store: Store,
+    instance: Instance,
+    // where the event buffer starts in guest memory, it is
+    // PLUGIN_EVENT_BUFFER_SIZE bytes long
+    event_buffer: Option<usize>,
+}
+
+pub struct WasmPlugin {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    state: Arc<Mutex<PluginHostState>>,
+    instance: Option<PluginInstance>,
+    error: Option<PluginError>,
+    event_bytes: Vec<u8>,
+}
+
+impl WasmPlugin {
+    /// Loads and instantiates the plugin at `path`. A plugin that fails to load
+    /// is still returned so it can be picked up again by `reload_if_changed`.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let mut plugin = Self {
+            path: path.as_ref().to_path_buf(),
+            modified: None,
+            state: Default::default(),
+            instance: None,
+            error: None,
+            event_bytes: Vec::new(),
+        };
+        let _ = plugin.reload();
+        plugin
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.instance.is_some()
+    }
+
+    pub fn error(&self) -> Option<&PluginError> {
+        self.error.as_ref()
+    }
+
+    /// (Re)instantiates the plugin from disk, dropping all of its state.
+    pub fn reload(&mut self) -> Result<(), &PluginError> {
+        self.modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        self.instance = None;
+        *self.state.lock().unwrap() = Default::default();
+        match self.instantiate() {
+            Ok(instance) => {
+                self.instance = Some(instance);
+                self.error = None;
+                if let Err(e) = self.call_export(EXPORT_INIT, &[]) {
+                    self.fault(e);
+                }
+            }
+            Err(e) => self.error = Some(e)
+        }
+        match &self.error {
+            None => Ok(()),
+            Some(e) => Err(e)
+        }
+    }
+
+    /// Reloads the plugin when the file on disk has a different modification
+    /// time than the one we loaded. Returns true if a reload happened.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != self.modified {
+            let _ = self.reload();
+            return true
+        }
+        false
+    }
+
+    fn instantiate(&mut self) -> Result<PluginInstance, PluginError> {
+        let bytes = fs::read(&self.path)?;
+        let engine = Engine::new();
+        let module = Module::new(&engine, &bytes).map_err(|e| PluginError::Decode(e.to_string()))?;
+        let mut store = Store::new(engine);
+        store.set_fuel(Some(PLUGIN_CALL_FUEL));
+        let linker = self.define_host_abi(&mut store);
+        let instance = linker.instantiate(&mut store, &module)?;
+
+        let mem = instance.exported_mem(EXPORT_MEMORY).ok_or(PluginError::MissingExport(EXPORT_MEMORY))?;
+        self.state.lock().unwrap().mem = Some(GuestMem(mem));
+
+        let version_fn = instance.exported_func(EXPORT_ABI_VERSION).ok_or(PluginError::MissingExport(EXPORT_ABI_VERSION))?;
+        let mut version = [Val::I32(0)];
+        version_fn.call(&mut store, &[], &mut version)?;
+        let found = version[0].to_i32().unwrap_or(0) as u32;
+        if found != PLUGIN_ABI_VERSION {
+            return Err(PluginError::AbiVersionMismatch {expected: PLUGIN_ABI_VERSION, found})
+        }
+
+        let event_buffer = if let Some(buffer_fn) = instance.exported_func(EXPORT_EVENT_BUFFER) {
+            let mut ptr = [Val::I32(0)];
+            buffer_fn.call(&mut store, &[], &mut ptr)?;
+            // memory never shrinks, so a buffer that fits now fits for good
+            let start = ptr[0].to_i32().unwrap_or(0) as u32 as usize;
+            if start.checked_add(PLUGIN_EVENT_BUFFER_SIZE).is_none_or(|end| end > mem.bytes(&store).len()) {
+                return Err(PluginError::Trap(Trap::MemAccessOutOfBounds))
+            }
+            Some(start)
+        }
+        else {
+            None
+        };
+
+        Ok(PluginInstance {store, instance, event_buffer})
+    }
+
+    fn define_host_abi(&self, store: &mut Store) -> Linker {
+        let mut linker = Linker::new();
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "log", Func::wrap(store, move |store: &mut Store, ptr: u32, len: u32| -> Result<(), Error> {
+            let mut state = state.lock().unwrap();
+            let msg = read_guest_string(store, state.mem, ptr, len)?;
+            state.logs.push(msg);
+            Ok(())
+        }));
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "redraw", Func::wrap(store, move || {
+            state.lock().unwrap().redraw = true;
+        }));
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "draw_rect", Func::wrap(store, move |x: f32, y: f32, w: f32, h: f32, color: u32| {
+            state.lock().unwrap().draw_cmds.push(PluginDrawCmd::Rect {
+                rect: Rect {pos: dvec2(x as f64, y as f64), size: dvec2(w as f64, h as f64)},
+                color: Vec4::from_u32(color)
+            });
+        }));
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "draw_line", Func::wrap(store, move |x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: u32| {
+            state.lock().unwrap().draw_cmds.push(PluginDrawCmd::Line {
+                start: dvec2(x0 as f64, y0 as f64),
+                end: dvec2(x1 as f64, y1 as f64),
+                width: width as f64,
+                color: Vec4::from_u32(color)
+            });
+        }));
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "draw_text", Func::wrap(store, move |store: &mut Store, x: f32, y: f32, ptr: u32, len: u32, color: u32| -> Result<(), Error> {
+            let mut state = state.lock().unwrap();
+            let text = read_guest_string(store, state.mem, ptr, len)?;
+            state.draw_cmds.push(PluginDrawCmd::Text {
+                pos: dvec2(x as f64, y as f64),
+                text,
+                color: Vec4::from_u32(color)
+            });
+            Ok(())
+        }));
+
+        let state = self.state.clone();
+        linker.define(PLUGIN_HOST_MODULE, "emit_action", Func::wrap(store, move |store: &mut Store, name_ptr: u32, name_len: u32, data_ptr: u32, data_len: u32| -> Result<(), Error> {
+            let mut state = state.lock().unwrap();
+            let name = read_guest_string(store, state.mem, name_ptr, name_len)?;
+            let data = read_guest_bytes(store, state.mem, data_ptr, data_len)?;
+            state.actions.push(PluginActionItem {name, data});
+            Ok(())
+        }));
+
+        linker
+    }
+
+    // Calls an optional export. Missing exports are not an error, a plugin only
+    // has to implement what it needs.
+    fn call_export(&mut self, name: &'static str, args: &[Val]) -> Result<(), PluginError> {
+        let Some(inst) = &mut self.instance else {
+            return Err(PluginError::Faulted)
+        };
+        let Some(func) = inst.instance.exported_func(name) else {
+            return Ok(())
+        };
+        inst.store.set_fuel(Some(PLUGIN_CALL_FUEL));
+        func.call(&mut inst.store, args, &mut [])?;
+        Ok(())
+    }
+
+    // A trap leaves the guest in an unknown state, so we drop the instance and
+    // wait for the file to change before trying again.
+    fn fault(&mut self, error: PluginError) {
+        error!("Plugin {:?}: {}", self.path, error);
+        self.instance = None;
+        self.error = Some(error);
+    }
+
+    /// Delivers an event to the plugin. Events are silently dropped when the
+    /// plugin has no event buffer or handler.
+    pub fn handle_event(&mut self, event: &PluginEvent) -> Result<(), &PluginError> {
+        let Some(inst) = &mut self.instance else {
+            return Err(self.error.as_ref().unwrap_or(&PluginError::Faulted))
+        };
+        let Some(start) = inst.event_buffer else {
+            return Ok(())
+        };
+        event.write_to(&mut self.event_bytes);
+        if self.event_bytes.len() > PLUGIN_EVENT_BUFFER_SIZE {
+            self.fault(PluginError::Trap(Trap::MemAccessOutOfBounds));
+            return Err(self.error.as_ref().unwrap())
+        }
+        let GuestMem(mem) = self.state.lock().unwrap().mem.unwrap();
+        let guest = mem.bytes_mut(&mut inst.store);
+        guest[start..start + self.event_bytes.len()].copy_from_slice(&self.event_bytes);
+        let len = self.event_bytes.len() as i32;
+        if let Err(e) = self.call_export(EXPORT_HANDLE_EVENT, &[Val::I32(len)]) {
+            self.fault(e);
+            return Err(self.error.as_ref().unwrap())
+        }
+        Ok(())
+    }
+
+    /// Runs the plugin draw function for a view of the given size and returns
+    /// the draw commands it produced.
+    pub fn draw(&mut self, size: DVec2) -> Result<Vec<PluginDrawCmd>, &PluginError> {
+        if self.instance.is_none() {
+            return Err(self.error.as_ref().unwrap_or(&PluginError::Faulted))
+        }
+        self.state.lock().unwrap().draw_cmds.clear();
+        if let Err(e) = self.call_export(EXPORT_DRAW, &[Val::F32(size.x as f32), Val::F32(size.y as f32)]) {
+            self.fault(e);
+            return Err(self.error.as_ref().unwrap())
+        }
+        Ok(std::mem::take(&mut self.state.lock().unwrap().draw_cmds))
+    }
+
+    /// Flushes the log lines the plugin produced since the last call.
+    pub fn take_logs(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.state.lock().unwrap().logs)
+    }
+
+    /// Flushes the actions the plugin emitted since the last call.
+    pub fn take_actions(&mut self) -> Vec<PluginActionItem> {
+        std::mem::take(&mut self.state.lock().unwrap().actions)
+    }
+
+    /// Returns true once if the plugin asked for a redraw.
+    pub fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.state.lock().unwrap().redraw)
+    }
+}
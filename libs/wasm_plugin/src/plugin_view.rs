@@ -0,0 +1,204 @@
+use {
+    crate::{
+        abi::*,
+        makepad_widgets::*,
+        plugin::WasmPlugin,
+    },
+};
+
+live_design!{
+    link widgets;
+    use link::theme::*;
+    use link::shaders::*;
+
+    pub PluginViewBase = {{PluginView}} {}
+    pub PluginView = <PluginViewBase> {
+        width: Fill, height: Fill,
+        reload_interval: 0.5,
+        draw_bg: {color: #0000}
+        draw_text: {
+            text_style: <THEME_FONT_REGULAR> {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, DefaultNone)]
+pub enum PluginViewAction {
+    None,
+    Loaded,
+    Error(String),
+    Action {name: String, data: Vec<u8>},
+}
+
+/// Hosts a sandboxed wasm plugin and gives it a rectangle to draw into.
+/// The plugin file is polled every `reload_interval` seconds and hot-reloaded
+/// when it changes; set the interval to 0 to disable polling.
+#[derive(Live, Widget)]
+pub struct PluginView {
+    #[walk] walk: Walk,
+    #[redraw] #[live] draw_bg: DrawColor,
+    #[live] draw_rect: DrawColor,
+    #[live] draw_line: DrawLine,
+    #[live] draw_text: DrawText,
+    #[live] path: String,
+    #[live(0.5)] reload_interval: f64,
+    #[rust] plugin: Option<WasmPlugin>,
+    #[rust] reload_timer: Timer,
+    #[rust] last_error: Option<String>,
+    #[rust] pending_load: bool,
+}
+
+impl LiveHook for PluginView {
+    fn after_apply(&mut self, cx: &mut Cx, apply: &mut Apply, _index: usize, _nodes: &[LiveNode]) {
+        match apply.from {
+            ApplyFrom::NewFromDoc {..} |
+            ApplyFrom::UpdateFromDoc {..} |
+            ApplyFrom::Over {..} => {
+                let changed = self.plugin.as_ref().map(|p| p.path().to_str() != Some(&self.path)).unwrap_or(true);
+                if changed && !self.path.is_empty() {
+                    self.plugin = Some(WasmPlugin::load(&self.path));
+                    self.pending_load = true;
+                }
+                cx.stop_timer(self.reload_timer);
+                if self.reload_interval > 0.0 {
+                    self.reload_timer = cx.start_interval(self.reload_interval);
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+impl PluginView {
+    pub fn plugin(&mut self) -> Option<&mut WasmPlugin> {
+        self.plugin.as_mut()
+    }
+
+    pub fn reload(&mut self, cx: &mut Cx) {
+        if let Some(plugin) = &mut self.plugin {
+            let _ = plugin.reload();
+            self.pending_load = true;
+            self.draw_bg.redraw(cx);
+        }
+    }
+
+    // Moves everything the plugin queued during its last call out into the app:
+    // logs go to the log, actions become widget actions and state changes
+    // (loaded / faulted) are reported once.
+    fn flush_plugin(&mut self, cx: &mut Cx, uid: WidgetUid, path: &HeapLiveIdPath) {
+        let Some(plugin) = &mut self.plugin else {
+            return
+        };
+        for line in plugin.take_logs() {
+            log!("{}: {}", plugin.path().display(), line);
+        }
+        for item in plugin.take_actions() {
+            cx.widget_action(uid, path, PluginViewAction::Action {name: item.name, data: item.data});
+        }
+        if plugin.take_redraw() {
+            self.draw_bg.redraw(cx);
+        }
+        let error = plugin.error().map(|e| e.to_string());
+        if error != self.last_error {
+            if let Some(error) = &error {
+                error!("Plugin {}: {}", plugin.path().display(), error);
+                cx.widget_action(uid, path, PluginViewAction::Error(error.clone()));
+            }
+            self.last_error = error;
+        }
+        if self.pending_load && plugin.is_loaded() {
+            self.pending_load = false;
+            cx.widget_action(uid, path, PluginViewAction::Loaded);
+        }
+    }
+}
+
+impl Widget for PluginView {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        let uid = self.widget_uid();
+        if self.reload_timer.is_event(event).is_some() {
+            if let Some(plugin) = &mut self.plugin {
+                if plugin.reload_if_changed() {
+                    self.pending_load = true;
+                    self.draw_bg.redraw(cx);
+                }
+            }
+        }
+        let area = self.draw_bg.area();
+        let hit = event.hits(cx, area);
+        if let Hit::FingerDown(_) = hit {
+            cx.set_key_focus(area);
+        }
+        if let Some(plugin) = &mut self.plugin {
+            if plugin.is_loaded() {
+                if let Some(plugin_event) = PluginEvent::from_hit(&hit, area.rect(cx).pos) {
+                    let _ = plugin.handle_event(&plugin_event);
+                }
+            }
+        }
+        self.flush_plugin(cx, uid, &scope.path);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        let rect = self.draw_bg.draw_walk(cx, walk);
+        let cmds = match &mut self.plugin {
+            Some(plugin) if plugin.is_loaded() => plugin.draw(rect.size).unwrap_or_default(),
+            _ => Vec::new()
+        };
+        for cmd in cmds {
+            match cmd {
+                PluginDrawCmd::Rect {rect: r, color} => {
+                    self.draw_rect.color = color;
+                    self.draw_rect.draw_abs(cx, Rect {pos: rect.pos + r.pos, size: r.size});
+                }
+                PluginDrawCmd::Line {start, end, width, color} => {
+                    self.draw_line.draw_line_abs(cx, rect.pos + start, rect.pos + end, color, width);
+                }
+                PluginDrawCmd::Text {pos, text, color} => {
+                    self.draw_text.color = color;
+                    self.draw_text.draw_abs(cx, rect.pos + pos, &text);
+                }
+            }
+        }
+        let uid = self.widget_uid();
+        self.flush_plugin(cx, uid, &scope.path);
+        DrawStep::done()
+    }
+}
+
+impl PluginViewRef {
+    /// Returns the plugin actions emitted by this view, in order.
+    pub fn plugin_actions(&self, actions: &Actions) -> Vec<PluginActionItem> {
+        let Some(inner) = self.borrow() else {
+            return Vec::new()
+        };
+        actions.filter_widget_actions_cast::<PluginViewAction>(inner.widget_uid()).filter_map(|action| {
+            if let PluginViewAction::Action {name, data} = action {
+                Some(PluginActionItem {name, data})
+            }
+            else {
+                None
+            }
+        }).collect()
+    }
+
+    pub fn loaded(&self, actions: &Actions) -> bool {
+        let Some(inner) = self.borrow() else {
+            return false
+        };
+        actions.filter_widget_actions_cast::<PluginViewAction>(inner.widget_uid()).any(|action| matches!(action, PluginViewAction::Loaded))
+    }
+
+    pub fn error(&self, actions: &Actions) -> Option<String> {
+        let inner = self.borrow()?;
+        actions.filter_widget_actions_cast::<PluginViewAction>(inner.widget_uid()).find_map(|action| {
+            if let PluginViewAction::Error(e) = action {Some(e)} else {None}
+        })
+    }
+
+    pub fn reload(&self, cx: &mut Cx) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.reload(cx);
+        }
+    }
+}
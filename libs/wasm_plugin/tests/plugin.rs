@@ -0,0 +1,194 @@
+use makepad_wasm_plugin::{
+    makepad_stitch::Trap,
+    abi::{PluginEventKind, PLUGIN_EVENT_BUFFER_SIZE},
+    makepad_widgets::{dvec2, KeyModifiers},
+    PluginError, PluginEvent, WasmPlugin,
+};
+
+// A tiny wasm assembler, just enough to build the test plugins without a
+// wat toolchain.
+
+fn leb(mut value: u32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(mut value: i32, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn name(name: &str, out: &mut Vec<u8>) {
+    leb(name.len() as u32, out);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn section(id: u8, count: usize, items: &[u8], out: &mut Vec<u8>) {
+    let mut body = Vec::new();
+    leb(count as u32, &mut body);
+    body.extend_from_slice(items);
+    out.push(id);
+    leb(body.len() as u32, out);
+    out.extend_from_slice(&body);
+}
+
+fn i32_const(value: i32) -> Vec<u8> {
+    let mut out = vec![0x41];
+    sleb(value, &mut out);
+    out
+}
+
+// The string the echo handler uses as action name lives at address 0
+const ACTION_NAME: &str = "event";
+
+// A plugin with one page of memory, an event buffer at `event_buffer` and
+// `handle_event` as the body of its `makepad_handle_event(len: i32)`. It
+// imports `emit_action` as function 0.
+fn plugin_wasm(event_buffer: i32, handle_event: &[u8]) -> Vec<u8> {
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    // () -> i32, (i32) -> (), (i32, i32, i32, i32) -> ()
+    section(1, 3, &[0x60, 0, 1, 0x7f, 0x60, 1, 0x7f, 0, 0x60, 4, 0x7f, 0x7f, 0x7f, 0x7f, 0], &mut out);
+    let mut imports = Vec::new();
+    name("makepad_v1", &mut imports);
+    name("emit_action", &mut imports);
+    imports.extend_from_slice(&[0x00, 2]);
+    section(2, 1, &imports, &mut out);
+    section(3, 3, &[0, 0, 1], &mut out);
+    section(5, 1, &[0x00, 1], &mut out);
+    let mut exports = Vec::new();
+    for (export, kind, index) in [("memory", 2, 0), ("makepad_abi_version", 0, 1), ("makepad_event_buffer", 0, 2), ("makepad_handle_event", 0, 3)] {
+        name(export, &mut exports);
+        exports.extend_from_slice(&[kind, index]);
+    }
+    section(7, 4, &exports, &mut out);
+    let mut code = Vec::new();
+    for body in [i32_const(1), i32_const(event_buffer), handle_event.to_vec()] {
+        let mut func = vec![0];
+        func.extend_from_slice(&body);
+        func.push(0x0b);
+        leb(func.len() as u32, &mut code);
+        code.extend_from_slice(&func);
+    }
+    section(10, 3, &code, &mut out);
+    let mut data = vec![0x00];
+    data.extend_from_slice(&i32_const(0));
+    data.push(0x0b);
+    name(ACTION_NAME, &mut data);
+    section(11, 1, &data, &mut out);
+    out
+}
+
+const EVENT_BUFFER: i32 = 1024;
+
+// emit_action(ACTION_NAME, event buffer, len), echoes every event back
+fn echo_handler() -> Vec<u8> {
+    let mut body = i32_const(0);
+    body.extend(i32_const(ACTION_NAME.len() as i32));
+    body.extend(i32_const(EVENT_BUFFER));
+    body.extend_from_slice(&[0x20, 0, 0x10, 0]);
+    body
+}
+
+fn load_plugin(file: &str, wasm: &[u8]) -> WasmPlugin {
+    let path = std::env::temp_dir().join(format!("makepad_wasm_plugin_{}_{}.wasm", std::process::id(), file));
+    std::fs::write(&path, wasm).unwrap();
+    let plugin = WasmPlugin::load(&path);
+    let _ = std::fs::remove_file(&path);
+    plugin
+}
+
+fn assert_trap(error: Option<&PluginError>, expected: fn(&Trap) -> bool) {
+    match error {
+        Some(PluginError::Trap(trap)) if expected(trap) => (),
+        other => panic!("unexpected plugin state {:?}", other),
+    }
+}
+
+#[test]
+fn loads_a_plugin() {
+    let plugin = load_plugin("load", &plugin_wasm(EVENT_BUFFER, &echo_handler()));
+    assert!(plugin.is_loaded(), "{:?}", plugin.error());
+    assert!(plugin.error().is_none());
+
+    let plugin = load_plugin("garbage", b"not a wasm module");
+    assert!(!plugin.is_loaded());
+    assert!(matches!(plugin.error(), Some(PluginError::Decode(_))));
+}
+
+#[test]
+fn delivers_events() {
+    let mut plugin = load_plugin("events", &plugin_wasm(EVENT_BUFFER, &echo_handler()));
+    plugin.handle_event(&PluginEvent::KeyFocus).unwrap();
+    plugin.handle_event(&PluginEvent::Finger {
+        kind: PluginEventKind::FingerDown,
+        pos: dvec2(3.0, 4.0),
+        digit: 1,
+        modifiers: KeyModifiers {shift: true, ..Default::default()},
+    }).unwrap();
+
+    let actions = plugin.take_actions();
+    assert_eq!(actions.len(), 2);
+    assert!(actions.iter().all( | a | a.name == ACTION_NAME));
+    assert_eq!(actions[0].data, (PluginEventKind::KeyFocus as u32).to_le_bytes());
+    let mut finger = Vec::new();
+    finger.extend_from_slice(&(PluginEventKind::FingerDown as u32).to_le_bytes());
+    finger.extend_from_slice(&3.0f32.to_le_bytes());
+    finger.extend_from_slice(&4.0f32.to_le_bytes());
+    finger.extend_from_slice(&1u32.to_le_bytes());
+    finger.extend_from_slice(&1u32.to_le_bytes());
+    assert_eq!(actions[1].data, finger);
+    assert!(plugin.take_actions().is_empty());
+}
+
+#[test]
+fn long_text_input_stays_in_the_event_buffer() {
+    let mut plugin = load_plugin("text", &plugin_wasm(EVENT_BUFFER, &echo_handler()));
+    plugin.handle_event(&PluginEvent::TextInput("é".repeat(PLUGIN_EVENT_BUFFER_SIZE))).unwrap();
+    let actions = plugin.take_actions();
+    assert!(actions[0].data.len() <= PLUGIN_EVENT_BUFFER_SIZE);
+    assert!(String::from_utf8(actions[0].data[8..].to_vec()).is_ok());
+}
+
+#[test]
+fn a_trap_faults_the_plugin() {
+    let mut plugin = load_plugin("trap", &plugin_wasm(EVENT_BUFFER, &[0x00]));
+    assert!(plugin.is_loaded());
+    assert!(plugin.handle_event(&PluginEvent::KeyFocus).is_err());
+    assert!(!plugin.is_loaded());
+    assert_trap(plugin.error(), | t | matches!(t, Trap::Unreachable));
+    // a faulted plugin stays down until it is reloaded
+    assert!(matches!(plugin.handle_event(&PluginEvent::KeyFocus), Err(PluginError::Trap(_))));
+}
+
+#[test]
+fn a_looping_plugin_runs_out_of_fuel() {
+    // loop br 0 end
+    let mut plugin = load_plugin("loop", &plugin_wasm(EVENT_BUFFER, &[0x03, 0x40, 0x0c, 0, 0x0b]));
+    assert!(plugin.handle_event(&PluginEvent::KeyFocus).is_err());
+    assert_trap(plugin.error(), | t | matches!(t, Trap::OutOfFuel));
+}
+
+#[test]
+fn an_event_buffer_past_the_end_of_memory_faults() {
+    let plugin = load_plugin("oob", &plugin_wasm(65536 - 16, &echo_handler()));
+    assert!(!plugin.is_loaded());
+    assert_trap(plugin.error(), | t | matches!(t, Trap::MemAccessOutOfBounds));
+
+    let plugin = load_plugin("negative", &plugin_wasm(-4, &echo_handler()));
+    assert!(!plugin.is_loaded());
+    assert_trap(plugin.error(), | t | matches!(t, Trap::MemAccessOutOfBounds));
+}
@@ -87,6 +87,18 @@ fn derive_live_atomic_impl_inner(parser: &mut TokenParser, tb: &mut TokenBuilder
         tb.add("        }");
         tb.add("        index");
         tb.add("    }");
+
+        tb.add("    fn set_atomic_path(&self, path:&[LiveId], value:&LiveValue)->bool{");
+        tb.add("        let Some((id, rest)) = path.split_first() else{return false};");
+        tb.add("        match *id {");
+        for field in &fields {
+            if field.attrs[0].name == "live" {
+                tb.add("    LiveId(").suf_u64(LiveId::from_str(&field.name).0).add(")=>self.").ident(&field.name).add(".set_atomic_path(rest, value),");
+            }
+        }
+        tb.add("            _=>false");
+        tb.add("        }");
+        tb.add("    }");
         tb.add("}");
         if main_attribs.iter().any( | attr | attr.name == "live_debug") {
             tb.eprint();
@@ -161,6 +173,15 @@ struct EnumItem {
         tb.add("        _=>panic!(").string("Invalid u32 for enum, should be impossible").add(")");
         tb.add("        }");
         tb.add("    }");
+
+        tb.add("    fn from_live_id(id:LiveId) -> Option<Self> {");
+        tb.add("        match(id){");
+        for item in &items {
+            tb.add("        LiveId(").suf_u64(LiveId::from_str(&item.name).0).add(")=>Some(Self::").ident(&item.name).add("),");
+        }
+        tb.add("        _=>None");
+        tb.add("        }");
+        tb.add("    }");
         tb.add("}");
         
         Ok(())
@@ -962,8 +962,10 @@ pub fn expand_all_documents(&mut self, errors: &mut Vec<LiveError>) {
         self.doc_original_raw_imports_to_resolved_recur(main_file_id, errors, &mut dep_order);
         
         // FIX dont hardcode this, will fix it up with the icon refactor
-        let fixup_file_id = self.path_end_to_file_id("draw_trapezoid.rs").unwrap();
-        self.doc_original_raw_imports_to_resolved_recur(fixup_file_id, errors, &mut dep_order);
+        // not there when nothing draws, like in an audio plugin
+        if let Some(fixup_file_id) = self.path_end_to_file_id("draw_trapezoid.rs") {
+            self.doc_original_raw_imports_to_resolved_recur(fixup_file_id, errors, &mut dep_order);
+        }
         
         /*
         for dep in &dep_order{
@@ -127,6 +127,16 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 }
 
 
+/// The audio server missed a deadline and the audio glitched. Sent by the
+/// backends that can tell, JACK on Linux
+#[derive(Clone, Copy, Debug)]
+pub struct AudioXrunEvent {
+    /// Since the last event
+    pub count: usize,
+    /// Since the app started
+    pub total: usize,
+}
+
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum AudioDeviceType {
     Input,
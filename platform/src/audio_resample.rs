@@ -0,0 +1,318 @@
+// Windowed sinc sample rate conversion, used where a device runs at a different
+// rate than the audio that is fed to it or read from it
+
+use crate::audio::*;
+
+/// Trade-off between cpu use and how clean the conversion is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// 24 taps, at least 50dB of alias rejection
+    Fast,
+    /// 64 taps, at least 80dB
+    #[default]
+    Balanced,
+    /// 128 taps, at least 110dB, for offline work and mastering
+    High,
+}
+
+impl ResampleQuality {
+    // taps on either side of the center, number of filter phases, kaiser beta
+    fn params(&self) -> (usize, usize, f64) {
+        match self {
+            Self::Fast => (12, 64, 5.0),
+            Self::Balanced => (32, 256, 8.5),
+            Self::High => (64, 512, 12.0),
+        }
+    }
+}
+
+/// A streaming polyphase resampler. Input is pushed in any block size and
+/// output is pulled in any block size, the filter state carries over so
+/// block edges don't click. Works for any rate ratio, not just the
+/// rational ones
+pub struct Resampler {
+    from_rate: f64,
+    to_rate: f64,
+    channel_count: usize,
+    quality: ResampleQuality,
+    // taps on either side of the center, in input frames
+    half: usize,
+    phases: usize,
+    // (phases + 1) rows of 2 * half taps
+    table: Vec<f32>,
+    // input frames per output frame
+    step: f64,
+    history: Vec<Vec<f32>>,
+    // where the next output frame sits in history
+    position: f64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: f64, to_rate: f64, channel_count: usize, quality: ResampleQuality) -> Self {
+        assert!(from_rate > 0.0 && to_rate > 0.0, "Resampler needs positive sample rates");
+        let mut resampler = Self {
+            from_rate,
+            to_rate,
+            channel_count,
+            quality,
+            half: 0,
+            phases: 0,
+            table: Vec::new(),
+            step: 0.0,
+            history: Vec::new(),
+            position: 0.0,
+        };
+        resampler.build_table();
+        resampler
+    }
+
+    pub fn from_rate(&self) -> f64 {self.from_rate}
+    pub fn to_rate(&self) -> f64 {self.to_rate}
+    pub fn channel_count(&self) -> usize {self.channel_count}
+    pub fn quality(&self) -> ResampleQuality {self.quality}
+
+    /// Changes the conversion, this drops whatever is buffered
+    pub fn set_rates(&mut self, from_rate: f64, to_rate: f64) {
+        assert!(from_rate > 0.0 && to_rate > 0.0, "Resampler needs positive sample rates");
+        self.from_rate = from_rate;
+        self.to_rate = to_rate;
+        self.build_table();
+    }
+
+    /// Forgets all buffered input, for when the stream is interrupted
+    pub fn reset(&mut self) {
+        // the first output frame lines up with the first input frame, the
+        // filter looks back into silence for it
+        self.history = vec![vec![0.0; self.half - 1]; self.channel_count];
+        self.position = (self.half - 1) as f64;
+    }
+
+    /// How far the output lags behind the input, in seconds
+    pub fn latency(&self) -> f64 {
+        self.half as f64 / self.from_rate
+    }
+
+    /// The latency in frames at the output rate
+    pub fn latency_frames(&self) -> usize {
+        (self.latency() * self.to_rate).round() as usize
+    }
+
+    fn build_table(&mut self) {
+        let (half, phases, beta) = self.quality.params();
+        // the kaiser formula for the transition band this many taps gets us,
+        // the cutoff is put so the stopband starts at nyquist
+        let attenuation = beta / 0.1102 + 8.7;
+        let transition = (attenuation - 8.0) / (2.285 * 2.0 * half as f64 * std::f64::consts::PI);
+        // going down the filter has to cut at the output nyquist, which
+        // widens it by the same factor in input frames
+        let scale = (self.to_rate / self.from_rate).min(1.0);
+        let cutoff = (1.0 - transition / 2.0) * scale;
+        let half = (half as f64 / scale).ceil() as usize;
+        let taps = 2 * half;
+
+        self.half = half;
+        self.phases = phases;
+        self.step = self.from_rate / self.to_rate;
+        self.table.clear();
+        self.table.reserve((phases + 1) * taps);
+        let norm = bessel_i0(beta);
+        for phase in 0..=phases {
+            let frac = phase as f64 / phases as f64;
+            for k in 0..taps {
+                let t = (k as f64 - (half - 1) as f64) - frac;
+                let x = t / half as f64;
+                let window = if x.abs() >= 1.0 {0.0} else {bessel_i0(beta * (1.0 - x * x).sqrt()) / norm};
+                self.table.push((cutoff * sinc(cutoff * t) * window) as f32);
+            }
+        }
+        self.reset();
+    }
+
+    /// Adds input frames. Missing channels repeat the last one the input has
+    pub fn push(&mut self, input: &AudioBuffer) {
+        if input.channel_count() == 0 {
+            return
+        }
+        for (c, history) in self.history.iter_mut().enumerate() {
+            history.extend_from_slice(input.channel(c.min(input.channel_count() - 1)));
+        }
+    }
+
+    // where output frame `k` from now sits in history. Everything that asks
+    // goes through here, so the rounding always agrees
+    fn input_position(&self, k: usize) -> f64 {
+        self.position + k as f64 * self.step
+    }
+
+    /// The number of output frames that can be pulled right now
+    pub fn available(&self) -> usize {
+        let len = self.history.first().map_or(0, | h | h.len());
+        if len < self.half + 1 {
+            return 0
+        }
+        let last = (len - self.half - 1) as f64;
+        if self.position > last {
+            return 0
+        }
+        // floor(position + k * step) <= last, corrected for rounding
+        let mut count = ((last + 1.0 - self.position) / self.step).ceil() as usize;
+        while count > 0 && self.input_position(count - 1).floor() > last {
+            count -= 1;
+        }
+        while self.input_position(count).floor() <= last {
+            count += 1;
+        }
+        count
+    }
+
+    /// How many more input frames have to be pushed before `frames` output
+    /// frames can be pulled
+    pub fn input_needed(&self, frames: usize) -> usize {
+        if frames == 0 {
+            return 0
+        }
+        let len = self.history.first().map_or(0, | h | h.len());
+        let last = self.input_position(frames - 1).floor() as usize;
+        (last + self.half + 1).saturating_sub(len)
+    }
+
+    /// Fills `output` with as many frames as are available and returns that
+    /// count, the rest of the buffer is zeroed
+    pub fn pull(&mut self, output: &mut AudioBuffer) -> usize {
+        let frames = self.available().min(output.frame_count());
+        let taps = 2 * self.half;
+        let phases = self.phases as f64;
+        let out_channels = output.channel_count();
+        for c in 0..out_channels {
+            let history = &self.history[c.min(self.channel_count - 1)];
+            let out = output.channel_mut(c);
+            for (k, sample) in out[..frames].iter_mut().enumerate() {
+                let position = self.input_position(k);
+                let index = position.floor();
+                let phase = (position - index) * phases;
+                let row = phase.floor();
+                let blend = (phase - row) as f32;
+                let row = row as usize * taps;
+                let start = index as usize + 1 - self.half;
+                let input = &history[start..start + taps];
+                let mut a = 0.0;
+                let mut b = 0.0;
+                for ((x, h0), h1) in input.iter().zip(&self.table[row..row + taps]).zip(&self.table[row + taps..row + 2 * taps]) {
+                    a += x * h0;
+                    b += x * h1;
+                }
+                *sample = a + (b - a) * blend;
+            }
+            for sample in out[frames..].iter_mut() {
+                *sample = 0.0;
+            }
+        }
+        self.position = self.input_position(frames);
+
+        // drop the input nothing looks at anymore
+        let consumed = (self.position.floor() as usize + 1).saturating_sub(self.half);
+        if consumed > 0 {
+            for history in &mut self.history {
+                history.drain(..consumed);
+            }
+            self.position -= consumed as f64;
+        }
+        frames
+    }
+
+    /// Pushes `input` and pulls everything it made available into `output`,
+    /// which is resized to fit
+    pub fn process_into(&mut self, input: &AudioBuffer, output: &mut AudioBuffer) {
+        self.push(input);
+        output.resize(self.available(), self.channel_count);
+        self.pull(output);
+    }
+
+    pub fn process(&mut self, input: &AudioBuffer) -> AudioBuffer {
+        let mut output = AudioBuffer::default();
+        self.process_into(input, &mut output);
+        output
+    }
+}
+
+/// Wraps an output callback so it renders at `sample_rate` whatever rate the
+/// device runs at. The callback gets blocks of varying size when the rates differ
+pub fn resampled_audio_output(sample_rate: f64, quality: ResampleQuality, mut f: AudioOutputFn) -> AudioOutputFn {
+    let mut resampler: Option<Resampler> = None;
+    let mut render = AudioBuffer::default();
+    Box::new(move | info, output | {
+        if info.sample_rate == sample_rate || info.sample_rate <= 0.0 {
+            resampler = None;
+            return f(info, output)
+        }
+        let resampler = match &mut resampler {
+            Some(r) if r.to_rate() == info.sample_rate && r.channel_count() == output.channel_count() => r,
+            _ => resampler.insert(Resampler::new(sample_rate, info.sample_rate, output.channel_count(), quality))
+        };
+        let needed = resampler.input_needed(output.frame_count());
+        if needed > 0 {
+            render.resize(needed, output.channel_count());
+            f(converted_info(info, sample_rate), &mut render);
+            resampler.push(&render);
+        }
+        resampler.pull(output);
+    })
+}
+
+/// Wraps an input callback so it receives audio at `sample_rate` whatever
+/// rate the device records at
+pub fn resampled_audio_input(sample_rate: f64, quality: ResampleQuality, mut f: AudioInputFn) -> AudioInputFn {
+    let mut resampler: Option<Resampler> = None;
+    let mut converted = AudioBuffer::default();
+    Box::new(move | info, input | {
+        if info.sample_rate == sample_rate || info.sample_rate <= 0.0 {
+            resampler = None;
+            return f(info, input)
+        }
+        let resampler = match &mut resampler {
+            Some(r) if r.from_rate() == info.sample_rate && r.channel_count() == input.channel_count() => r,
+            _ => resampler.insert(Resampler::new(info.sample_rate, sample_rate, input.channel_count(), quality))
+        };
+        resampler.process_into(input, &mut converted);
+        if converted.frame_count() > 0 {
+            f(converted_info(info, sample_rate), &converted);
+        }
+    })
+}
+
+fn converted_info(info: AudioInfo, sample_rate: f64) -> AudioInfo {
+    AudioInfo {
+        device_id: info.device_id,
+        time: info.time.map( | time | AudioTime {
+            sample_time: time.sample_time * sample_rate / info.sample_rate,
+            ..time
+        }),
+        sample_rate,
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    }
+    else {
+        let x = x * std::f64::consts::PI;
+        x.sin() / x
+    }
+}
+
+// the zeroth order modified bessel function, the series converges quickly
+// for the betas used here
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= half / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-17 {
+            break
+        }
+    }
+    sum
+}
@@ -32,6 +32,7 @@
             Trigger,
             CxKeyboard,
             NextFrame,
+            CxHttpStreams,
         },
         studio::StudioScreenshotRequest,
         action::ActionsBuf,
@@ -42,6 +43,8 @@
         window::CxWindowPool,
         draw_list::CxDrawListPool,
         web_socket::WebSocket,
+        http_cache::HttpCache,
+        network_mock::NetworkMock,
         pass::CxPassPool,
         texture::{CxTexturePool,TextureFormat,Texture,TextureUpdated},
         geometry::{
@@ -94,6 +97,10 @@ pub struct Cx {
     
     pub (crate) platform_ops: Vec<CxOsOp>,
     
+    pub (crate) http_streams: CxHttpStreams,
+    pub (crate) http_cache: Option<HttpCache>,
+    pub (crate) network_mock: Option<NetworkMock>,
+    
     pub (crate) new_next_frames: HashSet<NextFrame>,
     
     pub new_actions: ActionsBuf,
@@ -324,6 +331,9 @@ pub fn new(event_handler: Box<dyn FnMut(&mut Cx, &Event)>) -> Self {
             drag_drop: Default::default(),
             ime_area: Default::default(),
             platform_ops: Default::default(),
+            http_streams: Default::default(),
+            http_cache: None,
+            network_mock: None,
             studio_web_socket: None,
             studio_http: "".to_string(),
             new_next_frames: Default::default(),
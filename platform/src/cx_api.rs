@@ -669,13 +669,13 @@ pub fn spawner(&self) -> &Spawner {
     }
 
     pub fn http_request(&mut self, request_id: LiveId, request: HttpRequest) {
-        self.platform_ops.push(CxOsOp::HttpRequest {
-            request_id,
-            request,
-        });
+        self.push_http_request(request_id, request);
     }
 
     pub fn cancel_http_request(&mut self, request_id: LiveId) {
+        self.cancel_http_stream(request_id);
+        self.cancel_http_cache_request(request_id);
+        self.cancel_mock_http_request(request_id);
         self.platform_ops.push(CxOsOp::CancelHttpRequest {
             request_id,
         });
@@ -22,7 +22,7 @@
         },
         action::ActionsBuf,
         animator::Ease,
-        audio::AudioDevicesEvent,
+        audio::{AudioDevicesEvent, AudioXrunEvent},
         midi::MidiPortsEvent,
         video::VideoInputsEvent,
         draw_list::DrawListId,
@@ -198,6 +198,7 @@ pub enum Event {
 
     Actions(ActionsBuf),
     AudioDevices(AudioDevicesEvent),
+    AudioXrun(AudioXrunEvent),
     MidiPorts(MidiPortsEvent),
     VideoInputs(VideoInputsEvent),
     NetworkResponses(NetworkResponsesEvent),
@@ -304,6 +305,7 @@ pub fn name_from_u32(v:u32)->&'static str{
             
             56=>"DesignerPick",
             57=>"XrLocal",
+            58=>"AudioXrun",
             _=>panic!()
         }
     }
@@ -377,7 +379,8 @@ pub fn to_u32(&self)->u32{
             Self::ToWasmMsg(_)=>55,
             
             Self::DesignerPick(_) =>56,
-            Self::XrLocal(_)=>57
+            Self::XrLocal(_)=>57,
+            Self::AudioXrun(_)=>58,
         }
     }
 
@@ -0,0 +1,355 @@
+use crate::{
+    cx::Cx,
+    event::{Event, Timer, TimerEvent},
+    event::network::*,
+    makepad_live_id::LiveId,
+};
+use std::collections::HashMap;
+
+// what the spec says a client waits before reconnecting when the server didn't say
+const SSE_DEFAULT_RETRY_MS: u64 = 3000;
+
+/// One server-sent event
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SseEvent {
+    /// The `event:` field, `message` when the server didn't give one
+    pub event: String,
+    /// All `data:` lines of the event joined with newlines
+    pub data: String,
+    /// The last event id the server set, which is what a reconnect sends back
+    pub id: String,
+    /// The reconnection time in milliseconds, when this event changed it
+    pub retry: Option<u64>,
+}
+
+/// Turns a `text/event-stream` body into events, whatever the chunks
+/// it arrives in look like
+#[derive(Clone, Debug, Default)]
+pub struct SseParser {
+    line: Vec<u8>,
+    // a \r ended the last chunk, so a \n starting the next one belongs to it
+    after_cr: bool,
+    started: bool,
+    event: String,
+    data: String,
+    retry_changed: Option<u64>,
+    pub last_event_id: String,
+    pub retry: Option<u64>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8], out: &mut Vec<SseEvent>) {
+        let mut data = data;
+        if !self.started && !data.is_empty() {
+            self.started = true;
+            // a leading byte order mark is not part of the first field name
+            if let Some(rest) = data.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+                data = rest;
+            }
+        }
+        for &byte in data {
+            match byte {
+                b'\n' if self.after_cr => {
+                    self.after_cr = false;
+                }
+                b'\r' | b'\n' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    self.process_line(&String::from_utf8_lossy(&line), out);
+                }
+                _ => {
+                    self.after_cr = false;
+                    self.line.push(byte);
+                }
+            }
+        }
+    }
+
+    /// Call when the body ends. An event the server didn't finish with a
+    /// blank line is dropped, as the spec wants
+    pub fn finish(&mut self) {
+        self.line.clear();
+        self.after_cr = false;
+        self.started = false;
+        self.event.clear();
+        self.data.clear();
+        self.retry_changed = None;
+    }
+
+    fn process_line(&mut self, line: &str, out: &mut Vec<SseEvent>) {
+        if line.is_empty() {
+            self.dispatch(out);
+            return
+        }
+        if line.starts_with(':') {
+            // a comment, servers send these to keep the connection open
+            return
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, "")
+        };
+        match field {
+            "event" => {
+                self.event = value.to_string();
+            }
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => {
+                self.last_event_id = value.to_string();
+            }
+            "retry" if !value.is_empty() && value.bytes().all( | b | b.is_ascii_digit()) => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
+                    self.retry_changed = Some(retry);
+                }
+            }
+            _ => ()
+        }
+    }
+
+    fn dispatch(&mut self, out: &mut Vec<SseEvent>) {
+        let event = std::mem::take(&mut self.event);
+        let mut data = std::mem::take(&mut self.data);
+        let retry = self.retry_changed.take();
+        if data.is_empty() {
+            return
+        }
+        data.pop();
+        out.push(SseEvent {
+            event: if event.is_empty() {"message".to_string()} else {event},
+            data,
+            id: self.last_event_id.clone(),
+            retry,
+        });
+    }
+}
+
+/// Splits a newline delimited json body into its lines. Blank lines are
+/// skipped, the text is not parsed
+#[derive(Clone, Debug, Default)]
+pub struct NdJsonParser {
+    line: Vec<u8>,
+}
+
+impl NdJsonParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8], out: &mut Vec<String>) {
+        for &byte in data {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                Self::push_line(&line, out);
+            }
+            else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    /// Call when the body ends, a last line without a newline is still a line
+    pub fn finish(&mut self, out: &mut Vec<String>) {
+        let line = std::mem::take(&mut self.line);
+        Self::push_line(&line, out);
+    }
+
+    fn push_line(line: &[u8], out: &mut Vec<String>) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if !line.is_empty() {
+            out.push(line.to_string());
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum HttpStreamParser {
+    Sse(SseParser),
+    NdJson(NdJsonParser),
+}
+
+struct CxHttpStream {
+    parser: HttpStreamParser,
+    // kept to send again when the stream reconnects
+    reconnect: Option<HttpRequest>,
+    // the server told us to stop, or answered with an error
+    stopped: bool,
+    timer: Timer,
+}
+
+/// The streamed requests the platform splits into events for the app
+#[derive(Default)]
+pub(crate) struct CxHttpStreams {
+    streams: HashMap<LiveId, CxHttpStream>,
+}
+
+impl Cx {
+    /// Starts a request for a `text/event-stream` and delivers it as
+    /// `NetworkResponse::HttpSseEvent`s. With `reconnect` the stream is opened
+    /// again when it ends, sending `Last-Event-ID`, until the server answers
+    /// 204 or `cancel_http_request` is called. Errors are still reported
+    /// while it keeps trying
+    pub fn http_sse_request(&mut self, request_id: LiveId, mut request: HttpRequest, reconnect: bool) {
+        request.set_is_streaming();
+        request.headers.insert("Accept".into(), vec!["text/event-stream".into()]);
+        request.headers.insert("Cache-Control".into(), vec!["no-cache".into()]);
+        self.start_http_stream(request_id, request, HttpStreamParser::Sse(SseParser::new()), reconnect);
+    }
+
+    /// Starts a request for a newline delimited json body and delivers each
+    /// line as a `NetworkResponse::HttpJsonLine`
+    pub fn http_ndjson_request(&mut self, request_id: LiveId, mut request: HttpRequest) {
+        request.set_is_streaming();
+        self.start_http_stream(request_id, request, HttpStreamParser::NdJson(NdJsonParser::new()), false);
+    }
+
+    fn start_http_stream(&mut self, request_id: LiveId, request: HttpRequest, parser: HttpStreamParser, reconnect: bool) {
+        self.cancel_http_stream(request_id);
+        self.http_streams.streams.insert(request_id, CxHttpStream {
+            parser,
+            reconnect: if reconnect {Some(request.clone())} else {None},
+            stopped: false,
+            timer: Timer::empty(),
+        });
+        self.push_http_request(request_id, request);
+    }
+
+    pub(crate) fn cancel_http_stream(&mut self, request_id: LiveId) {
+        if let Some(stream) = self.http_streams.streams.remove(&request_id) {
+            self.stop_timer(stream.timer);
+        }
+    }
+
+    /// Everything the backends receive goes through here, so streamed
+    /// requests get split up before the app and scripts see them
+    pub(crate) fn handle_network_responses(&mut self, items: Vec<NetworkResponseItem>) {
+        let items = self.handle_http_cache_responses(items);
+        let items = if self.http_streams.streams.is_empty() {items} else {self.frame_http_streams(items)};
+        if !items.is_empty() {
+            self.handle_script_network_events(&items);
+            self.call_event_handler(&Event::NetworkResponses(items));
+        }
+    }
+
+    fn frame_http_streams(&mut self, items: Vec<NetworkResponseItem>) -> Vec<NetworkResponseItem> {
+        let mut out = Vec::new();
+        for item in items {
+            let request_id = item.request_id;
+            let Some(stream) = self.http_streams.streams.get_mut(&request_id) else {
+                out.push(item);
+                continue
+            };
+            match item.response {
+                NetworkResponse::HttpStreamResponse(res) if res.status_code >= 300 => {
+                    // error pages aren't events, hand them over as they are
+                    stream.stopped = true;
+                    out.push(NetworkResponseItem {request_id, response: NetworkResponse::HttpStreamResponse(res)});
+                }
+                NetworkResponse::HttpStreamResponse(res) => {
+                    if let Some(body) = &res.body {
+                        stream.feed(request_id, body, &mut out);
+                    }
+                }
+                // backends without streaming support deliver the whole body at once
+                NetworkResponse::HttpResponse(mut res) | NetworkResponse::HttpStreamComplete(mut res) => {
+                    if res.status_code >= 300 {
+                        stream.stopped = true;
+                    }
+                    else if let Some(body) = res.body.take() {
+                        stream.feed(request_id, &body, &mut out);
+                    }
+                    stream.finish(request_id, &mut out);
+                    if res.status_code == 204 {
+                        stream.stopped = true;
+                    }
+                    if !self.schedule_reconnect(request_id) {
+                        self.http_streams.streams.remove(&request_id);
+                        out.push(NetworkResponseItem {request_id, response: NetworkResponse::HttpStreamComplete(res)});
+                    }
+                }
+                NetworkResponse::HttpRequestError(err) => {
+                    stream.finish(request_id, &mut out);
+                    if !self.schedule_reconnect(request_id) {
+                        self.http_streams.streams.remove(&request_id);
+                    }
+                    out.push(NetworkResponseItem {request_id, response: NetworkResponse::HttpRequestError(err)});
+                }
+                response => {
+                    out.push(NetworkResponseItem {request_id, response});
+                }
+            }
+        }
+        out
+    }
+
+    fn schedule_reconnect(&mut self, request_id: LiveId) -> bool {
+        let Some(stream) = self.http_streams.streams.get(&request_id) else {
+            return false
+        };
+        if stream.stopped || stream.reconnect.is_none() {
+            return false
+        }
+        let retry = match &stream.parser {
+            HttpStreamParser::Sse(parser) => parser.retry.unwrap_or(SSE_DEFAULT_RETRY_MS),
+            HttpStreamParser::NdJson(_) => SSE_DEFAULT_RETRY_MS,
+        };
+        let timer = self.start_timeout(retry as f64 / 1000.0);
+        self.http_streams.streams.get_mut(&request_id).unwrap().timer = timer;
+        true
+    }
+
+    /// Returns true when the timer was a stream waiting to reconnect
+    pub(crate) fn handle_http_stream_timer(&mut self, te: &TimerEvent) -> bool {
+        let Some((request_id, stream)) = self.http_streams.streams.iter_mut().find( | (_, s) | s.timer.is_timer(te).is_some()) else {
+            return false
+        };
+        let request_id = *request_id;
+        stream.timer = Timer::empty();
+        let Some(mut request) = stream.reconnect.clone() else {
+            return true
+        };
+        if let HttpStreamParser::Sse(parser) = &stream.parser {
+            if !parser.last_event_id.is_empty() {
+                request.headers.insert("Last-Event-ID".into(), vec![parser.last_event_id.clone()]);
+            }
+        }
+        self.push_http_request(request_id, request);
+        true
+    }
+}
+
+impl CxHttpStream {
+    fn feed(&mut self, request_id: LiveId, body: &[u8], out: &mut Vec<NetworkResponseItem>) {
+        match &mut self.parser {
+            HttpStreamParser::Sse(parser) => {
+                let mut events = Vec::new();
+                parser.feed(body, &mut events);
+                out.extend(events.into_iter().map( | e | NetworkResponseItem {request_id, response: NetworkResponse::HttpSseEvent(e)}));
+            }
+            HttpStreamParser::NdJson(parser) => {
+                let mut lines = Vec::new();
+                parser.feed(body, &mut lines);
+                out.extend(lines.into_iter().map( | l | NetworkResponseItem {request_id, response: NetworkResponse::HttpJsonLine(l)}));
+            }
+        }
+    }
+
+    fn finish(&mut self, request_id: LiveId, out: &mut Vec<NetworkResponseItem>) {
+        match &mut self.parser {
+            HttpStreamParser::Sse(parser) => parser.finish(),
+            HttpStreamParser::NdJson(parser) => {
+                let mut lines = Vec::new();
+                parser.finish(&mut lines);
+                out.extend(lines.into_iter().map( | l | NetworkResponseItem {request_id, response: NetworkResponse::HttpJsonLine(l)}));
+            }
+        }
+    }
+}
@@ -5,6 +5,7 @@
 pub mod xr;
 pub mod drag_drop;
 pub mod network;
+pub mod http_stream;
 pub mod video_playback;
 pub mod designer;
 
@@ -16,4 +17,5 @@
 pub use xr::*;
 pub use drag_drop::*;
 pub use network::*;
+pub use http_stream::*;
 pub use video_playback::*;
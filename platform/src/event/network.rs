@@ -3,6 +3,7 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::collections::BTreeMap;
 use std::str;
+use crate::event::http_stream::SseEvent;
 
 #[derive(Clone, Debug)]
 pub struct NetworkResponseItem{
@@ -32,6 +33,10 @@ pub enum NetworkResponse{
     HttpStreamResponse(HttpResponse),
     HttpStreamComplete(HttpResponse),
     HttpProgress(HttpProgress),
+    /// An event of a request started with `Cx::http_sse_request`
+    HttpSseEvent(SseEvent),
+    /// A line of a request started with `Cx::http_ndjson_request`
+    HttpJsonLine(String),
 }
 /*
 pub struct NetworkResponseIter<I> {
@@ -78,7 +83,7 @@ pub struct HttpRequest {
 }
 */
 
-#[derive(PartialEq, Debug, Script, ScriptHook, Default)]
+#[derive(Clone, PartialEq, Debug, Script, ScriptHook, Default)]
 pub struct HttpRequest {
     #[live] pub metadata_id: LiveId,
     #[live] pub url: String,
@@ -244,7 +249,7 @@ fn parse_headers(headers_string: String) -> BTreeMap<String, Vec<String>> {
     }
 }
 
-#[derive(PartialEq, Debug, Script, ScriptHook, Default)]
+#[derive(Clone, PartialEq, Debug, Script, ScriptHook, Default)]
 pub enum HttpMethod{
     #[default]
     #[pick]
@@ -0,0 +1,679 @@
+use crate::{
+    cx::Cx,
+    cx_api::CxOsOp,
+    event::network::*,
+    thread::SignalToUI,
+    makepad_live_id::LiveId,
+    makepad_micro_serde::*,
+    makepad_http::cookie::parse_http_date,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const INDEX_FILE: &str = "index.bin";
+// responses without any freshness information get 10% of their age since last modification, at most a day
+const MAX_HEURISTIC_FRESHNESS: u64 = 24 * 3600;
+// delta seconds past this count as this, as RFC 9111 asks
+const MAX_DELTA_SECONDS: u64 = 1 << 31;
+// the index is written once the cache has had no disk work for this long
+const INDEX_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(SerBin, DeBin, Clone, Debug)]
+struct HttpCacheEntry {
+    url: String,
+    file: String,
+    status_code: u16,
+    // one pair per header value
+    header_names: Vec<String>,
+    header_values: Vec<String>,
+    etag: String,
+    last_modified: String,
+    // unix seconds when the response was received
+    stored: u64,
+    // how many seconds after that the response is fresh
+    fresh_for: u64,
+    // the server wants us to ask every time
+    no_cache: bool,
+    // the request headers the response `Vary`s on, and what they were
+    vary_names: Vec<String>,
+    vary_values: Vec<String>,
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(SerBin, DeBin, Default)]
+struct HttpCacheIndex {
+    entries: Vec<HttpCacheEntry>,
+}
+
+struct HttpCachePending {
+    key: String,
+    // the conditional request went out for this entry
+    revalidating: bool,
+    // for the `Vary` of the response
+    request_headers: BTreeMap<String, Vec<String>>,
+}
+
+struct HttpCacheRead {
+    request_id: LiveId,
+    metadata_id: LiveId,
+    path: PathBuf,
+    status_code: u16,
+    headers: BTreeMap<String, Vec<String>>,
+}
+
+enum HttpCacheJob {
+    Read(HttpCacheRead),
+    Write {path: PathBuf, body: Vec<u8>},
+    Remove(PathBuf),
+    SaveIndex {path: PathBuf, data: Vec<u8>},
+    // answers when everything before it is done, and the index is written
+    Flush(Sender<()>),
+}
+
+// Does the disk work of the cache on its own thread, in the order it was
+// asked for, so a read always sees the writes before it. The web has no
+// threads, and no disk either, so the work happens right away there
+struct HttpCacheIo {
+    jobs: Option<Sender<HttpCacheJob>>,
+    thread: Option<JoinHandle<()>>,
+    out: Sender<NetworkResponseItem>,
+}
+
+/// An on-disk cache for GET requests. It follows the `Cache-Control`,
+/// `Expires`, `ETag` and `Last-Modified` headers of the responses it stores,
+/// revalidates stale ones with conditional requests, and throws out the least
+/// recently used responses when it grows past its size. Turn it on with
+/// `Cx::enable_http_cache` or `Cx::set_http_cache`; from then on every
+/// `Cx::http_request` goes through it
+pub struct HttpCache {
+    dir: PathBuf,
+    max_size: u64,
+    entries: HashMap<String, HttpCacheEntry>,
+    total_size: u64,
+    use_counter: u64,
+    pending: HashMap<LiveId, HttpCachePending>,
+    // responses being read from disk, fresh hits have their request to send
+    // out again when that fails
+    reading: HashMap<LiveId, Option<HttpRequest>>,
+    /// Answer with the stored response, however old, when the network fails
+    pub offline_fallback: bool,
+    io: HttpCacheIo,
+    read_receiver: Receiver<NetworkResponseItem>,
+}
+
+impl HttpCache {
+    /// Opens the cache in `dir`, creating the directory when needed
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> io::Result<HttpCache> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let index = std::fs::read(dir.join(INDEX_FILE)).ok()
+            .and_then( | data | HttpCacheIndex::deserialize_bin(&data).ok())
+            .unwrap_or_default();
+        let (read_sender, read_receiver) = channel();
+        let mut cache = HttpCache {
+            dir,
+            max_size,
+            entries: HashMap::new(),
+            total_size: 0,
+            use_counter: 0,
+            pending: HashMap::new(),
+            reading: HashMap::new(),
+            offline_fallback: true,
+            io: HttpCacheIo {jobs: None, thread: None, out: read_sender},
+            read_receiver,
+        };
+        for entry in index.entries {
+            // the index may be behind on files that were removed
+            if cache.dir.join(&entry.file).is_file() {
+                cache.total_size += entry.size;
+                cache.use_counter = cache.use_counter.max(entry.last_used);
+                cache.entries.insert(entry.url.clone(), entry);
+            }
+        }
+        cache.evict(0);
+        Ok(cache)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The bytes of response bodies on disk
+    pub fn size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.entries.contains_key(cache_key(url))
+    }
+
+    pub fn remove(&mut self, url: &str) {
+        if let Some(entry) = self.entries.remove(cache_key(url)) {
+            self.total_size -= entry.size;
+            self.io.send(HttpCacheJob::Remove(self.dir.join(&entry.file)));
+            self.save_index();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for entry in self.entries.values() {
+            self.io.send(HttpCacheJob::Remove(self.dir.join(&entry.file)));
+        }
+        self.entries.clear();
+        self.total_size = 0;
+        self.save_index();
+    }
+
+    /// Waits until the disk work the cache queued up is done, and the index
+    /// is written
+    pub fn flush(&mut self) {
+        let (done, wait) = channel();
+        self.io.send(HttpCacheJob::Flush(done));
+        wait.recv().ok();
+    }
+
+    fn save_index(&mut self) {
+        let index = HttpCacheIndex {entries: self.entries.values().cloned().collect()};
+        self.io.send(HttpCacheJob::SaveIndex {path: self.dir.join(INDEX_FILE), data: index.serialize_bin()});
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.use_counter += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.last_used = self.use_counter;
+        }
+    }
+
+    // makes room for `incoming` bytes, oldest use first
+    fn evict(&mut self, incoming: u64) {
+        if self.total_size + incoming <= self.max_size {
+            return
+        }
+        let mut by_use: Vec<(u64, String)> = self.entries.values().map( | e | (e.last_used, e.url.clone())).collect();
+        by_use.sort();
+        for (_, key) in by_use {
+            if self.total_size + incoming <= self.max_size {
+                break
+            }
+            let entry = self.entries.remove(&key).unwrap();
+            self.total_size -= entry.size;
+            self.io.send(HttpCacheJob::Remove(self.dir.join(&entry.file)));
+        }
+    }
+
+    fn store(&mut self, key: &str, request_headers: &BTreeMap<String, Vec<String>>, response: &HttpResponse) {
+        let Some(policy) = CachePolicy::from_headers(&response.headers) else {
+            // the server doesn't want it kept, forget what we had
+            self.remove(key);
+            return
+        };
+        let body = response.body.as_deref().unwrap_or(&[]);
+        let size = body.len() as u64;
+        if size > self.max_size {
+            self.remove(key);
+            return
+        }
+        if let Some(old) = self.entries.remove(key) {
+            self.total_size -= old.size;
+        }
+        self.evict(size);
+        let file = format!("{:016x}", LiveId::from_str(key).0);
+        self.io.send(HttpCacheJob::Write {path: self.dir.join(&file), body: body.to_vec()});
+        let mut header_names = Vec::new();
+        let mut header_values = Vec::new();
+        for (name, values) in &response.headers {
+            for value in values {
+                header_names.push(name.clone());
+                header_values.push(value.clone());
+            }
+        }
+        let vary_names: Vec<String> = header(&response.headers, "vary").unwrap_or_default()
+            .split(',').map( | name | name.trim().to_ascii_lowercase()).filter( | name | !name.is_empty()).collect();
+        let vary_values = vary_names.iter().map( | name | header(request_headers, name).unwrap_or_default()).collect();
+        self.use_counter += 1;
+        self.entries.insert(key.to_string(), HttpCacheEntry {
+            url: key.to_string(),
+            file,
+            status_code: response.status_code,
+            header_names,
+            header_values,
+            etag: header(&response.headers, "etag").unwrap_or_default(),
+            last_modified: header(&response.headers, "last-modified").unwrap_or_default(),
+            stored: unix_now(),
+            fresh_for: policy.fresh_for,
+            no_cache: policy.no_cache,
+            vary_names,
+            vary_values,
+            size,
+            last_used: self.use_counter,
+        });
+        self.total_size += size;
+        self.save_index();
+    }
+
+    // a 304 says what we have is still good, with new freshness
+    fn refresh(&mut self, key: &str, response: &HttpResponse) {
+        let policy = CachePolicy::from_headers(&response.headers);
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.stored = unix_now();
+            if let Some(policy) = policy {
+                entry.fresh_for = policy.fresh_for;
+                entry.no_cache = policy.no_cache;
+            }
+            if let Some(etag) = header(&response.headers, "etag") {
+                entry.etag = etag;
+            }
+        }
+        self.touch(key);
+        self.save_index();
+    }
+
+    // the stored response comes back through the io thread
+    fn read_async(&mut self, request_id: LiveId, metadata_id: LiveId, key: &str, resend: Option<HttpRequest>) -> bool {
+        let Some(entry) = self.entries.get(key) else {
+            return false
+        };
+        let read = HttpCacheRead {
+            request_id,
+            metadata_id,
+            path: self.dir.join(&entry.file),
+            status_code: entry.status_code,
+            headers: entry.headers(),
+        };
+        self.touch(key);
+        self.io.send(HttpCacheJob::Read(read));
+        self.reading.insert(request_id, resend);
+        true
+    }
+
+    /// Decides what happens to an outgoing request. Returns None when the
+    /// answer comes from disk and the request shouldn't go out
+    fn handle_request(&mut self, request_id: LiveId, mut request: HttpRequest) -> Option<HttpRequest> {
+        self.pending.remove(&request_id);
+        if request.method != HttpMethod::GET || request.is_streaming {
+            return Some(request)
+        }
+        let request_cache_control = header(&request.headers, "cache-control").unwrap_or_default().to_ascii_lowercase();
+        // what one user is allowed to see isn't for everyone else
+        if request_cache_control.contains("no-store") || header(&request.headers, "authorization").is_some() {
+            return Some(request)
+        }
+        let key = cache_key(&request.url).to_string();
+        let request_headers = request.headers.clone();
+        let mut revalidating = false;
+        if let Some(entry) = self.entries.get(&key).filter( | entry | entry.varies_with(&request.headers)) {
+            if entry.is_fresh(unix_now()) && !request_cache_control.contains("no-cache") {
+                self.read_async(request_id, request.metadata_id, &key, Some(request));
+                return None
+            }
+            if !entry.etag.is_empty() {
+                request.headers.insert("If-None-Match".into(), vec![entry.etag.clone()]);
+                revalidating = true;
+            }
+            if !entry.last_modified.is_empty() {
+                request.headers.insert("If-Modified-Since".into(), vec![entry.last_modified.clone()]);
+                revalidating = true;
+            }
+        }
+        self.pending.insert(request_id, HttpCachePending {key, revalidating, request_headers});
+        Some(request)
+    }
+
+    // None when the answer comes from disk instead
+    fn handle_response(&mut self, item: NetworkResponseItem) -> Option<NetworkResponseItem> {
+        let request_id = item.request_id;
+        let response = match item.response {
+            NetworkResponse::HttpResponse(res) => {
+                let Some(pending) = self.pending.remove(&request_id) else {
+                    return Some(NetworkResponseItem {request_id, response: NetworkResponse::HttpResponse(res)})
+                };
+                if res.status_code == 304 && pending.revalidating {
+                    self.refresh(&pending.key, &res);
+                    if self.read_async(request_id, res.metadata_id, &pending.key, None) {
+                        return None
+                    }
+                    NetworkResponse::HttpResponse(res)
+                }
+                else if res.status_code >= 500 && self.offline_fallback && self.read_async(request_id, res.metadata_id, &pending.key, None) {
+                    return None
+                }
+                else {
+                    if res.status_code == 200 {
+                        self.store(&pending.key, &pending.request_headers, &res);
+                    }
+                    NetworkResponse::HttpResponse(res)
+                }
+            }
+            NetworkResponse::HttpRequestError(err) => {
+                let pending = self.pending.remove(&request_id);
+                if let Some(pending) = pending.filter( | _ | self.offline_fallback) {
+                    if self.read_async(request_id, err.metadata_id, &pending.key, None) {
+                        return None
+                    }
+                }
+                NetworkResponse::HttpRequestError(err)
+            }
+            response => response
+        };
+        Some(NetworkResponseItem {request_id, response})
+    }
+}
+
+impl HttpCacheIo {
+    fn send(&mut self, job: HttpCacheJob) {
+        if cfg!(target_arch = "wasm32") {
+            job.run(&self.out);
+            return
+        }
+        let out = self.out.clone();
+        let jobs = self.jobs.get_or_insert_with( || {
+            let (sender, receiver) = channel();
+            self.thread = Some(std::thread::spawn(move || Self::run(receiver, out)));
+            sender
+        });
+        jobs.send(job).ok();
+    }
+
+    fn run(jobs: Receiver<HttpCacheJob>, out: Sender<NetworkResponseItem>) {
+        // the latest index waiting to be written
+        let mut index = None;
+        loop {
+            let job = if index.is_some() {
+                match jobs.recv_timeout(INDEX_SAVE_DELAY) {
+                    Ok(job) => job,
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some(save) = index.take() {
+                            HttpCacheJob::run(save, &out);
+                        }
+                        continue
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break
+                }
+            }
+            else {
+                match jobs.recv() {
+                    Ok(job) => job,
+                    Err(_) => break
+                }
+            };
+            match job {
+                HttpCacheJob::SaveIndex {..} => index = Some(job),
+                HttpCacheJob::Flush(done) => {
+                    if let Some(save) = index.take() {
+                        HttpCacheJob::run(save, &out);
+                    }
+                    done.send(()).ok();
+                }
+                job => job.run(&out)
+            }
+        }
+        if let Some(save) = index {
+            save.run(&out);
+        }
+    }
+}
+
+impl Drop for HttpCacheIo {
+    fn drop(&mut self) {
+        // let the thread finish what it has, the index included
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl HttpCacheJob {
+    fn run(self, out: &Sender<NetworkResponseItem>) {
+        match self {
+            HttpCacheJob::Read(read) => {
+                let response = match std::fs::read(&read.path) {
+                    Ok(body) => NetworkResponse::HttpResponse(HttpResponse {
+                        metadata_id: read.metadata_id,
+                        status_code: read.status_code,
+                        headers: read.headers,
+                        body: Some(body),
+                    }),
+                    Err(e) => NetworkResponse::HttpRequestError(HttpError {
+                        message: format!("Cannot read http cache entry: {}", e),
+                        metadata_id: read.metadata_id,
+                    })
+                };
+                out.send(NetworkResponseItem {request_id: read.request_id, response}).ok();
+                SignalToUI::set_ui_signal();
+            }
+            HttpCacheJob::Write {path, body} => {
+                if let Err(e) = std::fs::write(&path, body) {
+                    crate::error!("Cannot write http cache entry: {}", e);
+                }
+            }
+            HttpCacheJob::Remove(path) => {
+                std::fs::remove_file(path).ok();
+            }
+            HttpCacheJob::SaveIndex {path, data} => {
+                if let Err(e) = std::fs::write(&path, data) {
+                    crate::error!("Cannot write http cache index: {}", e);
+                }
+            }
+            HttpCacheJob::Flush(done) => {
+                done.send(()).ok();
+            }
+        }
+    }
+}
+
+impl HttpCacheEntry {
+    fn is_fresh(&self, now: u64) -> bool {
+        !self.no_cache && now < self.stored.saturating_add(self.fresh_for)
+    }
+
+    // the request asks for the same variant as the one stored
+    fn varies_with(&self, request_headers: &BTreeMap<String, Vec<String>>) -> bool {
+        self.vary_names.iter().zip(self.vary_values.iter())
+            .all( | (name, value) | header(request_headers, name).unwrap_or_default() == *value)
+    }
+
+    fn headers(&self) -> BTreeMap<String, Vec<String>> {
+        let mut headers = BTreeMap::new();
+        for (name, value) in self.header_names.iter().zip(self.header_values.iter()) {
+            headers.entry(name.clone()).or_insert_with(Vec::new).push(value.clone());
+        }
+        headers
+    }
+}
+
+struct CachePolicy {
+    fresh_for: u64,
+    no_cache: bool,
+}
+
+impl CachePolicy {
+    // None when the response may not be stored at all
+    fn from_headers(headers: &BTreeMap<String, Vec<String>>) -> Option<CachePolicy> {
+        let cache_control = header(headers, "cache-control").unwrap_or_default().to_ascii_lowercase();
+        let mut max_age = None;
+        let mut no_cache = false;
+        for directive in cache_control.split(',') {
+            let directive = directive.trim();
+            if directive == "no-store" {
+                return None
+            }
+            if directive == "no-cache" {
+                no_cache = true;
+            }
+            if let Some(value) = directive.strip_prefix("max-age=") {
+                max_age = delta_seconds(value.trim_matches('"'));
+            }
+        }
+        if header(headers, "vary").map( | v | v.trim() == "*").unwrap_or(false) {
+            return None
+        }
+        let now = SystemTime::now();
+        let date = header(headers, "date").and_then( | d | parse_http_date(&d)).unwrap_or(now);
+        let lifetime = if let Some(max_age) = max_age {
+            max_age
+        }
+        else if let Some(expires) = header(headers, "expires") {
+            // an expires we can't read means already expired
+            parse_http_date(&expires).and_then( | e | e.duration_since(date).ok()).map( | d | d.as_secs()).unwrap_or(0)
+        }
+        else if let Some(modified) = header(headers, "last-modified").and_then( | m | parse_http_date(&m)) {
+            (date.duration_since(modified).map( | d | d.as_secs()).unwrap_or(0) / 10).min(MAX_HEURISTIC_FRESHNESS)
+        }
+        else {
+            0
+        };
+        let age = header(headers, "age").and_then( | a | delta_seconds(a.trim())).unwrap_or(0);
+        Some(CachePolicy {
+            fresh_for: lifetime.saturating_sub(age),
+            no_cache,
+        })
+    }
+}
+
+// headers come in whatever case the backend and server used
+fn header(headers: &BTreeMap<String, Vec<String>>, name: &str) -> Option<String> {
+    headers.iter().find( | (k, _) | k.trim().eq_ignore_ascii_case(name)).map( | (_, v) | v.join(",").trim().to_string())
+}
+
+// a number of seconds, where anything too big is MAX_DELTA_SECONDS
+fn delta_seconds(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all( | b | b.is_ascii_digit()) {
+        return None
+    }
+    Some(value.parse::<u64>().unwrap_or(MAX_DELTA_SECONDS).min(MAX_DELTA_SECONDS))
+}
+
+fn cache_key(url: &str) -> &str {
+    url.split_once('#').map( | (url, _) | url).unwrap_or(url)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map( | d | d.as_secs()).unwrap_or(0)
+}
+
+impl Cx {
+    /// Turns on the http cache in the app data directory, or the user's
+    /// cache directory where the platform has no app data directory.
+    /// Returns false when there is nowhere to put it
+    pub fn enable_http_cache(&mut self, max_size: u64) -> bool {
+        let Some(dir) = self.default_http_cache_dir() else {
+            return false
+        };
+        match HttpCache::new(dir, max_size) {
+            Ok(cache) => {
+                self.http_cache = Some(cache);
+                true
+            }
+            Err(e) => {
+                crate::error!("Cannot open http cache: {}", e);
+                false
+            }
+        }
+    }
+
+    pub fn set_http_cache(&mut self, cache: Option<HttpCache>) {
+        self.http_cache = cache;
+    }
+
+    pub fn http_cache(&mut self) -> Option<&mut HttpCache> {
+        self.http_cache.as_mut()
+    }
+
+    fn default_http_cache_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = self.get_data_dir() {
+            return Some(Path::new(&dir).join("http_cache"))
+        }
+        let env = | name | std::env::var_os(name).filter( | v | !v.is_empty()).map(PathBuf::from);
+        let base = if cfg!(target_os = "windows") {
+            env("LOCALAPPDATA")
+        }
+        else if cfg!(target_os = "macos") {
+            env("HOME").map( | home | home.join("Library/Caches"))
+        }
+        else if cfg!(target_arch = "wasm32") {
+            None
+        }
+        else {
+            env("XDG_CACHE_HOME").or_else( || env("HOME").map( | home | home.join(".cache")))
+        }?;
+        let app = std::env::current_exe().ok()?.file_stem()?.to_string_lossy().to_string();
+        Some(base.join(app).join("http_cache"))
+    }
+
+    pub(crate) fn push_http_request(&mut self, request_id: LiveId, request: HttpRequest) {
+        let request = match &mut self.http_cache {
+            Some(cache) => cache.handle_request(request_id, request),
+            None => Some(request)
+        };
+        if let Some(request) = request.and_then( | request | self.mock_http_request(request_id, request)) {
+            self.platform_ops.push(CxOsOp::HttpRequest {request_id, request});
+        }
+    }
+
+    pub(crate) fn cancel_http_cache_request(&mut self, request_id: LiveId) {
+        if let Some(cache) = &mut self.http_cache {
+            cache.pending.remove(&request_id);
+            cache.reading.remove(&request_id);
+        }
+    }
+
+    pub(crate) fn handle_http_cache_responses(&mut self, items: Vec<NetworkResponseItem>) -> Vec<NetworkResponseItem> {
+        match &mut self.http_cache {
+            Some(cache) if !cache.pending.is_empty() => items.into_iter().filter_map( | item | cache.handle_response(item)).collect(),
+            _ => items
+        }
+    }
+
+    /// Delivers the responses the cache read from disk
+    pub(crate) fn handle_http_cache_signals(&mut self) {
+        let Some(cache) = &mut self.http_cache else {
+            return
+        };
+        let mut items = Vec::new();
+        let mut resend = Vec::new();
+        let read: Vec<NetworkResponseItem> = cache.read_receiver.try_iter().collect();
+        for item in read {
+            match (cache.reading.remove(&item.request_id), &item.response) {
+                // cancelled while it was being read
+                (None, _) => (),
+                // a fresh hit whose file is gone, ask the server after all
+                (Some(Some(request)), NetworkResponse::HttpRequestError(_)) => {
+                    cache.remove(&request.url);
+                    resend.push((item.request_id, request));
+                }
+                _ => items.push(item)
+            }
+        }
+        for (request_id, request) in resend {
+            self.push_http_request(request_id, request);
+        }
+        if !items.is_empty() {
+            self.handle_network_responses(items);
+        }
+    }
+
+    /// Waits for the disk work of the http cache, and delivers the responses
+    /// it read
+    pub fn flush_http_cache(&mut self) {
+        if let Some(cache) = &mut self.http_cache {
+            cache.flush();
+        }
+        self.handle_http_cache_signals();
+    }
+}
@@ -50,8 +50,14 @@
 
 pub mod web_socket;
 
+pub mod http_cache;
+
+pub mod network_mock;
+
 pub mod audio_stream;
 
+pub mod audio_resample;
+
 pub mod file_dialogs;
 
 mod media_api;
@@ -163,9 +169,12 @@
         },
         midi::*,
         audio::*,
+        audio_resample::*,
         thread::*,
         video::*,
         web_socket::{WebSocket,WebSocketMessage},
+        http_cache::HttpCache,
+        network_mock::{NetworkMock, MockReply, MockRoute, MockRequest, MockWebSocket},
         event::{
             VirtualKeyboardEvent,
             HttpRequest,
@@ -175,6 +184,9 @@
             HttpError,
             NetworkResponse,
             NetworkResponsesEvent,
+            SseEvent,
+            SseParser,
+            NdJsonParser,
             Margin,
             KeyCode,
             Event,
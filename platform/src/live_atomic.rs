@@ -19,11 +19,22 @@ pub trait LiveAtomicValue {
 
 pub trait LiveAtomic {
     fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[LiveNode]) -> usize;
+
+    /// Sets the value at the field `path` without a Cx, so an audio thread can
+    /// do it. Returns false when the path or the value don't fit
+    fn set_atomic_path(&self, _path: &[LiveId], _value: &LiveValue) -> bool {
+        false
+    }
 }
 
 pub trait LiveAtomicU32Enum {
     fn as_u32(&self) -> u32;
     fn from_u32(val: u32) -> Self;
+
+    /// The variant with this name
+    fn from_live_id(_id: LiveId) -> Option<Self> where Self: Sized {
+        None
+    }
 }
 
 // Atomic u32 enum template
@@ -62,6 +73,13 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.set(value);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        match value {
+            LiveValue::BareEnum(id) if path.is_empty() => T::from_live_id(*id).map( | value | self.set(value)).is_some(),
+            _ => false
+        }
+    }
 }
 
 impl<T> LiveHook for U32A<T> where T: LiveApply + LiveNew + 'static +  LiveAtomicU32Enum {}
@@ -155,6 +173,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.set(val);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_float().map( | value | self.set(value as f32)).is_some()
+    }
 }
 
 impl LiveHook for f32a {}
@@ -220,6 +242,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.set(val);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_float().map( | value | self.set(value)).is_some()
+    }
 }
 
 impl LiveHook for f64a {}
@@ -287,6 +313,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.0.store(val, Ordering::Relaxed);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_int().map( | value | self.set(value.max(0) as u32)).is_some()
+    }
 }
 
 impl LiveHook for u32a {}
@@ -352,6 +382,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.0.store(val, Ordering::Relaxed);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_int().map( | value | self.set(value)).is_some()
+    }
 }
 
 impl<T, const N:usize> LiveAtomic for [T;N]  where T: LiveAtomic {
@@ -449,6 +483,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.0.store(val, Ordering::Relaxed);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_int().map( | value | self.set(value as i32)).is_some()
+    }
 }
 
 impl LiveHook for i32a {}
@@ -519,6 +557,10 @@ fn apply_atomic(&self, cx: &mut Cx, apply: &mut Apply, index: usize, nodes: &[Li
         self.0.store(val, Ordering::Relaxed);
         index
     }
+
+    fn set_atomic_path(&self, path: &[LiveId], value: &LiveValue) -> bool {
+        path.is_empty() && value.as_bool().map( | value | self.set(value)).is_some()
+    }
 }
 
 impl LiveHook for boola {}
@@ -251,33 +251,41 @@ pub fn handle_live_edit(&mut self)->bool{
         // lets poll our studio connection
         let mut all_changes:Vec<LiveFileChange> = Vec::new();
         let mut actions = Vec::new();
+        let mut msgs = std::mem::take(&mut self.script_data.deferred_studio_messages);
         if let Some(studio_socket) = &mut self.studio_web_socket{
             while let Ok(msg) = studio_socket.try_recv(){
                 match msg {
                     WebSocketMessage::Binary(bin)=>{
                         if let Ok(data) = StudioToAppVec::deserialize_bin(&bin){
-                            for data in data.0{
-                                match data{
-                                    StudioToApp::LiveChange{file_name, content}=>{
-                                        all_changes.retain(|v| v.file_name != file_name); 
-                                        all_changes.push(LiveFileChange{file_name, content})
-                                    }
-                                    StudioToApp::Screenshot(req)=>{
-                                        self.screenshot_requests.push(req);
-                                    }
-                                    StudioToApp::KeepAlive=>{}
-                                    x=>{
-                                        actions.push(x);
-                                    }
-                                }
-                                
-                            }
+                            msgs.extend(data.0);
                         }
                     }
                     _=>()
                 }
             }
         }
+        for data in msgs{
+            match data{
+                StudioToApp::LiveChange{file_name, content}=>{
+                    all_changes.retain(|v| v.file_name != file_name); 
+                    all_changes.push(LiveFileChange{file_name, content})
+                }
+                StudioToApp::Screenshot(req)=>{
+                    self.screenshot_requests.push(req);
+                }
+                StudioToApp::KeepAlive=>{}
+                StudioToApp::ScriptDebugAttach{..} |
+                StudioToApp::ScriptDebugBreakpoints{..} |
+                StudioToApp::ScriptDebugPause |
+                StudioToApp::ScriptDebugStep(_) |
+                StudioToApp::ScriptDebugDetach=>{
+                    self.handle_script_debug(data);
+                }
+                x=>{
+                    actions.push(x);
+                }
+            }
+        }
         for action in actions{
             self.action(action);
         }
@@ -293,6 +301,9 @@ pub fn handle_live_edit(&mut self)->bool{
             }
             all_changes.extend(changes);
         }
+        // apps get a LiveEdit event when script modules changed on disk
+        let changed:Vec<String> = all_changes.iter().map(|c| c.file_name.clone()).collect();
+        let reloaded_scripts = !self.reload_script_modules(&changed).is_empty();
         let mut live_registry = self.live_registry.borrow_mut();
         if all_changes.len()>0{
             let mut errs = Vec::new();
@@ -324,7 +335,7 @@ pub fn handle_live_edit(&mut self)->bool{
             true
         }
         else{
-            false
+            reloaded_scripts
         }
     }
     
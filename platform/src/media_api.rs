@@ -1,5 +1,6 @@
 use crate::{
     audio::{AudioDeviceId, AudioInfo, AudioBuffer,AudioInputFn,AudioOutputFn},
+    audio_resample::{ResampleQuality, resampled_audio_output, resampled_audio_input},
     video::*,
     midi::*,
 };
@@ -22,6 +23,15 @@ fn audio_input<F>(&mut self, index:usize, f: F) where F: FnMut(AudioInfo, &Audio
         self.audio_input_box(index, Box::new(f))
     }
     
+    /// Like `audio_output` but the callback always renders at `sample_rate`,
+    /// the device side is converted when the device runs at another rate
+    fn audio_output_at_rate<F>(&mut self, index:usize, sample_rate:f64, quality:ResampleQuality, f: F) where F: FnMut(AudioInfo, &mut AudioBuffer) + Send  + 'static{
+        self.audio_output_box(index, resampled_audio_output(sample_rate, quality, Box::new(f)))
+    }
+    fn audio_input_at_rate<F>(&mut self, index:usize, sample_rate:f64, quality:ResampleQuality, f: F) where F: FnMut(AudioInfo, &AudioBuffer) + Send  + 'static{
+        self.audio_input_box(index, resampled_audio_input(sample_rate, quality, Box::new(f)))
+    }
+    
     fn audio_output_box(&mut self, index:usize, f: AudioOutputFn);
     fn audio_input_box(&mut self, index:usize, f: AudioInputFn);
 
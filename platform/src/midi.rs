@@ -1,6 +1,7 @@
 use {
     crate::{
         os::{OsMidiOutput,OsMidiInput},
+        audio::AudioTime,
         makepad_live_id::{LiveId, FromLiveId},
     }
 };
@@ -60,6 +61,13 @@ impl MidiInput {
     pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         self.0.as_mut().unwrap().receive()
     }
+    
+    /// Like `receive`, with when the message arrived on the clock of the audio
+    /// device callbacks where the backend has one, JACK on Linux. Its
+    /// `sample_time` is comparable to the one in `AudioInfo::time`
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<AudioTime>)> {
+        self.0.as_mut().unwrap().receive_timed()
+    }
 }
 
 #[derive(Default)]
@@ -71,6 +79,14 @@ pub fn send(&self, port: Option<MidiPortId>, data: MidiData) {
         let output = self.0.as_ref().unwrap();
         output.send(port, data);
     } 
+    
+    /// Like `send`, at `time` on the clock of the audio device callbacks where
+    /// the backend has one, JACK on Linux, the others send right away. Late
+    /// messages go out as soon as they can
+    pub fn send_timed(&self, port: Option<MidiPortId>, data: MidiData, time: Option<AudioTime>) {
+        let output = self.0.as_ref().unwrap();
+        output.send_timed(port, data, time);
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)] 
@@ -0,0 +1,485 @@
+use crate::{
+    cx::Cx,
+    event::network::*,
+    thread::SignalToUI,
+    web_socket::WebSocketMessage,
+    makepad_live_id::LiveId,
+};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{Arc, Mutex, mpsc::{channel, Receiver, Sender}},
+    time::{Duration, Instant},
+};
+
+/// What a mocked request answers with
+#[derive(Clone, Debug)]
+pub enum MockReply {
+    Response(HttpResponse),
+    /// Delivered as separate `HttpStreamResponse`s to streaming requests,
+    /// and as one body to the others
+    Stream {status_code: u16, headers: BTreeMap<String, Vec<String>>, chunks: Vec<Vec<u8>>},
+    Error(String),
+}
+
+impl MockReply {
+    pub fn bytes(status_code: u16, body: Vec<u8>) -> Self {
+        MockReply::Response(HttpResponse {
+            metadata_id: LiveId(0),
+            status_code,
+            headers: BTreeMap::new(),
+            body: Some(body),
+        })
+    }
+
+    pub fn text(status_code: u16, body: &str) -> Self {
+        Self::bytes(status_code, body.as_bytes().to_vec()).with_header("Content-Type", "text/plain")
+    }
+
+    pub fn json(status_code: u16, body: &str) -> Self {
+        Self::bytes(status_code, body.as_bytes().to_vec()).with_header("Content-Type", "application/json")
+    }
+
+    pub fn stream(status_code: u16, chunks: Vec<Vec<u8>>) -> Self {
+        MockReply::Stream {status_code, headers: BTreeMap::new(), chunks}
+    }
+
+    pub fn error(message: &str) -> Self {
+        MockReply::Error(message.to_string())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        match &mut self {
+            MockReply::Response(HttpResponse {headers, ..}) | MockReply::Stream {headers, ..} => {
+                headers.entry(name.to_string()).or_default().push(value.to_string());
+            }
+            MockReply::Error(_) => ()
+        }
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct MockRoute {
+    method: Option<HttpMethod>,
+    url: String,
+    reply: MockReply,
+    latency: f64,
+    once: bool,
+}
+
+impl MockRoute {
+    /// Seconds before the reply arrives
+    pub fn with_latency(&mut self, latency: f64) -> &mut Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Answer only the first matching request, later ones fall through to
+    /// the next route. Use it to script a sequence of answers
+    pub fn once(&mut self) -> &mut Self {
+        self.once = true;
+        self
+    }
+
+    // a trailing * matches any rest of the url
+    fn matches(&self, request: &HttpRequest) -> bool {
+        if self.method.as_ref().is_some_and( | m | *m != request.method) {
+            return false
+        }
+        match self.url.strip_suffix('*') {
+            Some(prefix) => request.url.starts_with(prefix),
+            None => request.url == self.url
+        }
+    }
+}
+
+/// A request the app made while the mock was installed
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub request_id: LiveId,
+    pub request: HttpRequest,
+}
+
+#[derive(Debug)]
+enum MockWebSocketStep {
+    Send(WebSocketMessage),
+    Wait(f64),
+    Expect,
+}
+
+/// What a mocked websocket server does, one step after another. The
+/// socket is `Opened` before the first step
+#[derive(Debug)]
+pub struct MockWebSocket {
+    steps: Vec<MockWebSocketStep>,
+    refused: bool,
+}
+
+impl Default for MockWebSocket {
+    fn default() -> Self {
+        Self {steps: Vec::new(), refused: false}
+    }
+}
+
+impl MockWebSocket {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The server can't be reached, the socket never opens
+    pub fn refuse(message: &str) -> Self {
+        MockWebSocket {steps: Vec::new(), refused: true}.fail(message)
+    }
+
+    pub fn send(mut self, message: WebSocketMessage) -> Self {
+        self.steps.push(MockWebSocketStep::Send(message));
+        self
+    }
+
+    pub fn send_string(self, data: &str) -> Self {
+        self.send(WebSocketMessage::String(data.to_string()))
+    }
+
+    pub fn send_binary(self, data: Vec<u8>) -> Self {
+        self.send(WebSocketMessage::Binary(data))
+    }
+
+    /// Waits for the app to send a message before going on
+    pub fn expect_message(mut self) -> Self {
+        self.steps.push(MockWebSocketStep::Expect);
+        self
+    }
+
+    pub fn wait(mut self, seconds: f64) -> Self {
+        self.steps.push(MockWebSocketStep::Wait(seconds));
+        self
+    }
+
+    /// The server closes the socket
+    pub fn close(self, code: u16, reason: &str) -> Self {
+        self.send(WebSocketMessage::Close {code, reason: reason.to_string()}).send(WebSocketMessage::Closed)
+    }
+
+    /// The connection drops
+    pub fn fail(self, message: &str) -> Self {
+        self.send(WebSocketMessage::Error(message.to_string())).send(WebSocketMessage::Closed)
+    }
+}
+
+#[derive(Default)]
+struct MockWebSockets {
+    routes: Vec<(String, MockWebSocket)>,
+    sent: Vec<(String, WebSocketMessage)>,
+}
+
+thread_local! {
+    // websockets are opened without a Cx, so the mock for them lives with the thread that installed it
+    static MOCK_WEB_SOCKETS: RefCell<Option<Arc<Mutex<MockWebSockets>>>> = const {RefCell::new(None)};
+}
+
+/// The app side of a mocked websocket
+pub(crate) struct MockWebSocketLink {
+    url: String,
+    sender: Sender<WebSocketMessage>,
+    mock: Arc<Mutex<MockWebSockets>>,
+}
+
+impl MockWebSocketLink {
+    pub(crate) fn send(&self, message: WebSocketMessage) -> Result<(), ()> {
+        let copy = match &message {
+            WebSocketMessage::Binary(data) => WebSocketMessage::Binary(data.clone()),
+            WebSocketMessage::String(data) => WebSocketMessage::String(data.clone()),
+            _ => return Err(())
+        };
+        self.mock.lock().unwrap().sent.push((self.url.clone(), copy));
+        self.sender.send(message).map_err( | _ | ())
+    }
+}
+
+/// Opens a scripted websocket when a mock is installed on this thread
+pub(crate) fn open_mock_web_socket(request: &HttpRequest) -> Option<(MockWebSocketLink, Receiver<WebSocketMessage>)> {
+    let mock = MOCK_WEB_SOCKETS.with( | m | m.borrow().clone())?;
+    let script = {
+        let mut sockets = mock.lock().unwrap();
+        let index = sockets.routes.iter().position( | (url, _) | match url.strip_suffix('*') {
+            Some(prefix) => request.url.starts_with(prefix),
+            None => request.url == *url
+        });
+        match index {
+            Some(index) => sockets.routes.remove(index).1,
+            None => MockWebSocket::refuse(&format!("No mock for websocket {}", request.url))
+        }
+    };
+    let (rx_sender, rx_receiver) = channel();
+    let (sender, app_receiver) = channel();
+    std::thread::spawn(move || {
+        if !script.refused {
+            let _ = rx_sender.send(WebSocketMessage::Opened);
+            SignalToUI::set_ui_signal();
+        }
+        for step in script.steps {
+            match step {
+                MockWebSocketStep::Send(message) => {
+                    if rx_sender.send(message).is_err() {
+                        return
+                    }
+                    SignalToUI::set_ui_signal();
+                }
+                MockWebSocketStep::Wait(seconds) => {
+                    std::thread::sleep(Duration::from_secs_f64(seconds));
+                }
+                MockWebSocketStep::Expect => {
+                    if app_receiver.recv().is_err() {
+                        return
+                    }
+                }
+            }
+        }
+        // stay connected until the app lets go
+        while app_receiver.recv().is_ok() {}
+    });
+    Some((MockWebSocketLink {url: request.url.clone(), sender, mock}, rx_receiver))
+}
+
+struct MockDelivery {
+    due: Instant,
+    item: NetworkResponseItem,
+}
+
+/// Stands in for the network in tests. Installed with `Cx::set_network_mock`
+/// it answers `Cx::http_request`s from its routes and `WebSocket::open`s
+/// from its scripts, and remembers what the app sent. Responses are
+/// delivered by `Cx::deliver_network_mock` or `Cx::settle_network_mock`,
+/// or by the event loop when there is one
+#[derive(Default)]
+pub struct NetworkMock {
+    routes: Vec<MockRoute>,
+    requests: Vec<MockRequest>,
+    pending: Vec<MockDelivery>,
+    passthrough: bool,
+    web_sockets: Arc<Mutex<MockWebSockets>>,
+}
+
+impl NetworkMock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests no route matches go out to the real network, instead of failing
+    pub fn with_passthrough(mut self) -> Self {
+        self.passthrough = true;
+        self
+    }
+
+    /// Answers requests for `url` with `reply`. `method` None matches any
+    /// method, a `*` at the end of `url` matches any rest. The first route
+    /// added wins
+    pub fn route(&mut self, method: Option<HttpMethod>, url: &str, reply: MockReply) -> &mut MockRoute {
+        self.routes.push(MockRoute {
+            method,
+            url: url.to_string(),
+            reply,
+            latency: 0.0,
+            once: false,
+        });
+        self.routes.last_mut().unwrap()
+    }
+
+    pub fn get(&mut self, url: &str, reply: MockReply) -> &mut MockRoute {
+        self.route(Some(HttpMethod::GET), url, reply)
+    }
+
+    pub fn post(&mut self, url: &str, reply: MockReply) -> &mut MockRoute {
+        self.route(Some(HttpMethod::POST), url, reply)
+    }
+
+    /// Scripts the next websocket opened to `url`
+    pub fn web_socket(&mut self, url: &str, script: MockWebSocket) {
+        self.web_sockets.lock().unwrap().routes.push((url.to_string(), script));
+    }
+
+    pub fn requests(&self) -> &[MockRequest] {
+        &self.requests
+    }
+
+    pub fn take_requests(&mut self) -> Vec<MockRequest> {
+        std::mem::take(&mut self.requests)
+    }
+
+    /// The messages the app sent on mocked websockets, with their url
+    pub fn take_web_socket_messages(&mut self) -> Vec<(String, WebSocketMessage)> {
+        std::mem::take(&mut self.web_sockets.lock().unwrap().sent)
+    }
+
+    /// Whether responses are still waiting out their latency
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // None when the mock answers, the request when it should go out
+    fn handle_request(&mut self, request_id: LiveId, request: HttpRequest) -> Option<HttpRequest> {
+        self.requests.push(MockRequest {request_id, request: request.clone()});
+        let Some(index) = self.routes.iter().position( | r | r.matches(&request)) else {
+            if self.passthrough {
+                return Some(request)
+            }
+            self.push(request_id, 0.0, NetworkResponse::HttpRequestError(HttpError {
+                message: format!("No mock for {:?} {}", request.method, request.url),
+                metadata_id: request.metadata_id,
+            }));
+            return None
+        };
+        let route = &self.routes[index];
+        let (reply, latency) = (route.reply.clone(), route.latency);
+        if route.once {
+            self.routes.remove(index);
+        }
+        let metadata_id = request.metadata_id;
+        match reply {
+            MockReply::Response(mut res) => {
+                res.metadata_id = metadata_id;
+                if request.is_streaming {
+                    let (status_code, headers) = (res.status_code, res.headers.clone());
+                    self.push(request_id, latency, NetworkResponse::HttpStreamResponse(res));
+                    self.push(request_id, latency, NetworkResponse::HttpStreamComplete(HttpResponse {
+                        metadata_id,
+                        status_code,
+                        headers,
+                        body: None,
+                    }));
+                }
+                else {
+                    self.push(request_id, latency, NetworkResponse::HttpResponse(res));
+                }
+            }
+            MockReply::Stream {status_code, headers, chunks} => {
+                if request.is_streaming {
+                    for chunk in chunks {
+                        self.push(request_id, latency, NetworkResponse::HttpStreamResponse(HttpResponse {
+                            metadata_id,
+                            status_code,
+                            headers: headers.clone(),
+                            body: Some(chunk),
+                        }));
+                    }
+                    self.push(request_id, latency, NetworkResponse::HttpStreamComplete(HttpResponse {
+                        metadata_id,
+                        status_code,
+                        headers,
+                        body: None,
+                    }));
+                }
+                else {
+                    self.push(request_id, latency, NetworkResponse::HttpResponse(HttpResponse {
+                        metadata_id,
+                        status_code,
+                        headers,
+                        body: Some(chunks.concat()),
+                    }));
+                }
+            }
+            MockReply::Error(message) => {
+                self.push(request_id, latency, NetworkResponse::HttpRequestError(HttpError {message, metadata_id}));
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, request_id: LiveId, latency: f64, response: NetworkResponse) {
+        let latency = Duration::from_secs_f64(latency.max(0.0));
+        self.pending.push(MockDelivery {
+            due: Instant::now() + latency,
+            item: NetworkResponseItem {request_id, response},
+        });
+        // wake up the event loop when the reply is due
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if latency.is_zero() {
+                SignalToUI::set_ui_signal();
+            }
+            else {
+                std::thread::spawn(move || {
+                    std::thread::sleep(latency);
+                    SignalToUI::set_ui_signal();
+                });
+            }
+        }
+    }
+
+    fn take_due(&mut self, now: Instant) -> Vec<NetworkResponseItem> {
+        let mut due = Vec::new();
+        let mut i = 0;
+        // keeps the order replies were pushed in
+        while i < self.pending.len() {
+            if self.pending[i].due <= now {
+                due.push(self.pending.remove(i).item);
+            }
+            else {
+                i += 1;
+            }
+        }
+        due
+    }
+}
+
+impl Cx {
+    /// Puts a mock in front of the network, or takes it away with None.
+    /// Websocket scripts apply to sockets opened on this thread
+    pub fn set_network_mock(&mut self, mock: Option<NetworkMock>) {
+        MOCK_WEB_SOCKETS.with( | m | *m.borrow_mut() = mock.as_ref().map( | mock | mock.web_sockets.clone()));
+        self.network_mock = mock;
+    }
+
+    pub fn network_mock(&mut self) -> Option<&mut NetworkMock> {
+        self.network_mock.as_mut()
+    }
+
+    /// Hands the replies that are due to the app, returns how many
+    pub fn deliver_network_mock(&mut self) -> usize {
+        let Some(mock) = &mut self.network_mock else {
+            return 0
+        };
+        if mock.pending.is_empty() {
+            return 0
+        }
+        let items = mock.take_due(Instant::now());
+        let count = items.len();
+        if count > 0 {
+            self.handle_network_responses(items);
+        }
+        count
+    }
+
+    /// Waits out the latency of all replies and delivers them, including
+    /// any the app asks for while handling them
+    pub fn settle_network_mock(&mut self) {
+        loop {
+            let Some(mock) = &self.network_mock else {
+                return
+            };
+            let Some(next) = mock.pending.iter().map( | d | d.due).min() else {
+                return
+            };
+            let now = Instant::now();
+            if next > now {
+                std::thread::sleep(next - now);
+            }
+            self.deliver_network_mock();
+        }
+    }
+
+    // None when the mock took the request
+    pub(crate) fn mock_http_request(&mut self, request_id: LiveId, request: HttpRequest) -> Option<HttpRequest> {
+        match &mut self.network_mock {
+            Some(mock) => mock.handle_request(request_id, request),
+            None => Some(request)
+        }
+    }
+
+    pub(crate) fn cancel_mock_http_request(&mut self, request_id: LiveId) {
+        if let Some(mock) = &mut self.network_mock {
+            mock.pending.retain( | d | d.item.request_id != request_id);
+        }
+    }
+}
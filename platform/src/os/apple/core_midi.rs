@@ -23,6 +23,11 @@ pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         }
         None
     }
+    
+    // the messages here don't come with a time on an audio clock
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<crate::audio::AudioTime>)> {
+        self.receive().map( | (port_id, data) | (port_id, data, None))
+    }
 }
 
 impl OsMidiOutput {
@@ -48,6 +53,10 @@ pub fn send(&self, port_id: Option<MidiPortId>, d: MidiData) {
             }
         }
     }
+    
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, _time: Option<crate::audio::AudioTime>) {
+        self.send(port_id, data)
+    }
 }
 
 impl CoreMidiPort {
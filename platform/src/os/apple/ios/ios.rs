@@ -117,8 +117,7 @@ pub(crate) fn handle_networking_events(&mut self) {
             out.push(item);
         }
         if out.len()>0{
-            self.handle_script_network_events(&out);
-            self.call_event_handler(& Event::NetworkResponses(out))
+            self.handle_network_responses(out);
         }
     }
 
@@ -153,6 +152,7 @@ fn ios_event_callback(
                     // check signals
                     if SignalToUI::check_and_clear_ui_signal(){
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
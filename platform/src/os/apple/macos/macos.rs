@@ -197,8 +197,7 @@ pub fn event_loop(cx: Rc<RefCell<Cx >>) {
             out.push(item);
         }
         if out.len()>0 {
-            self.handle_script_network_events(&out);
-            self.call_event_handler(&Event::NetworkResponses(out))
+            self.handle_network_responses(out);
         }
     }
 
@@ -256,6 +255,7 @@ fn cocoa_event_callback(
                     // check signals
                     if SignalToUI::check_and_clear_ui_signal() {
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                         needs_timer = true;
@@ -275,6 +275,7 @@ pub fn stdin_event_loop(&mut self, metal_cx: &mut MetalCx) {
                     }
                     if SignalToUI::check_and_clear_ui_signal() {
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
@@ -98,8 +98,7 @@ pub(crate) fn handle_networking_events(&mut self) {
             out.push(item);
         }
         if out.len()>0{
-            self.handle_script_network_events(&out);
-            self.call_event_handler(&Event::NetworkResponses(out))
+            self.handle_network_responses(out);
         }
     }
     
@@ -119,6 +118,7 @@ fn tvos_event_callback(
                 if te.timer_id == 0 {
                    if SignalToUI::check_and_clear_ui_signal(){
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
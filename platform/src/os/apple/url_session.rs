@@ -62,6 +62,16 @@ struct UrlSessionDataDelegateContext{
     metadata_id: LiveId
 }
 
+// the http status of a streaming task, its response is there from the first data on
+unsafe fn task_status_code(task: ObjcId) -> u16 {
+    let response: ObjcId = msg_send![task, response];
+    if response == nil {
+        return 0
+    }
+    let status_code: isize = msg_send![response, statusCode];
+    status_code as u16
+}
+
 pub fn define_url_session_data_delegate() -> *const Class {
     extern "C" fn did_receive_response(
         _this: &Object,
@@ -84,7 +94,7 @@ extern "C" fn did_receive_data(
         this: &Object,
         _: Sel,
         _session: ObjcId,
-        _data_task: ObjcId,
+        data_task: ObjcId,
         data: ObjcId,
     ) {
         unsafe {
@@ -102,7 +112,7 @@ extern "C" fn did_receive_data(
                 response: NetworkResponse::HttpStreamResponse(HttpResponse{
                     headers: Default::default(),
                     metadata_id: context_box.metadata_id,
-                    status_code: 0,
+                    status_code: task_status_code(data_task),
                     body:Some(data_bytes.to_vec())
                 }),
             };
@@ -117,7 +127,7 @@ extern "C" fn did_complete_with_error(
         this: &Object,
         _: Sel,
         _session: ObjcId,
-        _task: ObjcId,
+        task: ObjcId,
         error: ObjcId,
     ) {
         unsafe {
@@ -143,7 +153,7 @@ extern "C" fn did_complete_with_error(
                     response: NetworkResponse::HttpStreamComplete(HttpResponse{
                         headers: Default::default(),
                         metadata_id: context_box.metadata_id,
-                        status_code: 0,
+                        status_code: task_status_code(task),
                         body:None
                     }),
                 };
@@ -191,7 +191,17 @@ pub fn handle_actions(&mut self) {
         }
     }
     
+    pub (crate) fn handle_network_signals(&mut self) {
+        self.handle_http_cache_signals();
+        self.deliver_network_mock();
+    }
+
     pub (crate) fn call_event_handler(&mut self, event: &Event) {
+        if let Event::Timer(te) = event {
+            if self.handle_http_stream_timer(te) {
+                return
+            }
+        }
         self.inner_call_event_handler(event);
         self.inner_key_focus_change();
         self.handle_triggers();
@@ -21,6 +21,7 @@ struct AlsaAudioDevice {
     device_handle: *mut snd_pcm_t,
     channel_count: usize,
     frame_count: usize,
+    sample_rate: f64,
     interleaved: Vec<f32>,
     _buffer_size: usize,
 }
@@ -216,7 +217,7 @@ pub fn use_audio_inputs(&mut self, devices: &[AudioDeviceId]) {
                                 AudioInfo {
                                     device_id,
                                     time: None,
-                                    sample_rate: 48000.0,
+                                    sample_rate: device.sample_rate,
                                 },
                                 &audio_buffer
                             );
@@ -276,7 +277,7 @@ pub fn use_audio_outputs(&mut self, devices: &[AudioDeviceId]) {
                                 AudioInfo {
                                     device_id,
                                     time: None,
-                                    sample_rate: 48000.0,
+                                    sample_rate: device.sample_rate,
                                 },
                                 &mut audio_buffer
                             );
@@ -315,6 +316,9 @@ fn new(device_name: &str, device_id: AudioDeviceId, direction: snd_pcm_stream_t)
             alsa_error!(snd_pcm_hw_params_any(handle, hw_params)) ?;
             alsa_error!(snd_pcm_hw_params_set_access(handle, hw_params, SND_PCM_ACCESS_RW_INTERLEAVED)) ?;
             alsa_error!(snd_pcm_hw_params_set_format(handle, hw_params, SND_PCM_FORMAT_FLOAT_LE)) ?;
+            // run at a rate the hardware really has, audio at other rates goes
+            // through our own resampler which is better than the alsa-lib one
+            alsa_error!(snd_pcm_hw_params_set_rate_resample(handle, hw_params, 0)) ?;
             alsa_error!(snd_pcm_hw_params_set_rate_near(handle, hw_params, &mut rate, 0 as *mut _)) ?;
             alsa_error!(snd_pcm_hw_params_set_channels(handle, hw_params, 2)) ?;
             let mut periods = 2;
@@ -323,7 +327,6 @@ fn new(device_name: &str, device_id: AudioDeviceId, direction: snd_pcm_stream_t)
             let mut buffer_size = 512;
             alsa_error!(snd_pcm_hw_params_set_buffer_size_near(handle, hw_params, &mut buffer_size)) ?;
             alsa_error!(snd_pcm_hw_params(handle, hw_params)) ?;
-            alsa_error!(snd_pcm_hw_params_set_rate_resample(handle, hw_params, 1)) ?;
             let mut buffer_size = 0;
             alsa_error!(snd_pcm_hw_params_get_buffer_size(hw_params, &mut buffer_size)) ?;
             let mut channel_count = 0;
@@ -338,6 +341,7 @@ fn new(device_name: &str, device_id: AudioDeviceId, direction: snd_pcm_stream_t)
                 device_handle: handle,
                 channel_count: channel_count as usize,
                 frame_count: frame_count as usize,
+                sample_rate: rate as f64,
                 _buffer_size: buffer_size as usize,
             }, AlsaAudioDeviceRef {
                 device_id,
@@ -8,9 +8,11 @@
     super::{
         alsa_sys::*,
         alsa_audio::AlsaError,
+        jack::JackMidiOutput,
     },
     crate::{
         makepad_live_id::*,
+        audio::AudioTime,
         midi::*,
         thread::SignalToUI,
     }
@@ -18,35 +20,46 @@
  
 
 #[derive(Clone)]
-pub struct OsMidiOutput(pub (crate) Arc<Mutex<AlsaMidiAccess >>);
+pub struct OsMidiOutput(pub (crate) Arc<Mutex<AlsaMidiAccess >>, pub (crate) Option<JackMidiOutput>);
 
-pub struct OsMidiInput(mpsc::Receiver<(MidiPortId, MidiData) >);
+pub struct OsMidiInput(mpsc::Receiver<(MidiPortId, MidiData, Option<AudioTime>) >);
 
 impl OsMidiOutput {
     pub fn send(&self, port_id: Option<MidiPortId>, d: MidiData) {
-        // alright lets send some midi.
-        // send some midi here
-        let _ = self.0.lock().unwrap().send_midi(port_id, d);
+        self.send_timed(port_id, d, None);
+    }
+    
+    // ALSA sends right away, JACK at the frame of the time
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, d: MidiData, time: Option<AudioTime>) {
+        self.0.lock().unwrap().send_midi(port_id, d);
+        if let Some(jack) = &self.1 {
+            jack.send_timed(port_id, d, time);
+        }
     }
 }
 
 impl OsMidiInput {
     pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
-        if let Ok((port_id, data)) = self.0.try_recv() {
+        if let Ok((port_id, data, _)) = self.0.try_recv() {
             return Some((port_id, data))
         }
         None
     }
+    
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<AudioTime>)> {
+        self.0.try_recv().ok()
+    }
 }
 
-type InputSenders = Arc<Mutex<Vec<mpsc::Sender<(MidiPortId, MidiData) >> >>;
+// the JACK backend sends to the same inputs, with the time of the message
+pub type InputSenders = Arc<Mutex<Vec<mpsc::Sender<(MidiPortId, MidiData, Option<AudioTime>) >> >>;
 
 #[derive(Clone)]
 pub struct AlsaMidiOutput {
 }
 
 pub struct AlsaMidiAccess {
-    input_senders: InputSenders,
+    pub (crate) input_senders: InputSenders,
     //event_sender: mpsc::Sender<AlsaMidiEvent>,
     ports: Vec<AlsaMidiPort>,
     client: Result<AlsaClient, AlsaError>,
@@ -359,7 +372,7 @@ pub fn new(change_signal: SignalToUI) -> Arc<Mutex<Self >> {
                     ) {
                         let mut senders = input_senders.lock().unwrap();
                         senders.retain( | s | {
-                            s.send((port_id, msg)).is_ok()
+                            s.send((port_id, msg, None)).is_ok()
                         });
                         if senders.len()>0 {
                             // make sure our eventloop runs
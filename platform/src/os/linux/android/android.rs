@@ -346,9 +346,7 @@ pub(crate) fn handle_message(&mut self, msg: FromJavaMessage) {
                         ))
                     }
                 ];
-                self.handle_script_network_events(&out);
-                let e = Event::NetworkResponses(out);
-                self.call_event_handler(&e);
+                self.handle_network_responses(out);
             }
             FromJavaMessage::HttpRequestError {request_id, metadata_id, error, ..} => {
                 let out = vec![
@@ -360,9 +358,7 @@ pub(crate) fn handle_message(&mut self, msg: FromJavaMessage) {
                         })
                     }
                 ];
-                self.handle_script_network_events(&out);
-                let e = Event::NetworkResponses(out);
-                self.call_event_handler(&e);
+                self.handle_network_responses(out);
             }
             FromJavaMessage::WebSocketMessage {message, sender} => {
                 let ws_message_parser = self.os.websocket_parsers.entry(sender.0).or_insert_with(||  WebSocketImpl::new());
@@ -537,6 +533,7 @@ pub(crate) fn handle_other_events(&mut self) {
         // Signals
         if SignalToUI::check_and_clear_ui_signal() {
             self.handle_media_signals();
+            self.handle_network_signals();
             self.handle_script_signals();
             self.call_event_handler(&Event::Signal);
         }
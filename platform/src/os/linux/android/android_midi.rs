@@ -26,6 +26,10 @@ impl OsMidiOutput {
     pub fn send(&self, port_id: Option<MidiPortId>, data: MidiData) {
         self.amidi.lock().unwrap().send_midi(port_id, data);
     }
+    
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, _time: Option<crate::audio::AudioTime>) {
+        self.send(port_id, data)
+    }
 }
 
 pub struct OsMidiInput {
@@ -42,6 +46,11 @@ pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         }
         None
     }
+    
+    // the messages here don't come with a time on an audio clock
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<crate::audio::AudioTime>)> {
+        self.receive().map( | (port_id, data) | (port_id, data, None))
+    }
 }
 
 pub struct AndroidMidiOutput {
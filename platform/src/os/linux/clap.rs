@@ -0,0 +1,1191 @@
+use {
+    std::{
+        collections::HashMap,
+        ffi::{CStr, CString},
+        os::raw::{c_char, c_ulong, c_void},
+        path::{Path, PathBuf},
+        ptr,
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc, Mutex, OnceLock, Weak,
+        },
+        thread::{self, ThreadId},
+    },
+    self::super::clap_sys::*,
+    crate::{
+        audio::AudioBuffer,
+        midi::MidiData,
+        module_loader::ModuleLoader,
+        thread::SignalToUI,
+    },
+};
+
+// events we queue per block, more than this in one block are dropped
+const MAX_EVENTS: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClapError {
+    LoadFailed(String),
+    NoEntry,
+    IncompatibleVersion,
+    InitFailed,
+    NoFactory,
+    PluginNotFound(String),
+    CreateFailed,
+    ActivateFailed,
+    // a processor of the plugin is still alive, it has to go first
+    StillProcessing,
+    NoGui,
+    GuiFailed,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ClapPluginInfo {
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    pub description: String,
+    pub features: Vec<String>,
+    pub path: String,
+}
+
+impl ClapPluginInfo {
+    pub fn is_instrument(&self) -> bool {
+        self.features.iter().any( | f | f == "instrument")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClapParamInfo {
+    pub id: u32,
+    pub name: String,
+    pub module: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub flags: u32,
+}
+
+impl ClapParamInfo {
+    pub fn is_stepped(&self) -> bool {self.flags & CLAP_PARAM_IS_STEPPED != 0}
+    pub fn is_hidden(&self) -> bool {self.flags & CLAP_PARAM_IS_HIDDEN != 0}
+    pub fn is_read_only(&self) -> bool {self.flags & CLAP_PARAM_IS_READONLY != 0}
+    pub fn is_automatable(&self) -> bool {self.flags & CLAP_PARAM_IS_AUTOMATABLE != 0}
+}
+
+#[derive(Clone, Debug)]
+pub struct ClapAudioPortInfo {
+    pub id: u32,
+    pub name: String,
+    pub channel_count: usize,
+    pub is_main: bool,
+}
+
+/// Parameter changes that come from the plugin itself, its own gui for instance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClapParamEvent {
+    Value {id: u32, value: f64},
+    GestureBegin {id: u32},
+    GestureEnd {id: u32},
+}
+
+/// What the plugin asked the host for since the last `handle_main_thread`
+#[derive(Clone, Debug, Default)]
+pub struct ClapHostRequests {
+    // deactivate and activate again, for instance because the latency changed
+    pub restart: bool,
+    pub rescan_params: bool,
+    pub gui_resize: Option<(u32, u32)>,
+    pub gui_closed: bool,
+    // the state changed in a way the host didn't see, like an edit in the gui
+    pub state_changed: bool,
+}
+
+fn c_str(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new()
+    }
+    unsafe {CStr::from_ptr(ptr)}.to_string_lossy().into_owned()
+}
+
+fn c_array(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter().take_while( | c | **c != 0).map( | c | *c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A loaded `.clap` file. Bundles are shared, the library is initialised once
+/// however many plugins of it are running
+pub struct ClapBundle {
+    path: String,
+    entry: *const clap_plugin_entry,
+    factory: *const clap_plugin_factory,
+    _module: Option<ModuleLoader>,
+}
+
+unsafe impl Send for ClapBundle {}
+unsafe impl Sync for ClapBundle {}
+
+impl ClapBundle {
+    pub fn load(path: &str) -> Result<Arc<ClapBundle>, ClapError> {
+        static BUNDLES: OnceLock<Mutex<HashMap<String, Weak<ClapBundle>>>> = OnceLock::new();
+        let mut bundles = BUNDLES.get_or_init(Default::default).lock().unwrap();
+        if let Some(bundle) = bundles.get(path).and_then( | b | b.upgrade()) {
+            return Ok(bundle)
+        }
+        let module = ModuleLoader::load(path).map_err( | _ | ClapError::LoadFailed(path.to_string())) ?;
+        let entry: *const clap_plugin_entry = module.get_symbol("clap_entry").map_err( | _ | ClapError::NoEntry) ?;
+        let bundle = Self::init(path, entry, Some(module)) ?;
+        bundles.insert(path.to_string(), Arc::downgrade(&bundle));
+        Ok(bundle)
+    }
+
+    /// A plugin that is linked into the binary instead of loaded from a file,
+    /// `path` is only handed to its init
+    pub fn from_entry(path: &str, entry: &'static clap_plugin_entry) -> Result<Arc<ClapBundle>, ClapError> {
+        Self::init(path, entry, None)
+    }
+
+    fn init(path: &str, entry: *const clap_plugin_entry, module: Option<ModuleLoader>) -> Result<Arc<ClapBundle>, ClapError> {
+        let entry_ref = unsafe {&*entry};
+        if !entry_ref.clap_version.is_compatible() {
+            return Err(ClapError::IncompatibleVersion)
+        }
+        let c_path = CString::new(path).unwrap_or_default();
+        if !unsafe {(entry_ref.init)(c_path.as_ptr())} {
+            return Err(ClapError::InitFailed)
+        }
+        let factory = unsafe {(entry_ref.get_factory)(CLAP_PLUGIN_FACTORY_ID.as_ptr() as *const c_char)} as *const clap_plugin_factory;
+        if factory.is_null() {
+            unsafe {(entry_ref.deinit)()};
+            return Err(ClapError::NoFactory)
+        }
+        Ok(Arc::new(ClapBundle {
+            path: path.to_string(),
+            entry,
+            factory,
+            _module: module,
+        }))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn plugins(&self) -> Vec<ClapPluginInfo> {
+        let factory = unsafe {&*self.factory};
+        let count = unsafe {(factory.get_plugin_count)(self.factory)};
+        (0..count).filter_map( | index | {
+            let desc = unsafe {(factory.get_plugin_descriptor)(self.factory, index)};
+            (!desc.is_null()).then( || self.plugin_info(desc))
+        }).collect()
+    }
+
+    fn plugin_info(&self, desc: *const clap_plugin_descriptor) -> ClapPluginInfo {
+        let desc = unsafe {&*desc};
+        let mut features = Vec::new();
+        if !desc.features.is_null() {
+            let mut feature = desc.features;
+            while !unsafe {*feature}.is_null() {
+                features.push(c_str(unsafe {*feature}));
+                feature = unsafe {feature.add(1)};
+            }
+        }
+        ClapPluginInfo {
+            id: c_str(desc.id),
+            name: c_str(desc.name),
+            vendor: c_str(desc.vendor),
+            version: c_str(desc.version),
+            description: c_str(desc.description),
+            features,
+            path: self.path.clone(),
+        }
+    }
+
+    /// Creates and initialises a plugin, by id or by name
+    pub fn new_instance(self: &Arc<Self>, plugin: &str) -> Result<ClapInstance, ClapError> {
+        let info = self.plugins().into_iter().find( | p | p.id == plugin || p.name == plugin)
+            .ok_or_else( || ClapError::PluginNotFound(plugin.to_string())) ?;
+        let host = HostData::new();
+        let id = CString::new(info.id.clone()).unwrap_or_default();
+        let factory = unsafe {&*self.factory};
+        let plugin = unsafe {(factory.create_plugin)(self.factory, &host.host, id.as_ptr())};
+        if plugin.is_null() {
+            return Err(ClapError::CreateFailed)
+        }
+        if !unsafe {((*plugin).init)(plugin)} {
+            unsafe {((*plugin).destroy)(plugin)};
+            return Err(ClapError::InitFailed)
+        }
+        let extension = | id: &[u8] | unsafe {((*plugin).get_extension)(plugin, id.as_ptr() as *const c_char)};
+        Ok(ClapInstance {
+            shared: Arc::new(ClapShared {
+                params: extension(CLAP_EXT_PARAMS) as *const clap_plugin_params,
+                audio_ports: extension(CLAP_EXT_AUDIO_PORTS) as *const clap_plugin_audio_ports,
+                note_ports: extension(CLAP_EXT_NOTE_PORTS) as *const clap_plugin_note_ports,
+                gui: extension(CLAP_EXT_GUI) as *const clap_plugin_gui,
+                state: extension(CLAP_EXT_STATE) as *const clap_plugin_state,
+                _bundle: self.clone(),
+                plugin,
+                host,
+                active: AtomicBool::new(false),
+                param_queue: Mutex::new(Vec::new()),
+                param_events: Mutex::new(Vec::new()),
+            }),
+            info,
+            gui_open: false,
+        })
+    }
+
+    /// Where plugins are installed: `CLAP_PATH`, then `~/.clap` and `/usr/lib/clap`
+    pub fn search_paths() -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = std::env::var_os("CLAP_PATH")
+            .map( | p | std::env::split_paths(&p).collect())
+            .unwrap_or_default();
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(Path::new(&home).join(".clap"));
+        }
+        paths.push(PathBuf::from("/usr/lib/clap"));
+        paths
+    }
+
+    /// All plugins in the search paths
+    pub fn scan() -> Vec<ClapPluginInfo> {
+        fn find(dir: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+            let Ok(entries) = std::fs::read_dir(dir) else {return};
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and( | e | e == "clap") && path.is_file() {
+                    out.push(path);
+                }
+                else if path.is_dir() && depth < 4 {
+                    find(&path, depth + 1, out);
+                }
+            }
+        }
+        let mut files = Vec::new();
+        for dir in Self::search_paths() {
+            find(&dir, 0, &mut files);
+        }
+        let mut plugins = Vec::new();
+        for file in files {
+            match Self::load(&file.to_string_lossy()) {
+                Ok(bundle) => plugins.extend(bundle.plugins()),
+                Err(err) => crate::warning!("Cannot load CLAP plugin {}: {:?}", file.display(), err),
+            }
+        }
+        plugins
+    }
+}
+
+impl Drop for ClapBundle {
+    fn drop(&mut self) {
+        unsafe {((*self.entry).deinit)()};
+    }
+}
+
+// what the host callbacks get to, host_data of the clap_host points here
+struct HostData {
+    host: clap_host,
+    main_thread: ThreadId,
+    // set while a ClapProcessor of the plugin exists
+    processing: AtomicBool,
+    callback_requested: AtomicBool,
+    restart_requested: AtomicBool,
+    flush_requested: AtomicBool,
+    rescan_params: AtomicBool,
+    gui_resize: AtomicU32Pair,
+    gui_closed: AtomicBool,
+    state_changed: AtomicBool,
+    signal: SignalToUI,
+}
+
+// a width and height, 0 for none
+#[derive(Default)]
+struct AtomicU32Pair(AtomicU32, AtomicU32);
+
+impl HostData {
+    fn new() -> Box<HostData> {
+        let mut data = Box::new(HostData {
+            host: clap_host {
+                clap_version: CLAP_VERSION,
+                host_data: ptr::null_mut(),
+                name: c"Makepad".as_ptr(),
+                vendor: c"Makepad".as_ptr(),
+                url: c"https://makepad.dev".as_ptr(),
+                version: c"1.0.0".as_ptr(),
+                get_extension: host_get_extension,
+                request_restart: host_request_restart,
+                request_process: host_request_process,
+                request_callback: host_request_callback,
+            },
+            main_thread: thread::current().id(),
+            processing: AtomicBool::new(false),
+            callback_requested: AtomicBool::new(false),
+            restart_requested: AtomicBool::new(false),
+            flush_requested: AtomicBool::new(false),
+            rescan_params: AtomicBool::new(false),
+            gui_resize: AtomicU32Pair::default(),
+            gui_closed: AtomicBool::new(false),
+            state_changed: AtomicBool::new(false),
+            signal: SignalToUI::new(),
+        });
+        data.host.host_data = &*data as *const HostData as *mut c_void;
+        data
+    }
+
+    unsafe fn from_host<'a>(host: *const clap_host) -> &'a HostData {
+        &*((*host).host_data as *const HostData)
+    }
+
+    fn is_main_thread(&self) -> bool {
+        thread::current().id() == self.main_thread
+    }
+}
+
+unsafe extern "C" fn host_get_extension(_host: *const clap_host, id: *const c_char) -> *const c_void {
+    let id = CStr::from_ptr(id).to_bytes_with_nul();
+    match id {
+        _ if id == CLAP_EXT_LOG => &HOST_LOG as *const clap_host_log as *const c_void,
+        _ if id == CLAP_EXT_THREAD_CHECK => &HOST_THREAD_CHECK as *const clap_host_thread_check as *const c_void,
+        _ if id == CLAP_EXT_PARAMS => &HOST_PARAMS as *const clap_host_params as *const c_void,
+        _ if id == CLAP_EXT_AUDIO_PORTS => &HOST_AUDIO_PORTS as *const clap_host_audio_ports as *const c_void,
+        _ if id == CLAP_EXT_NOTE_PORTS => &HOST_NOTE_PORTS as *const clap_host_note_ports as *const c_void,
+        _ if id == CLAP_EXT_GUI => &HOST_GUI as *const clap_host_gui as *const c_void,
+        _ if id == CLAP_EXT_STATE => &HOST_STATE as *const clap_host_state as *const c_void,
+        _ => ptr::null(),
+    }
+}
+
+unsafe extern "C" fn host_request_restart(host: *const clap_host) {
+    let data = HostData::from_host(host);
+    data.restart_requested.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+unsafe extern "C" fn host_request_process(_host: *const clap_host) {
+    // we never put a plugin to sleep, it gets processed anyway
+}
+
+unsafe extern "C" fn host_request_callback(host: *const clap_host) {
+    let data = HostData::from_host(host);
+    data.callback_requested.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+static HOST_LOG: clap_host_log = clap_host_log {log: host_log};
+
+unsafe extern "C" fn host_log(_host: *const clap_host, severity: clap_log_severity, msg: *const c_char) {
+    let msg = c_str(msg);
+    match severity {
+        CLAP_LOG_DEBUG | CLAP_LOG_INFO => crate::log!("CLAP: {}", msg),
+        CLAP_LOG_WARNING => crate::warning!("CLAP: {}", msg),
+        _ => crate::error!("CLAP: {}", msg),
+    }
+}
+
+static HOST_THREAD_CHECK: clap_host_thread_check = clap_host_thread_check {
+    is_main_thread: host_is_main_thread,
+    is_audio_thread: host_is_audio_thread,
+};
+
+unsafe extern "C" fn host_is_main_thread(host: *const clap_host) -> bool {
+    HostData::from_host(host).is_main_thread()
+}
+
+unsafe extern "C" fn host_is_audio_thread(host: *const clap_host) -> bool {
+    let data = HostData::from_host(host);
+    !data.is_main_thread() && data.processing.load(Ordering::SeqCst)
+}
+
+static HOST_PARAMS: clap_host_params = clap_host_params {
+    rescan: host_params_rescan,
+    clear: host_params_clear,
+    request_flush: host_params_request_flush,
+};
+
+unsafe extern "C" fn host_params_rescan(host: *const clap_host, _flags: u32) {
+    let data = HostData::from_host(host);
+    data.rescan_params.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+unsafe extern "C" fn host_params_clear(_host: *const clap_host, _param_id: clap_id, _flags: u32) {
+    // we don't hold on to automation or modulation of a parameter
+}
+
+unsafe extern "C" fn host_params_request_flush(host: *const clap_host) {
+    let data = HostData::from_host(host);
+    data.flush_requested.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+static HOST_AUDIO_PORTS: clap_host_audio_ports = clap_host_audio_ports {
+    is_rescan_flag_supported: host_audio_ports_is_rescan_flag_supported,
+    rescan: host_audio_ports_rescan,
+};
+
+unsafe extern "C" fn host_audio_ports_is_rescan_flag_supported(_host: *const clap_host, _flag: u32) -> bool {
+    // every change of the ports restarts the plugin
+    true
+}
+
+unsafe extern "C" fn host_audio_ports_rescan(host: *const clap_host, _flags: u32) {
+    host_request_restart(host)
+}
+
+static HOST_NOTE_PORTS: clap_host_note_ports = clap_host_note_ports {
+    supported_dialects: host_note_ports_supported_dialects,
+    rescan: host_note_ports_rescan,
+};
+
+unsafe extern "C" fn host_note_ports_supported_dialects(_host: *const clap_host) -> u32 {
+    CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI
+}
+
+unsafe extern "C" fn host_note_ports_rescan(host: *const clap_host, _flags: u32) {
+    host_request_restart(host)
+}
+
+static HOST_GUI: clap_host_gui = clap_host_gui {
+    resize_hints_changed: host_gui_resize_hints_changed,
+    request_resize: host_gui_request_resize,
+    request_show: host_gui_request_show,
+    request_hide: host_gui_request_hide,
+    closed: host_gui_closed,
+};
+
+unsafe extern "C" fn host_gui_resize_hints_changed(_host: *const clap_host) {
+}
+
+unsafe extern "C" fn host_gui_request_resize(host: *const clap_host, width: u32, height: u32) -> bool {
+    let data = HostData::from_host(host);
+    data.gui_resize.0.store(width, Ordering::SeqCst);
+    data.gui_resize.1.store(height, Ordering::SeqCst);
+    data.signal.set();
+    true
+}
+
+unsafe extern "C" fn host_gui_request_show(_host: *const clap_host) -> bool {
+    false
+}
+
+unsafe extern "C" fn host_gui_request_hide(_host: *const clap_host) -> bool {
+    false
+}
+
+unsafe extern "C" fn host_gui_closed(host: *const clap_host, _was_destroyed: bool) {
+    let data = HostData::from_host(host);
+    data.gui_closed.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+static HOST_STATE: clap_host_state = clap_host_state {
+    mark_dirty: host_state_mark_dirty,
+};
+
+unsafe extern "C" fn host_state_mark_dirty(host: *const clap_host) {
+    let data = HostData::from_host(host);
+    data.state_changed.store(true, Ordering::SeqCst);
+    data.signal.set();
+}
+
+// the plugin and everything the main and audio thread share of it. Dropping
+// the last of the ClapInstance and the ClapProcessor destroys the plugin
+struct ClapShared {
+    _bundle: Arc<ClapBundle>,
+    plugin: *const clap_plugin,
+    host: Box<HostData>,
+    params: *const clap_plugin_params,
+    audio_ports: *const clap_plugin_audio_ports,
+    note_ports: *const clap_plugin_note_ports,
+    gui: *const clap_plugin_gui,
+    state: *const clap_plugin_state,
+    active: AtomicBool,
+    // parameter changes from the ui, picked up by the next process or flush
+    param_queue: Mutex<Vec<(u32, f64)>>,
+    param_events: Mutex<Vec<ClapParamEvent>>,
+}
+
+unsafe impl Send for ClapShared {}
+unsafe impl Sync for ClapShared {}
+
+impl ClapShared {
+    fn plugin(&self) -> &clap_plugin {
+        unsafe {&*self.plugin}
+    }
+
+    fn params(&self) -> Option<&clap_plugin_params> {
+        unsafe {self.params.as_ref()}
+    }
+
+    fn note_dialects(&self) -> u32 {
+        let Some(note_ports) = (unsafe {self.note_ports.as_ref()}) else {return 0};
+        if unsafe {(note_ports.count)(self.plugin, true)} == 0 {
+            return 0
+        }
+        let mut info: clap_note_port_info = unsafe {std::mem::zeroed()};
+        if !unsafe {(note_ports.get)(self.plugin, 0, true, &mut info)} {
+            return 0
+        }
+        info.supported_dialects
+    }
+
+    fn audio_ports(&self, is_input: bool) -> Vec<ClapAudioPortInfo> {
+        let Some(audio_ports) = (unsafe {self.audio_ports.as_ref()}) else {return Vec::new()};
+        let count = unsafe {(audio_ports.count)(self.plugin, is_input)};
+        (0..count).filter_map( | index | {
+            let mut info: clap_audio_port_info = unsafe {std::mem::zeroed()};
+            unsafe {(audio_ports.get)(self.plugin, index, is_input, &mut info)}.then( || ClapAudioPortInfo {
+                id: info.id,
+                name: c_array(&info.name),
+                channel_count: info.channel_count as usize,
+                is_main: info.flags & CLAP_AUDIO_PORT_IS_MAIN != 0,
+            })
+        }).collect()
+    }
+}
+
+impl Drop for ClapShared {
+    fn drop(&mut self) {
+        let plugin = self.plugin();
+        unsafe {
+            if self.active.load(Ordering::SeqCst) {
+                (plugin.deactivate)(self.plugin);
+            }
+            (plugin.destroy)(self.plugin);
+        }
+    }
+}
+
+/// A running plugin, on the main thread. Audio goes through the
+/// `ClapProcessor` that `activate` hands out, on the audio thread
+pub struct ClapInstance {
+    shared: Arc<ClapShared>,
+    info: ClapPluginInfo,
+    gui_open: bool,
+}
+
+impl ClapInstance {
+    pub fn info(&self) -> &ClapPluginInfo {
+        &self.info
+    }
+
+    /// Set when the plugin wants `handle_main_thread` to be called
+    pub fn signal(&self) -> &SignalToUI {
+        &self.shared.host.signal
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.shared.active.load(Ordering::SeqCst)
+    }
+
+    /// Gets the plugin ready to process at `sample_rate`, in blocks of at most
+    /// `max_frames`. The processor of an earlier activation has to be dropped
+    /// first
+    pub fn activate(&mut self, sample_rate: f64, max_frames: usize) -> Result<ClapProcessor, ClapError> {
+        if self.shared.host.processing.load(Ordering::SeqCst) {
+            return Err(ClapError::StillProcessing)
+        }
+        self.deactivate();
+        let max_frames = max_frames.max(1);
+        if !unsafe {(self.shared.plugin().activate)(self.shared.plugin, sample_rate, 1, max_frames as u32)} {
+            return Err(ClapError::ActivateFailed)
+        }
+        self.shared.active.store(true, Ordering::SeqCst);
+        Ok(ClapProcessor::new(self.shared.clone(), sample_rate, max_frames))
+    }
+
+    pub fn deactivate(&mut self) {
+        if self.shared.host.processing.load(Ordering::SeqCst) {
+            return
+        }
+        if self.shared.active.swap(false, Ordering::SeqCst) {
+            unsafe {(self.shared.plugin().deactivate)(self.shared.plugin)};
+        }
+    }
+
+    pub fn audio_ports(&self, is_input: bool) -> Vec<ClapAudioPortInfo> {
+        self.shared.audio_ports(is_input)
+    }
+
+    /// Whether the plugin takes notes, in either of the dialects
+    pub fn takes_notes(&self) -> bool {
+        self.shared.note_dialects() & (CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI) != 0
+    }
+
+    pub fn params(&self) -> Vec<ClapParamInfo> {
+        let Some(params) = self.shared.params() else {return Vec::new()};
+        let plugin = self.shared.plugin;
+        let count = unsafe {(params.count)(plugin)};
+        (0..count).filter_map( | index | {
+            let mut info: clap_param_info = unsafe {std::mem::zeroed()};
+            unsafe {(params.get_info)(plugin, index, &mut info)}.then( || ClapParamInfo {
+                id: info.id,
+                name: c_array(&info.name),
+                module: c_array(&info.module),
+                min: info.min_value,
+                max: info.max_value,
+                default: info.default_value,
+                flags: info.flags,
+            })
+        }).collect()
+    }
+
+    pub fn param_value(&self, id: u32) -> Option<f64> {
+        let params = self.shared.params() ?;
+        let mut value = 0.0;
+        unsafe {(params.get_value)(self.shared.plugin, id, &mut value)}.then_some(value)
+    }
+
+    /// The value as the plugin shows it, with its unit
+    pub fn param_text(&self, id: u32, value: f64) -> Option<String> {
+        let params = self.shared.params() ?;
+        let mut text = [0 as c_char; CLAP_NAME_SIZE];
+        unsafe {(params.value_to_text)(self.shared.plugin, id, value, text.as_mut_ptr(), text.len() as u32)}
+            .then( || c_array(&text))
+    }
+
+    /// Changes a parameter. While processing it takes effect at the start of
+    /// the next block, otherwise right away
+    pub fn set_param(&mut self, id: u32, value: f64) {
+        self.shared.param_queue.lock().unwrap().push((id, value));
+        self.flush_params();
+    }
+
+    /// The changes the plugin made to its parameters since the last call
+    pub fn param_events(&mut self) -> Vec<ClapParamEvent> {
+        std::mem::take(&mut *self.shared.param_events.lock().unwrap())
+    }
+
+    fn flush_params(&mut self) {
+        let Some(params) = self.shared.params() else {return};
+        if self.shared.host.processing.load(Ordering::SeqCst) {
+            return
+        }
+        let mut events = EventList::default();
+        for (id, value) in self.shared.param_queue.lock().unwrap().drain(..) {
+            events.push_param(0, id, value);
+        }
+        let mut out = OutputEvents::default();
+        unsafe {(params.flush)(self.shared.plugin, &events.as_clap(), &out.as_clap())};
+        self.shared.param_events.lock().unwrap().append(&mut out.events);
+    }
+
+    /// Does what the plugin asked for on the main thread, call it when the
+    /// `signal` is set. Things only the owner can do are returned
+    pub fn handle_main_thread(&mut self) -> ClapHostRequests {
+        if self.shared.host.callback_requested.swap(false, Ordering::SeqCst) {
+            unsafe {(self.shared.plugin().on_main_thread)(self.shared.plugin)};
+        }
+        if self.shared.host.flush_requested.swap(false, Ordering::SeqCst) {
+            self.flush_params();
+        }
+        let host = &self.shared.host;
+        let width = host.gui_resize.0.swap(0, Ordering::SeqCst);
+        let height = host.gui_resize.1.swap(0, Ordering::SeqCst);
+        let gui_closed = host.gui_closed.swap(false, Ordering::SeqCst);
+        let restart = host.restart_requested.swap(false, Ordering::SeqCst);
+        let rescan_params = host.rescan_params.swap(false, Ordering::SeqCst);
+        let state_changed = host.state_changed.swap(false, Ordering::SeqCst);
+        if gui_closed {
+            self.close_gui();
+        }
+        ClapHostRequests {
+            restart,
+            rescan_params,
+            gui_resize: (width != 0 && height != 0).then_some((width, height)),
+            gui_closed,
+            state_changed,
+        }
+    }
+
+    /// The state of the plugin to store with a project, in the plugin's format
+    pub fn save_state(&self) -> Option<Vec<u8>> {
+        let state = unsafe {self.shared.state.as_ref()} ?;
+        let mut data = Vec::new();
+        let stream = clap_ostream {
+            ctx: &mut data as *mut Vec<u8> as *mut c_void,
+            write: ostream_write,
+        };
+        unsafe {(state.save)(self.shared.plugin, &stream)}.then_some(data)
+    }
+
+    /// Restores what `save_state` returned, the plugin reports its new
+    /// parameter values as a rescan
+    pub fn load_state(&mut self, data: &[u8]) -> bool {
+        let Some(state) = (unsafe {self.shared.state.as_ref()}) else {return false};
+        let mut reader = (data, 0usize);
+        let stream = clap_istream {
+            ctx: &mut reader as *mut (&[u8], usize) as *mut c_void,
+            read: istream_read,
+        };
+        unsafe {(state.load)(self.shared.plugin, &stream)}
+    }
+
+    fn gui(&self) -> Option<&clap_plugin_gui> {
+        unsafe {self.shared.gui.as_ref()}
+    }
+
+    /// Whether the plugin has an X11 gui, in a window of its own when `floating`
+    pub fn has_gui(&self, floating: bool) -> bool {
+        self.gui().is_some_and( | gui | unsafe {(gui.is_api_supported)(self.shared.plugin, CLAP_WINDOW_API_X11.as_ptr() as *const c_char, floating)})
+    }
+
+    pub fn is_gui_open(&self) -> bool {
+        self.gui_open
+    }
+
+    /// Opens the gui of the plugin in the X11 window `parent`, or in a window
+    /// of its own without one. Returns the size the gui wants
+    pub fn open_gui(&mut self, parent: Option<u64>) -> Result<(u32, u32), ClapError> {
+        if self.gui_open {
+            self.close_gui();
+        }
+        let floating = parent.is_none();
+        if !self.has_gui(floating) {
+            return Err(ClapError::NoGui)
+        }
+        let plugin = self.shared.plugin;
+        // has_gui checked it is there
+        let gui = unsafe {&*self.shared.gui};
+        let api = CLAP_WINDOW_API_X11.as_ptr() as *const c_char;
+        unsafe {
+            if !(gui.create)(plugin, api, floating) {
+                return Err(ClapError::GuiFailed)
+            }
+            let placed = if let Some(parent) = parent {
+                let window = clap_window {api, handle: clap_window_handle {x11: parent as c_ulong}};
+                (gui.set_parent)(plugin, &window)
+            }
+            else {
+                let title = CString::new(self.info.name.clone()).unwrap_or_default();
+                (gui.suggest_title)(plugin, title.as_ptr());
+                true
+            };
+            if !placed || !(gui.show)(plugin) {
+                (gui.destroy)(plugin);
+                return Err(ClapError::GuiFailed)
+            }
+            self.gui_open = true;
+            let mut size = (0, 0);
+            (gui.get_size)(plugin, &mut size.0, &mut size.1);
+            Ok(size)
+        }
+    }
+
+    /// Resizes an embedded gui, returns the size the plugin went with
+    pub fn set_gui_size(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let gui = self.gui().filter( | _ | self.gui_open) ?;
+        let plugin = self.shared.plugin;
+        let mut size = (width, height);
+        unsafe {
+            if !(gui.can_resize)(plugin) {
+                (gui.get_size)(plugin, &mut size.0, &mut size.1);
+                return Some(size)
+            }
+            (gui.adjust_size)(plugin, &mut size.0, &mut size.1);
+            (gui.set_size)(plugin, size.0, size.1).then_some(size)
+        }
+    }
+
+    pub fn close_gui(&mut self) {
+        if !self.gui_open {
+            return
+        }
+        self.gui_open = false;
+        if let Some(gui) = self.gui() {
+            unsafe {
+                (gui.hide)(self.shared.plugin);
+                (gui.destroy)(self.shared.plugin);
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn ostream_write(stream: *const clap_ostream, buffer: *const c_void, size: u64) -> i64 {
+    let data = &mut *((*stream).ctx as *mut Vec<u8>);
+    data.extend_from_slice(std::slice::from_raw_parts(buffer as *const u8, size as usize));
+    size as i64
+}
+
+unsafe extern "C" fn istream_read(stream: *const clap_istream, buffer: *mut c_void, size: u64) -> i64 {
+    let (data, pos) = &mut *((*stream).ctx as *mut (&[u8], usize));
+    let len = (size as usize).min(data.len() - *pos);
+    std::slice::from_raw_parts_mut(buffer as *mut u8, len).copy_from_slice(&data[*pos..*pos + len]);
+    *pos += len;
+    len as i64
+}
+
+impl Drop for ClapInstance {
+    fn drop(&mut self) {
+        self.close_gui();
+    }
+}
+
+#[derive(Clone, Copy)]
+enum InputEvent {
+    Note(clap_event_note),
+    Param(clap_event_param_value),
+    Midi(clap_event_midi),
+}
+
+impl InputEvent {
+    fn header(&self) -> *const clap_event_header {
+        match self {
+            InputEvent::Note(e) => &e.header,
+            InputEvent::Param(e) => &e.header,
+            InputEvent::Midi(e) => &e.header,
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut clap_event_header {
+        match self {
+            InputEvent::Note(e) => &mut e.header,
+            InputEvent::Param(e) => &mut e.header,
+            InputEvent::Midi(e) => &mut e.header,
+        }
+    }
+
+    fn time(&self) -> u32 {
+        unsafe {(*self.header()).time}
+    }
+}
+
+// events in frame order, which is what plugins expect
+#[derive(Default)]
+struct EventList {
+    events: Vec<InputEvent>,
+}
+
+impl EventList {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {events: Vec::with_capacity(capacity)}
+    }
+
+    fn push(&mut self, event: InputEvent) {
+        if self.events.len() < MAX_EVENTS {
+            // behind the events at the same frame, they keep their order
+            let time = event.time();
+            let at = self.events.partition_point( | e | e.time() <= time);
+            self.events.insert(at, event);
+        }
+    }
+
+    fn push_param(&mut self, time: u32, id: u32, value: f64) {
+        self.push(InputEvent::Param(clap_event_param_value {
+            header: clap_event_header::new::<clap_event_param_value>(CLAP_EVENT_PARAM_VALUE, time),
+            param_id: id,
+            cookie: ptr::null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value,
+        }));
+    }
+
+    fn push_note(&mut self, time: u32, type_: u16, channel: i16, key: i16, velocity: f64) {
+        self.push(InputEvent::Note(clap_event_note {
+            header: clap_event_header::new::<clap_event_note>(type_, time),
+            note_id: -1,
+            port_index: 0,
+            channel,
+            key,
+            velocity,
+        }));
+    }
+
+    fn push_midi(&mut self, time: u32, data: [u8; 3]) {
+        self.push(InputEvent::Midi(clap_event_midi {
+            header: clap_event_header::new::<clap_event_midi>(CLAP_EVENT_MIDI, time),
+            port_index: 0,
+            data,
+        }));
+    }
+
+    fn as_clap(&self) -> clap_input_events {
+        clap_input_events {
+            ctx: self as *const EventList as *mut c_void,
+            size: input_events_size,
+            get: input_events_get,
+        }
+    }
+}
+
+unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
+    (*((*list).ctx as *const EventList)).events.len() as u32
+}
+
+unsafe extern "C" fn input_events_get(list: *const clap_input_events, index: u32) -> *const clap_event_header {
+    let list = &*((*list).ctx as *const EventList);
+    list.events.get(index as usize).map_or(ptr::null(), | e | e.header())
+}
+
+#[derive(Default)]
+struct OutputEvents {
+    events: Vec<ClapParamEvent>,
+}
+
+impl OutputEvents {
+    fn as_clap(&mut self) -> clap_output_events {
+        clap_output_events {
+            ctx: self as *mut OutputEvents as *mut c_void,
+            try_push: output_events_try_push,
+        }
+    }
+}
+
+unsafe extern "C" fn output_events_try_push(list: *const clap_output_events, event: *const clap_event_header) -> bool {
+    let out = &mut *((*list).ctx as *mut OutputEvents);
+    if (*event).space_id != CLAP_CORE_EVENT_SPACE_ID {
+        return true
+    }
+    let event = match (*event).type_ {
+        CLAP_EVENT_PARAM_VALUE => {
+            let e = &*(event as *const clap_event_param_value);
+            ClapParamEvent::Value {id: e.param_id, value: e.value}
+        }
+        CLAP_EVENT_PARAM_GESTURE_BEGIN => ClapParamEvent::GestureBegin {id: (*(event as *const clap_event_param_gesture)).param_id},
+        CLAP_EVENT_PARAM_GESTURE_END => ClapParamEvent::GestureEnd {id: (*(event as *const clap_event_param_gesture)).param_id},
+        // notes the plugin sends out have nowhere to go
+        _ => return true
+    };
+    if out.events.len() >= MAX_EVENTS {
+        return false
+    }
+    out.events.push(event);
+    true
+}
+
+// a port's channels, laid out so they can be handed over as pointers
+struct PortBuffer {
+    buffer: AudioBuffer,
+    channels: Vec<*mut f32>,
+    is_main: bool,
+}
+
+impl PortBuffer {
+    fn new(port: &ClapAudioPortInfo, max_frames: usize) -> Self {
+        let mut buffer = AudioBuffer::new_with_size(max_frames, port.channel_count);
+        let channels = (0..port.channel_count).map( | c | buffer.channel_mut(c).as_mut_ptr()).collect();
+        Self {buffer, channels, is_main: port.is_main}
+    }
+
+    fn as_clap(&mut self) -> clap_audio_buffer {
+        clap_audio_buffer {
+            data32: self.channels.as_mut_ptr(),
+            data64: ptr::null_mut(),
+            channel_count: self.channels.len() as u32,
+            latency: 0,
+            constant_mask: 0,
+        }
+    }
+}
+
+/// The audio thread side of a `ClapInstance`
+pub struct ClapProcessor {
+    shared: Arc<ClapShared>,
+    sample_rate: f64,
+    max_frames: usize,
+    started: bool,
+    steady_time: i64,
+    note_dialects: u32,
+    inputs: Vec<PortBuffer>,
+    outputs: Vec<PortBuffer>,
+    clap_inputs: Vec<clap_audio_buffer>,
+    clap_outputs: Vec<clap_audio_buffer>,
+    // what is queued up, and what goes out with the current run of the plugin
+    events: EventList,
+    run_events: EventList,
+    out_events: OutputEvents,
+}
+
+unsafe impl Send for ClapProcessor {}
+
+impl ClapProcessor {
+    fn new(shared: Arc<ClapShared>, sample_rate: f64, max_frames: usize) -> Self {
+        let inputs: Vec<PortBuffer> = shared.audio_ports(true).iter().map( | p | PortBuffer::new(p, max_frames)).collect();
+        let outputs: Vec<PortBuffer> = shared.audio_ports(false).iter().map( | p | PortBuffer::new(p, max_frames)).collect();
+        shared.host.processing.store(true, Ordering::SeqCst);
+        Self {
+            note_dialects: shared.note_dialects(),
+            clap_inputs: Vec::with_capacity(inputs.len()),
+            clap_outputs: Vec::with_capacity(outputs.len()),
+            inputs,
+            outputs,
+            shared,
+            sample_rate,
+            max_frames,
+            started: false,
+            steady_time: 0,
+            events: EventList::with_capacity(MAX_EVENTS),
+            run_events: EventList::with_capacity(MAX_EVENTS),
+            out_events: OutputEvents {events: Vec::with_capacity(MAX_EVENTS)},
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Passes midi on to the plugin at the start of the next block
+    pub fn handle_midi_data(&mut self, data: MidiData) {
+        self.handle_midi_data_at(0, data);
+    }
+
+    /// Passes midi on to the plugin `frame` frames into the next block.
+    /// Plugins that don't take midi get note on and off as CLAP notes
+    pub fn handle_midi_data_at(&mut self, frame: u32, data: MidiData) {
+        if self.note_dialects & CLAP_NOTE_DIALECT_MIDI != 0 {
+            self.events.push_midi(frame, data.data);
+            return
+        }
+        if self.note_dialects & CLAP_NOTE_DIALECT_CLAP == 0 {
+            return
+        }
+        let channel = (data.data[0] & 0xf) as i16;
+        let key = data.data[1] as i16;
+        let velocity = data.data[2] as f64 / 127.0;
+        match data.data[0] & 0xf0 {
+            0x90 if data.data[2] > 0 => self.events.push_note(frame, CLAP_EVENT_NOTE_ON, channel, key, velocity),
+            0x80 | 0x90 => self.events.push_note(frame, CLAP_EVENT_NOTE_OFF, channel, key, velocity),
+            _ => ()
+        }
+    }
+
+    /// Automates a parameter `frame` frames into the next block, where
+    /// `ClapInstance::set_param` lands at its start
+    pub fn set_param_at(&mut self, frame: u32, id: u32, value: f64) {
+        self.events.push_param(frame, id, value);
+    }
+
+    pub fn all_notes_off(&mut self) {
+        if self.note_dialects & CLAP_NOTE_DIALECT_MIDI != 0 {
+            for channel in 0..16 {
+                self.events.push_midi(0, [0xb0 | channel, 123, 0]);
+            }
+        }
+        else if self.note_dialects & CLAP_NOTE_DIALECT_CLAP != 0 {
+            // -1 is a wildcard for the channel and key
+            self.events.push_note(0, CLAP_EVENT_NOTE_OFF, -1, -1, 0.0);
+        }
+    }
+
+    /// Runs the plugin over `output`, with `input` on its main input port.
+    /// Without a main output port the input passes through. Returns false
+    /// when the plugin failed, the output is silent then
+    pub fn process(&mut self, input: Option<&AudioBuffer>, output: &mut AudioBuffer) -> bool {
+        if !self.started {
+            if !unsafe {(self.shared.plugin().start_processing)(self.shared.plugin)} {
+                output.zero();
+                return false
+            }
+            self.started = true;
+        }
+        if let Ok(mut queue) = self.shared.param_queue.try_lock() {
+            for (id, value) in queue.drain(..) {
+                self.events.push_param(0, id, value);
+            }
+        }
+
+        let frame_count = output.frame_count();
+        let mut offset = 0;
+        let mut ok = true;
+        while offset < frame_count {
+            let frames = (frame_count - offset).min(self.max_frames);
+            for port in &mut self.inputs {
+                for c in 0..port.buffer.channel_count() {
+                    let channel = &mut port.buffer.channel_mut(c)[..frames];
+                    match input.filter( | i | port.is_main && i.channel_count() > 0) {
+                        Some(input) => copy_padded(channel, input.channel(c.min(input.channel_count() - 1)), offset),
+                        None => channel.fill(0.0)
+                    }
+                }
+            }
+            // the events of this run, with frames counted from its start
+            let end = (offset + frames) as u32;
+            let due = self.events.events.partition_point( | e | e.time() < end);
+            self.run_events.events.clear();
+            self.run_events.events.extend(self.events.events.drain(..due).map( | mut e | {
+                e.header_mut().time -= offset as u32;
+                e
+            }));
+            self.clap_inputs.clear();
+            self.clap_inputs.extend(self.inputs.iter_mut().map( | p | p.as_clap()));
+            self.clap_outputs.clear();
+            self.clap_outputs.extend(self.outputs.iter_mut().map( | p | p.as_clap()));
+            let in_events = self.run_events.as_clap();
+            let out_events = self.out_events.as_clap();
+            let process = clap_process {
+                steady_time: self.steady_time,
+                frames_count: frames as u32,
+                transport: ptr::null(),
+                audio_inputs: self.clap_inputs.as_ptr(),
+                audio_outputs: self.clap_outputs.as_mut_ptr(),
+                audio_inputs_count: self.clap_inputs.len() as u32,
+                audio_outputs_count: self.clap_outputs.len() as u32,
+                in_events: &in_events,
+                out_events: &out_events,
+            };
+            let status = unsafe {(self.shared.plugin().process)(self.shared.plugin, &process)};
+            self.run_events.events.clear();
+            self.steady_time += frames as i64;
+            if status == CLAP_PROCESS_ERROR {
+                ok = false;
+                break
+            }
+            let main_output = self.outputs.iter().find( | p | p.is_main && p.buffer.channel_count() > 0);
+            for c in 0..output.channel_count() {
+                let target = &mut output.channel_mut(c)[offset..offset + frames];
+                match (main_output, input) {
+                    (Some(port), _) => {
+                        let from = c.min(port.buffer.channel_count() - 1);
+                        target.copy_from_slice(&port.buffer.channel(from)[..frames]);
+                    }
+                    (None, Some(input)) if c < input.channel_count() => copy_padded(target, input.channel(c), offset),
+                    _ => target.fill(0.0)
+                }
+            }
+            offset += frames;
+        }
+        if !ok {
+            output.zero();
+        }
+        // events past the end of the block go out with the next one
+        for event in &mut self.events.events {
+            let header = event.header_mut();
+            header.time = header.time.saturating_sub(frame_count as u32);
+        }
+
+        if !self.out_events.events.is_empty() {
+            if let Ok(mut events) = self.shared.param_events.try_lock() {
+                events.append(&mut self.out_events.events);
+                self.shared.host.signal.set();
+            }
+        }
+        ok
+    }
+
+    /// Clears the plugin's state, like its tails, on the audio thread
+    pub fn reset(&mut self) {
+        unsafe {(self.shared.plugin().reset)(self.shared.plugin)};
+    }
+}
+
+impl Drop for ClapProcessor {
+    fn drop(&mut self) {
+        if self.started {
+            unsafe {(self.shared.plugin().stop_processing)(self.shared.plugin)};
+        }
+        self.shared.host.processing.store(false, Ordering::SeqCst);
+    }
+}
+
+// an input shorter than the output block is padded with silence
+fn copy_padded(target: &mut [f32], source: &[f32], offset: usize) {
+    let source = source.get(offset..).unwrap_or(&[]);
+    let len = target.len().min(source.len());
+    target[..len].copy_from_slice(&source[..len]);
+    target[len..].fill(0.0);
+}
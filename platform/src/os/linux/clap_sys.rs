@@ -0,0 +1,408 @@
+#![allow(non_camel_case_types)]
+// The parts of the CLAP plugin ABI the host and the plugin export use, after
+// clap/include/clap/*.h of CLAP 1.2. Nothing links against a CLAP library
+
+use std::os::raw::{c_char, c_ulong, c_void};
+
+pub type clap_id = u32;
+
+pub const CLAP_INVALID_ID: clap_id = u32::MAX;
+pub const CLAP_NAME_SIZE: usize = 256;
+pub const CLAP_PATH_SIZE: usize = 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_version {
+    pub major: u32,
+    pub minor: u32,
+    pub revision: u32,
+}
+
+pub const CLAP_VERSION: clap_version = clap_version {major: 1, minor: 2, revision: 2};
+
+impl clap_version {
+    /// Plugins of a later 1.x are fine, 0.x plugins are from before the ABI was stable
+    pub fn is_compatible(&self) -> bool {
+        self.major >= 1
+    }
+}
+
+pub const CLAP_PLUGIN_FACTORY_ID: &[u8] = b"clap.plugin-factory\0";
+pub const CLAP_EXT_PARAMS: &[u8] = b"clap.params\0";
+pub const CLAP_EXT_AUDIO_PORTS: &[u8] = b"clap.audio-ports\0";
+pub const CLAP_EXT_NOTE_PORTS: &[u8] = b"clap.note-ports\0";
+pub const CLAP_EXT_GUI: &[u8] = b"clap.gui\0";
+pub const CLAP_EXT_LOG: &[u8] = b"clap.log\0";
+pub const CLAP_EXT_THREAD_CHECK: &[u8] = b"clap.thread-check\0";
+pub const CLAP_EXT_STATE: &[u8] = b"clap.state\0";
+pub const CLAP_WINDOW_API_X11: &[u8] = b"x11\0";
+
+#[repr(C)]
+pub struct clap_plugin_entry {
+    pub clap_version: clap_version,
+    pub init: unsafe extern "C" fn(plugin_path: *const c_char) -> bool,
+    pub deinit: unsafe extern "C" fn(),
+    pub get_factory: unsafe extern "C" fn(factory_id: *const c_char) -> *const c_void,
+}
+
+#[repr(C)]
+pub struct clap_plugin_descriptor {
+    pub clap_version: clap_version,
+    pub id: *const c_char,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub manual_url: *const c_char,
+    pub support_url: *const c_char,
+    pub version: *const c_char,
+    pub description: *const c_char,
+    // null terminated
+    pub features: *const *const c_char,
+}
+
+#[repr(C)]
+pub struct clap_plugin_factory {
+    pub get_plugin_count: unsafe extern "C" fn(factory: *const clap_plugin_factory) -> u32,
+    pub get_plugin_descriptor: unsafe extern "C" fn(factory: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor,
+    pub create_plugin: unsafe extern "C" fn(factory: *const clap_plugin_factory, host: *const clap_host, plugin_id: *const c_char) -> *const clap_plugin,
+}
+
+#[repr(C)]
+pub struct clap_host {
+    pub clap_version: clap_version,
+    pub host_data: *mut c_void,
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub url: *const c_char,
+    pub version: *const c_char,
+    pub get_extension: unsafe extern "C" fn(host: *const clap_host, extension_id: *const c_char) -> *const c_void,
+    pub request_restart: unsafe extern "C" fn(host: *const clap_host),
+    pub request_process: unsafe extern "C" fn(host: *const clap_host),
+    pub request_callback: unsafe extern "C" fn(host: *const clap_host),
+}
+
+#[repr(C)]
+pub struct clap_plugin {
+    pub desc: *const clap_plugin_descriptor,
+    pub plugin_data: *mut c_void,
+    pub init: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+    pub destroy: unsafe extern "C" fn(plugin: *const clap_plugin),
+    pub activate: unsafe extern "C" fn(plugin: *const clap_plugin, sample_rate: f64, min_frames_count: u32, max_frames_count: u32) -> bool,
+    pub deactivate: unsafe extern "C" fn(plugin: *const clap_plugin),
+    pub start_processing: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+    pub stop_processing: unsafe extern "C" fn(plugin: *const clap_plugin),
+    pub reset: unsafe extern "C" fn(plugin: *const clap_plugin),
+    pub process: unsafe extern "C" fn(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status,
+    pub get_extension: unsafe extern "C" fn(plugin: *const clap_plugin, id: *const c_char) -> *const c_void,
+    pub on_main_thread: unsafe extern "C" fn(plugin: *const clap_plugin),
+}
+
+pub type clap_process_status = i32;
+
+pub const CLAP_PROCESS_ERROR: clap_process_status = 0;
+pub const CLAP_PROCESS_CONTINUE: clap_process_status = 1;
+pub const CLAP_PROCESS_CONTINUE_IF_NOT_QUIET: clap_process_status = 2;
+pub const CLAP_PROCESS_TAIL: clap_process_status = 3;
+pub const CLAP_PROCESS_SLEEP: clap_process_status = 4;
+
+#[repr(C)]
+pub struct clap_audio_buffer {
+    pub data32: *mut *mut f32,
+    pub data64: *mut *mut f64,
+    pub channel_count: u32,
+    pub latency: u32,
+    pub constant_mask: u64,
+}
+
+#[repr(C)]
+pub struct clap_process {
+    pub steady_time: i64,
+    pub frames_count: u32,
+    // may be null, we don't have a transport
+    pub transport: *const c_void,
+    pub audio_inputs: *const clap_audio_buffer,
+    pub audio_outputs: *mut clap_audio_buffer,
+    pub audio_inputs_count: u32,
+    pub audio_outputs_count: u32,
+    pub in_events: *const clap_input_events,
+    pub out_events: *const clap_output_events,
+}
+
+// events
+
+pub const CLAP_CORE_EVENT_SPACE_ID: u16 = 0;
+
+pub const CLAP_EVENT_NOTE_ON: u16 = 0;
+pub const CLAP_EVENT_NOTE_OFF: u16 = 1;
+pub const CLAP_EVENT_NOTE_CHOKE: u16 = 2;
+pub const CLAP_EVENT_NOTE_END: u16 = 3;
+pub const CLAP_EVENT_PARAM_VALUE: u16 = 5;
+pub const CLAP_EVENT_PARAM_GESTURE_BEGIN: u16 = 7;
+pub const CLAP_EVENT_PARAM_GESTURE_END: u16 = 8;
+pub const CLAP_EVENT_MIDI: u16 = 10;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_event_header {
+    pub size: u32,
+    pub time: u32,
+    pub space_id: u16,
+    pub type_: u16,
+    pub flags: u32,
+}
+
+impl clap_event_header {
+    pub fn new<T>(type_: u16, time: u32) -> Self {
+        Self {size: std::mem::size_of::<T>() as u32, time, space_id: CLAP_CORE_EVENT_SPACE_ID, type_, flags: 0}
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_event_note {
+    pub header: clap_event_header,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    pub velocity: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_event_param_value {
+    pub header: clap_event_header,
+    pub param_id: clap_id,
+    pub cookie: *mut c_void,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    pub value: f64,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_event_param_gesture {
+    pub header: clap_event_header,
+    pub param_id: clap_id,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct clap_event_midi {
+    pub header: clap_event_header,
+    pub port_index: u16,
+    pub data: [u8; 3],
+}
+
+#[repr(C)]
+pub struct clap_input_events {
+    pub ctx: *mut c_void,
+    pub size: unsafe extern "C" fn(list: *const clap_input_events) -> u32,
+    pub get: unsafe extern "C" fn(list: *const clap_input_events, index: u32) -> *const clap_event_header,
+}
+
+#[repr(C)]
+pub struct clap_output_events {
+    pub ctx: *mut c_void,
+    pub try_push: unsafe extern "C" fn(list: *const clap_output_events, event: *const clap_event_header) -> bool,
+}
+
+// params
+
+pub const CLAP_PARAM_IS_STEPPED: u32 = 1 << 0;
+pub const CLAP_PARAM_IS_PERIODIC: u32 = 1 << 1;
+pub const CLAP_PARAM_IS_HIDDEN: u32 = 1 << 2;
+pub const CLAP_PARAM_IS_READONLY: u32 = 1 << 3;
+pub const CLAP_PARAM_IS_BYPASS: u32 = 1 << 4;
+pub const CLAP_PARAM_IS_AUTOMATABLE: u32 = 1 << 5;
+
+pub const CLAP_PARAM_RESCAN_VALUES: u32 = 1 << 0;
+pub const CLAP_PARAM_RESCAN_TEXT: u32 = 1 << 1;
+pub const CLAP_PARAM_RESCAN_INFO: u32 = 1 << 2;
+pub const CLAP_PARAM_RESCAN_ALL: u32 = 1 << 3;
+
+#[repr(C)]
+pub struct clap_param_info {
+    pub id: clap_id,
+    pub flags: u32,
+    pub cookie: *mut c_void,
+    pub name: [c_char; CLAP_NAME_SIZE],
+    pub module: [c_char; CLAP_PATH_SIZE],
+    pub min_value: f64,
+    pub max_value: f64,
+    pub default_value: f64,
+}
+
+#[repr(C)]
+pub struct clap_plugin_params {
+    pub count: unsafe extern "C" fn(plugin: *const clap_plugin) -> u32,
+    pub get_info: unsafe extern "C" fn(plugin: *const clap_plugin, param_index: u32, param_info: *mut clap_param_info) -> bool,
+    pub get_value: unsafe extern "C" fn(plugin: *const clap_plugin, param_id: clap_id, out_value: *mut f64) -> bool,
+    pub value_to_text: unsafe extern "C" fn(plugin: *const clap_plugin, param_id: clap_id, value: f64, out_buffer: *mut c_char, out_buffer_capacity: u32) -> bool,
+    pub text_to_value: unsafe extern "C" fn(plugin: *const clap_plugin, param_id: clap_id, param_value_text: *const c_char, out_value: *mut f64) -> bool,
+    pub flush: unsafe extern "C" fn(plugin: *const clap_plugin, in_: *const clap_input_events, out: *const clap_output_events),
+}
+
+#[repr(C)]
+pub struct clap_host_params {
+    pub rescan: unsafe extern "C" fn(host: *const clap_host, flags: u32),
+    pub clear: unsafe extern "C" fn(host: *const clap_host, param_id: clap_id, flags: u32),
+    pub request_flush: unsafe extern "C" fn(host: *const clap_host),
+}
+
+// audio ports
+
+pub const CLAP_AUDIO_PORT_IS_MAIN: u32 = 1 << 0;
+
+#[repr(C)]
+pub struct clap_audio_port_info {
+    pub id: clap_id,
+    pub name: [c_char; CLAP_NAME_SIZE],
+    pub flags: u32,
+    pub channel_count: u32,
+    pub port_type: *const c_char,
+    pub in_place_pair: clap_id,
+}
+
+#[repr(C)]
+pub struct clap_plugin_audio_ports {
+    pub count: unsafe extern "C" fn(plugin: *const clap_plugin, is_input: bool) -> u32,
+    pub get: unsafe extern "C" fn(plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_audio_port_info) -> bool,
+}
+
+#[repr(C)]
+pub struct clap_host_audio_ports {
+    pub is_rescan_flag_supported: unsafe extern "C" fn(host: *const clap_host, flag: u32) -> bool,
+    pub rescan: unsafe extern "C" fn(host: *const clap_host, flags: u32),
+}
+
+// note ports
+
+pub const CLAP_NOTE_DIALECT_CLAP: u32 = 1 << 0;
+pub const CLAP_NOTE_DIALECT_MIDI: u32 = 1 << 1;
+
+#[repr(C)]
+pub struct clap_note_port_info {
+    pub id: clap_id,
+    pub supported_dialects: u32,
+    pub preferred_dialect: u32,
+    pub name: [c_char; CLAP_NAME_SIZE],
+}
+
+#[repr(C)]
+pub struct clap_plugin_note_ports {
+    pub count: unsafe extern "C" fn(plugin: *const clap_plugin, is_input: bool) -> u32,
+    pub get: unsafe extern "C" fn(plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_note_port_info) -> bool,
+}
+
+#[repr(C)]
+pub struct clap_host_note_ports {
+    pub supported_dialects: unsafe extern "C" fn(host: *const clap_host) -> u32,
+    pub rescan: unsafe extern "C" fn(host: *const clap_host, flags: u32),
+}
+
+// gui
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union clap_window_handle {
+    pub cocoa: *mut c_void,
+    pub x11: c_ulong,
+    pub win32: *mut c_void,
+    pub ptr: *mut c_void,
+}
+
+#[repr(C)]
+pub struct clap_window {
+    pub api: *const c_char,
+    pub handle: clap_window_handle,
+}
+
+#[repr(C)]
+pub struct clap_gui_resize_hints {
+    pub can_resize_horizontally: bool,
+    pub can_resize_vertically: bool,
+    pub preserve_aspect_ratio: bool,
+    pub aspect_ratio_width: u32,
+    pub aspect_ratio_height: u32,
+}
+
+#[repr(C)]
+pub struct clap_plugin_gui {
+    pub is_api_supported: unsafe extern "C" fn(plugin: *const clap_plugin, api: *const c_char, is_floating: bool) -> bool,
+    pub get_preferred_api: unsafe extern "C" fn(plugin: *const clap_plugin, api: *mut *const c_char, is_floating: *mut bool) -> bool,
+    pub create: unsafe extern "C" fn(plugin: *const clap_plugin, api: *const c_char, is_floating: bool) -> bool,
+    pub destroy: unsafe extern "C" fn(plugin: *const clap_plugin),
+    pub set_scale: unsafe extern "C" fn(plugin: *const clap_plugin, scale: f64) -> bool,
+    pub get_size: unsafe extern "C" fn(plugin: *const clap_plugin, width: *mut u32, height: *mut u32) -> bool,
+    pub can_resize: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+    pub get_resize_hints: unsafe extern "C" fn(plugin: *const clap_plugin, hints: *mut clap_gui_resize_hints) -> bool,
+    pub adjust_size: unsafe extern "C" fn(plugin: *const clap_plugin, width: *mut u32, height: *mut u32) -> bool,
+    pub set_size: unsafe extern "C" fn(plugin: *const clap_plugin, width: u32, height: u32) -> bool,
+    pub set_parent: unsafe extern "C" fn(plugin: *const clap_plugin, window: *const clap_window) -> bool,
+    pub set_transient: unsafe extern "C" fn(plugin: *const clap_plugin, window: *const clap_window) -> bool,
+    pub suggest_title: unsafe extern "C" fn(plugin: *const clap_plugin, title: *const c_char),
+    pub show: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+    pub hide: unsafe extern "C" fn(plugin: *const clap_plugin) -> bool,
+}
+
+#[repr(C)]
+pub struct clap_host_gui {
+    pub resize_hints_changed: unsafe extern "C" fn(host: *const clap_host),
+    pub request_resize: unsafe extern "C" fn(host: *const clap_host, width: u32, height: u32) -> bool,
+    pub request_show: unsafe extern "C" fn(host: *const clap_host) -> bool,
+    pub request_hide: unsafe extern "C" fn(host: *const clap_host) -> bool,
+    pub closed: unsafe extern "C" fn(host: *const clap_host, was_destroyed: bool),
+}
+
+// state
+
+#[repr(C)]
+pub struct clap_istream {
+    pub ctx: *mut c_void,
+    // returns the bytes read, 0 at the end and -1 on an error
+    pub read: unsafe extern "C" fn(stream: *const clap_istream, buffer: *mut c_void, size: u64) -> i64,
+}
+
+#[repr(C)]
+pub struct clap_ostream {
+    pub ctx: *mut c_void,
+    // returns the bytes written or -1 on an error
+    pub write: unsafe extern "C" fn(stream: *const clap_ostream, buffer: *const c_void, size: u64) -> i64,
+}
+
+#[repr(C)]
+pub struct clap_plugin_state {
+    pub save: unsafe extern "C" fn(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool,
+    pub load: unsafe extern "C" fn(plugin: *const clap_plugin, stream: *const clap_istream) -> bool,
+}
+
+#[repr(C)]
+pub struct clap_host_state {
+    pub mark_dirty: unsafe extern "C" fn(host: *const clap_host),
+}
+
+// log and thread check
+
+pub type clap_log_severity = i32;
+
+pub const CLAP_LOG_DEBUG: clap_log_severity = 0;
+pub const CLAP_LOG_INFO: clap_log_severity = 1;
+pub const CLAP_LOG_WARNING: clap_log_severity = 2;
+pub const CLAP_LOG_ERROR: clap_log_severity = 3;
+pub const CLAP_LOG_FATAL: clap_log_severity = 4;
+pub const CLAP_LOG_HOST_MISBEHAVING: clap_log_severity = 5;
+pub const CLAP_LOG_PLUGIN_MISBEHAVING: clap_log_severity = 6;
+
+#[repr(C)]
+pub struct clap_host_log {
+    pub log: unsafe extern "C" fn(host: *const clap_host, severity: clap_log_severity, msg: *const c_char),
+}
+
+#[repr(C)]
+pub struct clap_host_thread_check {
+    pub is_main_thread: unsafe extern "C" fn(host: *const clap_host) -> bool,
+    pub is_audio_thread: unsafe extern "C" fn(host: *const clap_host) -> bool,
+}
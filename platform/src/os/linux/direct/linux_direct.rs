@@ -179,6 +179,7 @@ fn direct_event_callback(
                 if e.timer_id == 0 {
                     if SignalToUI::check_and_clear_ui_signal() {
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
@@ -0,0 +1,803 @@
+use {
+    std::cell::UnsafeCell,
+    std::collections::HashSet,
+    std::mem::MaybeUninit,
+    std::ffi::{CStr, CString},
+    std::os::raw::{c_char, c_int, c_ulong, c_void},
+    std::path::Path,
+    std::ptr,
+    std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+    std::sync::{Arc, Mutex, OnceLock},
+    std::thread::Thread,
+    std::time::{Duration, Instant},
+    self::super::{
+        jack_sys::*,
+        alsa_midi::InputSenders,
+    },
+    crate::{
+        makepad_live_id::*,
+        thread::SignalToUI,
+        audio::*,
+        midi::*,
+    }
+};
+
+// One JACK client per app, with ports under its name that patchbays show:
+// out_1 out_2 for the first audio output, in_1 in_2 for the first input,
+// out2_1 for the second and so on, midi_in_1 midi_out_1 per MIDI port in use.
+// Using a device connects those ports to it once, after that anyone can
+// repatch them. The devices are the other clients, one with audio inputs is
+// an output device and the other way around. Everything runs in the process
+// callback of the client, at the rate and period of the server, and that
+// callback never waits: the streams are handed to it whole, it skips a
+// callback someone else holds the lock of and MIDI passes through rings
+struct JackAudioDesc {
+    // the full port names of the device, in channel order
+    ports: Vec<String>,
+    desc: AudioDeviceDesc,
+}
+
+struct JackMidiDesc {
+    port: String,
+    desc: MidiPortDesc,
+}
+
+struct JackAudioStream {
+    device_id: AudioDeviceId,
+    index: usize,
+    ports: Vec<*mut jack_port_t>,
+    buffer: AudioBuffer,
+}
+
+#[derive(Clone, Copy)]
+struct JackMidiPort {
+    port_id: MidiPortId,
+    number: usize,
+    port: *mut jack_port_t,
+}
+
+// MIDI messages on their way in or out of the process callback, past that
+// they are dropped
+const MIDI_RING_SIZE: usize = 1024;
+
+// A queue of a fixed size between one thread that pushes and one that pops,
+// so MIDI gets in and out of the process callback without locks or allocations
+struct MidiRing<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    // how many were popped and pushed, the slot is that modulo the size
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+unsafe impl<T: Send> Sync for MidiRing<T> {}
+
+impl<T: Copy> MidiRing<T> {
+    fn new(size: usize) -> Self {
+        Self {
+            slots: (0..size).map( | _ | UnsafeCell::new(MaybeUninit::uninit())).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+    
+    // only from the one pushing thread, false when full
+    fn push(&self, value: T) -> bool {
+        let write = self.write.load(Ordering::Relaxed);
+        if write.wrapping_sub(self.read.load(Ordering::Acquire)) == self.slots.len() {
+            return false
+        }
+        unsafe {(*self.slots[write % self.slots.len()].get()).write(value)};
+        self.write.store(write.wrapping_add(1), Ordering::Release);
+        true
+    }
+    
+    // only from the one popping thread
+    fn peek(&self) -> Option<T> {
+        let read = self.read.load(Ordering::Relaxed);
+        if read == self.write.load(Ordering::Acquire) {
+            return None
+        }
+        Some(unsafe {(*self.slots[read % self.slots.len()].get()).assume_init()})
+    }
+    
+    fn pop(&self) -> Option<T> {
+        let value = self.peek() ?;
+        self.read.fetch_add(1, Ordering::Release);
+        Some(value)
+    }
+}
+
+#[derive(Default)]
+struct JackStreams {
+    audio_inputs: Vec<JackAudioStream>,
+    audio_outputs: Vec<JackAudioStream>,
+    midi_inputs: Vec<JackMidiPort>,
+    midi_outputs: Vec<JackMidiPort>,
+}
+
+impl JackStreams {
+    // a copy for the process callback, with buffers it doesn't have to resize
+    fn for_process(&self, frame_count: usize) -> Self {
+        let with_buffers = | streams: &Vec<JackAudioStream> | streams.iter().map( | v | JackAudioStream {
+            ports: v.ports.clone(),
+            buffer: AudioBuffer::new_with_size(frame_count, v.ports.len()),
+            ..*v
+        }).collect();
+        Self {
+            audio_inputs: with_buffers(&self.audio_inputs),
+            audio_outputs: with_buffers(&self.audio_outputs),
+            midi_inputs: self.midi_inputs.clone(),
+            midi_outputs: self.midi_outputs.clone(),
+        }
+    }
+    
+    fn resize_buffers(&mut self, frame_count: usize) {
+        for stream in self.audio_inputs.iter_mut().chain(self.audio_outputs.iter_mut()) {
+            stream.buffer.resize(frame_count, stream.ports.len());
+        }
+    }
+}
+
+fn drop_streams(streams: *mut JackStreams) {
+    if !streams.is_null() {
+        drop(unsafe {Box::from_raw(streams)});
+    }
+}
+
+/// What the client shares with the other backends, the signals it raises, the
+/// audio callbacks and where MIDI input goes
+#[derive(Clone, Default)]
+pub struct JackHooks {
+    pub audio_change: SignalToUI,
+    pub midi_change: SignalToUI,
+    pub xrun_signal: SignalToUI,
+    pub audio_input_cb: [Arc<Mutex<Option<AudioInputFn> > >; MAX_AUDIO_DEVICE_INDEX],
+    pub audio_output_cb: [Arc<Mutex<Option<AudioOutputFn> > >; MAX_AUDIO_DEVICE_INDEX],
+    pub midi_input_senders: InputSenders,
+}
+
+// What the callbacks of the client see. It lives as long as the client, which
+// is as long as the app, like the pulse context
+struct JackShared {
+    lib: &'static LibJack,
+    client: *mut jack_client_t,
+    sample_rate: f64,
+    hooks: JackHooks,
+    // the streams the process callback is on, only it and the buffer size
+    // callback touch them, the server doesn't run the two at once
+    streams: UnsafeCell<Box<JackStreams >>,
+    // the next streams from the ui thread, and the last ones for it to drop
+    next_streams: AtomicPtr<JackStreams>,
+    old_streams: AtomicPtr<JackStreams>,
+    buffer_size: AtomicUsize,
+    // input from the process callback, the forwarder thread passes it on to
+    // the senders, which the callback can't as sending may allocate
+    midi_input: MidiRing<(MidiPortId, MidiData, Option<AudioTime>)>,
+    midi_forwarder: OnceLock<Thread>,
+    // output with the frame to send it at, the lock is for the many senders
+    midi_output: MidiRing<(Option<MidiPortId>, MidiData, Option<jack_nframes_t>)>,
+    midi_output_lock: Mutex<()>,
+    xrun_count: AtomicUsize,
+    is_shutdown: AtomicBool,
+}
+
+// the process callback runs on a thread of the server, the pointers are only
+// handed back to the jack API, which can take them from any thread
+unsafe impl Send for JackShared {}
+unsafe impl Sync for JackShared {}
+
+pub struct JackAccess {
+    shared: Option<Arc<JackShared>>,
+    client_name: String,
+    audio_descs: Vec<JackAudioDesc>,
+    midi_descs: Vec<JackMidiDesc>,
+    // what the process callback has, without the buffers
+    streams: JackStreams,
+    // ports to unregister once the process callback has let go of them
+    stale_ports: Vec<*mut jack_port_t>,
+    failed_devices: HashSet<AudioDeviceId>,
+    reported_xruns: usize,
+}
+
+unsafe impl Send for JackAccess {}
+
+/// Sends MIDI out of the JACK ports, without going through the `JackAccess`
+/// lock
+#[derive(Clone)]
+pub struct JackMidiOutput(Arc<JackShared>);
+
+impl JackMidiOutput {
+    /// Sends at the start of the next cycle
+    pub fn send(&self, port_id: Option<MidiPortId>, data: MidiData) {
+        self.send_timed(port_id, data, None);
+    }
+    
+    /// Sends at the frame of `time.sample_time`, on the clock of
+    /// `AudioInfo::time`. What comes after it waits for it
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, time: Option<AudioTime>) {
+        if !self.0.is_shutdown.load(Ordering::Relaxed) {
+            let _lock = self.0.midi_output_lock.lock().unwrap();
+            self.0.midi_output.push((port_id, data, time.map( | t | t.sample_time as jack_nframes_t)));
+        }
+    }
+}
+
+// Whether there is a server to talk to, without loading libjack: the sockets
+// jackd puts in /dev/shm, or the one of the PipeWire that serves the JACK API
+fn server_is_running() -> bool {
+    let jackd = std::fs::read_dir("/dev/shm").map( | dir | {
+        dir.flatten().any( | entry | entry.file_name().to_string_lossy().starts_with("jack"))
+    }).unwrap_or(false);
+    let pipewire = std::env::var_os("PIPEWIRE_RUNTIME_DIR").or_else( || std::env::var_os("XDG_RUNTIME_DIR")).map( | dir | {
+        Path::new(&dir).join(std::env::var_os("PIPEWIRE_REMOTE").unwrap_or("pipewire-0".into())).exists()
+    }).unwrap_or(false);
+    jackd || pipewire
+}
+
+impl JackAccess {
+    /// The client of the app, None when there is no server. It is probed once,
+    /// apps on systems without JACK or PipeWire don't load libjack
+    pub fn new(hooks: JackHooks) -> Option<Arc<Mutex<Self >> > {
+        if !server_is_running() {
+            return None
+        }
+        let mut client_name = std::env::current_exe().ok()
+            .and_then( | path | Some(path.file_stem()?.to_string_lossy().to_string()))
+            .unwrap_or("makepad".into());
+        client_name.truncate(32);
+        Self::open(LibJack::get()?, &client_name, hooks)
+    }
+    
+    /// A client on the server behind `lib`, None when it can't connect
+    pub fn open(lib: &'static LibJack, client_name: &str, hooks: JackHooks) -> Option<Arc<Mutex<Self >> > {
+        let shared = unsafe {Self::open_client(lib, client_name, hooks)?};
+        // the server may have made the name unique
+        let client_name = unsafe {
+            CStr::from_ptr((lib.jack_get_client_name)(shared.client)).to_string_lossy().to_string()
+        };
+        shared.hooks.audio_change.set();
+        shared.hooks.midi_change.set();
+        Some(Arc::new(Mutex::new(Self {
+            shared: Some(shared),
+            client_name,
+            audio_descs: Vec::new(),
+            midi_descs: Vec::new(),
+            streams: JackStreams::default(),
+            stale_ports: Vec::new(),
+            failed_devices: Default::default(),
+            reported_xruns: 0,
+        })))
+    }
+    
+    unsafe fn open_client(lib: &'static LibJack, client_name: &str, hooks: JackHooks) -> Option<Arc<JackShared >> {
+        let name = CString::new(client_name).ok() ?;
+        let mut status = 0;
+        // don't start a jackd of our own when there is no server
+        let client = (lib.jack_client_open)(name.as_ptr(), JackNoStartServer, &mut status);
+        if client.is_null() {
+            return None
+        }
+        let buffer_size = (lib.jack_get_buffer_size)(client) as usize;
+        let shared = Arc::new(JackShared {
+            lib,
+            client,
+            sample_rate: (lib.jack_get_sample_rate)(client) as f64,
+            hooks,
+            streams: UnsafeCell::new(Box::new(JackStreams::default().for_process(buffer_size))),
+            next_streams: AtomicPtr::new(ptr::null_mut()),
+            old_streams: AtomicPtr::new(ptr::null_mut()),
+            buffer_size: AtomicUsize::new(buffer_size),
+            midi_input: MidiRing::new(MIDI_RING_SIZE),
+            midi_forwarder: OnceLock::new(),
+            midi_output: MidiRing::new(MIDI_RING_SIZE),
+            midi_output_lock: Mutex::new(()),
+            xrun_count: AtomicUsize::new(0),
+            is_shutdown: AtomicBool::new(false),
+        });
+        let forwarder = std::thread::spawn({
+            let shared = shared.clone();
+            move || shared.forward_midi_input()
+        });
+        let _ = shared.midi_forwarder.set(forwarder.thread().clone());
+        // the callbacks keep this reference for the life of the client
+        let arg = Arc::into_raw(shared.clone()) as *mut c_void;
+        (lib.jack_set_process_callback)(client, Self::process_callback, arg);
+        (lib.jack_set_buffer_size_callback)(client, Self::buffer_size_callback, arg);
+        (lib.jack_set_xrun_callback)(client, Self::xrun_callback, arg);
+        (lib.jack_set_port_registration_callback)(client, Self::port_registration_callback, arg);
+        (lib.jack_on_shutdown)(client, Self::shutdown_callback, arg);
+        if (lib.jack_activate)(client) != 0 {
+            println!("Cannot activate the JACK client");
+            shared.is_shutdown.store(true, Ordering::Relaxed);
+            (lib.jack_client_close)(client);
+            return None
+        }
+        Some(shared)
+    }
+    
+    unsafe extern "C" fn process_callback(nframes: jack_nframes_t, arg: *mut c_void) -> c_int {
+        let shared = &*(arg as *const JackShared);
+        shared.process(nframes);
+        0
+    }
+    
+    // not on the process thread and not in real time, so the buffers can grow
+    unsafe extern "C" fn buffer_size_callback(nframes: jack_nframes_t, arg: *mut c_void) -> c_int {
+        let shared = &*(arg as *const JackShared);
+        shared.buffer_size.store(nframes as usize, Ordering::Release);
+        shared.take_next_streams().resize_buffers(nframes as usize);
+        0
+    }
+    
+    unsafe extern "C" fn xrun_callback(arg: *mut c_void) -> c_int {
+        let shared = &*(arg as *const JackShared);
+        shared.xrun_count.fetch_add(1, Ordering::Relaxed);
+        shared.hooks.xrun_signal.set();
+        0
+    }
+    
+    unsafe extern "C" fn port_registration_callback(port_id: jack_port_id_t, _register: c_int, arg: *mut c_void) {
+        let shared = &*(arg as *const JackShared);
+        let lib = shared.lib;
+        let port = (lib.jack_port_by_id)(shared.client, port_id);
+        if port.is_null() {
+            shared.hooks.audio_change.set();
+            shared.hooks.midi_change.set();
+            return
+        }
+        // our own ports coming and going don't change the devices
+        if (lib.jack_port_is_mine)(shared.client, port) != 0 {
+            return
+        }
+        if CStr::from_ptr((lib.jack_port_type)(port)).to_bytes_with_nul() == JACK_DEFAULT_MIDI_TYPE.as_bytes() {
+            shared.hooks.midi_change.set();
+        }
+        else {
+            shared.hooks.audio_change.set();
+        }
+    }
+    
+    unsafe extern "C" fn shutdown_callback(arg: *mut c_void) {
+        let shared = &*(arg as *const JackShared);
+        shared.is_shutdown.store(true, Ordering::Relaxed);
+        shared.hooks.audio_change.set();
+        shared.hooks.midi_change.set();
+    }
+    
+    fn live_shared(&self) -> Option<Arc<JackShared >> {
+        self.shared.clone().filter( | shared | !shared.is_shutdown.load(Ordering::Relaxed))
+    }
+    
+    /// Where to send MIDI to the ports in use, None once the server is gone
+    pub fn midi_output(&self) -> Option<JackMidiOutput> {
+        Some(JackMidiOutput(self.live_shared()?))
+    }
+    
+    pub fn get_updated_audio_descs(&mut self) -> Vec<AudioDeviceDesc> {
+        self.audio_descs.clear();
+        let Some(shared) = self.live_shared() else {
+            return Vec::new()
+        };
+        let own_prefix = format!("{}:", self.client_name);
+        // a client with audio inputs is somewhere to play to
+        for (device_type, flags) in [(AudioDeviceType::Output, JackPortIsInput), (AudioDeviceType::Input, JackPortIsOutput)] {
+            let physical = unsafe {shared.get_ports(JACK_DEFAULT_AUDIO_TYPE, flags | JackPortIsPhysical)};
+            let mut default_client = None;
+            let mut clients: Vec<(String, Vec<String>)> = Vec::new();
+            for port in unsafe {shared.get_ports(JACK_DEFAULT_AUDIO_TYPE, flags)} {
+                if port.starts_with(&own_prefix) {
+                    continue;
+                }
+                let Some((client, _)) = port.split_once(':') else {continue};
+                let client = client.to_string();
+                if default_client.is_none() && physical.contains(&port) {
+                    default_client = Some(client.clone());
+                }
+                if let Some((_, ports)) = clients.iter_mut().find( | (name, _) | *name == client) {
+                    ports.push(port);
+                }
+                else {
+                    clients.push((client, vec![port]));
+                }
+            }
+            for (client, ports) in clients {
+                let device_id = LiveId::from_str(&format!("jack {} {:?}", client, device_type)).into();
+                self.audio_descs.push(JackAudioDesc {
+                    desc: AudioDeviceDesc {
+                        has_failed: self.failed_devices.contains(&device_id),
+                        device_id,
+                        device_type,
+                        is_default: default_client.as_ref() == Some(&client),
+                        channel_count: ports.len(),
+                        name: format!("[JACK] {}", client),
+                    },
+                    ports,
+                });
+            }
+        }
+        self.audio_descs.iter().map( | v | v.desc.clone()).collect()
+    }
+
+    pub fn get_updated_midi_descs(&mut self) -> Vec<MidiPortDesc> {
+        self.midi_descs.clear();
+        let Some(shared) = self.live_shared() else {
+            return Vec::new()
+        };
+        let own_prefix = format!("{}:", self.client_name);
+        // a port that sends MIDI is an input to us
+        for (port_type, flags, suffix) in [(MidiPortType::Input, JackPortIsOutput, "input"), (MidiPortType::Output, JackPortIsInput, "output")] {
+            for port in unsafe {shared.get_ports(JACK_DEFAULT_MIDI_TYPE, flags)} {
+                if port.starts_with(&own_prefix) {
+                    continue;
+                }
+                self.midi_descs.push(JackMidiDesc {
+                    desc: MidiPortDesc {
+                        port_id: LiveId::from_str(&format!("jack {} {}", port, suffix)).into(),
+                        name: format!("[JACK] {}", port),
+                        port_type,
+                    },
+                    port,
+                });
+            }
+        }
+        self.midi_descs.iter().map( | v | v.desc.clone()).collect()
+    }
+
+    /// The xruns since the last call, and all of them
+    pub fn take_xruns(&mut self) -> Option<AudioXrunEvent> {
+        let total = self.shared.as_ref()?.xrun_count.load(Ordering::Relaxed);
+        let count = total - self.reported_xruns;
+        self.reported_xruns = total;
+        if count == 0 {
+            return None
+        }
+        Some(AudioXrunEvent {count, total})
+    }
+
+    pub fn use_audio_inputs(&mut self, devices: &[AudioDeviceId]) {
+        self.use_audio_streams(devices, AudioDeviceType::Input);
+    }
+    
+    pub fn use_audio_outputs(&mut self, devices: &[AudioDeviceId]) {
+        self.use_audio_streams(devices, AudioDeviceType::Output);
+    }
+    
+    fn use_audio_streams(&mut self, devices: &[AudioDeviceId], device_type: AudioDeviceType) {
+        let Some(shared) = self.live_shared() else {return};
+        let streams = if device_type.is_output() {&mut self.streams.audio_outputs} else {&mut self.streams.audio_inputs};
+        let (keep, removed): (Vec<_>, Vec<_>) = std::mem::take(streams).into_iter().partition( | v | devices.contains(&v.device_id));
+        *streams = keep;
+        for (index, device_id) in devices.iter().enumerate() {
+            if streams.iter().any( | v | v.device_id == *device_id) {
+                continue;
+            }
+            let Some(desc) = self.audio_descs.iter().find( | v | v.desc.device_id == *device_id && v.desc.device_type == device_type) else {
+                continue
+            };
+            if let Some(stream) = unsafe {shared.register_audio_stream(index, desc)} {
+                streams.push(stream);
+            }
+            else {
+                self.failed_devices.insert(*device_id);
+                shared.hooks.audio_change.set();
+            }
+        }
+        self.stale_ports.extend(removed.into_iter().flat_map( | v | v.ports));
+        self.hand_over_streams(&shared);
+    }
+    
+    pub fn use_midi_inputs(&mut self, ports: &[MidiPortId]) {
+        self.use_midi_ports(ports, MidiPortType::Input);
+    }
+    
+    pub fn use_midi_outputs(&mut self, ports: &[MidiPortId]) {
+        self.use_midi_ports(ports, MidiPortType::Output);
+    }
+    
+    fn use_midi_ports(&mut self, port_ids: &[MidiPortId], port_type: MidiPortType) {
+        let Some(shared) = self.live_shared() else {return};
+        let ports = if port_type.is_input() {&mut self.streams.midi_inputs} else {&mut self.streams.midi_outputs};
+        let (keep, removed): (Vec<_>, Vec<_>) = std::mem::take(ports).into_iter().partition( | v | port_ids.contains(&v.port_id));
+        *ports = keep;
+        for port_id in port_ids {
+            if ports.iter().any( | v | v.port_id == *port_id) {
+                continue;
+            }
+            let Some(desc) = self.midi_descs.iter().find( | v | v.desc.port_id == *port_id && v.desc.port_type == port_type) else {
+                continue
+            };
+            // the first free number, so the names stay short
+            let mut number = 1;
+            while ports.iter().any( | v | v.number == number) {
+                number += 1;
+            }
+            if let Some(port) = unsafe {shared.register_midi_port(number, desc)} {
+                ports.push(port);
+            }
+        }
+        self.stale_ports.extend(removed.into_iter().map( | v | v.port));
+        self.hand_over_streams(&shared);
+    }
+    
+    // Gives the process callback a copy of the streams with buffers of the size
+    // it runs at, and unregisters the ports it no longer uses once it has let go
+    // of the streams before
+    fn hand_over_streams(&mut self, shared: &JackShared) {
+        loop {
+            let frame_count = shared.buffer_size.load(Ordering::Acquire);
+            drop_streams(shared.old_streams.swap(ptr::null_mut(), Ordering::AcqRel));
+            let next = Box::into_raw(Box::new(self.streams.for_process(frame_count)));
+            drop_streams(shared.next_streams.swap(next, Ordering::AcqRel));
+            // it takes them at the start of the next period, it only doesn't
+            // when the server is gone or stuck, and then the ports wait
+            let start = Instant::now();
+            loop {
+                let old = shared.old_streams.swap(ptr::null_mut(), Ordering::AcqRel);
+                if !old.is_null() {
+                    drop_streams(old);
+                    break
+                }
+                if shared.is_shutdown.load(Ordering::Relaxed) || start.elapsed() > Duration::from_secs(1) {
+                    return
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            // the size changed while they were made, they are skipped until
+            // they are made again
+            if shared.buffer_size.load(Ordering::Acquire) == frame_count {
+                break
+            }
+        }
+        for port in self.stale_ports.drain(..) {
+            unsafe {(shared.lib.jack_port_unregister)(shared.client, port)};
+        }
+    }
+}
+
+impl JackShared {
+    unsafe fn get_ports(&self, port_type: &str, flags: c_ulong) -> Vec<String> {
+        let list = (self.lib.jack_get_ports)(self.client, std::ptr::null(), port_type.as_ptr() as *const _, flags);
+        if list.is_null() {
+            return Vec::new()
+        }
+        let mut ports = Vec::new();
+        let mut i = 0;
+        while !(*list.add(i)).is_null() {
+            ports.push(CStr::from_ptr(*list.add(i)).to_string_lossy().to_string());
+            i += 1;
+        }
+        (self.lib.jack_free)(list as *mut c_void);
+        ports
+    }
+
+    unsafe fn register_port(&self, name: &str, port_type: &str, flags: c_ulong) -> Option<*mut jack_port_t> {
+        let name = CString::new(name).ok() ?;
+        let port = (self.lib.jack_port_register)(self.client, name.as_ptr(), port_type.as_ptr() as *const _, flags, 0);
+        if port.is_null() {
+            println!("Cannot register JACK port {:?}", name);
+            return None
+        }
+        Some(port)
+    }
+
+    unsafe fn connect(&self, from: *const c_char, to: *const c_char) {
+        // EEXIST is fine, someone else may have patched it already
+        (self.lib.jack_connect)(self.client, from, to);
+    }
+
+    unsafe fn register_audio_stream(&self, index: usize, desc: &JackAudioDesc) -> Option<JackAudioStream> {
+        let is_output = desc.desc.device_type.is_output();
+        let mut ports = Vec::new();
+        for channel in 0..2 {
+            let name = port_name(if is_output {"out"} else {"in"}, index, channel);
+            let Some(port) = self.register_port(&name, JACK_DEFAULT_AUDIO_TYPE, if is_output {JackPortIsOutput} else {JackPortIsInput}) else {
+                for port in ports {
+                    (self.lib.jack_port_unregister)(self.client, port);
+                }
+                return None
+            };
+            // a mono device gets both channels
+            let theirs = CString::new(desc.ports[channel.min(desc.ports.len() - 1)].as_str()).unwrap();
+            let ours = (self.lib.jack_port_name)(port);
+            if is_output {
+                self.connect(ours, theirs.as_ptr());
+            }
+            else {
+                self.connect(theirs.as_ptr(), ours);
+            }
+            ports.push(port);
+        }
+        Some(JackAudioStream {
+            device_id: desc.desc.device_id,
+            index,
+            ports,
+            buffer: AudioBuffer::default(),
+        })
+    }
+
+    unsafe fn register_midi_port(&self, number: usize, desc: &JackMidiDesc) -> Option<JackMidiPort> {
+        let is_input = desc.desc.port_type.is_input();
+        let name = format!("{}_{}", if is_input {"midi_in"} else {"midi_out"}, number);
+        let port = self.register_port(&name, JACK_DEFAULT_MIDI_TYPE, if is_input {JackPortIsInput} else {JackPortIsOutput}) ?;
+        let theirs = CString::new(desc.port.as_str()).unwrap();
+        let ours = (self.lib.jack_port_name)(port);
+        if is_input {
+            self.connect(theirs.as_ptr(), ours);
+        }
+        else {
+            self.connect(ours, theirs.as_ptr());
+        }
+        Some(JackMidiPort {
+            port_id: desc.desc.port_id,
+            number,
+            port,
+        })
+    }
+
+    // The streams to use this cycle, the next ones from the ui thread when it
+    // has picked up the ones before. Only the process and buffer size callbacks
+    // call this, and never at once
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn take_next_streams(&self) -> &mut JackStreams {
+        let streams = &mut *self.streams.get();
+        if self.old_streams.load(Ordering::Acquire).is_null() {
+            let next = self.next_streams.swap(ptr::null_mut(), Ordering::AcqRel);
+            if !next.is_null() {
+                let old = std::mem::replace(streams, Box::from_raw(next));
+                self.old_streams.store(Box::into_raw(old), Ordering::Release);
+            }
+        }
+        streams
+    }
+    
+    unsafe fn process(&self, nframes: jack_nframes_t) {
+        let lib = self.lib;
+        let frame_count = nframes as usize;
+        let frame_time = (lib.jack_last_frame_time)(self.client);
+        let time_at = | frame: jack_nframes_t | AudioTime {
+            sample_time: frame as f64,
+            host_time: (lib.jack_frames_to_time)(self.client, frame),
+            rate_scalar: 1.0,
+        };
+        let streams = self.take_next_streams();
+        
+        // inputs first, so passing input through to an output takes no extra cycle.
+        // streams made for another buffer size wait for the ones that are not
+        for input in &mut streams.audio_inputs {
+            if input.buffer.frame_count() != frame_count {
+                continue;
+            }
+            for (channel, port) in input.ports.iter().enumerate() {
+                let data = (lib.jack_port_get_buffer)(*port, nframes) as *const jack_default_audio_sample_t;
+                input.buffer.channel_mut(channel).copy_from_slice(std::slice::from_raw_parts(data, frame_count));
+            }
+            if let Ok(mut input_fn) = self.hooks.audio_input_cb[input.index].try_lock() {
+                if let Some(input_fn) = &mut *input_fn {
+                    input_fn(AudioInfo {
+                        device_id: input.device_id,
+                        time: Some(time_at(frame_time)),
+                        sample_rate: self.sample_rate,
+                    }, &input.buffer);
+                }
+            }
+        }
+        for output in &mut streams.audio_outputs {
+            // silence while the ui thread holds the callback
+            let mut output_fn = self.hooks.audio_output_cb[output.index].try_lock().ok();
+            let output_fn = output_fn.as_deref_mut().and_then( | f | f.as_mut()).filter( | _ | output.buffer.frame_count() == frame_count);
+            let is_playing = output_fn.is_some();
+            if let Some(output_fn) = output_fn {
+                output_fn(AudioInfo {
+                    device_id: output.device_id,
+                    time: Some(time_at(frame_time)),
+                    sample_rate: self.sample_rate,
+                }, &mut output.buffer);
+            }
+            for (channel, port) in output.ports.iter().enumerate() {
+                let data = (lib.jack_port_get_buffer)(*port, nframes) as *mut jack_default_audio_sample_t;
+                let data = std::slice::from_raw_parts_mut(data, frame_count);
+                if is_playing {
+                    data.copy_from_slice(output.buffer.channel(channel));
+                }
+                else {
+                    data.fill(0.0);
+                }
+            }
+        }
+        
+        // the events carry their frame in the cycle, on the clock the audio
+        // callbacks get in AudioInfo::time
+        let mut received = false;
+        for input in &streams.midi_inputs {
+            let buffer = (lib.jack_port_get_buffer)(input.port, nframes);
+            for i in 0..(lib.jack_midi_get_event_count)(buffer) {
+                let mut event: jack_midi_event_t = std::mem::zeroed();
+                if (lib.jack_midi_event_get)(&mut event, buffer, i) != 0 {
+                    continue;
+                }
+                // sysex doesn't fit in MidiData
+                if event.size == 0 || event.size > 3 || *event.buffer == 0xf0 {
+                    continue;
+                }
+                let mut data = MidiData {data: [0; 3]};
+                data.data[..event.size].copy_from_slice(std::slice::from_raw_parts(event.buffer, event.size));
+                // a full ring drops it, the forwarder hasn't run for long
+                if self.midi_input.push((input.port_id, data, Some(time_at(frame_time.wrapping_add(event.time))))) {
+                    received = true;
+                }
+            }
+        }
+        if received {
+            if let Some(forwarder) = self.midi_forwarder.get() {
+                forwarder.unpark();
+            }
+        }
+        
+        for output in &streams.midi_outputs {
+            (lib.jack_midi_clear_buffer)((lib.jack_port_get_buffer)(output.port, nframes));
+        }
+        // what was sent goes out at its frame, or at the start of this cycle
+        // when it has none or is late. Events in a buffer can't go back in time
+        let mut last_frame = 0;
+        while let Some((port_id, data, at)) = self.midi_output.peek() {
+            let frame = match at {
+                // counted from the start of the cycle, across the wrap of the clock
+                Some(at) => match at.wrapping_sub(frame_time) as i32 {
+                    offset if offset >= nframes as i32 => break,
+                    offset => offset.max(0) as jack_nframes_t
+                },
+                None => 0
+            }.max(last_frame);
+            last_frame = frame;
+            self.midi_output.pop();
+            let len = midi_message_len(data.data[0]);
+            if len == 0 {
+                continue;
+            }
+            for output in &streams.midi_outputs {
+                if port_id.is_none() || port_id == Some(output.port_id) {
+                    let buffer = (lib.jack_port_get_buffer)(output.port, nframes);
+                    (lib.jack_midi_event_write)(buffer, frame, data.data.as_ptr(), len);
+                }
+            }
+        }
+    }
+    
+    // hands the MIDI input of the process callback to the senders, woken up
+    // by the callback
+    fn forward_midi_input(&self) {
+        while !self.is_shutdown.load(Ordering::Relaxed) {
+            std::thread::park_timeout(Duration::from_millis(100));
+            if self.midi_input.peek().is_none() {
+                continue;
+            }
+            let mut senders = self.hooks.midi_input_senders.lock().unwrap();
+            while let Some(message) = self.midi_input.pop() {
+                senders.retain( | s | s.send(message).is_ok());
+            }
+            drop(senders);
+            // make sure our eventloop runs
+            SignalToUI::set_ui_signal();
+        }
+    }
+}
+
+// out_1 out_2 for the first stream, out2_1 out2_2 for the second
+fn port_name(prefix: &str, index: usize, channel: usize) -> String {
+    if index == 0 {
+        format!("{}_{}", prefix, channel + 1)
+    }
+    else {
+        format!("{}{}_{}", prefix, index + 1, channel + 1)
+    }
+}
+
+// MidiData is always 3 bytes, JACK wants the real length of the message
+fn midi_message_len(status: u8) -> usize {
+    match status {
+        0x80..=0xbf | 0xe0..=0xef | 0xf2 => 3,
+        0xc0..=0xdf | 0xf1 | 0xf3 => 2,
+        0xf6 | 0xf8..=0xff => 1,
+        _ => 0
+    }
+}
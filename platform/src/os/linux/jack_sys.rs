@@ -0,0 +1,133 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+// The JACK client API, after jack/jack.h, types.h and midiport.h. PipeWire
+// serves the same library through pipewire-jack. It is loaded at runtime like
+// libssl, so binaries run on systems without JACK
+
+use std::{
+    os::raw::{c_char, c_int, c_ulong, c_void},
+    sync::OnceLock,
+};
+use crate::module_loader::ModuleLoader;
+
+pub enum jack_client_t {}
+pub enum jack_port_t {}
+
+pub type jack_nframes_t = u32;
+pub type jack_time_t = u64;
+pub type jack_port_id_t = u32;
+pub type jack_options_t = c_int;
+pub type jack_status_t = c_int;
+pub type jack_default_audio_sample_t = f32;
+
+pub const JackNullOption: jack_options_t = 0x00;
+pub const JackNoStartServer: jack_options_t = 0x01;
+
+pub const JackPortIsInput: c_ulong = 0x1;
+pub const JackPortIsOutput: c_ulong = 0x2;
+pub const JackPortIsPhysical: c_ulong = 0x4;
+pub const JackPortCanMonitor: c_ulong = 0x8;
+pub const JackPortIsTerminal: c_ulong = 0x10;
+
+pub const JACK_DEFAULT_AUDIO_TYPE: &str = "32 bit float mono audio\0";
+pub const JACK_DEFAULT_MIDI_TYPE: &str = "8 bit raw midi\0";
+
+#[repr(C)]
+pub struct jack_midi_event_t {
+    pub time: jack_nframes_t,
+    pub size: usize,
+    pub buffer: *mut u8,
+}
+
+pub type JackProcessCallback = unsafe extern "C" fn(nframes: jack_nframes_t, arg: *mut c_void) -> c_int;
+pub type JackBufferSizeCallback = unsafe extern "C" fn(nframes: jack_nframes_t, arg: *mut c_void) -> c_int;
+pub type JackXRunCallback = unsafe extern "C" fn(arg: *mut c_void) -> c_int;
+pub type JackPortRegistrationCallback = unsafe extern "C" fn(port: jack_port_id_t, register: c_int, arg: *mut c_void);
+pub type JackClientRegistrationCallback = unsafe extern "C" fn(name: *const c_char, register: c_int, arg: *mut c_void);
+pub type JackShutdownCallback = unsafe extern "C" fn(arg: *mut c_void);
+
+pub struct LibJack {
+    pub jack_client_open: unsafe extern "C" fn(client_name: *const c_char, options: jack_options_t, status: *mut jack_status_t, ...) -> *mut jack_client_t,
+    pub jack_client_close: unsafe extern "C" fn(client: *mut jack_client_t) -> c_int,
+    pub jack_get_client_name: unsafe extern "C" fn(client: *mut jack_client_t) -> *mut c_char,
+    pub jack_activate: unsafe extern "C" fn(client: *mut jack_client_t) -> c_int,
+    pub jack_set_process_callback: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackProcessCallback, arg: *mut c_void) -> c_int,
+    pub jack_set_buffer_size_callback: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackBufferSizeCallback, arg: *mut c_void) -> c_int,
+    pub jack_set_xrun_callback: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackXRunCallback, arg: *mut c_void) -> c_int,
+    pub jack_set_port_registration_callback: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackPortRegistrationCallback, arg: *mut c_void) -> c_int,
+    pub jack_set_client_registration_callback: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackClientRegistrationCallback, arg: *mut c_void) -> c_int,
+    pub jack_on_shutdown: unsafe extern "C" fn(client: *mut jack_client_t, callback: JackShutdownCallback, arg: *mut c_void),
+    pub jack_get_sample_rate: unsafe extern "C" fn(client: *mut jack_client_t) -> jack_nframes_t,
+    pub jack_get_buffer_size: unsafe extern "C" fn(client: *mut jack_client_t) -> jack_nframes_t,
+    pub jack_port_register: unsafe extern "C" fn(client: *mut jack_client_t, port_name: *const c_char, port_type: *const c_char, flags: c_ulong, buffer_size: c_ulong) -> *mut jack_port_t,
+    pub jack_port_unregister: unsafe extern "C" fn(client: *mut jack_client_t, port: *mut jack_port_t) -> c_int,
+    pub jack_port_get_buffer: unsafe extern "C" fn(port: *mut jack_port_t, nframes: jack_nframes_t) -> *mut c_void,
+    pub jack_port_name: unsafe extern "C" fn(port: *const jack_port_t) -> *const c_char,
+    pub jack_port_type: unsafe extern "C" fn(port: *const jack_port_t) -> *const c_char,
+    pub jack_port_flags: unsafe extern "C" fn(port: *const jack_port_t) -> c_int,
+    pub jack_port_is_mine: unsafe extern "C" fn(client: *const jack_client_t, port: *const jack_port_t) -> c_int,
+    pub jack_port_by_id: unsafe extern "C" fn(client: *mut jack_client_t, port_id: jack_port_id_t) -> *mut jack_port_t,
+    pub jack_port_by_name: unsafe extern "C" fn(client: *mut jack_client_t, port_name: *const c_char) -> *mut jack_port_t,
+    pub jack_connect: unsafe extern "C" fn(client: *mut jack_client_t, source_port: *const c_char, destination_port: *const c_char) -> c_int,
+    pub jack_get_ports: unsafe extern "C" fn(client: *mut jack_client_t, port_name_pattern: *const c_char, type_name_pattern: *const c_char, flags: c_ulong) -> *mut *const c_char,
+    pub jack_free: unsafe extern "C" fn(ptr: *mut c_void),
+    pub jack_last_frame_time: unsafe extern "C" fn(client: *const jack_client_t) -> jack_nframes_t,
+    pub jack_frames_to_time: unsafe extern "C" fn(client: *const jack_client_t, frames: jack_nframes_t) -> jack_time_t,
+    pub jack_midi_get_event_count: unsafe extern "C" fn(port_buffer: *mut c_void) -> u32,
+    pub jack_midi_event_get: unsafe extern "C" fn(event: *mut jack_midi_event_t, port_buffer: *mut c_void, event_index: u32) -> c_int,
+    pub jack_midi_clear_buffer: unsafe extern "C" fn(port_buffer: *mut c_void),
+    pub jack_midi_event_write: unsafe extern "C" fn(port_buffer: *mut c_void, time: jack_nframes_t, data: *const u8, data_size: usize) -> c_int,
+    // keeps the library loaded, None for one made of functions like the fake
+    // server in the tests
+    pub module: Option<ModuleLoader>,
+}
+
+// the module handle is only used to keep the library loaded
+unsafe impl Send for LibJack {}
+unsafe impl Sync for LibJack {}
+
+impl LibJack {
+    fn try_load() -> Result<LibJack, ()> {
+        let module = ModuleLoader::load("libjack.so.0")
+            .or_else( | _ | ModuleLoader::load("libjack.so"))?;
+        Ok(LibJack {
+            jack_client_open: module.get_symbol("jack_client_open")?,
+            jack_client_close: module.get_symbol("jack_client_close")?,
+            jack_get_client_name: module.get_symbol("jack_get_client_name")?,
+            jack_activate: module.get_symbol("jack_activate")?,
+            jack_set_process_callback: module.get_symbol("jack_set_process_callback")?,
+            jack_set_buffer_size_callback: module.get_symbol("jack_set_buffer_size_callback")?,
+            jack_set_xrun_callback: module.get_symbol("jack_set_xrun_callback")?,
+            jack_set_port_registration_callback: module.get_symbol("jack_set_port_registration_callback")?,
+            jack_set_client_registration_callback: module.get_symbol("jack_set_client_registration_callback")?,
+            jack_on_shutdown: module.get_symbol("jack_on_shutdown")?,
+            jack_get_sample_rate: module.get_symbol("jack_get_sample_rate")?,
+            jack_get_buffer_size: module.get_symbol("jack_get_buffer_size")?,
+            jack_port_register: module.get_symbol("jack_port_register")?,
+            jack_port_unregister: module.get_symbol("jack_port_unregister")?,
+            jack_port_get_buffer: module.get_symbol("jack_port_get_buffer")?,
+            jack_port_name: module.get_symbol("jack_port_name")?,
+            jack_port_type: module.get_symbol("jack_port_type")?,
+            jack_port_flags: module.get_symbol("jack_port_flags")?,
+            jack_port_is_mine: module.get_symbol("jack_port_is_mine")?,
+            jack_port_by_id: module.get_symbol("jack_port_by_id")?,
+            jack_port_by_name: module.get_symbol("jack_port_by_name")?,
+            jack_connect: module.get_symbol("jack_connect")?,
+            jack_get_ports: module.get_symbol("jack_get_ports")?,
+            jack_free: module.get_symbol("jack_free")?,
+            jack_last_frame_time: module.get_symbol("jack_last_frame_time")?,
+            jack_frames_to_time: module.get_symbol("jack_frames_to_time")?,
+            jack_midi_get_event_count: module.get_symbol("jack_midi_get_event_count")?,
+            jack_midi_event_get: module.get_symbol("jack_midi_event_get")?,
+            jack_midi_clear_buffer: module.get_symbol("jack_midi_clear_buffer")?,
+            jack_midi_event_write: module.get_symbol("jack_midi_event_write")?,
+            module: Some(module),
+        })
+    }
+
+    pub fn get() -> Option<&'static LibJack> {
+        static LIB_JACK: OnceLock<Option<LibJack>> = OnceLock::new();
+        LIB_JACK.get_or_init( || LibJack::try_load().ok()).as_ref()
+    }
+}
@@ -5,6 +5,7 @@
         alsa_audio::AlsaAudioAccess,
         pulse_audio::PulseAudioAccess,
         alsa_midi::*,
+        jack::{JackAccess, JackHooks},
     },
     crate::{
         cx::Cx,
@@ -25,16 +26,27 @@ impl Cx {
             let mut descs = self.os.media.alsa_audio().lock().unwrap().get_updated_descs();
             let descs2 = self.os.media.pulse_audio().lock().unwrap().get_updated_descs();
             descs.extend(descs2);
+            if let Some(jack) = self.os.media.jack() {
+                descs.extend(jack.lock().unwrap().get_updated_audio_descs());
+            }
             self.call_event_handler(&Event::AudioDevices(AudioDevicesEvent {
                 descs
             }));
         }
         if self.os.media.alsa_midi_change.check_and_clear() {
-            let descs = self.os.media.alsa_midi().lock().unwrap().get_updated_descs();
+            let mut descs = self.os.media.alsa_midi().lock().unwrap().get_updated_descs();
+            if let Some(jack) = self.os.media.jack() {
+                descs.extend(jack.lock().unwrap().get_updated_midi_descs());
+            }
             self.call_event_handler(&Event::MidiPorts(MidiPortsEvent {
                 descs,
             }));
         }
+        if self.os.media.audio_xrun.check_and_clear() {
+            if let Some(e) = self.os.media.jack().and_then( | jack | jack.lock().unwrap().take_xruns()) {
+                self.call_event_handler(&Event::AudioXrun(e));
+            }
+        }
     }
 }
 
@@ -45,6 +57,9 @@ pub struct CxLinuxMedia {
     pub (crate) audio_change: SignalToUI,
     pub (crate) alsa_midi: Option<Arc<Mutex<AlsaMidiAccess >> >,
     pub (crate) alsa_midi_change: SignalToUI,
+    // probed once, None inside when there is no JACK server
+    pub (crate) jack: Option<Option<Arc<Mutex<JackAccess >> > >,
+    pub (crate) audio_xrun: SignalToUI,
 }
 
 impl CxLinuxMedia {
@@ -68,8 +83,22 @@ pub fn alsa_midi(&mut self) -> Arc<Mutex<AlsaMidiAccess >> {
         }
         self.alsa_midi.as_ref().unwrap().clone()
     }
-
-
+    
+    pub fn jack(&mut self) -> Option<Arc<Mutex<JackAccess >> > {
+        if self.jack.is_none() {
+            let alsa_audio = self.alsa_audio();
+            let alsa_audio = alsa_audio.lock().unwrap();
+            self.jack = Some(JackAccess::new(JackHooks {
+                audio_change: self.audio_change.clone(),
+                midi_change: self.alsa_midi_change.clone(),
+                xrun_signal: self.audio_xrun.clone(),
+                audio_input_cb: alsa_audio.audio_input_cb.clone(),
+                audio_output_cb: alsa_audio.audio_output_cb.clone(),
+                midi_input_senders: self.alsa_midi().lock().unwrap().input_senders.clone(),
+            }));
+        }
+        self.jack.clone().flatten()
+    }
 }
 
 impl CxMediaApi for Cx { 
@@ -79,7 +108,8 @@ fn midi_input(&mut self) -> MidiInput {
     }
     
     fn midi_output(&mut self) -> MidiOutput {
-        MidiOutput(Some(OsMidiOutput(self.os.media.alsa_midi())))
+        let jack = self.os.media.jack().and_then( | jack | jack.lock().unwrap().midi_output());
+        MidiOutput(Some(OsMidiOutput(self.os.media.alsa_midi(), jack)))
     }
     
     fn midi_reset(&mut self) {
@@ -87,20 +117,32 @@ fn midi_reset(&mut self) {
     
     fn use_midi_inputs(&mut self, ports: &[MidiPortId]) {
         self.os.media.alsa_midi().lock().unwrap().use_midi_inputs(ports);
+        if let Some(jack) = self.os.media.jack() {
+            jack.lock().unwrap().use_midi_inputs(ports);
+        }
     }
     
     fn use_midi_outputs(&mut self, ports: &[MidiPortId]) {
         self.os.media.alsa_midi().lock().unwrap().use_midi_outputs(ports);
+        if let Some(jack) = self.os.media.jack() {
+            jack.lock().unwrap().use_midi_outputs(ports);
+        }
     }
     
     fn use_audio_inputs(&mut self, devices: &[AudioDeviceId]) {
         self.os.media.alsa_audio().lock().unwrap().use_audio_inputs(devices);
         self.os.media.pulse_audio().lock().unwrap().use_audio_inputs(devices);
+        if let Some(jack) = self.os.media.jack() {
+            jack.lock().unwrap().use_audio_inputs(devices);
+        }
     }
     
     fn use_audio_outputs(&mut self, devices: &[AudioDeviceId]) {
         self.os.media.alsa_audio().lock().unwrap().use_audio_outputs(devices);
         self.os.media.pulse_audio().lock().unwrap().use_audio_outputs(devices);
+        if let Some(jack) = self.os.media.jack() {
+            jack.lock().unwrap().use_audio_outputs(devices);
+        }
     }
     
     fn audio_output_box(&mut self, index: usize, f: AudioOutputFn){
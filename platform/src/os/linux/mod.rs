@@ -51,8 +51,20 @@
 #[cfg(not(any(target_env="ohos", target_os="android")))]
 pub mod pulse_sys;
 
+#[cfg(not(any(target_env="ohos", target_os="android")))]
+pub mod jack_sys;
+#[cfg(not(any(target_env="ohos", target_os="android")))]
+pub mod jack;
+
+#[cfg(not(any(target_env="ohos", target_os="android")))]
+pub mod clap_sys;
+#[cfg(not(any(target_env="ohos", target_os="android")))]
+pub mod clap;
+
 #[cfg(not(target_os="android"))]
 mod web_socket;
+#[cfg(not(target_os="android"))]
+pub mod openssl_sys;
 
 #[cfg(target_os="android")]
 pub mod android;
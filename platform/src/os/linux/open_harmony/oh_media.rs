@@ -19,6 +19,10 @@ pub struct OsMidiOutput {
 impl OsMidiOutput {
     pub fn send(&self, _port_id: Option<MidiPortId>, _data: MidiData) {
     }
+    
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, _time: Option<crate::audio::AudioTime>) {
+        self.send(port_id, data)
+    }
 }
 
 pub struct OsMidiInput {
@@ -28,6 +32,11 @@ impl OsMidiInput {
     pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         None
     }
+    
+    // the messages here don't come with a time on an audio clock
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<crate::audio::AudioTime>)> {
+        self.receive().map( | (port_id, data) | (port_id, data, None))
+    }
 }
 
 
@@ -94,6 +94,7 @@ fn handle_other_events(&mut self) {
         // Signals
         if SignalToUI::check_and_clear_ui_signal() {
             self.handle_media_signals();
+            self.handle_network_signals();
             self.handle_script_signals();
             self.call_event_handler(&Event::Signal);
         }
@@ -0,0 +1,203 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+// The bits of libssl we need for a TLS client connection. It is loaded at
+// runtime like EGL, so binaries don't link against a specific openssl
+
+use std::{
+    ffi::CString,
+    io::{self, Read, Write},
+    net::TcpStream,
+    os::fd::AsRawFd,
+    os::raw::{c_char, c_int, c_long, c_void},
+    sync::OnceLock,
+};
+use crate::module_loader::ModuleLoader;
+use makepad_http::client::{HttpStream, HttpTlsConnector};
+
+pub enum SSL_METHOD {}
+pub enum SSL_CTX {}
+pub enum SSL {}
+
+const SSL_VERIFY_NONE: c_int = 0;
+const SSL_VERIFY_PEER: c_int = 1;
+const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+const TLSEXT_NAMETYPE_HOST_NAME: c_long = 0;
+const SSL_ERROR_WANT_READ: c_int = 2;
+const SSL_ERROR_WANT_WRITE: c_int = 3;
+const SSL_ERROR_SYSCALL: c_int = 5;
+const SSL_ERROR_ZERO_RETURN: c_int = 6;
+
+pub struct LibSsl {
+    pub TLS_client_method: unsafe extern "C" fn() -> *const SSL_METHOD,
+    pub SSL_CTX_new: unsafe extern "C" fn(method: *const SSL_METHOD) -> *mut SSL_CTX,
+    pub SSL_CTX_free: unsafe extern "C" fn(ctx: *mut SSL_CTX),
+    pub SSL_CTX_set_default_verify_paths: unsafe extern "C" fn(ctx: *mut SSL_CTX) -> c_int,
+    pub SSL_CTX_set_verify: unsafe extern "C" fn(ctx: *mut SSL_CTX, mode: c_int, callback: *const c_void),
+    pub SSL_new: unsafe extern "C" fn(ctx: *mut SSL_CTX) -> *mut SSL,
+    pub SSL_free: unsafe extern "C" fn(ssl: *mut SSL),
+    pub SSL_set_fd: unsafe extern "C" fn(ssl: *mut SSL, fd: c_int) -> c_int,
+    pub SSL_ctrl: unsafe extern "C" fn(ssl: *mut SSL, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long,
+    pub SSL_set1_host: unsafe extern "C" fn(ssl: *mut SSL, hostname: *const c_char) -> c_int,
+    pub SSL_connect: unsafe extern "C" fn(ssl: *mut SSL) -> c_int,
+    pub SSL_read: unsafe extern "C" fn(ssl: *mut SSL, buf: *mut c_void, num: c_int) -> c_int,
+    pub SSL_write: unsafe extern "C" fn(ssl: *mut SSL, buf: *const c_void, num: c_int) -> c_int,
+    pub SSL_get_error: unsafe extern "C" fn(ssl: *const SSL, ret: c_int) -> c_int,
+    pub SSL_shutdown: unsafe extern "C" fn(ssl: *mut SSL) -> c_int,
+    _keep_module_alive: ModuleLoader,
+}
+
+// the module handle is only used to keep the library loaded
+unsafe impl Send for LibSsl {}
+unsafe impl Sync for LibSsl {}
+
+impl LibSsl {
+    fn try_load() -> Result<LibSsl, ()> {
+        let module = ModuleLoader::load("libssl.so.3")
+            .or_else( | _ | ModuleLoader::load("libssl.so"))
+            .or_else( | _ | ModuleLoader::load("libssl.so.1.1"))?;
+        Ok(LibSsl {
+            TLS_client_method: module.get_symbol("TLS_client_method")?,
+            SSL_CTX_new: module.get_symbol("SSL_CTX_new")?,
+            SSL_CTX_free: module.get_symbol("SSL_CTX_free")?,
+            SSL_CTX_set_default_verify_paths: module.get_symbol("SSL_CTX_set_default_verify_paths")?,
+            SSL_CTX_set_verify: module.get_symbol("SSL_CTX_set_verify")?,
+            SSL_new: module.get_symbol("SSL_new")?,
+            SSL_free: module.get_symbol("SSL_free")?,
+            SSL_set_fd: module.get_symbol("SSL_set_fd")?,
+            SSL_ctrl: module.get_symbol("SSL_ctrl")?,
+            SSL_set1_host: module.get_symbol("SSL_set1_host")?,
+            SSL_connect: module.get_symbol("SSL_connect")?,
+            SSL_read: module.get_symbol("SSL_read")?,
+            SSL_write: module.get_symbol("SSL_write")?,
+            SSL_get_error: module.get_symbol("SSL_get_error")?,
+            SSL_shutdown: module.get_symbol("SSL_shutdown")?,
+            _keep_module_alive: module,
+        })
+    }
+
+    pub fn get() -> Option<&'static LibSsl> {
+        static LIB_SSL: OnceLock<Option<LibSsl>> = OnceLock::new();
+        LIB_SSL.get_or_init( || LibSsl::try_load().ok()).as_ref()
+    }
+}
+
+/// A TLS client connection over a TcpStream. Read timeouts set on the
+/// stream come out of `read` as WouldBlock
+pub struct TlsStream {
+    lib: &'static LibSsl,
+    ctx: *mut SSL_CTX,
+    ssl: *mut SSL,
+    stream: TcpStream,
+}
+
+// an SSL object can move between threads, it just can't be shared
+unsafe impl Send for TlsStream {}
+
+impl TlsStream {
+    /// Does the handshake, checking the certificate against the system
+    /// roots and `host` unless `verify` is false
+    pub fn connect(stream: TcpStream, host: &str, verify: bool) -> Result<TlsStream, String> {
+        let lib = LibSsl::get().ok_or_else( || "Cannot load libssl for a TLS connection".to_string())?;
+        let host_c = CString::new(host).map_err( | _ | "Invalid host name".to_string())?;
+        unsafe {
+            let ctx = (lib.SSL_CTX_new)((lib.TLS_client_method)());
+            if ctx.is_null() {
+                return Err("SSL_CTX_new failed".into())
+            }
+            // the ssl object copies the verify mode, so set up the context first
+            if verify {
+                (lib.SSL_CTX_set_default_verify_paths)(ctx);
+                (lib.SSL_CTX_set_verify)(ctx, SSL_VERIFY_PEER, std::ptr::null());
+            }
+            else {
+                (lib.SSL_CTX_set_verify)(ctx, SSL_VERIFY_NONE, std::ptr::null());
+            }
+            let ssl = (lib.SSL_new)(ctx);
+            // from here on drop frees both
+            let tls = TlsStream {lib, ctx, ssl, stream};
+            if ssl.is_null() {
+                return Err("SSL_new failed".into())
+            }
+            if verify && (lib.SSL_set1_host)(ssl, host_c.as_ptr()) != 1 {
+                return Err("SSL_set1_host failed".into())
+            }
+            // server name indication, so virtual hosts pick the right certificate
+            (lib.SSL_ctrl)(ssl, SSL_CTRL_SET_TLSEXT_HOSTNAME, TLSEXT_NAMETYPE_HOST_NAME, host_c.as_ptr() as *mut c_void);
+            if (lib.SSL_set_fd)(ssl, tls.stream.as_raw_fd()) != 1 {
+                return Err("SSL_set_fd failed".into())
+            }
+            if (lib.SSL_connect)(ssl) != 1 {
+                return Err(format!("TLS handshake with {} failed", host))
+            }
+            Ok(tls)
+        }
+    }
+
+    pub fn tcp_stream(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    fn error(&self, ret: c_int) -> io::Error {
+        match unsafe {(self.lib.SSL_get_error)(self.ssl, ret)} {
+            SSL_ERROR_WANT_READ | SSL_ERROR_WANT_WRITE => io::Error::from(io::ErrorKind::WouldBlock),
+            SSL_ERROR_SYSCALL => {
+                let os = io::Error::last_os_error();
+                if os.raw_os_error() == Some(0) {io::Error::from(io::ErrorKind::UnexpectedEof)} else {os}
+            }
+            e => io::Error::other(format!("TLS error {}", e))
+        }
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        let ret = unsafe {(self.lib.SSL_read)(self.ssl, buf.as_mut_ptr() as *mut c_void, len)};
+        if ret > 0 {
+            return Ok(ret as usize)
+        }
+        if unsafe {(self.lib.SSL_get_error)(self.ssl, ret)} == SSL_ERROR_ZERO_RETURN {
+            return Ok(0)
+        }
+        Err(self.error(ret))
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        let ret = unsafe {(self.lib.SSL_write)(self.ssl, buf.as_ptr() as *const c_void, len)};
+        if ret > 0 {
+            return Ok(ret as usize)
+        }
+        Err(self.error(ret))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.ssl.is_null() {
+                (self.lib.SSL_shutdown)(self.ssl);
+                (self.lib.SSL_free)(self.ssl);
+            }
+            (self.lib.SSL_CTX_free)(self.ctx);
+        }
+    }
+}
+
+/// Gives the makepad-http client https through libssl
+pub struct OpensslConnector;
+
+impl HttpTlsConnector for OpensslConnector {
+    fn connect(&self, stream: TcpStream, host: &str, verify: bool) -> Result<Box<dyn HttpStream>, String> {
+        Ok(Box::new(TlsStream::connect(stream, host, verify)?))
+    }
+}
@@ -21,6 +21,7 @@
 
 struct PulseAudioDesc {
     name: String,
+    sample_rate: u32,
     desc: AudioDeviceDesc,
 }
 /*
@@ -47,16 +48,17 @@ struct PulseInputStream {
 struct PulseInputStruct {
     device_id: AudioDeviceId,
     input_fn: Arc<Mutex<Option<AudioInputFn> > >,
+    sample_rate: f64,
     audio_buffer: AudioBuffer,
     ready_state: AtomicU32,
 }
 
 impl PulseInputStream {
-    unsafe fn new(device_id: AudioDeviceId, name: &str, index: usize, pulse: &PulseAudioAccess) -> PulseInputStream {
+    unsafe fn new(device_id: AudioDeviceId, name: &str, sample_rate: u32, index: usize, pulse: &PulseAudioAccess) -> PulseInputStream {
         pa_threaded_mainloop_lock(pulse.main_loop);
         let sample_spec = pa_sample_spec {
             format: PA_SAMPLE_FLOAT32LE,
-            rate: 48000,
+            rate: sample_rate,
             channels: 2
         };
         
@@ -68,6 +70,7 @@ unsafe fn new(device_id: AudioDeviceId, name: &str, index: usize, pulse: &PulseA
             device_id,
             ready_state: AtomicU32::new(0),
             input_fn: pulse.audio_input_cb[index].clone(),
+            sample_rate: sample_rate as f64,
             audio_buffer: AudioBuffer::default()
         }));
         pa_stream_set_state_callback(stream, Some(Self::recording_stream_state_callback), input_ptr as *mut _);
@@ -139,7 +142,7 @@ pub unsafe fn terminate(self, pulse: &PulseAudioAccess) {
             input_fn(AudioInfo {
                 device_id: input.device_id,
                 time: None,
-                sample_rate: 48000.0,
+                sample_rate: input.sample_rate,
             }, &input.audio_buffer);
         }        
         pa_stream_drop(stream);
@@ -176,6 +179,7 @@ struct PulseOutputStream {
 struct PulseOutputStruct {
     device_id: AudioDeviceId,
     output_fn: Arc<Mutex<Option<AudioOutputFn> > >,
+    sample_rate: f64,
     write_byte_count: usize,
     clear_on_read: bool,
     ready_state: AtomicU32,
@@ -183,12 +187,12 @@ struct PulseOutputStruct {
 }
 
 impl PulseOutputStream {
-    unsafe fn new(device_id: AudioDeviceId, name: &str, index: usize, pulse: &PulseAudioAccess) -> Option<PulseOutputStream> {
+    unsafe fn new(device_id: AudioDeviceId, name: &str, sample_rate: u32, index: usize, pulse: &PulseAudioAccess) -> Option<PulseOutputStream> {
         
         pa_threaded_mainloop_lock(pulse.main_loop);
         let sample_spec = pa_sample_spec {
             format: PA_SAMPLE_FLOAT32LE,
-            rate: 48000,
+            rate: sample_rate,
             channels: 2
         };
         
@@ -201,6 +205,7 @@ unsafe fn new(device_id: AudioDeviceId, name: &str, index: usize, pulse: &PulseA
             device_id,
             clear_on_read: true,
             output_fn: pulse.audio_output_cb[index].clone(),
+            sample_rate: sample_rate as f64,
             write_byte_count: 0,
             ready_state: AtomicU32::new(0),
             audio_buffer: AudioBuffer::default()
@@ -287,7 +292,7 @@ pub unsafe fn terminate_stream(stream:*mut pa_stream, pulse: &PulseAudioAccess)
                 output_fn(AudioInfo {
                     device_id: output.device_id,
                     time: None,
-                    sample_rate: 48000.0,
+                    sample_rate: output.sample_rate,
                 }, &mut output.audio_buffer);
                 // lets copy it to interleaved format
                 let interleaved = std::slice::from_raw_parts_mut(write_ptr as *mut f32, output.write_byte_count / 4);
@@ -357,6 +362,7 @@ pub struct PulseAudioAccess {
 struct PulseDeviceDesc {
     name: String,
     description: String,
+    sample_rate: u32,
     _index: u32,
 }
 
@@ -475,6 +481,7 @@ pub fn destroy(&self){
         query.sink_list.push(PulseDeviceDesc {
             name: CStr::from_ptr((*info).name).to_str().unwrap().to_string(),
             description: CStr::from_ptr((*info).description).to_str().unwrap().to_string(),
+            sample_rate: (*info).sample_spec.rate,
             _index: (*info).index
         })
     }
@@ -493,6 +500,7 @@ pub fn destroy(&self){
         query.source_list.push(PulseDeviceDesc {
             name: CStr::from_ptr((*info).name).to_str().unwrap().to_string(),
             description: CStr::from_ptr((*info).description).to_str().unwrap().to_string(),
+            sample_rate: (*info).sample_spec.rate,
             _index: (*info).index
         })
     }
@@ -538,6 +546,7 @@ pub fn get_updated_descs(&mut self) -> Vec<AudioDeviceDesc> {
                 });
                 device_descs.push(PulseAudioDesc {
                     name: source.name.clone(),
+                    sample_rate: source.sample_rate,
                     desc: out.last().cloned().unwrap()
                 });
             }
@@ -553,6 +562,7 @@ pub fn get_updated_descs(&mut self) -> Vec<AudioDeviceDesc> {
                 });
                 device_descs.push(PulseAudioDesc {
                     name: sink.name.clone(),
+                    sample_rate: sink.sample_rate,
                     desc: out.last().cloned().unwrap()
                 });
             }
@@ -579,15 +589,15 @@ pub fn use_audio_inputs(&mut self, devices: &[AudioDeviceId]) {
             for (index, device_id) in devices.iter().enumerate() {
                 if self.audio_outputs.iter().find( | v | v.device_id == *device_id).is_none() {
                     if let Some(v) = self.device_descs.iter().find( | v | v.desc.device_id == *device_id) {
-                        new.push((index, *device_id, &v.name))
+                        new.push((index, *device_id, &v.name, v.sample_rate))
                     }
                 }
             }
             new
             
         };
-        for (index, device_id, name) in new {
-            let new_input = unsafe {PulseInputStream::new(device_id, name, index, self)};
+        for (index, device_id, name, sample_rate) in new {
+            let new_input = unsafe {PulseInputStream::new(device_id, name, sample_rate, index, self)};
             self.audio_inputs.push(new_input);
         }
     }
@@ -609,15 +619,15 @@ pub fn use_audio_outputs(&mut self, devices: &[AudioDeviceId]) {
             for (index, device_id) in devices.iter().enumerate() {
                 if self.audio_outputs.iter().find( | v | v.device_id == *device_id).is_none() {
                     if let Some(v) = self.device_descs.iter().find( | v | v.desc.device_id == *device_id) {
-                        new.push((index, *device_id, &v.name))
+                        new.push((index, *device_id, &v.name, v.sample_rate))
                     }
                 }
             }
             new
             
         };
-        for (index, device_id, name) in new {
-            if let Some(new_output) = unsafe {PulseOutputStream::new(device_id, name, index, self)}{
+        for (index, device_id, name, sample_rate) in new {
+            if let Some(new_output) = unsafe {PulseOutputStream::new(device_id, name, sample_rate, index, self)}{
                 self.audio_outputs.push(new_output);
             }
             else{
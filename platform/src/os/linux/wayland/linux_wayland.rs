@@ -227,6 +227,7 @@ fn state_event_callback(&mut self, state: &mut WaylandState, event: XlibEvent) -
                 if e.timer_id == 0{
                     if SignalToUI::check_and_clear_ui_signal(){
                         cx.handle_media_signals();
+                        cx.handle_network_signals();
                         cx.call_event_handler(&Event::Signal);
                     }
                     cx.handle_action_receiver();
@@ -1,11 +1,60 @@
-
 use crate::event::HttpRequest;
 use crate::web_socket::{WebSocketMessage};
-use std::sync::mpsc::{channel, Sender};
+use crate::os::linux::openssl_sys::TlsStream;
+use std::sync::mpsc::{channel, Sender, Receiver, TryRecvError};
 use std::net::{TcpStream, Shutdown};
-use std::io::{Read};
-use makepad_http::utils::write_bytes_to_tcp_stream_no_error;
-use makepad_http::websocket::{ServerWebSocket, ServerWebSocketMessageFormat, ServerWebSocketMessageHeader, ServerWebSocketMessage, SERVER_WEB_SOCKET_PONG_MESSAGE};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+use makepad_http::websocket::*;
+use makepad_http::websocket_deflate::{WebSocketDeflate, WebSocketDeflateParams};
+
+// how long we wait on the socket before looking at the outgoing queue
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// ping the server when it has been quiet this long, give up after twice that
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// how long we wait for the server to answer our close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+// don't bother compressing tiny messages
+const DEFLATE_MIN_SIZE: usize = 64;
+
+enum ClientStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream>),
+}
+
+impl ClientStream {
+    fn tcp_stream(&self) -> &TcpStream {
+        match self {
+            ClientStream::Plain(stream) => stream,
+            ClientStream::Tls(stream) => stream.tcp_stream(),
+        }
+    }
+}
+
+impl Read for ClientStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Plain(stream) => stream.read(buf),
+            ClientStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Plain(stream) => stream.write(buf),
+            ClientStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ClientStream::Plain(stream) => stream.flush(),
+            ClientStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
 
 pub struct OsWebSocket{
     sender: Option<Sender<WebSocketMessage>>,
@@ -14,10 +63,8 @@ pub struct OsWebSocket{
 
 impl Drop for OsWebSocket{
     fn drop(&mut self){
+        // dropping the sender makes the io thread say goodbye to the server
         self.sender.take();
-        if let Some(stream) = self.stream.take(){
-            stream.shutdown(Shutdown::Both).ok();
-        }
     }
 }
 
@@ -32,115 +79,270 @@ pub fn send_message(&mut self, message:WebSocketMessage)->Result<(),()>{
         }
         Err(())
     }
-            
+
     pub fn close(&mut self){
+        if let Some(sender) = self.sender.take(){
+            if sender.send(WebSocketMessage::Close{code: CLOSE_NORMAL, reason: String::new()}).is_err(){
+                // the io thread is gone already
+                if let Some(stream) = self.stream.take(){
+                    stream.shutdown(Shutdown::Both).ok();
+                }
+            }
+        }
     }
-            
-    pub fn open(_socket_id:u64, request: HttpRequest, rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
-        // parse the url
+
+    fn connect(request: &HttpRequest)->Result<(ClientStream, Vec<u8>, Option<WebSocketDeflateParams>), String>{
         let split = request.split_url();
-        // strip off any hashes
-        // alright we have proto, host, port and hash now
-        // lets open a tcpstream
-        let stream = TcpStream::connect(format!("{}:{}", split.host, split.port));
-        // alright lets construct a http request
-        // lets join the headers
-                        
-        let mut http_request = format!("GET /{} HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: SxJdXBRtW7Q4awLDhflO0Q==\r\n", split.file, split.host);
-        http_request.push_str(&request.get_headers_string());
-        http_request.push_str("\r\n"); 
-                        
-        // lets write the http request
-        if stream.is_err(){
-            rx_sender.send(WebSocketMessage::Error("Error connecting websocket tcpstream".into())).unwrap();
-            return OsWebSocket{sender:None, stream:None}
-        }
-        let mut stream = stream.unwrap();
-        if write_bytes_to_tcp_stream_no_error(&mut stream, http_request.as_bytes()){
-            rx_sender.send(WebSocketMessage::Error("Error writing request to websocket".into())).unwrap();
+        let stream = TcpStream::connect(format!("{}:{}", split.host, split.port))
+            .map_err(|e| format!("Error connecting websocket tcpstream: {}", e))?;
+        stream.set_nodelay(true).ok();
+        let mut stream = match split.proto{
+            "wss" | "https" => ClientStream::Tls(Box::new(TlsStream::connect(stream, split.host, !request.ignore_ssl_cert)?)),
+            _ => ClientStream::Plain(stream)
+        };
+
+        let handshake = ClientWebSocketHandshake::new();
+        let http_request = handshake.request(split.host, split.file, &request.get_headers_string(), true);
+        stream.write_all(http_request.as_bytes())
+            .map_err(|e| format!("Error writing request to websocket: {}", e))?;
+        let (head, rest) = read_http_head(&mut stream)?;
+        let deflate = handshake.check_response(&head)?;
+        Ok((stream, rest, deflate))
+    }
+
+    pub fn open(_socket_id:u64, request: HttpRequest, rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
+        let (stream, rest, deflate) = match Self::connect(&request){
+            Ok(connection) => connection,
+            Err(e) => {
+                let _ = rx_sender.send(WebSocketMessage::Error(e));
+                return OsWebSocket{sender:None, stream:None}
+            }
+        };
+        // a short read timeout lets one thread do both reading and writing,
+        // which a tls stream needs as it can't be split
+        if stream.tcp_stream().set_read_timeout(Some(POLL_INTERVAL)).is_err(){
+            let _ = rx_sender.send(WebSocketMessage::Error("Cannot set websocket read timeout".into()));
             return OsWebSocket{sender:None, stream:None}
         }
-                        
-        // lets start the thread
-        let mut input_stream = stream.try_clone().unwrap();
-        let mut output_stream = stream.try_clone().unwrap();
+        let tcp_stream = stream.tcp_stream().try_clone().ok();
+        let _ = rx_sender.send(WebSocketMessage::Opened);
+
         let (sender, receiver) = channel();
-                        
-        let _writer_thread = std::thread::spawn(move || {
-            while let Ok(msg) = receiver.recv(){
-                match msg{
-                    WebSocketMessage::Binary(data)=>{
-                        let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                        if write_bytes_to_tcp_stream_no_error(&mut output_stream, header.as_slice()) ||
-                        write_bytes_to_tcp_stream_no_error(&mut output_stream, &data){
-                            break;
-                        }
+        std::thread::spawn(move || {
+            let mut io = WebSocketIo{
+                stream,
+                web_socket: ServerWebSocket::new(),
+                deflate: deflate.map(|d| d.client_deflate()),
+                rx_sender,
+                last_received: Instant::now(),
+                ping_sent: false,
+                closing: None,
+                done: false,
+            };
+            io.web_socket.set_inflate(deflate.map(|d| d.client_inflate()));
+            // the server may have sent frames right behind its handshake
+            io.parse(&rest);
+            io.run(receiver);
+        });
+        OsWebSocket{sender:Some(sender), stream:tcp_stream}
+    }
+}
+
+struct WebSocketIo{
+    stream: ClientStream,
+    web_socket: ServerWebSocket,
+    deflate: Option<WebSocketDeflate>,
+    rx_sender: Sender<WebSocketMessage>,
+    last_received: Instant,
+    ping_sent: bool,
+    // when we sent our close frame
+    closing: Option<Instant>,
+    done: bool,
+}
+
+impl WebSocketIo{
+    fn run(&mut self, receiver: Receiver<WebSocketMessage>){
+        let mut buffer = vec![0u8; 65536];
+        while !self.done{
+            // everything the app queued up
+            while self.closing.is_none(){
+                match receiver.try_recv(){
+                    Ok(message) => self.send(message),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.send(WebSocketMessage::Close{code: CLOSE_GOING_AWAY, reason: String::new()});
                     }
-                    WebSocketMessage::String(data)=>{
-                        let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                        if write_bytes_to_tcp_stream_no_error(&mut output_stream, header.as_slice()) ||
-                        write_bytes_to_tcp_stream_no_error(&mut output_stream, &data.as_bytes()){
-                            break;
-                        }
+                }
+            }
+            if self.done{
+                break
+            }
+            match self.stream.read(&mut buffer){
+                Ok(0) => {
+                    if self.closing.is_none(){
+                        let _ = self.rx_sender.send(WebSocketMessage::Error("Websocket connection closed without a close frame".into()));
                     }
-                    _=>{
-                        crate::error!("WebSocketMessage of this type sending not implemented");
+                    self.finish();
+                }
+                Ok(n) => {
+                    self.last_received = Instant::now();
+                    self.ping_sent = false;
+                    self.parse(&buffer[0..n]);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    self.idle();
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    if self.closing.is_none(){
+                        let _ = self.rx_sender.send(WebSocketMessage::Error(format!("Websocket read failed: {}", e)));
                     }
+                    self.finish();
                 }
             }
-        });
-                        
-        let _reader_thread = std::thread::spawn(move || {
-            let mut web_socket = ServerWebSocket::new();
-            let mut done = false;
-            let mut first = true;
-            while !done {
-                let mut buffer = [0u8; 65535];
-                match input_stream.read(&mut buffer) {
-                    Ok(bytes_read) => {
-                        if first{
-                            first = false;
-                            continue;
-                        }
-                        web_socket.parse(&buffer[0..bytes_read], | result | {
-                            match result {
-                                Ok(ServerWebSocketMessage::Ping(_)) => {
-                                    if write_bytes_to_tcp_stream_no_error(&mut input_stream, &SERVER_WEB_SOCKET_PONG_MESSAGE){
-                                        done = true;
-                                        let _ = rx_sender.send(WebSocketMessage::Error("Pong message send failed".into()));
-                                    }
-                                },
-                                Ok(ServerWebSocketMessage::Pong(_)) => {
-                                },
-                                Ok(ServerWebSocketMessage::Text(text)) => {
-                                    if rx_sender.send(WebSocketMessage::String(text.into())).is_err(){
-                                        done = true;
-                                    };
-                                },
-                                Ok(ServerWebSocketMessage::Binary(data)) => {
-                                    if rx_sender.send(WebSocketMessage::Binary(data.into())).is_err(){
-                                        done = true;
-                                    };
-                                },
-                                Ok(ServerWebSocketMessage::Close) => {
-                                    let _ = rx_sender.send(WebSocketMessage::Closed);
-                                    done = true;
-                                },
-                                Err(e) => {
-                                    eprintln!("Websocket error {:?}", e);
-                                }
-                            }
-                        });                            
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to receive data: {}", e);
-                        let _ = rx_sender.send(WebSocketMessage::Closed);
-                        done = true;
+        }
+        self.stream.tcp_stream().shutdown(Shutdown::Both).ok();
+    }
+
+    fn idle(&mut self){
+        let quiet = self.last_received.elapsed();
+        if let Some(closing) = self.closing{
+            // the server didn't answer our close, don't wait forever
+            if closing.elapsed() > CLOSE_TIMEOUT{
+                self.finish();
+            }
+        }
+        else if quiet > KEEPALIVE_INTERVAL * 2{
+            let _ = self.rx_sender.send(WebSocketMessage::Error("Websocket server stopped answering pings".into()));
+            self.finish();
+        }
+        else if quiet > KEEPALIVE_INTERVAL && !self.ping_sent{
+            self.ping_sent = true;
+            self.write_frame(ServerWebSocketMessageFormat::Ping, &[], false);
+        }
+    }
+
+    fn send(&mut self, message: WebSocketMessage){
+        match message{
+            WebSocketMessage::Binary(data) => {
+                self.write_frame(ServerWebSocketMessageFormat::Binary, &data, true);
+            }
+            WebSocketMessage::String(data) => {
+                self.write_frame(ServerWebSocketMessageFormat::Text, data.as_bytes(), true);
+            }
+            WebSocketMessage::Close{code, reason} => {
+                let frame = ServerWebSocket::build_close(Some(code), &reason, true);
+                self.write(&frame);
+                self.closing = Some(Instant::now());
+            }
+            _ => {
+                crate::error!("WebSocketMessage of this type sending not implemented");
+            }
+        }
+    }
+
+    fn write_frame(&mut self, format: ServerWebSocketMessageFormat, data: &[u8], compress: bool){
+        let compress = compress && data.len() > DEFLATE_MIN_SIZE;
+        let frame = match &mut self.deflate{
+            Some(deflate) if compress => {
+                let data = deflate.compress(data);
+                let mut header = ServerWebSocketMessageHeader::from_len(data.len(), format, true);
+                header.set_compressed();
+                ServerWebSocket::build_message(header, &data)
+            }
+            _ => {
+                let header = ServerWebSocketMessageHeader::from_len(data.len(), format, true);
+                ServerWebSocket::build_message(header, data)
+            }
+        };
+        self.write(&frame);
+    }
+
+    fn write(&mut self, frame: &[u8]){
+        if self.done{
+            return
+        }
+        // a TLS write that has to read first, like for a key update, runs into
+        // the poll timeout of the socket and has to be made again
+        let mut written = 0;
+        let mut blocked_since = None;
+        while written < frame.len(){
+            let error = match self.stream.write(&frame[written..]){
+                Ok(0) => io::Error::from(io::ErrorKind::WriteZero),
+                Ok(n) => {
+                    written += n;
+                    blocked_since = None;
+                    continue
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if blocked_since.get_or_insert_with(Instant::now).elapsed() < KEEPALIVE_INTERVAL * 2{
+                        continue
                     }
+                    e
+                }
+                Err(e) => e
+            };
+            let _ = self.rx_sender.send(WebSocketMessage::Error(format!("Websocket write failed: {}", error)));
+            self.finish();
+            return
+        }
+    }
+
+    fn finish(&mut self){
+        if !self.done{
+            self.done = true;
+            let _ = self.rx_sender.send(WebSocketMessage::Closed);
+        }
+    }
+
+    fn parse(&mut self, data: &[u8]){
+        // the parser borrows its output, so collect what to do first
+        let mut replies = Vec::new();
+        let mut close = None;
+        let mut error = None;
+        let rx_sender = &self.rx_sender;
+        let mut app_gone = false;
+        self.web_socket.parse(data, | result | {
+            match result {
+                Ok(ServerWebSocketMessage::Ping(data)) => {
+                    let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Pong, true);
+                    replies.push(ServerWebSocket::build_message(header, data));
+                },
+                Ok(ServerWebSocketMessage::Pong(_)) => {
+                },
+                Ok(ServerWebSocketMessage::Text(text)) => {
+                    app_gone |= rx_sender.send(WebSocketMessage::String(text.into())).is_err();
+                },
+                Ok(ServerWebSocketMessage::Binary(data)) => {
+                    app_gone |= rx_sender.send(WebSocketMessage::Binary(data.into())).is_err();
+                },
+                Ok(ServerWebSocketMessage::Close{code, reason}) => {
+                    close = Some((code, reason.to_string()));
+                },
+                Err(e) => {
+                    error = Some((e.close_code(), format!("Websocket error {:?}", e)));
                 }
             }
         });
-                                
-        OsWebSocket{sender:Some(sender), stream:Some(stream)}
+        for reply in replies{
+            self.write(&reply);
+        }
+        if let Some((code, reason)) = close{
+            // echo the close unless it answers ours
+            if self.closing.is_none(){
+                self.write(&ServerWebSocket::build_close(code, "", true));
+            }
+            let _ = self.rx_sender.send(WebSocketMessage::Close{code: code.unwrap_or(1005), reason});
+            self.finish();
+        }
+        else if let Some((code, message)) = error{
+            self.write(&ServerWebSocket::build_close(Some(code), "", true));
+            let _ = self.rx_sender.send(WebSocketMessage::Error(message));
+            self.finish();
+        }
+        else if app_gone && self.closing.is_none(){
+            self.send(WebSocketMessage::Close{code: CLOSE_GOING_AWAY, reason: String::new()});
+        }
     }
-}
\ No newline at end of file
+}
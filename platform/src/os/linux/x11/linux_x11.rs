@@ -234,6 +234,7 @@ fn xlib_event_callback(
                 if e.timer_id == 0{
                     if SignalToUI::check_and_clear_ui_signal(){
                         cx.handle_media_signals();
+                        cx.handle_network_signals();
                         self.handle_script_signals();
                         cx.call_event_handler(&Event::Signal);
                     }
@@ -185,6 +185,7 @@ pub fn process_to_wasm(&mut self, msg_ptr: u32) -> u32 {
                     let tw = ToWasmSignal::read_to_wasm(&mut to_wasm);
                     if tw.flags & 1 != 0{
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
@@ -369,8 +370,7 @@ pub fn process_to_wasm(&mut self, msg_ptr: u32) -> u32 {
         }
 
         if network_responses.len() != 0 {
-            self.handle_script_network_events(&network_responses);
-            self.call_event_handler(&Event::NetworkResponses(network_responses));
+            self.handle_network_responses(network_responses);
         }
         
         if self.handle_live_edit(){ 
@@ -380,6 +380,7 @@ pub fn process_to_wasm(&mut self, msg_ptr: u32) -> u32 {
         
         self.handle_platform_ops();
         self.handle_media_signals();
+        self.handle_network_signals();
 
         if self.any_passes_dirty() || self.need_redrawing() || self.new_next_frames.len() != 0 || self.demo_time_repaint{
             self.os.from_wasm(FromWasmRequestAnimationFrame {});
@@ -26,12 +26,21 @@ pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         }
         None
     }
+    
+    // the messages here don't come with a time on an audio clock
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<crate::audio::AudioTime>)> {
+        self.receive().map( | (port_id, data) | (port_id, data, None))
+    }
 }
 impl OsMidiOutput {
     pub fn send(&self, port_id: Option<MidiPortId>, d: MidiData) {
         let _ = self.sender.send((port_id, d));
         SignalToUI::set_ui_signal();
     }
+    
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, _time: Option<crate::audio::AudioTime>) {
+        self.send(port_id, data)
+    }
 }
 
 #[derive(Default)]
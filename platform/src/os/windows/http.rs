@@ -81,6 +81,7 @@ async fn streaming_request(request_id:LiveId, request: HttpRequest, response_sen
             let client = HttpClient::new()?;
             let req =  create_request(&request).await?;
             let response = client.SendRequestWithOptionAsync(&req, HttpCompletionOption::ResponseHeadersRead)?.await?;
+            let status_code = response.StatusCode()?.0 as u16;
             
             let input_stream = response.Content()?.ReadAsInputStreamAsync()?.await?;
             let buffer = Buffer::Create(1024*1024)?; // 1MB chunks
@@ -103,7 +104,7 @@ async fn streaming_request(request_id:LiveId, request: HttpRequest, response_sen
                     response: NetworkResponse::HttpStreamResponse(HttpResponse{
                         headers: Default::default(),
                         metadata_id: request.metadata_id,
-                        status_code: 0,
+                        status_code,
                         body:Some(chunk.to_vec())
                     }),
                 };
@@ -114,7 +115,7 @@ async fn streaming_request(request_id:LiveId, request: HttpRequest, response_sen
                 response: NetworkResponse::HttpStreamComplete(HttpResponse{
                     headers: Default::default(),
                     metadata_id: request.metadata_id,
-                    status_code: 0,
+                    status_code,
                     body:None
                 }),
             };
@@ -126,6 +127,7 @@ async fn non_streaming_request(request_id:LiveId, request: HttpRequest, response
             let client = HttpClient::new()?;
             let req =  create_request(&request).await?;
             let response = client.SendRequestWithOptionAsync(&req, HttpCompletionOption::ResponseHeadersRead)?.await?;
+            let status_code = response.StatusCode()?.0 as u16;
             
             let buffer = response.Content()?.ReadAsBufferAsync()?.await?;
             let byte_access:IBufferByteAccess = buffer.cast()?;
@@ -136,7 +138,7 @@ async fn non_streaming_request(request_id:LiveId, request: HttpRequest, response
                 response: NetworkResponse::HttpResponse(HttpResponse{
                     headers: Default::default(),
                     metadata_id: request.metadata_id,
-                    status_code: 0,
+                    status_code,
                     body:Some(chunk.to_vec())
                 }),
             };
@@ -122,7 +122,7 @@ pub fn open(_socket_id:u64, request: HttpRequest, rx_sender:Sender<WebSocketMess
                                         done = true;
                                     };
                                 },
-                                Ok(ServerWebSocketMessage::Close) => {
+                                Ok(ServerWebSocketMessage::Close{..}) => {
                                     let _ = rx_sender.send(WebSocketMessage::Closed);
                                     done = true;
                                 },
@@ -235,6 +235,7 @@ fn win32_event_callback(
             Win32Event::Signal => {
                 if SignalToUI::check_and_clear_ui_signal() {
                     self.handle_media_signals();
+                    self.handle_network_signals();
                     self.handle_script_signals();
                     self.call_event_handler(&Event::Signal);
                 }
@@ -297,8 +298,7 @@ pub(crate) fn handle_networking_events(&mut self) {
             out.push(event);
         }
         if out.len()>0{
-            self.handle_script_network_events(&out);
-            self.call_event_handler(& Event::NetworkResponses(out))
+            self.handle_network_responses(out);
         }
     }
     
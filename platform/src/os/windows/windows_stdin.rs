@@ -222,6 +222,7 @@ pub fn stdin_event_loop(&mut self, d3d11_cx: &mut D3d11Cx) {
                     // check signals
                     if SignalToUI::check_and_clear_ui_signal() {
                         self.handle_media_signals();
+                        self.handle_network_signals();
                         self.handle_script_signals();
                         self.call_event_handler(&Event::Signal);
                     }
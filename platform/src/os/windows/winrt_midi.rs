@@ -37,6 +37,10 @@ impl OsMidiOutput {
     pub fn send(&self, port_id: Option<MidiPortId>, d: MidiData) {
         let _ =  self.0.lock().unwrap().event_sender.send(WinRTMidiEvent::SendMidi(port_id, d));
     }
+    
+    pub fn send_timed(&self, port_id: Option<MidiPortId>, data: MidiData, _time: Option<crate::audio::AudioTime>) {
+        self.send(port_id, data)
+    }
 }
 
 impl OsMidiInput {
@@ -46,6 +50,11 @@ pub fn receive(&mut self) -> Option<(MidiPortId, MidiData)> {
         }
         None
     }
+    
+    // the messages here don't come with a time on an audio clock
+    pub fn receive_timed(&mut self) -> Option<(MidiPortId, MidiData, Option<crate::audio::AudioTime>)> {
+        self.receive().map( | (port_id, data) | (port_id, data, None))
+    }
 }
 
 type InputSenders = Arc<Mutex<Vec<mpsc::Sender<(MidiPortId, MidiData) >> >>;
@@ -0,0 +1,145 @@
+use crate::*;
+use crate::studio::*;
+use crate::web_socket::WebSocketMessage;
+use crate::makepad_micro_serde::*;
+use makepad_script::*;
+
+// Lets studio debug the script VM over the studio websocket. While paused we
+// block the UI thread inside the VM on the websocket receiver until a step
+// command comes in, everything else that arrives is kept for handle_live_edit.
+
+fn to_breakpoints(breakpoints:Vec<StudioScriptBreakpoint>)->Vec<ScriptBreakpoint>{
+    breakpoints.into_iter().map(|bp| ScriptBreakpoint{
+        file: bp.file_name,
+        line: bp.line
+    }).collect()
+}
+
+struct StudioScriptDebugger;
+
+impl StudioScriptDebugger{
+    fn paused_info(paused:&ScriptPaused)->StudioScriptPaused{
+        let reason = match paused.reason{
+            ScriptPauseReason::Breakpoint=>"breakpoint".to_string(),
+            ScriptPauseReason::Step=>"step".to_string(),
+            ScriptPauseReason::PauseRequest=>"pause".to_string(),
+            ScriptPauseReason::Error(value)=>format!("error {}", value),
+        };
+        let to_vars = |vars:Vec<ScriptDebugVar>| vars.into_iter().map(|v| StudioScriptVar{
+            name: v.name,
+            value: v.value
+        }).collect();
+        let frames = paused.call_stack().into_iter().map(|frame|{
+            let (file_name, line, column) = match frame.loc{
+                Some(loc)=>(loc.file, loc.line, loc.col),
+                None=>("native".to_string(), 0, 0)
+            };
+            StudioScriptFrame{
+                file_name,
+                line,
+                column,
+                scope: to_vars(paused.scope_vars(frame.depth)),
+                me: to_vars(paused.me_vars(frame.depth)),
+            }
+        }).collect();
+        StudioScriptPaused{reason, frames}
+    }
+
+    fn wait_for_command(cx:&mut Cx, breakpoints:&mut Vec<ScriptBreakpoint>)->ScriptDebugCommand{
+        loop{
+            let Some(studio_socket) = &mut cx.studio_web_socket else{
+                return ScriptDebugCommand::Detach
+            };
+            // the socket is fed from its own thread, so this blocks until
+            // studio sends something or goes away
+            let Ok(msg) = studio_socket.recv() else{
+                return ScriptDebugCommand::Detach
+            };
+            let WebSocketMessage::Binary(bin) = msg else{continue};
+            let Ok(data) = StudioToAppVec::deserialize_bin(&bin) else{continue};
+            let mut msgs = data.0.into_iter();
+            while let Some(data) = msgs.next(){
+                let cmd = match data{
+                    StudioToApp::ScriptDebugStep(step)=>match step{
+                        StudioScriptStep::Continue=>ScriptDebugCommand::Continue,
+                        StudioScriptStep::StepInto=>ScriptDebugCommand::StepInto,
+                        StudioScriptStep::StepOver=>ScriptDebugCommand::StepOver,
+                        StudioScriptStep::StepOut=>ScriptDebugCommand::StepOut,
+                    },
+                    StudioToApp::ScriptDebugDetach=>ScriptDebugCommand::Detach,
+                    StudioToApp::ScriptDebugBreakpoints{breakpoints:bps}=>{
+                        *breakpoints = to_breakpoints(bps);
+                        continue
+                    }
+                    StudioToApp::KeepAlive | StudioToApp::ScriptDebugPause | StudioToApp::ScriptDebugAttach{..}=>continue,
+                    x=>{
+                        cx.script_data.deferred_studio_messages.push(x);
+                        continue
+                    }
+                };
+                cx.script_data.deferred_studio_messages.extend(msgs);
+                Cx::send_studio_message(AppToStudio::ScriptDebugResumed);
+                return cmd
+            }
+        }
+    }
+}
+
+impl ScriptDebugHandler for StudioScriptDebugger{
+    fn on_pause(&mut self, paused:&mut ScriptPaused)->ScriptDebugCommand{
+        Cx::send_studio_message(AppToStudio::ScriptDebugPaused(Self::paused_info(paused)));
+        let Some(cx) = paused.host.downcast_mut::<Cx>() else{
+            return ScriptDebugCommand::Continue
+        };
+        // sitting on a breakpoint doesnt count against the script timeout
+        cx.suspend_script_timeout();
+        let cmd = Self::wait_for_command(cx, paused.breakpoints);
+        cx.resume_script_timeout();
+        cmd
+    }
+}
+
+impl Cx{
+    pub(crate) fn handle_script_debug(&mut self, msg:StudioToApp){
+        let Some(script_vm) = &mut self.script_vm else{
+            return
+        };
+        let thread = &mut script_vm.threads[0];
+        match msg{
+            StudioToApp::ScriptDebugAttach{breakpoints}=>{
+                // on the web the studio socket is pumped from the UI thread,
+                // so we cant block it waiting for commands
+                if cfg!(target_arch = "wasm32"){
+                    error!("Script debugging is not supported on web");
+                    return
+                }
+                let mut debug = ScriptDebug::new(Box::new(StudioScriptDebugger));
+                debug.breakpoints = to_breakpoints(breakpoints);
+                thread.attach_debugger(debug);
+            }
+            // studio doesnt track which apps have a debugger attached, setting
+            // breakpoints or pausing attaches one when needed
+            StudioToApp::ScriptDebugBreakpoints{breakpoints}=>{
+                if let Some(debug) = thread.debugger(){
+                    debug.breakpoints = to_breakpoints(breakpoints);
+                }
+                else if !breakpoints.is_empty(){
+                    self.handle_script_debug(StudioToApp::ScriptDebugAttach{breakpoints});
+                }
+            }
+            StudioToApp::ScriptDebugPause=>{
+                if thread.debugger().is_none(){
+                    self.handle_script_debug(StudioToApp::ScriptDebugAttach{breakpoints: Vec::new()});
+                }
+                if let Some(debug) = self.script_vm.as_mut().and_then(|vm| vm.threads[0].debugger()){
+                    debug.request_pause();
+                }
+            }
+            StudioToApp::ScriptDebugDetach=>{
+                thread.detach_debugger();
+            }
+            // step commands only mean something while paused
+            _=>()
+        }
+    }
+}
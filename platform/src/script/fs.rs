@@ -6,9 +6,85 @@
 use makepad_script::array::*;
 use std::io::Read;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Resolves `use "..."` imports of script modules against the filesystem.
+// Paths starting with . are relative to the importing file, other relative
+// paths are relative to the package root of the importing script.
+pub struct CxScriptModuleLoader;
+
+impl CxScriptModuleLoader{
+    fn importer_dir(package:&str, from:&str)->PathBuf{
+        let from = Path::new(from);
+        if from.is_absolute(){
+            return from.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+        }
+        // script! blocks carry a file!() path, which is relative to the
+        // workspace root, some parent of the manifest dir, or to the manifest
+        // dir itself outside a workspace. Try the longest crate path first.
+        let manifest = Path::new(package);
+        let mut fallback = None;
+        for root in manifest.ancestors().collect::<Vec<_>>().into_iter().rev(){
+            let Ok(crate_path) = manifest.strip_prefix(root) else{
+                continue
+            };
+            let Ok(in_crate) = from.strip_prefix(crate_path) else{
+                continue
+            };
+            let file = manifest.join(in_crate);
+            if file.exists(){
+                return file.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+            }
+            fallback = file.parent().map(|p| p.to_path_buf());
+        }
+        fallback.unwrap_or_else(|| manifest.to_path_buf())
+    }
+}
+
+impl ScriptModuleLoader for CxScriptModuleLoader{
+    fn resolve(&mut self, package:&str, from:&str, path:&str)->Option<String>{
+        let path = Path::new(path);
+        let full = if path.is_absolute(){
+            path.to_path_buf()
+        }
+        else if path.starts_with(".") || path.starts_with(".."){
+            Self::importer_dir(package, from).join(path)
+        }
+        else{
+            Path::new(package).join(path)
+        };
+        let full = fs::canonicalize(full).ok()?;
+        Some(full.to_string_lossy().trim_start_matches("\\\\?\\").replace("\\", "/"))
+    }
+    
+    fn load(&mut self, path:&str)->Option<String>{
+        fs::read_to_string(path).ok()
+    }
+    
+    fn stamp(&mut self, path:&str)->Option<u64>{
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        modified.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_nanos() as u64)
+    }
+}
+
+impl Cx{
+    /// Reloads the script modules among the `changed` files that changed on
+    /// disk, and the modules importing them. Returns the reloaded module paths.
+    pub fn reload_script_modules(&mut self, changed:&[String])->Vec<String>{
+        if self.script_vm.is_none() || changed.is_empty(){
+            return Vec::new()
+        }
+        let reloaded = self.with_vm(|vm| vm.reload_modules(changed));
+        for path in &reloaded{
+            log!("Reloaded script module {}", path);
+        }
+        reloaded
+    }
+}
 
 pub fn define_fs_module(vm:&mut ScriptVm){
     let fs = vm.new_module(id!(fs));
+    vm.set_module_loader(Box::new(CxScriptModuleLoader));
     
     for sym in [id!(read), id!(read_to_string)]{    
         vm.add_fn(fs, sym, script_args_def!(path=NIL), |vm, args|{
@@ -7,6 +7,8 @@
 pub mod run;
 pub mod std;
 pub mod script;
+pub mod debug;
+pub mod watchdog;
 
 pub fn define_script_modules(vm:&mut ScriptVm){
     crate::script::net::define_net_module(vm);
@@ -71,6 +71,9 @@ pub(crate) fn handle_script_web_sockets(&mut self){
                     }
                     i += 1;
                 }
+                Ok(WebSocketMessage::Close{..})=>{
+                    i += 1;
+                }
                 Ok(WebSocketMessage::Closed)=>{
                     if let Some(handler) = self.script_data.web_sockets[i].events.on_closed.as_obj(){
                         self.with_vm(|vm|{
@@ -143,6 +146,8 @@ pub(crate) fn handle_script_network_events(&mut self, items: &[NetworkResponseIt
                 }
                 NetworkResponse::HttpProgress(_p)=>{
                 }
+                NetworkResponse::HttpSseEvent(_) | NetworkResponse::HttpJsonLine(_)=>{
+                }
             }
         } 
     }
@@ -1,6 +1,8 @@
 use crate::script::net::*;
 use crate::script::std::*;
 use crate::script::run::*;
+use crate::studio::StudioToApp;
+use crate::script::watchdog::*;
 
 #[derive(Default)]
 pub struct CxScriptData{
@@ -9,4 +11,7 @@ pub struct CxScriptData{
     pub child_processes: Vec<CxScriptChildProcess>,
     pub web_sockets: Vec<CxScriptWebSocket>,
     pub http_requests: Vec<CxScriptHttp>,
+    // studio messages that came in while the script debugger was paused
+    pub deferred_studio_messages: Vec<StudioToApp>,
+    pub watchdog: CxScriptWatchdog,
 }
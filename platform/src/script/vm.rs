@@ -8,7 +8,10 @@ pub fn with_vm<R,F:FnOnce(&mut ScriptVm)->R>(&mut self, f:F)->R{
         let mut script_vm = None;
         std::mem::swap(&mut self.script_vm, &mut script_vm);
         let r = if let Some(script_vm) = &mut script_vm{
-            f(&mut script_vm.as_ref_host(self))
+            self.script_data.watchdog.arm(script_vm.threads[0].cancel_handle());
+            let r = f(&mut script_vm.as_ref_host(self));
+            self.script_data.watchdog.disarm();
+            r
         }
         else{
             panic!()
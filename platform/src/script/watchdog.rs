@@ -0,0 +1,131 @@
+use crate::*;
+use makepad_script::*;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// Aborts script runs that take too long. A timer on the UI thread can't fire
+// while a script is blocking it, so the watchdog lives on its own thread and
+// cancels the run through the ScriptCancel handle of the VM thread. The
+// thread sleeps on a condvar while nothing is armed and exits on drop. A
+// debugger holding the script stops the clock, the run keeps what it had left.
+
+#[derive(Default)]
+struct WatchdogState{
+    armed: Option<(Instant, ScriptCancel)>,
+    // the time left while suspended
+    suspended: Option<(Duration, ScriptCancel)>,
+    stop: bool,
+}
+
+#[derive(Default)]
+pub struct CxScriptWatchdog{
+    timeout: Option<f64>,
+    state: Arc<(Mutex<WatchdogState>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CxScriptWatchdog{
+    pub(crate) fn arm(&mut self, cancel:ScriptCancel){
+        // no threads and no Instant on the web
+        if cfg!(target_arch = "wasm32"){
+            return
+        }
+        let Some(timeout) = self.timeout else{
+            return
+        };
+        if self.thread.is_none(){
+            let state = self.state.clone();
+            self.thread = Some(std::thread::spawn(move || Self::run(&state)));
+        }
+        let (lock, cvar) = &*self.state;
+        lock.lock().unwrap().armed = Some((Instant::now() + Duration::from_secs_f64(timeout), cancel));
+        cvar.notify_one();
+    }
+
+    pub(crate) fn disarm(&mut self){
+        if self.thread.is_some(){
+            let mut state = self.state.0.lock().unwrap();
+            state.armed = None;
+            state.suspended = None;
+        }
+    }
+
+    pub(crate) fn suspend(&mut self){
+        if self.thread.is_some(){
+            let mut state = self.state.0.lock().unwrap();
+            if let Some((deadline, cancel)) = state.armed.take(){
+                state.suspended = Some((deadline.saturating_duration_since(Instant::now()), cancel));
+            }
+        }
+    }
+
+    pub(crate) fn resume(&mut self){
+        if self.thread.is_some(){
+            let (lock, cvar) = &*self.state;
+            let mut state = lock.lock().unwrap();
+            if let Some((left, cancel)) = state.suspended.take(){
+                state.armed = Some((Instant::now() + left, cancel));
+                cvar.notify_one();
+            }
+        }
+    }
+
+    fn run(state:&(Mutex<WatchdogState>, Condvar)){
+        let (lock, cvar) = state;
+        let mut state = lock.lock().unwrap();
+        while !state.stop{
+            let Some((deadline, _)) = &state.armed else{
+                state = cvar.wait(state).unwrap();
+                continue
+            };
+            let now = Instant::now();
+            if now < *deadline{
+                // re-arming wakes us up early
+                let wait = *deadline - now;
+                state = cvar.wait_timeout(state, wait).unwrap().0;
+                continue
+            }
+            if let Some((_, cancel)) = state.armed.take(){
+                cancel.cancel();
+            }
+        }
+    }
+}
+
+impl Drop for CxScriptWatchdog{
+    fn drop(&mut self){
+        if let Some(thread) = self.thread.take(){
+            let (lock, cvar) = &*self.state;
+            lock.lock().unwrap().stop = true;
+            cvar.notify_one();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Cx{
+    /// Aborts any single script run that takes longer than `timeout` seconds
+    /// with a BudgetExhausted error. None disables the watchdog.
+    pub fn set_script_timeout(&mut self, timeout:Option<f64>){
+        self.script_data.watchdog.timeout = timeout;
+    }
+
+    /// Stops the clock of the script timeout, for debug handlers that hold
+    /// the script while it is paused.
+    pub fn suspend_script_timeout(&mut self){
+        self.script_data.watchdog.suspend();
+    }
+
+    /// Starts the clock again with the time the run had left when it was
+    /// suspended.
+    pub fn resume_script_timeout(&mut self){
+        self.script_data.watchdog.resume();
+    }
+
+    /// A handle that cancels the script currently running on the VM, it can
+    /// be used from any thread.
+    pub fn script_cancel_handle(&self)->Option<ScriptCancel>{
+        self.script_vm.as_ref().map(|vm| vm.threads[0].cancel_handle())
+    }
+}
@@ -116,7 +116,9 @@ pub enum AppToStudio{
     },
     SwapSelection(SwapSelection),
     Screenshot(StudioScreenshotResponse),
-    FocusDesign
+    FocusDesign,
+    ScriptDebugPaused(StudioScriptPaused),
+    ScriptDebugResumed,
 }
 
 #[derive(SerBin, DeBin, Debug)]
@@ -127,6 +129,42 @@ pub struct StudioScreenshotResponse{
     pub height: u32
 }
 
+#[derive(SerBin, DeBin, Debug, Clone)]
+pub struct StudioScriptBreakpoint{
+    pub file_name: String,
+    pub line: u32,
+}
+
+#[derive(SerBin, DeBin, Debug, Clone, Copy)]
+pub enum StudioScriptStep{
+    Continue,
+    StepInto,
+    StepOver,
+    StepOut,
+}
+
+#[derive(SerBin, DeBin, Debug, Clone)]
+pub struct StudioScriptVar{
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(SerBin, DeBin, Debug, Clone)]
+pub struct StudioScriptFrame{
+    pub file_name: String,
+    pub line: u32,
+    pub column: u32,
+    pub scope: Vec<StudioScriptVar>,
+    pub me: Vec<StudioScriptVar>,
+}
+
+#[derive(SerBin, DeBin, Debug, Clone)]
+pub struct StudioScriptPaused{
+    pub reason: String,
+    // innermost frame first
+    pub frames: Vec<StudioScriptFrame>,
+}
+
 #[derive(SerBin, DeBin)]
 pub struct AppToStudioVec(pub Vec<AppToStudio>);
 
@@ -151,6 +189,15 @@ pub enum StudioToApp{
     DesignerSelectFile{
         file_name: String,
     },
+    ScriptDebugAttach{
+        breakpoints: Vec<StudioScriptBreakpoint>
+    },
+    ScriptDebugBreakpoints{
+        breakpoints: Vec<StudioScriptBreakpoint>
+    },
+    ScriptDebugPause,
+    ScriptDebugStep(StudioScriptStep),
+    ScriptDebugDetach,
     None,
 }
 
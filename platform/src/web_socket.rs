@@ -1,6 +1,7 @@
 #[allow(unused_imports)]
 use crate::{
     os::OsWebSocket,
+    network_mock::{open_mock_web_socket, MockWebSocketLink},
     cx_api::*,
     Cx,
     studio::{AppToStudio,AppToStudioVec},
@@ -42,6 +43,7 @@ pub enum WebSocketThreadMsg{
 pub struct WebSocket{
     socket_id: u64,
     pub rx_receiver: Receiver<WebSocketMessage>,
+    mock: Option<MockWebSocketLink>,
 }
 
 #[derive(Debug)]
@@ -50,6 +52,9 @@ pub enum WebSocketMessage{
     Binary(Vec<u8>),
     String(String),
     Opened,
+    /// The close frame the server sent, comes right before `Closed`.
+    /// Code 1005 means the server didn't give one
+    Close{code: u16, reason: String},
     Closed
 }
 
@@ -240,6 +245,9 @@ pub fn send_studio_message(msg:AppToStudio){
 impl WebSocket{    
     
     pub fn close(&mut self){
+        if self.mock.take().is_some(){
+            return
+        }
         if let Ok(sender) = WEB_SOCKET_THREAD_SENDER.lock(){
             if let Some(sender) = &*sender{
                 let _ = sender.send(WebSocketThreadMsg::Close{
@@ -250,6 +258,13 @@ pub fn close(&mut self){
     }
     
     pub fn open(request:HttpRequest)->WebSocket {
+        if let Some((mock, rx_receiver)) = open_mock_web_socket(&request){
+            return WebSocket{
+                socket_id: WEB_SOCKET_ID.fetch_add(1, Ordering::SeqCst),
+                rx_receiver,
+                mock: Some(mock),
+            }
+        }
         let (rx_sender, rx_receiver) = channel();
         let sender = WEB_SOCKET_THREAD_SENDER.lock().unwrap();
         let socket_id = WEB_SOCKET_ID.fetch_add(1, Ordering::SeqCst);
@@ -266,10 +281,14 @@ pub fn open(request:HttpRequest)->WebSocket {
         WebSocket{
             socket_id,
             rx_receiver,
+            mock: None,
         }
     }
     
     pub fn send_binary(&mut self, data:Vec<u8>)->Result<(),()>{
+        if let Some(mock) = &self.mock{
+            return mock.send(WebSocketMessage::Binary(data))
+        }
         let sender = WEB_SOCKET_THREAD_SENDER.lock().unwrap();
         if let Some(sender) = &*sender{
             sender.send(WebSocketThreadMsg::SendMessage{
@@ -283,6 +302,9 @@ pub fn send_binary(&mut self, data:Vec<u8>)->Result<(),()>{
     }
     
     pub fn send_string(&mut self, data:String)->Result<(),()>{
+        if let Some(mock) = &self.mock{
+            return mock.send(WebSocketMessage::String(data))
+        }
         let sender = WEB_SOCKET_THREAD_SENDER.lock().unwrap();
         if let Some(sender) = &*sender{
             sender.send(WebSocketThreadMsg::SendMessage{
@@ -0,0 +1,151 @@
+use makepad_platform::*;
+use std::f64::consts::PI;
+
+fn sine(frequency: f64, sample_rate: f64, frames: usize) -> AudioBuffer {
+    let data = (0..frames).map( | i | (2.0 * PI * frequency * i as f64 / sample_rate).sin() as f32).collect();
+    AudioBuffer::from_data(data, 1)
+}
+
+fn peak(buffer: &AudioBuffer, skip: usize) -> f32 {
+    buffer.channel(0)[skip..buffer.frame_count() - skip].iter().fold(0.0, | m, s | m.max(s.abs()))
+}
+
+#[test]
+fn converts_a_sine() {
+    for quality in [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::High] {
+        let mut resampler = Resampler::new(44100.0, 48000.0, 1, quality);
+        let output = resampler.process(&sine(1000.0, 44100.0, 4410));
+        assert!(output.frame_count() > 4700);
+        // the first output frame lines up with the first input frame
+        let error = (200..output.frame_count() - 200).map( | i | {
+            let expected = (2.0 * PI * 1000.0 * i as f64 / 48000.0).sin() as f32;
+            (output.channel(0)[i] - expected).abs()
+        }).fold(0.0, f32::max);
+        let limit = if quality == ResampleQuality::Fast {1e-2} else {1e-3};
+        assert!(error < limit, "{:?} error {}", quality, error);
+        assert_eq!(resampler.latency_frames(), (resampler.latency() * 48000.0).round() as usize);
+    }
+}
+
+#[test]
+fn block_size_does_not_matter() {
+    let input = sine(3000.0, 48000.0, 3000);
+    let mut whole = Resampler::new(48000.0, 44100.0, 1, ResampleQuality::Balanced);
+    let whole = whole.process(&input);
+
+    let mut resampler = Resampler::new(48000.0, 44100.0, 1, ResampleQuality::Balanced);
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    for size in [1, 7, 128, 333, 64, 1000].iter().cycle() {
+        let end = (offset + size).min(input.frame_count());
+        let block = AudioBuffer::from_data(input.channel(0)[offset..end].to_vec(), 1);
+        pieces.extend_from_slice(resampler.process(&block).channel(0));
+        offset = end;
+        if offset == input.frame_count() {
+            break
+        }
+    }
+    assert_eq!(pieces.len(), whole.frame_count());
+    for (a, b) in pieces.iter().zip(whole.channel(0)) {
+        assert!((a - b).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn pulls_exact_blocks() {
+    let mut resampler = Resampler::new(44100.0, 48000.0, 2, ResampleQuality::Fast);
+    let mut output = AudioBuffer::new_with_size(256, 2);
+    for _ in 0..20 {
+        let needed = resampler.input_needed(256);
+        resampler.push(&AudioBuffer::new_with_size(needed, 2));
+        assert!(resampler.available() >= 256);
+        assert_eq!(resampler.pull(&mut output), 256);
+        assert_eq!(resampler.input_needed(0), 0);
+    }
+}
+
+#[test]
+fn filters_above_the_output_nyquist() {
+    // 23khz folds down to 21.1khz when it isn't filtered out
+    let mut resampler = Resampler::new(48000.0, 44100.0, 1, ResampleQuality::High);
+    let output = resampler.process(&sine(23000.0, 48000.0, 4800));
+    assert!(peak(&output, 300) < 1e-3, "alias {}", peak(&output, 300));
+}
+
+#[test]
+fn stopband_matches_the_quality() {
+    for (quality, db) in [(ResampleQuality::Fast, -50.0), (ResampleQuality::Balanced, -80.0), (ResampleQuality::High, -110.0)] {
+        for (from, to) in [(48000.0, 44100.0), (96000.0, 48000.0)] {
+            // from the output nyquist up to the input nyquist
+            for i in 0..12 {
+                let frequency = to / 2.0 + 10.0 + (from - to - 20.0) / 2.0 * i as f64 / 11.0;
+                let mut resampler = Resampler::new(from, to, 1, quality);
+                let output = resampler.process(&sine(frequency, from, 8000));
+                let level = 20.0 * peak(&output, 500).log10();
+                assert!(level < db, "{:?} {} -> {} at {}hz: {}dB", quality, from, to, frequency, level);
+            }
+        }
+    }
+}
+
+#[test]
+fn wrapped_output_renders_at_its_own_rate() {
+    let mut rendered = 0;
+    let mut seen_rate = 0.0;
+    let (send, recv) = std::sync::mpsc::channel();
+    let mut output_fn = resampled_audio_output(44100.0, ResampleQuality::Fast, Box::new(move | info, buffer | {
+        send.send((info.sample_rate, buffer.frame_count())).unwrap();
+        buffer.zero();
+    }));
+    let mut buffer = AudioBuffer::new_with_size(480, 2);
+    for _ in 0..100 {
+        output_fn(AudioInfo {device_id: Default::default(), time: None, sample_rate: 48000.0}, &mut buffer);
+        assert_eq!(buffer.frame_count(), 480);
+    }
+    while let Ok((rate, frames)) = recv.try_recv() {
+        seen_rate = rate;
+        rendered += frames;
+    }
+    assert_eq!(seen_rate, 44100.0);
+    // one second of output takes one second of input plus the filter lookahead
+    assert!((rendered as i64 - 44100).abs() < 64, "rendered {}", rendered);
+}
+
+#[test]
+fn whole_ratios_up_to_48k() {
+    // a third and two thirds don't add up exactly in floating point, the
+    // read position has to agree with what available promised
+    for from in [16000.0, 32000.0] {
+        for quality in [ResampleQuality::Fast, ResampleQuality::Balanced, ResampleQuality::High] {
+            let mut resampler = Resampler::new(from, 48000.0, 2, quality);
+            let mut output = AudioBuffer::new_with_size(480, 2);
+            for _ in 0..1000 {
+                let needed = resampler.input_needed(480);
+                resampler.push(&AudioBuffer::new_with_size(needed, 2));
+                assert_eq!(resampler.pull(&mut output), 480);
+            }
+
+            let input = sine(1000.0, from, from as usize);
+            let mut resampler = Resampler::new(from, 48000.0, 1, quality);
+            let mut output = Vec::new();
+            let mut offset = 0;
+            for size in [1, 3, 160, 7, 441].iter().cycle() {
+                let end = (offset + size).min(input.frame_count());
+                let block = AudioBuffer::from_data(input.channel(0)[offset..end].to_vec(), 1);
+                output.extend_from_slice(resampler.process(&block).channel(0));
+                offset = end;
+                if offset == input.frame_count() {
+                    break
+                }
+            }
+            // everything up to the filter lookahead came out
+            assert!(output.len() + resampler.latency_frames() + 3 >= 48000, "{} {:?} {}", from, quality, output.len());
+            let error = (200..output.len() - 200).map( | i | {
+                let expected = (2.0 * PI * 1000.0 * i as f64 / 48000.0).sin() as f32;
+                (output[i] - expected).abs()
+            }).fold(0.0, f32::max);
+            let limit = if quality == ResampleQuality::Fast {1e-2} else {1e-3};
+            assert!(error < limit, "{} {:?} error {}", from, quality, error);
+        }
+    }
+}
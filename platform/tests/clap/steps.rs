@@ -0,0 +1,192 @@
+// A plugin that the clap_host test builds into a .clap file of its own, so
+// loading from disk gets tested too. The output is the input plus one while
+// a note is held, and it changes on the frame the note event is at
+
+#![allow(non_upper_case_globals, dead_code)]
+
+#[path = "../../src/os/linux/clap_sys.rs"]
+mod clap_sys;
+
+use clap_sys::*;
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_void},
+    ptr,
+};
+
+struct Shared<T>(T);
+unsafe impl<T> Sync for Shared<T> {}
+
+static FEATURES: Shared<[*const c_char; 2]> = Shared([c"instrument".as_ptr(), ptr::null()]);
+
+static DESCRIPTOR: Shared<clap_plugin_descriptor> = Shared(clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"dev.makepad.test-steps".as_ptr(),
+    name: c"Test Steps".as_ptr(),
+    vendor: c"Makepad".as_ptr(),
+    url: ptr::null(),
+    manual_url: ptr::null(),
+    support_url: ptr::null(),
+    version: c"1.0".as_ptr(),
+    description: c"Steps up while a note is held".as_ptr(),
+    features: &FEATURES.0 as *const [*const c_char; 2] as *const *const c_char,
+});
+
+#[no_mangle]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: entry_init,
+    deinit: entry_deinit,
+    get_factory: entry_get_factory,
+};
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: factory_count,
+    get_plugin_descriptor: factory_descriptor,
+    create_plugin: factory_create,
+};
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: audio_ports_count,
+    get: audio_ports_get,
+};
+
+static NOTE_PORTS: clap_plugin_note_ports = clap_plugin_note_ports {
+    count: note_ports_count,
+    get: note_ports_get,
+};
+
+unsafe extern "C" fn entry_init(_path: *const c_char) -> bool {true}
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(id: *const c_char) -> *const c_void {
+    if CStr::from_ptr(id).to_bytes_with_nul() == CLAP_PLUGIN_FACTORY_ID {&FACTORY as *const _ as *const c_void} else {ptr::null()}
+}
+
+unsafe extern "C" fn factory_count(_factory: *const clap_plugin_factory) -> u32 {1}
+
+unsafe extern "C" fn factory_descriptor(_factory: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor {
+    if index == 0 {&DESCRIPTOR.0} else {ptr::null()}
+}
+
+struct Steps {
+    plugin: clap_plugin,
+    held: bool,
+}
+
+unsafe extern "C" fn factory_create(_factory: *const clap_plugin_factory, _host: *const clap_host, id: *const c_char) -> *const clap_plugin {
+    if CStr::from_ptr(id) != CStr::from_ptr(DESCRIPTOR.0.id) {
+        return ptr::null()
+    }
+    let steps = Box::into_raw(Box::new(Steps {
+        plugin: clap_plugin {
+            desc: &DESCRIPTOR.0,
+            plugin_data: ptr::null_mut(),
+            init: plugin_init,
+            destroy: plugin_destroy,
+            activate: plugin_activate,
+            deactivate: plugin_deactivate,
+            start_processing: plugin_start_processing,
+            stop_processing: plugin_stop_processing,
+            reset: plugin_reset,
+            process: plugin_process,
+            get_extension: plugin_get_extension,
+            on_main_thread: plugin_on_main_thread,
+        },
+        held: false,
+    }));
+    (*steps).plugin.plugin_data = steps as *mut c_void;
+    &(*steps).plugin
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {true}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(Box::from_raw((*plugin).plugin_data as *mut Steps));
+}
+
+unsafe extern "C" fn plugin_activate(_plugin: *const clap_plugin, _sample_rate: f64, _min: u32, _max: u32) -> bool {true}
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {true}
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_process(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status {
+    let steps = &mut *((*plugin).plugin_data as *mut Steps);
+    let process = &*process;
+    let events = process.in_events;
+    let count = ((*events).size)(events);
+    let mut next = 0;
+    let input = &*process.audio_inputs;
+    let output = &*process.audio_outputs;
+    for i in 0..process.frames_count {
+        while next < count {
+            let header = ((*events).get)(events, next);
+            // the host has to keep events inside the block
+            if (*header).time >= process.frames_count {
+                return CLAP_PROCESS_ERROR
+            }
+            if (*header).time > i {
+                break
+            }
+            match (*header).type_ {
+                CLAP_EVENT_NOTE_ON => steps.held = true,
+                CLAP_EVENT_NOTE_OFF => steps.held = false,
+                _ => ()
+            }
+            next += 1;
+        }
+        for c in 0..2 {
+            let value = *(*input.data32.add(c)).add(i as usize) + if steps.held {1.0} else {0.0};
+            *(*output.data32.add(c)).add(i as usize) = value;
+        }
+    }
+    CLAP_PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_get_extension(_plugin: *const clap_plugin, id: *const c_char) -> *const c_void {
+    match CStr::from_ptr(id).to_bytes_with_nul() {
+        id if id == CLAP_EXT_AUDIO_PORTS => &AUDIO_PORTS as *const _ as *const c_void,
+        id if id == CLAP_EXT_NOTE_PORTS => &NOTE_PORTS as *const _ as *const c_void,
+        _ => ptr::null()
+    }
+}
+
+fn write_name(target: &mut [c_char], name: &str) {
+    for (t, b) in target.iter_mut().zip(name.bytes().chain(Some(0))) {
+        *t = b as c_char;
+    }
+}
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const clap_plugin, _is_input: bool) -> u32 {1}
+
+unsafe extern "C" fn audio_ports_get(_plugin: *const clap_plugin, index: u32, _is_input: bool, info: *mut clap_audio_port_info) -> bool {
+    if index != 0 {
+        return false
+    }
+    let info = &mut *info;
+    info.id = 0;
+    write_name(&mut info.name, "Main");
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = ptr::null();
+    info.in_place_pair = CLAP_INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+    is_input as u32
+}
+
+unsafe extern "C" fn note_ports_get(_plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_note_port_info) -> bool {
+    if index != 0 || !is_input {
+        return false
+    }
+    let info = &mut *info;
+    info.id = 0;
+    info.supported_dialects = CLAP_NOTE_DIALECT_CLAP;
+    info.preferred_dialect = CLAP_NOTE_DIALECT_CLAP;
+    write_name(&mut info.name, "Notes");
+    true
+}
@@ -0,0 +1,492 @@
+#![cfg(all(target_os = "linux", not(target_env = "ohos")))]
+
+use makepad_platform::*;
+use makepad_platform::os::linux::{clap::*, clap_sys::*};
+use std::{
+    ffi::CStr,
+    os::raw::{c_char, c_void},
+    ptr,
+    sync::Arc,
+};
+
+// A small stereo gain plugin. It adds 0.5 to the output while a note is held,
+// turns itself down to 0.5 on a note on the way its gui would, and counts the
+// main thread callbacks it gets in a read only parameter
+
+const GAIN: u32 = 7;
+const CALLBACKS: u32 = 8;
+
+struct Shared<T>(T);
+unsafe impl<T> Sync for Shared<T> {}
+
+static FEATURES: Shared<[*const c_char; 3]> = Shared([c"audio-effect".as_ptr(), c"stereo".as_ptr(), ptr::null()]);
+
+static DESCRIPTOR: Shared<clap_plugin_descriptor> = Shared(clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"dev.makepad.test-gain".as_ptr(),
+    name: c"Test Gain".as_ptr(),
+    vendor: c"Makepad".as_ptr(),
+    url: ptr::null(),
+    manual_url: ptr::null(),
+    support_url: ptr::null(),
+    version: c"1.0".as_ptr(),
+    description: c"A gain for testing the host".as_ptr(),
+    features: &FEATURES.0 as *const [*const c_char; 3] as *const *const c_char,
+});
+
+static ENTRY: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: entry_init,
+    deinit: entry_deinit,
+    get_factory: entry_get_factory,
+};
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: factory_count,
+    get_plugin_descriptor: factory_descriptor,
+    create_plugin: factory_create,
+};
+
+unsafe extern "C" fn entry_init(_path: *const c_char) -> bool {true}
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(id: *const c_char) -> *const c_void {
+    if CStr::from_ptr(id).to_bytes_with_nul() == CLAP_PLUGIN_FACTORY_ID {&FACTORY as *const _ as *const c_void} else {ptr::null()}
+}
+
+unsafe extern "C" fn factory_count(_factory: *const clap_plugin_factory) -> u32 {1}
+
+unsafe extern "C" fn factory_descriptor(_factory: *const clap_plugin_factory, index: u32) -> *const clap_plugin_descriptor {
+    if index == 0 {&DESCRIPTOR.0} else {ptr::null()}
+}
+
+struct Gain {
+    plugin: clap_plugin,
+    host: *const clap_host,
+    gain: f64,
+    note: bool,
+    callbacks: u32,
+}
+
+unsafe fn gain<'a>(plugin: *const clap_plugin) -> &'a mut Gain {
+    &mut *((*plugin).plugin_data as *mut Gain)
+}
+
+unsafe extern "C" fn factory_create(_factory: *const clap_plugin_factory, host: *const clap_host, id: *const c_char) -> *const clap_plugin {
+    if CStr::from_ptr(id) != CStr::from_ptr(DESCRIPTOR.0.id) {
+        return ptr::null()
+    }
+    let gain = Box::into_raw(Box::new(Gain {
+        plugin: clap_plugin {
+            desc: &DESCRIPTOR.0,
+            plugin_data: ptr::null_mut(),
+            init: plugin_init,
+            destroy: plugin_destroy,
+            activate: plugin_activate,
+            deactivate: plugin_deactivate,
+            start_processing: plugin_start_processing,
+            stop_processing: plugin_stop_processing,
+            reset: plugin_reset,
+            process: plugin_process,
+            get_extension: plugin_get_extension,
+            on_main_thread: plugin_on_main_thread,
+        },
+        host,
+        gain: 1.0,
+        note: false,
+        callbacks: 0,
+    }));
+    (*gain).plugin.plugin_data = gain as *mut c_void;
+    &(*gain).plugin
+}
+
+unsafe extern "C" fn plugin_init(plugin: *const clap_plugin) -> bool {
+    let host = gain(plugin).host;
+    let log = ((*host).get_extension)(host, CLAP_EXT_LOG.as_ptr() as *const c_char) as *const clap_host_log;
+    let thread_check = ((*host).get_extension)(host, CLAP_EXT_THREAD_CHECK.as_ptr() as *const c_char) as *const clap_host_thread_check;
+    if log.is_null() || thread_check.is_null() {
+        return false
+    }
+    ((*log).log)(host, CLAP_LOG_DEBUG, c"test gain init".as_ptr());
+    ((*thread_check).is_main_thread)(host)
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(Box::from_raw((*plugin).plugin_data as *mut Gain));
+}
+
+unsafe extern "C" fn plugin_activate(plugin: *const clap_plugin, _sample_rate: f64, _min: u32, _max: u32) -> bool {
+    let host = gain(plugin).host;
+    ((*host).request_callback)(host);
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {true}
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+
+unsafe fn handle_events(gain: &mut Gain, events: *const clap_input_events, out: *const clap_output_events) {
+    for i in 0..((*events).size)(events) {
+        let header = ((*events).get)(events, i);
+        match (*header).type_ {
+            CLAP_EVENT_PARAM_VALUE => {
+                let event = &*(header as *const clap_event_param_value);
+                if event.param_id == GAIN {
+                    gain.gain = event.value;
+                }
+            }
+            CLAP_EVENT_NOTE_ON => {
+                gain.note = true;
+                gain.gain = 0.5;
+                let event = clap_event_param_value {
+                    header: clap_event_header::new::<clap_event_param_value>(CLAP_EVENT_PARAM_VALUE, 0),
+                    param_id: GAIN,
+                    cookie: ptr::null_mut(),
+                    note_id: -1,
+                    port_index: -1,
+                    channel: -1,
+                    key: -1,
+                    value: 0.5,
+                };
+                ((*out).try_push)(out, &event.header);
+            }
+            CLAP_EVENT_NOTE_OFF => gain.note = false,
+            _ => ()
+        }
+    }
+}
+
+unsafe extern "C" fn plugin_process(plugin: *const clap_plugin, process: *const clap_process) -> clap_process_status {
+    let gain = gain(plugin);
+    let process = &*process;
+    handle_events(gain, process.in_events, process.out_events);
+    let input = &*process.audio_inputs;
+    let output = &*process.audio_outputs;
+    for c in 0..2 {
+        let input = std::slice::from_raw_parts(*input.data32.add(c), process.frames_count as usize);
+        let output = std::slice::from_raw_parts_mut(*output.data32.add(c), process.frames_count as usize);
+        for (o, i) in output.iter_mut().zip(input) {
+            *o = *i * gain.gain as f32 + if gain.note {0.5} else {0.0};
+        }
+    }
+    CLAP_PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_on_main_thread(plugin: *const clap_plugin) {
+    gain(plugin).callbacks += 1;
+}
+
+static PARAMS: clap_plugin_params = clap_plugin_params {
+    count: params_count,
+    get_info: params_get_info,
+    get_value: params_get_value,
+    value_to_text: params_value_to_text,
+    text_to_value: params_text_to_value,
+    flush: params_flush,
+};
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: audio_ports_count,
+    get: audio_ports_get,
+};
+
+static NOTE_PORTS: clap_plugin_note_ports = clap_plugin_note_ports {
+    count: note_ports_count,
+    get: note_ports_get,
+};
+
+unsafe extern "C" fn plugin_get_extension(_plugin: *const clap_plugin, id: *const c_char) -> *const c_void {
+    match CStr::from_ptr(id).to_bytes_with_nul() {
+        id if id == CLAP_EXT_PARAMS => &PARAMS as *const _ as *const c_void,
+        id if id == CLAP_EXT_AUDIO_PORTS => &AUDIO_PORTS as *const _ as *const c_void,
+        id if id == CLAP_EXT_NOTE_PORTS => &NOTE_PORTS as *const _ as *const c_void,
+        _ => ptr::null()
+    }
+}
+
+fn write_name(target: &mut [c_char], name: &str) {
+    for (t, b) in target.iter_mut().zip(name.bytes().chain(Some(0))) {
+        *t = b as c_char;
+    }
+}
+
+unsafe extern "C" fn params_count(_plugin: *const clap_plugin) -> u32 {2}
+
+unsafe extern "C" fn params_get_info(_plugin: *const clap_plugin, index: u32, info: *mut clap_param_info) -> bool {
+    let info = &mut *info;
+    match index {
+        0 => {
+            info.id = GAIN;
+            info.flags = CLAP_PARAM_IS_AUTOMATABLE;
+            write_name(&mut info.name, "Gain");
+            write_name(&mut info.module, "Main");
+            (info.min_value, info.max_value, info.default_value) = (0.0, 2.0, 1.0);
+        }
+        1 => {
+            info.id = CALLBACKS;
+            info.flags = CLAP_PARAM_IS_READONLY | CLAP_PARAM_IS_STEPPED;
+            write_name(&mut info.name, "Callbacks");
+            (info.min_value, info.max_value, info.default_value) = (0.0, 100.0, 0.0);
+        }
+        _ => return false
+    }
+    true
+}
+
+unsafe extern "C" fn params_get_value(plugin: *const clap_plugin, id: clap_id, value: *mut f64) -> bool {
+    let gain = gain(plugin);
+    *value = match id {
+        GAIN => gain.gain,
+        CALLBACKS => gain.callbacks as f64,
+        _ => return false
+    };
+    true
+}
+
+unsafe extern "C" fn params_value_to_text(_plugin: *const clap_plugin, id: clap_id, value: f64, out: *mut c_char, capacity: u32) -> bool {
+    if id != GAIN {
+        return false
+    }
+    write_name(std::slice::from_raw_parts_mut(out, capacity as usize), &format!("{:.1} x", value));
+    true
+}
+
+unsafe extern "C" fn params_text_to_value(_plugin: *const clap_plugin, _id: clap_id, _text: *const c_char, _value: *mut f64) -> bool {
+    false
+}
+
+unsafe extern "C" fn params_flush(plugin: *const clap_plugin, events: *const clap_input_events, out: *const clap_output_events) {
+    handle_events(gain(plugin), events, out);
+}
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const clap_plugin, _is_input: bool) -> u32 {1}
+
+unsafe extern "C" fn audio_ports_get(_plugin: *const clap_plugin, index: u32, _is_input: bool, info: *mut clap_audio_port_info) -> bool {
+    if index != 0 {
+        return false
+    }
+    let info = &mut *info;
+    info.id = 0;
+    write_name(&mut info.name, "Main");
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = ptr::null();
+    info.in_place_pair = CLAP_INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
+    is_input as u32
+}
+
+unsafe extern "C" fn note_ports_get(_plugin: *const clap_plugin, index: u32, is_input: bool, info: *mut clap_note_port_info) -> bool {
+    if index != 0 || !is_input {
+        return false
+    }
+    let info = &mut *info;
+    info.id = 0;
+    info.supported_dialects = CLAP_NOTE_DIALECT_CLAP;
+    info.preferred_dialect = CLAP_NOTE_DIALECT_CLAP;
+    write_name(&mut info.name, "Notes");
+    true
+}
+
+fn new_instance() -> ClapInstance {
+    ClapBundle::from_entry("/test/gain.clap", &ENTRY).unwrap().new_instance("Test Gain").unwrap()
+}
+
+fn ramp(frames: usize) -> AudioBuffer {
+    let mut buffer = AudioBuffer::new_with_size(frames, 2);
+    for c in 0..2 {
+        for (i, s) in buffer.channel_mut(c).iter_mut().enumerate() {
+            *s = (i as f32 / frames as f32) * if c == 0 {1.0} else {-1.0};
+        }
+    }
+    buffer
+}
+
+fn assert_scaled(input: &AudioBuffer, output: &AudioBuffer, gain: f32, offset: f32) {
+    for (i, o) in input.data.iter().zip(&output.data) {
+        assert!((o - (i * gain + offset)).abs() < 1e-6, "{} is not {} * {} + {}", o, i, gain, offset);
+    }
+}
+
+#[test]
+fn lists_and_creates_plugins() {
+    let bundle = ClapBundle::from_entry("/test/gain.clap", &ENTRY).unwrap();
+    let plugins = bundle.plugins();
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].id, "dev.makepad.test-gain");
+    assert_eq!(plugins[0].features, ["audio-effect", "stereo"]);
+    assert_eq!(plugins[0].path, "/test/gain.clap");
+    assert!(!plugins[0].is_instrument());
+
+    let instance = bundle.new_instance("dev.makepad.test-gain").unwrap();
+    assert_eq!(instance.info().name, "Test Gain");
+    assert!(instance.takes_notes());
+    let ports = instance.audio_ports(false);
+    assert_eq!(ports.len(), 1);
+    assert!(ports[0].is_main && ports[0].channel_count == 2);
+    assert_eq!(bundle.new_instance("Nope").err(), Some(ClapError::PluginNotFound("Nope".into())));
+    assert!(matches!(ClapBundle::load("/nonexistent/plugin.clap"), Err(ClapError::LoadFailed(_))));
+}
+
+#[test]
+fn parameters() {
+    let mut instance = new_instance();
+    let params = instance.params();
+    assert_eq!(params.len(), 2);
+    assert_eq!((params[0].id, params[0].name.as_str(), params[0].module.as_str()), (GAIN, "Gain", "Main"));
+    assert_eq!((params[0].min, params[0].max, params[0].default), (0.0, 2.0, 1.0));
+    assert!(params[0].is_automatable() && !params[0].is_read_only());
+    assert!(params[1].is_read_only() && params[1].is_stepped());
+    assert_eq!(instance.param_value(GAIN), Some(1.0));
+    assert_eq!(instance.param_value(99), None);
+    assert_eq!(instance.param_text(GAIN, 1.5).as_deref(), Some("1.5 x"));
+
+    // not processing, so it gets flushed right away
+    instance.set_param(GAIN, 0.25);
+    assert_eq!(instance.param_value(GAIN), Some(0.25));
+}
+
+#[test]
+fn processes_audio_and_automation() {
+    let mut instance = new_instance();
+    instance.set_param(GAIN, 0.5);
+    let mut processor = instance.activate(48000.0, 64).unwrap();
+    assert!(instance.is_active());
+    assert_eq!(processor.sample_rate(), 48000.0);
+    assert_eq!(instance.activate(44100.0, 64).err(), Some(ClapError::StillProcessing));
+
+    // a block that doesn't fit the maximum block size of the plugin
+    let input = ramp(200);
+    let mut output = AudioBuffer::new_with_size(200, 2);
+    assert!(processor.process(Some(&input), &mut output));
+    assert_scaled(&input, &output, 0.5, 0.0);
+
+    // while processing changes wait for the next block
+    instance.set_param(GAIN, 2.0);
+    assert_eq!(instance.param_value(GAIN), Some(0.5));
+    assert!(processor.process(Some(&input), &mut output));
+    assert_scaled(&input, &output, 2.0, 0.0);
+
+    // a mono input goes to both channels of the plugin
+    let mono = AudioBuffer::from_data(vec![0.25; 32], 1);
+    let mut output = AudioBuffer::new_with_size(32, 2);
+    processor.process(Some(&mono), &mut output);
+    assert!(output.data.iter().all( | s | *s == 0.5));
+
+    // an input shorter than the output is padded with silence
+    let mut output = AudioBuffer::new_with_size(100, 2);
+    assert!(processor.process(Some(&mono), &mut output));
+    for c in 0..2 {
+        assert!(output.channel(c)[..32].iter().all( | s | *s == 0.5));
+        assert!(output.channel(c)[32..].iter().all( | s | *s == 0.0));
+    }
+
+    drop(processor);
+    assert!(instance.activate(44100.0, 256).is_ok());
+}
+
+#[test]
+fn notes_and_main_thread_requests() {
+    let mut instance = new_instance();
+    let mut processor = instance.activate(48000.0, 512).unwrap();
+    // the plugin asked for a callback when it got activated
+    assert!(instance.signal().check_and_clear());
+    assert_eq!(instance.param_value(CALLBACKS), Some(0.0));
+    assert!(!instance.handle_main_thread().restart);
+    assert_eq!(instance.param_value(CALLBACKS), Some(1.0));
+
+    // the plugin only speaks CLAP notes, midi gets translated
+    let input = ramp(128);
+    let mut output = AudioBuffer::new_with_size(128, 2);
+    processor.handle_midi_data(MidiData {data: [0x90, 60, 100]});
+    processor.process(Some(&input), &mut output);
+    assert_scaled(&input, &output, 0.5, 0.5);
+    assert!(instance.signal().check_and_clear());
+    assert_eq!(instance.param_events(), [ClapParamEvent::Value {id: GAIN, value: 0.5}]);
+    assert!(instance.param_events().is_empty());
+
+    processor.handle_midi_data(MidiData {data: [0x90, 60, 0]});
+    processor.process(Some(&input), &mut output);
+    assert_scaled(&input, &output, 0.5, 0.0);
+    processor.handle_midi_data(MidiData {data: [0x90, 62, 100]});
+    processor.all_notes_off();
+    processor.process(Some(&input), &mut output);
+    assert_scaled(&input, &output, 0.5, 0.0);
+}
+
+#[test]
+fn without_a_gui() {
+    let mut instance = new_instance();
+    assert!(!instance.has_gui(true) && !instance.has_gui(false));
+    assert_eq!(instance.open_gui(None), Err(ClapError::NoGui));
+    assert!(!instance.is_gui_open());
+}
+
+// builds tests/clap/steps.rs into a .clap file, None without a rustc
+fn build_steps_plugin() -> Option<String> {
+    let dir = std::env::temp_dir().join(format!("makepad_clap_host_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).ok()?;
+    let path = dir.join("steps.clap");
+    let rustc = std::env::var("RUSTC").unwrap_or_else( | _ | "rustc".into());
+    let status = std::process::Command::new(rustc)
+        .args(["--edition", "2021", "--crate-type", "cdylib", "-o"])
+        .arg(&path)
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/clap/steps.rs"))
+        .status()
+        .ok()?;
+    status.success().then( || path.to_string_lossy().to_string())
+}
+
+fn assert_steps(output: &AudioBuffer, base: &[f32], held: std::ops::Range<usize>) {
+    for c in 0..2 {
+        for (i, s) in output.channel(c).iter().enumerate() {
+            let expected = base.get(i).copied().unwrap_or(0.0) + if held.contains(&i) {1.0} else {0.0};
+            assert_eq!(*s, expected, "channel {} frame {}", c, i);
+        }
+    }
+}
+
+#[test]
+fn loads_a_built_plugin() {
+    let Some(path) = build_steps_plugin() else {
+        eprintln!("no rustc to build the test plugin with, skipping");
+        return
+    };
+    let bundle = ClapBundle::load(&path).unwrap();
+    let plugins = bundle.plugins();
+    assert_eq!(plugins.len(), 1);
+    assert_eq!(plugins[0].id, "dev.makepad.test-steps");
+    assert!(plugins[0].is_instrument());
+    // the library is loaded once
+    assert!(Arc::ptr_eq(&bundle, &ClapBundle::load(&path).unwrap()));
+
+    let mut instance = bundle.new_instance("dev.makepad.test-steps").unwrap();
+    let mut processor = instance.activate(48000.0, 64).unwrap();
+    let silence = AudioBuffer::new_with_size(200, 2);
+    let mut output = AudioBuffer::new_with_size(200, 2);
+    // notes go out on their frame, across the runs of 64 frames
+    processor.handle_midi_data_at(150, MidiData {data: [0x80, 60, 0]});
+    processor.handle_midi_data_at(100, MidiData {data: [0x90, 60, 100]});
+    processor.handle_midi_data_at(250, MidiData {data: [0x90, 60, 100]});
+    assert!(processor.process(Some(&silence), &mut output));
+    assert_steps(&output, &[], 100..150);
+    // the one past the end of the block is in the next one
+    assert!(processor.process(Some(&silence), &mut output));
+    assert_steps(&output, &[], 50..200);
+
+    // a short input is padded, the note is still held
+    let mut short = AudioBuffer::new_with_size(30, 2);
+    short.data.fill(0.25);
+    assert!(processor.process(Some(&short), &mut output));
+    assert_steps(&output, &[0.25; 30], 0..200);
+    processor.handle_midi_data(MidiData {data: [0x80, 60, 0]});
+    assert!(processor.process(Some(&silence), &mut output));
+    assert_steps(&output, &[], 0..0);
+
+    drop(processor);
+    std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,220 @@
+use makepad_platform::*;
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+fn cx_with_log(name: &str) -> (Cx, Rc<RefCell<NetworkResponsesEvent>>, PathBuf) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    let mut cx = Cx::new(Box::new(move | _cx, event | {
+        if let Event::NetworkResponses(items) = event {
+            sink.borrow_mut().extend(items.iter().cloned());
+        }
+    }));
+    let dir = std::env::temp_dir().join(format!("makepad_http_cache_{}_{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    cx.set_http_cache(Some(HttpCache::new(&dir, 1 << 20).unwrap()));
+    (cx, log, dir)
+}
+
+// runs a GET to the end, through the mock and the cache
+fn get(cx: &mut Cx, log: &Rc<RefCell<NetworkResponsesEvent>>, url: &str) -> HttpResponse {
+    log.borrow_mut().clear();
+    cx.http_request(live_id!(get), HttpRequest::new(url.into(), HttpMethod::GET));
+    cx.settle_network_mock();
+    cx.flush_http_cache();
+    let item = log.borrow_mut().pop().unwrap();
+    let NetworkResponse::HttpResponse(res) = item.response else {panic!("no response for {}", url)};
+    res
+}
+
+fn sent(cx: &mut Cx) -> Vec<HttpRequest> {
+    cx.network_mock().unwrap().take_requests().into_iter().map( | r | r.request).collect()
+}
+
+#[test]
+fn max_age_is_served_from_disk() {
+    let (mut cx, log, dir) = cx_with_log("max_age");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "fresh").with_header("Cache-Control", "public, max-age=3600"));
+    cx.set_network_mock(Some(mock));
+
+    assert_eq!(get(&mut cx, &log, "https://api.test/a").get_string_body().unwrap(), "fresh");
+    assert_eq!(sent(&mut cx).len(), 1);
+    let res = get(&mut cx, &log, "https://api.test/a");
+    assert_eq!(res.get_string_body().unwrap(), "fresh");
+    assert_eq!(res.status_code, 200);
+    assert!(sent(&mut cx).is_empty());
+
+    // the index outlives the cache
+    cx.set_http_cache(None);
+    let cache = HttpCache::new(&dir, 1 << 20).unwrap();
+    assert!(cache.contains("https://api.test/a"));
+    drop(cache);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn no_store_is_not_kept() {
+    let (mut cx, log, dir) = cx_with_log("no_store");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "secret").with_header("Cache-Control", "no-store, max-age=3600"));
+    cx.set_network_mock(Some(mock));
+
+    get(&mut cx, &log, "https://api.test/a");
+    assert!(!cx.http_cache().unwrap().contains("https://api.test/a"));
+    get(&mut cx, &log, "https://api.test/a");
+    assert_eq!(sent(&mut cx).len(), 2);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn no_cache_revalidates_with_etag() {
+    let (mut cx, log, dir) = cx_with_log("no_cache");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "body").with_header("Cache-Control", "no-cache").with_header("ETag", "\"v1\"")).once();
+    mock.get("https://api.test/a", MockReply::text(304, ""));
+    cx.set_network_mock(Some(mock));
+
+    get(&mut cx, &log, "https://api.test/a");
+    assert!(cx.http_cache().unwrap().contains("https://api.test/a"));
+    let res = get(&mut cx, &log, "https://api.test/a");
+    // the 304 is answered with what was stored
+    assert_eq!(res.status_code, 200);
+    assert_eq!(res.get_string_body().unwrap(), "body");
+    let sent = sent(&mut cx);
+    assert_eq!(sent.len(), 2);
+    assert!(!sent[0].headers.contains_key("If-None-Match"));
+    assert_eq!(sent[1].headers["If-None-Match"], vec!["\"v1\"".to_string()]);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn last_modified_revalidates() {
+    let (mut cx, log, dir) = cx_with_log("last_modified");
+    let date = "Tue, 15 Nov 1994 12:45:26 GMT";
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "old").with_header("Cache-Control", "max-age=0").with_header("Last-Modified", date)).once();
+    mock.get("https://api.test/a", MockReply::text(200, "new").with_header("Cache-Control", "max-age=0").with_header("Last-Modified", date));
+    cx.set_network_mock(Some(mock));
+
+    get(&mut cx, &log, "https://api.test/a");
+    // a changed resource comes back whole, and replaces the old one
+    assert_eq!(get(&mut cx, &log, "https://api.test/a").get_string_body().unwrap(), "new");
+    let sent = sent(&mut cx);
+    assert_eq!(sent[1].headers["If-Modified-Since"], vec![date.to_string()]);
+    assert!(!sent[1].headers.contains_key("If-None-Match"));
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn expired_entries_are_fetched_again() {
+    let (mut cx, log, dir) = cx_with_log("expired");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/past", MockReply::text(200, "one")
+        .with_header("Date", "Tue, 15 Nov 1994 12:45:26 GMT")
+        .with_header("Expires", "Tue, 15 Nov 1994 08:12:31 GMT"));
+    mock.get("https://api.test/aged", MockReply::text(200, "two").with_header("Cache-Control", "max-age=60").with_header("Age", "120"));
+    mock.get("https://api.test/future", MockReply::text(200, "three")
+        .with_header("Date", "Tue, 15 Nov 1994 08:00:00 GMT")
+        .with_header("Expires", "Tue, 15 Nov 1994 09:00:00 GMT"));
+    cx.set_network_mock(Some(mock));
+
+    for url in ["https://api.test/past", "https://api.test/aged", "https://api.test/future"] {
+        get(&mut cx, &log, url);
+        get(&mut cx, &log, url);
+    }
+    let urls: Vec<String> = sent(&mut cx).into_iter().map( | r | r.url).collect();
+    // an Expires an hour after the Date is fresh for an hour from now
+    assert_eq!(urls, vec![
+        "https://api.test/past", "https://api.test/past",
+        "https://api.test/aged", "https://api.test/aged",
+        "https://api.test/future",
+    ]);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn missing_file_goes_to_the_network() {
+    let (mut cx, log, dir) = cx_with_log("missing_file");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "body").with_header("Cache-Control", "max-age=3600"));
+    cx.set_network_mock(Some(mock));
+
+    get(&mut cx, &log, "https://api.test/a");
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.file_name().unwrap() != "index.bin" {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+    log.borrow_mut().clear();
+    cx.http_request(live_id!(get), HttpRequest::new("https://api.test/a".into(), HttpMethod::GET));
+    cx.flush_http_cache();
+    cx.settle_network_mock();
+    let NetworkResponse::HttpResponse(res) = log.borrow_mut().pop().unwrap().response else {panic!()};
+    assert_eq!(res.get_string_body().unwrap(), "body");
+    assert_eq!(sent(&mut cx).len(), 2);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn huge_max_age_is_clamped() {
+    let (mut cx, log, dir) = cx_with_log("huge_max_age");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "forever").with_header("Cache-Control", "max-age=18446744073709551615"));
+    mock.get("https://api.test/b", MockReply::text(200, "longer").with_header("Cache-Control", "max-age=99999999999999999999999"));
+    cx.set_network_mock(Some(mock));
+
+    for url in ["https://api.test/a", "https://api.test/b"] {
+        get(&mut cx, &log, url);
+        get(&mut cx, &log, url);
+    }
+    assert_eq!(sent(&mut cx).len(), 2);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn authorized_and_varying_requests() {
+    let (mut cx, _log, dir) = cx_with_log("vary");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/me", MockReply::text(200, "mine").with_header("Cache-Control", "max-age=3600"));
+    mock.get("https://api.test/text", MockReply::text(200, "hello").with_header("Cache-Control", "max-age=3600").with_header("Vary", "Accept-Language"));
+    cx.set_network_mock(Some(mock));
+
+    let mut authorized = HttpRequest::new("https://api.test/me".into(), HttpMethod::GET);
+    authorized.set_header("Authorization".into(), "Bearer x".into());
+    cx.http_request(live_id!(get), authorized);
+    cx.settle_network_mock();
+    cx.flush_http_cache();
+    assert!(!cx.http_cache().unwrap().contains("https://api.test/me"));
+
+    let with_language = | language: &str | {
+        let mut request = HttpRequest::new("https://api.test/text".into(), HttpMethod::GET);
+        request.set_header("Accept-Language".into(), language.into());
+        request
+    };
+    for language in ["en", "en", "nl"] {
+        cx.http_request(live_id!(get), with_language(language));
+        cx.settle_network_mock();
+        cx.flush_http_cache();
+    }
+    let sent: Vec<String> = sent(&mut cx).iter().map( | r | r.headers.get("Accept-Language").map( | v | v.join(",")).unwrap_or_default()).collect();
+    // the second en is a hit, nl is another variant
+    assert_eq!(sent, vec!["", "en", "nl"]);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn cancelled_hits_are_not_delivered() {
+    let (mut cx, log, dir) = cx_with_log("cancelled");
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/a", MockReply::text(200, "body").with_header("Cache-Control", "max-age=3600"));
+    cx.set_network_mock(Some(mock));
+
+    get(&mut cx, &log, "https://api.test/a");
+    log.borrow_mut().clear();
+    cx.http_request(live_id!(get), HttpRequest::new("https://api.test/a".into(), HttpMethod::GET));
+    cx.cancel_http_request(live_id!(get));
+    cx.flush_http_cache();
+    assert!(log.borrow().is_empty());
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,111 @@
+use makepad_platform::*;
+
+fn sse(chunks: &[&[u8]]) -> (Vec<SseEvent>, SseParser) {
+    let mut parser = SseParser::new();
+    let mut out = Vec::new();
+    for chunk in chunks {
+        parser.feed(chunk, &mut out);
+    }
+    (out, parser)
+}
+
+// feeds the body split at every possible point
+fn sse_all_splits(body: &[u8]) -> Vec<SseEvent> {
+    let (whole, _) = sse(&[body]);
+    for at in 0..=body.len() {
+        let (split, _) = sse(&[&body[..at], &body[at..]]);
+        assert_eq!(split, whole, "split at {}", at);
+    }
+    let bytes: Vec<&[u8]> = body.chunks(1).collect();
+    assert_eq!(sse(&bytes).0, whole);
+    whole
+}
+
+fn event(event: &str, data: &str, id: &str, retry: Option<u64>) -> SseEvent {
+    SseEvent {event: event.to_string(), data: data.to_string(), id: id.to_string(), retry}
+}
+
+#[test]
+fn sse_chunk_splits() {
+    let body = "data: héllo 😀\n\nevent: update\ndata: {\"a\":1}\n\n".as_bytes();
+    assert_eq!(sse_all_splits(body), [
+        event("message", "héllo 😀", "", None),
+        event("update", "{\"a\":1}", "", None),
+    ]);
+}
+
+#[test]
+fn sse_line_endings() {
+    let expected = [event("message", "a", "", None), event("message", "b", "", None)];
+    assert_eq!(sse_all_splits(b"data: a\r\n\r\ndata: b\r\n\r\n"), expected);
+    assert_eq!(sse_all_splits(b"data: a\r\rdata: b\r\r"), expected);
+    assert_eq!(sse_all_splits(b"data: a\n\r\ndata: b\r\n\n"), expected);
+}
+
+#[test]
+fn sse_fields() {
+    let body = b": keep alive\n\
+        id: 1\n\
+        retry: 1500\n\
+        data: first\n\
+        data\n\
+        data:  last\n\
+        \n\
+        : another comment\n\
+        data: next\n\
+        \n\
+        id\n\
+        retry: soon\n\
+        data:x\n\
+        \n";
+    let events = sse_all_splits(body);
+    assert_eq!(events, [
+        event("message", "first\n\n last", "1", Some(1500)),
+        event("message", "next", "1", None),
+        event("message", "x", "", None),
+    ]);
+    let (_, parser) = sse(&[body]);
+    assert_eq!(parser.retry, Some(1500));
+    assert_eq!(parser.last_event_id, "");
+}
+
+#[test]
+fn sse_unfinished_events() {
+    // an event needs a blank line, and one without data isn't dispatched
+    let (events, mut parser) = sse(&[b"event: ping\n\ndata: cut off"]);
+    assert!(events.is_empty());
+    parser.finish();
+    let mut out = Vec::new();
+    parser.feed(b"\xef\xbb\xbfdata: fresh\n\n", &mut out);
+    assert_eq!(out, [event("message", "fresh", "", None)]);
+}
+
+fn ndjson(chunks: &[&[u8]]) -> Vec<String> {
+    let mut parser = NdJsonParser::new();
+    let mut out = Vec::new();
+    for chunk in chunks {
+        parser.feed(chunk, &mut out);
+    }
+    parser.finish(&mut out);
+    out
+}
+
+#[test]
+fn ndjson_lines() {
+    let body = "{\"a\":\"é\"}\r\n\n  {\"b\":2}\n{\"c\":3}".as_bytes();
+    let expected = ["{\"a\":\"é\"}", "{\"b\":2}", "{\"c\":3}"];
+    assert_eq!(ndjson(&[body]), expected);
+    for at in 0..=body.len() {
+        assert_eq!(ndjson(&[&body[..at], &body[at..]]), expected, "split at {}", at);
+    }
+
+    // the trailing partial line only comes out when the body ends
+    let mut parser = NdJsonParser::new();
+    let mut out = Vec::new();
+    parser.feed(b"{\"a\":1}\n{\"b\"", &mut out);
+    assert_eq!(out, ["{\"a\":1}"]);
+    parser.feed(b":2}", &mut out);
+    assert_eq!(out.len(), 1);
+    parser.finish(&mut out);
+    assert_eq!(out, ["{\"a\":1}", "{\"b\":2}"]);
+}
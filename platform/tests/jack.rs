@@ -0,0 +1,432 @@
+#![cfg(all(target_os = "linux", not(target_env = "ohos")))]
+
+use makepad_platform::*;
+use makepad_platform::os::linux::{jack::*, jack_sys::*};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int, c_ulong, c_void},
+    ptr,
+    sync::{mpsc, Arc, Mutex, MutexGuard, OnceLock, atomic::{AtomicUsize, Ordering}},
+    time::{Duration, Instant},
+};
+
+// A JACK server in the test with a sound card, a synth and a keyboard. Like
+// jackd it runs the process callback of its client on a thread of its own,
+// here every millisecond, until the next test starts its own. A port is its
+// index in the list plus one
+
+const RATE: u32 = 48000;
+
+struct FakePort {
+    name: CString,
+    port_type: &'static str,
+    flags: c_ulong,
+    is_mine: bool,
+    is_gone: bool,
+    audio: Vec<f32>,
+    midi: Vec<(u32, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct Server {
+    is_running: bool,
+    stop: bool,
+    client_name: CString,
+    ports: Vec<FakePort>,
+    connections: Vec<(String, String)>,
+    process: Option<(JackProcessCallback, usize)>,
+    buffer_size_cb: Option<(JackBufferSizeCallback, usize)>,
+    xrun_cb: Option<(JackXRunCallback, usize)>,
+    buffer_size: u32,
+    next_buffer_size: Option<u32>,
+    frame_time: u32,
+    cycles: usize,
+    // MIDI for a port next cycle, and what went out of ours on the frame clock
+    incoming: Vec<(&'static str, u32, Vec<u8>)>,
+    sent: Vec<(String, u32, Vec<u8>)>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn server<R>(f: impl FnOnce(&mut Server) -> R) -> R {
+    f(SERVER.lock().unwrap().as_mut().unwrap())
+}
+
+fn port(server: &mut Server, port: *const jack_port_t) -> &mut FakePort {
+    &mut server.ports[port as usize - 1]
+}
+
+fn add_port(server: &mut Server, name: &str, port_type: &'static str, flags: c_ulong, is_mine: bool) -> *mut jack_port_t {
+    server.ports.push(FakePort {
+        name: CString::new(name).unwrap(),
+        port_type,
+        flags,
+        is_mine,
+        is_gone: false,
+        audio: vec![0.0; 4096],
+        midi: Vec::new(),
+    });
+    server.ports.len() as *mut jack_port_t
+}
+
+fn port_type(s: &str) -> &'static str {
+    [JACK_DEFAULT_AUDIO_TYPE, JACK_DEFAULT_MIDI_TYPE].into_iter().find( | t | t.trim_end_matches('\0') == s).unwrap()
+}
+
+// the real one is variadic, nobody passes it the server name
+unsafe extern "C" fn client_open(name: *const c_char, _options: jack_options_t, _status: *mut jack_status_t) -> *mut jack_client_t {
+    server( | s | s.client_name = CStr::from_ptr(name).into());
+    ptr::dangling_mut()
+}
+unsafe extern "C" fn client_close(_client: *mut jack_client_t) -> c_int {0}
+unsafe extern "C" fn get_client_name(_client: *mut jack_client_t) -> *mut c_char {
+    server( | s | s.client_name.as_ptr() as *mut c_char)
+}
+unsafe extern "C" fn activate(_client: *mut jack_client_t) -> c_int {
+    server( | s | s.is_running = true);
+    std::thread::spawn(run_server);
+    0
+}
+unsafe extern "C" fn set_process_callback(_client: *mut jack_client_t, callback: JackProcessCallback, arg: *mut c_void) -> c_int {
+    server( | s | s.process = Some((callback, arg as usize)));
+    0
+}
+unsafe extern "C" fn set_buffer_size_callback(_client: *mut jack_client_t, callback: JackBufferSizeCallback, arg: *mut c_void) -> c_int {
+    server( | s | s.buffer_size_cb = Some((callback, arg as usize)));
+    0
+}
+unsafe extern "C" fn set_xrun_callback(_client: *mut jack_client_t, callback: JackXRunCallback, arg: *mut c_void) -> c_int {
+    server( | s | s.xrun_cb = Some((callback, arg as usize)));
+    0
+}
+unsafe extern "C" fn set_port_registration_callback(_client: *mut jack_client_t, _callback: JackPortRegistrationCallback, _arg: *mut c_void) -> c_int {0}
+unsafe extern "C" fn set_client_registration_callback(_client: *mut jack_client_t, _callback: JackClientRegistrationCallback, _arg: *mut c_void) -> c_int {0}
+unsafe extern "C" fn on_shutdown(_client: *mut jack_client_t, _callback: JackShutdownCallback, _arg: *mut c_void) {}
+unsafe extern "C" fn get_sample_rate(_client: *mut jack_client_t) -> jack_nframes_t {RATE}
+unsafe extern "C" fn get_buffer_size(_client: *mut jack_client_t) -> jack_nframes_t {
+    server( | s | s.buffer_size)
+}
+unsafe extern "C" fn port_register(_client: *mut jack_client_t, name: *const c_char, port_type_name: *const c_char, flags: c_ulong, _buffer_size: c_ulong) -> *mut jack_port_t {
+    let name = format!("{}:{}", server( | s | s.client_name.to_string_lossy().to_string()), CStr::from_ptr(name).to_string_lossy());
+    let port_type = port_type(&CStr::from_ptr(port_type_name).to_string_lossy());
+    server( | s | add_port(s, &name, port_type, flags, true))
+}
+unsafe extern "C" fn port_unregister(_client: *mut jack_client_t, p: *mut jack_port_t) -> c_int {
+    server( | s | port(s, p).is_gone = true);
+    0
+}
+unsafe extern "C" fn port_get_buffer(p: *mut jack_port_t, _nframes: jack_nframes_t) -> *mut c_void {
+    server( | s | {
+        let port = port(s, p);
+        // MIDI buffers are the port, the event functions look it up
+        if port.port_type == JACK_DEFAULT_MIDI_TYPE {p as *mut c_void} else {port.audio.as_mut_ptr() as *mut c_void}
+    })
+}
+unsafe extern "C" fn port_name(p: *const jack_port_t) -> *const c_char {
+    server( | s | port(s, p).name.as_ptr())
+}
+unsafe extern "C" fn port_type_of(p: *const jack_port_t) -> *const c_char {
+    server( | s | port(s, p).port_type.as_ptr() as *const c_char)
+}
+unsafe extern "C" fn port_flags(p: *const jack_port_t) -> c_int {
+    server( | s | port(s, p).flags as c_int)
+}
+unsafe extern "C" fn port_is_mine(_client: *const jack_client_t, p: *const jack_port_t) -> c_int {
+    server( | s | port(s, p).is_mine as c_int)
+}
+unsafe extern "C" fn port_by_id(_client: *mut jack_client_t, _port_id: jack_port_id_t) -> *mut jack_port_t {ptr::null_mut()}
+unsafe extern "C" fn port_by_name(_client: *mut jack_client_t, _name: *const c_char) -> *mut jack_port_t {ptr::null_mut()}
+unsafe extern "C" fn connect(_client: *mut jack_client_t, from: *const c_char, to: *const c_char) -> c_int {
+    let connection = (CStr::from_ptr(from).to_string_lossy().to_string(), CStr::from_ptr(to).to_string_lossy().to_string());
+    server( | s | s.connections.push(connection));
+    0
+}
+unsafe extern "C" fn get_ports(_client: *mut jack_client_t, _name_pattern: *const c_char, type_pattern: *const c_char, flags: c_ulong) -> *mut *const c_char {
+    let port_type = port_type(&CStr::from_ptr(type_pattern).to_string_lossy());
+    let mut names: Vec<*const c_char> = server( | s | s.ports.iter()
+        .filter( | p | !p.is_gone && p.port_type == port_type && p.flags & flags == flags)
+        .map( | p | p.name.as_ptr())
+        .collect());
+    names.push(ptr::null());
+    // the names live as long as the server, the list is never freed
+    Box::leak(names.into_boxed_slice()).as_mut_ptr()
+}
+unsafe extern "C" fn free(_ptr: *mut c_void) {}
+unsafe extern "C" fn last_frame_time(_client: *const jack_client_t) -> jack_nframes_t {
+    server( | s | s.frame_time)
+}
+fn frames_to_time(frames: jack_nframes_t) -> jack_time_t {
+    5_000_000 + frames as u64 * 1_000_000 / RATE as u64
+}
+unsafe extern "C" fn frames_to_time_fn(_client: *const jack_client_t, frames: jack_nframes_t) -> jack_time_t {
+    frames_to_time(frames)
+}
+unsafe extern "C" fn midi_get_event_count(buffer: *mut c_void) -> u32 {
+    server( | s | port(s, buffer as *const _).midi.len() as u32)
+}
+unsafe extern "C" fn midi_event_get(event: *mut jack_midi_event_t, buffer: *mut c_void, index: u32) -> c_int {
+    server( | s | {
+        let (time, data) = &mut port(s, buffer as *const _).midi[index as usize];
+        *event = jack_midi_event_t {time: *time, size: data.len(), buffer: data.as_mut_ptr()};
+    });
+    0
+}
+unsafe extern "C" fn midi_clear_buffer(buffer: *mut c_void) {
+    server( | s | port(s, buffer as *const _).midi.clear());
+}
+unsafe extern "C" fn midi_event_write(buffer: *mut c_void, time: jack_nframes_t, data: *const u8, size: usize) -> c_int {
+    let data = std::slice::from_raw_parts(data, size).to_vec();
+    server( | s | {
+        let name = port(s, buffer as *const _).name.to_string_lossy().to_string();
+        s.sent.push((name, s.frame_time + time, data.clone()));
+        port(s, buffer as *const _).midi.push((time, data));
+    });
+    0
+}
+
+type ClientOpen = unsafe extern "C" fn(*const c_char, jack_options_t, *mut jack_status_t) -> *mut jack_client_t;
+type VariadicClientOpen = unsafe extern "C" fn(*const c_char, jack_options_t, *mut jack_status_t, ...) -> *mut jack_client_t;
+
+fn lib() -> &'static LibJack {
+    static LIB: OnceLock<LibJack> = OnceLock::new();
+    LIB.get_or_init( || LibJack {
+        jack_client_open: unsafe {std::mem::transmute::<ClientOpen, VariadicClientOpen>(client_open)},
+        jack_client_close: client_close,
+        jack_get_client_name: get_client_name,
+        jack_activate: activate,
+        jack_set_process_callback: set_process_callback,
+        jack_set_buffer_size_callback: set_buffer_size_callback,
+        jack_set_xrun_callback: set_xrun_callback,
+        jack_set_port_registration_callback: set_port_registration_callback,
+        jack_set_client_registration_callback: set_client_registration_callback,
+        jack_on_shutdown: on_shutdown,
+        jack_get_sample_rate: get_sample_rate,
+        jack_get_buffer_size: get_buffer_size,
+        jack_port_register: port_register,
+        jack_port_unregister: port_unregister,
+        jack_port_get_buffer: port_get_buffer,
+        jack_port_name: port_name,
+        jack_port_type: port_type_of,
+        jack_port_flags: port_flags,
+        jack_port_is_mine: port_is_mine,
+        jack_port_by_id: port_by_id,
+        jack_port_by_name: port_by_name,
+        jack_connect: connect,
+        jack_get_ports: get_ports,
+        jack_free: free,
+        jack_last_frame_time: last_frame_time,
+        jack_frames_to_time: frames_to_time_fn,
+        jack_midi_get_event_count: midi_get_event_count,
+        jack_midi_event_get: midi_event_get,
+        jack_midi_clear_buffer: midi_clear_buffer,
+        jack_midi_event_write: midi_event_write,
+        module: None,
+    })
+}
+
+fn run_server() {
+    loop {
+        std::thread::sleep(Duration::from_millis(1));
+        let (process, buffer_size_cb, nframes) = {
+            let mut guard = SERVER.lock().unwrap();
+            let s = guard.as_mut().unwrap();
+            if s.stop {
+                s.is_running = false;
+                return
+            }
+            for (name, time, data) in std::mem::take(&mut s.incoming) {
+                let port = s.ports.iter_mut().find( | p | p.name.to_str().unwrap() == name).unwrap();
+                port.midi.push((time, data));
+            }
+            let resized = s.next_buffer_size.take();
+            if let Some(size) = resized {
+                s.buffer_size = size;
+            }
+            (s.process.unwrap(), resized.and(s.buffer_size_cb), s.buffer_size)
+        };
+        unsafe {
+            if let Some((callback, arg)) = buffer_size_cb {
+                callback(nframes, arg as *mut c_void);
+            }
+            (process.0)(nframes, process.1 as *mut c_void);
+        }
+        server( | s | {
+            for port in s.ports.iter_mut().filter( | p | p.is_mine && p.flags & JackPortIsInput != 0) {
+                port.midi.clear();
+            }
+            s.frame_time += nframes;
+            s.cycles += 1;
+        });
+    }
+}
+
+fn start() -> MutexGuard<'static, ()> {
+    let serial = SERIAL.lock().unwrap_or_else( | e | e.into_inner());
+    // the server of the test before goes first
+    if let Some(s) = SERVER.lock().unwrap().as_mut() {
+        s.stop = true;
+    }
+    while SERVER.lock().unwrap().as_ref().is_some_and( | s | s.is_running) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let mut s = Server {
+        buffer_size: 64,
+        ..Default::default()
+    };
+    let audio = JACK_DEFAULT_AUDIO_TYPE;
+    let midi = JACK_DEFAULT_MIDI_TYPE;
+    add_port(&mut s, "system:playback_1", audio, JackPortIsInput | JackPortIsPhysical, false);
+    add_port(&mut s, "system:playback_2", audio, JackPortIsInput | JackPortIsPhysical, false);
+    add_port(&mut s, "system:capture_1", audio, JackPortIsOutput | JackPortIsPhysical, false);
+    add_port(&mut s, "synth:in_l", audio, JackPortIsInput, false);
+    add_port(&mut s, "synth:in_r", audio, JackPortIsInput, false);
+    add_port(&mut s, "synth:midi_in", midi, JackPortIsInput, false);
+    add_port(&mut s, "keyboard:out", midi, JackPortIsOutput, false);
+    *SERVER.lock().unwrap() = Some(s);
+    serial
+}
+
+fn wait_cycles(count: usize) {
+    let end = server( | s | s.cycles) + count;
+    let start = Instant::now();
+    while server( | s | s.cycles) < end {
+        assert!(start.elapsed() < Duration::from_secs(5), "the server stopped");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn audio_of(name: &str, frames: usize) -> Vec<f32> {
+    server( | s | s.ports.iter().find( | p | p.name.to_str().unwrap() == name).unwrap().audio[..frames].to_vec())
+}
+
+#[test]
+fn lists_the_other_clients() {
+    let _serial = start();
+    let hooks = JackHooks::default();
+    let jack = JackAccess::open(lib(), "app", hooks.clone()).unwrap();
+    assert!(hooks.audio_change.check_and_clear());
+    assert!(hooks.midi_change.check_and_clear());
+    let mut jack = jack.lock().unwrap();
+
+    let descs = jack.get_updated_audio_descs();
+    let listed: Vec<_> = descs.iter().map( | d | (d.name.as_str(), d.device_type, d.channel_count, d.is_default)).collect();
+    assert_eq!(listed, vec![
+        ("[JACK] system", AudioDeviceType::Output, 2, true),
+        ("[JACK] synth", AudioDeviceType::Output, 2, false),
+        ("[JACK] system", AudioDeviceType::Input, 1, true),
+    ]);
+    let midi: Vec<_> = jack.get_updated_midi_descs().into_iter().map( | d | (d.name, d.port_type)).collect();
+    assert_eq!(midi, vec![
+        ("[JACK] keyboard:out".to_string(), MidiPortType::Input),
+        ("[JACK] synth:midi_in".to_string(), MidiPortType::Output),
+    ]);
+
+    // our own ports are not devices
+    jack.use_audio_outputs(&[descs[0].device_id]);
+    assert_eq!(jack.get_updated_audio_descs().len(), 3);
+    assert_eq!(server( | s | s.connections.clone()), vec![
+        ("app:out_1".to_string(), "system:playback_1".to_string()),
+        ("app:out_2".to_string(), "system:playback_2".to_string()),
+    ]);
+    // and they go when the device isn't used
+    jack.use_audio_outputs(&[]);
+    assert!(server( | s | s.ports.iter().filter( | p | p.is_mine).all( | p | p.is_gone)));
+
+    let (xrun, arg) = server( | s | s.xrun_cb.unwrap());
+    unsafe {xrun(arg as *mut c_void)};
+    assert!(hooks.xrun_signal.check_and_clear());
+    let xruns = jack.take_xruns().unwrap();
+    assert_eq!((xruns.count, xruns.total), (1, 1));
+    assert!(jack.take_xruns().is_none());
+}
+
+#[test]
+fn plays_silence_while_the_callback_is_locked() {
+    let _serial = start();
+    let hooks = JackHooks::default();
+    let frames = Arc::new(AtomicUsize::new(0));
+    let seen = frames.clone();
+    *hooks.audio_output_cb[0].lock().unwrap() = Some(Box::new(move | _info, buffer | {
+        seen.store(buffer.frame_count(), Ordering::Relaxed);
+        buffer.data.fill(0.25);
+    }));
+    let jack = JackAccess::open(lib(), "app", hooks.clone()).unwrap();
+    let mut jack = jack.lock().unwrap();
+    let system = jack.get_updated_audio_descs()[0].device_id;
+    jack.use_audio_outputs(&[system]);
+    wait_cycles(2);
+    assert_eq!(audio_of("app:out_2", 64), vec![0.25; 64]);
+
+    let locked = hooks.audio_output_cb[0].lock().unwrap();
+    wait_cycles(2);
+    assert_eq!(audio_of("app:out_1", 64), vec![0.0; 64]);
+    drop(locked);
+
+    // the buffers are made for a new size before the cycle that has it
+    server( | s | s.next_buffer_size = Some(128));
+    wait_cycles(3);
+    assert_eq!(frames.load(Ordering::Relaxed), 128);
+    assert_eq!(audio_of("app:out_1", 128), vec![0.25; 128]);
+}
+
+#[test]
+fn midi_carries_its_frame() {
+    let _serial = start();
+    let hooks = JackHooks::default();
+    let (sender, receiver) = mpsc::channel();
+    hooks.midi_input_senders.lock().unwrap().push(sender);
+    let jack = JackAccess::open(lib(), "app", hooks.clone()).unwrap();
+    let mut jack = jack.lock().unwrap();
+    let descs = jack.get_updated_midi_descs();
+    jack.use_midi_inputs(&[descs[0].port_id]);
+    jack.use_midi_outputs(&[descs[1].port_id]);
+    assert_eq!(server( | s | s.connections.clone()), vec![
+        ("keyboard:out".to_string(), "app:midi_in_1".to_string()),
+        ("app:midi_out_1".to_string(), "synth:midi_in".to_string()),
+    ]);
+
+    // sysex doesn't fit a MidiData and is left out
+    server( | s | {
+        s.incoming.push(("app:midi_in_1", 3, vec![0xf0, 0x7e, 0xf7]));
+        s.incoming.push(("app:midi_in_1", 17, vec![0x90, 60, 100]));
+    });
+    let (port_id, data, time) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(port_id, descs[0].port_id);
+    assert_eq!(data.data, [0x90, 60, 100]);
+    let time = time.unwrap();
+    assert_eq!(time.sample_time as u32 % 64, 17);
+    assert_eq!(time.host_time, frames_to_time(time.sample_time as u32));
+    assert!(receiver.try_recv().is_err());
+
+    // sent MIDI goes out at the start of the next cycle, as long as it is
+    jack.midi_output().unwrap().send(None, MidiData {data: [0xc0, 5, 0]});
+    jack.midi_output().unwrap().send(Some(descs[1].port_id), MidiData {data: [0x80, 60, 0]});
+    wait_cycles(2);
+    let sent = server( | s | s.sent.clone());
+    assert_eq!(sent.iter().map( | (port, _, data) | (port.as_str(), data.clone())).collect::<Vec<_>>(), vec![
+        ("app:midi_out_1", vec![0xc0, 5]),
+        ("app:midi_out_1", vec![0x80, 60, 0]),
+    ]);
+    assert!(sent.iter().all( | (_, time, _) | *time == sent[0].1 && time % 64 == 0));
+
+    // timed MIDI goes out at its frame, a few cycles on, and late MIDI at the
+    // start of the next cycle
+    let at = | frame: u32 | Some(AudioTime {sample_time: frame as f64, host_time: 0, rate_scalar: 1.0});
+    let now = server( | s | s.frame_time);
+    let target = now + 64 * 4 + 40;
+    let output = jack.midi_output().unwrap();
+    output.send_timed(None, MidiData {data: [0xb0, 1, 2]}, at(now - 10));
+    output.send_timed(None, MidiData {data: [0x90, 64, 90]}, at(target));
+    output.send_timed(None, MidiData {data: [0x80, 64, 0]}, at(target + 1));
+    output.send_timed(None, MidiData {data: [0x80, 65, 0]}, at(target + 100));
+    wait_cycles(8);
+    let sent = server( | s | s.sent[2..].to_vec());
+    assert_eq!(sent.len(), 4);
+    assert!(sent[0].1 % 64 == 0 && sent[0].1 < target && sent[0].2 == vec![0xb0, 1, 2]);
+    assert_eq!(sent[1..], vec![
+        ("app:midi_out_1".to_string(), target, vec![0x90, 64, 90]),
+        ("app:midi_out_1".to_string(), target + 1, vec![0x80, 64, 0]),
+        ("app:midi_out_1".to_string(), target + 100, vec![0x80, 65, 0]),
+    ]);
+}
@@ -0,0 +1,127 @@
+use makepad_platform::*;
+use std::{cell::RefCell, rc::Rc, time::{Duration, Instant}};
+
+fn cx_with_log() -> (Cx, Rc<RefCell<NetworkResponsesEvent>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    let cx = Cx::new(Box::new(move | _cx, event | {
+        if let Event::NetworkResponses(items) = event {
+            sink.borrow_mut().extend(items.iter().cloned());
+        }
+    }));
+    (cx, log)
+}
+
+#[test]
+fn http_routes() {
+    let (mut cx, log) = cx_with_log();
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/user", MockReply::json(200, r#"{"name":"ada"}"#)).with_latency(0.05);
+    mock.post("https://api.test/items*", MockReply::error("connection reset")).once();
+    mock.post("https://api.test/items*", MockReply::text(201, "created"));
+    cx.set_network_mock(Some(mock));
+
+    cx.http_request(live_id!(user), HttpRequest::new("https://api.test/user".into(), HttpMethod::GET));
+    let mut post = HttpRequest::new("https://api.test/items/1".into(), HttpMethod::POST);
+    post.set_body_string("one");
+    cx.http_request(live_id!(first), post);
+    cx.http_request(live_id!(second), HttpRequest::new("https://api.test/items/2".into(), HttpMethod::POST));
+    cx.http_request(live_id!(unknown), HttpRequest::new("https://other.test/".into(), HttpMethod::GET));
+
+    // the user response is still on its way
+    assert_eq!(cx.deliver_network_mock(), 3);
+    {
+        let log = log.borrow();
+        assert!(matches!(&log[0].response, NetworkResponse::HttpRequestError(e) if e.message == "connection reset"));
+        assert!(matches!(&log[1].response, NetworkResponse::HttpResponse(r) if r.status_code == 201));
+        assert!(matches!(&log[2].response, NetworkResponse::HttpRequestError(e) if e.message.contains("other.test")));
+    }
+    let start = Instant::now();
+    cx.settle_network_mock();
+    assert!(start.elapsed() >= Duration::from_millis(40));
+    let last = log.borrow().last().cloned().unwrap();
+    assert_eq!(last.request_id, live_id!(user));
+    let NetworkResponse::HttpResponse(res) = last.response else {panic!()};
+    assert_eq!(res.get_string_body().unwrap(), r#"{"name":"ada"}"#);
+    assert_eq!(res.headers["Content-Type"], vec!["application/json".to_string()]);
+
+    let requests = cx.network_mock().unwrap().take_requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[1].request.body.as_deref(), Some(&b"one"[..]));
+}
+
+#[test]
+fn cancel_and_stream() {
+    let (mut cx, log) = cx_with_log();
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/slow", MockReply::text(200, "late")).with_latency(0.02);
+    mock.get("https://api.test/events", MockReply::stream(200, vec![b"event: tick\nda".to_vec(), b"ta: 1\n\ndata: 2\n\n".to_vec()]));
+    cx.set_network_mock(Some(mock));
+
+    cx.http_request(live_id!(slow), HttpRequest::new("https://api.test/slow".into(), HttpMethod::GET));
+    cx.cancel_http_request(live_id!(slow));
+    cx.http_sse_request(live_id!(events), HttpRequest::new("https://api.test/events".into(), HttpMethod::GET), false);
+    cx.settle_network_mock();
+
+    let log = log.borrow();
+    assert!(log.iter().all( | item | item.request_id == live_id!(events)));
+    let events: Vec<(String, String)> = log.iter().filter_map( | item | match &item.response {
+        NetworkResponse::HttpSseEvent(e) => Some((e.event.clone(), e.data.clone())),
+        _ => None
+    }).collect();
+    assert_eq!(events, vec![("tick".to_string(), "1".to_string()), ("message".to_string(), "2".to_string())]);
+    assert!(matches!(log.last().unwrap().response, NetworkResponse::HttpStreamComplete(_)));
+    // the sse request asked for an event stream
+    let requests = cx.network_mock().unwrap().requests().to_vec();
+    assert_eq!(requests.last().unwrap().request.headers["Accept"], vec!["text/event-stream".to_string()]);
+}
+
+#[test]
+fn streamed_response_keeps_its_status() {
+    let (mut cx, log) = cx_with_log();
+    let mut mock = NetworkMock::new();
+    mock.get("https://api.test/missing", MockReply::text(404, "not here"));
+    cx.set_network_mock(Some(mock));
+
+    let mut request = HttpRequest::new("https://api.test/missing".into(), HttpMethod::GET);
+    request.set_is_streaming();
+    cx.http_request(live_id!(missing), request);
+    cx.settle_network_mock();
+
+    let log = log.borrow();
+    assert_eq!(log.len(), 2);
+    assert!(matches!(&log[0].response, NetworkResponse::HttpStreamResponse(r) if r.status_code == 404));
+    assert!(matches!(&log[1].response, NetworkResponse::HttpStreamComplete(r) if r.status_code == 404));
+}
+
+#[test]
+fn web_socket_script() {
+    let (mut cx, _log) = cx_with_log();
+    let mut mock = NetworkMock::new();
+    mock.web_socket("wss://chat.test/*", MockWebSocket::new()
+        .send_string("hello")
+        .expect_message()
+        .send_binary(vec![1, 2, 3])
+        .close(1000, "bye"));
+    cx.set_network_mock(Some(mock));
+
+    let mut socket = WebSocket::open(HttpRequest::new("wss://chat.test/room".into(), HttpMethod::GET));
+    let recv = | socket: &mut WebSocket | socket.rx_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(recv(&mut socket), WebSocketMessage::Opened));
+    assert!(matches!(recv(&mut socket), WebSocketMessage::String(s) if s == "hello"));
+    // nothing more until we answer
+    assert!(socket.rx_receiver.recv_timeout(Duration::from_millis(20)).is_err());
+    socket.send_string("hi there".into()).unwrap();
+    assert!(matches!(recv(&mut socket), WebSocketMessage::Binary(b) if b == [1, 2, 3]));
+    assert!(matches!(recv(&mut socket), WebSocketMessage::Close{code: 1000, ..}));
+    assert!(matches!(recv(&mut socket), WebSocketMessage::Closed));
+
+    let sent = cx.network_mock().unwrap().take_web_socket_messages();
+    assert_eq!(sent.len(), 1);
+    assert!(matches!(&sent[0], (url, WebSocketMessage::String(s)) if url == "wss://chat.test/room" && s == "hi there"));
+
+    // sockets without a script fail like an unreachable server would
+    let mut other = WebSocket::open(HttpRequest::new("wss://nowhere.test".into(), HttpMethod::GET));
+    assert!(matches!(recv(&mut other), WebSocketMessage::Error(_)));
+    assert!(matches!(recv(&mut other), WebSocketMessage::Closed));
+}
@@ -0,0 +1,73 @@
+use makepad_platform::*;
+use makepad_platform::makepad_script::*;
+use std::time::{Duration, Instant};
+
+fn eval(cx:&mut Cx, code:&str)->ScriptValue{
+    cx.eval(ScriptBlock{
+        file: "tests/script_watchdog.mps".to_string(),
+        code: code.to_string(),
+        ..Default::default()
+    })
+}
+
+fn timed_out(value:ScriptValue)->bool{
+    value.as_err().map(|e| e.ty) == Some(ScriptValueType::ERR_BUDGET_EXHAUSTED)
+}
+
+const FOREVER: &str = "let c = 0 loop{ c += 1 }";
+
+#[test]
+fn timeout_cancels_a_run(){
+    let mut cx = Cx::new(Box::new(|_, _|{}));
+    cx.set_script_timeout(Some(0.05));
+    let start = Instant::now();
+    assert!(timed_out(eval(&mut cx, FOREVER)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn disarm_after_a_run(){
+    let mut cx = Cx::new(Box::new(|_, _|{}));
+    cx.set_script_timeout(Some(0.02));
+    assert!(!eval(&mut cx, "1 + 2").is_err());
+    // the deadline of the finished run passes without cancelling anything
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(!cx.script_cancel_handle().unwrap().is_cancelled());
+    assert!(!eval(&mut cx, "let c = 0 for i in 0..1000{ c += i } c").is_err());
+}
+
+#[test]
+fn rearms_after_a_cancel(){
+    let mut cx = Cx::new(Box::new(|_, _|{}));
+    cx.set_script_timeout(Some(0.05));
+    assert!(timed_out(eval(&mut cx, FOREVER)));
+    assert!(timed_out(eval(&mut cx, FOREVER)));
+    assert!(!eval(&mut cx, "1 + 2").is_err());
+
+    cx.set_script_timeout(None);
+    assert!(!eval(&mut cx, "1 + 2").is_err());
+}
+
+// holds the script on every pause for longer than the timeout
+struct SlowDebugger;
+
+impl ScriptDebugHandler for SlowDebugger{
+    fn on_pause(&mut self, paused:&mut ScriptPaused)->ScriptDebugCommand{
+        let cx = paused.host.downcast_mut::<Cx>().unwrap();
+        cx.suspend_script_timeout();
+        std::thread::sleep(Duration::from_millis(100));
+        cx.resume_script_timeout();
+        ScriptDebugCommand::Continue
+    }
+}
+
+#[test]
+fn paused_time_doesnt_count(){
+    let mut cx = Cx::new(Box::new(|_, _|{}));
+    cx.set_script_timeout(Some(0.05));
+    let mut debug = ScriptDebug::new(Box::new(SlowDebugger));
+    debug.breakpoints = vec![ScriptBreakpoint{file: "tests/script_watchdog.mps".to_string(), line: 2}];
+    cx.script_vm.as_mut().unwrap().threads[0].attach_debugger(debug);
+    // enough work after the pause for the cancel flag to be looked at
+    assert!(!eval(&mut cx, "let c = 0\nc += 1\nfor i in 0..10000{ c += i }\nc").is_err());
+}
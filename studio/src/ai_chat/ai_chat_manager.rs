@@ -448,6 +448,15 @@ fn new()->Self{
         }
     }
     
+    fn append_assistant(&mut self, text:&str){
+        if let Some(AiChatMessage::Assistant(s)) = self.messages.last_mut(){
+            s.push_str(text);
+        }
+        else{
+            self.messages.push(AiChatMessage::Assistant(text.to_string()))
+        }
+    }
+    
     fn follow_up(&mut self){
        self.messages.push(AiChatMessage::User(AiUserMessage{
             message:"".to_string()
@@ -610,61 +619,59 @@ pub fn handle_event(&mut self, cx: &mut Cx, event: &Event, fs:&mut FileSystem) {
                         NetworkResponse::HttpRequestError(_err)=>{
                             println!("HTTP ERROR {:?}", _err);
                         }
-                        NetworkResponse::HttpStreamResponse(res)=>{
-                            let data = res.get_string_body().unwrap();
-                                                        
+                        NetworkResponse::HttpStreamResponse(res) if res.status_code >= 300=>{
+                            // an error page rather than events, show it in the chat
+                            if let Some(msg) = doc.file.history.get_mut(in_flight.history_slot){
+                                msg.append_assistant(&res.get_string_body().unwrap_or_default());
+                            }
+                            cx.action(AppAction::RedrawAiChat{chat_id});
+                        }
+                        NetworkResponse::HttpSseEvent(event)=>{
+                            let data = &event.data;
                             let mut changed = false;
                             match in_flight.backend{
                                 AiBackend::OpenAI{..}=>{
-                                    for data in data.split("\n\n"){
-                                        if let Some(data) = data.strip_prefix("data: "){
-                                            if data != "[DONE]"{
-                                                match OpenAiChatResponse::deserialize_json(data){
-                                                    Ok(chat_response)=>{
-                                                        if let Some(content) = &chat_response.choices[0].delta.as_ref().unwrap().content{
-                                                            if let Some(msg) = doc.file.history.get_mut(in_flight.history_slot){
-                                                                if let Some(AiChatMessage::Assistant(s)) = msg.messages.last_mut(){
-                                                                    s.push_str(&content);
-                                                                }
-                                                                else{
-                                                                    msg.messages.push(AiChatMessage::Assistant(content.clone()))
-                                                                }
-                                                            }
-                                                            changed = true;
+                                    if data != "[DONE]"{
+                                        match OpenAiChatResponse::deserialize_json(data){
+                                            Ok(chat_response)=>{
+                                                if let Some(content) = &chat_response.choices[0].delta.as_ref().unwrap().content{
+                                                    if let Some(msg) = doc.file.history.get_mut(in_flight.history_slot){
+                                                        if let Some(AiChatMessage::Assistant(s)) = msg.messages.last_mut(){
+                                                            s.push_str(&content);
+                                                        }
+                                                        else{
+                                                            msg.messages.push(AiChatMessage::Assistant(content.clone()))
                                                         }
                                                     }
-                                                    Err(e)=>{
-                                                        println!("JSon parse error {:?} {}", e, data);
-                                                    }
+                                                    changed = true;
                                                 }
                                             }
+                                            Err(e)=>{
+                                                println!("JSon parse error {:?} {}", e, data);
+                                            }
                                         }
                                     }
                                 }
                                 AiBackend::Google{..}=>{
-                                    for data in data.split("\r\n\r\n"){
-                                        if let Some(data) = data.strip_prefix("data: "){
-                                            match GoogleAiResponse::deserialize_json(&data){
-                                                Ok(response)=>{
-                                                    for candidate in &response.candidates{
-                                                        for part in &candidate.content.parts{
-                                                            if let Some(msg) = doc.file.history.get_mut(in_flight.history_slot){
-                                                                if let Some(AiChatMessage::Assistant(s)) = msg.messages.last_mut(){
-                                                                    s.push_str(&part.text);
-                                                                }
-                                                                else{
-                                                                    msg.messages.push(AiChatMessage::Assistant(part.text.clone()))
-                                                                }
-                                                            }
-                                                            changed = true;
+                                    match GoogleAiResponse::deserialize_json(data){
+                                        Ok(response)=>{
+                                            for candidate in &response.candidates{
+                                                for part in &candidate.content.parts{
+                                                    if let Some(msg) = doc.file.history.get_mut(in_flight.history_slot){
+                                                        if let Some(AiChatMessage::Assistant(s)) = msg.messages.last_mut(){
+                                                            s.push_str(&part.text);
+                                                        }
+                                                        else{
+                                                            msg.messages.push(AiChatMessage::Assistant(part.text.clone()))
                                                         }
                                                     }
-                                                }
-                                                Err(e)=>{
-                                                    println!("JSon parse error {:?} {}", e, data);
+                                                    changed = true;
                                                 }
                                             }
-                                        }   
+                                        }
+                                        Err(e)=>{
+                                            println!("JSon parse error {:?} {}", e, data);
+                                        }
                                     }
                                 }
                             }
@@ -675,18 +682,24 @@ pub fn handle_event(&mut self, cx: &mut Cx, event: &Event, fs:&mut FileSystem) {
                                 //fs.request_save_file_for_file_node_id(chat_id, false);
                             }
                         }
-                        NetworkResponse::HttpStreamComplete(_res)=>{
+                        NetworkResponse::HttpStreamComplete(res)=>{
                             // done?..
                            //let chat_id = res.metadata_id;
                             // alright lets fetch the chat object
                             if let Some(OpenDocument::AiChat(doc)) = fs.open_documents.get_mut(&chat_id){
                                 if let Some(in_flight) = doc.in_flight.take(){
                                     doc.in_flight = None;
+                                    let failed = res.status_code >= 300;
+                                    if failed{
+                                        // backends that don't stream hand the error page over here
+                                        let body = res.get_string_body().unwrap_or_default();
+                                        doc.file.history[in_flight.history_slot].append_assistant(&format!("\n\nRequest failed with HTTP status {}\n{}", res.status_code, body));
+                                    }
                                     doc.file.history[in_flight.history_slot].follow_up();
                                     cx.action(AppAction::RedrawAiChat{chat_id});
                                     cx.action(AppAction::SaveAiChat{chat_id});
                                     
-                                    if doc.auto_run{
+                                    if doc.auto_run && !failed{
                                         let item_id = doc.file.history[in_flight.history_slot].messages.len().saturating_sub(3);
                                         // lets check it auto_run = true
                                         cx.action(AppAction::RunAiChat{chat_id, history_slot:in_flight.history_slot, item_id});
@@ -789,7 +802,6 @@ pub fn send_chat_to_backend(&mut self, cx: &mut Cx, chat_id:LiveId, history_slot
                 AiBackend::OpenAI{url, model, key, reasoning_effort}=>{
                     
                     let mut request = HttpRequest::new(url.clone(), HttpMethod::POST);
-                    request.set_is_streaming();
                     request.set_header("Authorization".to_string(), format!("Bearer {key}"));
                     request.set_header("Content-Type".to_string(), "application/json".to_string());
                     request.set_metadata_id(chat_id); 
@@ -856,7 +868,6 @@ pub fn send_chat_to_backend(&mut self, cx: &mut Cx, chat_id:LiveId, history_slot
                 }
                 AiBackend::Google{url, key}=>{
                     let mut request = HttpRequest::new(format!("{}{}", url.clone(),key), HttpMethod::POST);
-                    request.set_is_streaming();
                     request.set_header("Content-Type".to_string(), "application/json".to_string());
                     request.set_metadata_id(chat_id); 
                     let mut contents = Vec::new();
@@ -964,7 +975,7 @@ pub fn send_chat_to_backend(&mut self, cx: &mut Cx, chat_id:LiveId, history_slot
                 backend,
                 request_id
             });
-            cx.http_request(request_id, request);
+            cx.http_sse_request(request_id, request, false);
         }
     }
     
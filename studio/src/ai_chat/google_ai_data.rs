@@ -16,54 +16,64 @@ pub struct GoogleAiPart {
     pub text: String,
 } 
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiResponse{
     pub candidates: Vec<GoogleAiCandidate>,
-    pub usageMetadata: GoogleAiMetadata,
-    pub modelVersion: String,
-    pub responseId: String,
+    #[rename = "usageMetadata"]
+    pub usage_metadata: GoogleAiMetadata,
+    #[rename = "modelVersion"]
+    pub model_version: String,
+    #[rename = "responseId"]
+    pub response_id: String,
 }
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiCitation {
-    pub citationSources: Vec<GoogleAiCitationSource>,
+    #[rename = "citationSources"]
+    pub citation_sources: Vec<GoogleAiCitationSource>,
 } 
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiCitationSource {
-    pub startIndex: usize,
-    pub endIndex: usize,
+    #[rename = "startIndex"]
+    pub start_index: usize,
+    #[rename = "endIndex"]
+    pub end_index: usize,
     pub uri: String,
     pub license: String,
 } 
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiMetadata {
-    pub promptTokenCount: usize,
-    pub candidatesTokenCount: usize,
-    pub totalTokenCount: usize,
-    pub thoughtsTokenCount: usize,
-    pub cachedContentTokenCount: Option<usize>,
-    pub promptTokensDetails: Vec<GoogleAiTokenDetail>,
-    pub cacheTokensDetails: Option<Vec<GoogleAiTokenDetail>>
+    #[rename = "promptTokenCount"]
+    pub prompt_token_count: usize,
+    #[rename = "candidatesTokenCount"]
+    pub candidates_token_count: usize,
+    #[rename = "totalTokenCount"]
+    pub total_token_count: usize,
+    #[rename = "thoughtsTokenCount"]
+    pub thoughts_token_count: usize,
+    #[rename = "cachedContentTokenCount"]
+    pub cached_content_token_count: Option<usize>,
+    #[rename = "promptTokensDetails"]
+    pub prompt_tokens_details: Vec<GoogleAiTokenDetail>,
+    #[rename = "cacheTokensDetails"]
+    pub cache_tokens_details: Option<Vec<GoogleAiTokenDetail>>
 } 
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiTokenDetail {
     modality: String,
-    tokenCount: usize
+    #[rename = "tokenCount"]
+    token_count: usize
 }
 
-#[allow(non_snake_case)]
 #[derive(Debug, SerJson, DeJson)]
 pub struct GoogleAiCandidate {
     pub content: GoogleAiContent,
-    pub finishReason: Option<String>,
+    #[rename = "finishReason"]
+    pub finish_reason: Option<String>,
     pub index: usize,
-    pub citationMetadata: Option<GoogleAiCitation>,
+    #[rename = "citationMetadata"]
+    pub citation_metadata: Option<GoogleAiCitation>,
 } 
@@ -31,7 +31,7 @@ pub struct OpenAiChatResponse {
 }
 
 #[allow(unused)]
-#[derive(Debug, DeJson)]
+#[derive(Debug, Default, DeJson)]
 pub struct OpenAiCompletionDetails {
     pub reasoning_tokens: i32,
 }
@@ -42,6 +42,8 @@ pub struct OpenAiChatUsage {
     pub prompt_tokens: i32,
     pub completion_tokens: i32,
     pub total_tokens: i32,
+    // openai compatible servers tend to leave this out
+    #[default]
     pub completion_tokens_details: OpenAiCompletionDetails
 }
 
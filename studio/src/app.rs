@@ -14,7 +14,7 @@
     run_view::*,
     snapshot::*,
     studio_file_tree::*,
-    makepad_platform::studio::{JumpToFile,EditFile, SelectInFile, PatchFile, SwapSelection},
+    makepad_platform::studio::{JumpToFile,EditFile, SelectInFile, PatchFile, SwapSelection, StudioScriptStep},
     log_list::*,
     makepad_code_editor::{CodeSession,text::{Position}},
     ai_chat::ai_chat_manager::AiChatManager,
@@ -569,6 +569,43 @@ fn handle_action(&mut self, cx:&mut Cx, action:&Action){
                         self.data.file_system.search_string(cx, set);
                     } 
                 },
+                CodeEditorAction::GutterClicked(line) => {
+                    let tab_id = action.path.from_end(1);
+                    if let Some(file_name) = self.data.file_system.tab_id_to_path(tab_id){
+                        self.data.build_manager.toggle_script_breakpoint(&file_name, line as u32 + 1);
+                        dock.redraw_tab(cx, tab_id);
+                    }
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F9 =>{
+                    let tab_id = action.path.from_end(1);
+                    if let (Some(file_name), Some(line)) = (
+                        self.data.file_system.tab_id_to_path(tab_id),
+                        self.data.file_system.get_cursor_line_for_session(tab_id)
+                    ){
+                        self.data.build_manager.toggle_script_breakpoint(&file_name, line as u32 + 1);
+                        dock.redraw_tab(cx, tab_id);
+                    }
+                }
+                // script debugger run and step keys
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F5 && ke.modifiers.shift =>{
+                    self.data.build_manager.script_debug_detach_all();
+                    dock.redraw(cx);
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F5 =>{
+                    self.data.build_manager.script_debug_step_all(StudioScriptStep::Continue);
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F6 =>{
+                    self.data.build_manager.script_debug_pause_all();
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F10 =>{
+                    self.data.build_manager.script_debug_step_all(StudioScriptStep::StepOver);
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F11 && ke.modifiers.shift =>{
+                    self.data.build_manager.script_debug_step_all(StudioScriptStep::StepOut);
+                }
+                CodeEditorAction::UnhandledKeyDown(ke) if ke.key_code == KeyCode::F11 =>{
+                    self.data.build_manager.script_debug_step_all(StudioScriptStep::StepInto);
+                }
                 CodeEditorAction::TextDidChange => {
                     // lets write the file
                     self.data.file_system.request_save_file_for_tab_id(action.path.from_end(1), false)
@@ -14,6 +14,7 @@
             DesignerComponentPosition,
             DesignerZoomPan,
             AppToStudio, AppToStudioVec, EventSample, GPUSample, StudioToApp, StudioToAppVec,
+            JumpToFile, StudioScriptBreakpoint, StudioScriptPaused, StudioScriptStep,
         },
         makepad_shell::*,
         makepad_widgets::*,
@@ -22,7 +23,9 @@
         decoration::{Decoration, DecorationType},
         text,
     },
-    makepad_http::server::*,
+    makepad_http::{
+        request::HttpResponse, router::Router, server::*, static_files::StaticFiles,
+    },
     std::{
         cell::RefCell,
         collections::{hash_map, HashMap},
@@ -138,11 +141,14 @@ pub struct BuildManager {
     pub websocket_alive_timer: Timer,
     //pub send_file_change: FromUISender<LiveFileChange>,
     pub active_build_websockets: Arc<Mutex<RefCell<ActiveBuildWebSockets>>>,
+    pub script_debug_paused: HashMap<LiveId, StudioScriptPaused>,
 }
 
 #[derive(Default)]
 pub struct ActiveBuildWebSockets{
-    pub sockets: Vec<ActiveBuildSocket>
+    pub sockets: Vec<ActiveBuildSocket>,
+    // kept here so apps that connect later get them too
+    pub script_breakpoints: Vec<StudioScriptBreakpoint>,
 }
 
 impl ActiveBuildWebSockets{
@@ -411,6 +417,73 @@ pub fn live_reload_needed(&mut self, live_file_change: LiveFileChange) {
         }
     }
 
+    /// Toggles a script breakpoint on a line counting from 1 and sends the
+    /// breakpoints to every running app, which attaches its script debugger
+    /// when it gets any. Paused apps report back with `ScriptDebugPaused`.
+    pub fn toggle_script_breakpoint(&mut self, file_name: &str, line: u32) {
+        if let Ok(d) = self.active_build_websockets.lock() {
+            let mut d = d.borrow_mut();
+            if let Some(i) = d.script_breakpoints.iter().position(|bp| bp.file_name == file_name && bp.line == line) {
+                d.script_breakpoints.remove(i);
+            }
+            else {
+                d.script_breakpoints.push(StudioScriptBreakpoint {file_name: file_name.to_string(), line});
+            }
+            let breakpoints = d.script_breakpoints.clone();
+            let build_ids: Vec<LiveId> = d.sockets.iter().map(|s| s.build_id).collect();
+            for build_id in build_ids {
+                d.send_studio_to_app(build_id, StudioToApp::ScriptDebugBreakpoints {breakpoints: breakpoints.clone()});
+            }
+        }
+    }
+
+    /// The lines counting from 0 that have a script breakpoint in a file
+    pub fn script_breakpoint_lines(&self, file_name: &str) -> Vec<usize> {
+        let Ok(d) = self.active_build_websockets.lock() else {
+            return Vec::new()
+        };
+        let d = d.borrow();
+        d.script_breakpoints.iter()
+            .filter(|bp| bp.file_name == file_name)
+            .map(|bp| bp.line.saturating_sub(1) as usize)
+            .collect()
+    }
+
+    /// The line counting from 0 a paused app is stopped on in a file
+    pub fn script_paused_line(&self, file_name: &str) -> Option<usize> {
+        self.script_debug_paused.values()
+            .filter_map(|paused| paused.frames.first())
+            // apps report file!() paths, ours start with the root name
+            .find(|frame| file_name.ends_with(&frame.file_name) || frame.file_name.ends_with(file_name))
+            .map(|frame| frame.line.saturating_sub(1) as usize)
+    }
+
+    pub fn script_debug_pause_all(&mut self) {
+        for build_id in self.active.builds.keys().copied().collect::<Vec<_>>() {
+            self.send_script_debug(build_id, StudioToApp::ScriptDebugPause);
+        }
+    }
+
+    /// Resumes every paused app with a step or continue
+    pub fn script_debug_step_all(&mut self, step: StudioScriptStep) {
+        for build_id in self.script_debug_paused.keys().copied().collect::<Vec<_>>() {
+            self.send_script_debug(build_id, StudioToApp::ScriptDebugStep(step));
+        }
+    }
+
+    pub fn script_debug_detach_all(&mut self) {
+        for build_id in self.active.builds.keys().copied().collect::<Vec<_>>() {
+            self.send_script_debug(build_id, StudioToApp::ScriptDebugDetach);
+        }
+        self.script_debug_paused.clear();
+    }
+
+    fn send_script_debug(&mut self, build_id: LiveId, msg: StudioToApp) {
+        if let Ok(d) = self.active_build_websockets.lock() {
+            d.borrow_mut().send_studio_to_app(build_id, msg);
+        }
+    }
+
     pub fn broadcast_to_stdin(&mut self, msg: HostToStdin) {
         for build_id in self.active.builds.keys() {
             self.clients[0].send_cmd_with_id(*build_id, BuildCmd::HostToStdin(msg.to_json()));
@@ -574,6 +647,31 @@ pub fn handle_event(&mut self, cx: &mut Cx, event: &Event, file_system: &mut Fil
                             cx.action(AppAction::RedrawProfiler)
                         }
                         AppToStudio::FocusDesign => cx.action(AppAction::FocusDesign(build_id)),
+                        AppToStudio::ScriptDebugPaused(paused) => {
+                            if let Some(frame) = paused.frames.first(){
+                                let file_name = if let Some(build) = active.builds.get(&build_id){
+                                    self.roots.map_path(&build.root, &frame.file_name)
+                                }
+                                else{
+                                    self.roots.map_path("", &frame.file_name)
+                                };
+                                cx.action(AppAction::JumpTo(JumpToFile{
+                                    file_name,
+                                    line: frame.line.saturating_sub(1),
+                                    column: frame.column
+                                }));
+                            }
+                            self.script_debug_paused.insert(build_id, paused);
+                        }
+                        AppToStudio::ScriptDebugResumed => {
+                            // take the paused marker out of the gutter
+                            if let Some(frame) = self.script_debug_paused.remove(&build_id).and_then(|p| p.frames.into_iter().next()){
+                                let root = active.builds.get(&build_id).map(|b| b.root.as_str()).unwrap_or("");
+                                if let Some(file_id) = file_system.path_to_file_node_id(&self.roots.map_path(root, &frame.file_name)){
+                                    cx.action(AppAction::RedrawFile(file_id));
+                                }
+                            }
+                        }
                         AppToStudio::PatchFile(ef) => cx.action(AppAction::PatchFile(ef)),
                         AppToStudio::EditFile(ef) => cx.action(AppAction::EditFile(ef)),
                         AppToStudio::JumpToFile(jt) => {
@@ -728,11 +826,44 @@ pub fn start_http_server(&mut self) {
         let addr = SocketAddr::new("0.0.0.0".parse().unwrap(), self.http_port as u16);
         let (tx_request, rx_request) = mpsc::channel::<HttpServerRequest>();
         //log!("Http server at http://127.0.0.1:{}/ for wasm examples and mobile", self.http_port);
-        start_http_server(HttpServer {
-            listen_address: addr,
-            post_max_size: 1024 * 1024,
-            request: tx_request,
-        });
+
+        // TODO fix this proper:
+        let makepad_path = "./".to_string();
+        let abs_makepad_path = std::env::current_dir()
+            .unwrap()
+            .join(makepad_path.clone())
+            .canonicalize()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut root = "./".to_string();
+        for arg in std::env::args() {
+            if let Some(prefix) = arg.strip_prefix("--root=") {
+                root = prefix.to_string();
+                break;
+            }
+        }
+        // empty parts of a route don't count, so /makepad// is /makepad/
+        let remaps = [
+            (format!("/makepad/{}/", abs_makepad_path), makepad_path.clone()),
+            (format!("/makepad/{}/", std::env::current_dir().unwrap().display()), "./".to_string()),
+            ("/makepad/".to_string(), format!("{}/{}", root, makepad_path)),
+            ("/".to_string(), "./".to_string()),
+        ];
+        let mut router = Router::new();
+        router.get("/$watch", |_| HttpResponse::ok().with_header("Cache-Control", "max-age=0"));
+        router.get("/favicon.ico", |_| HttpResponse::ok());
+        for (prefix, dir) in remaps {
+            let files = StaticFiles::new(dir)
+                .with_cache_control("max-age=0")
+                .with_header("Cross-Origin-Embedder-Policy", "require-corp")
+                .with_header("Cross-Origin-Opener-Policy", "same-origin");
+            router.files(&format!("{}*path", prefix), files);
+        }
+        let mut server = RouterServer::new(addr, router);
+        server.web_sockets = Some(tx_request);
+        start_router_server(server);
         /*
         let rx_file_change = self.send_file_change.receiver();
         //let (tx_live_file, rx_live_file) = mpsc::channel::<HttpServerRequest> ();
@@ -750,42 +881,6 @@ pub fn start_http_server(&mut self) {
         let studio_sender = self.recv_studio_msg.sender();
         let active_build_websockets = self.active_build_websockets.clone();
         std::thread::spawn(move || {
-            // TODO fix this proper:
-            let makepad_path = "./".to_string();
-            let abs_makepad_path = std::env::current_dir()
-                .unwrap()
-                .join(makepad_path.clone())
-                .canonicalize()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
-            let mut root = "./".to_string();
-            for arg in std::env::args() {
-                if let Some(prefix) = arg.strip_prefix("--root=") {
-                    root = prefix.to_string();
-                    break;
-                }
-            }
-            let remaps = [
-                (
-                    format!("/makepad/{}/", abs_makepad_path),
-                    makepad_path.clone(),
-                ),
-                (
-                    format!("/makepad/{}/", std::env::current_dir().unwrap().display()),
-                    "".to_string(),
-                ),
-                (
-                    "/makepad//".to_string(),
-                    format!("{}/{}", root, makepad_path.clone()),
-                ),
-                (
-                    "/makepad/".to_string(),
-                    format!("{}/{}", root, makepad_path.clone()),
-                ),
-                ("/".to_string(), "".to_string()),
-            ];
             let mut socket_id_to_build_id = HashMap::new();
             while let Ok(message) = rx_request.recv() {
                 // only store last change, fix later
@@ -798,15 +893,19 @@ pub fn start_http_server(&mut self) {
                         if let Some(id) = headers.path.rsplit("/").next() {
                             if let Ok(id) = id.parse::<u64>() {
                                 socket_id_to_build_id.insert(web_socket_id, LiveId(id));
-                                active_build_websockets
-                                    .lock()
-                                    .unwrap()
-                                    .borrow_mut()
-                                    .sockets.push(ActiveBuildSocket{
-                                        web_socket_id, 
-                                        build_id: LiveId(id), 
-                                        sender: response_sender
-                                    });
+                                let sockets = active_build_websockets.lock().unwrap();
+                                let mut sockets = sockets.borrow_mut();
+                                if !sockets.script_breakpoints.is_empty() {
+                                    let data = StudioToAppVec(vec![StudioToApp::ScriptDebugAttach {
+                                        breakpoints: sockets.script_breakpoints.clone()
+                                    }]).serialize_bin();
+                                    let _ = response_sender.send(data);
+                                }
+                                sockets.sockets.push(ActiveBuildSocket{
+                                    web_socket_id, 
+                                    build_id: LiveId(id), 
+                                    sender: response_sender
+                                });
                             }
                         }
                     }
@@ -830,88 +929,8 @@ pub fn start_http_server(&mut self) {
                         }
                         // new incombing message from client
                     }
-                    HttpServerRequest::Get {
-                        headers,
-                        response_sender,
-                    } => {
-                        let path = &headers.path;
-                        if path == "/$watch" {
-                            let header = "HTTP/1.1 200 OK\r\n\
-                                Cache-Control: max-age:0\r\n\
-                                Connection: close\r\n\r\n"
-                                .to_string();
-                            let _ = response_sender.send(HttpServerResponse {
-                                header,
-                                body: vec![],
-                            });
-                            continue;
-                        }
-                        if path == "/favicon.ico" {
-                            let header = "HTTP/1.1 200 OK\r\n\r\n".to_string();
-                            let _ = response_sender.send(HttpServerResponse {
-                                header,
-                                body: vec![],
-                            });
-                            continue;
-                        }
-
-                        let mime_type = if path.ends_with(".html") {
-                            "text/html"
-                        } else if path.ends_with(".wasm") {
-                            "application/wasm"
-                        } else if path.ends_with(".css") {
-                            "text/css"
-                        } else if path.ends_with(".js") {
-                            "text/javascript"
-                        } else if path.ends_with(".ttf") {
-                            "application/ttf"
-                        } else if path.ends_with(".png") {
-                            "image/png"
-                        } else if path.ends_with(".jpg") {
-                            "image/jpg"
-                        } else if path.ends_with(".svg") {
-                            "image/svg+xml"
-                        } else if path.ends_with(".md") {
-                            "text/markdown"
-                        } else {
-                            continue;
-                        };
-
-                        if path.contains("..") || path.contains('\\') {
-                            continue;
-                        }
-
-                        let mut strip = None;
-                        for remap in &remaps {
-                            if let Some(s) = path.strip_prefix(&remap.0) {
-                                strip = Some(format!("{}{}", remap.1, s));
-                                break;
-                            }
-                        }
-                        if let Some(base) = strip {
-                            if let Ok(mut file_handle) = File::open(base) {
-                                let mut body = Vec::<u8>::new();
-                                if file_handle.read_to_end(&mut body).is_ok() {
-                                    let header = format!(
-                                        "HTTP/1.1 200 OK\r\n\
-                                            Content-Type: {}\r\n\
-                                            Cross-Origin-Embedder-Policy: require-corp\r\n\
-                                            Cross-Origin-Opener-Policy: same-origin\r\n\
-                                            Content-encoding: none\r\n\
-                                            Cache-Control: max-age:0\r\n\
-                                            Content-Length: {}\r\n\
-                                            Connection: close\r\n\r\n",
-                                        mime_type,
-                                        body.len()
-                                    );
-                                    let _ =
-                                        response_sender.send(HttpServerResponse { header, body });
-                                }
-                            }
-                        }
-                    }
-                    HttpServerRequest::Post { .. } => { //headers, body, response}=>{
-                    }
+                    // the router answers plain requests itself
+                    HttpServerRequest::Get { .. } | HttpServerRequest::Post { .. } => {}
                 }
             }
         });
@@ -259,6 +259,18 @@ pub fn get_word_under_cursor_for_session(&mut self, tab_id: LiveId)->Option<Stri
         None
     }
     
+    pub fn get_cursor_line_for_session(&self, tab_id: LiveId)->Option<usize> {
+        if let Some(EditSession::Code(session)) = self.tab_id_to_session.get(&tab_id){
+            let index = session.last_added_selection_index()?;
+            return Some(session.selections()[index].cursor.position.line_index);
+        }
+        None
+    }
+    
+    pub fn tab_id_to_path(&self, tab_id: LiveId) -> Option<String> {
+        self.tab_id_to_file_node_id.get(&tab_id).map(|file_id| self.file_node_path(*file_id))
+    }
+    
     pub fn get_session_mut(&mut self, tab_id: LiveId) -> Option<&mut EditSession> {
         // lets see if we have a document yet
         if let Some(file_id) = self.tab_id_to_file_node_id.get(&tab_id) {
@@ -494,9 +506,18 @@ pub fn process_save_response(&mut self, cx:&mut Cx, response:SaveFileResponse){
             return
         }
         
-        if response.old_data != response.new_data && response.kind != SaveKind::Patch {
-            self.process_possible_live_reload(cx, &response.path, &response.old_data, &response.new_data, true);
+        if response.old_data == response.new_data || response.kind == SaveKind::Patch {
+            return
+        }
+        // script modules have no live_design, the app reloads them from disk
+        if response.path.ends_with(".mps"){
+            cx.action(FileSystemAction::LiveReloadNeeded(LiveFileChange {
+                file_name: response.path.clone(),
+                content: response.new_data.clone(),
+            }));
+            return
         }
+        self.process_possible_live_reload(cx, &response.path, &response.old_data, &response.new_data, true);
     }
     
     pub fn handle_sessions(&mut self) {
@@ -30,6 +30,10 @@ fn draw_walk(&mut self, cx: &mut Cx2d, scope:&mut Scope, walk:Walk)->DrawStep{
         // alright we have a scope, and an id, so now we can properly draw the editor.
         let session_id = scope.path.from_end(1);
         let app_scope = scope.data.get_mut::<AppData>().unwrap();
+        if let Some(file_name) = app_scope.file_system.tab_id_to_path(session_id){
+            self.editor.breakpoint_lines = app_scope.build_manager.script_breakpoint_lines(&file_name);
+            self.editor.paused_line = app_scope.build_manager.script_paused_line(&file_name);
+        }
         if let Some(EditSession::Code(session)) = app_scope.file_system.get_session_mut(session_id){
             self.editor.draw_walk_editor(cx, session, walk);
         }
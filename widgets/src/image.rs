@@ -138,6 +138,7 @@ fn handle_event(&mut self, cx:&mut Cx, event:&Event, _scope:&mut Scope){
         if self.animator_handle_event(cx, event).must_redraw() {
             self.draw_bg.redraw(cx);
         }
+        self.handle_image_url_responses(cx, event);
         // lets check if we have a post action
         if let Event::Actions(actions) = &event{
             for action in actions{
@@ -359,6 +360,25 @@ pub fn load_image_file_by_path_async(&mut self, cx: &mut Cx,  image_path: &Path)
         }
         Ok(())
     }    
+    /// Downloads the image at `url` into this `Image`, through the http cache when it is on.
+    pub fn load_image_from_url_async(&mut self, cx: &mut Cx, url: &str) -> Result<(), ImageError> {
+        if let Ok(result) = self.load_image_from_url_async_impl(cx, url, 0){
+            match result{
+                AsyncLoadResult::Loading(..)=>{
+                    // the real size isn't known until it's downloaded
+                    self.async_image_size = Some((self.min_width as usize, self.min_height as usize));
+                    self.async_image_path = Some(url.into());
+                    self.animator_play(cx, ids!(async_load.on));
+                    self.redraw(cx);
+                }
+                AsyncLoadResult::Loaded=>{
+                    self.redraw(cx);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn load_image_from_data_async(&mut self, cx: &mut Cx, image_path: &Path, data: Arc<Vec<u8>>) -> Result<(), ImageError> {
         if let Ok(result) = self.load_image_from_data_async_impl(cx, image_path, data, 0){
             match result{
@@ -410,6 +430,14 @@ pub fn load_image_file_by_path_async(&self, cx: &mut Cx,  image_path: &Path) ->
         Ok(())
     }    
             
+    /// Downloads the image at `url` into this `ImageRef`.
+    pub fn load_image_from_url_async(&self, cx: &mut Cx, url: &str) -> Result<(), ImageError> {
+        if let Some(mut inner) = self.borrow_mut() {
+            return inner.load_image_from_url_async(cx, url)
+        }
+        Ok(())
+    }
+
     /// Loads the image at the given `image_path` on disk into this `ImageRef`.
     pub fn load_image_from_data_async(&self, cx: &mut Cx,  image_path: &Path, data:Arc<Vec<u8>>) -> Result<(), ImageError> {
         if let Some(mut inner) = self.borrow_mut() {
@@ -209,6 +209,22 @@ fn decode_animated_png(decoder: &mut PngDecoder<&[u8]>) -> Result<ImageBuffer, I
         Ok(final_buffer)
     }
 
+    /// Decodes a png or jpg, going by the first bytes of the data
+    pub fn from_data(data: &[u8]) -> Result<Self, ImageError> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+            Self::from_png(data)
+        }
+        else if data.starts_with(&[0xff, 0xd8]) {
+            Self::from_jpg(data)
+        }
+        else if data.is_empty() {
+            Err(ImageError::EmptyData)
+        }
+        else {
+            Err(ImageError::UnsupportedFormat)
+        }
+    }
+
     pub fn from_jpg(data: &[u8]) -> Result<Self, ImageError> {
         let mut decoder = JpegDecoder::new(&*data);
         match decoder.decode() {
@@ -239,6 +255,8 @@ pub struct AsyncImageLoad{
 pub struct ImageCache {
     map: HashMap<PathBuf, ImageCacheEntry>,
     pub thread_pool: Option<TagThreadPool<PathBuf>>,
+    // the url images being downloaded, by request id
+    url_requests: HashMap<LiveId, PathBuf>,
 }
 
 impl ImageCache {
@@ -246,6 +264,7 @@ pub fn new() -> Self {
         Self {
             map: HashMap::new(),
             thread_pool: None,
+            url_requests: HashMap::new(),
         }
     }
 }
@@ -265,6 +284,9 @@ pub enum ImageError {
     PathNotFound(PathBuf),
     /// The image data could not be decoded as a PNG.
     PngDecode(PngDecodeErrors),
+    /// The image could not be downloaded from its url.
+    /// The http status or network error is included.
+    Http(String),
     /// The image data was in an unsupported format.
     /// Currently, only JPEG and PNG are supported.
     UnsupportedFormat,
@@ -370,9 +392,20 @@ fn image_size_by_path(image_path:&Path)-> Result<(usize,usize), ImageError> {
 
     fn process_async_image_load(&mut self, cx:&mut Cx, image_path: &Path, result: Result<ImageBuffer, ImageError>)->bool{
         // alright now we should stuff this thing into our cache
-        if let Ok(data) = result{
-            let texture = data.into_new_texture(cx);
-            cx.get_global::<ImageCache>().map.insert(image_path.into(), ImageCacheEntry::Loaded(texture.clone()));
+        match result{
+            Ok(data)=>{
+                let texture = data.into_new_texture(cx);
+                cx.get_global::<ImageCache>().map.insert(image_path.into(), ImageCacheEntry::Loaded(texture.clone()));
+            }
+            Err(_)=>{
+                // a url image that didn't decode is forgotten, so a later
+                // load downloads it again. A file that fails stays loading as
+                // it always has, or every draw would read it again
+                let is_url = image_path.to_str().is_some_and(|p| p.starts_with("http://") || p.starts_with("https://"));
+                if is_url && matches!(cx.get_global::<ImageCache>().map.get(image_path), Some(ImageCacheEntry::Loading(..))){
+                    cx.get_global::<ImageCache>().map.remove(image_path);
+                }
+            }
         }
         false
     }
@@ -550,6 +583,82 @@ fn load_image_file_by_path_async_impl(
         }
     }
 
+    /// Starts downloading an image from an http(s) url. With the http cache
+    /// turned on (`Cx::enable_http_cache`) the image comes from disk when it
+    /// can. Widgets using this need to pass their events to
+    /// `handle_image_url_responses`, the decoded image arrives as an `AsyncImageLoad`
+    fn load_image_from_url_async_impl(
+        &mut self,
+        cx: &mut Cx,
+        url: &str,
+        id: usize,
+    ) -> Result<AsyncLoadResult, ImageError> {
+        let image_path = PathBuf::from(url);
+        if let Some(texture) = cx.get_global::<ImageCache>().map.get(&image_path){
+            match texture{
+                ImageCacheEntry::Loaded(texture)=>{
+                    let texture = texture.clone();
+                    self.set_texture(Some(texture), id);
+                    Ok(AsyncLoadResult::Loaded)
+                }
+                ImageCacheEntry::Loading(w,h)=>{
+                    Ok(AsyncLoadResult::Loading(*w, *h))
+                }
+            }
+        }
+        else{
+            let request_id = LiveId::unique();
+            let image_cache = cx.get_global::<ImageCache>();
+            // the size isn't known until the headers of the image are in
+            image_cache.map.insert(image_path.clone(), ImageCacheEntry::Loading(0, 0));
+            image_cache.url_requests.insert(request_id, image_path);
+            cx.http_request(request_id, HttpRequest::new(url.to_string(), HttpMethod::GET));
+            Ok(AsyncLoadResult::Loading(0, 0))
+        }
+    }
+
+    /// Decodes the url images that came in with this event
+    fn handle_image_url_responses(&mut self, cx: &mut Cx, event: &Event) {
+        let Event::NetworkResponses(responses) = event else {
+            return
+        };
+        for item in responses{
+            let result = match &item.response{
+                NetworkResponse::HttpResponse(res) if res.status_code == 200 => Ok(res),
+                NetworkResponse::HttpResponse(res) => Err(format!("http status {}", res.status_code)),
+                NetworkResponse::HttpRequestError(err) => Err(err.message.clone()),
+                _ => continue
+            };
+            // the first widget to see the response does the work
+            let Some(image_path) = cx.get_global::<ImageCache>().url_requests.remove(&item.request_id) else {
+                continue
+            };
+            match result{
+                Ok(res)=>{
+                    let data = res.body.clone().unwrap_or_default();
+                    if cx.get_global::<ImageCache>().thread_pool.is_none(){
+                        cx.get_global::<ImageCache>().thread_pool = Some(TagThreadPool::new(cx, cx.cpu_cores().max(3) - 2))
+                    }
+                    cx.get_global::<ImageCache>().thread_pool.as_mut().unwrap().execute_rev(image_path, move |image_path|{
+                        Cx::post_action(AsyncImageLoad{
+                            image_path,
+                            result: RefCell::new(Some(ImageBuffer::from_data(&data)))
+                        });
+                    });
+                }
+                Err(message)=>{
+                    // forget it, so a later load tries again
+                    cx.get_global::<ImageCache>().map.remove(&image_path);
+                    error!("load_image_from_url: {:?} {}", image_path, message);
+                    cx.action(AsyncImageLoad{
+                        image_path,
+                        result: RefCell::new(Some(Err(ImageError::Http(message))))
+                    });
+                }
+            }
+        }
+    }
+
     fn load_image_file_by_path_and_data(&mut self, cx:&mut Cx, data:&[u8], id:usize, image_path:&Path)-> Result<(), ImageError> {
         if image_path.extension().map(|s| s == "jpg").unwrap_or(false) {
             match ImageBuffer::from_jpg(&*data){
@@ -6,6 +6,7 @@ fn handle_next_frame(&mut self, _cx: &mut Cx, _e:&NextFrameEvent, _scope: &mut S
     fn handle_actions(&mut self, _cx: &mut Cx, _e:&Actions, _scope: &mut Scope){}
     fn handle_signal(&mut self, _cx: &mut Cx, _scope: &mut Scope){}
     fn handle_audio_devices(&mut self, _cx: &mut Cx, _e:&AudioDevicesEvent, _scope: &mut Scope){}
+    fn handle_audio_xrun(&mut self, _cx: &mut Cx, _e:&AudioXrunEvent, _scope: &mut Scope){}
     fn handle_midi_ports(&mut self, _cx: &mut Cx, _e:&MidiPortsEvent, _scope: &mut Scope){}
     fn handle_video_inputs(&mut self, _cx: &mut Cx, _e:&VideoInputsEvent, _scope: &mut Scope){}
     
@@ -14,6 +15,8 @@ fn handle_http_request_error(&mut self, _cx:&mut Cx, _request_id:LiveId, _err:&H
     fn handle_http_progress(&mut self, _cx:&mut Cx, _request_id:LiveId, _progress:&HttpProgress, _scope: &mut Scope){}
     fn handle_http_stream(&mut self, _cx:&mut Cx, _request_id:LiveId, _data:&HttpResponse, _scope: &mut Scope){}
     fn handle_http_stream_complete(&mut self, _cx:&mut Cx, _request_id:LiveId, _data:&HttpResponse, _scope: &mut Scope){}
+    fn handle_http_sse_event(&mut self, _cx:&mut Cx, _request_id:LiveId, _event:&SseEvent, _scope: &mut Scope){}
+    fn handle_http_json_line(&mut self, _cx:&mut Cx, _request_id:LiveId, _line:&str, _scope: &mut Scope){}
         
     fn handle_network_responses(&mut self, cx: &mut Cx, e:&NetworkResponsesEvent, scope: &mut Scope){
         for e in e{
@@ -33,6 +36,12 @@ fn handle_network_responses(&mut self, cx: &mut Cx, e:&NetworkResponsesEvent, sc
                 NetworkResponse::HttpStreamComplete(res)=>{
                     self.handle_http_stream_complete(cx, e.request_id, res, scope);
                 }
+                NetworkResponse::HttpSseEvent(event)=>{
+                    self.handle_http_sse_event(cx, e.request_id, event, scope);
+                }
+                NetworkResponse::HttpJsonLine(line)=>{
+                    self.handle_http_json_line(cx, e.request_id, line, scope);
+                }
             }
         }
     }
@@ -42,6 +51,7 @@ fn widget_match_event(&mut self, cx:&mut Cx, event:&Event, scope: &mut Scope){
             Event::NextFrame(e)=>self.handle_next_frame(cx, e, scope),
             Event::Actions(e)=>self.handle_actions(cx,e, scope),
             Event::AudioDevices(e)=>self.handle_audio_devices(cx, e, scope),
+            Event::AudioXrun(e)=>self.handle_audio_xrun(cx, e, scope),
             Event::MidiPorts(e)=>self.handle_midi_ports(cx, e, scope),
             Event::VideoInputs(e)=>self.handle_video_inputs(cx, e, scope),
             Event::NetworkResponses(e)=>self.handle_network_responses(cx, e, scope),